@@ -0,0 +1,21 @@
+// ANSI colors for terminal output; disabled when the stream is not a terminal
+// or NO_COLOR is set
+
+use std::io::IsTerminal;
+
+pub const RED: &str = "\x1b[31m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+pub fn enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+pub fn paint(color: &str, text: &str) -> String {
+    if enabled() {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,161 @@
+// Tab completion for the prompt: command names for the first word, then option,
+// class or property names depending on the command being typed
+
+use std::borrow::Cow;
+
+use dss_common::CommandList;
+use dss_core::class_names;
+use dss_exec::{Executive, OPTIONS};
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::color;
+
+pub struct DssHelper {
+    commands: CommandList,
+    // Properties already set on the active element
+    properties: Vec<String>,
+}
+
+impl DssHelper {
+    pub fn new(exec: &Executive) -> Self {
+        let names: Vec<&str> = exec.command_names().collect();
+        let mut helper = DssHelper {
+            commands: CommandList::new(&names),
+            properties: Vec::new(),
+        };
+        helper.refresh(exec);
+        helper
+    }
+
+    // Refreshes the property names after a command has run: those of the
+    // active element's class, or those it was given if its class has no table
+    pub fn refresh(&mut self, exec: &Executive) {
+        self.properties = exec
+            .get_active_circuit()
+            .and_then(|circuit| circuit.element(circuit.get_active_element()?))
+            .map(|element| {
+                let table = element.base().properties();
+                if table.is_empty() {
+                    element
+                        .property_values()
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .filter(|name| !name.starts_with('#'))
+                        .collect()
+                } else {
+                    table
+                        .iter()
+                        .map(|property| property.name.to_string())
+                        .collect()
+                }
+            })
+            .unwrap_or_default();
+    }
+
+    // Start of the word being completed and the names it may become
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line
+            .rfind(|c: char| c.is_whitespace())
+            .map_or(0, |index| index + 1);
+        let word = &line[start..];
+        if word.contains('=') {
+            return (start, Vec::new());
+        }
+
+        let names: Vec<String> = if start == 0 {
+            self.commands.names().map(str::to_string).collect()
+        } else {
+            let first = line.split_whitespace().next().unwrap_or("");
+            let command = self
+                .commands
+                .get_command(first)
+                .and_then(|index| self.commands.get(index))
+                .unwrap_or("");
+            match command {
+                "Set" | "Get" => OPTIONS
+                    .iter()
+                    .map(|option| option.name.to_string())
+                    .collect(),
+                "New" | "Edit" | "Select" | "BatchEdit" | "?" if !word.contains('.') => {
+                    let mut names: Vec<String> =
+                        class_names().map(|class| format!("{}.", class)).collect();
+                    if command == "New" {
+                        names.insert(0, "Circuit.".to_string());
+                    }
+                    names
+                }
+                "New" | "Edit" | "Select" | "BatchEdit" | "?" => Vec::new(),
+                _ => self.properties.clone(),
+            }
+        };
+
+        let key = word.to_lowercase();
+        let matches = names
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&key))
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for DssHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for DssHelper {
+    type Hint = String;
+}
+
+impl Highlighter for DssHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        Cow::Owned(color::paint(color::BOLD, prompt))
+    }
+}
+
+impl Validator for DssHelper {}
+
+impl Helper for DssHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1 kw=10 kvar=2").unwrap();
+        let helper = DssHelper::new(&exec);
+
+        assert_eq!(
+            helper.candidates("red", 3),
+            (0, vec!["Redirect".to_string(), "Reduce".to_string()])
+        );
+        let (start, names) = helper.candidates("set max", 7);
+        assert_eq!(start, 4);
+        assert_eq!(names, ["maxiterations", "maxcontroliter"]);
+        assert_eq!(helper.candidates("new ci", 6).1, ["Circuit."]);
+        assert_eq!(helper.candidates("edit load", 9).1, ["LoadShape.", "Load."]);
+        assert!(helper.candidates("edit load.l", 11).1.is_empty());
+        assert_eq!(helper.candidates("~ kv", 4).1, ["kv", "kvar", "kva"]);
+        assert!(helper.candidates("~ kw=1", 6).1.is_empty());
+    }
+}
@@ -1,3 +1,108 @@
-fn main() {
-    println!("Hello, world!");
+// DSS console: runs script files given on the command line, or reads commands
+// at an interactive prompt with history and tab completion
+
+mod color;
+mod completion;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use dss_common::{DssError, DssResult};
+use dss_exec::{CommandResult, Executive};
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Config, Editor};
+
+use completion::DssHelper;
+
+const PROMPT: &str = "DSS> ";
+
+fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".dss_history"))
+}
+
+fn print_error(err: &DssError) {
+    let label = color::paint(color::RED, &format!("error[{}]", err.number()));
+    eprintln!("{}: {}", label, err);
+}
+
+// Prints the output and warnings of a command
+fn report(result: &DssResult<CommandResult>) {
+    match result {
+        Ok(result) => {
+            for warning in &result.warnings {
+                eprintln!("{}", color::paint(color::YELLOW, &warning.to_string()));
+            }
+            if !result.output.is_empty() {
+                println!("{}", result.output);
+            }
+        }
+        Err(err) => print_error(err),
+    }
+}
+
+fn run_files(exec: &mut Executive, files: &[String]) -> ExitCode {
+    for file in files {
+        let result = exec.execute(&format!("compile \"{}\"", file));
+        report(&result);
+        if result.is_err() {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_prompt(exec: &mut Executive) -> rustyline::Result<()> {
+    let config = Config::builder().auto_add_history(true).build();
+    let mut editor: Editor<DssHelper, FileHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(DssHelper::new(exec)));
+    let history = history_path();
+    if let Some(path) = &history {
+        // no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    println!(
+        "dss-rs {} - type Help for commands, Quit to leave",
+        env!("CARGO_PKG_VERSION")
+    );
+    loop {
+        match editor.readline(PROMPT) {
+            Ok(line) => {
+                let command = line.trim();
+                if command.eq_ignore_ascii_case("quit") || command.eq_ignore_ascii_case("exit") {
+                    break;
+                }
+                report(&exec.execute(&line));
+                if let Some(helper) = editor.helper_mut() {
+                    helper.refresh(exec);
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    let mut exec = Executive::new();
+    if !files.is_empty() {
+        return run_files(&mut exec, &files);
+    }
+
+    match run_prompt(&mut exec) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}: {}", color::paint(color::RED, "error"), err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+// Name table used for commands, options and properties (Pascal TCommandList).
+// Lookup is case-insensitive; when abbreviations are allowed a name that is
+// not found matches the first entry it is a prefix of, so list order decides
+// which of several candidates wins, exactly as in OpenDSS.
+#[derive(Debug, Clone)]
+pub struct CommandList {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    abbrev_allowed: bool,
+}
+
+impl CommandList {
+    pub fn new(names: &[&str]) -> Self {
+        let mut list = CommandList {
+            names: Vec::with_capacity(names.len()),
+            index: HashMap::with_capacity(names.len()),
+            abbrev_allowed: true,
+        };
+        for name in names {
+            list.add(name);
+        }
+        list
+    }
+
+    // Appends a name and returns its index; an existing name keeps its index
+    pub fn add(&mut self, name: &str) -> usize {
+        let key = name.to_lowercase();
+        if let Some(&index) = self.index.get(&key) {
+            return index;
+        }
+        self.names.push(name.to_string());
+        self.index.insert(key, self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    pub fn get_command(&self, cmd: &str) -> Option<usize> {
+        let key = cmd.to_lowercase();
+        if let Some(&index) = self.index.get(&key) {
+            return Some(index);
+        }
+        if !self.abbrev_allowed || key.is_empty() {
+            return None;
+        }
+        self.names
+            .iter()
+            .position(|name| name.to_lowercase().starts_with(&key))
+    }
+
+    // Indices of every entry `prefix` abbreviates, in list order
+    pub fn matches(&self, prefix: &str) -> Vec<usize> {
+        let key = prefix.to_lowercase();
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| name.to_lowercase().starts_with(&key))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // True when `cmd` is not a full name but abbreviates several of them
+    pub fn is_ambiguous(&self, cmd: &str) -> bool {
+        self.abbrev_allowed
+            && !self.index.contains_key(&cmd.to_lowercase())
+            && self.matches(cmd).len() > 1
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(String::as_str)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn get_abbrev_allowed(&self) -> bool {
+        self.abbrev_allowed
+    }
+
+    pub fn set_abbrev_allowed(&mut self, allowed: bool) {
+        self.abbrev_allowed = allowed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_and_abbreviated_lookup() {
+        let list = CommandList::new(&["New", "Edit", "Solve", "Set", "Select"]);
+        assert_eq!(list.get_command("new"), Some(0));
+        assert_eq!(list.get_command("EDIT"), Some(1));
+        assert_eq!(list.get_command("so"), Some(2));
+        // the first candidate in list order wins
+        assert_eq!(list.get_command("s"), Some(2));
+        assert_eq!(list.get_command("se"), Some(3));
+        assert_eq!(list.get_command("sel"), Some(4));
+        assert_eq!(list.get_command("xyz"), None);
+        assert_eq!(list.get_command(""), None);
+        assert_eq!(list.get(4), Some("Select"));
+    }
+
+    #[test]
+    fn test_ambiguity_and_abbrev_switch() {
+        let mut list = CommandList::new(&["Set", "Select", "Solve"]);
+        assert!(list.is_ambiguous("se"));
+        assert!(!list.is_ambiguous("set"));
+        assert!(!list.is_ambiguous("so"));
+        assert_eq!(list.matches("s"), vec![0, 1, 2]);
+
+        list.set_abbrev_allowed(false);
+        assert_eq!(list.get_command("sol"), None);
+        assert_eq!(list.get_command("solve"), Some(2));
+        assert!(!list.is_ambiguous("se"));
+    }
+
+    #[test]
+    fn test_add_keeps_existing_index() {
+        let mut list = CommandList::new(&["kV"]);
+        assert_eq!(list.add("kW"), 1);
+        assert_eq!(list.add("KV"), 0);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.names().collect::<Vec<_>>(), vec!["kV", "kW"]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+// Kinds of non-fatal problems; processing goes on after any of these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    AmbiguousAbbreviation,
+    ValueClamped,
+    ValueIgnored,
+    Syntax,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+    pub context: Option<String>,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, message: &str) -> Self {
+        Warning {
+            kind,
+            message: message.to_string(),
+            context: None,
+        }
+    }
+
+    pub fn with_context(mut self, context: &str) -> Self {
+        self.context = Some(context.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "Warning: {} ({})", self.message, context),
+            None => write!(f, "Warning: {}", self.message),
+        }
+    }
+}
+
+// Collects the warnings raised while processing a command, kept apart from
+// hard errors as in OpenDSS. The caller reads or drains them afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    warnings: Vec<Warning>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    pub fn warn(&mut self, kind: WarningKind, message: &str) {
+        self.push(Warning::new(kind, message));
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    // Returns the collected warnings, leaving the sink empty
+    pub fn take(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn clear(&mut self) {
+        self.warnings.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_and_take_warnings() {
+        let mut diagnostics = Diagnostics::new();
+        assert!(diagnostics.is_empty());
+
+        diagnostics.warn(
+            WarningKind::ValueIgnored,
+            "Invalid number \"x\" in vector, using 0",
+        );
+        diagnostics.push(
+            Warning::new(WarningKind::ValueClamped, "kW limited to 100").with_context("Load.L1"),
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics.warnings()[0].kind, WarningKind::ValueIgnored);
+        assert_eq!(
+            diagnostics.warnings()[1].to_string(),
+            "Warning: kW limited to 100 (Load.L1)"
+        );
+
+        let taken = diagnostics.take();
+        assert_eq!(taken.len(), 2);
+        assert!(diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+// Error numbers reported through the API, grouped by subsystem in the same
+// ranges OpenDSS uses so that applications checking them keep working
+pub mod codes {
+    // Circuit element problems (100 series)
+    pub const SINGULAR_MATRIX: i32 = 183;
+
+    // Executive problems (200 series)
+    pub const UNKNOWN_COMMAND: i32 = 201;
+    pub const NO_ACTIVE_CIRCUIT: i32 = 202;
+    pub const UNKNOWN_CLASS: i32 = 203;
+    pub const OBJECT_NOT_FOUND: i32 = 204;
+    pub const UNKNOWN_OPTION: i32 = 205;
+    pub const FILE_ERROR: i32 = 206;
+    pub const UNKNOWN_PROPERTY: i32 = 207;
+    pub const SYNTAX_ERROR: i32 = 208;
+    pub const NOT_IMPLEMENTED: i32 = 299;
+
+    // Solution problems (400 series)
+    pub const NOT_CONVERGED: i32 = 470;
+    pub const MAX_CONTROL_ITERATIONS: i32 = 485;
+
+    // Parser problems (700 series)
+    pub const PARSER_ERROR: i32 = 700;
+    pub const CONVERSION_ERROR: i32 = 701;
+    pub const UNTERMINATED_QUOTE: i32 = 702;
+    pub const UNKNOWN_RPN_TOKEN: i32 = 703;
+    pub const UNKNOWN_VARIABLE: i32 = 704;
+    pub const MATRIX_DIMENSION_MISMATCH: i32 = 705;
+    pub const INLINE_MATH_ERROR: i32 = 706;
+}
+
+// Error carried across crate boundaries: the OpenDSS error number, the message
+// and optionally where it happened (an element name, a script file and line...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DssError {
+    number: i32,
+    message: String,
+    context: Option<String>,
+}
+
+impl DssError {
+    pub fn new(number: i32, message: &str) -> Self {
+        DssError {
+            number,
+            message: message.to_string(),
+            context: None,
+        }
+    }
+
+    pub fn with_context(mut self, context: &str) -> Self {
+        self.context = Some(context.to_string());
+        self
+    }
+
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+}
+
+impl fmt::Display for DssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{} ({})", self.message, context),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for DssError {}
+
+pub type DssResult<T> = Result<T, DssError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_number_and_message() {
+        let err = DssError::new(codes::CONVERSION_ERROR, "bad number");
+        assert_eq!(err.number(), 701);
+        assert_eq!(err.message(), "bad number");
+        assert_eq!(err.context(), None);
+        assert_eq!(err.to_string(), "bad number");
+    }
+
+    #[test]
+    fn test_error_context() {
+        let err = DssError::new(codes::UNKNOWN_VARIABLE, "Variable not found: \"@x\"")
+            .with_context("Line.L1");
+        assert_eq!(err.context(), Some("Line.L1"));
+        assert_eq!(err.to_string(), "Variable not found: \"@x\" (Line.L1)");
+    }
+}
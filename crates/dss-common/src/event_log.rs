@@ -0,0 +1,111 @@
+use std::fmt;
+
+// One control action: when it happened in solution time, which element acted
+// and what it did, e.g. a regulator changing taps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub hour: i32,
+    pub sec: f64,
+    // Control iteration of the solution step in which the action happened
+    pub control_iteration: u32,
+    pub element: String,
+    pub action: String,
+}
+
+impl Event {
+    pub fn new(hour: i32, sec: f64, element: &str, action: &str) -> Self {
+        Event {
+            hour,
+            sec,
+            control_iteration: 0,
+            element: element.to_string(),
+            action: action.to_string(),
+        }
+    }
+
+    pub fn with_control_iteration(mut self, control_iteration: u32) -> Self {
+        self.control_iteration = control_iteration;
+        self
+    }
+}
+
+// Same layout as the OpenDSS event log
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Hour={}, Sec={}, ControlIter={}, Element={}, Action={}",
+            self.hour, self.sec, self.control_iteration, self.element, self.action
+        )
+    }
+}
+
+// Control actions in the order they happened (Pascal EventStrings)
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    events: Vec<Event>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog { events: Vec::new() }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn log(&mut self, hour: i32, sec: f64, element: &str, action: &str) {
+        self.push(Event::new(hour, sec, element, action));
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // One line per event
+    pub fn to_text(&self) -> String {
+        let lines: Vec<String> = self.events.iter().map(Event::to_string).collect();
+        lines.join("\n")
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log() {
+        let mut log = EventLog::new();
+        assert!(log.is_empty());
+        log.log(1, 0.0, "Regulator.reg1", "CHANGED 1 TAPS TO 1.00625.");
+        log.push(Event::new(1, 15.5, "Capacitor.c1", "**OPENED**").with_control_iteration(2));
+
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log.to_text(),
+            "Hour=1, Sec=0, ControlIter=0, Element=Regulator.reg1, Action=CHANGED 1 TAPS TO 1.00625.\n\
+             Hour=1, Sec=15.5, ControlIter=2, Element=Capacitor.c1, Action=**OPENED**"
+        );
+        log.clear();
+        assert_eq!(log.to_text(), "");
+    }
+}
@@ -0,0 +1,11 @@
+// Types shared by all dss crates
+
+mod command_list;
+mod diagnostics;
+mod error;
+mod event_log;
+
+pub use command_list::CommandList;
+pub use diagnostics::{Diagnostics, Warning, WarningKind};
+pub use error::{DssError, DssResult, codes};
+pub use event_log::{Event, EventLog};
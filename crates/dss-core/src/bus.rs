@@ -0,0 +1,503 @@
+// Buses of a circuit.
+//
+// Buses are not defined on their own in DSS script; they come into being when
+// an element connects to them ("bus1=b.1.2"). The bus list collects them with
+// the nodes in use. Nominal voltages are found by carrying the source voltage
+// through the network: the same kV across lines and other series elements,
+// the winding kV across transformers. This matches the zero-load solution
+// OpenDSS uses for radial circuits without voltage regulation. A fault study
+// leaves each bus its short circuit matrices and the voltages before the
+// fault, from which the fault currents follow.
+
+use std::collections::HashMap;
+
+use dss_common::{DssError, DssResult, codes};
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElementBase;
+use crate::classes::{Transformer, Vsource};
+use crate::cmatrix::CMatrix;
+use crate::object::DssObject;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bus {
+    name: String,
+    // Node numbers in use, ground (0) excluded, in order of first use
+    nodes: Vec<u32>,
+    // Global node number of each node, the index into solution vectors
+    refs: Vec<usize>,
+    // Line-to-line base voltage in kV; 0 until CalcVoltageBases
+    kv_base: f64,
+    // Position for plots, from BusCoords
+    coords: Option<(f64, f64)>,
+    // Short circuit impedance and admittance among the nodes, in node
+    // order, and the node voltages before the fault (Pascal Zsc, Ysc and
+    // VBus); empty until a fault study
+    zsc: Option<CMatrix>,
+    ysc: Option<CMatrix>,
+    vbus: Vec<Complex64>,
+}
+
+impl Bus {
+    pub fn new(name: &str) -> Self {
+        Bus {
+            name: name.to_lowercase(),
+            nodes: Vec::new(),
+            refs: Vec::new(),
+            kv_base: 0.0,
+            coords: None,
+            zsc: None,
+            ysc: None,
+            vbus: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn nodes(&self) -> &[u32] {
+        &self.nodes
+    }
+
+    // Global node numbers, in the order of nodes()
+    pub fn refs(&self) -> &[usize] {
+        &self.refs
+    }
+
+    // Global node number of a node; ground is always 0
+    pub fn get_ref(&self, node: u32) -> Option<usize> {
+        if node == 0 {
+            return Some(0);
+        }
+        let index = self.nodes.iter().position(|&other| other == node)?;
+        Some(self.refs[index])
+    }
+
+    pub fn get_kv_base(&self) -> f64 {
+        self.kv_base
+    }
+
+    pub fn set_kv_base(&mut self, kv_base: f64) {
+        self.kv_base = kv_base;
+    }
+
+    pub fn get_coords(&self) -> Option<(f64, f64)> {
+        self.coords
+    }
+
+    pub fn set_coords(&mut self, x: f64, y: f64) {
+        self.coords = Some((x, y));
+    }
+
+    pub fn get_zsc(&self) -> Option<&CMatrix> {
+        self.zsc.as_ref()
+    }
+
+    pub fn get_ysc(&self) -> Option<&CMatrix> {
+        self.ysc.as_ref()
+    }
+
+    pub fn get_vbus(&self) -> &[Complex64] {
+        &self.vbus
+    }
+
+    // Keeps what a fault study found; Ysc is the inverse of Zsc, left out
+    // when that is singular
+    pub fn set_short_circuit(&mut self, zsc: CMatrix, vbus: Vec<Complex64>) {
+        let mut ysc = zsc.clone();
+        self.ysc = ysc.invert().then_some(ysc);
+        self.zsc = Some(zsc);
+        self.vbus = vbus;
+    }
+
+    // Currents into ground with every node faulted at once
+    pub fn all_node_fault_currents(&self) -> Vec<Complex64> {
+        self.ysc
+            .as_ref()
+            .map_or_else(Vec::new, |ysc| ysc.mv_mult(&self.vbus))
+    }
+
+    // Current of a fault from each node alone to ground
+    pub fn node_fault_currents(&self) -> Vec<Complex64> {
+        let Some(zsc) = &self.zsc else {
+            return Vec::new();
+        };
+        self.vbus
+            .iter()
+            .enumerate()
+            .map(|(k, v)| v / zsc.get(k, k))
+            .collect()
+    }
+
+    // Current of a fault between each node and the next, with the nodes
+    // faulted (Pascal "Adjacent Node-Node Faults")
+    pub fn node_node_fault_currents(&self) -> Vec<((u32, u32), Complex64)> {
+        let Some(zsc) = &self.zsc else {
+            return Vec::new();
+        };
+        (1..self.nodes.len())
+            .map(|j| {
+                let i = j - 1;
+                let z = zsc.get(i, i) + zsc.get(j, j) - zsc.get(i, j) - zsc.get(j, i);
+                (
+                    (self.nodes[i], self.nodes[j]),
+                    (self.vbus[i] - self.vbus[j]) / z,
+                )
+            })
+            .collect()
+    }
+}
+
+// The buses of a circuit with the global node numbering (Pascal BusList and
+// MapNodeToBus). Node numbers start at 1 and follow the order in which
+// nodes are first connected; 0 is ground. A solution vector holds one value
+// per node number, ground included.
+#[derive(Debug, Clone)]
+pub struct BusList {
+    buses: Vec<Bus>,
+    index: HashMap<String, usize>,
+    // Bus and node of each node number, starting at node number 1
+    node_map: Vec<(usize, u32)>,
+}
+
+impl BusList {
+    pub fn new() -> Self {
+        BusList {
+            buses: Vec::new(),
+            index: HashMap::new(),
+            node_map: Vec::new(),
+        }
+    }
+
+    // Index of a bus, added if it is new
+    pub fn add(&mut self, name: &str) -> usize {
+        let name = name.to_lowercase();
+        if let Some(&index) = self.index.get(&name) {
+            return index;
+        }
+        self.buses.push(Bus::new(&name));
+        self.index.insert(name, self.buses.len() - 1);
+        self.buses.len() - 1
+    }
+
+    // Global node number of a node of a bus, numbering the node if it is new
+    pub fn add_node(&mut self, bus: usize, node: u32) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        if let Some(node_ref) = self.buses[bus].get_ref(node) {
+            return node_ref;
+        }
+        self.node_map.push((bus, node));
+        let node_ref = self.node_map.len();
+        self.buses[bus].nodes.push(node);
+        self.buses[bus].refs.push(node_ref);
+        node_ref
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.index.get(&name.to_lowercase()).copied()
+    }
+
+    pub fn buses(&self) -> &[Bus] {
+        &self.buses
+    }
+
+    pub fn buses_mut(&mut self) -> &mut [Bus] {
+        &mut self.buses
+    }
+
+    pub fn len(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buses.is_empty()
+    }
+
+    // Number of nodes, ground not counted
+    pub fn num_nodes(&self) -> usize {
+        self.node_map.len()
+    }
+
+    // Global node number of node `node` of the named bus
+    pub fn node_ref(&self, bus_name: &str, node: u32) -> Option<usize> {
+        self.buses[self.find(bus_name)?].get_ref(node)
+    }
+
+    // Bus index and node of a global node number; None for ground
+    pub fn node_bus(&self, node_ref: usize) -> Option<(usize, u32)> {
+        self.node_map.get(node_ref.checked_sub(1)?).copied()
+    }
+}
+
+impl Default for BusList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Splits a bus specification "name.1.2.3" into the bus name and its nodes.
+// Without nodes the element connects to 1..=phases.
+pub fn parse_bus_spec(spec: &str, phases: u32) -> DssResult<(String, Vec<u32>)> {
+    let mut parts = spec.split('.');
+    let name = parts.next().unwrap_or("").to_lowercase();
+    if name.is_empty() {
+        return Err(DssError::new(
+            codes::SYNTAX_ERROR,
+            &format!("Bus name missing in \"{}\"", spec),
+        ));
+    }
+    let nodes = parts
+        .filter(|node| !node.is_empty())
+        .map(|node| {
+            node.parse::<u32>().map_err(|_| {
+                DssError::new(
+                    codes::SYNTAX_ERROR,
+                    &format!("Invalid node \"{}\" in bus \"{}\"", node, spec),
+                )
+            })
+        })
+        .collect::<DssResult<Vec<u32>>>()?;
+    if nodes.is_empty() {
+        return Ok((name, (1..=phases).collect()));
+    }
+    Ok((name, nodes))
+}
+
+// Splits an array value "[a, b c]" (brackets already removed by the parser)
+fn split_array(value: &str) -> Vec<&str> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+// Bus specifications of an element, terminal by terminal
+fn element_buses(element: &dyn DssObject) -> Vec<String> {
+    if let Some(element) = element.as_ckt_element() {
+        return element.ckt_base().buses().to_vec();
+    }
+    if let Some(buses) = element.get_property_value("buses") {
+        return split_array(&buses)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+    }
+    ["bus1", "bus2", "bus"]
+        .iter()
+        .filter_map(|name| element.get_property_value(name))
+        .collect()
+}
+
+fn element_phases(element: &dyn DssObject) -> u32 {
+    if let Some(element) = element.as_ckt_element() {
+        return element.nphases() as u32;
+    }
+    element
+        .get_property_value("phases")
+        .and_then(|phases| phases.parse().ok())
+        .unwrap_or(3)
+}
+
+fn bus_name(spec: &str) -> String {
+    spec.split('.').next().unwrap_or("").to_lowercase()
+}
+
+// Numbers the nodes of a circuit element's terminals and stores them in the
+// element (Pascal ProcessBusDefs). Conductors default to nodes 1, 2, ... for
+// the phases and ground for the rest; the nodes given in the bus
+// specification replace the defaults in order.
+fn set_node_refs(list: &mut BusList, element: &mut CktElementBase) -> DssResult<()> {
+    let (nphases, nconds) = (element.nphases(), element.nconds());
+    for terminal in 0..element.nterms() {
+        let spec = element.get_bus(terminal).to_string();
+        if spec.is_empty() {
+            continue;
+        }
+        let (name, given) = parse_bus_spec(&spec, 0)?;
+        let mut nodes: Vec<u32> = (1..=nconds as u32)
+            .map(|node| if node as usize > nphases { 0 } else { node })
+            .collect();
+        for (node, given) in nodes.iter_mut().zip(given) {
+            *node = given;
+        }
+        let bus = list.add(&name);
+        let refs: Vec<usize> = nodes.iter().map(|&node| list.add_node(bus, node)).collect();
+        element.set_node_refs(terminal, &refs);
+    }
+    Ok(())
+}
+
+impl Circuit {
+    // Rebuilds the bus list from the bus specifications of all elements;
+    // disabled circuit elements are left out (Pascal ReProcessBusDefs). Base
+    // voltages and coordinates of buses still in use are kept (Pascal
+    // SaveBusInfo/RestoreBusInfo).
+    pub fn make_bus_list(&mut self) -> DssResult<()> {
+        let mut list = BusList::new();
+        for id in 0..self.elements().len() {
+            let Some(element) = self.element_mut(id) else {
+                continue;
+            };
+            if let Some(element) = element.as_ckt_element_mut() {
+                if element.ckt_base().is_enabled() {
+                    set_node_refs(&mut list, element.ckt_base_mut())?;
+                }
+                continue;
+            }
+            let phases = element_phases(element);
+            for spec in element_buses(element) {
+                let (name, nodes) = parse_bus_spec(&spec, phases)?;
+                let bus = list.add(&name);
+                for node in nodes {
+                    list.add_node(bus, node);
+                }
+            }
+        }
+        for bus in list.buses_mut() {
+            let Some(old) = self.bus_list().find(&bus.name) else {
+                continue;
+            };
+            let old = &self.buses()[old];
+            bus.kv_base = old.kv_base;
+            bus.coords = old.coords;
+        }
+        self.set_bus_list(list);
+        Ok(())
+    }
+
+    // Nominal line-to-line kV of each bus, carried out from the sources;
+    // None for buses the sources do not reach
+    pub fn nominal_kv(&self) -> Vec<Option<f64>> {
+        let index_of = |spec: &str| -> Option<usize> { self.bus_list().find(&bus_name(spec)) };
+        let mut kv: Vec<Option<f64>> = vec![None; self.buses().len()];
+
+        for element in self.elements() {
+            let Some(source) = element.as_any().downcast_ref::<Vsource>() else {
+                continue;
+            };
+            let source_kv = source.get_base_kv();
+            if let Some(index) = element_buses(element.as_ref())
+                .first()
+                .and_then(|spec| index_of(spec))
+            {
+                kv[index] = Some(source_kv);
+            }
+        }
+
+        // repeat until nothing changes; each pass carries voltages one
+        // element further at least
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for element in self.elements() {
+                let terminals: Vec<Option<usize>> = element_buses(element.as_ref())
+                    .iter()
+                    .map(|spec| index_of(spec))
+                    .collect();
+                if terminals.len() < 2 {
+                    continue;
+                }
+
+                if let Some(transformer) = element.as_any().downcast_ref::<Transformer>() {
+                    let kvs = transformer.winding_kvs();
+                    let known = terminals
+                        .iter()
+                        .zip(&kvs)
+                        .any(|(terminal, _)| terminal.is_some_and(|bus| kv[bus].is_some()));
+                    if !known {
+                        continue;
+                    }
+                    for (terminal, winding_kv) in terminals.iter().zip(&kvs) {
+                        if let Some(bus) = *terminal
+                            && kv[bus].is_none()
+                        {
+                            kv[bus] = Some(*winding_kv);
+                            changed = true;
+                        }
+                    }
+                    continue;
+                }
+
+                let Some(known) = terminals.iter().flatten().find_map(|&bus| kv[bus]) else {
+                    continue;
+                };
+                for &bus in terminals.iter().flatten() {
+                    if kv[bus].is_none() {
+                        kv[bus] = Some(known);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bus_spec() {
+        assert_eq!(
+            parse_bus_spec("B1", 3).unwrap(),
+            ("b1".to_string(), vec![1, 2, 3])
+        );
+        assert_eq!(parse_bus_spec("b1.2", 1).unwrap().1, vec![2]);
+        assert_eq!(parse_bus_spec("b1.1.0", 1).unwrap().1, vec![1, 0]);
+        assert_eq!(parse_bus_spec("b1", 1).unwrap().1, vec![1]);
+        assert!(parse_bus_spec("b1.x", 3).is_err());
+        assert!(parse_bus_spec(".1", 3).is_err());
+    }
+
+    #[test]
+    fn test_fault_currents() {
+        let mut bus = Bus::new("b");
+        bus.nodes = vec![1, 2];
+        let c = |re: f64, im: f64| Complex64::new(re, im);
+        let mut zsc = CMatrix::new(2);
+        zsc.set(0, 0, c(1.0, 2.0));
+        zsc.set(1, 1, c(1.0, 2.0));
+        zsc.set_sym(0, 1, c(0.5, 1.0));
+        let vbus = vec![c(100.0, 0.0), c(-50.0, -86.6)];
+        bus.set_short_circuit(zsc, vbus.clone());
+
+        let slg = bus.node_fault_currents();
+        assert!((slg[0] - vbus[0] / c(1.0, 2.0)).norm() < 1e-12);
+        // node to node through the impedance of the loop
+        let (nodes, ll) = bus.node_node_fault_currents()[0];
+        assert_eq!(nodes, (1, 2));
+        assert!((ll - (vbus[0] - vbus[1]) / c(1.0, 2.0)).norm() < 1e-12);
+        // with both nodes faulted, Zsc carries the currents to the voltages
+        let all = bus.all_node_fault_currents();
+        let back = bus.get_zsc().unwrap().mv_mult(&all);
+        assert!((back[0] - vbus[0]).norm() < 1e-9 && (back[1] - vbus[1]).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_node_numbering() {
+        let mut list = BusList::new();
+        let source = list.add("SourceBus");
+        for node in [1, 2, 3] {
+            list.add_node(source, node);
+        }
+        let b = list.add("b");
+        assert_eq!(list.add_node(b, 2), 4);
+        assert_eq!(list.add_node(b, 0), 0);
+        assert_eq!(list.add_node(source, 2), 2);
+        assert_eq!(list.add("sourcebus"), source);
+
+        assert_eq!(list.num_nodes(), 4);
+        assert_eq!(list.node_ref("b", 2), Some(4));
+        assert_eq!(list.node_ref("b", 1), None);
+        assert_eq!(list.node_ref("b", 0), Some(0));
+        assert_eq!(list.node_bus(3), Some((source, 3)));
+        assert_eq!(list.node_bus(0), None);
+        assert_eq!(list.node_bus(5), None);
+        assert_eq!(list.buses()[source].refs(), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,381 @@
+// Circuit model: the elements of a circuit with their name registry, the
+// active element, the buses and the node voltages of the last solution.
+
+use std::path::{Path, PathBuf};
+
+use num_complex::Complex64;
+
+use crate::bus::{Bus, BusList};
+use crate::class::DssClass;
+use crate::classes::find_class;
+use crate::generic::GenericClass;
+use crate::meter_element::SolutionState;
+use crate::object::DssObject;
+use crate::registry::{ElementId, Registry};
+
+// Case-insensitive match with '*' for any run of characters and '?' for
+// exactly one character
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last '*' and of the text it was tried against
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // let the last '*' absorb one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    name: String,
+    elements: Vec<Box<dyn DssObject>>,
+    registry: Registry,
+    active_element: Option<ElementId>,
+    // Class of the last element referenced; object names given without a
+    // class refer to it
+    active_class: Option<&'static str>,
+    // 1-based terminal of the active element set by Select
+    active_terminal: usize,
+    // Filled in by make_bus_list
+    bus_list: BusList,
+    // Node voltages of the last solution, index 0 ground; empty until the
+    // circuit is solved (Pascal NodeV)
+    node_voltages: Vec<Complex64>,
+    // Directory that file names given in property values are relative to;
+    // the executive keeps it at its current directory
+    current_dir: PathBuf,
+}
+
+impl Circuit {
+    // A new circuit always comes with its source, "Vsource.source", which
+    // starts out as the active element
+    pub fn new(name: &str) -> Self {
+        let mut circuit = Circuit {
+            name: name.to_lowercase(),
+            elements: Vec::new(),
+            registry: Registry::new(),
+            active_element: None,
+            active_class: None,
+            active_terminal: 1,
+            bus_list: BusList::new(),
+            node_voltages: Vec::new(),
+            current_dir: PathBuf::from("."),
+        };
+        if let Some(class) = find_class("Vsource") {
+            let source = circuit.add_element(class.new_object("source"));
+            circuit.set_active_element(source);
+        }
+        circuit
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Adds an element, replacing any element with the same full name
+    pub fn add_element(&mut self, element: Box<dyn DssObject>) -> ElementId {
+        match self.find_element(element.class_name(), element.name()) {
+            Some(id) => {
+                self.elements[id] = element;
+                id
+            }
+            None => {
+                let id = self.elements.len();
+                self.registry.add(element.class_name(), element.name(), id);
+                self.elements.push(element);
+                id
+            }
+        }
+    }
+
+    // Removes the element added last, taking back add_element; handles of
+    // the other elements stay valid
+    pub fn remove_last_element(&mut self) -> Option<Box<dyn DssObject>> {
+        let element = self.elements.pop()?;
+        self.registry.remove(element.class_name(), element.name());
+        if self.active_element == Some(self.elements.len()) {
+            self.active_element = None;
+        }
+        Some(element)
+    }
+
+    pub fn find_element(&self, class_name: &str, name: &str) -> Option<ElementId> {
+        self.registry.find(class_name, name)
+    }
+
+    // An object of a class as its concrete type, for objects that refer to
+    // others by name
+    pub fn find_object_as<T: 'static>(&self, class_name: &str, name: &str) -> Option<&T> {
+        let id = self.find_element(class_name, name)?;
+        self.elements[id].as_any().downcast_ref::<T>()
+    }
+
+    // Elements of a class, in order of definition
+    pub fn class_elements(&self, class_name: &str) -> &[ElementId] {
+        self.registry.class_elements(class_name)
+    }
+
+    // Elements whose full name matches a wildcard pattern; a pattern with a
+    // plain class part only looks through that class
+    pub fn find_matching(&self, pattern: &str) -> Vec<ElementId> {
+        let matches = |id: &ElementId| wildcard_match(pattern, &self.elements[*id].full_name());
+        match pattern.split_once('.') {
+            Some((class_name, _)) if !class_name.contains(['*', '?']) => self
+                .class_elements(class_name)
+                .iter()
+                .copied()
+                .filter(matches)
+                .collect(),
+            _ => (0..self.elements.len()).filter(matches).collect(),
+        }
+    }
+
+    pub fn element(&self, id: ElementId) -> Option<&dyn DssObject> {
+        self.elements.get(id).map(Box::as_ref)
+    }
+
+    pub fn element_mut(&mut self, id: ElementId) -> Option<&mut (dyn DssObject + 'static)> {
+        self.elements.get_mut(id).map(Box::as_mut)
+    }
+
+    // Takes an element out to edit it while the rest of the circuit is
+    // looked at; a stand-in with the same name holds its place until
+    // replace_element puts it back
+    pub fn take_element(&mut self, id: ElementId) -> Option<Box<dyn DssObject>> {
+        let current = self.elements.get_mut(id)?;
+        let stand_in = GenericClass::new(current.class_name()).new_object(current.name());
+        Some(std::mem::replace(current, stand_in))
+    }
+
+    // Puts an earlier copy of an element back in its place
+    pub fn replace_element(&mut self, id: ElementId, element: Box<dyn DssObject>) {
+        if let Some(current) = self.elements.get_mut(id) {
+            *current = element;
+        }
+    }
+
+    pub fn elements(&self) -> &[Box<dyn DssObject>] {
+        &self.elements
+    }
+
+    pub fn buses(&self) -> &[Bus] {
+        self.bus_list.buses()
+    }
+
+    pub fn buses_mut(&mut self) -> &mut [Bus] {
+        self.bus_list.buses_mut()
+    }
+
+    pub fn bus_list(&self) -> &BusList {
+        &self.bus_list
+    }
+
+    pub fn set_bus_list(&mut self, bus_list: BusList) {
+        self.bus_list = bus_list;
+    }
+
+    // Number of nodes in the bus list, ground not counted
+    pub fn num_nodes(&self) -> usize {
+        self.bus_list.num_nodes()
+    }
+
+    pub fn get_current_dir(&self) -> &Path {
+        &self.current_dir
+    }
+
+    pub fn set_current_dir(&mut self, dir: &Path) {
+        self.current_dir = dir.to_path_buf();
+    }
+
+    pub fn get_node_voltages(&self) -> &[Complex64] {
+        &self.node_voltages
+    }
+
+    pub fn set_node_voltages(&mut self, voltages: Vec<Complex64>) {
+        self.node_voltages = voltages;
+    }
+
+    // Has every enabled meter element record the last solution; each is
+    // taken out of the circuit while it looks at the rest
+    pub fn sample_meters(&mut self, state: &SolutionState) {
+        self.sample_class_meters(None, state);
+    }
+
+    // Samples the meter elements of a class only, or of all classes
+    pub fn sample_class_meters(&mut self, class_name: Option<&str>, state: &SolutionState) {
+        for id in 0..self.elements.len() {
+            let in_class = class_name
+                .is_none_or(|name| self.elements[id].class_name().eq_ignore_ascii_case(name));
+            let is_meter = self.elements[id]
+                .as_ckt_element()
+                .filter(|element| element.ckt_base().is_enabled())
+                .and_then(|element| element.as_meter_element())
+                .is_some();
+            if !in_class || !is_meter {
+                continue;
+            }
+            let Some(mut element) = self.take_element(id) else {
+                continue;
+            };
+            if let Some(meter) = element
+                .as_ckt_element_mut()
+                .and_then(|element| element.as_meter_element_mut())
+            {
+                meter.take_sample(self, &self.node_voltages, state);
+            }
+            self.replace_element(id, element);
+        }
+    }
+
+    // Clears what the meter elements of a class recorded, or of all classes
+    pub fn reset_meters(&mut self, class_name: Option<&str>) {
+        for element in &mut self.elements {
+            let in_class =
+                class_name.is_none_or(|name| element.class_name().eq_ignore_ascii_case(name));
+            if let Some(meter) = element
+                .as_ckt_element_mut()
+                .and_then(|element| element.as_meter_element_mut())
+                .filter(|_| in_class)
+            {
+                meter.reset();
+            }
+        }
+    }
+
+    // Total, load and no-load losses of a power delivery element at the
+    // last solution, in VA; None for other and disabled elements
+    pub fn element_losses(&self, id: ElementId) -> Option<(Complex64, Complex64, Complex64)> {
+        self.element(id)?
+            .as_ckt_element()
+            .filter(|element| element.ckt_base().is_enabled())?
+            .as_pd_element()
+            .map(|element| element.losses(&self.node_voltages))
+    }
+
+    // Losses of the whole circuit at the last solution, VA (Pascal Losses)
+    pub fn losses(&self) -> Complex64 {
+        self.ids_losses(0..self.elements.len())
+    }
+
+    // Losses of the elements of one class, e.g. Line for the line losses
+    // (Pascal LineLosses)
+    pub fn class_losses(&self, class_name: &str) -> Complex64 {
+        self.ids_losses(self.class_elements(class_name).iter().copied())
+    }
+
+    fn ids_losses(&self, ids: impl Iterator<Item = ElementId>) -> Complex64 {
+        ids.filter_map(|id| self.element_losses(id))
+            .fold(Complex64::new(0.0, 0.0), |sum, (total, _, _)| sum + total)
+    }
+
+    pub fn get_active_element(&self) -> Option<ElementId> {
+        self.active_element
+    }
+
+    // Also makes the element's class the active class and its first terminal
+    // the active terminal
+    pub fn set_active_element(&mut self, id: ElementId) {
+        if let Some(element) = self.elements.get(id) {
+            self.active_class = Some(element.class_name());
+            self.active_element = Some(id);
+            self.active_terminal = 1;
+        }
+    }
+
+    pub fn get_active_class(&self) -> Option<&'static str> {
+        self.active_class
+    }
+
+    pub fn set_active_class(&mut self, class_name: &'static str) {
+        self.active_class = Some(class_name);
+    }
+
+    pub fn get_active_terminal(&self) -> usize {
+        self.active_terminal
+    }
+
+    pub fn set_active_terminal(&mut self, terminal: usize) {
+        self.active_terminal = terminal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_object;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("Load.*", "load.ld1"));
+        assert!(wildcard_match("line.l?", "Line.l1"));
+        assert!(!wildcard_match("line.l?", "Line.l12"));
+        assert!(wildcard_match("*.a*b", "Line.axxbyyb"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("line.*x", "Line.l1"));
+        assert!(wildcard_match("line.l1", "LINE.L1"));
+    }
+
+    fn new_element(class_name: &str, name: &str, properties: &str) -> Box<dyn DssObject> {
+        new_object(class_name, name, properties, &Circuit::new("test"))
+    }
+
+    #[test]
+    fn test_circuit_elements() {
+        let mut circuit = Circuit::new("Test");
+        assert_eq!(circuit.name(), "test");
+        assert_eq!(circuit.elements()[0].full_name(), "Vsource.source");
+        assert_eq!(circuit.get_active_element(), Some(0));
+        assert_eq!(circuit.get_active_class(), Some("Vsource"));
+
+        let id = circuit.add_element(new_element("Line", "L1", "bus1=a Bus1=b length=2"));
+        assert_eq!(circuit.find_element("LINE", "l1"), Some(id));
+        circuit.set_active_element(id);
+        assert_eq!(circuit.get_active_class(), Some("Line"));
+        circuit.set_active_element(99);
+        assert_eq!(circuit.get_active_element(), Some(id));
+
+        let line = circuit.element(id).unwrap();
+        assert_eq!(line.get_property_value("BUS1").as_deref(), Some("b"));
+        assert_eq!(line.property_values().len(), 2);
+
+        circuit.add_element(new_element("Line", "l2", ""));
+        circuit.add_element(new_element("Load", "l3", ""));
+        assert_eq!(circuit.find_matching("line.*"), vec![id, id + 1]);
+        assert_eq!(circuit.find_matching("*.l?"), vec![1, 2, 3]);
+        assert_eq!(circuit.class_elements("load"), [3]);
+
+        // same full name replaces the element
+        assert_eq!(circuit.add_element(new_element("Line", "l1", "")), id);
+        assert!(circuit.element(id).unwrap().property_values().is_empty());
+
+        assert!(circuit.remove_last_element().is_some());
+        assert_eq!(circuit.find_element("Load", "l3"), None);
+        assert!(circuit.class_elements("Load").is_empty());
+    }
+
+    #[test]
+    fn test_many_elements() {
+        let mut circuit = Circuit::new("big");
+        let class = find_class("Load").unwrap();
+        for n in 0..20_000 {
+            circuit.add_element(class.new_object(&format!("ld{}", n)));
+        }
+        assert_eq!(circuit.find_element("load", "LD19999"), Some(20_000));
+        assert_eq!(circuit.class_elements("Load").len(), 20_000);
+    }
+}
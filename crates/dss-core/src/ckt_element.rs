@@ -0,0 +1,538 @@
+// Circuit elements (Pascal TDSSCktElement): objects connected to buses through
+// terminals, described to the solution by their primitive admittance matrix.
+// Each terminal has one conductor per phase plus any extra (neutral)
+// conductors; Yprim has one row per conductor of every terminal.
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::cmatrix::CMatrix;
+use crate::control_element::ControlElement;
+use crate::meter_element::MeterElement;
+use crate::object::DssObject;
+use crate::pc_element::PcElement;
+use crate::pd_element::PdElement;
+use crate::property::{PropertyDef, PropertyKind, interpret_yes_no};
+
+// Properties every circuit element has, after those of its class
+pub const CKT_PROPERTIES: [PropertyDef; 2] = [
+    PropertyDef {
+        name: "basefreq",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Base frequency for ratings, Hz.",
+    },
+    PropertyDef {
+        name: "enabled",
+        kind: PropertyKind::Bool,
+        default: "true",
+        help: "{Yes|No or True|False} Indicates whether this element is enabled.",
+    },
+];
+
+// How the phases of a terminal are connected (Pascal Connection 0/1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connection {
+    Wye,
+    Delta,
+}
+
+impl Connection {
+    // Names in property values; "ln" and "ll" are the line-to-neutral and
+    // line-to-line aliases of wye and delta
+    pub const NAMES: &'static [&'static str] = &["wye", "delta", "y", "ln", "ll"];
+
+    // Connection of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        if name.eq_ignore_ascii_case("delta") || name.eq_ignore_ascii_case("ll") {
+            Connection::Delta
+        } else {
+            Connection::Wye
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Connection::Wye => "wye",
+            Connection::Delta => "delta",
+        }
+    }
+}
+
+// State every circuit element has; element implementations embed one next
+// to their ObjectBase
+#[derive(Debug, Clone)]
+pub struct CktElementBase {
+    nphases: usize,
+    nconds: usize,
+    // Bus specification of each terminal, e.g. "bus1.1.2.3"
+    buses: Vec<String>,
+    // Global node number of each conductor, terminal by terminal; zeros
+    // until the bus list is built
+    node_refs: Vec<usize>,
+    // Whether each conductor is closed, terminal by terminal; switching
+    // controls open them (Pascal Terminals[].Conductors[].Closed)
+    closed: Vec<bool>,
+    yprim: Option<CMatrix>,
+    base_frequency: f64,
+    enabled: bool,
+}
+
+impl CktElementBase {
+    pub fn new(nphases: usize, nterms: usize) -> Self {
+        CktElementBase {
+            nphases,
+            nconds: nphases,
+            buses: vec![String::new(); nterms],
+            node_refs: vec![0; nterms * nphases],
+            closed: vec![true; nterms * nphases],
+            yprim: None,
+            base_frequency: 60.0,
+            enabled: true,
+        }
+    }
+
+    // Takes property `index` of CKT_PROPERTIES from the parser
+    pub fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match index {
+            0 => self.base_frequency = parser.make_double()?,
+            1 => self.enabled = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        self.yprim = None;
+        Ok(())
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.nphases
+    }
+
+    pub fn nterms(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub fn nconds(&self) -> usize {
+        self.nconds
+    }
+
+    // Order of Yprim: every conductor of every terminal
+    pub fn y_order(&self) -> usize {
+        self.nterms() * self.nconds
+    }
+
+    // Sets the phases; the conductors follow unless the element has more
+    pub fn set_phases(&mut self, nphases: usize) {
+        let extra = self.nconds.saturating_sub(self.nphases);
+        self.nphases = nphases;
+        self.set_conductors(nphases + extra);
+    }
+
+    pub fn set_conductors(&mut self, nconds: usize) {
+        self.nconds = nconds;
+        self.node_refs = vec![0; self.y_order()];
+        self.closed = vec![true; self.y_order()];
+        self.yprim = None;
+    }
+
+    pub fn set_terminals(&mut self, nterms: usize) {
+        self.buses.resize(nterms, String::new());
+        self.node_refs = vec![0; self.y_order()];
+        self.closed = vec![true; self.y_order()];
+        self.yprim = None;
+    }
+
+    pub fn get_bus(&self, terminal: usize) -> &str {
+        self.buses.get(terminal).map_or("", String::as_str)
+    }
+
+    pub fn set_bus(&mut self, terminal: usize, spec: &str) {
+        if let Some(bus) = self.buses.get_mut(terminal) {
+            *bus = spec.to_string();
+        }
+    }
+
+    pub fn buses(&self) -> &[String] {
+        &self.buses
+    }
+
+    pub fn node_refs(&self) -> &[usize] {
+        &self.node_refs
+    }
+
+    // Node numbers of one terminal's conductors
+    pub fn terminal_refs(&self, terminal: usize) -> &[usize] {
+        &self.node_refs[terminal * self.nconds..(terminal + 1) * self.nconds]
+    }
+
+    pub fn set_node_refs(&mut self, terminal: usize, refs: &[usize]) {
+        let start = terminal * self.nconds;
+        self.node_refs[start..start + refs.len()].copy_from_slice(refs);
+    }
+
+    pub fn get_yprim(&self) -> Option<&CMatrix> {
+        self.yprim.as_ref()
+    }
+
+    // Stores Yprim with open conductors taken out: each is eliminated as a
+    // node carrying no current, so a series element opened at one end no
+    // longer loads the other, then a tiny admittance to ground keeps the
+    // isolated node from floating
+    pub fn set_yprim(&mut self, mut yprim: CMatrix) {
+        let order = yprim.order();
+        let zero = Complex64::new(0.0, 0.0);
+        for i in (0..order).filter(|&i| !self.closed.get(i).copied().unwrap_or(true)) {
+            let pivot = yprim.get(i, i);
+            if pivot.norm() > 0.0 {
+                for row in (0..order).filter(|&row| row != i) {
+                    let factor = yprim.get(row, i) / pivot;
+                    for col in (0..order).filter(|&col| col != i) {
+                        yprim.add(row, col, -factor * yprim.get(i, col));
+                    }
+                }
+            }
+            for j in 0..order {
+                yprim.set_sym(i, j, zero);
+            }
+            yprim.set(i, i, Complex64::new(1.0e-12, 0.0));
+        }
+        self.yprim = Some(yprim);
+    }
+
+    pub fn is_closed(&self, terminal: usize, conductor: usize) -> bool {
+        self.closed
+            .get(terminal * self.nconds + conductor)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // True when every conductor of the terminal is closed
+    pub fn is_terminal_closed(&self, terminal: usize) -> bool {
+        (0..self.nconds).all(|conductor| self.is_closed(terminal, conductor))
+    }
+
+    // Opens or closes one conductor of a terminal, or all of them when no
+    // conductor is given
+    pub fn set_closed(&mut self, terminal: usize, conductor: Option<usize>, closed: bool) {
+        if terminal >= self.nterms() {
+            return;
+        }
+        let conductors = match conductor {
+            Some(conductor) if conductor < self.nconds => conductor..conductor + 1,
+            Some(_) => return,
+            None => 0..self.nconds,
+        };
+        for conductor in conductors {
+            self.closed[terminal * self.nconds + conductor] = closed;
+        }
+        self.yprim = None;
+    }
+
+    // Marks Yprim for recalculation after a change to the element
+    pub fn invalidate_yprim(&mut self) {
+        self.yprim = None;
+    }
+
+    pub fn get_base_frequency(&self) -> f64 {
+        self.base_frequency
+    }
+
+    pub fn set_base_frequency(&mut self, frequency: f64) {
+        self.base_frequency = frequency;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+pub trait CktElement: DssObject {
+    fn ckt_base(&self) -> &CktElementBase;
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase;
+
+    // Builds Yprim at the given frequency and stores it in the base
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()>;
+
+    // The element as a power delivery, power conversion, control or meter
+    // element
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        None
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        None
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        None
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        None
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        None
+    }
+
+    fn as_meter_element(&self) -> Option<&dyn MeterElement> {
+        None
+    }
+
+    fn as_meter_element_mut(&mut self) -> Option<&mut dyn MeterElement> {
+        None
+    }
+
+    // Currents the element injects into its conductors apart from Yprim
+    // (Pascal InjCurrents); passive elements inject none
+    fn get_injection_currents(&self, _voltages: &[Complex64]) -> Vec<Complex64> {
+        vec![Complex64::new(0.0, 0.0); self.ckt_base().y_order()]
+    }
+
+    // Currents flowing into the element at each conductor, given the node
+    // voltages of the circuit (index 0 is ground)
+    fn get_currents(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let Some(yprim) = self.ckt_base().get_yprim() else {
+            return vec![Complex64::new(0.0, 0.0); self.ckt_base().y_order()];
+        };
+        // Nothing flows through an open conductor, whatever the element injects
+        let currents = yprim.mv_mult(&self.terminal_voltages(voltages));
+        let injection = self.get_injection_currents(voltages);
+        let nconds = self.nconds().max(1);
+        currents
+            .iter()
+            .zip(injection)
+            .enumerate()
+            .map(|(i, (current, injected))| {
+                if self.ckt_base().is_closed(i / nconds, i % nconds) {
+                    current - injected
+                } else {
+                    Complex64::new(0.0, 0.0)
+                }
+            })
+            .collect()
+    }
+
+    fn nphases(&self) -> usize {
+        self.ckt_base().nphases()
+    }
+
+    fn nterms(&self) -> usize {
+        self.ckt_base().nterms()
+    }
+
+    fn nconds(&self) -> usize {
+        self.ckt_base().nconds()
+    }
+
+    fn get_bus(&self, terminal: usize) -> &str {
+        self.ckt_base().get_bus(terminal)
+    }
+
+    // Voltage at each conductor, terminal by terminal
+    fn terminal_voltages(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        self.ckt_base()
+            .node_refs()
+            .iter()
+            .map(|&node_ref| {
+                voltages
+                    .get(node_ref)
+                    .copied()
+                    .unwrap_or(Complex64::new(0.0, 0.0))
+            })
+            .collect()
+    }
+
+    // Complex power flowing into the element at each terminal, in VA
+    fn terminal_powers(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let nconds = self.nconds();
+        let currents = self.get_currents(voltages);
+        self.terminal_voltages(voltages)
+            .iter()
+            .zip(&currents)
+            .map(|(v, i)| v * i.conj())
+            .collect::<Vec<_>>()
+            .chunks(nconds.max(1))
+            .map(|terminal| terminal.iter().sum())
+            .collect()
+    }
+
+    // Total power into the element over all terminals; for a line, its losses
+    fn total_power(&self, voltages: &[Complex64]) -> Complex64 {
+        self.terminal_powers(voltages).iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use dss_parser::DSSParser;
+
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::object::ObjectBase;
+    use crate::property::{PropertyDef, PropertyKind};
+
+    const PROPERTIES: &[PropertyDef] = &[
+        PropertyDef {
+            name: "bus1",
+            kind: PropertyKind::Bus,
+            default: "",
+            help: "First terminal.",
+        },
+        PropertyDef {
+            name: "bus2",
+            kind: PropertyKind::Bus,
+            default: "",
+            help: "Second terminal.",
+        },
+    ];
+
+    // Series impedance of 1+1j ohm in each of three phases
+    #[derive(Debug, Clone)]
+    struct Series {
+        base: ObjectBase,
+        ckt: CktElementBase,
+    }
+
+    impl Series {
+        fn new(name: &str, bus1: &str, bus2: &str) -> Self {
+            let mut ckt = CktElementBase::new(3, 2);
+            ckt.set_bus(0, bus1);
+            ckt.set_bus(1, bus2);
+            Series {
+                base: ObjectBase::new("Series", name, PROPERTIES),
+                ckt,
+            }
+        }
+    }
+
+    impl DssObject for Series {
+        fn base(&self) -> &ObjectBase {
+            &self.base
+        }
+
+        fn base_mut(&mut self) -> &mut ObjectBase {
+            &mut self.base
+        }
+
+        fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+            self.ckt.set_bus(index, parser.get_token());
+            Ok(())
+        }
+
+        fn clone_object(&self) -> Box<dyn DssObject> {
+            Box::new(self.clone())
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+            Some(self)
+        }
+
+        fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+            Some(self)
+        }
+    }
+
+    impl CktElement for Series {
+        fn ckt_base(&self) -> &CktElementBase {
+            &self.ckt
+        }
+
+        fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+            &mut self.ckt
+        }
+
+        fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+            let y = Complex64::new(1.0, 0.0) / Complex64::new(1.0, 1.0);
+            let mut yprim = CMatrix::new(self.ckt.y_order());
+            for phase in 0..3 {
+                yprim.set(phase, phase, y);
+                yprim.set(phase + 3, phase + 3, y);
+                yprim.set_sym(phase, phase + 3, -y);
+            }
+            self.ckt.set_yprim(yprim);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_node_refs() {
+        let mut circuit = Circuit::new("test");
+        circuit.add_element(Box::new(Series::new("a", "b1", "b2.3.2.1")));
+        let mut grounded = Series::new("b", "b2.1", "b3");
+        grounded.ckt_base_mut().set_conductors(4);
+        let id = circuit.add_element(Box::new(grounded));
+        circuit.make_bus_list().unwrap();
+
+        // sourcebus takes nodes 1 to 3
+        let a = circuit.element(1).unwrap().as_ckt_element().unwrap();
+        assert_eq!(a.ckt_base().node_refs(), [4, 5, 6, 7, 8, 9]);
+        assert_eq!(circuit.bus_list().node_ref("b2", 1), Some(9));
+        // one phase given, the others and the neutral keep their defaults
+        let b = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        assert_eq!(b.ckt_base().terminal_refs(0), [9, 8, 7, 0]);
+        assert_eq!(b.ckt_base().terminal_refs(1), [10, 11, 12, 0]);
+    }
+
+    #[test]
+    fn test_currents_and_powers() {
+        let mut series = Series::new("a", "b1", "b2");
+        assert_eq!(series.ckt_base().y_order(), 6);
+        series.calc_yprim(60.0).unwrap();
+        for terminal in 0..2 {
+            let refs: Vec<usize> = (1..=3).map(|node| terminal * 3 + node).collect();
+            series.ckt_base_mut().set_node_refs(terminal, &refs);
+        }
+
+        // 2 V across each phase: 2 / (1+1j) = 1-1j flows in at terminal 1
+        let mut voltages = vec![Complex64::new(0.0, 0.0); 7];
+        for node in 1..=3 {
+            voltages[node] = Complex64::new(10.0, 0.0);
+            voltages[node + 3] = Complex64::new(8.0, 0.0);
+        }
+        let currents = series.get_currents(&voltages);
+        assert!((currents[0] - Complex64::new(1.0, -1.0)).norm() < 1e-12);
+        assert!((currents[3] + Complex64::new(1.0, -1.0)).norm() < 1e-12);
+
+        let powers = series.terminal_powers(&voltages);
+        assert!((powers[0] - Complex64::new(30.0, 30.0)).norm() < 1e-9);
+        assert!((powers[1] - Complex64::new(-24.0, -24.0)).norm() < 1e-9);
+        // losses: |I|^2 Z per phase
+        assert!((series.total_power(&voltages) - Complex64::new(6.0, 6.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_open_conductor() {
+        let mut series = Series::new("a", "b1", "b2");
+        series.ckt_base_mut().set_closed(1, Some(2), false);
+        assert!(!series.ckt_base().is_terminal_closed(1));
+        assert!(series.ckt_base().is_terminal_closed(0));
+        series.calc_yprim(60.0).unwrap();
+
+        // phase 3 carries nothing once its second terminal is open
+        let yprim = series.ckt_base().get_yprim().unwrap();
+        assert_eq!(yprim.get(2, 5), Complex64::new(0.0, 0.0));
+        assert!(yprim.get(5, 5).norm() < 1e-9);
+        assert!(yprim.get(2, 2).norm() < 1e-12);
+        assert!((yprim.get(0, 3) + Complex64::new(0.5, -0.5)).norm() < 1e-12);
+
+        series.ckt_base_mut().set_closed(1, None, true);
+        assert!(series.ckt_base().get_yprim().is_none());
+        assert!(series.ckt_base().is_closed(1, 2));
+    }
+}
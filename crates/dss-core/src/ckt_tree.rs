@@ -0,0 +1,402 @@
+// Topology of a radial circuit (Pascal TCktTree): the power delivery
+// elements reached through closed terminals from a starting branch or from
+// the sources, each with the branch it is fed from, and the power conversion
+// elements on the buses each branch feeds. The tree is traced breadth first,
+// so every branch comes after the one feeding it; shunt elements such as
+// capacitors are branches with nothing beyond. Meter zones, load allocation
+// and the Interpolate command walk it; elements the sources do not reach
+// are isolated.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::registry::ElementId;
+
+// Name of the bus of a bus spec, e.g. "b1" of "B1.1.2"
+pub(crate) fn bus_name(spec: &str) -> String {
+    spec.split('.').next().unwrap_or("").to_lowercase()
+}
+
+// A power delivery element of the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeBranch {
+    pub id: ElementId,
+    // Index of the branch feeding it; None for the branches at the root
+    pub parent: Option<usize>,
+    // Terminal toward the root, from 0
+    pub terminal: usize,
+    // Indices of the branches it feeds
+    pub children: Vec<usize>,
+    // Branches between it and the root
+    pub level: usize,
+    // Power conversion elements on the buses beyond it, in order of
+    // definition
+    pub shunts: Vec<ElementId>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CktTree {
+    branches: Vec<TreeBranch>,
+    index: HashMap<ElementId, usize>,
+    // Buses reached, the root buses included
+    buses: HashSet<String>,
+    // Power conversion elements on the root buses, fed by no branch
+    root_shunts: Vec<ElementId>,
+    // Branches reaching a bus the tree had already reached, closing a loop
+    loops: Vec<ElementId>,
+}
+
+// Terminals of the enabled circuit elements at each bus
+pub(crate) type BusTerminals = HashMap<String, Vec<(ElementId, usize)>>;
+
+pub(crate) fn bus_terminals(circuit: &Circuit) -> BusTerminals {
+    let mut at_bus = BusTerminals::new();
+    for (id, element) in circuit.elements().iter().enumerate() {
+        let Some(element) = element.as_ckt_element() else {
+            continue;
+        };
+        if !element.ckt_base().is_enabled() {
+            continue;
+        }
+        for terminal in 0..element.nterms() {
+            let bus = bus_name(element.get_bus(terminal));
+            at_bus.entry(bus).or_default().push((id, terminal));
+        }
+    }
+    at_bus
+}
+
+fn ckt(circuit: &Circuit, id: ElementId) -> Option<&dyn CktElement> {
+    circuit.element(id).and_then(|e| e.as_ckt_element())
+}
+
+impl CktTree {
+    // The tree beyond terminal `terminal` of branch `start`, as an
+    // EnergyMeter zone is: branches in `stops` and all beyond them are left
+    // out
+    pub fn from_branch(
+        circuit: &Circuit,
+        start: ElementId,
+        terminal: usize,
+        stops: &HashSet<ElementId>,
+    ) -> Self {
+        let mut tree = CktTree::default();
+        let Some(element) = ckt(circuit, start) else {
+            return tree;
+        };
+        tree.add_branch(start, None, terminal);
+        // the tree does not go back through the bus it is fed from
+        tree.buses.insert(bus_name(element.get_bus(terminal)));
+        let queue = (0..element.nterms())
+            .filter(|&t| t != terminal && element.ckt_base().is_terminal_closed(t))
+            .map(|t| (bus_name(element.get_bus(t)), Some(0)))
+            .collect();
+        tree.grow(circuit, queue, stops);
+        tree
+    }
+
+    // The tree beyond the buses of the circuit's voltage and current
+    // sources
+    pub fn from_sources(circuit: &Circuit) -> Self {
+        let mut tree = CktTree::default();
+        let queue = ["vsource", "isource"]
+            .iter()
+            .flat_map(|class| circuit.class_elements(class))
+            .filter_map(|&id| ckt(circuit, id))
+            .filter(|source| source.ckt_base().is_enabled() && source.nterms() > 0)
+            .map(|source| (bus_name(source.get_bus(0)), None))
+            .collect();
+        tree.grow(circuit, queue, &HashSet::new());
+        tree
+    }
+
+    fn add_branch(&mut self, id: ElementId, parent: Option<usize>, terminal: usize) -> usize {
+        let index = self.branches.len();
+        let level = parent.map_or(0, |parent| self.branches[parent].level + 1);
+        if let Some(parent) = parent {
+            self.branches[parent].children.push(index);
+        }
+        self.branches.push(TreeBranch {
+            id,
+            parent,
+            terminal,
+            children: Vec::new(),
+            level,
+            shunts: Vec::new(),
+        });
+        self.index.insert(id, index);
+        index
+    }
+
+    // Takes in the elements at each bus of the queue, with the branch
+    // feeding the bus, breadth first
+    fn grow(
+        &mut self,
+        circuit: &Circuit,
+        mut queue: VecDeque<(String, Option<usize>)>,
+        stops: &HashSet<ElementId>,
+    ) {
+        let at_bus = bus_terminals(circuit);
+        let mut taken: HashSet<ElementId> = self.index.keys().copied().collect();
+        let mut visited: HashSet<String> = HashSet::new();
+        // buses visited or waiting in the queue
+        let mut reached: HashSet<String> = queue
+            .iter()
+            .map(|(bus, _)| bus.clone())
+            .chain(self.buses.iter().cloned())
+            .collect();
+        while let Some((bus, feeder)) = queue.pop_front() {
+            if !visited.insert(bus.clone()) {
+                continue;
+            }
+            self.buses.insert(bus.clone());
+            for &(id, terminal) in at_bus.get(&bus).into_iter().flatten() {
+                let Some(element) = ckt(circuit, id) else {
+                    continue;
+                };
+                if taken.contains(&id) {
+                    continue;
+                }
+                if let Some(pd) = element.as_pd_element() {
+                    if stops.contains(&id) || !element.ckt_base().is_terminal_closed(terminal) {
+                        continue;
+                    }
+                    taken.insert(id);
+                    let branch = self.add_branch(id, feeder, terminal);
+                    if pd.is_shunt() {
+                        continue;
+                    }
+                    for next in (0..element.nterms()).filter(|&t| t != terminal) {
+                        if !element.ckt_base().is_terminal_closed(next) {
+                            continue;
+                        }
+                        let next = bus_name(element.get_bus(next));
+                        if reached.insert(next.clone()) {
+                            queue.push_back((next, Some(branch)));
+                        } else if next != bus {
+                            self.loops.push(id);
+                        }
+                    }
+                } else if element.as_pc_element().is_some() {
+                    taken.insert(id);
+                    match feeder {
+                        Some(feeder) => self.branches[feeder].shunts.push(id),
+                        None => self.root_shunts.push(id),
+                    }
+                }
+            }
+        }
+    }
+
+    // Branches in the order traced, each after the one feeding it
+    pub fn branches(&self) -> &[TreeBranch] {
+        &self.branches
+    }
+
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    // Index of a branch in the tree
+    pub fn find(&self, id: ElementId) -> Option<usize> {
+        self.index.get(&id).copied()
+    }
+
+    pub fn contains_bus(&self, bus: &str) -> bool {
+        self.buses.contains(&bus.to_lowercase())
+    }
+
+    // Power conversion elements on the root buses
+    pub fn root_shunts(&self) -> &[ElementId] {
+        &self.root_shunts
+    }
+
+    // Branches closing loops, each reaching a bus already fed by another
+    // path; the tree leaves out what is beyond them
+    pub fn loops(&self) -> &[ElementId] {
+        &self.loops
+    }
+
+    // The branch feeding a branch
+    pub fn parent(&self, id: ElementId) -> Option<ElementId> {
+        let parent = self.branches[self.find(id)?].parent?;
+        Some(self.branches[parent].id)
+    }
+
+    // The branches a branch feeds
+    pub fn children(&self, id: ElementId) -> Vec<ElementId> {
+        self.find(id).map_or_else(Vec::new, |index| {
+            self.branches[index]
+                .children
+                .iter()
+                .map(|&child| self.branches[child].id)
+                .collect()
+        })
+    }
+
+    // The branches from the one feeding a branch up to the root
+    pub fn upstream(&self, id: ElementId) -> Vec<ElementId> {
+        let mut path = Vec::new();
+        let mut next = self.find(id).and_then(|index| self.branches[index].parent);
+        while let Some(index) = next {
+            path.push(self.branches[index].id);
+            next = self.branches[index].parent;
+        }
+        path
+    }
+
+    // All branches below a branch, depth first
+    pub fn downstream(&self, id: ElementId) -> Vec<ElementId> {
+        let mut below = Vec::new();
+        let mut stack: Vec<usize> = self
+            .find(id)
+            .map_or_else(Vec::new, |index| self.branches[index].children.clone());
+        stack.reverse();
+        while let Some(index) = stack.pop() {
+            below.push(self.branches[index].id);
+            stack.extend(self.branches[index].children.iter().rev());
+        }
+        below
+    }
+
+    // Bus beyond a series branch, away from the root
+    fn far_bus(&self, circuit: &Circuit, index: usize) -> Option<String> {
+        let branch = &self.branches[index];
+        let element = ckt(circuit, branch.id)?;
+        if element.as_pd_element().is_some_and(|pd| pd.is_shunt()) {
+            return None;
+        }
+        (0..element.nterms())
+            .find(|&t| t != branch.terminal)
+            .map(|t| bus_name(element.get_bus(t)))
+    }
+
+    // Places the buses of the tree without coordinates evenly along the
+    // branches between the nearest buses up and down the tree that have
+    // them, following the first branch down (Pascal InterpolateCoordinates);
+    // buses with none below take the position of the bus above. The bus
+    // list must be made. Returns the number of buses placed.
+    pub fn interpolate_coords(&self, circuit: &mut Circuit) -> usize {
+        let coords = |circuit: &Circuit, bus: &str| {
+            let index = circuit.bus_list().find(bus)?;
+            circuit.buses()[index].get_coords()
+        };
+        let mut placed = 0;
+        for index in 0..self.branches.len() {
+            let Some(far) = self.far_bus(circuit, index) else {
+                continue;
+            };
+            let Some(bus) = circuit.bus_list().find(&far) else {
+                continue;
+            };
+            if circuit.buses()[bus].get_coords().is_some() {
+                continue;
+            }
+            let Some(element) = ckt(circuit, self.branches[index].id) else {
+                continue;
+            };
+            let near = bus_name(element.get_bus(self.branches[index].terminal));
+            let Some((x0, y0)) = coords(circuit, &near) else {
+                continue;
+            };
+
+            // down the first series branches to a bus with coordinates
+            let mut steps = 1.0;
+            let mut end = None;
+            let mut next = index;
+            while let Some(child) = self.branches[next]
+                .children
+                .iter()
+                .copied()
+                .find(|&child| self.far_bus(circuit, child).is_some())
+            {
+                steps += 1.0;
+                let below = self.far_bus(circuit, child).unwrap_or_default();
+                if let Some(found) = coords(circuit, &below) {
+                    end = Some(found);
+                    break;
+                }
+                next = child;
+            }
+            let (x, y) = match end {
+                Some((x1, y1)) => (x0 + (x1 - x0) / steps, y0 + (y1 - y0) / steps),
+                None => (x0, y0),
+            };
+            circuit.buses_mut()[bus].set_coords(x, y);
+            placed += 1;
+        }
+        placed
+    }
+}
+
+// Enabled circuit elements with none of their buses reached from the
+// sources (Pascal FindIsolatedElements)
+pub fn isolated_elements(circuit: &Circuit) -> Vec<ElementId> {
+    let tree = CktTree::from_sources(circuit);
+    circuit
+        .elements()
+        .iter()
+        .enumerate()
+        .filter(|(_, element)| {
+            element.as_ckt_element().is_some_and(|element| {
+                element.ckt_base().is_enabled()
+                    && element.nterms() > 0
+                    && (0..element.nterms())
+                        .all(|terminal| !tree.contains_bus(&bus_name(element.get_bus(terminal))))
+            })
+        })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::LineClass;
+    use crate::test_util::{add, edit};
+
+    // source - l1 - b - l2 - c, with l3 from b to d and a load on c
+    fn feeder() -> (Circuit, [ElementId; 5]) {
+        let mut circuit = Circuit::new("test");
+        add(&mut circuit, "vsource", "source", "bus1=a");
+        let l1 = add(&mut circuit, "line", "l1", "bus1=a bus2=b");
+        let l2 = add(&mut circuit, "line", "l2", "bus1=b bus2=c");
+        let l3 = add(&mut circuit, "line", "l3", "bus1=d bus2=b");
+        let load = add(&mut circuit, "load", "ld1", "bus1=c");
+        let cap = add(&mut circuit, "capacitor", "c1", "bus1=d");
+        (circuit, [l1, l2, l3, load, cap])
+    }
+
+    #[test]
+    fn test_tree() {
+        let (mut circuit, [l1, l2, l3, load, cap]) = feeder();
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(tree.len(), 4);
+        let l3_branch = &tree.branches()[tree.find(l3).unwrap()];
+        // l3 is fed from its second terminal
+        assert_eq!((l3_branch.terminal, l3_branch.level), (1, 1));
+        assert_eq!(tree.parent(l2), Some(l1));
+        assert_eq!(tree.children(l1), [l2, l3]);
+        assert_eq!(tree.upstream(cap), [l3, l1]);
+        assert_eq!(tree.downstream(l1), [l2, l3, cap]);
+        assert_eq!(tree.branches()[tree.find(l2).unwrap()].shunts, [load]);
+        assert_eq!(tree.root_shunts(), [0]);
+
+        // from l2 on, as a meter at l2 sees it
+        let tree = CktTree::from_branch(&circuit, l2, 0, &HashSet::new());
+        assert_eq!(tree.len(), 1);
+        assert!(tree.contains_bus("C"));
+        let tree = CktTree::from_branch(&circuit, l1, 0, &HashSet::from([l3]));
+        assert_eq!(tree.find(cap), None);
+
+        assert!(isolated_elements(&circuit).is_empty());
+        let line = circuit.element_mut(l2).unwrap();
+        edit(line, &LineClass, "enabled=no", &Circuit::new("other")).unwrap();
+        assert_eq!(isolated_elements(&circuit), [load]);
+    }
+}
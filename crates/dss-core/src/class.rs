@@ -0,0 +1,252 @@
+// DSS classes (Pascal TDSSClass): the property table and the edit loop that
+// reads property values from a command line into an object.
+
+use std::fmt;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::object::DssObject;
+use crate::property::{PropertyDef, find_property};
+
+pub trait DssClass: fmt::Debug + Sync {
+    fn name(&self) -> &'static str;
+
+    fn properties(&self) -> &'static [PropertyDef];
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject>;
+
+    fn find_property(&self, name: &str) -> Option<usize> {
+        find_property(self.properties(), name)
+    }
+
+    // Applies the remaining parameters of the parser's command line to
+    // `object`. A value given without a name goes to the property after the
+    // one set last, so "New Line.l1 b1 b2" sets bus1 and bus2. Objects
+    // referred to by name (line codes, shapes) are looked up in `circuit`.
+    fn edit(
+        &self,
+        object: &mut dyn DssObject,
+        parser: &mut DSSParser,
+        circuit: &Circuit,
+    ) -> DssResult<()> {
+        let mut next = 0;
+        loop {
+            let param_name = parser.next_param();
+            let value = parser.get_token().to_string();
+            if param_name.is_empty() && value.is_empty() {
+                break;
+            }
+            // like= copies another object of the class before the
+            // properties after it
+            if param_name.eq_ignore_ascii_case("like") {
+                let other = circuit
+                    .find_element(self.name(), &value)
+                    .and_then(|id| circuit.element(id))
+                    .ok_or_else(|| {
+                        DssError::new(
+                            codes::OBJECT_NOT_FOUND,
+                            &format!(
+                                "{}.{} not found for like= of \"{}\"",
+                                self.name(),
+                                value.to_lowercase(),
+                                object.full_name()
+                            ),
+                        )
+                    })?;
+                object.make_like(other, circuit)?;
+                continue;
+            }
+            let index = if param_name.is_empty() {
+                Some(next).filter(|&index| index < self.properties().len())
+            } else {
+                self.find_property(&param_name)
+            };
+            let Some(index) = index else {
+                let property = if param_name.is_empty() {
+                    format!("#{}", next + 1)
+                } else {
+                    param_name
+                };
+                return Err(DssError::new(
+                    codes::UNKNOWN_PROPERTY,
+                    &format!(
+                        "Unknown property \"{}\" for \"{}\"",
+                        property,
+                        object.full_name()
+                    ),
+                ));
+            };
+            next = index + 1;
+            object.set_property(index, parser)?;
+            object.resolve(index, circuit)?;
+            object.base_mut().set_value(index, &value);
+        }
+        object.recalc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::object::ObjectBase;
+    use crate::property::{PropertyKind, read_doubles};
+
+    const PROPERTIES: &[PropertyDef] = &[
+        PropertyDef {
+            name: "npts",
+            kind: PropertyKind::Integer,
+            default: "0",
+            help: "Number of points.",
+        },
+        PropertyDef {
+            name: "mult",
+            kind: PropertyKind::Doubles,
+            default: "",
+            help: "Multipliers.",
+        },
+        PropertyDef {
+            name: "mean",
+            kind: PropertyKind::Double,
+            default: "",
+            help: "Mean of the multipliers, computed.",
+        },
+    ];
+
+    #[derive(Debug, Clone)]
+    struct Shape {
+        base: ObjectBase,
+        npts: i32,
+        mult: Vec<f64>,
+    }
+
+    impl DssObject for Shape {
+        fn base(&self) -> &ObjectBase {
+            &self.base
+        }
+
+        fn base_mut(&mut self) -> &mut ObjectBase {
+            &mut self.base
+        }
+
+        fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+            match index {
+                0 => self.npts = parser.make_integer()?,
+                1 => self.mult = read_doubles(parser)?,
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn recalc(&mut self) -> DssResult<()> {
+            self.mult.resize(self.npts.max(0) as usize, 0.0);
+            Ok(())
+        }
+
+        fn clone_object(&self) -> Box<dyn DssObject> {
+            Box::new(self.clone())
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn get_property(&self, index: usize) -> String {
+            match index {
+                2 => (self.mult.iter().sum::<f64>() / self.mult.len() as f64).to_string(),
+                _ => self.base.get_value(index).to_string(),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ShapeClass;
+
+    impl DssClass for ShapeClass {
+        fn name(&self) -> &'static str {
+            "Shape"
+        }
+
+        fn properties(&self) -> &'static [PropertyDef] {
+            PROPERTIES
+        }
+
+        fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+            Box::new(Shape {
+                base: ObjectBase::new(self.name(), name, PROPERTIES),
+                npts: 0,
+                mult: Vec::new(),
+            })
+        }
+    }
+
+    fn edit(object: &mut dyn DssObject, line: &str) -> DssResult<()> {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string(line);
+        ShapeClass.edit(object, &mut parser, &Circuit::new("test"))
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut object = ShapeClass.new_object("Peak");
+        assert_eq!(object.full_name(), "Shape.peak");
+        edit(object.as_mut(), "mult=[1 2 3] np=4").unwrap();
+
+        let shape = object.as_any().downcast_ref::<Shape>().unwrap();
+        assert_eq!(shape.mult, [1.0, 2.0, 3.0, 0.0]);
+        assert_eq!(object.get_property_value("mean").as_deref(), Some("1.5"));
+        assert_eq!(
+            object.property_values(),
+            [
+                ("mult".to_string(), "1 2 3".to_string()),
+                ("npts".to_string(), "4".to_string())
+            ]
+        );
+
+        // positional values follow the property set last
+        edit(object.as_mut(), "npts=2 [5 5]").unwrap();
+        assert_eq!(object.get_property(1), "5 5");
+        assert_eq!(object.base().sequence(), [0, 1]);
+        let copy = object.clone();
+        assert_eq!(copy.get_property_value("mean").as_deref(), Some("5"));
+
+        let err = edit(object.as_mut(), "color=red").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_PROPERTY);
+        assert_eq!(
+            err.message(),
+            "Unknown property \"color\" for \"Shape.peak\""
+        );
+        assert!(edit(object.as_mut(), "npts=1 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_like() {
+        let mut circuit = Circuit::new("test");
+        let mut peak = ShapeClass.new_object("peak");
+        edit(peak.as_mut(), "mult=[1 2 3] npts=3").unwrap();
+        circuit.add_element(peak);
+
+        let mut parser = DSSParser::new();
+        let mut object = ShapeClass.new_object("base");
+        parser.set_cmd_string("npts=5 Like=Peak npts=2");
+        ShapeClass
+            .edit(object.as_mut(), &mut parser, &circuit)
+            .unwrap();
+        let shape = object.as_any().downcast_ref::<Shape>().unwrap();
+        assert_eq!(shape.mult, [1.0, 2.0]);
+        assert_eq!(object.to_script(), "New Shape.base mult=\"1 2 3\" npts=2");
+
+        parser.set_cmd_string("like=offpeak");
+        let err = ShapeClass
+            .edit(object.as_mut(), &mut parser, &circuit)
+            .unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+}
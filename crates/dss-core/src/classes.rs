@@ -0,0 +1,169 @@
+// The DSS classes, in OpenDSS registration order.
+
+mod cable_data;
+mod cap_control;
+mod capacitor;
+mod cn_data;
+mod energy_meter;
+mod exp_control;
+mod fault;
+mod fuse;
+mod gen_dispatcher;
+mod generator;
+mod growth_shape;
+mod inv_control;
+mod isource;
+mod line;
+mod line_code;
+mod line_geometry;
+mod line_spacing;
+mod load;
+mod load_shape;
+mod monitor;
+mod price_shape;
+mod pv_system;
+mod reactor;
+mod recloser;
+mod reg_control;
+mod relay;
+mod sensor;
+mod shape_data;
+mod spectrum;
+mod storage;
+mod storage_controller;
+mod swt_control;
+mod t_shape;
+mod tcc_curve;
+mod transformer;
+mod ts_data;
+mod vsource;
+mod wire_data;
+mod xfmr_code;
+mod xy_curve;
+
+pub use cable_data::CableData;
+pub use cap_control::{CapControl, CapControlClass, CapControlType};
+pub use capacitor::{Capacitor, CapacitorClass};
+pub use cn_data::{CNData, CNDataClass};
+pub use energy_meter::{
+    EnergyMeter, EnergyMeterClass, MeterAction, MeterZone, NUM_REGISTERS, REGISTER_NAMES,
+    ZoneBranch, allocate_loads,
+};
+pub use exp_control::{ExpControl, ExpControlClass};
+pub use fault::{Fault, FaultClass};
+pub use fuse::{Fuse, FuseClass};
+pub use gen_dispatcher::{GenDispatcher, GenDispatcherClass};
+pub use generator::{DispatchMode, Generator, GeneratorClass, MachineState};
+pub use growth_shape::{
+    DEFAULT_GROWTH_RATE, GrowthShape, GrowthShapeClass, apply_growth, growth_factor,
+};
+pub use inv_control::{
+    InvControl, InvControlClass, InvControlMode, RefReactivePower, VoltWattAxis, VoltageRef,
+};
+pub use isource::{Isource, IsourceClass, ScanType};
+pub use line::{Line, LineClass};
+pub use line_code::{LineCode, LineCodeClass};
+pub use line_geometry::{LineGeometry, LineGeometryClass};
+pub use line_spacing::{LineSpacing, LineSpacingClass};
+pub use load::{Load, LoadClass, LoadStatus};
+pub use load_shape::{
+    LoadShape, LoadShapeClass, RandomType, ShapeMode, apply_load_shapes, random_load_mults,
+};
+pub use monitor::{Monitor, MonitorAction, MonitorClass};
+pub use price_shape::{PriceShape, PriceShapeClass};
+pub use pv_system::{PVSystem, PVSystemClass};
+pub use reactor::{Reactor, ReactorClass};
+pub use recloser::{Recloser, RecloserClass};
+pub use reg_control::{RegControl, RegControlClass};
+pub use relay::{Relay, RelayClass, RelayType};
+pub use sensor::{Sensor, SensorClass};
+pub use shape_data::Interpolation;
+pub use spectrum::{Spectrum, SpectrumClass, find_spectrum};
+pub use storage::{Storage, StorageClass, StorageDispatch, StorageState, update_all_storage};
+pub use storage_controller::{
+    ChargeMode, DischargeMode, StorageController, StorageControllerClass,
+};
+pub use swt_control::{SwitchState, SwtControl, SwtControlClass};
+pub use t_shape::{TShape, TShapeClass};
+pub use tcc_curve::{TCCCurve, TCCCurveClass};
+pub use transformer::{Transformer, TransformerClass};
+pub use ts_data::{TSData, TSDataClass};
+pub use vsource::{Sequence, Vsource, VsourceClass};
+pub use wire_data::{ConductorData, WireData, WireDataClass};
+pub use xfmr_code::{XfmrCode, XfmrCodeClass};
+pub use xy_curve::{XYCurve, XYCurveClass, find_xy_curve};
+
+use crate::class::DssClass;
+
+static CLASSES: &[&dyn DssClass] = &[
+    &LineCodeClass,
+    &LoadShapeClass,
+    &TShapeClass,
+    &PriceShapeClass,
+    &XYCurveClass,
+    &GrowthShapeClass,
+    &TCCCurveClass,
+    &SpectrumClass,
+    &WireDataClass,
+    &CNDataClass,
+    &TSDataClass,
+    &LineGeometryClass,
+    &LineSpacingClass,
+    &XfmrCodeClass,
+    &LineClass,
+    &VsourceClass,
+    &IsourceClass,
+    &LoadClass,
+    &TransformerClass,
+    &RegControlClass,
+    &CapacitorClass,
+    &ReactorClass,
+    &CapControlClass,
+    &FaultClass,
+    &GeneratorClass,
+    &GenDispatcherClass,
+    &StorageClass,
+    &StorageControllerClass,
+    &RelayClass,
+    &RecloserClass,
+    &FuseClass,
+    &SwtControlClass,
+    &PVSystemClass,
+    &InvControlClass,
+    &ExpControlClass,
+    &MonitorClass,
+    &EnergyMeterClass,
+    &SensorClass,
+];
+
+pub fn classes() -> &'static [&'static dyn DssClass] {
+    CLASSES
+}
+
+pub fn class_names() -> impl Iterator<Item = &'static str> {
+    CLASSES.iter().map(|class| class.name())
+}
+
+// Class of a name, case-insensitive; class names cannot be abbreviated
+pub fn find_class(name: &str) -> Option<&'static dyn DssClass> {
+    CLASSES
+        .iter()
+        .find(|class| class.name().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_class() {
+        assert_eq!(find_class("line").map(|class| class.name()), Some("Line"));
+        assert_eq!(
+            find_class("TCC_CURVE").map(|class| class.name()),
+            Some("TCC_Curve")
+        );
+        assert!(find_class("lin").is_none());
+        assert_eq!(class_names().count(), classes().len());
+    }
+}
@@ -0,0 +1,134 @@
+// Data every cable has on top of its core conductor (Pascal TCableData):
+// the insulation around the core and the overall diameter. Dimensions are
+// in the units of the core's radius.
+
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::classes::wire_data::{CONDUCTOR_PROPERTIES, ConductorData};
+use crate::line_constants::EPSILON0;
+use crate::property::{PropertyDef, PropertyKind, concat_properties};
+
+// Properties every cable class has, after those of its own
+pub const CABLE_PROPERTIES: [PropertyDef; 15] = concat_properties(
+    &[
+        PropertyDef {
+            name: "epsr",
+            kind: PropertyKind::Double,
+            default: "2.3",
+            help: "Insulation layer relative permittivity.",
+        },
+        PropertyDef {
+            name: "inslayer",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Insulation layer thickness; same units as radius; no default.",
+        },
+        PropertyDef {
+            name: "diains",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Diameter over insulation layer; same units as radius; no default. Establishes inner diameter for capacitance calcs.",
+        },
+        PropertyDef {
+            name: "diacable",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Diameter over cable; same units as radius; no default.",
+        },
+    ],
+    &CONDUCTOR_PROPERTIES,
+);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CableData {
+    conductor: ConductorData,
+    eps_r: f64,
+    ins_layer: f64,
+    dia_ins: f64,
+    dia_cable: f64,
+}
+
+impl CableData {
+    pub fn new() -> Self {
+        CableData {
+            conductor: ConductorData::new(),
+            eps_r: 2.3,
+            ins_layer: -1.0,
+            dia_ins: -1.0,
+            dia_cable: -1.0,
+        }
+    }
+
+    pub fn conductor(&self) -> &ConductorData {
+        &self.conductor
+    }
+
+    // Takes property `index` of CABLE_PROPERTIES from the parser
+    pub fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match index {
+            0 => self.eps_r = parser.make_double()?,
+            1 => self.ins_layer = parser.make_double()?,
+            2 => self.dia_ins = parser.make_double()?,
+            3 => self.dia_cable = parser.make_double()?,
+            _ => return self.conductor.set_property(index - 4, parser),
+        }
+        Ok(())
+    }
+
+    // Fills in the insulation from the core radius and whichever of its
+    // thickness and diameter is given
+    pub fn fill_defaults(&mut self) {
+        self.conductor.fill_defaults();
+        let radius = self.conductor.radius_meters() / self.meters();
+        if self.ins_layer <= 0.0 && self.dia_ins > 0.0 {
+            self.ins_layer = self.dia_ins / 2.0 - radius;
+        }
+        if self.dia_ins <= 0.0 && self.ins_layer > 0.0 {
+            self.dia_ins = 2.0 * (radius + self.ins_layer);
+        }
+    }
+
+    // Meters per unit of the cable dimensions
+    pub fn meters(&self) -> f64 {
+        self.conductor.get_radius_units().to_meters()
+    }
+
+    pub fn dia_cable_meters(&self) -> f64 {
+        self.dia_cable * self.meters()
+    }
+
+    // The core and insulation data a line constants calculation needs
+    pub fn check(&self, name: &str) -> DssResult<()> {
+        self.conductor.check(name)?;
+        if self.ins_layer <= 0.0 || self.dia_ins <= 0.0 || self.dia_cable <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "The insulation and cable diameters of {} must be positive",
+                    name
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // Capacitance across the insulation, F per meter
+    pub fn insulation_capacitance(&self) -> f64 {
+        let outer = self.dia_ins / 2.0;
+        let inner = outer - self.ins_layer;
+        2.0 * PI * EPSILON0 * self.eps_r / (outer / inner).ln()
+    }
+
+    pub fn get_eps_r(&self) -> f64 {
+        self.eps_r
+    }
+}
+
+impl Default for CableData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
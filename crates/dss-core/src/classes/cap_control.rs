@@ -0,0 +1,720 @@
+// CapControl (Pascal TCapControl): switches the steps of a Capacitor by
+// what it sees at a terminal of another element: the voltage through a PT,
+// the current through a CT, the kvar, the power factor, or the time of day.
+// A step goes in when the quantity passes onsetting and out when it passes
+// offsetting, the gap between them the deadband, after delay (delayoff)
+// seconds for which the condition must hold. A step switched out stays out
+// for deadtime while the bank discharges, and with voltoverride the voltage
+// limits vmin and vmax switch steps whatever the control type.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::capacitor::Capacitor;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+use crate::registry::ElementId;
+
+const CAP_CONTROL_PROPERTIES: [PropertyDef; 17] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line or transformer, to which the capacitor control's PT and/or CT are connected.There is no default; must be specified.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the CapControl is connected. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "capacitor",
+        kind: PropertyKind::Object("Capacitor"),
+        default: "",
+        help: "Name of Capacitor element which the CapControl controls. No Default; Must be specified.Do not specify the full object name; \"Capacitor\" is assumed for the object class.  Example:Capacitor=cap1",
+    },
+    PropertyDef {
+        name: "type",
+        kind: PropertyKind::Choice(CapControlType::NAMES),
+        default: "current",
+        help: "{Current | voltage | kvar | PF | time } Control type.  Specify the ONsetting and OFFsetting appropriately with the type of control. (See help for ONsetting)",
+    },
+    PropertyDef {
+        name: "ptratio",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Ratio of the PT that converts the monitored voltage to the control voltage. Default is 60.  If the capacitor is Wye, the 1st phase line-to-neutral voltage is monitored.  Else, the line-to-line voltage (1st - 2nd phase) is monitored.",
+    },
+    PropertyDef {
+        name: "ctratio",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Ratio of the CT from line amps to control ampere setting for current and kvar control types. ",
+    },
+    PropertyDef {
+        name: "onsetting",
+        kind: PropertyKind::Double,
+        default: "300",
+        help: "Value at which the control arms to switch the capacitor ON (or ratchet up a step).  Type of Control:Current: Line Amps / CTratio; Voltage: Line-Neutral (or Line-Line for delta) Volts / PTratio; kvar:  Total kvar, all phases (3-phase for pos seq model). This is directional. ; PF:  Power Factor, Total power in monitored terminal. Negative for Leading. ; Time: Hrs from Midnight as a floating point number (decimal). 7:30am would be entered as 7.5.",
+    },
+    PropertyDef {
+        name: "offsetting",
+        kind: PropertyKind::Double,
+        default: "200",
+        help: "Value at which the control arms to switch the capacitor OFF. (See help for ONsetting)For Time control, is OK to have Off time the next day ( < On time)",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Time delay, in seconds, from when the control is armed before it sends out the switching command to turn ON.  The control may reset before the action actually occurs. This is used to determine which capacity control will act first. Default is 15.  You may specify any floating point number to achieve a model of whatever condition is necessary.",
+    },
+    PropertyDef {
+        name: "voltoverride",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No}  Default is No.  Switch to indicate whether VOLTAGE OVERRIDE is to be considered. Vmax and Vmin must be set to reasonable values if this property is Yes.",
+    },
+    PropertyDef {
+        name: "vmax",
+        kind: PropertyKind::Double,
+        default: "126",
+        help: "Maximum voltage, in volts.  If the voltage across the capacitor divided by the PTRATIO is greater than this voltage, the capacitor will switch OFF regardless of other control settings. Default is 126 (goes with a PT ratio of 60 for 12.47 kV system).",
+    },
+    PropertyDef {
+        name: "vmin",
+        kind: PropertyKind::Double,
+        default: "115",
+        help: "Minimum voltage, in volts.  If the voltage across the capacitor divided by the PTRATIO is less than this voltage, the capacitor will switch ON regardless of other control settings. Default is 115 (goes with a PT ratio of 60 for 12.47 kV system).",
+    },
+    PropertyDef {
+        name: "delayoff",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Time delay, in seconds, for control to turn OFF when present state is ON. Default is 15.",
+    },
+    PropertyDef {
+        name: "deadtime",
+        kind: PropertyKind::Double,
+        default: "300",
+        help: "Dead time after capacitor is turned OFF before it can be turned back ON. Default is 300 sec.",
+    },
+    PropertyDef {
+        name: "ctphase",
+        kind: PropertyKind::Text,
+        default: "1",
+        help: "Number of the phase being monitored for CURRENT control or one of {AVG | MAX | MIN}. Default = 1. If delta or L-L connection, enter the first or the two phases being monitored [1-2, 2-3, 3-1]. Must be less than the number of phases. Does not apply to kvar control which uses all phases by default.",
+    },
+    PropertyDef {
+        name: "ptphase",
+        kind: PropertyKind::Text,
+        default: "1",
+        help: "Number of the phase being monitored for VOLTAGE control or one of {AVG | MAX | MIN}. Default = 1. If delta or L-L connection, enter the first or the two phases being monitored [1-2, 2-3, 3-1]. Must be less than the number of phases. Does not apply to kvar control which uses all phases by default.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True* | No/False} Default is YES for CapControl. Log control actions to Eventlog.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 19] = concat_properties(&CAP_CONTROL_PROPERTIES, &CKT_PROPERTIES);
+
+// Codes of the queued actions
+const ACTION_CLOSE: i32 = 1;
+const ACTION_OPEN: i32 = 2;
+
+// What the control switches on (Pascal CapControlType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapControlType {
+    Current,
+    Voltage,
+    Kvar,
+    PowerFactor,
+    Time,
+}
+
+impl CapControlType {
+    pub const NAMES: &'static [&'static str] = &["current", "voltage", "kvar", "pf", "time"];
+
+    // Type of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "voltage" => CapControlType::Voltage,
+            "kvar" => CapControlType::Kvar,
+            "pf" => CapControlType::PowerFactor,
+            "time" => CapControlType::Time,
+            _ => CapControlType::Current,
+        }
+    }
+}
+
+// Which phases a PT or CT reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PhaseSelect {
+    // From 0
+    Phase(usize),
+    Average,
+    Max,
+    Min,
+}
+
+impl PhaseSelect {
+    fn parse(text: &str) -> DssResult<Self> {
+        match text.to_lowercase().as_str() {
+            "avg" => Ok(PhaseSelect::Average),
+            "max" => Ok(PhaseSelect::Max),
+            "min" => Ok(PhaseSelect::Min),
+            other => other
+                .parse::<usize>()
+                .ok()
+                .filter(|&phase| phase >= 1)
+                .map(|phase| PhaseSelect::Phase(phase - 1))
+                .ok_or_else(|| {
+                    DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!(
+                            "Invalid phase \"{}\"; expected a number, avg, max or min",
+                            text
+                        ),
+                    )
+                }),
+        }
+    }
+
+    // The magnitude read of the phase values
+    fn pick(self, values: &[f64]) -> f64 {
+        match self {
+            PhaseSelect::Phase(phase) => values.get(phase).copied().unwrap_or(0.0),
+            PhaseSelect::Average => values.iter().sum::<f64>() / values.len().max(1) as f64,
+            PhaseSelect::Max => values.iter().copied().fold(0.0, f64::max),
+            PhaseSelect::Min => values.iter().copied().reduce(f64::min).unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CapControlClass;
+
+#[derive(Debug, Clone)]
+pub struct CapControl {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    capacitor: String,
+    control_type: CapControlType,
+    pt_ratio: f64,
+    ct_ratio: f64,
+    on_setting: f64,
+    off_setting: f64,
+    delay: f64,
+    volt_override: bool,
+    vmax: f64,
+    vmin: f64,
+    delay_off: f64,
+    dead_time: f64,
+    ct_phase: PhaseSelect,
+    pt_phase: PhaseSelect,
+    event_log: bool,
+    // The queued action and its code
+    pending: Option<(usize, i32)>,
+    // Seconds of the solution time a step last went out
+    last_open: Option<f64>,
+}
+
+impl CapControl {
+    pub fn new(name: &str) -> Self {
+        CapControl {
+            base: ObjectBase::new("CapControl", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            capacitor: String::new(),
+            control_type: CapControlType::Current,
+            pt_ratio: 60.0,
+            ct_ratio: 60.0,
+            on_setting: 300.0,
+            off_setting: 200.0,
+            delay: 15.0,
+            volt_override: false,
+            vmax: 126.0,
+            vmin: 115.0,
+            delay_off: 15.0,
+            dead_time: 300.0,
+            ct_phase: PhaseSelect::Phase(0),
+            pt_phase: PhaseSelect::Phase(0),
+            event_log: true,
+            pending: None,
+            last_open: None,
+        }
+    }
+
+    pub fn get_capacitor(&self) -> &str {
+        &self.capacitor
+    }
+
+    pub fn get_type(&self) -> CapControlType {
+        self.control_type
+    }
+
+    pub fn get_on_setting(&self) -> f64 {
+        self.on_setting
+    }
+
+    pub fn get_off_setting(&self) -> f64 {
+        self.off_setting
+    }
+
+    // Whether to switch a step in (ACTION_CLOSE) or out (ACTION_OPEN) at
+    // the present reading, if either
+    fn wanted(&self, element: &dyn CktElement, voltages: &[Complex64], hour: f64) -> Option<i32> {
+        let first = (self.control.get_terminal() - 1) * element.nconds();
+        let nphases = element.nphases();
+        let v = element.terminal_voltages(voltages);
+        let volts: Vec<f64> = (0..nphases)
+            .filter_map(|phase| v.get(first + phase))
+            .map(|v| v.norm() / self.pt_ratio)
+            .collect();
+        let vcontrol = self.pt_phase.pick(&volts);
+        if self.volt_override {
+            if vcontrol > self.vmax {
+                return Some(ACTION_OPEN);
+            }
+            if vcontrol < self.vmin {
+                return Some(ACTION_CLOSE);
+            }
+        }
+        let (close, open) = match self.control_type {
+            CapControlType::Voltage => (vcontrol < self.on_setting, vcontrol > self.off_setting),
+            CapControlType::Current => {
+                let currents = element.get_currents(voltages);
+                let amps: Vec<f64> = (0..nphases)
+                    .filter_map(|phase| currents.get(first + phase))
+                    .map(|i| i.norm() / self.ct_ratio)
+                    .collect();
+                let amps = self.ct_phase.pick(&amps);
+                (amps > self.on_setting, amps < self.off_setting)
+            }
+            CapControlType::Kvar => {
+                let kvar = self.terminal_power(element, voltages).im / 1000.0;
+                (kvar > self.on_setting, kvar < self.off_setting)
+            }
+            CapControlType::PowerFactor => {
+                let pf = pf_value(self.terminal_power(element, voltages));
+                (
+                    pf_value_of(self.on_setting) > pf,
+                    pf_value_of(self.off_setting) < pf,
+                )
+            }
+            CapControlType::Time => {
+                let on = if self.on_setting <= self.off_setting {
+                    (self.on_setting..self.off_setting).contains(&hour)
+                } else {
+                    hour >= self.on_setting || hour < self.off_setting
+                };
+                (on, !on)
+            }
+        };
+        if close {
+            Some(ACTION_CLOSE)
+        } else if open {
+            Some(ACTION_OPEN)
+        } else {
+            None
+        }
+    }
+
+    fn terminal_power(&self, element: &dyn CktElement, voltages: &[Complex64]) -> Complex64 {
+        element
+            .terminal_powers(voltages)
+            .get(self.control.get_terminal() - 1)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn find_capacitor<'a>(&self, circuit: &'a Circuit) -> Option<(ElementId, &'a Capacitor)> {
+        let id = circuit.find_element("capacitor", &self.capacitor)?;
+        let capacitor = circuit.element(id)?.as_any().downcast_ref::<Capacitor>()?;
+        Some((id, capacitor))
+    }
+}
+
+// Power factor on a scale that rises through unity: lagging 0..1, leading
+// 1..2 (Pascal ConvertPFToPFRange2)
+fn pf_value(power: Complex64) -> f64 {
+    let s = power.norm();
+    if s == 0.0 {
+        return 1.0;
+    }
+    let pf = power.re.abs() / s;
+    if power.im < 0.0 { 2.0 - pf } else { pf }
+}
+
+// A setting on the same scale, negative meaning leading
+fn pf_value_of(setting: f64) -> f64 {
+    if setting < 0.0 {
+        2.0 + setting
+    } else {
+        setting
+    }
+}
+
+impl DssObject for CapControl {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = CAP_CONTROL_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - CAP_CONTROL_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => self.control.set_element(parser.get_token()),
+            "terminal" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "capacitor" => self.capacitor = parser.get_token().to_lowercase(),
+            "type" => {
+                let name = read_choice(parser, CapControlType::NAMES, "type")?;
+                self.control_type = CapControlType::from_name(name);
+            }
+            "ptratio" => self.pt_ratio = parser.make_double()?,
+            "ctratio" => self.ct_ratio = parser.make_double()?,
+            "onsetting" => self.on_setting = parser.make_double()?,
+            "offsetting" => self.off_setting = parser.make_double()?,
+            "delay" => self.delay = parser.make_double()?,
+            "voltoverride" => self.volt_override = interpret_yes_no(parser.get_token()),
+            "vmax" => self.vmax = parser.make_double()?,
+            "vmin" => self.vmin = parser.make_double()?,
+            "delayoff" => self.delay_off = parser.make_double()?,
+            "deadtime" => self.dead_time = parser.make_double()?,
+            "ctphase" => self.ct_phase = PhaseSelect::parse(parser.get_token())?,
+            "ptphase" => self.pt_phase = PhaseSelect::parse(parser.get_token())?,
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.pt_ratio <= 0.0 || self.ct_ratio <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs ptratio and ctratio > 0", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for CapControl {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for CapControl {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Arms the control when the reading calls for a step the bank can take,
+    // and disarms it when the reading comes back into the deadband
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some((_, capacitor)) = self.find_capacitor(circuit) else {
+            return;
+        };
+        let Some(element) = self
+            .control
+            .find_element(circuit)
+            .and_then(|element| circuit.element(element)?.as_ckt_element())
+        else {
+            return;
+        };
+        let (hour, sec) = queue.get_time();
+        let now = hour as f64 * 3600.0 + sec;
+        let in_service = capacitor.steps_in_service();
+        let wanted =
+            self.wanted(element, voltages, queue.hour_of_day())
+                .filter(|&code| match code {
+                    ACTION_CLOSE => {
+                        in_service < capacitor.num_steps()
+                            && self
+                                .last_open
+                                .is_none_or(|last| now - last >= self.dead_time)
+                    }
+                    _ => in_service > 0,
+                });
+        match (wanted, self.pending) {
+            (Some(code), Some((_, pending))) if code == pending => {}
+            (wanted, pending) => {
+                if let Some((handle, _)) = pending {
+                    queue.delete(handle);
+                }
+                self.pending = wanted.map(|code| {
+                    let delay = match code {
+                        ACTION_CLOSE => self.delay,
+                        _ => self.delay_off,
+                    };
+                    (queue.push(delay, code, 0, id), code)
+                });
+            }
+        }
+    }
+
+    // Switches a step in or out
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        self.pending = None;
+        let Some((id, _)) = self.find_capacitor(circuit) else {
+            return;
+        };
+        let Some(capacitor) = circuit
+            .element_mut(id)
+            .and_then(|element| element.as_any_mut().downcast_mut::<Capacitor>())
+        else {
+            return;
+        };
+        let switched = match action.code {
+            ACTION_CLOSE => capacitor.add_step(),
+            ACTION_OPEN => capacitor.subtract_step(),
+            _ => false,
+        };
+        if !switched {
+            return;
+        }
+        let in_service = capacitor.steps_in_service();
+        if action.code == ACTION_OPEN {
+            let (hour, sec) = queue.get_time();
+            self.last_open = Some(hour as f64 * 3600.0 + sec);
+        }
+        if self.event_log {
+            let what = match action.code {
+                ACTION_CLOSE => "Step up",
+                _ => "Step down",
+            };
+            queue.log(
+                &self.full_name(),
+                &format!("{}, {} steps in service", what, in_service),
+            );
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.pending = None;
+        self.last_open = None;
+    }
+}
+
+impl DssClass for CapControlClass {
+    fn name(&self) -> &'static str {
+        "CapControl"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(CapControl::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::capacitor::CapacitorClass;
+    use crate::classes::load::{Load, LoadClass};
+    use crate::test_util::{balanced_nodes, edited, placed};
+
+    // A circuit with a 3-phase load of `load` on nodes 1-3 and a two-step
+    // capacitor on the same nodes with every step out
+    fn circuit_with_bank(load: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let feeder = edited(Load::new("feeder"), &LoadClass, load, &circuit).unwrap();
+        circuit.add_element(Box::new(placed(feeder, &[&[1, 2, 3, 0]]).unwrap()));
+        let capacitor = edited(
+            Capacitor::new("c1"),
+            &CapacitorClass,
+            "numsteps=2 kvar=[300 300] states=[0 0]",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(capacitor, &[&[1, 2, 3]]).unwrap()));
+        circuit
+    }
+
+    fn new_control(properties: &str, circuit: &Circuit) -> CapControl {
+        edited(
+            CapControl::new("cc"),
+            &CapControlClass,
+            &format!("element=load.feeder capacitor=c1 {}", properties),
+            circuit,
+        )
+        .unwrap()
+    }
+
+    fn balanced(vpu: f64) -> Vec<Complex64> {
+        balanced_nodes(12470.0 / 3.0_f64.sqrt() * vpu)
+    }
+
+    fn steps(circuit: &Circuit) -> usize {
+        circuit
+            .find_object_as::<Capacitor>("capacitor", "c1")
+            .unwrap()
+            .steps_in_service()
+    }
+
+    // Moves the queue on to `sec` and carries out what has come due
+    fn run_to(control: &mut CapControl, circuit: &mut Circuit, queue: &mut ControlQueue, sec: f64) {
+        queue.set_time(0, sec);
+        while let Some(action) = queue.pop_due() {
+            control.do_pending_action(&action, circuit, &[], queue);
+        }
+    }
+
+    #[test]
+    fn test_kvar_control() {
+        let mut circuit = circuit_with_bank("kw=1000 kvar=600");
+        let mut control = new_control("type=kvar onsetting=500 offsetting=-100 delay=30", &circuit);
+        let mut queue = ControlQueue::new();
+        control.sample(99, &circuit, &balanced(1.0), &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 29.0);
+        assert_eq!(steps(&circuit), 0);
+        run_to(&mut control, &mut circuit, &mut queue, 30.0);
+        assert_eq!(steps(&circuit), 1);
+        // still armed on the next sample, one step at a time
+        control.sample(99, &circuit, &balanced(1.0), &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 60.0);
+        assert_eq!(steps(&circuit), 2);
+        control.sample(99, &circuit, &balanced(1.0), &mut queue);
+        assert!(queue.is_empty());
+        assert_eq!(queue.events().len(), 2);
+
+        // within the deadband the armed control resets
+        let circuit = circuit_with_bank("kw=1000 kvar=200");
+        let mut control = new_control("type=kvar onsetting=500 offsetting=-100", &circuit);
+        control.sample(99, &circuit, &balanced(1.0), &mut queue);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_voltage_and_dead_time() {
+        let mut circuit = circuit_with_bank("kw=1000");
+        let mut control = new_control(
+            "type=voltage ptratio=60 onsetting=118 offsetting=124 delay=10 delayoff=5 deadtime=300",
+            &circuit,
+        );
+        let mut queue = ControlQueue::new();
+        // 0.95 pu is 114 V on the PT
+        control.sample(99, &circuit, &balanced(0.95), &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 10.0);
+        assert_eq!(steps(&circuit), 1);
+
+        control.sample(99, &circuit, &balanced(1.05), &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 15.0);
+        assert_eq!(steps(&circuit), 0);
+
+        // low again, but the bank is still discharging
+        control.sample(99, &circuit, &balanced(0.95), &mut queue);
+        assert!(queue.is_empty());
+        run_to(&mut control, &mut circuit, &mut queue, 315.0);
+        control.sample(99, &circuit, &balanced(0.95), &mut queue);
+        assert_eq!(queue.len(), 1);
+
+        // the override opens the bank on high voltage whatever the type
+        let control = new_control(
+            "type=time onsetting=8 offsetting=20 voltoverride=yes vmax=126",
+            &circuit,
+        );
+        let load = circuit
+            .element(circuit.find_element("load", "feeder").unwrap())
+            .unwrap();
+        let load = load.as_ckt_element().unwrap();
+        assert_eq!(
+            control.wanted(load, &balanced(1.0), 12.0),
+            Some(ACTION_CLOSE)
+        );
+        assert_eq!(
+            control.wanted(load, &balanced(1.1), 12.0),
+            Some(ACTION_OPEN)
+        );
+        assert_eq!(
+            control.wanted(load, &balanced(1.0), 21.0),
+            Some(ACTION_OPEN)
+        );
+        assert!((pf_value(Complex64::new(0.8, -0.6)) - 1.2).abs() < 1e-12);
+        assert!((pf_value_of(-0.95) - 1.05).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,708 @@
+// Capacitor (Pascal TCapacitor): a capacitor bank of one or more steps, each
+// with its own rating and optional series R and XL, switched in and out by
+// its states. A shunt bank has its second terminal on bus1 with the nodes
+// grounded; given a bus2 of its own it is a series capacitor. Delta banks
+// have a single terminal with the steps between phases. The ratings come
+// from kvar, cuf or a whole capacitance matrix, whichever was given last.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::bus::parse_bus_spec;
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::classes::line::read_matrix;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, read_choice, read_doubles};
+
+const CAPACITOR_PROPERTIES: [PropertyDef; 13] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of first bus of 2-terminal capacitor. Examples: bus1=busname bus1=busname.1.2.3 If only one bus specified, Bus2 will default to this bus, Node 0, and the capacitor will be a Yg shunt bank.",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of 2nd bus. Defaults to all phases connected to first bus, node 0, (Shunt Wye Connection) except when Bus2 explicitly specified. Not necessary to specify for delta (LL) connection.",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Doubles,
+        default: "[1200]",
+        help: "Total kvar, if one step, or ARRAY of kvar ratings for each step.  Evenly divided among phases. See rules for NUMSTEPS.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "For 2, 3-phase, kV phase-phase. Otherwise specify actual can rating.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye | delta |LN |LL}  Default is wye, which is equivalent to LN",
+    },
+    PropertyDef {
+        name: "cmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Nodal cap. matrix, lower triangle, microfarads, of the following form: cmatrix=\"c11 | -c21 c22 | -c31 -c32 c33\" All steps are assumed the same if this property is used.",
+    },
+    PropertyDef {
+        name: "cuf",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "ARRAY of Capacitance, each phase, for each step, microfarads. See Rules for NumSteps.",
+    },
+    PropertyDef {
+        name: "r",
+        kind: PropertyKind::Doubles,
+        default: "[0]",
+        help: "ARRAY of series resistance in each phase (line), ohms. Default is 0.0",
+    },
+    PropertyDef {
+        name: "xl",
+        kind: PropertyKind::Doubles,
+        default: "[0]",
+        help: "ARRAY of series inductive reactance(s) in each phase (line) for filter, ohms at base frequency. Use this OR \"h\" property to define filter. Default is 0.0.",
+    },
+    PropertyDef {
+        name: "harm",
+        kind: PropertyKind::Doubles,
+        default: "[0]",
+        help: "ARRAY of harmonics to which each step is tuned. Zero is interpreted as meaning zero reactance (no filter). Default is zero.",
+    },
+    PropertyDef {
+        name: "numsteps",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of steps in this capacitor bank. Default = 1. Forces reallocation of the capacitance, reactor, and states array.  Rules: If this property was previously =1, the value in the kvar property is divided equally among the steps. The kvar property does not need to be reset if that is accurate.  If the Cuf or Cmatrix property was used previously, all steps are set to the value of the first step. The states property is set to all steps on. All filter steps are set to the same harmonic. If this property was previously >1, the arrays are reallocated, but no values are altered. You must SUBSEQUENTLY assign all array properties.",
+    },
+    PropertyDef {
+        name: "states",
+        kind: PropertyKind::Doubles,
+        default: "[1]",
+        help: "ARRAY of integers {1|0} states representing the state of each step (on|off). Defaults to 1 when reallocated (on). Capcontrol will modify this array as it turns steps on or off.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 20] = concat_properties(&CAPACITOR_PROPERTIES, &PD_PROPERTIES);
+
+// Which properties the capacitance was last given by (Pascal SpecType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CapSpec {
+    Kvar,
+    Cuf,
+    Cmatrix,
+}
+
+// One step of the bank, each phase of it
+#[derive(Debug, Clone)]
+struct Step {
+    kvar: f64,
+    // Capacitance per phase, μF
+    cuf: f64,
+    r: f64,
+    xl: f64,
+    harm: f64,
+    on: bool,
+}
+
+#[derive(Debug)]
+pub struct CapacitorClass;
+
+#[derive(Debug, Clone)]
+pub struct Capacitor {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pd: PdElementBase,
+    steps: Vec<Step>,
+    kv: f64,
+    connection: Connection,
+    // Capacitance matrix, μF
+    cmatrix: Option<CMatrix>,
+    spec: CapSpec,
+    bus2_given: bool,
+    // kvar or kv changed and the ratings follow unless given
+    ratings_stale: bool,
+}
+
+impl Capacitor {
+    pub fn new(name: &str) -> Self {
+        let mut capacitor = Capacitor {
+            base: ObjectBase::new("Capacitor", name, &PROPERTIES),
+            ckt: CktElementBase::new(3, 2),
+            pd: PdElementBase::new(),
+            steps: vec![Step {
+                kvar: 1200.0,
+                cuf: 0.0,
+                r: 0.0,
+                xl: 0.0,
+                harm: 0.0,
+                on: true,
+            }],
+            kv: 12.47,
+            connection: Connection::Wye,
+            cmatrix: None,
+            spec: CapSpec::Kvar,
+            bus2_given: false,
+            ratings_stale: true,
+        };
+        capacitor.set_capacitances();
+        capacitor.set_ratings();
+        capacitor
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn num_steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    // kvar of each step
+    pub fn get_kvars(&self) -> Vec<f64> {
+        self.steps.iter().map(|step| step.kvar).collect()
+    }
+
+    // Rated kvar of the steps in service
+    pub fn kvar_in_service(&self) -> f64 {
+        self.steps
+            .iter()
+            .filter(|step| step.on)
+            .map(|step| step.kvar)
+            .sum()
+    }
+
+    pub fn get_states(&self) -> Vec<bool> {
+        self.steps.iter().map(|step| step.on).collect()
+    }
+
+    // Switches a step; Yprim is rebuilt before the next solution
+    pub fn set_state(&mut self, step: usize, on: bool) {
+        if let Some(step) = self.steps.get_mut(step)
+            && step.on != on
+        {
+            step.on = on;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    // Number of steps in service, counted from the first (Pascal
+    // LastStepInService)
+    pub fn steps_in_service(&self) -> usize {
+        self.steps.iter().filter(|step| step.on).count()
+    }
+
+    // Switches in the next step; false if all are in (Pascal AddStep)
+    pub fn add_step(&mut self) -> bool {
+        let count = self.steps_in_service();
+        if count >= self.steps.len() {
+            return false;
+        }
+        self.set_steps_in_service(count + 1);
+        true
+    }
+
+    // Switches out the last step in service; false if none is (Pascal
+    // SubtractStep)
+    pub fn subtract_step(&mut self) -> bool {
+        let count = self.steps_in_service();
+        if count == 0 {
+            return false;
+        }
+        self.set_steps_in_service(count - 1);
+        true
+    }
+
+    // The first `count` steps in service, the rest out
+    pub fn set_steps_in_service(&mut self, count: usize) {
+        for step in 0..self.steps.len() {
+            self.set_state(step, step < count);
+        }
+    }
+
+    // Voltage across each phase of the bank at rated kV
+    fn phase_volts(&self) -> f64 {
+        if self.connection == Connection::Wye && self.ckt.nphases() > 1 {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+
+    // Capacitance of every step from its kvar, or kvar from capacitance
+    fn set_capacitances(&mut self) {
+        let omega = 2.0 * PI * self.ckt.get_base_frequency();
+        let volts = self.phase_volts();
+        let nphases = self.ckt.nphases() as f64;
+        for step in &mut self.steps {
+            match self.spec {
+                CapSpec::Kvar => {
+                    step.cuf = step.kvar * 1000.0 / nphases / (omega * volts * volts) * 1e6
+                }
+                CapSpec::Cuf => {
+                    step.kvar = omega * step.cuf * 1e-6 * volts * volts * nphases / 1000.0
+                }
+                CapSpec::Cmatrix => {}
+            }
+        }
+    }
+
+    // Normal and emergency ratings at 135% and 180% of the rated current
+    fn set_ratings(&mut self) {
+        let kvar: f64 = self.steps.iter().map(|step| step.kvar).sum();
+        let amps = if self.ckt.nphases() > 1 {
+            kvar / (3.0_f64.sqrt() * self.kv)
+        } else {
+            kvar / self.kv
+        };
+        self.pd.set_norm_amps(amps * 1.35);
+        self.pd.set_emerg_amps(amps * 1.8);
+    }
+
+    // Reallocates the steps by the rules of numsteps=
+    fn set_num_steps(&mut self, count: usize) {
+        let count = count.max(1);
+        if count == self.steps.len() {
+            return;
+        }
+        if self.steps.len() == 1 {
+            let mut step = self.steps[0].clone();
+            if self.spec == CapSpec::Kvar {
+                step.kvar /= count as f64;
+            }
+            self.steps = vec![step; count];
+        } else {
+            let last = self.steps[self.steps.len() - 1].clone();
+            self.steps.resize(count, last);
+        }
+        for step in &mut self.steps {
+            step.on = true;
+        }
+    }
+
+    // Values of an array property, one per step at most
+    fn read_step_values(&self, parser: &mut DSSParser) -> DssResult<Vec<f64>> {
+        let mut values = read_doubles(parser)?;
+        values.truncate(self.steps.len());
+        Ok(values)
+    }
+
+    // Series admittance of a step at the frequency
+    fn step_admittance(&self, step: &Step, frequency: f64) -> Complex64 {
+        let omega = 2.0 * PI * frequency;
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let c = step.cuf * 1e-6;
+        let xl = if step.harm > 0.0 {
+            // tuned to the harmonic at base frequency
+            let omega0 = 2.0 * PI * self.ckt.get_base_frequency();
+            1.0 / (omega0 * c) / (step.harm * step.harm)
+        } else {
+            step.xl
+        };
+        if step.r == 0.0 && xl == 0.0 {
+            return Complex64::new(0.0, omega * c);
+        }
+        Complex64::new(step.r, xl * freq_mult - 1.0 / (omega * c)).inv()
+    }
+
+    // Bus2 follows bus1 with its nodes grounded unless given
+    fn set_default_bus2(&mut self) {
+        if self.bus2_given || self.connection == Connection::Delta {
+            return;
+        }
+        let bus1 = self.ckt.get_bus(0);
+        let name = bus1.split('.').next().unwrap_or("").to_string();
+        if !name.is_empty() {
+            let bus2 = format!("{}{}", name, ".0".repeat(self.ckt.nphases()));
+            self.ckt.set_bus(1, &bus2);
+        }
+    }
+
+    // Terminals and conductors for the connection
+    fn set_connection(&mut self, connection: Connection) {
+        self.connection = connection;
+        let nphases = self.ckt.nphases();
+        match connection {
+            Connection::Delta => {
+                self.ckt.set_terminals(1);
+                self.ckt
+                    .set_conductors(if nphases == 1 { 2 } else { nphases });
+            }
+            Connection::Wye => {
+                self.ckt.set_terminals(2);
+                self.ckt.set_conductors(nphases);
+            }
+        }
+    }
+}
+
+impl DssObject for Capacitor {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = CAPACITOR_PROPERTIES.get(index) else {
+            let pd_index = index - CAPACITOR_PROPERTIES.len();
+            if pd_index < 2 {
+                self.ratings_stale = false;
+            }
+            return self.set_pd_property(pd_index, parser);
+        };
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "bus2" => {
+                self.ckt.set_bus(1, parser.get_token());
+                self.bus2_given = true;
+            }
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_connection(self.connection);
+                self.cmatrix = None;
+                if self.spec == CapSpec::Cmatrix {
+                    self.spec = CapSpec::Kvar;
+                }
+            }
+            "kvar" => {
+                let values = self.read_step_values(parser)?;
+                for (step, kvar) in self.steps.iter_mut().zip(values) {
+                    step.kvar = kvar;
+                }
+                self.spec = CapSpec::Kvar;
+                self.ratings_stale = true;
+            }
+            "kv" => {
+                self.kv = parser.make_double()?;
+                self.ratings_stale = true;
+            }
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.set_connection(Connection::from_name(name));
+            }
+            "cmatrix" => {
+                let mut cmatrix = CMatrix::new(self.ckt.nphases());
+                read_matrix(parser, &mut cmatrix, 1.0, false)?;
+                self.cmatrix = Some(cmatrix);
+                self.spec = CapSpec::Cmatrix;
+            }
+            "cuf" => {
+                let values = self.read_step_values(parser)?;
+                for (step, cuf) in self.steps.iter_mut().zip(values) {
+                    step.cuf = cuf;
+                }
+                self.spec = CapSpec::Cuf;
+                self.ratings_stale = true;
+            }
+            "r" | "xl" | "harm" => {
+                let values = self.read_step_values(parser)?;
+                for (step, value) in self.steps.iter_mut().zip(values) {
+                    match property.name {
+                        "r" => step.r = value,
+                        "xl" => step.xl = value,
+                        _ => step.harm = value,
+                    }
+                }
+            }
+            "numsteps" => {
+                let count = parser.make_integer()?.max(1) as usize;
+                self.set_num_steps(count);
+            }
+            "states" => {
+                let values = self.read_step_values(parser)?;
+                for (step, state) in self.steps.iter_mut().zip(values) {
+                    step.on = state != 0.0;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.kv <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs kv > 0", self.full_name()),
+            ));
+        }
+        if self.spec != CapSpec::Cmatrix
+            && let Some(step) = self.steps.iter().position(|step| {
+                (self.spec == CapSpec::Kvar && step.kvar <= 0.0)
+                    || (self.spec == CapSpec::Cuf && step.cuf <= 0.0)
+            })
+        {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Step {} of {} has no capacitance",
+                    step + 1,
+                    self.full_name()
+                ),
+            ));
+        }
+        self.set_capacitances();
+        if self.ratings_stale {
+            self.set_ratings();
+            self.ratings_stale = false;
+        }
+        self.set_default_bus2();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Capacitor {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        Some(self)
+    }
+
+    // The steps in service in parallel in each phase, or the capacitance
+    // matrix, placed between the two terminals or, for delta, between the
+    // phases of the one terminal
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nphases = self.ckt.nphases();
+        let mut y = CMatrix::new(nphases);
+        if let (CapSpec::Cmatrix, Some(cmatrix)) = (self.spec, &self.cmatrix) {
+            if self.steps.iter().any(|step| step.on) {
+                let omega = 2.0 * PI * frequency;
+                for i in 0..nphases {
+                    for j in 0..nphases {
+                        let c = cmatrix.get(i, j).re * 1e-6;
+                        y.set(i, j, Complex64::new(0.0, omega * c));
+                    }
+                }
+            }
+        } else {
+            let y_phase: Complex64 = self
+                .steps
+                .iter()
+                .filter(|step| step.on)
+                .map(|step| self.step_admittance(step, frequency))
+                .sum();
+            for phase in 0..nphases {
+                y.set(phase, phase, y_phase);
+            }
+        }
+
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        match self.connection {
+            Connection::Wye => {
+                for i in 0..nphases {
+                    for j in 0..nphases {
+                        let value = y.get(i, j);
+                        yprim.set(i, j, value);
+                        yprim.set(i + nphases, j + nphases, value);
+                        yprim.set(i, j + nphases, -value);
+                        yprim.set(i + nphases, j, -value);
+                    }
+                }
+            }
+            Connection::Delta if self.cmatrix.is_some() && self.spec == CapSpec::Cmatrix => {
+                for i in 0..nphases {
+                    for j in 0..nphases {
+                        yprim.set(i, j, y.get(i, j));
+                    }
+                }
+            }
+            Connection::Delta => {
+                for phase in 0..nphases {
+                    let (a, b) = if nphases == 1 {
+                        (0, 1)
+                    } else {
+                        (phase, (phase + 1) % nphases)
+                    };
+                    let value = y.get(phase, phase);
+                    yprim.add(a, a, value);
+                    yprim.add(b, b, value);
+                    yprim.add(a, b, -value);
+                    yprim.add(b, a, -value);
+                }
+            }
+        }
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+}
+
+impl PdElement for Capacitor {
+    fn pd_base(&self) -> &PdElementBase {
+        &self.pd
+    }
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase {
+        &mut self.pd
+    }
+
+    // A delta bank or one whose second terminal is all grounded
+    fn is_shunt(&self) -> bool {
+        if self.connection == Connection::Delta {
+            return true;
+        }
+        parse_bus_spec(self.ckt.get_bus(1), self.ckt.nphases() as u32)
+            .map(|(_, nodes)| nodes.iter().all(|&node| node == 0))
+            .unwrap_or(true)
+    }
+}
+
+impl DssClass for CapacitorClass {
+    fn name(&self) -> &'static str {
+        "Capacitor"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Capacitor::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close_rel, balanced_nodes, edited, first_nodes, placed};
+
+    fn new_capacitor(properties: &str) -> DssResult<Capacitor> {
+        let capacitor = edited(
+            Capacitor::new("c1"),
+            &CapacitorClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        let nodes = first_nodes(capacitor.nconds());
+        placed(capacitor, &[&nodes])
+    }
+
+    #[test]
+    fn test_rating() {
+        // 12.47 kV line to line
+        let nodes = balanced_nodes(12470.0 / 3.0_f64.sqrt());
+        let capacitor = new_capacitor("bus1=b kvar=1200 kv=12.47").unwrap();
+        assert_eq!(capacitor.get_bus(1), "b.0.0.0");
+        assert!(capacitor.is_shunt());
+        // the bank delivers its rating at rated voltage
+        let power = capacitor.total_power(&nodes);
+        assert_close_rel(power, Complex64::new(0.0, -1.2e6), 1e-6);
+        let amps = 1200.0 / (3.0_f64.sqrt() * 12.47);
+        assert!((capacitor.pd_base().get_norm_amps() - 1.35 * amps).abs() < 1e-9);
+
+        let delta = new_capacitor("bus1=b kvar=1200 conn=delta").unwrap();
+        assert_eq!((delta.nterms(), delta.nconds()), (1, 3));
+        assert_close_rel(delta.total_power(&nodes), Complex64::new(0.0, -1.2e6), 1e-6);
+
+        let cuf = new_capacitor("phases=1 kv=7.2 cuf=[51.17]").unwrap();
+        let kvar = 2.0 * PI * 60.0 * 51.17e-6 * 7200.0 * 7200.0 / 1000.0;
+        assert!((cuf.get_kvars()[0] - kvar).abs() < 1e-9);
+
+        assert!(new_capacitor("kvar=[0]").is_err());
+    }
+
+    #[test]
+    fn test_steps() {
+        // 12.47 kV line to line
+        let nodes = balanced_nodes(12470.0 / 3.0_f64.sqrt());
+        let mut capacitor = new_capacitor("kvar=1200 numsteps=3 states=[1 0 0]").unwrap();
+        assert_eq!(capacitor.get_kvars(), [400.0, 400.0, 400.0]);
+        assert_eq!(capacitor.kvar_in_service(), 400.0);
+        assert_close_rel(
+            capacitor.total_power(&nodes),
+            Complex64::new(0.0, -4e5),
+            1e-6,
+        );
+
+        assert!(capacitor.add_step());
+        assert!(capacitor.ckt_base().get_yprim().is_none());
+        capacitor.calc_yprim(60.0).unwrap();
+        assert_close_rel(
+            capacitor.total_power(&nodes),
+            Complex64::new(0.0, -8e5),
+            1e-6,
+        );
+        assert!(capacitor.add_step());
+        assert!(!capacitor.add_step());
+        capacitor.set_steps_in_service(0);
+        assert!(!capacitor.subtract_step());
+        capacitor.calc_yprim(60.0).unwrap();
+        assert!(capacitor.total_power(&nodes).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_filter_and_series() {
+        // tuned to the 5th, the filter is only its resistance there
+        let mut filter = new_capacitor("kvar=600 harm=5 r=0.5").unwrap();
+        filter.calc_yprim(300.0).unwrap();
+        let y = filter.ckt_base().get_yprim().unwrap().get(0, 0);
+        assert_close_rel(y, Complex64::new(2.0, 0.0), 1e-6);
+
+        let series = new_capacitor("bus1=a bus2=b kvar=600").unwrap();
+        assert!(!series.is_shunt());
+        let y = series.ckt_base().get_yprim().unwrap();
+        assert_eq!(y.get(0, 3), -y.get(0, 0));
+
+        let matrix = new_capacitor("phases=2 cmatrix=[10 | -2 10]").unwrap();
+        let y = matrix.ckt_base().get_yprim().unwrap();
+        assert_close_rel(
+            y.get(0, 1),
+            Complex64::new(0.0, -2.0 * PI * 60.0 * 2e-6),
+            1e-6,
+        );
+    }
+}
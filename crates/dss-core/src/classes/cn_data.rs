@@ -0,0 +1,165 @@
+// CNData (Pascal TCNData): a concentric neutral cable, the core surrounded by
+// insulation and a ring of neutral strands. The strands act as one
+// equivalent conductor at the radius of the ring.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::class::DssClass;
+use crate::classes::cable_data::{CABLE_PROPERTIES, CableData};
+use crate::line_constants::{Conductor, EPSILON0, Shield};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties};
+
+static PROPERTIES: [PropertyDef; 19] = concat_properties(
+    &[
+        PropertyDef {
+            name: "k",
+            kind: PropertyKind::Integer,
+            default: "2",
+            help: "Number of concentric neutral strands.",
+        },
+        PropertyDef {
+            name: "diastrand",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Diameter of a concentric neutral strand; same units as core conductor radius; no default.",
+        },
+        PropertyDef {
+            name: "gmrstrand",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Geometric mean radius of a concentric neutral strand; same units as core conductor GMR; defaults to 0.7788 * CN strand radius.",
+        },
+        PropertyDef {
+            name: "rstrand",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "AC resistance of a concentric neutral strand; same units as core conductor resistance; no default.",
+        },
+    ],
+    &CABLE_PROPERTIES,
+);
+
+#[derive(Debug)]
+pub struct CNDataClass;
+
+#[derive(Debug, Clone)]
+pub struct CNData {
+    base: ObjectBase,
+    cable: CableData,
+    strands: usize,
+    dia_strand: f64,
+    gmr_strand: f64,
+    r_strand: f64,
+}
+
+impl CNData {
+    pub fn new(name: &str) -> Self {
+        CNData {
+            base: ObjectBase::new("CNData", name, &PROPERTIES),
+            cable: CableData::new(),
+            strands: 2,
+            dia_strand: -1.0,
+            gmr_strand: -1.0,
+            r_strand: -1.0,
+        }
+    }
+
+    pub fn cable(&self) -> &CableData {
+        &self.cable
+    }
+
+    // The cable at (x, h) in meters, its neutral strands as the shield
+    pub fn to_conductor(&self, x: f64, h: f64, name: &str) -> DssResult<Conductor> {
+        self.cable.check(name)?;
+        if self.strands == 0 || self.dia_strand <= 0.0 || self.r_strand <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("The neutral strands of {} are not given", name),
+            ));
+        }
+        let core = self.cable.conductor();
+        let meters = self.cable.meters();
+        let k = self.strands as f64;
+        // radius of the circle through the strand centers
+        let radius = (self.cable.dia_cable_meters() - self.dia_strand * meters) / 2.0;
+        let strand_radius = self.dia_strand * meters / 2.0;
+        let gmr_strand = self.gmr_strand * core.get_gmr_units().to_meters();
+        // between core and strands (Kersting)
+        let denominator =
+            (radius / core.radius_meters()).ln() - (k * strand_radius / radius).ln() / k;
+        Ok(Conductor {
+            x,
+            h,
+            rac: core.rac_per_meter(),
+            gmr: core.gmr_meters(),
+            capradius: core.capradius_meters(),
+            shield: Some(Shield {
+                rac: self.r_strand / core.get_r_units().to_meters() / k,
+                gmr: (gmr_strand * k * radius.powf(k - 1.0)).powf(1.0 / k),
+                radius,
+                strands: Some(k),
+                capacitance: 2.0 * PI * EPSILON0 * self.cable.get_eps_r() / denominator,
+            }),
+        })
+    }
+}
+
+impl DssObject for CNData {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match index {
+            0 => self.strands = parser.make_integer()?.max(0) as usize,
+            1 => self.dia_strand = parser.make_double()?,
+            2 => self.gmr_strand = parser.make_double()?,
+            3 => self.r_strand = parser.make_double()?,
+            _ => return self.cable.set_property(index - 4, parser),
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.cable.fill_defaults();
+        if self.gmr_strand <= 0.0 && self.dia_strand > 0.0 {
+            self.gmr_strand = 0.7788 * self.dia_strand / 2.0;
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for CNDataClass {
+    fn name(&self) -> &'static str {
+        "CNData"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(CNData::new(name))
+    }
+}
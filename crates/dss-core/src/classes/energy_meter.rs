@@ -0,0 +1,967 @@
+// EnergyMeter (Pascal TEnergyMeter): meters a terminal of a branch and the
+// zone fed through it. The zone is traced from the other terminals of the
+// metered branch through the closed branches beyond, stopping at branches
+// metered by another EnergyMeter; loads on its buses belong to it. Each
+// sample adds the energy of the time step to the registers: the energy
+// through the meter and into the zone loads, the zone losses, the energy
+// over the normal and emergency ratings and the load energy served over
+// them (EEN) or unserved (UE).
+//
+// A branch above its rating puts the loads downstream of it over by the same
+// fraction; with kVAnormal or kVAemerg set, the power through the meter
+// against those limits decides for the whole zone instead. The radial/mesh
+// and combined/voltage options are taken as given but the zone is always
+// traced as radial and EEN/UE come from the overloads only.
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::bus::parse_bus_spec;
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::ckt_tree::{CktTree, bus_name, bus_terminals};
+use crate::class::DssClass;
+use crate::classes::load::Load;
+use crate::classes::sensor::Sensor;
+use crate::cmatrix::CMatrix;
+use crate::control_element::find_by_full_name;
+use crate::meter_element::{MeterElement, MeterElementBase, SolutionState};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names, read_choice,
+    read_doubles,
+};
+use crate::registry::ElementId;
+
+const ENERGY_METER_PROPERTIES: [PropertyDef; 11] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name (Full Object name) of element to which the meter is connected.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the meter is connected. 1 or 2, typically.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(MeterAction::NAMES),
+        default: "",
+        help: "{Clear (reset) | Save | Take}\n\n(C)lear = reset all registers to zero\n(S)ave = keeps the current register values; they remain readable until the next Clear\n(T)ake = Takes a sample at present solution",
+    },
+    PropertyDef {
+        name: "option",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Enter a string ARRAY of any combination of the following. Options processed left-to-right:\n\n(E)xcess : (default) UE/EEN is estimate of energy over capacity \n(T)otal: UE/EEN is total energy after capacity exceeded\n(R)adial : (default) Treats zone as a radial circuit\n(M)esh : Treats zone as meshed network (not radial).\n(C)ombined : (default) Load UE/EEN computed from combination of overload and undervoltage.\n(V)oltage : Load UE/EEN computed based on voltage only.\n\nExample: option=(E, R)",
+    },
+    PropertyDef {
+        name: "kvanormal",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Upper limit on kVA load in the zone, Normal configuration. Default is 0.0 (ignored). Overrides limits on individual lines for overload EEN. With \"LocalOnly=Yes\" option, uses only load in metered branch.",
+    },
+    PropertyDef {
+        name: "kvaemerg",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Upper limit on kVA load in the zone, Emergency configuration. Default is 0.0 (ignored). Overrides limits on individual lines for overload UE. With \"LocalOnly=Yes\" option, uses only load in metered branch.",
+    },
+    PropertyDef {
+        name: "peakcurrent",
+        kind: PropertyKind::Doubles,
+        default: "(400, 400, 400)",
+        help: "ARRAY of current magnitudes representing the peak currents measured at this location for the load allocation function.  Default is (400, 400, 400). Enter one current for each phase",
+    },
+    PropertyDef {
+        name: "zonelist",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "ARRAY of full element names for this meter's zone.  Default is for meter to find it's own zone. If specified, DSS uses this list instead.  Example: \n\nzonelist=[line.L1, transformer.T1, Line.L3] ",
+    },
+    PropertyDef {
+        name: "localonly",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No}  Default is NO.  If Yes, meter considers only the monitored element for EEN and UE calcs.  Uses whole zone for losses.",
+    },
+    PropertyDef {
+        name: "mask",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Mask for adding registers whenever all meters are totalized.  Array of floating point numbers representing the multiplier to be used for summing each register from this meter. Default = (1, 1, 1, 1, ... ).  You only have to enter as many as are changed (positional). Useful when two meters monitor same energy, etc.",
+    },
+    PropertyDef {
+        name: "losses",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes | No}  Default is YES. Compute Zone losses. If NO, then no losses at all are computed.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 13] = concat_properties(&ENERGY_METER_PROPERTIES, &CKT_PROPERTIES);
+
+// The registers, in OpenDSS order
+pub const REGISTER_NAMES: [&str; NUM_REGISTERS] = [
+    "kWh",
+    "kvarh",
+    "Max kW",
+    "Max kVA",
+    "Zone kWh",
+    "Zone kvarh",
+    "Zone Max kW",
+    "Zone Max kVA",
+    "Overload kWh Normal",
+    "Overload kWh Emerg",
+    "Load EEN",
+    "Load UE",
+    "Zone Losses kWh",
+    "Zone Losses kvarh",
+    "Zone Max kW Losses",
+    "Zone Max kvar Losses",
+    "Load Losses kWh",
+    "Load Losses kvarh",
+    "No Load Losses kWh",
+    "No Load Losses kvarh",
+    "Max kW Load Losses",
+    "Max kW No Load Losses",
+];
+
+pub const NUM_REGISTERS: usize = 22;
+
+const REG_KWH: usize = 0;
+const REG_KVARH: usize = 1;
+const REG_MAX_KW: usize = 2;
+const REG_MAX_KVA: usize = 3;
+const REG_ZONE_KWH: usize = 4;
+const REG_ZONE_KVARH: usize = 5;
+const REG_ZONE_MAX_KW: usize = 6;
+const REG_ZONE_MAX_KVA: usize = 7;
+const REG_OVERLOAD_NORMAL: usize = 8;
+const REG_OVERLOAD_EMERG: usize = 9;
+const REG_LOAD_EEN: usize = 10;
+const REG_LOAD_UE: usize = 11;
+const REG_LOSSES_KWH: usize = 12;
+const REG_LOSSES_KVARH: usize = 13;
+const REG_MAX_LOSSES_KW: usize = 14;
+const REG_MAX_LOSSES_KVAR: usize = 15;
+const REG_LOAD_LOSSES_KWH: usize = 16;
+const REG_LOAD_LOSSES_KVARH: usize = 17;
+const REG_NO_LOAD_LOSSES_KWH: usize = 18;
+const REG_NO_LOAD_LOSSES_KVARH: usize = 19;
+const REG_MAX_LOAD_LOSSES: usize = 20;
+const REG_MAX_NO_LOAD_LOSSES: usize = 21;
+
+// What the action property asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterAction {
+    Clear,
+    Reset,
+    Save,
+    Take,
+}
+
+impl MeterAction {
+    pub const NAMES: &'static [&'static str] = &["clear", "reset", "save", "take"];
+
+    // Action of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "reset" => MeterAction::Reset,
+            "save" => MeterAction::Save,
+            "take" => MeterAction::Take,
+            _ => MeterAction::Clear,
+        }
+    }
+}
+
+// A branch of the zone and the branch it is fed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneBranch {
+    pub id: ElementId,
+    // Index in the zone of the upstream branch; None for the metered branch
+    // and for branches of a zone list
+    pub parent: Option<usize>,
+    // Terminal toward the meter, from 0
+    pub terminal: usize,
+}
+
+// The branches of a meter zone, upstream first, and its loads with the
+// index of the branch feeding each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeterZone {
+    pub branches: Vec<ZoneBranch>,
+    pub loads: Vec<(ElementId, usize)>,
+}
+
+#[derive(Debug)]
+pub struct EnergyMeterClass;
+
+#[derive(Debug, Clone)]
+pub struct EnergyMeter {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    // The metered branch and terminal
+    meter: MeterElementBase,
+    // Overload counted as the energy over the rating rather than all of it
+    excess: bool,
+    kva_normal: f64,
+    kva_emerg: f64,
+    peak_current: Vec<f64>,
+    zone_list: Vec<String>,
+    local_only: bool,
+    mask: Vec<f64>,
+    losses: bool,
+    registers: [f64; NUM_REGISTERS],
+    // Traced at the first sample after the zone may have changed
+    zone: Option<MeterZone>,
+    // Take action waiting for the solution to sample the meter
+    take_requested: bool,
+}
+
+impl EnergyMeter {
+    pub fn new(name: &str) -> Self {
+        EnergyMeter {
+            base: ObjectBase::new("EnergyMeter", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            meter: MeterElementBase::new(),
+            excess: true,
+            kva_normal: 0.0,
+            kva_emerg: 0.0,
+            peak_current: vec![400.0; 3],
+            zone_list: Vec::new(),
+            local_only: false,
+            mask: Vec::new(),
+            losses: true,
+            registers: [0.0; NUM_REGISTERS],
+            zone: None,
+            take_requested: false,
+        }
+    }
+
+    pub fn registers(&self) -> &[f64; NUM_REGISTERS] {
+        &self.registers
+    }
+
+    // Register of a name in REGISTER_NAMES, case-insensitive
+    pub fn get_register(&self, name: &str) -> Option<f64> {
+        REGISTER_NAMES
+            .iter()
+            .position(|register| register.eq_ignore_ascii_case(name))
+            .map(|index| self.registers[index])
+    }
+
+    // Multiplier of each register when meters are totalized
+    pub fn mask(&self) -> [f64; NUM_REGISTERS] {
+        std::array::from_fn(|k| self.mask.get(k).copied().unwrap_or(1.0))
+    }
+
+    pub fn get_peak_current(&self) -> &[f64] {
+        &self.peak_current
+    }
+
+    pub fn set_peak_current(&mut self, currents: Vec<f64>) {
+        self.peak_current = currents;
+    }
+
+    // Whether a Take action waits for the next sample
+    pub fn is_take_requested(&self) -> bool {
+        self.take_requested
+    }
+
+    // The zone as last traced
+    pub fn zone(&self) -> Option<&MeterZone> {
+        self.zone.as_ref()
+    }
+
+    // Traces the zone again from the circuit as it is now
+    pub fn make_zone(&mut self, circuit: &Circuit) -> &MeterZone {
+        let zone = self.trace_zone(circuit);
+        self.zone.insert(zone)
+    }
+
+    // The tree beyond the metered terminal; branches metered by other
+    // meters start zones of their own
+    pub fn make_tree(&self, circuit: &Circuit) -> CktTree {
+        let Some(start) = self.meter.find_element(circuit) else {
+            return CktTree::default();
+        };
+        let stops: HashSet<ElementId> = circuit
+            .class_elements("energymeter")
+            .iter()
+            .filter_map(|&id| circuit.element(id))
+            .filter(|meter| !meter.name().eq_ignore_ascii_case(self.name()))
+            .filter_map(|meter| meter.as_any().downcast_ref::<EnergyMeter>())
+            .filter_map(|meter| meter.meter.find_element(circuit))
+            .collect();
+        CktTree::from_branch(circuit, start, self.meter.get_terminal() - 1, &stops)
+    }
+
+    fn trace_zone(&self, circuit: &Circuit) -> MeterZone {
+        let mut zone = MeterZone::default();
+        let Some(start) = self.meter.find_element(circuit) else {
+            return zone;
+        };
+        let first = self.meter.get_terminal() - 1;
+
+        if self.zone_list.is_empty() {
+            let tree = self.make_tree(circuit);
+            for (index, branch) in tree.branches().iter().enumerate() {
+                zone.branches.push(ZoneBranch {
+                    id: branch.id,
+                    parent: branch.parent,
+                    terminal: branch.terminal,
+                });
+                for &id in &branch.shunts {
+                    if circuit.element(id).is_some_and(|e| e.as_any().is::<Load>()) {
+                        zone.loads.push((id, index));
+                    }
+                }
+            }
+        } else {
+            // a given zone: its branches and the loads on their buses
+            let at_bus = bus_terminals(circuit);
+            let ckt = |id: ElementId| circuit.element(id).and_then(|e| e.as_ckt_element());
+            let mut in_zone: HashSet<ElementId> = HashSet::new();
+            for name in &self.zone_list {
+                if let Some(id) = find_by_full_name(circuit, name)
+                    && in_zone.insert(id)
+                {
+                    zone.branches.push(ZoneBranch {
+                        id,
+                        parent: None,
+                        terminal: if id == start { first } else { 0 },
+                    });
+                }
+            }
+            for (branch, zone_branch) in zone.branches.iter().enumerate() {
+                let Some(element) = ckt(zone_branch.id) else {
+                    continue;
+                };
+                for terminal in 0..element.nterms() {
+                    let bus = bus_name(element.get_bus(terminal));
+                    for &(id, _) in at_bus.get(&bus).into_iter().flatten() {
+                        let is_load = circuit.element(id).is_some_and(|e| e.as_any().is::<Load>());
+                        if is_load && in_zone.insert(id) {
+                            zone.loads.push((id, branch));
+                        }
+                    }
+                }
+            }
+        }
+        zone
+    }
+
+    // Fraction of the power through a branch over its normal and emergency
+    // ratings
+    fn branch_overloads(
+        &self,
+        element: &dyn CktElement,
+        voltages: &[Complex64],
+        terminal: usize,
+    ) -> (f64, f64) {
+        let Some(pd) = element.as_pd_element() else {
+            return (0.0, 0.0);
+        };
+        if pd.is_shunt() {
+            return (0.0, 0.0);
+        }
+        let current = pd.max_terminal_current(voltages, terminal);
+        let over = |rating: f64| match rating {
+            _ if rating <= 0.0 || current <= rating => 0.0,
+            _ if self.excess => 1.0 - rating / current,
+            _ => 1.0,
+        };
+        (
+            over(pd.pd_base().get_norm_amps()),
+            over(pd.pd_base().get_emerg_amps()),
+        )
+    }
+
+    // Fraction of the meter power over a zone kVA limit
+    fn zone_overload(&self, kva: f64, limit: f64) -> f64 {
+        match limit {
+            _ if limit <= 0.0 || kva <= limit => 0.0,
+            _ if self.excess => 1.0 - limit / kva,
+            _ => 1.0,
+        }
+    }
+}
+
+// Scales the loads in the zone of each meter so the computed currents at the
+// meter match its peak currents (Pascal AllocateLoad). A sensor on a branch
+// of the zone gives the currents for that branch and all below it instead.
+// Phase k of a branch is taken as phase k of the meter. Loads follow the
+// average factor of the phases they connect to; only loads given by kVA of
+// the transformer or kWh change.
+pub fn allocate_loads(circuit: &mut Circuit, voltages: &[Complex64]) {
+    let meters: Vec<ElementId> = circuit.class_elements("energymeter").to_vec();
+    // measured currents by the element they sense
+    let sensed: HashMap<ElementId, Vec<f64>> = circuit
+        .class_elements("sensor")
+        .iter()
+        .filter_map(|&id| circuit.element(id))
+        .filter_map(|sensor| sensor.as_any().downcast_ref::<Sensor>())
+        .filter(|sensor| sensor.ckt_base().is_enabled())
+        .filter(|sensor| !sensor.get_measured_currents().is_empty())
+        .filter_map(|sensor| {
+            let id = sensor.meter_base().find_element(circuit)?;
+            Some((id, sensor.get_measured_currents().to_vec()))
+        })
+        .collect();
+
+    let mut factors: HashMap<ElementId, f64> = HashMap::new();
+    for meter_id in meters {
+        let Some(meter) = circuit
+            .element(meter_id)
+            .and_then(|meter| meter.as_any().downcast_ref::<EnergyMeter>())
+        else {
+            continue;
+        };
+        if !meter.ckt_base().is_enabled() {
+            continue;
+        }
+        let peak = meter.peak_current.clone();
+        let zone = meter.trace_zone(circuit);
+
+        // per phase factors of each branch of the zone, in tracing order
+        let mut branch_factors: Vec<Vec<f64>> = Vec::with_capacity(zone.branches.len());
+        for branch in &zone.branches {
+            let measured = match (branch.parent, sensed.get(&branch.id)) {
+                (_, Some(currents)) => Some(currents.as_slice()),
+                (None, None) => Some(peak.as_slice()),
+                _ => None,
+            };
+            let own = measured.and_then(|measured| {
+                let element = circuit.element(branch.id)?.as_ckt_element()?;
+                let computed = element
+                    .get_currents(voltages)
+                    .into_iter()
+                    .skip(branch.terminal * element.nconds())
+                    .take(element.nphases())
+                    .map(|current| current.norm());
+                Some(
+                    computed
+                        .zip(measured)
+                        .map(|(computed, measured)| match computed {
+                            0.0 => 1.0,
+                            _ => measured / computed,
+                        })
+                        .collect::<Vec<f64>>(),
+                )
+            });
+            let inherited = || match branch.parent {
+                Some(parent) => branch_factors[parent].clone(),
+                None => Vec::new(),
+            };
+            let factors = own.unwrap_or_else(inherited);
+            branch_factors.push(factors);
+        }
+
+        for &(load_id, branch) in &zone.loads {
+            let Some(load) = circuit.element(load_id).and_then(|e| e.as_ckt_element()) else {
+                continue;
+            };
+            let Ok((_, nodes)) = parse_bus_spec(load.get_bus(0), load.nphases() as u32) else {
+                continue;
+            };
+            let phases: Vec<f64> = nodes
+                .iter()
+                .take(load.nphases())
+                .filter_map(|&node| branch_factors[branch].get((node as usize).checked_sub(1)?))
+                .copied()
+                .collect();
+            if !phases.is_empty() {
+                factors.insert(load_id, phases.iter().sum::<f64>() / phases.len() as f64);
+            }
+        }
+    }
+
+    for (id, factor) in factors {
+        if let Some(load) = circuit
+            .element_mut(id)
+            .and_then(|load| load.as_any_mut().downcast_mut::<Load>())
+        {
+            load.allocate(factor);
+        }
+    }
+}
+
+impl DssObject for EnergyMeter {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = ENERGY_METER_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - ENERGY_METER_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => {
+                self.meter.set_element(parser.get_token());
+                self.zone = None;
+            }
+            "terminal" => {
+                self.meter
+                    .set_terminal(parser.make_integer()?.max(1) as usize);
+                self.zone = None;
+            }
+            "action" => {
+                match MeterAction::from_name(read_choice(parser, MeterAction::NAMES, "action")?) {
+                    MeterAction::Clear | MeterAction::Reset => MeterElement::reset(self),
+                    MeterAction::Take => self.take_requested = true,
+                    // The registers are kept in memory until cleared
+                    MeterAction::Save => {}
+                }
+            }
+            "option" => {
+                for option in parse_names(parser.get_token()) {
+                    match option.chars().next().map(|c| c.to_ascii_lowercase()) {
+                        Some('e') => self.excess = true,
+                        Some('t') => self.excess = false,
+                        Some('r' | 'm' | 'c' | 'v') => {}
+                        _ => {
+                            return Err(DssError::new(
+                                codes::SYNTAX_ERROR,
+                                &format!(
+                                    "Unknown option \"{}\" for {}; expected Excess, Total, Radial, Mesh, Combined or Voltage",
+                                    option,
+                                    self.full_name()
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+            "kvanormal" => self.kva_normal = parser.make_double()?,
+            "kvaemerg" => self.kva_emerg = parser.make_double()?,
+            "peakcurrent" => self.peak_current = read_doubles(parser)?,
+            "zonelist" => {
+                self.zone_list = parse_names(parser.get_token());
+                self.zone = None;
+            }
+            "localonly" => self.local_only = interpret_yes_no(parser.get_token()),
+            "mask" => self.mask = read_doubles(parser)?,
+            "losses" => self.losses = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let name = ENERGY_METER_PROPERTIES.get(index).map(|p| p.name);
+        if name != Some("element") {
+            return Ok(());
+        }
+        let element = self.meter.get_element();
+        let id = self.meter.find_element(circuit).ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!(
+                    "Metered element \"{}\" not found for {}",
+                    element,
+                    self.full_name()
+                ),
+            )
+        })?;
+        let is_branch = circuit
+            .element(id)
+            .and_then(|element| element.as_ckt_element())
+            .is_some_and(|element| element.as_pd_element().is_some());
+        if !is_branch {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} must meter a power delivery element; \"{}\" is not one",
+                    self.full_name(),
+                    element
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for EnergyMeter {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_meter_element(&self) -> Option<&dyn MeterElement> {
+        Some(self)
+    }
+
+    fn as_meter_element_mut(&mut self) -> Option<&mut dyn MeterElement> {
+        Some(self)
+    }
+
+    // Meters are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl MeterElement for EnergyMeter {
+    fn meter_base(&self) -> &MeterElementBase {
+        &self.meter
+    }
+
+    fn meter_base_mut(&mut self) -> &mut MeterElementBase {
+        &mut self.meter
+    }
+
+    fn take_sample(&mut self, circuit: &Circuit, voltages: &[Complex64], state: &SolutionState) {
+        self.take_requested = false;
+        let zone = match self.zone.take() {
+            Some(zone) => zone,
+            None => self.trace_zone(circuit),
+        };
+        let ckt = |id: ElementId| circuit.element(id).and_then(|e| e.as_ckt_element());
+        let hours = state.interval / 3600.0;
+        let zero = Complex64::new(0.0, 0.0);
+
+        // through the meter
+        let metered = zone.branches.first().and_then(|branch| {
+            let power = ckt(branch.id)?.terminal_powers(voltages);
+            power.get(branch.terminal).copied()
+        });
+        let s_meter = metered.unwrap_or(zero) / 1000.0;
+
+        // into the zone loads
+        let load_powers: Vec<Complex64> = zone
+            .loads
+            .iter()
+            .map(|&(id, _)| ckt(id).map_or(zero, |load| load.total_power(voltages) / 1000.0))
+            .collect();
+        let s_zone: Complex64 = load_powers.iter().sum();
+
+        // losses of the zone branches: total, load and no-load
+        let (mut total, mut load, mut no_load) = (zero, zero, zero);
+        if self.losses {
+            for branch in &zone.branches {
+                if let Some(pd) = ckt(branch.id).and_then(|element| element.as_pd_element()) {
+                    let (t, l, n) = pd.losses(voltages);
+                    total += t / 1000.0;
+                    load += l / 1000.0;
+                    no_load += n / 1000.0;
+                }
+            }
+        }
+
+        // overloads: of the zone against its kVA limits, or of the branches
+        // against their ratings, each load taking the worst upstream of it
+        let kva = s_meter.norm();
+        let mut branch_over = vec![(0.0, 0.0); zone.branches.len()];
+        let (max_kw_normal, max_kw_emerg);
+        if self.kva_normal > 0.0 || self.kva_emerg > 0.0 {
+            let over = (
+                self.zone_overload(kva, self.kva_normal),
+                self.zone_overload(kva, self.kva_emerg),
+            );
+            branch_over.fill(over);
+            max_kw_normal = s_meter.re * over.0;
+            max_kw_emerg = s_meter.re * over.1;
+        } else {
+            let (mut normal, mut emerg) = (0.0_f64, 0.0_f64);
+            for (k, branch) in zone.branches.iter().enumerate() {
+                if self.local_only && k > 0 {
+                    branch_over[k] = branch_over[0];
+                    continue;
+                }
+                let Some(element) = ckt(branch.id) else {
+                    continue;
+                };
+                let own = self.branch_overloads(element, voltages, branch.terminal);
+                let kw = element
+                    .terminal_powers(voltages)
+                    .get(branch.terminal)
+                    .map_or(0.0, |s| s.re / 1000.0);
+                normal = normal.max(kw * own.0);
+                emerg = emerg.max(kw * own.1);
+                // upstream branches come first in the zone
+                let upstream = branch.parent.map_or((0.0, 0.0), |p| branch_over[p]);
+                branch_over[k] = (own.0.max(upstream.0), own.1.max(upstream.1));
+            }
+            max_kw_normal = normal;
+            max_kw_emerg = emerg;
+        }
+        let (mut een, mut ue) = (0.0, 0.0);
+        for (&(_, feeder), power) in zone.loads.iter().zip(&load_powers) {
+            let (normal, emerg) = branch_over.get(feeder).copied().unwrap_or((0.0, 0.0));
+            // served above normal but below emergency ratings, or unserved
+            ue += power.re * emerg;
+            een += power.re * (normal - emerg).max(0.0);
+        }
+
+        let r = &mut self.registers;
+        r[REG_KWH] += s_meter.re * hours;
+        r[REG_KVARH] += s_meter.im * hours;
+        r[REG_MAX_KW] = r[REG_MAX_KW].max(s_meter.re);
+        r[REG_MAX_KVA] = r[REG_MAX_KVA].max(kva);
+        r[REG_ZONE_KWH] += s_zone.re * hours;
+        r[REG_ZONE_KVARH] += s_zone.im * hours;
+        r[REG_ZONE_MAX_KW] = r[REG_ZONE_MAX_KW].max(s_zone.re);
+        r[REG_ZONE_MAX_KVA] = r[REG_ZONE_MAX_KVA].max(s_zone.norm());
+        r[REG_OVERLOAD_NORMAL] += max_kw_normal * hours;
+        r[REG_OVERLOAD_EMERG] += max_kw_emerg * hours;
+        r[REG_LOAD_EEN] += een * hours;
+        r[REG_LOAD_UE] += ue * hours;
+        r[REG_LOSSES_KWH] += total.re * hours;
+        r[REG_LOSSES_KVARH] += total.im * hours;
+        r[REG_MAX_LOSSES_KW] = r[REG_MAX_LOSSES_KW].max(total.re);
+        r[REG_MAX_LOSSES_KVAR] = r[REG_MAX_LOSSES_KVAR].max(total.im);
+        r[REG_LOAD_LOSSES_KWH] += load.re * hours;
+        r[REG_LOAD_LOSSES_KVARH] += load.im * hours;
+        r[REG_NO_LOAD_LOSSES_KWH] += no_load.re * hours;
+        r[REG_NO_LOAD_LOSSES_KVARH] += no_load.im * hours;
+        r[REG_MAX_LOAD_LOSSES] = r[REG_MAX_LOAD_LOSSES].max(load.re);
+        r[REG_MAX_NO_LOAD_LOSSES] = r[REG_MAX_NO_LOAD_LOSSES].max(no_load.re);
+        self.zone = Some(zone);
+    }
+
+    // The zone is traced again at the next sample, as the circuit may have
+    // changed since
+    fn reset(&mut self) {
+        self.registers = [0.0; NUM_REGISTERS];
+        self.zone = None;
+    }
+}
+
+impl DssClass for EnergyMeterClass {
+    fn name(&self) -> &'static str {
+        "EnergyMeter"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(EnergyMeter::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::classes::line::{Line, LineClass};
+    use crate::classes::load::LoadClass;
+    use crate::classes::sensor::SensorClass;
+    use crate::test_util::{self, add, edited, placed};
+
+    fn add_line(circuit: &mut Circuit, name: &str, buses: &str, nodes: [usize; 2]) {
+        let line = edited(
+            Line::new(name),
+            &LineClass,
+            &format!(
+                "{} r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1 normamps=5 emergamps=6",
+                buses
+            ),
+            circuit,
+        )
+        .unwrap();
+        let [from, to] = nodes.map(|first| (first..first + 3).collect::<Vec<usize>>());
+        circuit.add_element(Box::new(placed(line, &[&from, &to]).unwrap()));
+    }
+
+    // a - l1 - b - l2 - c - l3 - d with a 30 kW load at c; l3 is metered by
+    // a meter of its own
+    fn feeder() -> Circuit {
+        feeder_with_load("kw=30 pf=1")
+    }
+
+    fn feeder_with_load(power: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        add_line(&mut circuit, "l1", "bus1=a bus2=b", [1, 4]);
+        add_line(&mut circuit, "l2", "bus1=b bus2=c", [4, 7]);
+        add_line(&mut circuit, "l3", "bus1=c bus2=d", [7, 10]);
+        let load = edited(
+            Load::new("ld1"),
+            &LoadClass,
+            &format!("bus1=c phases=3 kv=1.7 {}", power),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(load, &[&[7, 8, 9, 0]]).unwrap()));
+        add(&mut circuit, "energymeter", "e2", "element=line.l3");
+        circuit
+    }
+
+    fn edit(meter: &mut EnergyMeter, properties: &str, circuit: &Circuit) -> DssResult<()> {
+        test_util::edit(meter, &EnergyMeterClass, properties, circuit)
+    }
+
+    fn new_meter(properties: &str, circuit: &Circuit) -> EnergyMeter {
+        let mut meter = EnergyMeter::new("e1");
+        edit(
+            &mut meter,
+            &format!("element=line.l1 {}", properties),
+            circuit,
+        )
+        .unwrap();
+        meter
+    }
+
+    // Balanced magnitudes at a, b, c and d
+    fn voltages(magnitudes: [f64; 4]) -> Vec<Complex64> {
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        for v in magnitudes {
+            voltages.extend([v.into(), a * v, a * a * v]);
+        }
+        voltages
+    }
+
+    fn one_hour() -> SolutionState {
+        SolutionState {
+            interval: 3600.0,
+            ..SolutionState::default()
+        }
+    }
+
+    #[test]
+    fn test_zone() {
+        let mut circuit = feeder();
+        let mut meter = new_meter("", &circuit);
+        let [l1, l2] = ["l1", "l2"].map(|name| circuit.find_element("line", name).unwrap());
+        let ld1 = circuit.find_element("load", "ld1").unwrap();
+        let zone = meter.make_zone(&circuit);
+        let ids: Vec<_> = zone.branches.iter().map(|b| (b.id, b.parent)).collect();
+        assert_eq!(ids, [(l1, None), (l2, Some(0))]);
+        assert_eq!(zone.loads, [(ld1, 1)]);
+
+        // an open line ends the zone
+        let line = circuit
+            .element_mut(l2)
+            .unwrap()
+            .as_ckt_element_mut()
+            .unwrap();
+        line.ckt_base_mut().set_closed(0, None, false);
+        assert_eq!(meter.make_zone(&circuit).branches.len(), 1);
+
+        let mut meter = new_meter("zonelist=[line.l1, line.l3]", &circuit);
+        let zone = meter.make_zone(&circuit);
+        assert_eq!(zone.branches.len(), 2);
+        assert_eq!(zone.loads, [(ld1, 1)]);
+
+        let err = edit(&mut meter, "element=load.ld1", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn test_registers() {
+        let circuit = feeder();
+        let mut meter = new_meter("", &circuit);
+        // 5 - j5 A through l1, above both ratings; half of that through l2
+        let v = voltages([1000.0, 990.0, 985.0, 985.0]);
+        meter.take_sample(&circuit, &v, &one_hour());
+        meter.take_sample(&circuit, &v, &one_hour());
+
+        let reg = |name: &str| meter.get_register(name).unwrap();
+        assert!((reg("kWh") - 30.0).abs() < 1e-9);
+        assert!((reg("kvarh") - 30.0).abs() < 1e-9);
+        assert!((reg("Max kW") - 15.0).abs() < 1e-9);
+        assert!((reg("Zone kWh") - 60.0).abs() < 1e-6);
+        assert!((reg("Zone Losses kWh") - 2.0 * 0.1875).abs() < 1e-9);
+        assert!((reg("Zone Max kW Losses") - 0.1875).abs() < 1e-9);
+
+        let over_normal = 1.0 - 5.0 / 50.0_f64.sqrt();
+        let over_emerg = 1.0 - 6.0 / 50.0_f64.sqrt();
+        assert!((reg("Overload kWh Normal") - 2.0 * 15.0 * over_normal).abs() < 1e-9);
+        assert!((reg("Overload kWh Emerg") - 2.0 * 15.0 * over_emerg).abs() < 1e-9);
+        // the load downstream of l1 shares its overload
+        assert!((reg("Load UE") - 2.0 * 30.0 * over_emerg).abs() < 1e-6);
+        let een = 2.0 * 30.0 * (over_normal - over_emerg);
+        assert!((reg("Load EEN") - een).abs() < 1e-6);
+
+        // a zone limit overrides the line ratings
+        let mut meter = new_meter("kvanormal=20 option=(total)", &circuit);
+        meter.take_sample(&circuit, &v, &one_hour());
+        assert!((meter.get_register("Overload kWh Normal").unwrap() - 15.0).abs() < 1e-9);
+        assert_eq!(meter.get_register("Overload kWh Emerg"), Some(0.0));
+
+        edit(&mut meter, "action=clear", &circuit).unwrap();
+        assert!(meter.registers().iter().all(|&r| r == 0.0));
+    }
+
+    #[test]
+    fn test_allocate_loads() {
+        let allocation = |circuit: &Circuit| {
+            circuit
+                .find_object_as::<Load>("load", "ld1")
+                .unwrap()
+                .get_allocation_factor()
+        };
+        // 5 - j5 A through l1 and half of that through l2
+        let v = voltages([1000.0, 990.0, 985.0, 985.0]);
+        let peak = 3.0 * 50.0_f64.sqrt();
+        let mut circuit = feeder_with_load("xfkva=50 pf=1");
+        circuit.add_element(Box::new(new_meter(
+            &format!("peakcurrent=[{0} {0} {0}]", peak),
+            &circuit,
+        )));
+        allocate_loads(&mut circuit, &v);
+        assert!((allocation(&circuit) - 0.5 * 3.0).abs() < 1e-9);
+
+        // a sensor on l2 measures twice its current
+        let mut sensor = Sensor::new("s1");
+        let measured = 2.0 * 12.5_f64.sqrt();
+        test_util::edit(
+            &mut sensor,
+            &SensorClass,
+            &format!("element=line.l2 currents=[{0} {0} {0}]", measured),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(sensor));
+        allocate_loads(&mut circuit, &v);
+        assert!((allocation(&circuit) - 0.5 * 3.0 * 2.0).abs() < 1e-9);
+
+        // loads given by kW stay as they are
+        let mut circuit = feeder();
+        circuit.add_element(Box::new(new_meter("", &circuit)));
+        allocate_loads(&mut circuit, &v);
+        let load = circuit.find_object_as::<Load>("load", "ld1").unwrap();
+        assert_eq!(load.get_allocation_factor(), 0.5);
+    }
+}
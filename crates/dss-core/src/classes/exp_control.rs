@@ -0,0 +1,541 @@
+// ExpControl (Pascal TExpControl): exponential volt-var control for PVSystem
+// and Storage inverters. The kvar of each inverter is driven by a straight
+// line through its reference voltage, qbias at vreg and falling by slope per
+// unit of kvar per unit of volts, within qmaxlead and qmaxlag of the kVA
+// rating. Over a time-series the reference itself follows the voltage with
+// time constant vregtau, so the control works against changes but not the
+// steady level. Each control iteration moves the kvar deltaq_factor of the
+// way to the line, or with tresponse set, along an exponential response in
+// time.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::inv_control::{Der, apply_set_points, der, terminal_vpu};
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names,
+};
+use crate::registry::ElementId;
+
+const EXP_CONTROL_PROPERTIES: [PropertyDef; 14] = [
+    PropertyDef {
+        name: "pvsystemlist",
+        kind: PropertyKind::Objects("PVSystem"),
+        default: "",
+        help: "Array list of PVSystems to be controlled.If not specified, all PVSystems in the circuit are assumed to be controlled by this ExpControl.",
+    },
+    PropertyDef {
+        name: "vreg",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per-unit voltage at which reactive power is zero; defaults to 1.0.This may dynamically self-adjust when VregTau > 0, limited by VregMin and VregMax.If input as 0, Vreg will be initialized from a snapshot solution with no inverter Q.The equilibrium point of reactive power is also affected by Qbias",
+    },
+    PropertyDef {
+        name: "slope",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Per-unit reactive power injection / per-unit voltage deviation from Vreg; defaults to 50.Unlike InvControl, base reactive power is constant at the inverter kva rating.",
+    },
+    PropertyDef {
+        name: "vregtau",
+        kind: PropertyKind::Double,
+        default: "1200",
+        help: "Time constant for adaptive Vreg. Defaults to 1200 seconds. When the control injects or absorbs reactive power due to a voltage deviation from the Q=0 crossing of the volt-var curve, the Q=0 crossing will move toward the actual terminal voltage with this time constant. Over time, the effect is to gradually bring inverter reactive power to zero as the grid voltage changes due to non-solar effects. If zero, then Vreg stays fixed. IEEE1547-2018 requires adjustability from 300s to 5000s",
+    },
+    PropertyDef {
+        name: "qbias",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Equilibrium per-unit reactive power when V=Vreg; defaults to 0.Enter > 0 for lagging (capacitive) bias, < 0 for leading (inductive) bias.",
+    },
+    PropertyDef {
+        name: "vregmin",
+        kind: PropertyKind::Double,
+        default: "0.95",
+        help: "Lower limit on adaptive Vreg; defaults to 0.95 per-unit",
+    },
+    PropertyDef {
+        name: "vregmax",
+        kind: PropertyKind::Double,
+        default: "1.05",
+        help: "Upper limit on adaptive Vreg; defaults to 1.05 per-unit",
+    },
+    PropertyDef {
+        name: "qmaxlead",
+        kind: PropertyKind::Double,
+        default: "0.44",
+        help: "Limit on leading (inductive) reactive power, the kvar the inverter absorbs, in per-unit of the kva rating; defaults to 0.44.",
+    },
+    PropertyDef {
+        name: "qmaxlag",
+        kind: PropertyKind::Double,
+        default: "0.44",
+        help: "Limit on lagging (capacitive) reactive power, the kvar the inverter produces, in per-unit of the kva rating; defaults to 0.44.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes/True* | No/False} Default is No for ExpControl. Log control actions to Eventlog.",
+    },
+    PropertyDef {
+        name: "deltaq_factor",
+        kind: PropertyKind::Double,
+        default: "0.7",
+        help: "Convergence parameter; Defaults to 0.7. Sets the maximum change (in per unit) from the prior var output level to the desired var output level during each control iteration. If numerical instability is noticed in solutions such as var sign changing from one control iteration to another and voltages oscillating between two values with some separation, this is an indication of numerical instability (use the EventLog to diagnose). If the maximum control iterations are exceeded, and no numerical instability is seen in the EventLog of via monitors, then try increasing the value of this parameter to reduce the number of control iterations needed to achieve the control criteria, and move to the power flow solution.",
+    },
+    PropertyDef {
+        name: "preferq",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes/True* | No/False} Default is No for ExpControl.Curtails real power output as needed to meet the reactive power requirement. IEEE1547-2018 requires Yes, but the default is No for backward compatibility of OpenDSS models.",
+    },
+    PropertyDef {
+        name: "tresponse",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Open-loop response time for changes in Q.The value of Q reaches 90% of the target change within Tresponse seconds.Typically, Tresponse will be less than the observation interval of a solution, so the actual response is instantaneous.IEEE1547-2018 requires adjustability from 1 to 90 seconds.This parameter is only used in time series simulations.",
+    },
+    PropertyDef {
+        name: "derlist",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Alternative to PVSystemList for CIM export and import.However, storage is not actually implemented yet.Use fully qualified PVSystem names.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 16] = concat_properties(&EXP_CONTROL_PROPERTIES, &CKT_PROPERTIES);
+
+// Change of kvar, per unit of the rating, below which no action is queued
+const KVAR_TOLERANCE: f64 = 1e-4;
+
+// Control state of one inverter
+#[derive(Debug, Clone)]
+struct DerControl {
+    id: ElementId,
+    // Reference voltage, per unit, once set
+    vreg: Option<f64>,
+    // Solution time of the last sample, seconds
+    last_time: Option<f64>,
+    // Set points the queued action will apply
+    kvar: Option<f64>,
+    pct_p: Option<f64>,
+    pending: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct ExpControlClass;
+
+#[derive(Debug, Clone)]
+pub struct ExpControl {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    der_list: Vec<String>,
+    vreg: f64,
+    slope: f64,
+    vreg_tau: f64,
+    q_bias: f64,
+    vreg_min: f64,
+    vreg_max: f64,
+    q_max_lead: f64,
+    q_max_lag: f64,
+    event_log: bool,
+    delta_q_factor: f64,
+    prefer_q: bool,
+    t_response: f64,
+    ders: Vec<DerControl>,
+}
+
+impl ExpControl {
+    pub fn new(name: &str) -> Self {
+        ExpControl {
+            base: ObjectBase::new("ExpControl", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            der_list: Vec::new(),
+            vreg: 1.0,
+            slope: 50.0,
+            vreg_tau: 1200.0,
+            q_bias: 0.0,
+            vreg_min: 0.95,
+            vreg_max: 1.05,
+            q_max_lead: 0.44,
+            q_max_lag: 0.44,
+            event_log: false,
+            delta_q_factor: 0.7,
+            prefer_q: false,
+            t_response: 0.0,
+            ders: Vec::new(),
+        }
+    }
+
+    // Present reference voltage of the i-th controlled inverter, per unit
+    pub fn get_vreg(&self, index: usize) -> Option<f64> {
+        self.ders.get(index).and_then(|state| state.vreg)
+    }
+
+    // PVSystem elements the control acts on
+    pub fn der_elements(&self, circuit: &Circuit) -> Vec<ElementId> {
+        if self.der_list.is_empty() {
+            return circuit.class_elements("pvsystem").to_vec();
+        }
+        self.der_list
+            .iter()
+            .filter_map(|name| match name.contains('.') {
+                true => find_by_full_name(circuit, name),
+                false => circuit.find_element("pvsystem", name),
+            })
+            .filter(|&id| der(circuit, id).is_some())
+            .collect()
+    }
+
+    // Moves the reference toward the voltage over `dt` seconds
+    fn track_vreg(&self, vreg: f64, vpu: f64, dt: f64) -> f64 {
+        if self.vreg_tau <= 0.0 || dt <= 0.0 {
+            return vreg;
+        }
+        let vreg = vreg + (vpu - vreg) * (1.0 - (-dt / self.vreg_tau).exp());
+        vreg.clamp(self.vreg_min, self.vreg_max)
+    }
+
+    // New kvar and, when Q is preferred, output limit for one iteration
+    fn next_set_points(&self, inverter: &Der, vpu: f64, vreg: f64, dt: f64) -> (f64, Option<f64>) {
+        let q_pu =
+            (self.q_bias - self.slope * (vpu - vreg)).clamp(-self.q_max_lead, self.q_max_lag);
+        let mut target = q_pu * inverter.kva;
+        let q_available = if self.prefer_q {
+            inverter.kva
+        } else {
+            (inverter.kva * inverter.kva - inverter.kw * inverter.kw)
+                .max(0.0)
+                .sqrt()
+        };
+        target = target.clamp(-q_available, q_available);
+        let step = if self.t_response > 0.0 && dt > 0.0 {
+            // 90% of the change within tresponse
+            1.0 - (-dt * 10.0_f64.ln() / self.t_response).exp()
+        } else {
+            self.delta_q_factor
+        };
+        let kvar = inverter.kvar + step * (target - inverter.kvar);
+        let kw_room = (inverter.kva * inverter.kva - kvar * kvar).max(0.0).sqrt();
+        let pct_p = (self.prefer_q && inverter.kw_available > kw_room && inverter.kw_base > 0.0)
+            .then(|| kw_room / inverter.kw_base * 100.0);
+        (kvar, pct_p)
+    }
+}
+
+impl DssObject for ExpControl {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = EXP_CONTROL_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - EXP_CONTROL_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "pvsystemlist" | "derlist" => self.der_list = parse_names(parser.get_token()),
+            "vreg" => self.vreg = parser.make_double()?,
+            "slope" => self.slope = parser.make_double()?,
+            "vregtau" => self.vreg_tau = parser.make_double()?.max(0.0),
+            "qbias" => self.q_bias = parser.make_double()?,
+            "vregmin" => self.vreg_min = parser.make_double()?,
+            "vregmax" => self.vreg_max = parser.make_double()?,
+            "qmaxlead" => self.q_max_lead = parser.make_double()?,
+            "qmaxlag" => self.q_max_lag = parser.make_double()?,
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            "deltaq_factor" => self.delta_q_factor = parser.make_double()?,
+            "preferq" => self.prefer_q = interpret_yes_no(parser.get_token()),
+            "tresponse" => self.t_response = parser.make_double()?.max(0.0),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.vreg_min > self.vreg_max {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs vregmin <= vregmax", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for ExpControl {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for ExpControl {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Moves each reference on to the present time and queues the new kvar
+    // of the inverters that are off the line
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let ids = self.der_elements(circuit);
+        if self
+            .ders
+            .iter()
+            .map(|state| state.id)
+            .ne(ids.iter().copied())
+        {
+            self.ders = ids
+                .iter()
+                .map(|&id| DerControl {
+                    id,
+                    vreg: None,
+                    last_time: None,
+                    kvar: None,
+                    pct_p: None,
+                    pending: None,
+                })
+                .collect();
+        }
+        let (hour, sec) = queue.get_time();
+        let now = hour as f64 * 3600.0 + sec;
+        for index in 0..self.ders.len() {
+            let Some(inverter) = der(circuit, self.ders[index].id) else {
+                continue;
+            };
+            let vpu = terminal_vpu(circuit, self.ders[index].id, voltages, inverter.vbase);
+            let state = &self.ders[index];
+            let dt = state.last_time.map_or(0.0, |last| now - last);
+            let vreg = match state.vreg {
+                Some(vreg) => self.track_vreg(vreg, vpu, dt),
+                None if self.vreg > 0.0 => self.vreg,
+                None => vpu,
+            };
+            let (kvar, pct_p) = self.next_set_points(&inverter, vpu, vreg, dt);
+            let state = &mut self.ders[index];
+            state.vreg = Some(vreg);
+            state.last_time = Some(now);
+            if (kvar - inverter.kvar).abs() <= KVAR_TOLERANCE * inverter.kva && pct_p.is_none() {
+                continue;
+            }
+            state.kvar = Some(kvar);
+            state.pct_p = pct_p;
+            if state.pending.is_none() {
+                state.pending = Some(queue.push(0.0, 0, index, id));
+            }
+        }
+    }
+
+    // Applies the set points of one inverter
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some(state) = self.ders.get_mut(action.proxy) else {
+            return;
+        };
+        state.pending = None;
+        let (kvar, pct_p) = (state.kvar.take(), state.pct_p.take());
+        let Some(element) = circuit.element_mut(state.id) else {
+            return;
+        };
+        let name = element.full_name();
+        apply_set_points(element, kvar, pct_p);
+        if self.event_log
+            && let Some(kvar) = kvar
+        {
+            queue.log(
+                &self.full_name(),
+                &format!("{} kvar set to {:.3}", name, kvar),
+            );
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.ders.clear();
+    }
+}
+
+impl DssClass for ExpControlClass {
+    fn name(&self) -> &'static str {
+        "ExpControl"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(ExpControl::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::pv_system::{PVSystem, PVSystemClass};
+    use crate::test_util::{edited, placed};
+
+    // A circuit with one 1-phase 10 kVA PVSystem on node 1
+    fn circuit_with_pv(properties: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let pv = edited(
+            PVSystem::new("pv1"),
+            &PVSystemClass,
+            &format!("phases=1 kv=1 pmpp=10 kva=10 {}", properties),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(pv, &[&[1, 0]]).unwrap()));
+        circuit
+    }
+
+    fn new_control(properties: &str, circuit: &Circuit) -> ExpControl {
+        edited(ExpControl::new("ec"), &ExpControlClass, properties, circuit).unwrap()
+    }
+
+    fn at(vpu: f64) -> Vec<Complex64> {
+        vec![Complex64::new(0.0, 0.0), Complex64::new(1000.0 * vpu, 0.0)]
+    }
+
+    // Samples and carries out what comes due; the number of actions taken
+    fn iterate(
+        control: &mut ExpControl,
+        circuit: &mut Circuit,
+        vpu: f64,
+        queue: &mut ControlQueue,
+    ) -> usize {
+        control.sample(99, circuit, &at(vpu), queue);
+        let mut count = 0;
+        while let Some(action) = queue.pop_due() {
+            control.do_pending_action(&action, circuit, &at(vpu), queue);
+            count += 1;
+        }
+        count
+    }
+
+    fn pv(circuit: &Circuit) -> &PVSystem {
+        circuit
+            .find_object_as::<PVSystem>("pvsystem", "pv1")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_slope_and_limits() {
+        let mut circuit = circuit_with_pv("irradiance=0.6");
+        let mut control = new_control("vregtau=0 deltaq_factor=0.5", &circuit);
+        let mut queue = ControlQueue::new();
+
+        // 4 mV high calls for -0.2 pu, half way each iteration
+        assert_eq!(iterate(&mut control, &mut circuit, 1.004, &mut queue), 1);
+        assert!((pv(&circuit).get_kvar() + 1.0).abs() < 1e-9);
+        iterate(&mut control, &mut circuit, 1.004, &mut queue);
+        assert!((pv(&circuit).get_kvar() + 1.5).abs() < 1e-9);
+
+        // far above vreg the kvar stops at qmaxlead
+        for _ in 0..30 {
+            iterate(&mut control, &mut circuit, 1.03, &mut queue);
+        }
+        assert!((pv(&circuit).get_kvar() + 4.4).abs() < 1e-2);
+        assert_eq!(iterate(&mut control, &mut circuit, 1.03, &mut queue), 0);
+
+        // preferring Q curtails the output to make room for it
+        let mut circuit = circuit_with_pv("irradiance=1");
+        let mut control = new_control("vregtau=0 deltaq_factor=1 preferq=yes", &circuit);
+        iterate(&mut control, &mut circuit, 1.03, &mut queue);
+        assert!((pv(&circuit).get_kvar() + 4.4).abs() < 1e-9);
+        let room = (100.0 - 4.4 * 4.4_f64).sqrt();
+        assert!((pv(&circuit).get_pct_pmpp() - room * 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_adaptive_vreg() {
+        let mut circuit = circuit_with_pv("irradiance=0.6");
+        let mut control = new_control("vreg=0 vregtau=100 deltaq_factor=1", &circuit);
+        let mut queue = ControlQueue::new();
+
+        // vreg=0 takes the first voltage, so nothing to do there
+        assert_eq!(iterate(&mut control, &mut circuit, 1.02, &mut queue), 0);
+        assert_eq!(control.get_vreg(0), Some(1.02));
+
+        // one time constant later the reference has moved 63% of the way
+        queue.set_time(0, 100.0);
+        iterate(&mut control, &mut circuit, 1.0, &mut queue);
+        let vreg = 1.02 - 0.02 * (1.0 - (-1.0_f64).exp());
+        assert!((control.get_vreg(0).unwrap() - vreg).abs() < 1e-12);
+        assert!((pv(&circuit).get_kvar() - 50.0 * (vreg - 1.0) * 10.0).abs() < 1e-9);
+
+        // and no further than vregmin
+        queue.set_time(0, 10000.0);
+        iterate(&mut control, &mut circuit, 0.9, &mut queue);
+        assert_eq!(control.get_vreg(0), Some(0.95));
+    }
+}
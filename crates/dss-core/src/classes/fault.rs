@@ -0,0 +1,419 @@
+// Fault (Pascal TFault): a resistive short between the conductors of its two
+// terminals. With only bus1 given the phases go to ground; bus1=b.1 bus2=b.2
+// makes a phase-to-phase fault. A fault may wait for its on-time, and a
+// temporary fault clears once its current falls below minamps.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::bus::parse_bus_spec;
+use crate::ckt_element::{CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::line::read_matrix;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, interpret_yes_no};
+
+const FAULT_PROPERTIES: [PropertyDef; 9] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of first bus. Examples: bus1=busname bus1=busname.1.2.3 Bus2 automatically defaults to busname.0,0,0 unless it was previously defined. ",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of 2nd bus of the 2-terminal Fault object. Defaults to all phases connected to first bus, node 0, if not specified. (Shunt Wye Connection to ground reference) That is, the Fault defaults to a ground fault unless otherwise specified.",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of Phases. Default is 1.",
+    },
+    PropertyDef {
+        name: "r",
+        kind: PropertyKind::Double,
+        default: "0.0001",
+        help: "Resistance, each phase, ohms. Default is 0.0001. Assumed to be Mean value if gaussian random mode.Max value if uniform mode.  A Fault is actually a series resistance that defaults to a wye connection to ground on the second terminal.  You may reconnect the 2nd terminal to achieve whatever connection.  Use the Gmatrix property to specify an arbitrary conductance matrix.",
+    },
+    PropertyDef {
+        name: "%stddev",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Percent standard deviation in resistance to assume for Monte Carlo fault (MF) solution mode for GAUSSIAN distribution. Default is 0 (no variation from mean).",
+    },
+    PropertyDef {
+        name: "gmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Use this to specify a nodal conductance (G) matrix to represent some arbitrary resistance network. Specify in lower triangle form as usual for DSS matrices.",
+    },
+    PropertyDef {
+        name: "ontime",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Time (sec) at which the fault is established for time varying simulations. Default is 0.0 (on at the beginning of the simulation)",
+    },
+    PropertyDef {
+        name: "temporary",
+        kind: PropertyKind::Bool,
+        default: "No",
+        help: "{Yes | No} Default is No.  Designate whether the fault is temporary.  For Time-varying simulations, the fault will be removed if the current through the fault drops below the MINAMPS criteria.",
+    },
+    PropertyDef {
+        name: "minamps",
+        kind: PropertyKind::Double,
+        default: "5",
+        help: "Minimum amps that can sustain a temporary fault. Default is 5.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 16] = concat_properties(&FAULT_PROPERTIES, &PD_PROPERTIES);
+
+#[derive(Debug)]
+pub struct FaultClass;
+
+#[derive(Debug, Clone)]
+pub struct Fault {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pd: PdElementBase,
+    r: f64,
+    // Resistance in effect, r or a random draw about it
+    r_actual: f64,
+    pct_stddev: f64,
+    // Conductance matrix, siemens
+    gmatrix: Option<CMatrix>,
+    on_time: f64,
+    temporary: bool,
+    min_amps: f64,
+    is_on: bool,
+    bus2_given: bool,
+}
+
+impl Fault {
+    pub fn new(name: &str) -> Self {
+        Fault {
+            base: ObjectBase::new("Fault", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 2),
+            pd: PdElementBase::new(),
+            r: 0.0001,
+            r_actual: 0.0001,
+            pct_stddev: 0.0,
+            gmatrix: None,
+            on_time: 0.0,
+            temporary: false,
+            min_amps: 5.0,
+            is_on: true,
+            bus2_given: false,
+        }
+    }
+
+    pub fn get_r(&self) -> f64 {
+        self.r
+    }
+
+    pub fn get_on_time(&self) -> f64 {
+        self.on_time
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+
+    pub fn get_min_amps(&self) -> f64 {
+        self.min_amps
+    }
+
+    // Whether the fault is in place now
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    pub fn set_on(&mut self, on: bool) {
+        if self.is_on != on {
+            self.is_on = on;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    // Resistance of a Monte Carlo draw, given a standard normal deviate
+    // (Pascal Randomize)
+    pub fn randomize(&mut self, deviate: f64) {
+        self.r_actual = (self.r * (1.0 + self.pct_stddev / 100.0 * deviate)).max(0.0);
+        self.ckt.invalidate_yprim();
+    }
+
+    // Puts the fault on once the simulation reaches its on-time; true if
+    // that changed it (Pascal CheckStatus)
+    pub fn check_status(&mut self, time: f64) -> bool {
+        let on = time >= self.on_time;
+        if !self.is_on && on {
+            self.set_on(true);
+            return true;
+        }
+        false
+    }
+
+    // Clears a temporary fault whose current has dropped below minamps;
+    // true if it cleared
+    pub fn check_clearing(&mut self, currents: &[Complex64]) -> bool {
+        if !self.temporary || !self.is_on {
+            return false;
+        }
+        let nconds = self.ckt.nconds();
+        if currents
+            .iter()
+            .take(nconds)
+            .all(|i| i.norm() < self.min_amps)
+        {
+            self.set_on(false);
+            return true;
+        }
+        false
+    }
+
+    // Bus2 follows bus1 with its nodes grounded unless given
+    fn set_default_bus2(&mut self) {
+        if self.bus2_given {
+            return;
+        }
+        let bus1 = self.ckt.get_bus(0);
+        let name = bus1.split('.').next().unwrap_or("").to_string();
+        if !name.is_empty() {
+            let bus2 = format!("{}{}", name, ".0".repeat(self.ckt.nphases()));
+            self.ckt.set_bus(1, &bus2);
+        }
+    }
+}
+
+impl DssObject for Fault {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = FAULT_PROPERTIES.get(index) else {
+            return self.set_pd_property(index - FAULT_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "bus2" => {
+                self.ckt.set_bus(1, parser.get_token());
+                self.bus2_given = true;
+            }
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.gmatrix = None;
+            }
+            "r" => {
+                self.r = parser.make_double()?;
+                self.gmatrix = None;
+            }
+            "%stddev" => self.pct_stddev = parser.make_double()?,
+            "gmatrix" => {
+                let mut gmatrix = CMatrix::new(self.ckt.nphases());
+                read_matrix(parser, &mut gmatrix, 1.0, false)?;
+                self.gmatrix = Some(gmatrix);
+            }
+            "ontime" => {
+                self.on_time = parser.make_double()?;
+                self.is_on = self.on_time <= 0.0;
+            }
+            "temporary" => self.temporary = interpret_yes_no(parser.get_token()),
+            "minamps" => self.min_amps = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.gmatrix.is_none() && self.r <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs r > 0", self.full_name()),
+            ));
+        }
+        self.r_actual = self.r;
+        self.set_default_bus2();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Fault {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        Some(self)
+    }
+
+    // The conductance between the terminals while the fault is on, nothing
+    // otherwise; the same at every frequency
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        let nphases = self.ckt.nphases();
+        let mut g = CMatrix::new(nphases);
+        if self.is_on {
+            match &self.gmatrix {
+                Some(gmatrix) => g = gmatrix.clone(),
+                None => {
+                    for phase in 0..nphases {
+                        g.set(phase, phase, Complex64::new(1.0 / self.r_actual, 0.0));
+                    }
+                }
+            }
+        }
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for i in 0..nphases {
+            for j in 0..nphases {
+                let value = g.get(i, j);
+                yprim.set(i, j, value);
+                yprim.set(i + nphases, j + nphases, value);
+                yprim.set(i, j + nphases, -value);
+                yprim.set(i + nphases, j, -value);
+            }
+        }
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+}
+
+impl PdElement for Fault {
+    fn pd_base(&self) -> &PdElementBase {
+        &self.pd
+    }
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase {
+        &mut self.pd
+    }
+
+    // A fault to ground or between conductors of one bus
+    fn is_shunt(&self) -> bool {
+        let nphases = self.ckt.nphases() as u32;
+        match (
+            parse_bus_spec(self.ckt.get_bus(0), nphases),
+            parse_bus_spec(self.ckt.get_bus(1), nphases),
+        ) {
+            (Ok((bus1, _)), Ok((bus2, nodes))) => {
+                bus1.eq_ignore_ascii_case(&bus2) || nodes.iter().all(|&node| node == 0)
+            }
+            _ => true,
+        }
+    }
+}
+
+impl DssClass for FaultClass {
+    fn name(&self) -> &'static str {
+        "Fault"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Fault::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{edited, placed};
+
+    fn new_fault(properties: &str) -> DssResult<Fault> {
+        let fault = edited(
+            Fault::new("f1"),
+            &FaultClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        placed(fault, &[])
+    }
+
+    fn yprim(fault: &Fault) -> &CMatrix {
+        fault.ckt_base().get_yprim().unwrap()
+    }
+
+    #[test]
+    fn test_connections() {
+        let ground = new_fault("bus1=b.2 r=0.5").unwrap();
+        assert_eq!(ground.get_bus(1), "b.0");
+        assert!(ground.is_shunt());
+        assert_eq!(yprim(&ground).get(0, 0), Complex64::new(2.0, 0.0));
+        assert_eq!(yprim(&ground).get(0, 1), Complex64::new(-2.0, 0.0));
+
+        let phase_phase = new_fault("bus1=b.1 bus2=b.2 r=1").unwrap();
+        assert_eq!(phase_phase.get_bus(1), "b.2");
+        assert!(phase_phase.is_shunt());
+
+        let three_phase = new_fault("bus1=b phases=3").unwrap();
+        assert_eq!(three_phase.get_bus(1), "b.0.0.0");
+        assert_eq!(yprim(&three_phase).get(2, 5), Complex64::new(-1e4, 0.0));
+
+        let matrix = new_fault("phases=2 gmatrix=[10 | -5 10]").unwrap();
+        assert_eq!(yprim(&matrix).get(1, 0), Complex64::new(-5.0, 0.0));
+
+        assert!(new_fault("r=0").is_err());
+    }
+
+    #[test]
+    fn test_time_behaviour() {
+        let mut fault = new_fault("bus1=b r=1 ontime=0.5 temporary=yes minamps=10").unwrap();
+        assert!(!fault.is_on());
+        assert_eq!(yprim(&fault).get(0, 0), Complex64::new(0.0, 0.0));
+        assert!(!fault.check_status(0.2));
+        assert!(fault.check_status(0.5));
+        assert!(fault.ckt_base().get_yprim().is_none());
+
+        let high = [Complex64::new(100.0, 0.0), Complex64::new(-100.0, 0.0)];
+        assert!(!fault.check_clearing(&high));
+        let low = [Complex64::new(4.0, 3.0), Complex64::new(-4.0, -3.0)];
+        assert!(fault.check_clearing(&low));
+        assert!(!fault.is_on());
+
+        let mut random = new_fault("r=2 %stddev=10").unwrap();
+        random.randomize(-1.5);
+        random.calc_yprim(60.0).unwrap();
+        assert!((yprim(&random).get(0, 0).re - 1.0 / 1.7).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,558 @@
+// Fuse (Pascal TFuse): a fuse in a terminal of an element, melting by the
+// current of a terminal of another element, by default the same one. Each
+// phase melts on its own, by the time its TCC curve gives for its current
+// in multiples of the rated current. Between samples a phase carrying more
+// than the curve's minimum melting current uses up the fraction of its
+// melting time that has passed, so a current that changes over a time or
+// dynamics simulation melts the fuse as its I²t adds up; a phase whose
+// current falls below the minimum cools back to unmelted. A melted phase
+// blows, opening its conductor, and stays open until closed by the action
+// property, which replaces the fuse.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::tcc_curve::{find_tcc_points, tcc_time};
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+use crate::registry::ElementId;
+
+const FUSE_PROPERTIES: [PropertyDef; 9] = [
+    PropertyDef {
+        name: "monitoredobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line, transformer, load, or generator, to which the Fuse is connected. This is the \"monitored\" element. There is no default; must be specified.",
+    },
+    PropertyDef {
+        name: "monitoredterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the Fuse is connected. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "switchedobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of circuit element switch that the Fuse controls. Specify the full object name.Defaults to the same as the Monitored element. This is the \"controlled\" element.",
+    },
+    PropertyDef {
+        name: "switchedterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the controlled element in which the switch is controlled by the Fuse. 1 or 2, typically.  Default is 1.  Assumes all phases of the element have a fuse of this type.",
+    },
+    PropertyDef {
+        name: "fusecurve",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "tlink",
+        help: "Name of the TCC Curve object that determines the fuse blowing.  Must have been previously defined as a TCC_Curve object. Default is \"Tlink\". Multiplying the current values in the curve by the \"RatedCurrent\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "ratedcurrent",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Multiplier or actual phase amps for the phase TCC curve.  Defaults to 1.0.",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Fixed delay time (sec) added to Fuse blowing time determined from the TCC curve. Default is 0.0. Used to represent fuse clearing time or any other delay.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(ACTION_NAMES),
+        default: "",
+        help: "{Open | Close} Action that overrides the Fuse control. Simulates manual control on Fuse \"Open\" or \"Close\" the fuse. \"Close\" replaces the fuse in every phase.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True* | No/False} Default is Yes for Fuse. Write blown phases and replacements to EventLog.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 11] = concat_properties(&FUSE_PROPERTIES, &CKT_PROPERTIES);
+
+// Values of the action property
+const ACTION_NAMES: &[&str] = &["open", "close"];
+
+// Codes of the queued actions; the proxy of a blow is its phase
+const ACTION_BLOW: i32 = 1;
+const ACTION_OPEN: i32 = 2;
+const ACTION_CLOSE: i32 = 3;
+
+// Melting of one phase
+#[derive(Debug, Clone, Copy, Default)]
+struct PhaseMelt {
+    // Fraction of the melting time used up
+    melted: f64,
+    // Solution time and melting time at the last sample, while melting
+    last: Option<(f64, f64)>,
+    // The queued blow
+    pending: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct FuseClass;
+
+#[derive(Debug, Clone)]
+pub struct Fuse {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    // Full name, lower case; empty for the monitored element
+    switched_obj: String,
+    // From 1
+    switched_term: usize,
+    fuse_curve: String,
+    // Points (multiple of the rated current, seconds) of the fuse curve, as
+    // resolved from the named TCC curve
+    curve_points: Vec<(f64, f64)>,
+    rated_current: f64,
+    delay: f64,
+    event_log: bool,
+    // Action given by the action property, carried out at the next sample
+    command: Option<i32>,
+    phases: Vec<PhaseMelt>,
+}
+
+impl Fuse {
+    pub fn new(name: &str) -> Self {
+        Fuse {
+            base: ObjectBase::new("Fuse", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            switched_obj: String::new(),
+            switched_term: 1,
+            fuse_curve: "tlink".to_string(),
+            curve_points: Vec::new(),
+            rated_current: 1.0,
+            delay: 0.0,
+            event_log: true,
+            command: None,
+            phases: Vec::new(),
+        }
+    }
+
+    // Full name of the element the fuse opens
+    pub fn get_switched_obj(&self) -> &str {
+        if self.switched_obj.is_empty() {
+            self.control.get_element()
+        } else {
+            &self.switched_obj
+        }
+    }
+
+    pub fn get_fuse_curve(&self) -> &str {
+        &self.fuse_curve
+    }
+
+    pub fn set_fuse_curve(&mut self, points: Vec<(f64, f64)>) {
+        self.curve_points = points;
+    }
+
+    // Fraction of the melting time a phase (from 0) has used up
+    pub fn get_melted(&self, phase: usize) -> f64 {
+        self.phases.get(phase).map_or(0.0, |phase| phase.melted)
+    }
+
+    fn switched_element(&self, circuit: &Circuit) -> Option<ElementId> {
+        find_by_full_name(circuit, self.get_switched_obj())
+    }
+
+    fn log(&self, queue: &mut ControlQueue, action: &str) {
+        if self.event_log {
+            queue.log(&self.full_name(), action);
+        }
+    }
+
+    fn cancel_all(&mut self, queue: &mut ControlQueue) {
+        for phase in &mut self.phases {
+            if let Some(handle) = phase.pending.take() {
+                queue.delete(handle);
+            }
+        }
+    }
+}
+
+impl DssObject for Fuse {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = FUSE_PROPERTIES.get(index) else {
+            return self.ckt.set_property(index - FUSE_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "monitoredobj" => self.control.set_element(parser.get_token()),
+            "monitoredterm" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "switchedobj" => self.switched_obj = parser.get_token().to_lowercase(),
+            "switchedterm" => self.switched_term = parser.make_integer()?.max(1) as usize,
+            "fusecurve" => self.fuse_curve = parser.get_token().to_lowercase(),
+            "ratedcurrent" => self.rated_current = parser.make_double()?,
+            "delay" => self.delay = parser.make_double()?,
+            "action" => {
+                let name = read_choice(parser, ACTION_NAMES, "action")?;
+                self.command = Some(match name {
+                    "close" => ACTION_CLOSE,
+                    _ => ACTION_OPEN,
+                });
+            }
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The fuse curve must be a TCC curve already defined
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        if FUSE_PROPERTIES.get(index).map(|property| property.name) != Some("fusecurve") {
+            return Ok(());
+        }
+        self.curve_points = match self.fuse_curve.as_str() {
+            "" => Vec::new(),
+            name => find_tcc_points(circuit, name, &self.base.full_name())?,
+        };
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Fuse {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for Fuse {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Adds up the melting of each closed phase since the last sample and
+    // queues its blow at the time left at the present current
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if let Some(code) = self.command.take() {
+            self.cancel_all(queue);
+            queue.push(0.0, code, 0, id);
+            return;
+        }
+        let (Some(switched), Some(element)) = (
+            self.switched_element(circuit)
+                .and_then(|switched| circuit.element(switched)?.as_ckt_element()),
+            self.control
+                .find_element(circuit)
+                .and_then(|element| circuit.element(element)?.as_ckt_element()),
+        ) else {
+            return;
+        };
+        let nphases = element.nphases();
+        self.phases.resize(nphases, PhaseMelt::default());
+        let first = (self.control.get_terminal() - 1) * element.nconds();
+        let currents = element.get_currents(voltages);
+        let (hour, sec) = queue.get_time();
+        let now = hour as f64 * 3600.0 + sec;
+        for phase in 0..nphases {
+            let closed = switched.ckt_base().is_closed(self.switched_term - 1, phase);
+            let amps = currents.get(first + phase).map_or(0.0, |i| i.norm());
+            let melt_time = (closed && self.rated_current > 0.0)
+                .then(|| tcc_time(&self.curve_points, amps / self.rated_current))
+                .flatten()
+                .filter(|&time| time > 0.0);
+            let state = &mut self.phases[phase];
+            if let Some((last, last_melt_time)) = state.last {
+                state.melted += (now - last) / last_melt_time;
+            }
+            if let Some(handle) = state.pending.take() {
+                queue.delete(handle);
+            }
+            match melt_time {
+                Some(melt_time) => {
+                    state.last = Some((now, melt_time));
+                    let left = (1.0 - state.melted).max(0.0) * melt_time;
+                    state.pending = Some(queue.push(left + self.delay, ACTION_BLOW, phase, id));
+                }
+                None => {
+                    state.last = None;
+                    if closed {
+                        state.melted = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    // Blows a phase, or opens or replaces every phase on command
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some(switched) = self.switched_element(circuit) else {
+            return;
+        };
+        let terminal = self.switched_term - 1;
+        let Some(element) = circuit
+            .element_mut(switched)
+            .and_then(|element| element.as_ckt_element_mut())
+        else {
+            return;
+        };
+        match action.code {
+            ACTION_BLOW => {
+                element
+                    .ckt_base_mut()
+                    .set_closed(terminal, Some(action.proxy), false);
+                if let Some(state) = self.phases.get_mut(action.proxy) {
+                    *state = PhaseMelt {
+                        melted: 1.0,
+                        ..PhaseMelt::default()
+                    };
+                }
+                self.log(queue, &format!("Phase {} blown", action.proxy + 1));
+            }
+            ACTION_OPEN => {
+                element.ckt_base_mut().set_closed(terminal, None, false);
+                self.log(queue, "Opened by command");
+            }
+            ACTION_CLOSE => {
+                element.ckt_base_mut().set_closed(terminal, None, true);
+                self.phases.fill(PhaseMelt::default());
+                self.log(queue, "Closed by command, fuses replaced");
+            }
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self, circuit: &mut Circuit) {
+        if let Some(element) = self
+            .switched_element(circuit)
+            .and_then(|switched| circuit.element_mut(switched))
+            .and_then(|element| element.as_ckt_element_mut())
+        {
+            element
+                .ckt_base_mut()
+                .set_closed(self.switched_term - 1, None, true);
+        }
+        self.phases.clear();
+    }
+}
+
+impl DssClass for FuseClass {
+    fn name(&self) -> &'static str {
+        "Fuse"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Fuse::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use dss_common::codes;
+
+    use super::*;
+    use crate::classes::line::{Line, LineClass};
+    use crate::classes::tcc_curve::{TCCCurve, TCCCurveClass};
+    use crate::test_util::{edit, edited, placed, run_to};
+
+    // A 3-phase line of 1+1j ohm per phase from nodes 1-3 to nodes 4-6
+    fn circuit_with_line() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("l1"),
+            &LineClass,
+            "r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(line, &[&[1, 2, 3], &[4, 5, 6]]).unwrap()));
+        circuit
+    }
+
+    fn new_fuse(properties: &str, circuit: &Circuit) -> Fuse {
+        let mut fuse = edited(
+            Fuse::new("f1"),
+            &FuseClass,
+            &format!("monitoredobj=line.l1 {}", properties),
+            circuit,
+        )
+        .unwrap();
+        // 4 s at twice the rating, 1 s at four times: constant I²t
+        fuse.set_fuse_curve(vec![(1.5, 64.0 / 9.0), (2.0, 4.0), (4.0, 1.0), (8.0, 0.25)]);
+        fuse
+    }
+
+    // Balanced 7.2 kV with `amps` through each phase, the first phase
+    // carrying `phase1` instead
+    fn voltages(amps: f64, phase1: f64) -> Vec<Complex64> {
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        let sending = [Complex64::from(7200.0), a * 7200.0, a * a * 7200.0];
+        voltages.extend(sending);
+        for (phase, v) in sending.iter().enumerate() {
+            let amps = if phase == 0 { phase1 } else { amps };
+            voltages.push(v * (1.0 - amps * 2.0_f64.sqrt() / 7200.0));
+        }
+        voltages
+    }
+
+    fn phase_closed(circuit: &Circuit, phase: usize) -> bool {
+        circuit
+            .find_object_as::<Line>("line", "l1")
+            .unwrap()
+            .ckt_base()
+            .is_closed(0, phase)
+    }
+
+    #[test]
+    fn test_phase_blows_alone() {
+        let mut circuit = circuit_with_line();
+        let mut fuse = new_fuse("ratedcurrent=100 delay=0.05", &circuit);
+        let mut queue = ControlQueue::new();
+        fuse.sample(99, &circuit, &voltages(50.0, 400.0), &mut queue);
+        assert_eq!(queue.len(), 1);
+        run_to(&mut fuse, &mut circuit, &mut queue, 1.0);
+        assert!(phase_closed(&circuit, 0));
+        run_to(&mut fuse, &mut circuit, &mut queue, 1.05);
+        assert!(!phase_closed(&circuit, 0));
+        assert!(phase_closed(&circuit, 1) && phase_closed(&circuit, 2));
+        assert_eq!(queue.events().events()[0].action, "Phase 1 blown");
+
+        // replaced by command
+        edit(&mut fuse, &FuseClass, "action=close", &circuit).unwrap();
+        fuse.sample(99, &circuit, &voltages(50.0, 50.0), &mut queue);
+        run_to(&mut fuse, &mut circuit, &mut queue, 1.05);
+        assert!(phase_closed(&circuit, 0));
+        assert_eq!(fuse.get_melted(0), 0.0);
+    }
+
+    #[test]
+    fn test_melting_adds_up() {
+        let mut circuit = circuit_with_line();
+        let mut fuse = new_fuse("ratedcurrent=100", &circuit);
+        let mut queue = ControlQueue::new();
+        // half the melting time at twice the rating
+        fuse.sample(99, &circuit, &voltages(200.0, 200.0), &mut queue);
+        run_to(&mut fuse, &mut circuit, &mut queue, 2.0);
+        // then at four times the rating the quarter second left melts it
+        fuse.sample(99, &circuit, &voltages(400.0, 400.0), &mut queue);
+        assert!((fuse.get_melted(0) - 0.5).abs() < 1e-9);
+        let (_, due) = queue.next_time().unwrap();
+        assert!((due - 2.5).abs() < 1e-9);
+        run_to(&mut fuse, &mut circuit, &mut queue, 2.6);
+        assert!((0..3).all(|phase| !phase_closed(&circuit, phase)));
+        assert_eq!(queue.events().len(), 3);
+
+        // below the minimum melting current a phase cools off
+        let circuit = circuit_with_line();
+        let mut fuse = new_fuse("ratedcurrent=100", &circuit);
+        fuse.sample(99, &circuit, &voltages(200.0, 200.0), &mut queue);
+        queue.set_time(0, 4.0);
+        fuse.sample(99, &circuit, &voltages(100.0, 100.0), &mut queue);
+        assert_eq!(fuse.get_melted(0), 0.0);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_fuse_curve() {
+        let mut circuit = circuit_with_line();
+        let curve = edited(
+            TCCCurve::new("tlink"),
+            &TCCCurveClass,
+            "c_array=[2 4 8] t_array=[4 1 0.25]",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(curve));
+
+        let mut fuse = edited(
+            Fuse::new("f1"),
+            &FuseClass,
+            "monitoredobj=line.l1 fusecurve=TLink",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(fuse.curve_points, [(2.0, 4.0), (4.0, 1.0), (8.0, 0.25)]);
+
+        let err = edit(&mut fuse, &FuseClass, "fusecurve=k10", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+}
@@ -0,0 +1,404 @@
+// GenDispatcher (Pascal TGenDispatcher): holds the power through a terminal
+// of an element near kwlimit and kvarlimit by redispatching a fleet of
+// generators. When the kW or kvar is off its limit by more than half of
+// kwband, the difference is shared out to the generators by their weights
+// and the new outputs are queued at once, so the solution iterates until
+// the power settles inside the band.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::generator::Generator;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, parse_names, read_doubles};
+use crate::registry::ElementId;
+
+const GEN_DISPATCHER_PROPERTIES: [PropertyDef; 7] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line or transformer, which the control is monitoring. There is no default; must be specified.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the GenDispatcher control is connected. 1 or 2, typically.  Default is 1. Make sure you have the direction on the power matching the sign of kWLimit.",
+    },
+    PropertyDef {
+        name: "kwlimit",
+        kind: PropertyKind::Double,
+        default: "8000",
+        help: "kW Limit for the monitored element. The generators are dispatched to hold the power in band.",
+    },
+    PropertyDef {
+        name: "kwband",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Bandwidth (kW) of the dead band around the target limit.No dispatch changes are attempted if the power in the monitored terminal stays within this band.",
+    },
+    PropertyDef {
+        name: "kvarlimit",
+        kind: PropertyKind::Double,
+        default: "4000",
+        help: "Max kvar to be delivered through the element.  Uses same dead band as kW.",
+    },
+    PropertyDef {
+        name: "genlist",
+        kind: PropertyKind::Objects("Generator"),
+        default: "",
+        help: "Array list of generators to be dispatched.  If not specified, all generators in the circuit are assumed dispatchable.",
+    },
+    PropertyDef {
+        name: "weights",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of proportional weights corresponding to each generator in the GenList. The needed kW to get back to center band is dispatched to each generator according to these weights. Default is to set all weights to 1.0.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 9] =
+    concat_properties(&GEN_DISPATCHER_PROPERTIES, &CKT_PROPERTIES);
+
+#[derive(Debug)]
+pub struct GenDispatcherClass;
+
+#[derive(Debug, Clone)]
+pub struct GenDispatcher {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    kw_limit: f64,
+    kw_band: f64,
+    kvar_limit: f64,
+    gen_list: Vec<String>,
+    weights: Vec<f64>,
+    // New (kW, kvar) of each generator the queued dispatch will set
+    dispatch: Vec<(ElementId, f64, f64)>,
+    pending: Option<usize>,
+}
+
+impl GenDispatcher {
+    pub fn new(name: &str) -> Self {
+        GenDispatcher {
+            base: ObjectBase::new("GenDispatcher", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            kw_limit: 8000.0,
+            kw_band: 100.0,
+            kvar_limit: 4000.0,
+            gen_list: Vec::new(),
+            weights: Vec::new(),
+            dispatch: Vec::new(),
+            pending: None,
+        }
+    }
+
+    pub fn get_kw_limit(&self) -> f64 {
+        self.kw_limit
+    }
+
+    pub fn get_kvar_limit(&self) -> f64 {
+        self.kvar_limit
+    }
+
+    // Generators of the fleet with their weights
+    pub fn fleet(&self, circuit: &Circuit) -> Vec<(ElementId, f64)> {
+        let ids: Vec<ElementId> = if self.gen_list.is_empty() {
+            circuit.class_elements("generator").to_vec()
+        } else {
+            self.gen_list
+                .iter()
+                .filter_map(|name| match name.contains('.') {
+                    true => find_by_full_name(circuit, name),
+                    false => circuit.find_element("generator", name),
+                })
+                .collect()
+        };
+        ids.into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, self.weights.get(index).copied().unwrap_or(1.0)))
+            .collect()
+    }
+}
+
+impl DssObject for GenDispatcher {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = GEN_DISPATCHER_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - GEN_DISPATCHER_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => self.control.set_element(parser.get_token()),
+            "terminal" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "kwlimit" => self.kw_limit = parser.make_double()?,
+            "kwband" => self.kw_band = parser.make_double()?,
+            "kvarlimit" => self.kvar_limit = parser.make_double()?,
+            "genlist" => self.gen_list = parse_names(parser.get_token()),
+            "weights" => self.weights = read_doubles(parser)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for GenDispatcher {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for GenDispatcher {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Shares out the kW and kvar outside the band; a positive difference
+    // means the generators must give more
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some(element) = self
+            .control
+            .find_element(circuit)
+            .and_then(|element| circuit.element(element)?.as_ckt_element())
+        else {
+            return;
+        };
+        let power = element
+            .terminal_powers(voltages)
+            .get(self.control.get_terminal() - 1)
+            .copied()
+            .unwrap_or_default()
+            / 1000.0;
+        let half_band = self.kw_band / 2.0;
+        let p_diff = power.re - self.kw_limit;
+        let q_diff = power.im - self.kvar_limit;
+        let redispatch_p = p_diff.abs() > half_band;
+        let redispatch_q = q_diff.abs() > half_band;
+        if !redispatch_p && !redispatch_q {
+            return;
+        }
+        let fleet: Vec<(ElementId, f64, &Generator)> = self
+            .fleet(circuit)
+            .into_iter()
+            .filter_map(|(gen_id, weight)| {
+                let generator = circuit
+                    .element(gen_id)?
+                    .as_any()
+                    .downcast_ref::<Generator>()?;
+                Some((gen_id, weight, generator))
+            })
+            .collect();
+        let total_weight: f64 = fleet.iter().map(|(_, weight, _)| weight).sum();
+        if total_weight <= 0.0 {
+            return;
+        }
+        let mut changed = false;
+        self.dispatch = fleet
+            .iter()
+            .map(|&(gen_id, weight, generator)| {
+                let share = weight / total_weight;
+                let mut kw = generator.get_kw();
+                let mut kvar = generator.get_kvar();
+                if redispatch_p {
+                    kw = (kw + p_diff * share).max(0.0);
+                }
+                if redispatch_q {
+                    kvar += q_diff * share;
+                }
+                changed |= kw != generator.get_kw() || kvar != generator.get_kvar();
+                (gen_id, kw, kvar)
+            })
+            .collect();
+        if changed && self.pending.is_none() {
+            self.pending = Some(queue.push(0.0, 0, 0, id));
+        }
+    }
+
+    // Sets the generators to the dispatched output
+    fn do_pending_action(
+        &mut self,
+        _action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        _queue: &mut ControlQueue,
+    ) {
+        self.pending = None;
+        for (gen_id, kw, kvar) in self.dispatch.drain(..) {
+            let Some(generator) = circuit
+                .element_mut(gen_id)
+                .and_then(|element| element.as_any_mut().downcast_mut::<Generator>())
+            else {
+                continue;
+            };
+            if kw != generator.get_kw() {
+                generator.set_kw(kw);
+            }
+            if kvar != generator.get_kvar() {
+                generator.set_kvar(kvar);
+            }
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.dispatch.clear();
+        self.pending = None;
+    }
+}
+
+impl DssClass for GenDispatcherClass {
+    fn name(&self) -> &'static str {
+        "GenDispatcher"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(GenDispatcher::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::generator::GeneratorClass;
+    use crate::classes::load::{Load, LoadClass};
+    use crate::test_util::{balanced_nodes, edited, placed};
+
+    // A 3-phase load of 5000 kW and 1000 kvar on nodes 1-3 and two 1000 kW
+    // generators on the same nodes
+    fn circuit_with_fleet() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let feeder = edited(
+            Load::new("feeder"),
+            &LoadClass,
+            "kw=5000 kvar=1000",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(feeder, &[&[1, 2, 3, 0]]).unwrap()));
+        for name in ["g1", "g2"] {
+            let generator = edited(
+                Generator::new(name),
+                &GeneratorClass,
+                "kw=1000 pf=1",
+                &circuit,
+            )
+            .unwrap();
+            circuit.add_element(Box::new(generator));
+        }
+        circuit
+    }
+
+    fn generator(circuit: &Circuit, name: &str) -> (f64, f64) {
+        let generator = circuit
+            .find_object_as::<Generator>("generator", name)
+            .unwrap();
+        (generator.get_kw(), generator.get_kvar())
+    }
+
+    #[test]
+    fn test_dispatch_by_weight() {
+        // 12.47 kV line to line
+        let nodes = balanced_nodes(12470.0 / 3.0_f64.sqrt());
+        let mut circuit = circuit_with_fleet();
+        let mut dispatcher = edited(
+            GenDispatcher::new("d1"),
+            &GenDispatcherClass,
+            "element=load.feeder kwlimit=4000 kvarlimit=800 kwband=100 genlist=[g1 g2] weights=[3 1]",
+            &circuit,
+        )
+        .unwrap();
+        let mut queue = ControlQueue::new();
+        dispatcher.sample(99, &circuit, &nodes, &mut queue);
+        let action = queue.pop_due().unwrap();
+        dispatcher.do_pending_action(&action, &mut circuit, &[], &mut queue);
+
+        // 1000 kW and 200 kvar over the limits, shared 3 to 1
+        let (kw1, kvar1) = generator(&circuit, "g1");
+        let (kw2, kvar2) = generator(&circuit, "g2");
+        assert!((kw1 - 1750.0).abs() < 1.0 && (kw2 - 1250.0).abs() < 1.0);
+        assert!((kvar1 - 150.0).abs() < 1.0 && (kvar2 - 50.0).abs() < 1.0);
+
+        // inside the band nothing is queued
+        let mut dispatcher = edited(
+            GenDispatcher::new("d2"),
+            &GenDispatcherClass,
+            "element=load.feeder kwlimit=5020 kvarlimit=990",
+            &circuit,
+        )
+        .unwrap();
+        dispatcher.sample(99, &circuit, &nodes, &mut queue);
+        assert!(queue.is_empty());
+    }
+}
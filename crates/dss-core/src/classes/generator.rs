@@ -0,0 +1,1152 @@
+// Generator (Pascal TGenerator): a power conversion element delivering kW
+// and kvar at one terminal, connected like a load. The model sets how the
+// output follows the voltage, from constant P+jQ to a PV bus holding vpu
+// within the kvar limits. Like a load it is its nominal admittance in Yprim
+// plus injection currents for the rest. The machine data (xd, xdp, h, ...)
+// give the Thevenin equivalent and swing state used in dynamics mode.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::classes::load::LoadStatus;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+
+const GENERATOR_PROPERTIES: [PropertyDef; 34] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of Phases, this Generator.  Power is evenly divided among phases.",
+    },
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Bus to which the Generator is connected.  May include specific node specification.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "Nominal rated (1.0 per unit) voltage, kV, for Generator. For 2- and 3-phase Generators, specify phase-phase kV. Otherwise, for phases=1 or phases>3, specify actual kV across each branch of the Generator. If wye (star), specify phase-neutral kV. If delta or phase-phase connected, specify phase-phase kV.",
+    },
+    PropertyDef {
+        name: "kw",
+        kind: PropertyKind::Double,
+        default: "1000",
+        help: "Total base kW for the Generator.  A positive value denotes power coming OUT of the element, which is the opposite of a load. This value is modified depending on the dispatch mode. Unaffected by the global load multiplier and growth curves. If you want there to be more generation, you must add more generators or change this value.",
+    },
+    PropertyDef {
+        name: "pf",
+        kind: PropertyKind::Double,
+        default: "0.88",
+        help: "Generator power factor. Default is 0.88. Enter negative for leading powerfactor (when kW and kvar have opposite signs.) A positive power factor for a generator signifies that the generator produces vars as is typical for a synchronous generator.  Induction machines would be specified with a negative power factor.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Double,
+        default: "539.742",
+        help: "Specify the base kvar.  Alternative to specifying the power factor.  Side effect:  the power factor value is altered to agree based on present value of kW.",
+    },
+    PropertyDef {
+        name: "model",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Integer code for the model to use for generation variation with voltage. Valid values are: 1:Generator injects a constant kW at specified power factor. 2:Generator is modeled as a constant admittance. 3:Const kW, constant kV.  Somewhat like a conventional transmission power flow P-V generator. 4:Const kW, Fixed Q (Q never varies) 5:Const kW, Fixed Q(as a constant reactance) 6:Compute load injection from User-written Model.(see usage of Xd, Xdp) 7:Constant kW, kvar, but current-limited below Vminpu. Approximates a simple inverter. See also Balanced.",
+    },
+    PropertyDef {
+        name: "vminpu",
+        kind: PropertyKind::Double,
+        default: "0.9",
+        help: "Default = 0.90.  Minimum per unit voltage for which the Model is assumed to apply. Below this value, the load model reverts to a constant impedance model. For model 7, the current is limited to the value computed for constant power at Vminpu.",
+    },
+    PropertyDef {
+        name: "vmaxpu",
+        kind: PropertyKind::Double,
+        default: "1.1",
+        help: "Default = 1.10.  Maximum per unit voltage for which the Model is assumed to apply. Above this value, the load model reverts to a constant impedance model.",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for yearly simulations.  Must be previously defined as a Loadshape object. If this is not specified, a constant value is assumed (no variation). If the generator is assumed to be ON continuously, specify Status=FIXED, or designate a curve that is 1.0 per unit at all times. Set to NONE to reset to no loadahape. Nominally for 8760 simulations.  If there are fewer points in the designated shape than the number of points in the solution, the curve is repeated.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for daily simulations.  Must be previously defined as a Loadshape object of 24 hrs, typically.  If generator is assumed to be ON continuously, specify Status=FIXED, or designate a Loadshape object that is 1.0 per unit for all hours. Set to NONE to reset to no loadahape.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Load shape to use for duty cycle dispatch simulations such as for wind generation. Must be previously defined as a Loadshape object. Typically would have time intervals less than 1 hr -- perhaps, in seconds. Set Status=Fixed to ignore Loadshape designation. Set to NONE to reset to no loadahape. Designate the number of points to solve using the Set Number=xxxx command. If there are fewer points in the actual shape, the shape is assumed to repeat.",
+    },
+    PropertyDef {
+        name: "dispmode",
+        kind: PropertyKind::Choice(DispatchMode::NAMES),
+        default: "default",
+        help: "{Default* | Loadlevel | Price } Default = Default. Dispatch mode. In default mode, gen is either always on or follows dispatch curve as specified. Otherwise, the gen comes on when either the global default load level (Loadshape \"default\") or the price level exceeds the dispatch value.",
+    },
+    PropertyDef {
+        name: "dispvalue",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Dispatch value. If = 0.0 (default) then Generator follow dispatch curves, if any. If > 0  then Generator is ON only when either the price signal (in Price dispatch mode) exceeds this value or the active circuit load multiplier * \"default\" loadshape value * the default yearly growth factor exceeds this value.  Then the generator follows dispatch curves (duty, daily, or yearly), if any (see also Status).",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye|LN|delta|LL}.  Default is wye.",
+    },
+    PropertyDef {
+        name: "rneut",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Removed due to causing confusion - Add neutral impedance externally.",
+    },
+    PropertyDef {
+        name: "xneut",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Removed due to causing confusion - Add neutral impedance externally.",
+    },
+    PropertyDef {
+        name: "status",
+        kind: PropertyKind::Choice(&["variable", "fixed"]),
+        default: "variable",
+        help: "={Fixed | Variable*}.  If Fixed, then dispatch multipliers do not apply. The generator is alway at full power when it is ON.  Default is Variable  (follows curves).",
+    },
+    PropertyDef {
+        name: "class",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "An arbitrary integer number representing the class of Generator so that Generator values may be segregated by class.",
+    },
+    PropertyDef {
+        name: "vpu",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per Unit voltage set point for Model = 3  (typical power flow model).  Default is 1.0.",
+    },
+    PropertyDef {
+        name: "maxkvar",
+        kind: PropertyKind::Double,
+        default: "1079.48",
+        help: "Maximum kvar limit for Model = 3.  Defaults to twice the specified load kvar.  Always reset this if you change PF or kvar properties.",
+    },
+    PropertyDef {
+        name: "minkvar",
+        kind: PropertyKind::Double,
+        default: "-1079.48",
+        help: "Minimum kvar limit for Model = 3. Enter a negative number if generator can absorb vars. Defaults to negative of Maxkvar.  Always reset this if you change PF or kvar properties.",
+    },
+    PropertyDef {
+        name: "pvfactor",
+        kind: PropertyKind::Double,
+        default: "0.1",
+        help: "Deceleration factor for P-V generator model (Model=3).  Default is 0.1. If the circuit converges easily, you may want to use a higher number such as 1.0. Use a lower number if solution diverges. Use Debugtrace=yes to create a file that will trace the convergence of a generator model.",
+    },
+    PropertyDef {
+        name: "forceon",
+        kind: PropertyKind::Bool,
+        default: "No",
+        help: "{Yes | No}  Forces generator ON despite requirements of other dispatch modes. Stays ON until this property is set to NO, or an internal algorithm cancels the forced ON state.",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "1200",
+        help: "kVA rating of electrical machine. Defaults to 1.2* kW if not specified. Applied to machine or inverter definition for Dynamics mode solutions.",
+    },
+    PropertyDef {
+        name: "mva",
+        kind: PropertyKind::Double,
+        default: "1.2",
+        help: "MVA rating of electrical machine.  Alternative to using kVA=.",
+    },
+    PropertyDef {
+        name: "xd",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per unit synchronous reactance of machine. Presently used only for Thevinen impedance for power flow calcs of user models (model=6). Typically use a value 0.4 to 1.0. Default is 1.0",
+    },
+    PropertyDef {
+        name: "xdp",
+        kind: PropertyKind::Double,
+        default: "0.28",
+        help: "Per unit transient reactance of the machine.  Used for Dynamics mode and Fault studies.  Default is 0.27.For user models, this value is used for the Thevinen/Norton impedance for Dynamics Mode.",
+    },
+    PropertyDef {
+        name: "xdpp",
+        kind: PropertyKind::Double,
+        default: "0.2",
+        help: "Per unit subtransient reactance of the machine.  Used for Harmonics. Default is 0.20.",
+    },
+    PropertyDef {
+        name: "h",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per unit mass constant of the machine.  MW-sec/MVA.  Default is 1.0.",
+    },
+    PropertyDef {
+        name: "d",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Damping constant.  Usual range is 0 to 4. Default is 1.0.  Adjust to get damping",
+    },
+    PropertyDef {
+        name: "dutystart",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Starting time offset [hours] into the duty cycle shape for this generator, defaults to 0",
+    },
+    PropertyDef {
+        name: "balanced",
+        kind: PropertyKind::Bool,
+        default: "No",
+        help: "{Yes | No*} Default is No.  For Model=7, force balanced current only for 3-phase generators. Force zero- and negative-sequence to zero.",
+    },
+    PropertyDef {
+        name: "xrdp",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Default is 20. X/R ratio for Xdp property for FaultStudy and Dynamic modes.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 37] = concat_properties(&GENERATOR_PROPERTIES, &PC_PROPERTIES);
+
+// When the generator is switched on by the dispatcher (Pascal DispatchMode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchMode {
+    Default,
+    LoadLevel,
+    Price,
+}
+
+impl DispatchMode {
+    pub const NAMES: &'static [&'static str] = &["default", "loadlevel", "price"];
+
+    // Mode of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "loadlevel" => DispatchMode::LoadLevel,
+            "price" => DispatchMode::Price,
+            _ => DispatchMode::Default,
+        }
+    }
+}
+
+// Swing state of the machine for dynamics mode (Pascal TGeneratorVars):
+// the internal voltage behind the transient reactance and its rotor
+#[derive(Debug, Clone, Default)]
+pub struct MachineState {
+    // Rotor angle, radians, and its speed deviation from synchronous, rad/s
+    pub theta: f64,
+    pub speed: f64,
+    // Derivatives of the angle and speed at the last step
+    pub d_theta: f64,
+    pub d_speed: f64,
+    // Mechanical power on the shaft, W
+    pub pshaft: f64,
+    // Voltage magnitude behind Zthev, per phase
+    pub e_mag: f64,
+    // Transient impedance of each phase, ohms
+    pub z_thev: Complex64,
+    // Angle and speed half a step on from the start of the step, which the
+    // trapezoidal rule adds the end of the step to
+    pub theta_history: f64,
+    pub speed_history: f64,
+}
+
+#[derive(Debug)]
+pub struct GeneratorClass;
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    connection: Connection,
+    kv: f64,
+    kw: f64,
+    kvar: f64,
+    pf: f64,
+    // kvar was given last rather than pf
+    kvar_given: bool,
+    model: usize,
+    vminpu: f64,
+    vmaxpu: f64,
+    yearly: String,
+    daily: String,
+    duty: String,
+    dispatch_mode: DispatchMode,
+    dispatch_value: f64,
+    rneut: f64,
+    xneut: f64,
+    status: LoadStatus,
+    gen_class: i32,
+    vpu: f64,
+    max_kvar: f64,
+    min_kvar: f64,
+    pv_factor: f64,
+    force_on: bool,
+    kva: f64,
+    kva_given: bool,
+    xd: f64,
+    xdp: f64,
+    xdpp: f64,
+    h: f64,
+    d: f64,
+    duty_start: f64,
+    balanced: bool,
+    xrdp: f64,
+    // Output multiplier set by the solution from the dispatch shapes
+    gen_mult: f64,
+    is_on: bool,
+    // kvar of each phase the PV model holds the voltage with
+    pv_kvar: f64,
+    // Admittance of each phase in Yprim, as last built
+    y_phase: Complex64,
+    state: MachineState,
+    // In dynamics mode the machine is its internal voltage behind Zthev
+    dynamic: bool,
+}
+
+impl Generator {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 1);
+        ckt.set_conductors(4);
+        let mut generator = Generator {
+            base: ObjectBase::new("Generator", name, &PROPERTIES),
+            ckt,
+            pc: PcElementBase::new("defaultgen"),
+            connection: Connection::Wye,
+            kv: 12.47,
+            kw: 1000.0,
+            kvar: 0.0,
+            pf: 0.88,
+            kvar_given: false,
+            model: 1,
+            vminpu: 0.9,
+            vmaxpu: 1.1,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            dispatch_mode: DispatchMode::Default,
+            dispatch_value: 0.0,
+            rneut: 0.0,
+            xneut: 0.0,
+            status: LoadStatus::Variable,
+            gen_class: 1,
+            vpu: 1.0,
+            max_kvar: 0.0,
+            min_kvar: 0.0,
+            pv_factor: 0.1,
+            force_on: false,
+            kva: 0.0,
+            kva_given: false,
+            xd: 1.0,
+            xdp: 0.28,
+            xdpp: 0.2,
+            h: 1.0,
+            d: 1.0,
+            duty_start: 0.0,
+            balanced: false,
+            xrdp: 20.0,
+            gen_mult: 1.0,
+            is_on: true,
+            pv_kvar: 0.0,
+            y_phase: Complex64::new(0.0, 0.0),
+            state: MachineState::default(),
+            dynamic: false,
+        };
+        generator.set_nominal_power();
+        generator.reset_kvar_limits();
+        generator
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_kw(&self) -> f64 {
+        self.kw
+    }
+
+    pub fn get_kvar(&self) -> f64 {
+        self.kvar
+    }
+
+    pub fn get_pf(&self) -> f64 {
+        self.pf
+    }
+
+    pub fn get_kva(&self) -> f64 {
+        self.kva
+    }
+
+    pub fn get_model(&self) -> usize {
+        self.model
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn get_status(&self) -> LoadStatus {
+        self.status
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    pub fn get_duty_start(&self) -> f64 {
+        self.duty_start
+    }
+
+    pub fn get_dispatch_mode(&self) -> DispatchMode {
+        self.dispatch_mode
+    }
+
+    // Present kvar of the PV model, all phases
+    pub fn get_pv_kvar(&self) -> f64 {
+        self.pv_kvar * self.ckt.nphases() as f64 / 1000.0
+    }
+
+    pub fn kvar_limits(&self) -> (f64, f64) {
+        (self.min_kvar, self.max_kvar)
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    pub fn get_gen_mult(&self) -> f64 {
+        self.gen_mult
+    }
+
+    // Scales the output from the dispatch shapes; fixed generators stay at
+    // full output
+    pub fn set_gen_mult(&mut self, mult: f64) {
+        let mult = if self.status == LoadStatus::Fixed {
+            1.0
+        } else {
+            mult
+        };
+        if mult != self.gen_mult {
+            self.gen_mult = mult;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    // Output set by a dispatcher; the kVA rating and kvar limits stay as
+    // given (Pascal kWBase)
+    pub fn set_kw(&mut self, kw: f64) {
+        let kva = self.kva;
+        self.kw = kw;
+        self.set_nominal_power();
+        self.kva = kva;
+        self.ckt.invalidate_yprim();
+    }
+
+    pub fn set_kvar(&mut self, kvar: f64) {
+        let kva = self.kva;
+        self.kvar = kvar;
+        self.kvar_given = true;
+        self.set_nominal_power();
+        self.kva = kva;
+        self.pv_kvar = self.kvar * 1000.0 / self.ckt.nphases() as f64;
+        self.ckt.invalidate_yprim();
+    }
+
+    // Switches the generator on or off for the load level or price of the
+    // present step (Pascal TakeSample/dispatch logic); forceon keeps it on
+    pub fn dispatch(&mut self, load_level: f64, price: f64) {
+        self.is_on = self.force_on
+            || self.dispatch_value <= 0.0
+            || match self.dispatch_mode {
+                DispatchMode::Default => true,
+                DispatchMode::LoadLevel => load_level >= self.dispatch_value,
+                DispatchMode::Price => price >= self.dispatch_value,
+            };
+    }
+
+    pub fn machine_state(&self) -> &MachineState {
+        &self.state
+    }
+
+    pub fn machine_state_mut(&mut self) -> &mut MachineState {
+        &mut self.state
+    }
+
+    // Inertia of the machine, J·s/rad: 2H times the rating over the
+    // synchronous speed (Pascal Mmass)
+    pub fn mass(&self) -> f64 {
+        let omega = 2.0 * PI * self.ckt.get_base_frequency();
+        2.0 * self.h * self.kva * 1000.0 / omega
+    }
+
+    // Damping torque per rad/s of speed deviation, from d per unit of the
+    // rating (Pascal D)
+    pub fn damping(&self) -> f64 {
+        let omega = 2.0 * PI * self.ckt.get_base_frequency();
+        self.d * self.kva * 1000.0 / omega
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    // Internal voltage of a phase, the phases 120 degrees apart
+    fn internal_voltage(&self, phase: usize) -> Complex64 {
+        let shift = if self.ckt.nphases() == 3 {
+            phase as f64 * 2.0 * PI / 3.0
+        } else {
+            0.0
+        };
+        Complex64::from_polar(self.state.e_mag, self.state.theta - shift)
+    }
+
+    // kvar and pf from whichever of them was given last
+    fn set_nominal_power(&mut self) {
+        if self.kvar_given {
+            let kva = self.kw.hypot(self.kvar);
+            self.pf = if kva == 0.0 { 1.0 } else { self.kw / kva };
+            if self.kw * self.kvar < 0.0 {
+                self.pf = -self.pf;
+            }
+        } else if self.pf.abs() >= 1.0 {
+            self.kvar = 0.0;
+        } else {
+            self.kvar = (self.kw * (1.0 / (self.pf * self.pf) - 1.0).sqrt()).copysign(self.pf);
+        }
+        if !self.kva_given {
+            self.kva = 1.2 * self.kw;
+        }
+    }
+
+    // maxkvar and minkvar back to their defaults after kW, pf or kvar change
+    fn reset_kvar_limits(&mut self) {
+        self.max_kvar = 2.0 * self.kvar.abs();
+        self.min_kvar = -self.max_kvar;
+        self.pv_kvar = self.kvar * 1000.0 / self.ckt.nphases() as f64;
+    }
+
+    fn vbase(&self) -> f64 {
+        let nphases = self.ckt.nphases();
+        if self.connection == Connection::Wye && (nphases == 2 || nphases == 3) {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+
+    // Watts and vars each phase delivers at 1 per unit, dispatch included
+    fn phase_power(&self) -> Complex64 {
+        Complex64::new(self.kw, self.kvar) * 1000.0 * self.gen_mult / self.ckt.nphases() as f64
+    }
+
+    // Admittance of a load drawing the nominal output; Yprim uses it to
+    // stay well conditioned and the injection currents do the rest
+    fn nominal_admittance(&self) -> Complex64 {
+        let vbase = self.vbase();
+        self.phase_power().conj() / (vbase * vbase)
+    }
+
+    fn phase_ends(&self, phase: usize) -> (usize, usize) {
+        let nphases = self.ckt.nphases();
+        match self.connection {
+            Connection::Wye => (phase, nphases),
+            Connection::Delta if nphases == 1 => (0, 1),
+            Connection::Delta => (phase, (phase + 1) % nphases),
+        }
+    }
+
+    fn set_conductors(&mut self) {
+        let nphases = self.ckt.nphases();
+        let nconds = match self.connection {
+            Connection::Wye => nphases + 1,
+            Connection::Delta if nphases == 1 => 2,
+            Connection::Delta => nphases,
+        };
+        if nconds != self.ckt.nconds() {
+            self.ckt.set_conductors(nconds);
+        }
+    }
+
+    // Multiplier of constant power within vminpu..vmaxpu, constant
+    // impedance matching it outside
+    fn within_limits(&self, vpu: f64) -> f64 {
+        let limited = vpu.clamp(self.vminpu, self.vmaxpu);
+        let ratio = vpu / limited;
+        ratio * ratio
+    }
+
+    // Current flowing into one phase across voltage `v`; negative real
+    // power as the generator delivers it
+    fn phase_current(&self, v: Complex64) -> Complex64 {
+        let vmag = v.norm();
+        if !self.is_on || vmag == 0.0 {
+            return Complex64::new(0.0, 0.0);
+        }
+        let vpu = vmag / self.vbase();
+        let power = self.phase_power();
+        let (p_mult, q) = match self.model {
+            2 => (vpu * vpu, power.im * vpu * vpu),
+            3 => (self.within_limits(vpu), self.pv_kvar),
+            4 => (self.within_limits(vpu), power.im),
+            5 => (self.within_limits(vpu), power.im * vpu * vpu),
+            7 if vpu < self.vminpu => {
+                // the current of constant power at vminpu, no more
+                let mult = vpu / self.vminpu;
+                (mult, power.im * mult)
+            }
+            _ => {
+                let mult = self.within_limits(vpu);
+                (mult, power.im * mult)
+            }
+        };
+        -(Complex64::new(power.re * p_mult, q) / v).conj()
+    }
+
+    // Voltage across each phase; balanced generators see the positive
+    // sequence of the three phases in each
+    fn phase_voltages(&self, v: &[Complex64]) -> Vec<Complex64> {
+        let nphases = self.ckt.nphases();
+        let across: Vec<Complex64> = (0..nphases)
+            .map(|phase| {
+                let (a, b) = self.phase_ends(phase);
+                v[a] - v[b]
+            })
+            .collect();
+        if !self.balanced || nphases != 3 {
+            return across;
+        }
+        let a = Complex64::from_polar(1.0, 2.0 * PI / 3.0);
+        let v1 = (across[0] + a * across[1] + a * a * across[2]) / 3.0;
+        vec![v1, a * a * v1, a * v1]
+    }
+
+    fn check(&self) -> DssResult<()> {
+        if self.model == 6 {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!(
+                    "User-written generator models are not supported ({})",
+                    self.full_name()
+                ),
+            ));
+        }
+        if !(1..=7).contains(&self.model) {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Invalid generator model {} for {}; must be 1 to 7",
+                    self.model,
+                    self.full_name()
+                ),
+            ));
+        }
+        if self.pf == 0.0 || self.pf.abs() > 1.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("Invalid power factor {} for {}", self.pf, self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DssObject for Generator {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = GENERATOR_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - GENERATOR_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_conductors();
+            }
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "kv" => self.kv = parser.make_double()?,
+            "kw" | "pf" | "kvar" => {
+                let value = parser.make_double()?;
+                match property.name {
+                    "kw" => self.kw = value,
+                    "pf" => {
+                        self.pf = value;
+                        self.kvar_given = false;
+                    }
+                    _ => {
+                        self.kvar = value;
+                        self.kvar_given = true;
+                    }
+                }
+                self.set_nominal_power();
+                self.reset_kvar_limits();
+            }
+            "model" => self.model = parser.make_integer()?.max(0) as usize,
+            "vminpu" => self.vminpu = parser.make_double()?,
+            "vmaxpu" => self.vmaxpu = parser.make_double()?,
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            "dispmode" => {
+                let name = read_choice(parser, DispatchMode::NAMES, "dispmode")?;
+                self.dispatch_mode = DispatchMode::from_name(name);
+            }
+            "dispvalue" => self.dispatch_value = parser.make_double()?,
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.connection = Connection::from_name(name);
+                self.set_conductors();
+            }
+            "rneut" => self.rneut = parser.make_double()?,
+            "xneut" => self.xneut = parser.make_double()?,
+            "status" => {
+                let name = read_choice(parser, &["variable", "fixed"], "status")?;
+                self.status = LoadStatus::from_name(name);
+            }
+            "class" => self.gen_class = parser.make_integer()?,
+            "vpu" => self.vpu = parser.make_double()?,
+            "maxkvar" => self.max_kvar = parser.make_double()?,
+            "minkvar" => self.min_kvar = parser.make_double()?,
+            "pvfactor" => self.pv_factor = parser.make_double()?,
+            "forceon" => self.force_on = interpret_yes_no(parser.get_token()),
+            "kva" | "mva" => {
+                let scale = if property.name == "mva" { 1000.0 } else { 1.0 };
+                self.kva = parser.make_double()? * scale;
+                self.kva_given = true;
+            }
+            "xd" => self.xd = parser.make_double()?,
+            "xdp" => self.xdp = parser.make_double()?,
+            "xdpp" => self.xdpp = parser.make_double()?,
+            "h" => self.h = parser.make_double()?,
+            "d" => self.d = parser.make_double()?,
+            "dutystart" => self.duty_start = parser.make_double()?,
+            "balanced" => self.balanced = interpret_yes_no(parser.get_token()),
+            "xrdp" => self.xrdp = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.check()?;
+        self.set_nominal_power();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Generator {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // The nominal admittance between the two conductors of each phase, its
+    // susceptance scaled to the frequency as an inductance
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let y = if self.dynamic {
+            let z = self.state.z_thev;
+            Complex64::new(z.re, z.im * freq_mult).inv()
+        } else {
+            let y_nominal = self.nominal_admittance();
+            Complex64::new(y_nominal.re, y_nominal.im / freq_mult)
+        };
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            yprim.add(a, a, y);
+            yprim.add(b, b, y);
+            yprim.add(a, b, -y);
+            yprim.add(b, a, -y);
+        }
+        self.y_phase = y;
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+
+    // What Yprim draws through each phase less what the model draws; in
+    // dynamics the Norton current of the internal voltage
+    fn get_injection_currents(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let mut injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        if self.ckt.get_yprim().is_none() {
+            return injection;
+        }
+        if self.dynamic {
+            for phase in 0..self.ckt.nphases() {
+                let (a, b) = self.phase_ends(phase);
+                let current = self.y_phase * self.internal_voltage(phase);
+                injection[a] += current;
+                injection[b] -= current;
+            }
+            return injection;
+        }
+        let v = self.terminal_voltages(voltages);
+        for (phase, across) in self.phase_voltages(&v).into_iter().enumerate() {
+            let (a, b) = self.phase_ends(phase);
+            let current = self.y_phase * (v[a] - v[b]) - self.phase_current(across);
+            injection[a] += current;
+            injection[b] -= current;
+        }
+        injection
+    }
+}
+
+impl PcElement for Generator {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+
+    // The PV model moves its vars by pvfactor of what would take the mean
+    // phase voltage to vpu, taking the full rating per 10% of voltage
+    fn update_vars(&mut self, voltages: &[Complex64]) {
+        if self.model != 3 || !self.is_on {
+            return;
+        }
+        let v = self.terminal_voltages(voltages);
+        let phases = self.phase_voltages(&v);
+        let mean = phases.iter().map(|v| v.norm()).sum::<f64>() / phases.len() as f64;
+        let nphases = self.ckt.nphases() as f64;
+        let dq_dv = self.kva * 1000.0 / nphases / 0.1;
+        let dq = self.pv_factor * dq_dv * (self.vpu - mean / self.vbase());
+        let (min, max) = (
+            self.min_kvar * 1000.0 / nphases,
+            self.max_kvar * 1000.0 / nphases,
+        );
+        self.pv_kvar = (self.pv_kvar + dq).clamp(min.min(max), max.max(min));
+    }
+
+    // The internal voltage is the terminal voltage plus the drop across
+    // Zthev carrying the output current of the first phase; the shaft holds
+    // the power delivered
+    fn init_dynamics(&mut self, voltages: &[Complex64]) {
+        if self.dynamic || !self.is_on {
+            return;
+        }
+        let nphases = self.ckt.nphases() as f64;
+        let zbase = self.vbase() * self.vbase() / (self.kva * 1000.0 / nphases);
+        let z_thev = Complex64::new(self.xdp / self.xrdp, self.xdp) * zbase;
+        let v = self.terminal_voltages(voltages);
+        let (a, b) = self.phase_ends(0);
+        let across = v[a] - v[b];
+        let output = -self.phase_current(across);
+        let e = across + z_thev * output;
+        let power = self.total_power(voltages);
+        self.state = MachineState {
+            theta: e.arg(),
+            pshaft: -power.re,
+            e_mag: e.norm(),
+            z_thev,
+            theta_history: e.arg(),
+            ..MachineState::default()
+        };
+        self.dynamic = true;
+        self.ckt.invalidate_yprim();
+    }
+
+    fn end_dynamics(&mut self) {
+        if self.dynamic {
+            self.dynamic = false;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    // Swing equation: the rotor speeds up by what the shaft gives beyond
+    // the electrical output and the damping
+    fn integrate_states(&mut self, voltages: &[Complex64], step: f64, iteration: usize) {
+        if !self.dynamic {
+            return;
+        }
+        if iteration == 0 {
+            self.state.theta_history = self.state.theta + 0.5 * step * self.state.d_theta;
+            self.state.speed_history = self.state.speed + 0.5 * step * self.state.d_speed;
+        }
+        let power_in = self.total_power(voltages).re;
+        let (mass, damping) = (self.mass(), self.damping());
+        let state = &mut self.state;
+        state.d_speed = (state.pshaft + power_in - damping * state.speed) / mass;
+        state.d_theta = state.speed;
+        state.speed = state.speed_history + 0.5 * step * state.d_speed;
+        state.theta = state.theta_history + 0.5 * step * state.d_theta;
+    }
+
+    fn variable_names(&self) -> Vec<String> {
+        [
+            "Frequency",
+            "Theta (Deg)",
+            "Vd",
+            "PShaft",
+            "dSpeed (Deg/sec)",
+            "dTheta (Deg)",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn variables(&self) -> Vec<f64> {
+        let state = &self.state;
+        vec![
+            self.ckt.get_base_frequency() + state.speed / (2.0 * PI),
+            state.theta.to_degrees(),
+            state.e_mag,
+            state.pshaft,
+            state.d_speed.to_degrees(),
+            state.d_theta.to_degrees(),
+        ]
+    }
+}
+
+impl DssClass for GeneratorClass {
+    fn name(&self) -> &'static str {
+        "Generator"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Generator::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close, edited, first_nodes, placed};
+
+    fn new_generator(properties: &str) -> DssResult<Generator> {
+        let generator = edited(
+            Generator::new("g1"),
+            &GeneratorClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        let nodes = first_nodes(generator.nphases());
+        placed(generator, &[&nodes])
+    }
+
+    // Single-phase voltage of `vpu` of 1 kV on node 1
+    fn voltages_at(vpu: f64) -> [Complex64; 2] {
+        [Complex64::new(0.0, 0.0), Complex64::new(1000.0 * vpu, 0.0)]
+    }
+
+    #[test]
+    fn test_ratings() {
+        let generator = Generator::new("g1");
+        assert!((generator.get_kvar() - 539.742).abs() < 1e-3);
+        assert_eq!(generator.get_kva(), 1200.0);
+        assert!((generator.kvar_limits().1 - 1079.485).abs() < 1e-3);
+
+        let generator = new_generator("kw=300 kvar=400 minkvar=-100").unwrap();
+        assert!((generator.get_pf() - 0.6).abs() < 1e-12);
+        assert_eq!(generator.kvar_limits(), (-100.0, 800.0));
+        let generator = new_generator("kw=300 pf=1 mva=0.5").unwrap();
+        assert_eq!((generator.get_kvar(), generator.get_kva()), (0.0, 500.0));
+
+        assert!(new_generator("model=6").is_err());
+        assert!(new_generator("model=8").is_err());
+    }
+
+    #[test]
+    fn test_models() {
+        let base = "phases=1 kv=1 kw=1 kvar=0.5";
+        let generator = new_generator(&format!("{} model=1", base)).unwrap();
+        // delivered power is negative power into the element
+        assert_close(
+            generator.total_power(&voltages_at(1.0)),
+            Complex64::new(-1000.0, -500.0),
+            1e-6,
+        );
+        assert_close(
+            generator.total_power(&voltages_at(0.95)),
+            Complex64::new(-1000.0, -500.0),
+            1e-6,
+        );
+        let below = (0.8_f64 / 0.9).powi(2);
+        assert_close(
+            generator.total_power(&voltages_at(0.8)),
+            Complex64::new(-1000.0 * below, -500.0 * below),
+            1e-6,
+        );
+
+        let generator = new_generator(&format!("{} model=2", base)).unwrap();
+        assert_close(
+            generator.total_power(&voltages_at(0.95)),
+            Complex64::new(-902.5, -451.25),
+            1e-6,
+        );
+        let generator = new_generator(&format!("{} model=5", base)).unwrap();
+        assert_close(
+            generator.total_power(&voltages_at(0.95)),
+            Complex64::new(-1000.0, -451.25),
+            1e-6,
+        );
+        let generator = new_generator(&format!("{} model=4", base)).unwrap();
+        assert_close(
+            generator.total_power(&voltages_at(0.8)),
+            Complex64::new(-1000.0 * below, -500.0),
+            1e-6,
+        );
+        // current limited below vminpu
+        let generator = new_generator(&format!("{} model=7", base)).unwrap();
+        let limited = 0.8 / 0.9;
+        assert_close(
+            generator.total_power(&voltages_at(0.8)),
+            Complex64::new(-1000.0 * limited, -500.0 * limited),
+            1e-6,
+        );
+
+        let mut generator = new_generator(&format!("{} model=1", base)).unwrap();
+        generator.set_gen_mult(0.5);
+        generator.calc_yprim(60.0).unwrap();
+        assert_close(
+            generator.total_power(&voltages_at(1.0)),
+            Complex64::new(-500.0, -250.0),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_pv_model() {
+        let mut generator =
+            new_generator("phases=1 kv=1 kw=1 kvar=0.5 kva=2 model=3 vpu=1.02 maxkvar=0.8")
+                .unwrap();
+        // low voltage raises the vars, up to maxkvar
+        generator.update_vars(&voltages_at(1.0));
+        assert!((generator.get_pv_kvar() - 0.54).abs() < 1e-9);
+        for _ in 0..50 {
+            generator.update_vars(&voltages_at(0.9));
+        }
+        assert!((generator.get_pv_kvar() - 0.8).abs() < 1e-9);
+        generator.update_vars(&voltages_at(1.1));
+        assert!(generator.get_pv_kvar() < 0.8);
+    }
+
+    #[test]
+    fn test_dispatch_and_balance() {
+        let mut generator = new_generator("dispmode=loadlevel dispvalue=0.8").unwrap();
+        generator.dispatch(0.5, 0.0);
+        assert!(!generator.is_on());
+        let vln = 12470.0 / 3.0_f64.sqrt();
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let voltages = [
+            Complex64::new(0.0, 0.0),
+            Complex64::new(vln, 0.0),
+            a * vln,
+            a * a * vln,
+        ];
+        assert_close(
+            generator.total_power(&voltages),
+            Complex64::new(0.0, 0.0),
+            1e-6,
+        );
+        generator.dispatch(0.9, 0.0);
+        assert!(generator.is_on());
+
+        // unbalanced voltages still give balanced currents
+        let mut generator = new_generator("kw=900 pf=1 balanced=yes").unwrap();
+        let mut unbalanced = voltages;
+        unbalanced[1] *= 0.95;
+        let currents = generator.get_currents(&unbalanced);
+        assert!((currents[0].norm() - currents[1].norm()).abs() < 1e-9);
+        assert!(currents[3].norm() < 1e-9);
+
+        let power = generator.total_power(&voltages);
+        generator.init_dynamics(&voltages);
+        let state = generator.machine_state();
+        assert!((state.pshaft - 9e5).abs() < 1e-3);
+        assert!(state.theta > 0.0 && state.e_mag > vln);
+
+        // behind Zthev the internal voltage gives the same output, so the
+        // rotor holds its speed
+        assert!(generator.ckt_base().get_yprim().is_none());
+        generator.calc_yprim(60.0).unwrap();
+        assert!((generator.total_power(&voltages) - power).norm() < 1e-3);
+        generator.integrate_states(&voltages, 0.001, 0);
+        assert!(generator.machine_state().speed.abs() < 1e-9);
+        assert_eq!(generator.variables()[0], 60.0);
+
+        // with the terminal voltage sagging it delivers less and speeds up
+        let low: Vec<Complex64> = voltages.iter().map(|v| v * 0.9).collect();
+        generator.integrate_states(&low, 0.001, 0);
+        assert!(generator.total_power(&low).re > -9e5);
+        assert!(generator.machine_state().speed > 0.0);
+        generator.end_dynamics();
+        assert!(!generator.is_dynamic());
+    }
+}
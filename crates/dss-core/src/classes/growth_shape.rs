@@ -0,0 +1,319 @@
+// GrowthShape (Pascal TGrowthShape): yearly growth multipliers for the loads
+// that name the shape in their growth property. Each multiplier takes the
+// previous year's load to the present year's and holds until the next year
+// given, so the growth of a year of the study compounds those of the years
+// before it.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::load::Load;
+use crate::classes::shape_data::{FileFormat, ShapeFile};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_doubles};
+
+// Yearly growth of loads without a growth shape when the circuit has no
+// "default" growth shape either (Pascal creates GrowthShape.default with
+// 2.5 % a year)
+pub const DEFAULT_GROWTH_RATE: f64 = 1.025;
+
+static PROPERTIES: [PropertyDef; 6] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Number of points to expect in subsequent vector.",
+    },
+    PropertyDef {
+        name: "year",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of year values, or a text file spec, corresponding to the multipliers. Enter only those years where the growth changes. May be any integer sequence -- just so it is consistent. See help on Mult.",
+    },
+    PropertyDef {
+        name: "mult",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of growth multiplier values, or a text file spec, corresponding to the year values. Enter the multiplier by which you would multiply the previous year's load to get the present year's.\n\nExamples:\n\n  Year = [1, 2, 5]   Mult=[1.05, 1.025, 1.02].\n  Year= (File=years.txt) Mult= (file=mults.txt).\n\nText files contain one value per line.",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of growth curve data to a csv file containing (year, mult) points, one per line.",
+    },
+    PropertyDef {
+        name: "sngfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of growth curve data to a binary file of singles containing (year, mult) points, packed one after another.",
+    },
+    PropertyDef {
+        name: "dblfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of growth curve data to a binary file of doubles containing (year, mult) points, packed one after another.",
+    },
+];
+
+// What a file read at the next resolve goes to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileTarget {
+    Years,
+    Mults,
+    // year, mult
+    Curve,
+}
+
+#[derive(Debug)]
+pub struct GrowthShapeClass;
+
+#[derive(Debug, Clone)]
+pub struct GrowthShape {
+    base: ObjectBase,
+    // Number of points; 0 to take as many as the multipliers given
+    npts: usize,
+    // Years at which the growth changes, ascending; the first is the base
+    // year of the study
+    years: Vec<f64>,
+    mults: Vec<f64>,
+    pending_file: Option<(FileTarget, ShapeFile)>,
+}
+
+impl GrowthShape {
+    pub fn new(name: &str) -> Self {
+        GrowthShape {
+            base: ObjectBase::new("GrowthShape", name, &PROPERTIES),
+            npts: 0,
+            years: Vec::new(),
+            mults: Vec::new(),
+            pending_file: None,
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        match self.npts {
+            0 => self.mults.len(),
+            npts => npts,
+        }
+    }
+
+    pub fn years(&self) -> &[f64] {
+        &self.years
+    }
+
+    pub fn mults(&self) -> &[f64] {
+        &self.mults
+    }
+
+    // Growth of the load in a year of the study, 1 being the first year
+    // (Pascal GetMult); year 0 is the base year, with no growth
+    pub fn get_mult(&self, year: u32) -> f64 {
+        if self.mults.is_empty() {
+            return 1.0;
+        }
+        let base = self.years.first().copied().unwrap_or(1.0);
+        (0..year)
+            .map(|k| {
+                let year = base + k as f64;
+                // the multiplier of the last year given up to this one
+                let point = self.years.iter().take_while(|&&y| y <= year).count();
+                self.mults[point.clamp(1, self.mults.len()) - 1]
+            })
+            .product()
+    }
+}
+
+// Growth of a load in a year of the study: from its growth shape, else from
+// the circuit's "default" growth shape, else at the default rate
+pub fn growth_factor(load: &Load, circuit: &Circuit, year: u32) -> f64 {
+    if year == 0 {
+        return 1.0;
+    }
+    let name = match load.get_growth() {
+        "" => "default",
+        name => name,
+    };
+    match circuit.find_object_as::<GrowthShape>("GrowthShape", name) {
+        Some(shape) => shape.get_mult(year),
+        None => DEFAULT_GROWTH_RATE.powi(year as i32),
+    }
+}
+
+// Sets the growth of every load for a year of the study
+pub fn apply_growth(circuit: &mut Circuit, year: u32) {
+    let loads: Vec<_> = circuit.class_elements("Load").to_vec();
+    for id in loads {
+        let Some(load) = circuit
+            .element(id)
+            .and_then(|load| load.as_any().downcast_ref::<Load>())
+        else {
+            continue;
+        };
+        let growth = growth_factor(load, circuit, year);
+        if let Some(load) = circuit
+            .element_mut(id)
+            .and_then(|load| load.as_any_mut().downcast_mut::<Load>())
+        {
+            load.set_growth_mult(growth);
+        }
+    }
+}
+
+impl DssObject for GrowthShape {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let name = PROPERTIES[index].name;
+        match name {
+            "npts" => self.npts = parser.make_integer()?.max(0) as usize,
+            "year" | "mult" => {
+                let target = match name {
+                    "year" => FileTarget::Years,
+                    _ => FileTarget::Mults,
+                };
+                let values = match ShapeFile::from_array(parser.get_token()) {
+                    Some(file) => {
+                        self.pending_file = Some((target, file));
+                        Vec::new()
+                    }
+                    None => read_doubles(parser)?,
+                };
+                match target {
+                    FileTarget::Years => self.years = values,
+                    _ => self.mults = values,
+                }
+            }
+            "csvfile" | "sngfile" | "dblfile" => {
+                let format = match name {
+                    "sngfile" => FileFormat::Sng,
+                    "dblfile" => FileFormat::Dbl,
+                    _ => FileFormat::Csv,
+                };
+                let file = ShapeFile::new(format, parser.get_token());
+                self.pending_file = Some((FileTarget::Curve, file));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some((target, file)) = self.pending_file.take() else {
+            return Ok(());
+        };
+        let width = match target {
+            FileTarget::Curve => 2,
+            _ => 1,
+        };
+        let records = file.read(circuit.get_current_dir(), width)?;
+        let column = |k: usize| records.iter().map(|record| record[k]).collect::<Vec<f64>>();
+        match target {
+            FileTarget::Years => self.years = column(0),
+            FileTarget::Mults => self.mults = column(0),
+            FileTarget::Curve => {
+                self.years = column(0);
+                self.mults = column(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.npts > 0 {
+            self.mults.resize(self.npts, 1.0);
+            if !self.years.is_empty() {
+                self.years.truncate(self.npts);
+            }
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for GrowthShapeClass {
+    fn name(&self) -> &'static str {
+        "GrowthShape"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(GrowthShape::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::load::LoadClass;
+    use crate::test_util::edited;
+
+    fn new_shape(properties: &str, circuit: &Circuit) -> GrowthShape {
+        edited(
+            GrowthShape::new("g1"),
+            &GrowthShapeClass,
+            properties,
+            circuit,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_growth() {
+        let mut circuit = Circuit::new("test");
+        let shape = new_shape("year=[2020 2021 2024] mult=[1.05 1.025 1.02]", &circuit);
+        assert_eq!(shape.get_mult(0), 1.0);
+        assert_eq!(shape.get_mult(1), 1.05);
+        // 2021 to 2023 grow at 2.5 %, 2024 on at 2 %
+        let expected = 1.05 * 1.025_f64.powi(3) * 1.02 * 1.02;
+        assert!((shape.get_mult(6) - expected).abs() < 1e-12);
+
+        let load = edited(
+            Load::new("ld1"),
+            &LoadClass,
+            "bus1=b kw=100 growth=g1",
+            &circuit,
+        )
+        .unwrap();
+        let id = circuit.add_element(Box::new(load));
+        // without the shape the load grows at the default rate
+        apply_growth(&mut circuit, 2);
+        let load = |circuit: &Circuit| {
+            circuit
+                .element(id)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Load>()
+                .unwrap()
+                .get_growth_mult()
+        };
+        assert!((load(&circuit) - 1.025 * 1.025).abs() < 1e-12);
+        circuit.add_element(Box::new(shape));
+        apply_growth(&mut circuit, 2);
+        assert!((load(&circuit) - 1.05 * 1.025).abs() < 1e-12);
+    }
+}
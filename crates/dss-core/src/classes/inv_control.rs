@@ -0,0 +1,939 @@
+// InvControl (Pascal TInvControl): smart inverter functions for PVSystem and
+// Storage elements. In volt-var mode the kvar of each inverter follows a
+// curve of the voltage at its terminal, in volt-watt mode its output is
+// limited along a curve of the voltage, and the combined mode does both.
+// Each control iteration moves the output only deltaq_factor (deltap_factor)
+// of the way to the curve so the solution settles; the voltage may be taken
+// relative to its average over a window rather than the rating, and the
+// volt-var curve may be shifted for falling voltages. The curves are
+// XYCurve objects of per unit voltage against per unit kvar or watts.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::pv_system::PVSystem;
+use crate::classes::storage::{Storage, StorageState};
+use crate::classes::xy_curve::find_xy_curve;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names, read_choice,
+};
+use crate::registry::ElementId;
+
+const INV_CONTROL_PROPERTIES: [PropertyDef; 18] = [
+    PropertyDef {
+        name: "derlist",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Array list of PVSystem and/or Storage elements to be controlled. If not specified, all PVSystem and Storage in the circuit are assumed to be controlled by this control. No capability of hierarchical control between two controls for a single element is implemented at this time.",
+    },
+    PropertyDef {
+        name: "mode",
+        kind: PropertyKind::Choice(InvControlMode::NAMES),
+        default: "voltvar",
+        help: "Smart inverter function in which the InvControl will control the PC elements specified in DERList, according to the options below:Must be one of: {VOLTVAR* | VOLTWATT }if the user desires to use modes simultaneously, then set the CombiMode property. Setting the Mode to any valid value disables combination mode.In volt-var mode (Default). This mode attempts to CONTROL the vars, according to one or two volt-var curves, depending on the monitored voltages, present active power output, and the capabilities of the PVSystem/Storage. In volt-watt mode. This mode attempts to LIMIT the watts, according to one defined volt-watt curve, depending on the monitored voltages and the capabilities of the PVSystem/Storage. ",
+    },
+    PropertyDef {
+        name: "combimode",
+        kind: PropertyKind::Choice(&["none", "vv_vw"]),
+        default: "none",
+        help: "Combination of smart inverter functions in which the InvControl will control the PC elements in DERList, according to the options below: Must be a combination of the following: {VV_VW} Not set by default In combined VV_VW mode, both volt-var and volt-watt control modes are active simultaneously.  See help individually for volt-var mode and volt-watt mode in Mode property.Note that the PVSystem/Storage will attempt to achieve both the volt-watt and volt-var set-points based on the capabilities of the inverter in the PVSystem/Storage (kVA rating, etc), any limits set on maximum active power,",
+    },
+    PropertyDef {
+        name: "vvc_curve1",
+        kind: PropertyKind::Object("XYCurve"),
+        default: "",
+        help: "Required for VOLTVAR mode. Name of the XYCurve object containing the volt-var curve. The positive values of the y-axis of the volt-var curve represent values in pu of the provided base reactive power. The negative values of the y-axis are values in pu of the absorbed base reactive power. Provided and absorbed base reactive power values are defined in the RefReactivePower property Units for the x-axis are per-unit voltage, which may be in per unit of the rated voltage for the PVSystem/Storage, or may be in per unit of the average voltage at the terminals over a user-defined number of prior solutions. ",
+    },
+    PropertyDef {
+        name: "hysteresis_offset",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Required for VOLTVAR mode, and defaults to 0. for the times when the terminal voltage is decreasing, this is the off-set in per-unit voltage of a curve whose shape is the same as vvc_curve. It is offset by a certain negative value of per-unit voltage, which is defined by the base quantity for the x-axis of the volt-var curve (see help for voltage_curvex_ref)if the PC terminal voltage begins to decrease from its peak value, the control will apply the hysteresis curve. Once the terminal voltage increases again, the control reverts to the original curve.",
+    },
+    PropertyDef {
+        name: "voltage_curvex_ref",
+        kind: PropertyKind::Choice(VoltageRef::NAMES),
+        default: "rated",
+        help: "Required for VOLTVAR and VOLTWATT modes, and defaults to rated.  Possible values are: {rated|avg}.  Defines whether the x-axis values (voltage in per unit) for vvc_curve1 and the volt-watt curve corresponds to:rated. The rated voltage for the PVSystem/Storage object (1.0 in the volt-var curve equals rated voltage).avg. The average terminal voltage recorded over a certain number of prior power-flow solutions.",
+    },
+    PropertyDef {
+        name: "avgwindowlen",
+        kind: PropertyKind::Text,
+        default: "0s",
+        help: "Required for VOLTVAR mode and VOLTWATT mode, and defaults to 0 seconds (0s). Sets the length of the averaging window over which the average PVSystem/Storage terminal voltage is calculated. Units are indicated by appending s, m, or h to the integer value. The averaging window will calculate the average PVSystem/Storage terminal voltage over the specified period of time, up to and including the last power flow solution. Note, if the solution stepsize is larger than the window length, then the voltage will be assumed to have been constant over the time-frame specified by the window length.",
+    },
+    PropertyDef {
+        name: "voltwatt_curve",
+        kind: PropertyKind::Object("XYCurve"),
+        default: "",
+        help: "Required for VOLTWATT mode. Name of the XYCurve object containing the volt-watt curve. Units for the x-axis are per-unit voltage, which may be in per unit of the rated voltage for the PVSystem/Storage, or may be in per unit of the average voltage at the terminals over a user-defined number of prior solutions. Units for the y-axis are either in one of the options described in the VoltwattYAxis property. ",
+    },
+    PropertyDef {
+        name: "deltaq_factor",
+        kind: PropertyKind::Double,
+        default: "0.7",
+        help: "Required for the VOLTVAR mode.  Defaults to 0.7. Sets the maximum change (in per unit) from the prior var output level to the desired var output level during each control iteration. A value of 1.0 means the inverter goes straight to the curve; smaller values damp the oscillations that may appear when the inverters' reactive power moves the voltage they follow.",
+    },
+    PropertyDef {
+        name: "voltagechangetolerance",
+        kind: PropertyKind::Double,
+        default: "0.0001",
+        help: "Defaults to 0.0001 per-unit voltage.  This parameter should only be modified by advanced users of the InvControl.  Tolerance in pu of the control loop convergence associated to the monitored voltage in pu. This value is compared with the difference of the monitored voltage in pu of the current and previous control iterations of the control loop.",
+    },
+    PropertyDef {
+        name: "varchangetolerance",
+        kind: PropertyKind::Double,
+        default: "0.025",
+        help: "Required for VOLTVAR mode. Defaults to 0.025 per unit of the base provided or absorbed reactive power described in the RefReactivePower property This parameter should only be modified by advanced users of the InvControl. Tolerance in pu of the convergence of the control loop associated with reactive power. For the same control iteration, this value is compared to the difference, as an absolute value (without sign), between the desired reactive power value in pu and the output reactive power in pu of the controlled element.",
+    },
+    PropertyDef {
+        name: "voltwattyaxis",
+        kind: PropertyKind::Choice(VoltWattAxis::NAMES),
+        default: "pmpppu",
+        help: "Required for VOLTWATT mode.  Must be one of: {PMPPPU* | PAVAILABLEPU}.  The default is PMPPPU.  Units for the y-axis of the volt-watt curve while in volt-watt mode. When set to PMPPPU. The y-axis corresponds to the value in pu of Pmpp property of the PVSystem. When set to PAVAILABLEPU. The y-axis corresponds to the value in pu of the available active power of the PVSystem. For Storage, both are in pu of the rated kW.",
+    },
+    PropertyDef {
+        name: "deltap_factor",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Required for the VOLTWATT modes.  Defaults to 1.0. Sets the maximum change (in unit of the y-axis) from the prior active power output level to the desired active power output level during each control iteration. If numerical instability is noticed in solutions such as active power changing substantially from one control iteration to the next and/or voltages oscillating between two values with some separation, this is an indication of numerical instability (use the EventLog to diagnose). If the maximum control iterations are exceeded, and no numerical instability is seen in the EventLog of via monitors, then try increasing the value of this parameter to reduce the number of control iterations needed to achieve the control criteria, and move to the power flow solution.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True* | No/False} Default is YES for InvControl. Log control actions to Eventlog.",
+    },
+    PropertyDef {
+        name: "refreactivepower",
+        kind: PropertyKind::Choice(RefReactivePower::NAMES),
+        default: "varaval",
+        help: "Optional, defaults to VARAVAL. Defines the base reactive power for both the provided and absorbed reactive power, according to one of the following options: VARAVAL. The base values for the provided and absorbed reactive power are equal to the available reactive power.VARMAX: The base values of the provided and absorbed reactive power are equal to the value defined in the kvarMax and kvarMaxAbs properties, respectively.",
+    },
+    PropertyDef {
+        name: "activepchangetolerance",
+        kind: PropertyKind::Double,
+        default: "0.01",
+        help: "Required for VOLTWATT. Default value is 0.01. Tolerance in pu of the control loop convergence associated with the active power. For the same control iteration, this value is compared to the difference between the active power limit in pu resulted from the convergence process and the one resulted from the volt-watt function.",
+    },
+    PropertyDef {
+        name: "pvsystemlist",
+        kind: PropertyKind::Objects("PVSystem"),
+        default: "",
+        help: "Deprecated, use DERList instead.",
+    },
+    PropertyDef {
+        name: "monbuses",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of monitored bus used by the voltage-dependent control modes. Default is bus of the controlled PVSystem/Storage. Not supported by this implementation; the terminal voltage of each element is used.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 20] = concat_properties(&INV_CONTROL_PROPERTIES, &CKT_PROPERTIES);
+
+// What the control does (Pascal ControlMode and CombiMode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvControlMode {
+    VoltVar,
+    VoltWatt,
+    // Both at once, combimode=vv_vw
+    VoltVarVoltWatt,
+}
+
+impl InvControlMode {
+    pub const NAMES: &'static [&'static str] = &["voltvar", "voltwatt"];
+
+    // Mode of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "voltwatt" => InvControlMode::VoltWatt,
+            _ => InvControlMode::VoltVar,
+        }
+    }
+
+    fn volt_var(self) -> bool {
+        self != InvControlMode::VoltWatt
+    }
+
+    fn volt_watt(self) -> bool {
+        self != InvControlMode::VoltVar
+    }
+}
+
+// What the x axis of the curves is per unit of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageRef {
+    Rated,
+    Average,
+}
+
+impl VoltageRef {
+    pub const NAMES: &'static [&'static str] = &["rated", "avg"];
+
+    // Reference of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "avg" => VoltageRef::Average,
+            _ => VoltageRef::Rated,
+        }
+    }
+}
+
+// What the y axis of the volt-watt curve is per unit of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltWattAxis {
+    Pmpp,
+    PAvailable,
+}
+
+impl VoltWattAxis {
+    pub const NAMES: &'static [&'static str] = &["pmpppu", "pavailablepu"];
+
+    // Axis of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "pavailablepu" => VoltWattAxis::PAvailable,
+            _ => VoltWattAxis::Pmpp,
+        }
+    }
+}
+
+// What the y axis of the volt-var curve is per unit of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefReactivePower {
+    // kvar the kVA leaves beside the present kW
+    VarAvailable,
+    // kvarmax, or kva for Storage
+    VarMax,
+}
+
+impl RefReactivePower {
+    pub const NAMES: &'static [&'static str] = &["varaval", "varmax"];
+
+    // Reference of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "varmax" => RefReactivePower::VarMax,
+            _ => RefReactivePower::VarAvailable,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvControlClass;
+
+// What the control keeps of each inverter between iterations
+#[derive(Debug, Clone)]
+struct DerControl {
+    id: ElementId,
+    // Voltages of the window, (seconds, per unit of rated)
+    history: Vec<(f64, f64)>,
+    last_vpu: Option<f64>,
+    falling: bool,
+    // Set points the queued action will apply
+    kvar: Option<f64>,
+    pct_p: Option<f64>,
+    pending: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvControl {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    der_list: Vec<String>,
+    mode: InvControlMode,
+    vvc_curve: String,
+    vvc_points: Vec<(f64, f64)>,
+    hysteresis_offset: f64,
+    voltage_ref: VoltageRef,
+    // Seconds
+    avg_window: f64,
+    voltwatt_curve: String,
+    voltwatt_points: Vec<(f64, f64)>,
+    delta_q_factor: f64,
+    voltage_change_tolerance: f64,
+    var_change_tolerance: f64,
+    voltwatt_axis: VoltWattAxis,
+    delta_p_factor: f64,
+    event_log: bool,
+    ref_reactive_power: RefReactivePower,
+    active_p_change_tolerance: f64,
+    ders: Vec<DerControl>,
+}
+
+impl InvControl {
+    pub fn new(name: &str) -> Self {
+        InvControl {
+            base: ObjectBase::new("InvControl", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            der_list: Vec::new(),
+            mode: InvControlMode::VoltVar,
+            vvc_curve: String::new(),
+            vvc_points: Vec::new(),
+            hysteresis_offset: 0.0,
+            voltage_ref: VoltageRef::Rated,
+            avg_window: 0.0,
+            voltwatt_curve: String::new(),
+            voltwatt_points: Vec::new(),
+            delta_q_factor: 0.7,
+            voltage_change_tolerance: 0.0001,
+            var_change_tolerance: 0.025,
+            voltwatt_axis: VoltWattAxis::Pmpp,
+            delta_p_factor: 1.0,
+            event_log: true,
+            ref_reactive_power: RefReactivePower::VarAvailable,
+            active_p_change_tolerance: 0.01,
+            ders: Vec::new(),
+        }
+    }
+
+    pub fn get_mode(&self) -> InvControlMode {
+        self.mode
+    }
+
+    pub fn get_vvc_curve(&self) -> &str {
+        &self.vvc_curve
+    }
+
+    pub fn get_voltwatt_curve(&self) -> &str {
+        &self.voltwatt_curve
+    }
+
+    // Points (per unit volts, per unit vars) of the volt-var curve, as
+    // looked up by vvc_curve1
+    pub fn set_vvc_points(&mut self, points: Vec<(f64, f64)>) {
+        self.vvc_points = points;
+    }
+
+    // Points (per unit volts, per unit watts) of the volt-watt curve, as
+    // looked up by voltwatt_curve
+    pub fn set_voltwatt_points(&mut self, points: Vec<(f64, f64)>) {
+        self.voltwatt_points = points;
+    }
+
+    // PVSystem and Storage elements the control acts on
+    pub fn der_elements(&self, circuit: &Circuit) -> Vec<ElementId> {
+        if self.der_list.is_empty() {
+            let mut ids = circuit.class_elements("pvsystem").to_vec();
+            ids.extend_from_slice(circuit.class_elements("storage"));
+            return ids;
+        }
+        self.der_list
+            .iter()
+            .filter_map(|name| match name.contains('.') {
+                true => find_by_full_name(circuit, name),
+                false => circuit.find_element("pvsystem", name),
+            })
+            .filter(|&id| der(circuit, id).is_some())
+            .collect()
+    }
+
+    // Voltage the curves are read at: per unit of rated or of the average
+    // over the window
+    fn curve_voltage(&self, state: &DerControl, vpu: f64) -> f64 {
+        if self.voltage_ref == VoltageRef::Rated || state.history.is_empty() {
+            return vpu;
+        }
+        let average =
+            state.history.iter().map(|(_, v)| v).sum::<f64>() / state.history.len() as f64;
+        vpu / average
+    }
+
+    // New kvar for one iteration: toward the volt-var curve, deltaq_factor
+    // of the way
+    fn next_kvar(&self, inverter: &Der, x: f64, falling: bool) -> Option<f64> {
+        if !self.mode.volt_var() || self.vvc_points.is_empty() {
+            return None;
+        }
+        let x = if falling && self.hysteresis_offset < 0.0 {
+            x - self.hysteresis_offset
+        } else {
+            x
+        };
+        let q_pu = interpolate(&self.vvc_points, x);
+        let q_base = match self.ref_reactive_power {
+            RefReactivePower::VarAvailable => (inverter.kva * inverter.kva
+                - inverter.kw * inverter.kw)
+                .max(0.0)
+                .sqrt(),
+            RefReactivePower::VarMax => inverter.kva,
+        };
+        let target = q_pu * q_base;
+        let kvar = inverter.kvar + self.delta_q_factor * (target - inverter.kvar);
+        ((kvar - inverter.kvar).abs() > self.var_change_tolerance * q_base.max(1e-9))
+            .then_some(kvar)
+    }
+
+    // New output limit for one iteration, percent of Pmpp (or the rated kW):
+    // toward the volt-watt curve, deltap_factor of the way
+    fn next_pct_p(&self, inverter: &Der, x: f64) -> Option<f64> {
+        if !self.mode.volt_watt() || self.voltwatt_points.is_empty() {
+            return None;
+        }
+        let p_pu = interpolate(&self.voltwatt_points, x);
+        let target = match self.voltwatt_axis {
+            VoltWattAxis::Pmpp => p_pu * 100.0,
+            VoltWattAxis::PAvailable => p_pu * inverter.kw_available / inverter.kw_base * 100.0,
+        };
+        let pct = inverter.pct_p + self.delta_p_factor * (target - inverter.pct_p);
+        ((pct - inverter.pct_p).abs() > self.active_p_change_tolerance * 100.0).then_some(pct)
+    }
+}
+
+// Piecewise linear in the points, flat beyond the ends
+fn interpolate(points: &[(f64, f64)], x: f64) -> f64 {
+    let Some(&(x0, y0)) = points.first() else {
+        return 0.0;
+    };
+    if x <= x0 {
+        return y0;
+    }
+    for pair in points.windows(2) {
+        let ((xa, ya), (xb, yb)) = (pair[0], pair[1]);
+        if x <= xb {
+            return if xb > xa {
+                ya + (yb - ya) * (x - xa) / (xb - xa)
+            } else {
+                yb
+            };
+        }
+    }
+    points[points.len() - 1].1
+}
+
+// Seconds of a window length such as "30s", "5m" or "1h"
+fn parse_window(text: &str) -> DssResult<f64> {
+    let text = text.trim().to_lowercase();
+    let (number, scale) = match text.chars().last() {
+        Some('s') => (&text[..text.len() - 1], 1.0),
+        Some('m') => (&text[..text.len() - 1], 60.0),
+        Some('h') => (&text[..text.len() - 1], 3600.0),
+        _ => (text.as_str(), 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .map(|value| value * scale)
+        .map_err(|_| {
+            DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("Invalid averaging window length \"{}\"", text),
+            )
+        })
+}
+
+// What the control needs to know of a PVSystem or Storage element
+pub(crate) struct Der {
+    pub(crate) kva: f64,
+    pub(crate) kw: f64,
+    pub(crate) kvar: f64,
+    // kW the inverter could deliver without a volt-watt limit
+    pub(crate) kw_available: f64,
+    // kW that 100% of the limit is, Pmpp or the rated kW
+    pub(crate) kw_base: f64,
+    // Present limit, percent of kw_base
+    pub(crate) pct_p: f64,
+    // Volts each phase to ground at 1 per unit
+    pub(crate) vbase: f64,
+}
+
+pub(crate) fn der(circuit: &Circuit, id: ElementId) -> Option<Der> {
+    let element = circuit.element(id)?;
+    let nphases = element.as_ckt_element()?.nphases();
+    let vbase = |kv: f64| match nphases {
+        1 => kv * 1000.0,
+        _ => kv * 1000.0 / 3.0_f64.sqrt(),
+    };
+    if let Some(pv) = element.as_any().downcast_ref::<PVSystem>() {
+        let kw_available = if pv.is_inverter_on() {
+            pv.get_panel_kw()
+        } else {
+            0.0
+        };
+        return Some(Der {
+            kva: pv.get_kva(),
+            kw: pv.get_kw(),
+            kvar: pv.get_kvar(),
+            kw_available,
+            kw_base: pv.get_pmpp(),
+            pct_p: pv.get_pct_pmpp(),
+            vbase: vbase(pv.get_kv()),
+        });
+    }
+    let storage = element.as_any().downcast_ref::<Storage>()?;
+    let discharging = storage.get_state() == StorageState::Discharging;
+    Some(Der {
+        kva: storage.get_kva(),
+        kw: storage.get_kw(),
+        kvar: storage.get_kvar(),
+        kw_available: if discharging {
+            storage.get_kw_rated()
+        } else {
+            0.0
+        },
+        kw_base: storage.get_kw_rated(),
+        pct_p: storage.get_pct_discharge(),
+        vbase: vbase(storage.get_kv()),
+    })
+}
+
+// Sets the kvar and the output limit, percent of Pmpp or the rated kW, of a
+// PVSystem or Storage element
+pub(crate) fn apply_set_points(element: &mut dyn DssObject, kvar: Option<f64>, pct_p: Option<f64>) {
+    if let Some(pv) = element.as_any_mut().downcast_mut::<PVSystem>() {
+        if let Some(kvar) = kvar {
+            pv.set_kvar(kvar);
+        }
+        if let Some(pct) = pct_p {
+            pv.set_pct_pmpp(pct);
+        }
+    } else if let Some(storage) = element.as_any_mut().downcast_mut::<Storage>() {
+        if let Some(kvar) = kvar {
+            storage.set_kvar(kvar);
+        }
+        if let Some(pct) = pct_p {
+            storage.set_pct_discharge(pct);
+        }
+    }
+}
+
+// Average of the phase to ground voltage magnitudes at the terminal
+pub(crate) fn terminal_vpu(
+    circuit: &Circuit,
+    id: ElementId,
+    voltages: &[Complex64],
+    vbase: f64,
+) -> f64 {
+    let Some(element) = circuit.element(id).and_then(|e| e.as_ckt_element()) else {
+        return 0.0;
+    };
+    let v = element.terminal_voltages(voltages);
+    let nphases = element.nphases().min(v.len()).max(1);
+    v.iter().take(nphases).map(|v| v.norm()).sum::<f64>() / nphases as f64 / vbase
+}
+
+impl DssObject for InvControl {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = INV_CONTROL_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - INV_CONTROL_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "derlist" | "pvsystemlist" => self.der_list = parse_names(parser.get_token()),
+            "mode" => {
+                let name = read_choice(parser, InvControlMode::NAMES, "mode")?;
+                self.mode = InvControlMode::from_name(name);
+            }
+            "combimode" => {
+                let name = read_choice(parser, &["none", "vv_vw"], "combimode")?;
+                if name == "vv_vw" {
+                    self.mode = InvControlMode::VoltVarVoltWatt;
+                }
+            }
+            "vvc_curve1" => self.vvc_curve = parser.get_token().to_lowercase(),
+            "hysteresis_offset" => self.hysteresis_offset = parser.make_double()?,
+            "voltage_curvex_ref" => {
+                let name = read_choice(parser, VoltageRef::NAMES, "voltage_curvex_ref")?;
+                self.voltage_ref = VoltageRef::from_name(name);
+            }
+            "avgwindowlen" => self.avg_window = parse_window(parser.get_token())?,
+            "voltwatt_curve" => self.voltwatt_curve = parser.get_token().to_lowercase(),
+            "deltaq_factor" => self.delta_q_factor = parser.make_double()?,
+            "voltagechangetolerance" => self.voltage_change_tolerance = parser.make_double()?,
+            "varchangetolerance" => self.var_change_tolerance = parser.make_double()?,
+            "voltwattyaxis" => {
+                let name = read_choice(parser, VoltWattAxis::NAMES, "voltwattyaxis")?;
+                self.voltwatt_axis = VoltWattAxis::from_name(name);
+            }
+            "deltap_factor" => self.delta_p_factor = parser.make_double()?,
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            "refreactivepower" => {
+                let name = read_choice(parser, RefReactivePower::NAMES, "refreactivepower")?;
+                self.ref_reactive_power = RefReactivePower::from_name(name);
+            }
+            "activepchangetolerance" => self.active_p_change_tolerance = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The volt-var and volt-watt curves must be XYCurves already defined
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some(property) = INV_CONTROL_PROPERTIES.get(index) else {
+            return Ok(());
+        };
+        let (name, points) = match property.name {
+            "vvc_curve1" => (&self.vvc_curve, &mut self.vvc_points),
+            "voltwatt_curve" => (&self.voltwatt_curve, &mut self.voltwatt_points),
+            _ => return Ok(()),
+        };
+        *points = match name.as_str() {
+            "" => Vec::new(),
+            name => find_xy_curve(circuit, name, &self.base.full_name())?.points(),
+        };
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if !(0.0..=1.0).contains(&self.delta_q_factor)
+            || !(0.0..=1.0).contains(&self.delta_p_factor)
+        {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} needs deltaq_factor and deltap_factor between 0 and 1",
+                    self.full_name()
+                ),
+            ));
+        }
+        self.ders.clear();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for InvControl {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for InvControl {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Reads each inverter's voltage off the curves and queues new set points
+    // for those that have moved more than the tolerances
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let ids = self.der_elements(circuit);
+        if self
+            .ders
+            .iter()
+            .map(|state| state.id)
+            .ne(ids.iter().copied())
+        {
+            self.ders = ids
+                .iter()
+                .map(|&id| DerControl {
+                    id,
+                    history: Vec::new(),
+                    last_vpu: None,
+                    falling: false,
+                    kvar: None,
+                    pct_p: None,
+                    pending: None,
+                })
+                .collect();
+        }
+        let (hour, sec) = queue.get_time();
+        let now = hour as f64 * 3600.0 + sec;
+        for index in 0..self.ders.len() {
+            let Some(inverter) = der(circuit, self.ders[index].id) else {
+                continue;
+            };
+            let vpu = terminal_vpu(circuit, self.ders[index].id, voltages, inverter.vbase);
+            let state = &mut self.ders[index];
+            state
+                .history
+                .retain(|&(time, _)| now - time < self.avg_window && time != now);
+            state.history.push((now, vpu));
+            // on the hysteresis curve from when the voltage falls until it
+            // rises again
+            if let Some(last) = state.last_vpu {
+                if vpu < last - self.voltage_change_tolerance {
+                    state.falling = true;
+                } else if vpu > last + self.voltage_change_tolerance {
+                    state.falling = false;
+                }
+            }
+            state.last_vpu = Some(vpu);
+            let falling = state.falling;
+
+            let x = self.curve_voltage(&self.ders[index], vpu);
+            let kvar = self.next_kvar(&inverter, x, falling);
+            let pct_p = self.next_pct_p(&inverter, x);
+            let state = &mut self.ders[index];
+            if kvar.is_none() && pct_p.is_none() {
+                continue;
+            }
+            state.kvar = kvar;
+            state.pct_p = pct_p;
+            if state.pending.is_none() {
+                state.pending = Some(queue.push(0.0, 0, index, id));
+            }
+        }
+    }
+
+    // Applies the set points of one inverter
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some(state) = self.ders.get_mut(action.proxy) else {
+            return;
+        };
+        state.pending = None;
+        let (kvar, pct_p) = (state.kvar.take(), state.pct_p.take());
+        let Some(element) = circuit.element_mut(state.id) else {
+            return;
+        };
+        let name = element.full_name();
+        apply_set_points(element, kvar, pct_p);
+        if self.event_log {
+            let mut what = Vec::new();
+            if let Some(kvar) = kvar {
+                what.push(format!("kvar set to {:.3}", kvar));
+            }
+            if let Some(pct) = pct_p {
+                what.push(format!("output limited to {:.2}%", pct));
+            }
+            queue.log(&self.full_name(), &format!("{} {}", name, what.join(", ")));
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.ders.clear();
+    }
+}
+
+impl DssClass for InvControlClass {
+    fn name(&self) -> &'static str {
+        "InvControl"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(InvControl::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::pv_system::PVSystemClass;
+    use crate::classes::xy_curve::{XYCurve, XYCurveClass};
+    use crate::test_util::{edit, edited, placed};
+
+    // A circuit with one 1-phase 10 kVA PVSystem on node 1
+    fn circuit_with_pv(properties: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let pv = edited(
+            PVSystem::new("pv1"),
+            &PVSystemClass,
+            &format!("phases=1 kv=1 pmpp=10 kva=10 {}", properties),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(pv, &[&[1, 0]]).unwrap()));
+        circuit
+    }
+
+    fn new_control(properties: &str, circuit: &Circuit) -> InvControl {
+        let mut control =
+            edited(InvControl::new("ic"), &InvControlClass, properties, circuit).unwrap();
+        control.set_vvc_points(vec![
+            (0.5, 1.0),
+            (0.95, 1.0),
+            (1.0, 0.0),
+            (1.05, -1.0),
+            (1.5, -1.0),
+        ]);
+        control.set_voltwatt_points(vec![(1.0, 1.0), (1.05, 1.0), (1.1, 0.0)]);
+        control
+    }
+
+    fn at(vpu: f64) -> Vec<Complex64> {
+        vec![Complex64::new(0.0, 0.0), Complex64::new(1000.0 * vpu, 0.0)]
+    }
+
+    // Samples and carries out what comes due; the number of actions taken
+    fn iterate(
+        control: &mut InvControl,
+        circuit: &mut Circuit,
+        vpu: f64,
+        queue: &mut ControlQueue,
+    ) -> usize {
+        control.sample(99, circuit, &at(vpu), queue);
+        let mut count = 0;
+        while let Some(action) = queue.pop_due() {
+            control.do_pending_action(&action, circuit, &at(vpu), queue);
+            count += 1;
+        }
+        count
+    }
+
+    fn pv(circuit: &Circuit) -> &PVSystem {
+        circuit
+            .find_object_as::<PVSystem>("pvsystem", "pv1")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_volt_var() {
+        let mut circuit = circuit_with_pv("irradiance=0.6");
+        let mut control = new_control("mode=voltvar deltaq_factor=0.5", &circuit);
+        let mut queue = ControlQueue::new();
+
+        // 1.025 pu calls for -0.5 pu of the 8 kvar available, half way
+        assert_eq!(iterate(&mut control, &mut circuit, 1.025, &mut queue), 1);
+        assert!((pv(&circuit).get_kvar() + 2.0).abs() < 1e-9);
+        iterate(&mut control, &mut circuit, 1.025, &mut queue);
+        assert!((pv(&circuit).get_kvar() + 3.0).abs() < 1e-9);
+        // settles within the var tolerance
+        for _ in 0..10 {
+            iterate(&mut control, &mut circuit, 1.025, &mut queue);
+        }
+        assert!((pv(&circuit).get_kvar() + 4.0).abs() < 0.3);
+        assert_eq!(iterate(&mut control, &mut circuit, 1.025, &mut queue), 0);
+        assert!(!queue.events().is_empty());
+
+        // falling voltage reads the curve shifted by the offset
+        let mut circuit = circuit_with_pv("irradiance=0.6");
+        let mut control = new_control(
+            "mode=voltvar deltaq_factor=1 hysteresis_offset=-0.025 eventlog=no",
+            &circuit,
+        );
+        iterate(&mut control, &mut circuit, 1.05, &mut queue);
+        assert!((pv(&circuit).get_kvar() + 8.0).abs() < 1e-9);
+        iterate(&mut control, &mut circuit, 1.025, &mut queue);
+        let q_base = (100.0 - 36.0_f64).sqrt();
+        assert!((pv(&circuit).get_kvar() + q_base).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_volt_watt() {
+        let mut circuit = circuit_with_pv("");
+        let mut control = new_control("mode=voltwatt", &circuit);
+        let mut queue = ControlQueue::new();
+        iterate(&mut control, &mut circuit, 1.075, &mut queue);
+        assert!((pv(&circuit).get_pct_pmpp() - 50.0).abs() < 1e-9);
+        assert!((pv(&circuit).get_kw() - 5.0).abs() < 1e-9);
+        assert!(pv(&circuit).get_kvar().abs() < 1e-12);
+
+        // both functions with the limit in pu of the available power
+        let mut circuit = circuit_with_pv("irradiance=0.5");
+        let mut control = new_control(
+            "combimode=vv_vw voltwattyaxis=pavailablepu deltaq_factor=1",
+            &circuit,
+        );
+        iterate(&mut control, &mut circuit, 1.075, &mut queue);
+        assert!((pv(&circuit).get_pct_pmpp() - 25.0).abs() < 1e-9);
+        assert!(pv(&circuit).get_kvar() < 0.0);
+
+        assert_eq!(parse_window("2m").unwrap(), 120.0);
+        assert!(parse_window("x").is_err());
+    }
+
+    #[test]
+    fn test_curves() {
+        let mut circuit = circuit_with_pv("");
+        let curve = edited(
+            XYCurve::new("vw"),
+            &XYCurveClass,
+            "xarray=[1 1.05 1.1] yarray=[1 1 0]",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(curve));
+
+        let mut control = edited(
+            InvControl::new("ic"),
+            &InvControlClass,
+            "mode=voltwatt voltwatt_curve=VW",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(
+            control.voltwatt_points,
+            [(1.0, 1.0), (1.05, 1.0), (1.1, 0.0)]
+        );
+        let mut queue = ControlQueue::new();
+        iterate(&mut control, &mut circuit, 1.075, &mut queue);
+        assert!((pv(&circuit).get_pct_pmpp() - 50.0).abs() < 1e-9);
+
+        let err = edit(
+            &mut control,
+            &InvControlClass,
+            "vvc_curve1=nowhere",
+            &circuit,
+        )
+        .unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+}
@@ -0,0 +1,436 @@
+// Isource (Pascal TIsource): an ideal current source between its two
+// terminals, injecting balanced currents of the given magnitude and angle
+// into bus1. It has no admittance of its own. At harmonics it injects its
+// current scaled by its spectrum, the phase angles following scantype.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::vsource::Sequence;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, read_choice};
+
+const ISOURCE_PROPERTIES: [PropertyDef; 11] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of bus to which source is connected. bus1=busname bus1=busname.1.2.3",
+    },
+    PropertyDef {
+        name: "amps",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Magnitude of current source, each phase, in Amps.",
+    },
+    PropertyDef {
+        name: "angle",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Phase angle in degrees of first phase: e.g.,Angle=10.3. Phase shift between phases is assumed 120 degrees when number of phases <= 3",
+    },
+    PropertyDef {
+        name: "frequency",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Source frequency.  Defaults to  circuit fundamental frequency.",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases.  Defaults to 3. For 3 or less, phase shift is 120 degrees.",
+    },
+    PropertyDef {
+        name: "scantype",
+        kind: PropertyKind::Choice(ScanType::NAMES),
+        default: "pos",
+        help: "{pos*| zero | none} Maintain specified sequence for harmonic frequency scan. Default is positive sequence. Otherwise, angle between phases rotates with harmonic.",
+    },
+    PropertyDef {
+        name: "sequence",
+        kind: PropertyKind::Choice(Sequence::NAMES),
+        default: "pos",
+        help: "{pos*| neg | zero} Set the phase angles for the specified symmetrical component sequence for non-harmonic solution modes. Default is positive sequence. ",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit current for YEARLY-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual Amp. Must be previously defined as a LOADSHAPE object. Is set to the Daily load shape when Daily is defined.  The daily load shape is repeated in this case. Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit current for DAILY-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual A. Must be previously defined as a LOADSHAPE object. Sets Yearly curve if it is not already defined.   Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit current for DUTYCYCLE-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual A. Must be previously defined as a LOADSHAPE object. Defaults to Daily load shape when Daily is defined.   Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of bus to which 2nd terminal is connected. bus2=busname bus2=busname.1.2.3 Default is Bus1.0.0.0 (grounded-wye connection)",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 14] = concat_properties(&ISOURCE_PROPERTIES, &PC_PROPERTIES);
+
+// How the phase angles of a source behave at harmonics (Pascal ScanType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanType {
+    // the phases stay 120 degrees apart, positive sequence
+    Positive,
+    // all phases in phase, zero sequence
+    Zero,
+    // the shifts between phases turn with the harmonic
+    None,
+}
+
+impl ScanType {
+    pub const NAMES: &'static [&'static str] = &["pos", "zero", "none"];
+
+    // Scan type of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "zero" => ScanType::Zero,
+            "none" => ScanType::None,
+            _ => ScanType::Positive,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IsourceClass;
+
+#[derive(Debug, Clone)]
+pub struct Isource {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    amps: f64,
+    angle: f64,
+    frequency: f64,
+    scan_type: ScanType,
+    sequence: Sequence,
+    yearly: String,
+    daily: String,
+    duty: String,
+    bus2_given: bool,
+    // Multiplier of the current set by the solution from the shapes
+    amps_mult: f64,
+    // Frequency Yprim was last built at; the source drives only at its own
+    yprim_frequency: f64,
+}
+
+impl Isource {
+    pub fn new(name: &str) -> Self {
+        Isource {
+            base: ObjectBase::new("Isource", name, &PROPERTIES),
+            ckt: CktElementBase::new(3, 2),
+            pc: PcElementBase::new("defaultvsource"),
+            amps: 0.0,
+            angle: 0.0,
+            frequency: 60.0,
+            scan_type: ScanType::Positive,
+            sequence: Sequence::Positive,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            bus2_given: false,
+            amps_mult: 1.0,
+            yprim_frequency: 60.0,
+        }
+    }
+
+    pub fn get_amps(&self) -> f64 {
+        self.amps
+    }
+
+    pub fn get_angle(&self) -> f64 {
+        self.angle
+    }
+
+    pub fn get_frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    pub fn set_amps_mult(&mut self, mult: f64) {
+        self.amps_mult = mult;
+    }
+
+    // Shift between the phases of the sequence, radians
+    fn phase_step(&self, sequence: Sequence) -> f64 {
+        let nphases = self.ckt.nphases();
+        let step = if nphases <= 3 {
+            2.0 * PI / 3.0
+        } else {
+            2.0 * PI / nphases as f64
+        };
+        match sequence {
+            Sequence::Positive => -step,
+            Sequence::Negative => step,
+            Sequence::Zero => 0.0,
+        }
+    }
+
+    // Current injected into each phase of bus1 at the fundamental
+    pub fn source_currents(&self) -> Vec<Complex64> {
+        let step = self.phase_step(self.sequence);
+        let amps = self.amps * self.amps_mult;
+        (0..self.ckt.nphases())
+            .map(|phase| Complex64::from_polar(amps, self.angle.to_radians() + step * phase as f64))
+            .collect()
+    }
+
+    // Current injected into each phase at a harmonic, given the spectrum
+    // multiplier for it
+    pub fn harmonic_currents(&self, harmonic: f64, multiplier: Complex64) -> Vec<Complex64> {
+        let amps = self.amps * self.amps_mult * multiplier.norm();
+        let first = harmonic * self.angle.to_radians() + multiplier.arg();
+        let step = match self.scan_type {
+            ScanType::Positive => self.phase_step(Sequence::Positive),
+            ScanType::Zero => 0.0,
+            ScanType::None => harmonic * self.phase_step(Sequence::Positive),
+        };
+        (0..self.ckt.nphases())
+            .map(|phase| Complex64::from_polar(amps, first + step * phase as f64))
+            .collect()
+    }
+
+    // Bus2 follows bus1 with its nodes grounded unless given
+    fn set_default_bus2(&mut self) {
+        if self.bus2_given {
+            return;
+        }
+        let bus1 = self.ckt.get_bus(0);
+        let name = bus1.split('.').next().unwrap_or("").to_string();
+        if !name.is_empty() {
+            let bus2 = format!("{}{}", name, ".0".repeat(self.ckt.nphases()));
+            self.ckt.set_bus(1, &bus2);
+        }
+    }
+}
+
+impl DssObject for Isource {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = ISOURCE_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - ISOURCE_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "amps" => self.amps = parser.make_double()?,
+            "angle" => self.angle = parser.make_double()?,
+            "frequency" => self.frequency = parser.make_double()?,
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+            }
+            "scantype" => {
+                self.scan_type =
+                    ScanType::from_name(read_choice(parser, ScanType::NAMES, "scantype")?)
+            }
+            "sequence" => {
+                self.sequence =
+                    Sequence::from_name(read_choice(parser, Sequence::NAMES, "sequence")?)
+            }
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            "bus2" => {
+                self.ckt.set_bus(1, parser.get_token());
+                self.bus2_given = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.set_default_bus2();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Isource {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // An ideal source has no admittance
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        self.yprim_frequency = frequency;
+        self.ckt.set_yprim(CMatrix::new(self.ckt.y_order()));
+        Ok(())
+    }
+
+    // The source currents into bus1 and back out of bus2; none away from
+    // the source frequency, where the harmonic currents take over
+    fn get_injection_currents(&self, _voltages: &[Complex64]) -> Vec<Complex64> {
+        let nphases = self.ckt.nphases();
+        let mut injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        if (self.yprim_frequency - self.frequency).abs() > 1e-6 * self.frequency {
+            return injection;
+        }
+        for (phase, current) in self.source_currents().into_iter().enumerate() {
+            injection[phase] = current;
+            injection[phase + nphases] = -current;
+        }
+        injection
+    }
+}
+
+impl PcElement for Isource {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+}
+
+impl DssClass for IsourceClass {
+    fn name(&self) -> &'static str {
+        "Isource"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Isource::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close, edited, placed};
+
+    fn new_source(properties: &str) -> Isource {
+        let source = edited(
+            Isource::new("i1"),
+            &IsourceClass,
+            properties,
+            &Circuit::new("test"),
+        )
+        .unwrap();
+        placed(source, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_injection() {
+        let source = new_source("bus1=b amps=100 angle=-30");
+        assert_eq!(source.get_bus(1), "b.0.0.0");
+        // the current flows out of terminal 1 into the bus
+        let currents = source.get_currents(&[Complex64::new(0.0, 0.0); 4]);
+        assert_close(currents[0], -Complex64::from_polar(100.0, -PI / 6.0), 1e-9);
+        assert_close(
+            currents[1],
+            -Complex64::from_polar(100.0, -PI / 6.0 - 2.0 * PI / 3.0),
+            1e-9,
+        );
+        assert_close(currents[3], -currents[0], 1e-9);
+
+        let single = new_source("bus1=b.2 phases=1 amps=5 bus2=c.1");
+        assert_eq!(single.get_bus(1), "c.1");
+        assert_eq!(single.nconds(), 1);
+
+        let mut harmonic = new_source("amps=100");
+        harmonic.calc_yprim(180.0).unwrap();
+        let injection = harmonic.get_injection_currents(&[]);
+        assert!(injection.iter().all(|current| current.norm() == 0.0));
+    }
+
+    #[test]
+    fn test_harmonic_currents() {
+        let multiplier = Complex64::from_polar(0.2, 0.5);
+        let positive = new_source("amps=100 angle=10").harmonic_currents(5.0, multiplier);
+        let first = 5.0 * 10.0_f64.to_radians() + 0.5;
+        assert_close(positive[0], Complex64::from_polar(20.0, first), 1e-9);
+        assert_close(
+            positive[1],
+            Complex64::from_polar(20.0, first - 2.0 * PI / 3.0),
+            1e-9,
+        );
+
+        let zero = new_source("amps=100 scantype=zero").harmonic_currents(3.0, multiplier);
+        assert_close(zero[2], zero[0], 1e-9);
+        // with no scan type the 5th harmonic turns into negative sequence
+        let none = new_source("amps=100 scantype=none").harmonic_currents(5.0, multiplier);
+        assert_close(
+            none[1],
+            none[0] * Complex64::from_polar(1.0, 2.0 * PI / 3.0),
+            1e-9,
+        );
+    }
+}
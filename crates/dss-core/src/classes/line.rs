@@ -0,0 +1,886 @@
+// Line (Pascal TLine): a multi-phase pi-section. The series impedance and
+// shunt capacitance are kept per unit length, from sequence values, matrices,
+// a line code, a geometry or a spacing with wires, and scaled by the length
+// when Yprim is built.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::line_code::LineCode;
+use crate::classes::line_geometry::{LineGeometry, Wire, wire_constants};
+use crate::classes::line_spacing::LineSpacing;
+use crate::cmatrix::CMatrix;
+use crate::line_constants::LineConstants;
+use crate::object::{DssObject, ObjectBase};
+use crate::pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names, read_choice,
+};
+use crate::units::LengthUnit;
+
+const LINE_PROPERTIES: [PropertyDef; 26] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of bus to which first terminal is connected, e.g. bus1=busname or bus1=busname.3.1.2.0 to give the nodes explicitly.",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of bus to which 2nd terminal is connected.",
+    },
+    PropertyDef {
+        name: "linecode",
+        kind: PropertyKind::Object("LineCode"),
+        default: "",
+        help: "Name of linecode object describing line impedances. The line code must have been previously defined; values specified later prevail.",
+    },
+    PropertyDef {
+        name: "length",
+        kind: PropertyKind::Double,
+        default: "1.0",
+        help: "Length of line. If units do not match the impedance data, specify \"units\" property.",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases, this line.",
+    },
+    PropertyDef {
+        name: "r1",
+        kind: PropertyKind::Double,
+        default: "0.058",
+        help: "Positive-sequence Resistance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "x1",
+        kind: PropertyKind::Double,
+        default: "0.1206",
+        help: "Positive-sequence Reactance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "r0",
+        kind: PropertyKind::Double,
+        default: "0.1784",
+        help: "Zero-sequence Resistance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "x0",
+        kind: PropertyKind::Double,
+        default: "0.4047",
+        help: "Zero-sequence Reactance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "c1",
+        kind: PropertyKind::Double,
+        default: "3.4",
+        help: "Positive-sequence capacitance, nf per unit length. Setting any of R1, R0, X1, X0, C1, C0 forces the symmetrical component line definition.",
+    },
+    PropertyDef {
+        name: "c0",
+        kind: PropertyKind::Double,
+        default: "1.6",
+        help: "Zero-sequence capacitance, nf per unit length.",
+    },
+    PropertyDef {
+        name: "rmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Resistance matrix, lower triangle, ohms per unit length. Order of the matrix is the number of phases. Using any of Rmatrix, Xmatrix, Cmatrix forces the matrix line definition.",
+    },
+    PropertyDef {
+        name: "xmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Reactance matrix, lower triangle, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "cmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Nodal Capacitance matrix, lower triangle, nf per unit length.",
+    },
+    PropertyDef {
+        name: "switch",
+        kind: PropertyKind::Bool,
+        default: "false",
+        help: "{y/n | T/F} Designates this line as a switch. Side effect: sets r1=1, x1=1, r0=1, x0=1, c1=1.1, c0=1, length=0.001.",
+    },
+    PropertyDef {
+        name: "rg",
+        kind: PropertyKind::Double,
+        default: "0.01805",
+        help: "Carson earth return resistance per unit length used to adjust impedances for frequency. Default is the 60 Hz value in ohms per kft.",
+    },
+    PropertyDef {
+        name: "xg",
+        kind: PropertyKind::Double,
+        default: "0.155081",
+        help: "Carson earth return reactance per unit length used to adjust impedances for frequency. Default is the 60 Hz value in ohms per kft.",
+    },
+    PropertyDef {
+        name: "rho",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Earth resistivity used to compute the earth correction factor, ohm-m.",
+    },
+    PropertyDef {
+        name: "geometry",
+        kind: PropertyKind::Object("LineGeometry"),
+        default: "",
+        help: "Geometry code for LineGeometry object. Supersedes any previous definition of line impedance.",
+    },
+    PropertyDef {
+        name: "units",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "none",
+        help: "Length Units = {none | mi|kft|km|m|Ft|in|cm|mm}. None assumes length units match impedance units.",
+    },
+    PropertyDef {
+        name: "spacing",
+        kind: PropertyKind::Object("LineSpacing"),
+        default: "",
+        help: "Reference to a LineSpacing for use in a line constants calculation. Must be used in conjunction with the Wires property.",
+    },
+    PropertyDef {
+        name: "wires",
+        kind: PropertyKind::Objects("WireData"),
+        default: "",
+        help: "Array of WireData names for use in an overhead line constants calculation. Specify the Spacing first, and ncond wires. May also be used to specify bare neutrals with cables, using ncond-nphase wires.",
+    },
+    PropertyDef {
+        name: "cncables",
+        kind: PropertyKind::Objects("CNData"),
+        default: "",
+        help: "Array of CNData names for use in a cable constants calculation. Specify the Spacing first, using only nphases cncables. You may later specify the neutral wires with the Wires property.",
+    },
+    PropertyDef {
+        name: "tscables",
+        kind: PropertyKind::Objects("TSData"),
+        default: "",
+        help: "Array of TSData names for use in a cable constants calculation. Specify the Spacing first, using only nphases tscables. You may later specify the neutral wires with the Wires property.",
+    },
+    PropertyDef {
+        name: "b1",
+        kind: PropertyKind::Double,
+        default: "1.2818",
+        help: "Alternate way to specify C1. MicroS per unit length.",
+    },
+    PropertyDef {
+        name: "b0",
+        kind: PropertyKind::Double,
+        default: "0.60319",
+        help: "Alternate way to specify C0. MicroS per unit length.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 33] = concat_properties(&LINE_PROPERTIES, &PD_PROPERTIES);
+
+// Z and Yc of `nphases` phases from sequence values: self terms
+// (2 Z1 + Z0) / 3, mutual terms (Z0 - Z1) / 3, and the same for Y
+pub(crate) fn sequence_matrices(
+    nphases: usize,
+    z1: Complex64,
+    z0: Complex64,
+    y1: Complex64,
+    y0: Complex64,
+) -> (CMatrix, CMatrix) {
+    let (zs, zm) = ((2.0 * z1 + z0) / 3.0, (z0 - z1) / 3.0);
+    let (ys, ym) = ((2.0 * y1 + y0) / 3.0, (y0 - y1) / 3.0);
+    let mut z = CMatrix::new(nphases);
+    let mut yc = CMatrix::new(nphases);
+    for i in 0..nphases {
+        z.set(i, i, zs);
+        yc.set(i, i, ys);
+        for j in 0..i {
+            z.set_sym(i, j, zm);
+            yc.set_sym(i, j, ym);
+        }
+    }
+    (z, yc)
+}
+
+// Reads a lower-triangle matrix into the real or imaginary parts of `m`
+pub(crate) fn read_matrix(
+    parser: &mut DSSParser,
+    m: &mut CMatrix,
+    scale: f64,
+    imaginary: bool,
+) -> DssResult<()> {
+    let order = m.order();
+    let values = parser.parse_as_sym_matrix(order)?;
+    for i in 0..order {
+        for j in 0..order {
+            let value = values[i + j * order] * scale;
+            let old = m.get(i, j);
+            if imaginary {
+                m.set(i, j, Complex64::new(old.re, value));
+            } else {
+                m.set(i, j, Complex64::new(value, old.im));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct LineClass;
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pd: PdElementBase,
+    // Sequence data per unit length; capacitances in F
+    r1: f64,
+    x1: f64,
+    r0: f64,
+    x0: f64,
+    c1: f64,
+    c0: f64,
+    // Z and Yc come from the sequence data rather than matrices
+    symmetrical: bool,
+    // Series impedance and shunt admittance per unit length at the base
+    // frequency
+    z: CMatrix,
+    yc: CMatrix,
+    length: f64,
+    length_units: LengthUnit,
+    // Units the impedances are per, when they come from a line code or
+    // geometry with units of its own
+    z_units: Option<LengthUnit>,
+    rg: f64,
+    xg: f64,
+    rho: f64,
+    is_switch: bool,
+    line_code: String,
+    geometry: String,
+    spacing: String,
+    // The spacing and the wires given for its conductors so far
+    line_spacing: Option<LineSpacing>,
+    wires: Vec<Option<Wire>>,
+    // Names given by the last wires=, cncables= or tscables=
+    wire_names: Vec<String>,
+    // Conductors of the geometry or spacing; Z and Yc are computed from them
+    // again for each frequency and earth resistivity
+    constants: Option<LineConstants>,
+}
+
+impl Line {
+    pub fn new(name: &str) -> Self {
+        let mut line = Line {
+            base: ObjectBase::new("Line", name, &PROPERTIES),
+            ckt: CktElementBase::new(3, 2),
+            pd: PdElementBase::new(),
+            r1: 0.058,
+            x1: 0.1206,
+            r0: 0.1784,
+            x0: 0.4047,
+            c1: 3.4e-9,
+            c0: 1.6e-9,
+            symmetrical: true,
+            z: CMatrix::new(3),
+            yc: CMatrix::new(3),
+            length: 1.0,
+            length_units: LengthUnit::None,
+            z_units: None,
+            rg: 0.01805,
+            xg: 0.155081,
+            rho: 100.0,
+            is_switch: false,
+            line_code: String::new(),
+            geometry: String::new(),
+            spacing: String::new(),
+            line_spacing: None,
+            wires: Vec::new(),
+            wire_names: Vec::new(),
+            constants: None,
+        };
+        line.calc_sequence_matrices();
+        line
+    }
+
+    pub fn get_length(&self) -> f64 {
+        self.length
+    }
+
+    pub fn get_length_units(&self) -> LengthUnit {
+        self.length_units
+    }
+
+    pub fn is_switch(&self) -> bool {
+        self.is_switch
+    }
+
+    pub fn get_line_code(&self) -> &str {
+        &self.line_code
+    }
+
+    pub fn get_geometry(&self) -> &str {
+        &self.geometry
+    }
+
+    pub fn get_spacing(&self) -> &str {
+        &self.spacing
+    }
+
+    pub fn get_rho(&self) -> f64 {
+        self.rho
+    }
+
+    // Series impedance per unit length at the base frequency
+    pub fn z(&self) -> &CMatrix {
+        &self.z
+    }
+
+    // Shunt admittance per unit length at the base frequency
+    pub fn yc(&self) -> &CMatrix {
+        &self.yc
+    }
+
+    // Series impedance of the whole line at the base frequency
+    pub fn total_z(&self) -> CMatrix {
+        let mut z = self.z.clone();
+        z.scale(Complex64::new(self.length_in_z_units(), 0.0));
+        z
+    }
+
+    // Shunt admittance of the whole line at the base frequency
+    pub fn total_yc(&self) -> CMatrix {
+        let mut yc = self.yc.clone();
+        yc.scale(Complex64::new(self.length_in_z_units(), 0.0));
+        yc
+    }
+
+    fn omega(&self) -> f64 {
+        2.0 * PI * self.ckt.get_base_frequency()
+    }
+
+    fn calc_sequence_matrices(&mut self) {
+        (self.z, self.yc) = sequence_matrices(
+            self.ckt.nphases(),
+            Complex64::new(self.r1, self.x1),
+            Complex64::new(self.r0, self.x0),
+            Complex64::new(0.0, self.omega() * self.c1),
+            Complex64::new(0.0, self.omega() * self.c0),
+        );
+    }
+
+    // Takes over the data of a line code (Pascal FetchLineCode); properties
+    // set after linecode= still override it
+    fn fetch_line_code(&mut self, code: &LineCode) {
+        self.ckt.set_phases(code.nphases());
+        self.ckt.set_conductors(code.nphases());
+        [self.r1, self.x1, self.r0, self.x0, self.c1, self.c0] = code.sequence_values();
+        self.symmetrical = code.is_symmetrical();
+        self.z = code.z().clone();
+        self.yc = code.yc().clone();
+        self.z_units = Some(code.get_units()).filter(|&units| units != LengthUnit::None);
+        self.ckt.set_base_frequency(code.get_base_frequency());
+        let [norm_amps, emerg_amps, fault_rate, pct_perm, hrs_to_repair] = code.ratings();
+        self.pd.set_norm_amps(norm_amps);
+        self.pd.set_emerg_amps(emerg_amps);
+        self.pd.set_fault_rate(fault_rate);
+        self.pd.set_pct_perm(pct_perm);
+        self.pd.set_hrs_to_repair(hrs_to_repair);
+        [self.rg, self.xg, self.rho] = code.earth_return();
+    }
+
+    // Takes over the conductors of a geometry (Pascal FetchGeometryCode).
+    // The line keeps all the conductors unless the geometry reduces them
+    // to the phases.
+    fn fetch_geometry(&mut self, geometry: &LineGeometry) -> DssResult<()> {
+        let constants = geometry.line_constants()?;
+        self.line_spacing = None;
+        self.wires.clear();
+        self.set_constants(
+            constants,
+            geometry.get_units(),
+            [geometry.get_norm_amps(), geometry.get_emerg_amps()],
+        )
+    }
+
+    // Takes over a spacing (Pascal FetchLineSpacing); the wires are given
+    // for its conductors afterwards
+    fn fetch_spacing(&mut self, spacing: &LineSpacing) {
+        self.wires = vec![None; spacing.nconds()];
+        self.line_spacing = Some(spacing.clone());
+        self.geometry.clear();
+        self.constants = None;
+    }
+
+    // Wires or cables for the conductors of the spacing (Pascal
+    // FetchWireList and the cable lists). Cables go on the phases; wires
+    // given after them are the bare neutrals.
+    fn fetch_wires(&mut self, property: &str, circuit: &Circuit) -> DssResult<()> {
+        let Some(spacing) = &self.line_spacing else {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "The spacing of {} must be given before its {}",
+                    self.full_name(),
+                    property
+                ),
+            ));
+        };
+        let class = match property {
+            "wires" => "WireData",
+            "cncables" => "CNData",
+            _ => "TSData",
+        };
+        let names = self.wire_names.clone();
+        let (positions, nphases, units) =
+            (spacing.positions(), spacing.nphases(), spacing.get_units());
+        let after_cables = matches!(self.wires.first(), Some(Some(wire)) if wire.is_cable());
+        let start = if class == "WireData" && after_cables {
+            nphases
+        } else {
+            0
+        };
+        if start + names.len() > self.wires.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} {} given for {} conductors of {}",
+                    names.len(),
+                    property,
+                    self.wires.len() - start,
+                    self.full_name()
+                ),
+            ));
+        }
+        for (i, name) in names.iter().enumerate() {
+            let wire = Wire::find(circuit, class, name).ok_or_else(|| {
+                DssError::new(
+                    codes::OBJECT_NOT_FOUND,
+                    &format!("{} \"{}\" not found for {}", class, name, self.full_name()),
+                )
+            })?;
+            self.wires[start + i] = Some(wire);
+        }
+
+        // the constants follow once every conductor has its wire
+        if self.wires.iter().any(Option::is_none) {
+            return Ok(());
+        }
+        let constants = wire_constants(&self.wires, &positions, nphases, false, &self.full_name())?;
+        let ratings = self.wires[0].as_ref().map(|wire| {
+            [
+                wire.conductor().get_norm_amps(),
+                wire.conductor().get_emerg_amps(),
+            ]
+        });
+        self.set_constants(constants, units, ratings.unwrap_or_default())
+    }
+
+    // Puts the line on computed constants per unit of `units`; its length is
+    // in those units too unless given
+    fn set_constants(
+        &mut self,
+        constants: LineConstants,
+        units: LengthUnit,
+        [norm_amps, emerg_amps]: [f64; 2],
+    ) -> DssResult<()> {
+        self.ckt.set_phases(constants.nphases());
+        self.ckt.set_conductors(constants.nconds());
+        self.pd.set_norm_amps(norm_amps);
+        self.pd.set_emerg_amps(emerg_amps);
+        self.z_units = Some(units);
+        if self.length_units == LengthUnit::None {
+            self.length_units = units;
+        }
+        self.symmetrical = false;
+        self.constants = Some(constants);
+        let base_frequency = self.ckt.get_base_frequency();
+        if let Some((z, yc)) = self.geometry_matrices(base_frequency)? {
+            (self.z, self.yc) = (z, yc);
+        }
+        Ok(())
+    }
+
+    // Impedances given directly or from a line code replace computed ones,
+    // and the line is back to a conductor per phase
+    fn clear_constants(&mut self) {
+        if self.constants.take().is_some() {
+            self.ckt.set_conductors(self.ckt.nphases());
+        }
+        self.line_spacing = None;
+        self.wires.clear();
+    }
+
+    // Z and Yc per unit of z_units at a frequency, from the geometry
+    fn geometry_matrices(&self, frequency: f64) -> DssResult<Option<(CMatrix, CMatrix)>> {
+        let Some(constants) = &self.constants else {
+            return Ok(None);
+        };
+        let (mut z, mut yc) = constants.calc(frequency, self.rho)?;
+        let meters = Complex64::new(self.z_units.map_or(1.0, LengthUnit::to_meters), 0.0);
+        z.scale(meters);
+        yc.scale(meters);
+        Ok(Some((z, yc)))
+    }
+
+    fn set_switch(&mut self) {
+        self.r1 = 1.0;
+        self.x1 = 1.0;
+        self.r0 = 1.0;
+        self.x0 = 1.0;
+        self.c1 = 1.1e-9;
+        self.c0 = 1.0e-9;
+        self.length = 0.001;
+        self.length_units = LengthUnit::None;
+        self.z_units = None;
+        self.symmetrical = true;
+    }
+
+    // Length in the units the impedances are per
+    fn length_in_z_units(&self) -> f64 {
+        match self.z_units {
+            Some(units) => self.length * self.length_units.conversion(units),
+            None => self.length,
+        }
+    }
+}
+
+impl DssObject for Line {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = LINE_PROPERTIES.get(index) else {
+            return self.set_pd_property(index - LINE_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "bus2" => self.ckt.set_bus(1, parser.get_token()),
+            "linecode" => self.line_code = parser.get_token().to_lowercase(),
+            "length" => self.length = parser.make_double()?,
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                if phases != self.ckt.nphases() {
+                    self.ckt.set_phases(phases);
+                    self.symmetrical = true;
+                }
+            }
+            "r1" => self.r1 = parser.make_double()?,
+            "x1" => self.x1 = parser.make_double()?,
+            "r0" => self.r0 = parser.make_double()?,
+            "x0" => self.x0 = parser.make_double()?,
+            "c1" => self.c1 = parser.make_double()? * 1e-9,
+            "c0" => self.c0 = parser.make_double()? * 1e-9,
+            "b1" => self.c1 = parser.make_double()? * 1e-6 / self.omega(),
+            "b0" => self.c0 = parser.make_double()? * 1e-6 / self.omega(),
+            "rmatrix" => read_matrix(parser, &mut self.z, 1.0, false)?,
+            "xmatrix" => read_matrix(parser, &mut self.z, 1.0, true)?,
+            "cmatrix" => {
+                let omega = self.omega();
+                read_matrix(parser, &mut self.yc, omega * 1e-9, true)?;
+            }
+            "switch" => {
+                self.is_switch = interpret_yes_no(parser.get_token());
+                if self.is_switch {
+                    self.set_switch();
+                }
+            }
+            "rg" => self.rg = parser.make_double()?,
+            "xg" => self.xg = parser.make_double()?,
+            "rho" => self.rho = parser.make_double()?,
+            "geometry" => self.geometry = parser.get_token().to_lowercase(),
+            "spacing" => self.spacing = parser.get_token().to_lowercase(),
+            "wires" | "cncables" | "tscables" => self.wire_names = parse_names(parser.get_token()),
+            "units" => {
+                let units = read_choice(parser, LengthUnit::NAMES, "units")?;
+                self.length_units = LengthUnit::from_name(units);
+            }
+            _ => {}
+        }
+        match property.name {
+            "r1" | "x1" | "r0" | "x0" | "c1" | "c0" | "b1" | "b0" => {
+                self.symmetrical = true;
+                self.z_units = None;
+                self.clear_constants();
+            }
+            "rmatrix" | "xmatrix" | "cmatrix" => {
+                self.symmetrical = false;
+                self.z_units = None;
+                self.clear_constants();
+            }
+            "linecode" | "switch" => self.clear_constants(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        match LINE_PROPERTIES.get(index).map(|property| property.name) {
+            Some("linecode") => {}
+            Some("geometry") => {
+                let geometry = circuit
+                    .find_object_as::<LineGeometry>("LineGeometry", &self.geometry)
+                    .ok_or_else(|| {
+                        DssError::new(
+                            codes::OBJECT_NOT_FOUND,
+                            &format!(
+                                "Line geometry \"{}\" not found for {}",
+                                self.geometry,
+                                self.full_name()
+                            ),
+                        )
+                    })?;
+                return self.fetch_geometry(geometry);
+            }
+            Some("spacing") => {
+                let spacing = circuit
+                    .find_object_as::<LineSpacing>("LineSpacing", &self.spacing)
+                    .ok_or_else(|| {
+                        DssError::new(
+                            codes::OBJECT_NOT_FOUND,
+                            &format!(
+                                "Line spacing \"{}\" not found for {}",
+                                self.spacing,
+                                self.full_name()
+                            ),
+                        )
+                    })?;
+                self.fetch_spacing(spacing);
+                return Ok(());
+            }
+            Some(property @ ("wires" | "cncables" | "tscables")) => {
+                return self.fetch_wires(property, circuit);
+            }
+            _ => return Ok(()),
+        }
+        let code = circuit
+            .find_object_as::<LineCode>("LineCode", &self.line_code)
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::OBJECT_NOT_FOUND,
+                    &format!(
+                        "Line code \"{}\" not found for {}",
+                        self.line_code,
+                        self.full_name()
+                    ),
+                )
+            })?;
+        self.fetch_line_code(code);
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.symmetrical {
+            self.calc_sequence_matrices();
+        }
+        let base_frequency = self.ckt.get_base_frequency();
+        if let Some((z, yc)) = self.geometry_matrices(base_frequency)? {
+            (self.z, self.yc) = (z, yc);
+        }
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Line {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        Some(self)
+    }
+
+    // Series admittance Zinv between the terminals, with half the shunt
+    // admittance at each end. Lines defined by a geometry have their line
+    // constants computed at the frequency; the others are corrected for it
+    // through the earth return terms Rg and Xg.
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nconds = self.ckt.nconds();
+        let length = self.length_in_z_units();
+        let mut zinv = CMatrix::new(nconds);
+        let mut yc = CMatrix::new(nconds);
+        if let Some((z, y)) = self.geometry_matrices(frequency)? {
+            for i in 0..nconds {
+                for j in 0..nconds {
+                    zinv.set(i, j, z.get(i, j) * length);
+                    yc.set(i, j, y.get(i, j) * length);
+                }
+            }
+        } else {
+            let freq_mult = frequency / self.ckt.get_base_frequency();
+            for i in 0..nconds {
+                for j in 0..nconds {
+                    let z = self.z.get(i, j);
+                    zinv.set(
+                        i,
+                        j,
+                        Complex64::new(
+                            (z.re + self.rg * (freq_mult - 1.0)) * length,
+                            (z.im - self.xg * freq_mult.ln()) * length * freq_mult,
+                        ),
+                    );
+                    yc.set(i, j, self.yc.get(i, j) * (length * freq_mult));
+                }
+            }
+        }
+        if !zinv.invert() {
+            return Err(DssError::new(
+                codes::SINGULAR_MATRIX,
+                &format!("Series Z matrix is singular for {}", self.full_name()),
+            ));
+        }
+
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for i in 0..nconds {
+            for j in 0..nconds {
+                let y = zinv.get(i, j);
+                let shunt = yc.get(i, j) / 2.0;
+                yprim.set(i, j, y + shunt);
+                yprim.set(i + nconds, j + nconds, y + shunt);
+                yprim.set(i, j + nconds, -y);
+                yprim.set(i + nconds, j, -y);
+            }
+        }
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+}
+
+impl PdElement for Line {
+    fn pd_base(&self) -> &PdElementBase {
+        &self.pd
+    }
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase {
+        &mut self.pd
+    }
+}
+
+impl DssClass for LineClass {
+    fn name(&self) -> &'static str {
+        "Line"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Line::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_close, edited};
+
+    fn new_line(properties: &str) -> Line {
+        edited(
+            Line::new("l1"),
+            &LineClass,
+            properties,
+            &Circuit::new("test"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sequence_impedances() {
+        let mut line = new_line("b1 b2 phases=1 r1=1 x1=0 r0=1 x0=0 c1=0 c0=0 length=2");
+        assert_eq!(line.get_bus(1), "b2");
+        line.calc_yprim(60.0).unwrap();
+        let yprim = line.ckt_base().get_yprim().unwrap();
+        assert_eq!(yprim.order(), 2);
+        assert_close(yprim.get(0, 0), Complex64::new(0.5, 0.0), 1e-9);
+        assert_close(yprim.get(0, 1), Complex64::new(-0.5, 0.0), 1e-9);
+
+        // 3 phases: self (2 Z1 + Z0) / 3, mutual (Z0 - Z1) / 3
+        let line = new_line("r1=1 x1=2 r0=4 x0=5");
+        assert_close(line.z().get(0, 0), Complex64::new(2.0, 3.0), 1e-9);
+        assert_close(line.z().get(2, 1), Complex64::new(1.0, 1.0), 1e-9);
+        assert_close(
+            line.yc().get(1, 1),
+            Complex64::new(0.0, 2.0 * PI * 60.0 * (2.0 * 3.4 + 1.6) / 3.0 * 1e-9),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn test_matrices_and_shunt() {
+        let mut line = new_line(
+            "phases=2 rmatrix=[1 | 0 1] xmatrix=[1 | 0.5 1] cmatrix=[10 | -2 10] length=3",
+        );
+        assert_close(line.z().get(1, 0), Complex64::new(0.0, 0.5), 1e-9);
+        line.calc_yprim(60.0).unwrap();
+        let yprim = line.ckt_base().get_yprim().unwrap();
+        assert_eq!(yprim.order(), 4);
+
+        // the series part sums to zero across a row; the shunt part is left
+        let row: Complex64 = (0..4).map(|j| yprim.get(0, j)).sum();
+        let shunt = 2.0 * PI * 60.0 * 8.0e-9 * 3.0 / 2.0;
+        assert_close(row, Complex64::new(0.0, shunt), 1e-9);
+    }
+
+    #[test]
+    fn test_switch_and_errors() {
+        let line = new_line("switch=yes");
+        assert!(line.is_switch());
+        assert_eq!(line.get_length(), 0.001);
+        assert_close(line.z().get(0, 0), Complex64::new(1.0, 1.0), 1e-9);
+
+        let line = new_line("units=kft length=2");
+        assert_eq!(line.get_length_units(), LengthUnit::Kft);
+
+        let mut line = new_line("phases=1 rmatrix=[0] xmatrix=[0]");
+        let err = line.calc_yprim(60.0).unwrap_err();
+        assert_eq!(err.number(), codes::SINGULAR_MATRIX);
+    }
+
+    #[test]
+    fn test_bus_connections() {
+        let mut circuit = Circuit::new("test");
+        let id = circuit.add_element(Box::new(new_line("sourcebus b2.1.2.3")));
+        circuit.make_bus_list().unwrap();
+        let line = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        assert_eq!(line.ckt_base().node_refs(), [1, 2, 3, 4, 5, 6]);
+        assert!(line.as_pd_element().is_some());
+    }
+}
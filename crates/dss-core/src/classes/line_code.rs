@@ -0,0 +1,463 @@
+// LineCode (Pascal TLineCode): impedance data per unit length shared by the
+// lines that name it, given as sequence values or as matrices. A line copies
+// the code's data when its linecode property is set.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::class::DssClass;
+use crate::classes::line::{read_matrix, sequence_matrices};
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, interpret_yes_no, read_choice};
+use crate::units::LengthUnit;
+
+static PROPERTIES: [PropertyDef; 24] = [
+    PropertyDef {
+        name: "nphases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases in the line this line code data represents.",
+    },
+    PropertyDef {
+        name: "r1",
+        kind: PropertyKind::Double,
+        default: "0.058",
+        help: "Positive-sequence Resistance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "x1",
+        kind: PropertyKind::Double,
+        default: "0.1206",
+        help: "Positive-sequence Reactance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "r0",
+        kind: PropertyKind::Double,
+        default: "0.1784",
+        help: "Zero-sequence Resistance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "x0",
+        kind: PropertyKind::Double,
+        default: "0.4047",
+        help: "Zero-sequence Reactance, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "c1",
+        kind: PropertyKind::Double,
+        default: "3.4",
+        help: "Positive-sequence capacitance, nf per unit length.",
+    },
+    PropertyDef {
+        name: "c0",
+        kind: PropertyKind::Double,
+        default: "1.6",
+        help: "Zero-sequence capacitance, nf per unit length.",
+    },
+    PropertyDef {
+        name: "units",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "none",
+        help: "One of (ohms per ...) {none|mi|km|kft|m|ft|in|cm|mm}. Lines using the code convert their length to these units.",
+    },
+    PropertyDef {
+        name: "rmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Resistance matrix, lower triangle, ohms per unit length. Order of the matrix is the number of phases.",
+    },
+    PropertyDef {
+        name: "xmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Reactance matrix, lower triangle, ohms per unit length.",
+    },
+    PropertyDef {
+        name: "cmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Nodal Capacitance matrix, lower triangle, nf per unit length.",
+    },
+    PropertyDef {
+        name: "basefreq",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Frequency at which impedances are specified, Hz.",
+    },
+    PropertyDef {
+        name: "normamps",
+        kind: PropertyKind::Double,
+        default: "400",
+        help: "Normal ampere limit on line. This is the so-called Planning Limit.",
+    },
+    PropertyDef {
+        name: "emergamps",
+        kind: PropertyKind::Double,
+        default: "600",
+        help: "Emergency ampere limit on line (usually one-hour rating).",
+    },
+    PropertyDef {
+        name: "faultrate",
+        kind: PropertyKind::Double,
+        default: "0.1",
+        help: "Number of faults per unit length per year.",
+    },
+    PropertyDef {
+        name: "pctperm",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Percentage of the faults that become permanent.",
+    },
+    PropertyDef {
+        name: "repair",
+        kind: PropertyKind::Double,
+        default: "3",
+        help: "Hours to repair.",
+    },
+    PropertyDef {
+        name: "kron",
+        kind: PropertyKind::Bool,
+        default: "n",
+        help: "Y/N. Perform Kron reduction on the impedance matrix after it is formed, eliminating the conductor given by \"neutral\". Do this after the matrices are defined.",
+    },
+    PropertyDef {
+        name: "rg",
+        kind: PropertyKind::Double,
+        default: "0.01805",
+        help: "Carson earth return resistance per unit length used to adjust impedances for frequency.",
+    },
+    PropertyDef {
+        name: "xg",
+        kind: PropertyKind::Double,
+        default: "0.155081",
+        help: "Carson earth return reactance per unit length used to adjust impedances for frequency.",
+    },
+    PropertyDef {
+        name: "rho",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Earth resistivity, ohm-m.",
+    },
+    PropertyDef {
+        name: "neutral",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Designates which conductor is the \"neutral\" conductor that will be eliminated by Kron reduction. Default is the last conductor.",
+    },
+    PropertyDef {
+        name: "b1",
+        kind: PropertyKind::Double,
+        default: "1.2818",
+        help: "Alternate way to specify C1. MicroS per unit length.",
+    },
+    PropertyDef {
+        name: "b0",
+        kind: PropertyKind::Double,
+        default: "0.60319",
+        help: "Alternate way to specify C0. MicroS per unit length.",
+    },
+];
+
+#[derive(Debug)]
+pub struct LineCodeClass;
+
+#[derive(Debug, Clone)]
+pub struct LineCode {
+    base: ObjectBase,
+    r1: f64,
+    x1: f64,
+    r0: f64,
+    x0: f64,
+    c1: f64,
+    c0: f64,
+    symmetrical: bool,
+    z: CMatrix,
+    yc: CMatrix,
+    units: LengthUnit,
+    base_frequency: f64,
+    norm_amps: f64,
+    emerg_amps: f64,
+    fault_rate: f64,
+    pct_perm: f64,
+    hrs_to_repair: f64,
+    rg: f64,
+    xg: f64,
+    rho: f64,
+    // 1-based conductor Kron reduction eliminates
+    neutral: usize,
+}
+
+impl LineCode {
+    pub fn new(name: &str) -> Self {
+        let mut code = LineCode {
+            base: ObjectBase::new("LineCode", name, &PROPERTIES),
+            r1: 0.058,
+            x1: 0.1206,
+            r0: 0.1784,
+            x0: 0.4047,
+            c1: 3.4e-9,
+            c0: 1.6e-9,
+            symmetrical: true,
+            z: CMatrix::new(3),
+            yc: CMatrix::new(3),
+            units: LengthUnit::None,
+            base_frequency: 60.0,
+            norm_amps: 400.0,
+            emerg_amps: 600.0,
+            fault_rate: 0.1,
+            pct_perm: 20.0,
+            hrs_to_repair: 3.0,
+            rg: 0.01805,
+            xg: 0.155081,
+            rho: 100.0,
+            neutral: 3,
+        };
+        code.calc_sequence_matrices(3);
+        code
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.z.order()
+    }
+
+    pub fn is_symmetrical(&self) -> bool {
+        self.symmetrical
+    }
+
+    // Positive and zero sequence impedances, and capacitances in F
+    pub fn sequence_values(&self) -> [f64; 6] {
+        [self.r1, self.x1, self.r0, self.x0, self.c1, self.c0]
+    }
+
+    // Series impedance per unit length at the base frequency
+    pub fn z(&self) -> &CMatrix {
+        &self.z
+    }
+
+    // Shunt admittance per unit length at the base frequency
+    pub fn yc(&self) -> &CMatrix {
+        &self.yc
+    }
+
+    pub fn get_units(&self) -> LengthUnit {
+        self.units
+    }
+
+    pub fn get_base_frequency(&self) -> f64 {
+        self.base_frequency
+    }
+
+    // Normal and emergency ratings, faults per unit length per year,
+    // percent permanent and hours to repair
+    pub fn ratings(&self) -> [f64; 5] {
+        [
+            self.norm_amps,
+            self.emerg_amps,
+            self.fault_rate,
+            self.pct_perm,
+            self.hrs_to_repair,
+        ]
+    }
+
+    // Carson earth return resistance and reactance, and earth resistivity
+    pub fn earth_return(&self) -> [f64; 3] {
+        [self.rg, self.xg, self.rho]
+    }
+
+    fn omega(&self) -> f64 {
+        2.0 * PI * self.base_frequency
+    }
+
+    fn calc_sequence_matrices(&mut self, nphases: usize) {
+        (self.z, self.yc) = sequence_matrices(
+            nphases,
+            Complex64::new(self.r1, self.x1),
+            Complex64::new(self.r0, self.x0),
+            Complex64::new(0.0, self.omega() * self.c1),
+            Complex64::new(0.0, self.omega() * self.c0),
+        );
+    }
+
+    // Eliminates the neutral conductor of matrix data; the capacitance is reduced through
+    // its inverse so the neutral is held at zero voltage, not zero current
+    fn kron_reduce(&mut self) {
+        let nphases = self.nphases();
+        if self.symmetrical || self.neutral == 0 || self.neutral > nphases || nphases < 2 {
+            return;
+        }
+        let neutral = self.neutral - 1;
+        self.z = self.z.kron(neutral);
+        let mut inverse = self.yc.clone();
+        if inverse.invert() {
+            let mut yc = inverse.kron(neutral);
+            if yc.invert() {
+                self.yc = yc;
+            }
+        } else {
+            self.yc = self.yc.kron(neutral);
+        }
+        self.symmetrical = false;
+        self.neutral = 0;
+    }
+}
+
+impl DssObject for LineCode {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "nphases" => {
+                let nphases = parser.make_integer()?.max(1) as usize;
+                if nphases != self.nphases() {
+                    self.neutral = nphases;
+                    self.symmetrical = true;
+                    self.calc_sequence_matrices(nphases);
+                }
+            }
+            "r1" => self.r1 = parser.make_double()?,
+            "x1" => self.x1 = parser.make_double()?,
+            "r0" => self.r0 = parser.make_double()?,
+            "x0" => self.x0 = parser.make_double()?,
+            "c1" => self.c1 = parser.make_double()? * 1e-9,
+            "c0" => self.c0 = parser.make_double()? * 1e-9,
+            "b1" => self.c1 = parser.make_double()? * 1e-6 / self.omega(),
+            "b0" => self.c0 = parser.make_double()? * 1e-6 / self.omega(),
+            "units" => {
+                let units = read_choice(parser, LengthUnit::NAMES, "units")?;
+                self.units = LengthUnit::from_name(units);
+            }
+            "rmatrix" => read_matrix(parser, &mut self.z, 1.0, false)?,
+            "xmatrix" => read_matrix(parser, &mut self.z, 1.0, true)?,
+            "cmatrix" => {
+                let omega = self.omega();
+                read_matrix(parser, &mut self.yc, omega * 1e-9, true)?;
+            }
+            "basefreq" => self.base_frequency = parser.make_double()?,
+            "normamps" => self.norm_amps = parser.make_double()?,
+            "emergamps" => self.emerg_amps = parser.make_double()?,
+            "faultrate" => self.fault_rate = parser.make_double()?,
+            "pctperm" => self.pct_perm = parser.make_double()?,
+            "repair" => self.hrs_to_repair = parser.make_double()?,
+            "kron" if interpret_yes_no(parser.get_token()) => self.kron_reduce(),
+            "rg" => self.rg = parser.make_double()?,
+            "xg" => self.xg = parser.make_double()?,
+            "rho" => self.rho = parser.make_double()?,
+            "neutral" => self.neutral = parser.make_integer()?.max(0) as usize,
+            _ => {}
+        }
+        match PROPERTIES[index].name {
+            "r1" | "x1" | "r0" | "x0" | "c1" | "c0" | "b1" | "b0" => self.symmetrical = true,
+            "rmatrix" | "xmatrix" | "cmatrix" => self.symmetrical = false,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.symmetrical {
+            self.calc_sequence_matrices(self.nphases());
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for LineCodeClass {
+    fn name(&self) -> &'static str {
+        "LineCode"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(LineCode::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::ckt_element::CktElement;
+    use crate::classes::line::{Line, LineClass};
+    use crate::pd_element::PdElement;
+    use crate::test_util;
+
+    fn edit(class: &dyn DssClass, object: &mut dyn DssObject, line: &str, circuit: &Circuit) {
+        test_util::edit(object, class, line, circuit).unwrap();
+    }
+
+    #[test]
+    fn test_kron_reduction() {
+        let circuit = Circuit::new("test");
+        let mut code = LineCode::new("4wire");
+        edit(
+            &LineCodeClass,
+            &mut code,
+            "nphases=4 rmatrix=[2 | 1 2 | 1 1 2 | 1 1 1 2] xmatrix=[0 | 0 0 | 0 0 0 | 0 0 0 0] kron=yes",
+            &circuit,
+        );
+        assert_eq!(code.nphases(), 3);
+        assert!(!code.is_symmetrical());
+        assert!((code.z().get(0, 0) - Complex64::new(1.5, 0.0)).norm() < 1e-12);
+        assert!((code.z().get(1, 0) - Complex64::new(0.5, 0.0)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_line_uses_code() {
+        let mut circuit = Circuit::new("test");
+        let mut code = LineCode::new("1ph");
+        edit(
+            &LineCodeClass,
+            &mut code,
+            "nphases=1 r1=0.3 x1=0.4 r0=0.3 x0=0.4 c1=0 c0=0 units=mi normamps=250",
+            &circuit,
+        );
+        circuit.add_element(Box::new(code));
+
+        // 528 ft is 0.1 mi
+        let mut line = Line::new("l1");
+        edit(
+            &LineClass,
+            &mut line,
+            "bus1=a bus2=b linecode=1PH length=0.528 units=kft",
+            &circuit,
+        );
+        assert_eq!(line.nphases(), 1);
+        assert_eq!(line.pd_base().get_norm_amps(), 250.0);
+        line.calc_yprim(60.0).unwrap();
+        let y = line.ckt_base().get_yprim().unwrap().get(0, 0);
+        assert!((y - Complex64::new(1.0, 0.0) / Complex64::new(0.03, 0.04)).norm() < 1e-9);
+
+        // values given after the code override it
+        edit(&LineClass, &mut line, "normamps=100", &circuit);
+        assert_eq!(line.pd_base().get_norm_amps(), 100.0);
+
+        let err = test_util::edit(&mut line, &LineClass, "linecode=missing", &circuit).unwrap_err();
+        assert_eq!(err.number(), dss_common::codes::OBJECT_NOT_FOUND);
+    }
+}
@@ -0,0 +1,539 @@
+// LineGeometry (Pascal TLineGeometry): the positions of the conductors of a
+// line and the wire each one is, from which the line constants are computed.
+// Conductors are defined one at a time: cond= selects one, and wire= (or
+// cncable=, tscable= for cables), x=, h= and units= then apply to it.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::cn_data::CNData;
+use crate::classes::ts_data::TSData;
+use crate::classes::wire_data::{ConductorData, WireData};
+use crate::line_constants::{Conductor, LineConstants};
+use crate::object::{DssObject, ObjectBase, copy_like};
+use crate::property::{PropertyDef, PropertyKind, interpret_yes_no, read_choice};
+use crate::units::LengthUnit;
+
+static PROPERTIES: [PropertyDef; 12] = [
+    PropertyDef {
+        name: "nconds",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of conductors in this geometry. Define first!",
+    },
+    PropertyDef {
+        name: "nphases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases. All other conductors are considered neutrals and might be reduced out.",
+    },
+    PropertyDef {
+        name: "cond",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Set this = number of the conductor you wish to define.",
+    },
+    PropertyDef {
+        name: "wire",
+        kind: PropertyKind::Object("WireData"),
+        default: "",
+        help: "Code from WireData. MUST BE PREVIOUSLY DEFINED.",
+    },
+    PropertyDef {
+        name: "x",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "x coordinate.",
+    },
+    PropertyDef {
+        name: "h",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Height of conductor.",
+    },
+    PropertyDef {
+        name: "units",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "ft",
+        help: "Units for x and h: {mi|kft|km|m|Ft|in|cm|mm}. Initial default is \"ft\", but defaults to last unit defined.",
+    },
+    PropertyDef {
+        name: "normamps",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Normal ampacity, amperes for the line. Defaults to first conductor if not specified.",
+    },
+    PropertyDef {
+        name: "emergamps",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Emergency ampacity, amperes. Defaults to first conductor if not specified.",
+    },
+    PropertyDef {
+        name: "reduce",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No} Reduce to Nphases (Kron Reduction). Reduce out neutrals.",
+    },
+    PropertyDef {
+        name: "cncable",
+        kind: PropertyKind::Object("CNData"),
+        default: "",
+        help: "Code from CNData. MUST BE PREVIOUSLY DEFINED. Specifies use of Concentric Neutral cable parameter calculation.",
+    },
+    PropertyDef {
+        name: "tscable",
+        kind: PropertyKind::Object("TSData"),
+        default: "",
+        help: "Code from TSData. MUST BE PREVIOUSLY DEFINED. Specifies use of Tape Shield cable parameter calculation.",
+    },
+];
+
+// What a conductor of a geometry or a spaced line is
+#[derive(Debug, Clone)]
+pub(crate) enum Wire {
+    Bare(ConductorData),
+    ConcentricNeutral(CNData),
+    TapeShield(TSData),
+}
+
+impl Wire {
+    // The wire or cable named, of class WireData, CNData or TSData
+    pub(crate) fn find(circuit: &Circuit, class: &str, name: &str) -> Option<Wire> {
+        match class {
+            "WireData" => circuit
+                .find_object_as::<WireData>(class, name)
+                .map(|wire| Wire::Bare(wire.conductor().clone())),
+            "CNData" => circuit
+                .find_object_as::<CNData>(class, name)
+                .map(|cable| Wire::ConcentricNeutral(cable.clone())),
+            "TSData" => circuit
+                .find_object_as::<TSData>(class, name)
+                .map(|cable| Wire::TapeShield(cable.clone())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_cable(&self) -> bool {
+        !matches!(self, Wire::Bare(_))
+    }
+
+    pub(crate) fn conductor(&self) -> &ConductorData {
+        match self {
+            Wire::Bare(conductor) => conductor,
+            Wire::ConcentricNeutral(cable) => cable.cable().conductor(),
+            Wire::TapeShield(cable) => cable.cable().conductor(),
+        }
+    }
+
+    fn to_conductor(&self, x: f64, h: f64, name: &str) -> DssResult<Conductor> {
+        match self {
+            Wire::Bare(conductor) => {
+                conductor.check(name)?;
+                Ok(Conductor {
+                    x,
+                    h,
+                    rac: conductor.rac_per_meter(),
+                    gmr: conductor.gmr_meters(),
+                    capradius: conductor.capradius_meters(),
+                    shield: None,
+                })
+            }
+            Wire::ConcentricNeutral(cable) => cable.to_conductor(x, h, name),
+            Wire::TapeShield(cable) => cable.to_conductor(x, h, name),
+        }
+    }
+}
+
+// The line constants of wires at (x, h) in meters; `owner` names the
+// geometry or line for the errors
+pub(crate) fn wire_constants(
+    wires: &[Option<Wire>],
+    positions: &[(f64, f64)],
+    nphases: usize,
+    reduce: bool,
+    owner: &str,
+) -> DssResult<LineConstants> {
+    let mut conductors = Vec::new();
+    for (i, (wire, &(x, h))) in wires.iter().zip(positions).enumerate() {
+        let Some(wire) = wire else {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("No wire given for conductor {} of {}", i + 1, owner),
+            ));
+        };
+        let name = format!("conductor {} of {}", i + 1, owner);
+        conductors.push(wire.to_conductor(x, h, &name)?);
+    }
+    Ok(LineConstants::new(conductors, nphases, reduce))
+}
+
+#[derive(Debug)]
+pub struct LineGeometryClass;
+
+#[derive(Debug, Clone)]
+pub struct LineGeometry {
+    base: ObjectBase,
+    nphases: usize,
+    // 0-based conductor cond= selected
+    active: usize,
+    wires: Vec<Option<Wire>>,
+    // Name given by the last wire=, cncable= or tscable=
+    wire_name: String,
+    x: Vec<f64>,
+    h: Vec<f64>,
+    units: Vec<LengthUnit>,
+    // Units set last, those lines using the geometry measure length in
+    last_units: LengthUnit,
+    norm_amps: f64,
+    emerg_amps: f64,
+    reduce: bool,
+}
+
+impl LineGeometry {
+    pub fn new(name: &str) -> Self {
+        let mut geometry = LineGeometry {
+            base: ObjectBase::new("LineGeometry", name, &PROPERTIES),
+            nphases: 3,
+            active: 0,
+            wires: Vec::new(),
+            wire_name: String::new(),
+            x: Vec::new(),
+            h: Vec::new(),
+            units: Vec::new(),
+            last_units: LengthUnit::Feet,
+            norm_amps: 0.0,
+            emerg_amps: 0.0,
+            reduce: false,
+        };
+        geometry.set_nconds(3);
+        geometry
+    }
+
+    pub fn nconds(&self) -> usize {
+        self.wires.len()
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.nphases
+    }
+
+    pub fn get_units(&self) -> LengthUnit {
+        self.last_units
+    }
+
+    fn set_nconds(&mut self, nconds: usize) {
+        self.wires.resize(nconds, None);
+        self.x.resize(nconds, 0.0);
+        self.h.resize(nconds, 0.0);
+        self.units.resize(nconds, self.last_units);
+        self.active = self.active.min(nconds.saturating_sub(1));
+    }
+
+    // Ratings of the line, those of the first conductor unless given
+    pub fn get_norm_amps(&self) -> f64 {
+        match self.wires.first() {
+            Some(Some(wire)) if self.norm_amps <= 0.0 => wire.conductor().get_norm_amps(),
+            _ => self.norm_amps,
+        }
+    }
+
+    pub fn get_emerg_amps(&self) -> f64 {
+        match self.wires.first() {
+            Some(Some(wire)) if self.emerg_amps <= 0.0 => wire.conductor().get_emerg_amps(),
+            _ => self.emerg_amps,
+        }
+    }
+
+    // The conductors in meters, ready for the line constants calculation
+    pub fn line_constants(&self) -> DssResult<LineConstants> {
+        let positions: Vec<(f64, f64)> = (0..self.nconds())
+            .map(|i| {
+                let meters = self.units[i].to_meters();
+                (self.x[i] * meters, self.h[i] * meters)
+            })
+            .collect();
+        wire_constants(
+            &self.wires,
+            &positions,
+            self.nphases,
+            self.reduce,
+            &self.full_name(),
+        )
+    }
+}
+
+impl DssObject for LineGeometry {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "nconds" => self.set_nconds(parser.make_integer()?.max(1) as usize),
+            "nphases" => self.nphases = parser.make_integer()?.max(1) as usize,
+            "cond" => {
+                let cond = parser.make_integer()?;
+                if cond < 1 || cond as usize > self.nconds() {
+                    return Err(DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!(
+                            "Conductor {} is out of range for {} with {} conductors",
+                            cond,
+                            self.full_name(),
+                            self.nconds()
+                        ),
+                    ));
+                }
+                self.active = cond as usize - 1;
+            }
+            "x" => self.x[self.active] = parser.make_double()?,
+            "h" => self.h[self.active] = parser.make_double()?,
+            "units" => {
+                let units = LengthUnit::from_name(read_choice(parser, LengthUnit::NAMES, "units")?);
+                // conductors defined later default to these units too
+                for conductor_units in &mut self.units[self.active..] {
+                    *conductor_units = units;
+                }
+                self.last_units = units;
+            }
+            "normamps" => self.norm_amps = parser.make_double()?,
+            "emergamps" => self.emerg_amps = parser.make_double()?,
+            "reduce" => self.reduce = interpret_yes_no(parser.get_token()),
+            "wire" | "cncable" | "tscable" => self.wire_name = parser.get_token().to_string(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let property = PROPERTIES[index].name;
+        let name = self.wire_name.clone();
+        let class = match property {
+            "wire" => "WireData",
+            "cncable" => "CNData",
+            "tscable" => "TSData",
+            _ => return Ok(()),
+        };
+        let wire = Wire::find(circuit, class, &name);
+        let wire = wire.ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!(
+                    "{} \"{}\" not found for {}",
+                    property,
+                    name,
+                    self.full_name()
+                ),
+            )
+        })?;
+        self.wires[self.active] = Some(wire);
+        Ok(())
+    }
+
+    // The properties of the conductors follow the selector set before them,
+    // which setting them again one by one would lose
+    fn make_like(&mut self, other: &dyn DssObject, _circuit: &Circuit) -> DssResult<()> {
+        copy_like(self, other);
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for LineGeometryClass {
+    fn name(&self) -> &'static str {
+        "LineGeometry"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(LineGeometry::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex64;
+
+    use super::*;
+    use crate::ckt_element::CktElement;
+    use crate::classes::LineClass;
+    use crate::classes::cn_data::CNDataClass;
+    use crate::classes::line::Line;
+    use crate::classes::ts_data::TSDataClass;
+    use crate::classes::wire_data::WireDataClass;
+    use crate::pd_element::PdElement;
+    use crate::test_util::{add, edit};
+
+    fn new_object(class: &dyn DssClass, name: &str, line: &str, circuit: &mut Circuit) {
+        add(circuit, class.name(), name, line);
+    }
+
+    // Kersting's example 4.1 as a script
+    fn kersting_circuit(reduce: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        new_object(
+            &WireDataClass,
+            "acsr336",
+            "rac=0.306 runits=mi gmrac=0.0244 gmrunits=ft diam=0.721 radunits=in normamps=530",
+            &mut circuit,
+        );
+        new_object(
+            &WireDataClass,
+            "acsr4/0",
+            "rac=0.592 runits=mi gmrac=0.00814 gmrunits=ft diam=0.563 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &LineGeometryClass,
+            "g1",
+            &format!(
+                "nconds=4 nphases=3 reduce={} cond=1 wire=acsr336 x=0 h=29 units=ft \
+                 cond=2 wire=acsr336 x=2.5 h=29 cond=3 wire=acsr336 x=7 h=29 \
+                 cond=4 wire=acsr4/0 x=4 h=25",
+                reduce
+            ),
+            &mut circuit,
+        );
+        new_object(
+            &LineClass,
+            "l1",
+            "bus1=a bus2=b geometry=g1 length=1 units=mi",
+            &mut circuit,
+        );
+        circuit
+    }
+
+    fn line(circuit: &Circuit) -> Line {
+        let id = circuit.find_element("Line", "l1").unwrap();
+        let line = circuit.element(id).unwrap().as_any().downcast_ref::<Line>();
+        line.unwrap().clone()
+    }
+
+    #[test]
+    fn test_line_from_geometry() {
+        let circuit = kersting_circuit("yes");
+        let mut line = line(&circuit);
+        assert_eq!(line.nconds(), 3);
+        assert_eq!(line.pd_base().get_norm_amps(), 530.0);
+        // per foot, the geometry's units
+        let zaa = line.z().get(0, 0) * 5280.0;
+        assert!((zaa - Complex64::new(0.4576, 1.0780)).norm() < 1e-3);
+
+        line.calc_yprim(60.0).unwrap();
+        let yprim = line.ckt_base().get_yprim().unwrap();
+        assert_eq!(yprim.order(), 6);
+    }
+
+    #[test]
+    fn test_neutral_kept() {
+        let mut circuit = kersting_circuit("no");
+        circuit.make_bus_list().unwrap();
+        let line = line(&circuit);
+        assert_eq!((line.nphases(), line.nconds()), (3, 4));
+        // the neutral defaults to ground
+        assert_eq!(line.ckt_base().terminal_refs(0)[3], 0);
+
+        let mut geometry = LineGeometry::new("g2");
+        let err = edit(
+            &mut geometry,
+            &LineGeometryClass,
+            "nconds=2 cond=3",
+            &circuit,
+        )
+        .unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+        assert!(geometry.line_constants().is_err());
+    }
+
+    fn per_mile(line: &Line, i: usize, j: usize) -> Complex64 {
+        line.z().get(i, j) * 5280.0
+    }
+
+    // Kersting's example 4.2: three concentric neutral cables 6 in apart
+    #[test]
+    fn test_concentric_neutral_geometry() {
+        let mut circuit = Circuit::new("test");
+        new_object(
+            &CNDataClass,
+            "cn250",
+            "k=13 diastrand=0.0641 gmrstrand=0.00208 rstrand=14.8722 diains=1.06 diacable=1.29 \
+             rac=0.41 runits=mi gmrac=0.0171 gmrunits=ft diam=0.567 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &LineGeometryClass,
+            "g1",
+            "nconds=3 nphases=3 cond=1 cncable=cn250 x=0 h=-4 units=ft \
+             cond=2 cncable=cn250 x=0.5 h=-4 cond=3 cncable=cn250 x=1 h=-4",
+            &mut circuit,
+        );
+        new_object(&LineClass, "l1", "bus1=a bus2=b geometry=g1", &mut circuit);
+        let line = line(&circuit);
+        assert_eq!(line.nconds(), 3);
+        assert!((per_mile(&line, 0, 0) - Complex64::new(0.7981, 0.4463)).norm() < 2e-3);
+        assert!((per_mile(&line, 0, 1) - Complex64::new(0.3188, 0.0334)).norm() < 2e-3);
+
+        let mut geometry = LineGeometry::new("g2");
+        let err = edit(
+            &mut geometry,
+            &LineGeometryClass,
+            "cncable=missing",
+            &circuit,
+        )
+        .unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    // Kersting's example 4.3: a tape shielded cable with a separate neutral
+    #[test]
+    fn test_tape_shield_geometry() {
+        let mut circuit = Circuit::new("test");
+        new_object(
+            &TSDataClass,
+            "ts1/0",
+            "diashield=0.88 tapelayer=0.005 tapelap=50 diains=0.78 diacable=1.06 \
+             rac=0.97 runits=mi gmrac=0.0111 gmrunits=ft diam=0.368 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &WireDataClass,
+            "cu1/0",
+            "rac=0.607 runits=mi gmrac=0.01113 gmrunits=ft diam=0.368 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &LineGeometryClass,
+            "g1",
+            "nconds=2 nphases=1 reduce=yes cond=1 tscable=ts1/0 x=0 h=-4 units=ft \
+             cond=2 wire=cu1/0 x=0.25 h=-4",
+            &mut circuit,
+        );
+        new_object(&LineClass, "l1", "bus1=a bus2=b geometry=g1", &mut circuit);
+        let line = line(&circuit);
+        assert_eq!(line.nconds(), 1);
+        assert!((per_mile(&line, 0, 0) - Complex64::new(1.3218, 0.6744)).norm() < 5e-3);
+    }
+}
@@ -0,0 +1,311 @@
+// LineSpacing (Pascal TLineSpacing): the positions of the conductors of a
+// line without the wires, which the line gives itself with wires=,
+// cncables= and tscables=.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::class::DssClass;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_choice, read_doubles};
+use crate::units::LengthUnit;
+
+static PROPERTIES: [PropertyDef; 5] = [
+    PropertyDef {
+        name: "nconds",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of wires in this geometry. Define first!",
+    },
+    PropertyDef {
+        name: "nphases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of retained phase conductors. If less than the number of wires, list the retained phase coordinates first.",
+    },
+    PropertyDef {
+        name: "x",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of wire X coordinates.",
+    },
+    PropertyDef {
+        name: "h",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of wire Heights.",
+    },
+    PropertyDef {
+        name: "units",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "ft",
+        help: "Units for x and h: {mi|kft|km|m|Ft|in|cm } Initial default is \"ft\", but defaults to last unit defined",
+    },
+];
+
+#[derive(Debug)]
+pub struct LineSpacingClass;
+
+#[derive(Debug, Clone)]
+pub struct LineSpacing {
+    base: ObjectBase,
+    nphases: usize,
+    x: Vec<f64>,
+    h: Vec<f64>,
+    units: LengthUnit,
+}
+
+impl LineSpacing {
+    pub fn new(name: &str) -> Self {
+        LineSpacing {
+            base: ObjectBase::new("LineSpacing", name, &PROPERTIES),
+            nphases: 3,
+            x: vec![0.0; 3],
+            h: vec![0.0; 3],
+            units: LengthUnit::Feet,
+        }
+    }
+
+    pub fn nconds(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.nphases
+    }
+
+    pub fn get_units(&self) -> LengthUnit {
+        self.units
+    }
+
+    // (x, h) of each conductor in meters
+    pub fn positions(&self) -> Vec<(f64, f64)> {
+        let meters = self.units.to_meters();
+        self.x
+            .iter()
+            .zip(&self.h)
+            .map(|(x, h)| (x * meters, h * meters))
+            .collect()
+    }
+
+    // Values of x= or h=, one per conductor at most
+    fn read_coordinates(&self, parser: &mut DSSParser, property: &str) -> DssResult<Vec<f64>> {
+        let values = read_doubles(parser)?;
+        if values.len() > self.nconds() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} values given for \"{}\" of {} with {} conductors",
+                    values.len(),
+                    property,
+                    self.full_name(),
+                    self.nconds()
+                ),
+            ));
+        }
+        Ok(values)
+    }
+}
+
+impl DssObject for LineSpacing {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "nconds" => {
+                let nconds = parser.make_integer()?.max(1) as usize;
+                self.x.resize(nconds, 0.0);
+                self.h.resize(nconds, 0.0);
+            }
+            "nphases" => self.nphases = parser.make_integer()?.max(1) as usize,
+            "x" => {
+                let values = self.read_coordinates(parser, "x")?;
+                self.x[..values.len()].copy_from_slice(&values);
+            }
+            "h" => {
+                let values = self.read_coordinates(parser, "h")?;
+                self.h[..values.len()].copy_from_slice(&values);
+            }
+            "units" => {
+                self.units = LengthUnit::from_name(read_choice(parser, LengthUnit::NAMES, "units")?)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.nphases > self.nconds() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has more phases ({}) than conductors ({})",
+                    self.full_name(),
+                    self.nphases,
+                    self.nconds()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for LineSpacingClass {
+    fn name(&self) -> &'static str {
+        "LineSpacing"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(LineSpacing::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex64;
+
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::ckt_element::CktElement;
+    use crate::classes::cn_data::CNDataClass;
+    use crate::classes::line::{Line, LineClass};
+    use crate::classes::wire_data::WireDataClass;
+    use crate::pd_element::PdElement;
+    use crate::test_util::{self, add, edited};
+
+    fn edit(spacing: &mut LineSpacing, line: &str) -> DssResult<()> {
+        test_util::edit(spacing, &LineSpacingClass, line, &Circuit::new("test"))
+    }
+
+    fn new_object(class: &dyn DssClass, name: &str, line: &str, circuit: &mut Circuit) {
+        add(circuit, class.name(), name, line);
+    }
+
+    fn new_line(line: &str, circuit: &Circuit) -> DssResult<Line> {
+        edited(Line::new("l1"), &LineClass, line, circuit)
+    }
+
+    // Kersting's example 4.1 as a spacing
+    fn kersting_circuit() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        new_object(
+            &WireDataClass,
+            "acsr336",
+            "rac=0.306 runits=mi gmrac=0.0244 gmrunits=ft diam=0.721 radunits=in normamps=530",
+            &mut circuit,
+        );
+        new_object(
+            &WireDataClass,
+            "acsr4/0",
+            "rac=0.592 runits=mi gmrac=0.00814 gmrunits=ft diam=0.563 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &LineSpacingClass,
+            "s1",
+            "nconds=4 nphases=3 x=[0 2.5 7 4] h=[29 29 29 25] units=ft",
+            &mut circuit,
+        );
+        circuit
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut spacing = LineSpacing::new("s1");
+        edit(
+            &mut spacing,
+            "nconds=4 nphases=3 x=[0 2.5 7 4] h=[29 29 29 25] units=ft",
+        )
+        .unwrap();
+        assert_eq!(spacing.nconds(), 4);
+        let positions = spacing.positions();
+        assert!((positions[1].0 - 0.762).abs() < 1e-9);
+        assert!((positions[3].1 - 7.62).abs() < 1e-9);
+
+        assert!(edit(&mut spacing, "x=[0 1 2 3 4]").is_err());
+        assert!(edit(&mut spacing, "nphases=5").is_err());
+    }
+
+    #[test]
+    fn test_line_with_spacing_and_wires() {
+        let circuit = kersting_circuit();
+        let line = new_line(
+            "bus1=a bus2=b spacing=s1 wires=[acsr336 acsr336 acsr336 acsr4/0] length=1 units=mi",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!((line.nphases(), line.nconds()), (3, 4));
+        assert_eq!(line.get_spacing(), "s1");
+        assert_eq!(line.pd_base().get_norm_amps(), 530.0);
+        // the neutral is kept, so Zaa is the primitive one, per foot
+        let zaa = line.z().get(0, 0) * 5280.0;
+        assert!((zaa - Complex64::new(0.4013, 1.4133)).norm() < 1e-3);
+
+        // wires need the spacing first, and one per conductor at most
+        let err = new_line("wires=[acsr336]", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+        assert!(new_line("spacing=s1 wires=[a b c d e]", &circuit).is_err());
+        let err = new_line("spacing=s1 wires=[acsr336 x]", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+        // sequence data drops the spacing
+        let line = new_line(
+            "spacing=s1 wires=[acsr336 acsr336 acsr336 acsr4/0] r1=0.1",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!((line.nconds(), line.z().order()), (3, 3));
+        assert!((line.z().get(0, 0).re - (2.0 * 0.1 + 0.1784) / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cables_with_bare_neutral() {
+        let mut circuit = kersting_circuit();
+        new_object(
+            &CNDataClass,
+            "cn250",
+            "k=13 diastrand=0.0641 gmrstrand=0.00208 rstrand=14.8722 diains=1.06 diacable=1.29 \
+             rac=0.41 runits=mi gmrac=0.0171 gmrunits=ft diam=0.567 radunits=in",
+            &mut circuit,
+        );
+        new_object(
+            &LineSpacingClass,
+            "s2",
+            "nconds=4 nphases=3 x=[0 0.5 1 1.5] h=[-4 -4 -4 -4] units=ft",
+            &mut circuit,
+        );
+        let mut line = new_line(
+            "bus1=a bus2=b spacing=s2 cncables=[cn250 cn250 cn250]",
+            &circuit,
+        )
+        .unwrap();
+        // the neutral is still missing
+        assert!(line.get_spacing() == "s2" && line.nconds() == 3);
+        test_util::edit(&mut line, &LineClass, "wires=[acsr4/0]", &circuit).unwrap();
+        assert_eq!((line.nphases(), line.nconds()), (3, 4));
+        assert!(line.z().get(0, 0).re > 0.0);
+    }
+}
@@ -0,0 +1,980 @@
+// Load (Pascal TLoad): a power conversion element drawing kW and kvar from
+// one terminal, wye loads between each phase and the neutral conductor,
+// delta loads between phases. The nominal power is given as kW and pf, kW
+// and kvar, kVA and pf, an allocation of the service transformer's kVA or
+// the kWh billed over a number of days. To the solution a load is its
+// nominal admittance in Yprim plus injection currents making up the
+// difference to the current of its model at the present voltage.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, read_choice, read_doubles};
+
+const LOAD_PROPERTIES: [PropertyDef; 38] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of Phases, this Load.  Load is evenly divided among phases.",
+    },
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Bus to which the load is connected.  May include specific node specification.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "Nominal rated (1.0 per unit) voltage, kV, for load. For 2- and 3-phase loads, specify phase-phase kV. Otherwise, specify actual kV across each branch of the load. If wye (star), specify phase-neutral kV. If delta or phase-phase connected, specify phase-phase kV.",
+    },
+    PropertyDef {
+        name: "kw",
+        kind: PropertyKind::Double,
+        default: "10",
+        help: "Total base kW for the load.  Normally, you would enter the maximum kW for the load for the first year and allow it to be adjusted by the load shapes, growth shapes, and global load multiplier. Legal ways to define base load: kW, PF; kW, kvar; kVA, PF; XFKVA * Allocationfactor, PF; kWh/(kWhdays*24) * Cfactor, PF.",
+    },
+    PropertyDef {
+        name: "pf",
+        kind: PropertyKind::Double,
+        default: "0.88",
+        help: "Load power factor.  Enter negative for leading powerfactor (when kW and kvar have opposite signs.)",
+    },
+    PropertyDef {
+        name: "model",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Integer code for the model to use for load variation with voltage. Valid values are: 1:Standard constant P+jQ load. 2:Constant impedance load. 3:Const P, Quadratic Q (like a motor). 4:Nominal Linear P, Quadratic Q (feeder mix). Use this with CVRfactor. 5:Constant Current Magnitude. 6:Const P, Fixed Q. 7:Const P, Fixed Impedance Q. 8:ZIPV (7 values). For Types 6 and 7, only the P is modified by load multipliers.",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for yearly simulations.  Must be previously defined as a Loadshape object.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for daily simulations.  Must be previously defined as a Loadshape object of 24 hrs, typically.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for duty cycle simulations.  Must be previously defined as a Loadshape object.  Typically would have time intervals less than 1 hr.",
+    },
+    PropertyDef {
+        name: "growth",
+        kind: PropertyKind::Object("GrowthShape"),
+        default: "",
+        help: "Characteristic  to use for growth factors by years.  Must be previously defined as a Growthshape object.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye or LN | delta or LL}.  Default is wye.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Double,
+        default: "5.39742",
+        help: "Specify the base kvar for specifying load as kW & kvar.  Assumes kW has been already defined.  Alternative to specifying the power factor.  Side effect:  the power factor and kVA is altered to agree.",
+    },
+    PropertyDef {
+        name: "rneut",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Default is -1. Neutral resistance of wye (star)-connected load in actual ohms. If entered as a negative value, the neutral can be open, or floating, or it can be connected to node 0 (ground), which is the usual default. If >=0 be sure to explicitly specify the node connection for the neutral, or last, conductor. Otherwise, the neutral impedance will be shorted to ground.",
+    },
+    PropertyDef {
+        name: "xneut",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Neutral reactance of wye(star)-connected load in actual ohms.  May be + or -.",
+    },
+    PropertyDef {
+        name: "status",
+        kind: PropertyKind::Choice(LoadStatus::NAMES),
+        default: "variable",
+        help: "={Variable | Fixed | Exempt}.  Default is variable. If Fixed, no load multipliers apply;  however, growth multipliers do apply.  All multipliers apply to Variable loads.  Exempt loads are not modified by the global load multiplier, such as in load duration curves, etc.  Daily multipliers do apply, so setting this property to Exempt is a good way to represent industrial load that stays the same day-after-day for the period study.",
+    },
+    PropertyDef {
+        name: "class",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "An arbitrary integer number representing the class of load so that load values may be segregated by load value. Default is 1; not used internally.",
+    },
+    PropertyDef {
+        name: "vminpu",
+        kind: PropertyKind::Double,
+        default: "0.95",
+        help: "Default = 0.95.  Minimum per unit voltage for which the MODEL is assumed to apply. Lower end of normal voltage range.Below this value, the load model reverts to a constant impedance model that matches the model at the transition voltage. See also \"Vlowpu\" which causes the model to match Model=2 below the transition voltage.",
+    },
+    PropertyDef {
+        name: "vmaxpu",
+        kind: PropertyKind::Double,
+        default: "1.05",
+        help: "Default = 1.05.  Maximum per unit voltage for which the MODEL is assumed to apply. Above this value, the load model reverts to a constant impedance model.",
+    },
+    PropertyDef {
+        name: "vminnorm",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Minimum per unit voltage for load EEN evaluations, Normal limit.  Default = 0, which defaults to system \"vminnorm\" property (see Set Command under Executive).  If this property is specified, it ALWAYS overrides the system specification. This allows you to have different criteria for different loads. Set to zero to revert to the default system value.",
+    },
+    PropertyDef {
+        name: "vminemerg",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Minimum per unit voltage for load UE evaluations, Emergency limit.  Default = 0, which defaults to system \"vminemerg\" property (see Set Command under Executive).  If this property is specified, it ALWAYS overrides the system specification. This allows you to have different criteria for different loads. Set to zero to revert to the default system value.",
+    },
+    PropertyDef {
+        name: "xfkva",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Default = 0.0.  Rated kVA of service transformer for allocating loads based on connected kVA at a bus. Side effect:  kW, PF, and kvar are modified. See help on kVA.",
+    },
+    PropertyDef {
+        name: "allocationfactor",
+        kind: PropertyKind::Double,
+        default: "0.5",
+        help: "Default = 0.5.  Allocation factor for allocating loads based on connected kVA at a bus. Side effect:  kW, PF, and kvar are modified by multiplying this factor times the XFKVA (if > 0).",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "11.3636",
+        help: "Specify base Load in kVA (and power factor). Legal ways to define base load: kW, PF; kW, kvar; kVA, PF; XFKVA * Allocationfactor, PF; kWh/(kWhdays*24) * Cfactor, PF.",
+    },
+    PropertyDef {
+        name: "%mean",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Percent mean value for load to use for monte carlo studies if no loadshape is assigned to this load. Default is 50.",
+    },
+    PropertyDef {
+        name: "%stddev",
+        kind: PropertyKind::Double,
+        default: "10",
+        help: "Percent Std deviation value for load to use for monte carlo studies if no loadshape is assigned to this load. Default is 10.",
+    },
+    PropertyDef {
+        name: "cvrwatts",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Percent reduction in active power (watts) per 1% reduction in voltage from 100% rated. Default=1.  Typical values range from 0.4 to 0.8. Applies to Model=4 only. Intended to represent conservation voltage reduction or voltage optimization measures.",
+    },
+    PropertyDef {
+        name: "cvrvars",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Percent reduction in reactive power (vars) per 1% reduction in voltage from 100% rated. Default=2.  Typical values range from 2 to 3. Applies to Model=4 only. Intended to represent conservation voltage reduction or voltage optimization measures.",
+    },
+    PropertyDef {
+        name: "kwh",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "kWh billed for this period. Default is 0. See help on kVA and Cfactor and kWhDays.",
+    },
+    PropertyDef {
+        name: "kwhdays",
+        kind: PropertyKind::Double,
+        default: "30",
+        help: "Length of kWh billing period in days (24 hr days). Default is 30. Average demand is computed using this value.",
+    },
+    PropertyDef {
+        name: "cfactor",
+        kind: PropertyKind::Double,
+        default: "4",
+        help: "Factor relating average kW to peak kW. Default is 4.0. See kWh and kWhdays. See kVA.",
+    },
+    PropertyDef {
+        name: "cvrcurve",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Default is NONE. Curve describing both watt and var factors as a function of time. Refers to a LoadShape object with both Mult and Qmult defined. Define a Loadshape to agree with yearly or daily curve according to the type of analysis being done. If NONE, the CVRwatts and CVRvars factors are used and assumed constant.",
+    },
+    PropertyDef {
+        name: "numcust",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of customers, this load. Default is 1.",
+    },
+    PropertyDef {
+        name: "zipv",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of 7 coefficients: First 3 are ZIP weighting factors for real power (should sum to 1), Next 3 are ZIP weighting factors for reactive power (should sum to 1), Last 1 is cut-off voltage in p.u. of base kV; load is 0 below this cut-off. No defaults; all coefficients must be specified if using model=8.",
+    },
+    PropertyDef {
+        name: "%seriesrl",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Percent of load that is series R-L for Harmonic studies. Default is 50. Remainder is assumed to be parallel R and L. This can have a significant impact on the amount of damping observed in Harmonics solutions.",
+    },
+    PropertyDef {
+        name: "relweight",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Relative weighting factor for reliability calcs. Default = 1. Used to designate high priority loads such as hospitals, etc. Is multiplied by number of customers and load kW during reliability calcs.",
+    },
+    PropertyDef {
+        name: "vlowpu",
+        kind: PropertyKind::Double,
+        default: "0.5",
+        help: "Default = 0.50.  Per unit voltage at which the model switches to same as constant Z model (model=2). This allows more consistent convergence at very low voltaes due to opening switches or solving for fault situations.",
+    },
+    PropertyDef {
+        name: "puxharm",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Special reactance, pu (based on kVA, kV properties), for the series impedance branch in the load model for HARMONICS analysis. Generally used to represent motor load blocked rotor reactance. If not specified (that is, set =0, the default value), the series branch is computed from the percentage of the nominal load at fundamental frequency specified by the %SERIESRL property.",
+    },
+    PropertyDef {
+        name: "xrharm",
+        kind: PropertyKind::Double,
+        default: "6",
+        help: "X/R ratio of the special harmonics mode reactance specified by the puXHARM property at fundamental frequency. Default is 6.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 41] = concat_properties(&LOAD_PROPERTIES, &PC_PROPERTIES);
+
+// Neutral admittance of a wye load with rneut=0, in siemens
+const SOLID_NEUTRAL: f64 = 1.0e6;
+
+// How a load follows the load multipliers (Pascal FixedLoad/ExemptLoad)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStatus {
+    Variable,
+    Fixed,
+    Exempt,
+}
+
+impl LoadStatus {
+    pub const NAMES: &'static [&'static str] = &["variable", "fixed", "exempt"];
+
+    // Status of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "fixed" => LoadStatus::Fixed,
+            "exempt" => LoadStatus::Exempt,
+            _ => LoadStatus::Variable,
+        }
+    }
+}
+
+// Which properties the nominal power was last given by (Pascal
+// LoadSpecType); the others are derived from them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadSpec {
+    KwPf,
+    KwKvar,
+    KvaPf,
+    XfKva,
+    Kwh,
+}
+
+#[derive(Debug)]
+pub struct LoadClass;
+
+#[derive(Debug, Clone)]
+pub struct Load {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    connection: Connection,
+    kv: f64,
+    kw: f64,
+    kvar: f64,
+    kva: f64,
+    pf: f64,
+    spec: LoadSpec,
+    model: usize,
+    yearly: String,
+    daily: String,
+    duty: String,
+    growth: String,
+    cvr_curve: String,
+    rneut: f64,
+    xneut: f64,
+    status: LoadStatus,
+    load_class: i32,
+    vminpu: f64,
+    vmaxpu: f64,
+    vminnorm: f64,
+    vminemerg: f64,
+    vlowpu: f64,
+    xfkva: f64,
+    allocation_factor: f64,
+    pct_mean: f64,
+    pct_stddev: f64,
+    cvr_watts: f64,
+    cvr_vars: f64,
+    kwh: f64,
+    kwh_days: f64,
+    cfactor: f64,
+    num_cust: usize,
+    zipv: Vec<f64>,
+    pct_series_rl: f64,
+    rel_weight: f64,
+    pu_xharm: f64,
+    xr_harm: f64,
+    // Multiplier of the nominal power set by the solution from the load
+    // shapes and the global load multiplier
+    load_mult: f64,
+    // Growth of the nominal power in the year of the study
+    growth_mult: f64,
+    // Admittance of each phase in Yprim, as last built
+    y_phase: Complex64,
+}
+
+impl Load {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 1);
+        ckt.set_conductors(4);
+        let mut load = Load {
+            base: ObjectBase::new("Load", name, &PROPERTIES),
+            ckt,
+            pc: PcElementBase::new("defaultload"),
+            connection: Connection::Wye,
+            kv: 12.47,
+            kw: 10.0,
+            kvar: 0.0,
+            kva: 0.0,
+            pf: 0.88,
+            spec: LoadSpec::KwPf,
+            model: 1,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            growth: String::new(),
+            cvr_curve: String::new(),
+            rneut: -1.0,
+            xneut: 0.0,
+            status: LoadStatus::Variable,
+            load_class: 1,
+            vminpu: 0.95,
+            vmaxpu: 1.05,
+            vminnorm: 0.0,
+            vminemerg: 0.0,
+            vlowpu: 0.5,
+            xfkva: 0.0,
+            allocation_factor: 0.5,
+            pct_mean: 50.0,
+            pct_stddev: 10.0,
+            cvr_watts: 1.0,
+            cvr_vars: 2.0,
+            kwh: 0.0,
+            kwh_days: 30.0,
+            cfactor: 4.0,
+            num_cust: 1,
+            zipv: Vec::new(),
+            pct_series_rl: 50.0,
+            rel_weight: 1.0,
+            pu_xharm: 0.0,
+            xr_harm: 6.0,
+            load_mult: 1.0,
+            growth_mult: 1.0,
+            y_phase: Complex64::new(0.0, 0.0),
+        };
+        load.set_nominal_power();
+        load
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_kw(&self) -> f64 {
+        self.kw
+    }
+
+    pub fn get_kvar(&self) -> f64 {
+        self.kvar
+    }
+
+    pub fn get_kva(&self) -> f64 {
+        self.kva
+    }
+
+    pub fn get_pf(&self) -> f64 {
+        self.pf
+    }
+
+    pub fn get_model(&self) -> usize {
+        self.model
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn get_status(&self) -> LoadStatus {
+        self.status
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    pub fn get_growth(&self) -> &str {
+        &self.growth
+    }
+
+    // Mean and spread of the load for Monte Carlo draws without a yearly
+    // shape, in percent
+    pub fn get_pct_mean(&self) -> f64 {
+        self.pct_mean
+    }
+
+    pub fn get_pct_stddev(&self) -> f64 {
+        self.pct_stddev
+    }
+
+    pub fn get_num_cust(&self) -> usize {
+        self.num_cust
+    }
+
+    pub fn get_load_mult(&self) -> f64 {
+        self.load_mult
+    }
+
+    // Scales the nominal power; Yprim follows at the next build
+    pub fn set_load_mult(&mut self, mult: f64) {
+        if mult != self.load_mult {
+            self.load_mult = mult;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    pub fn get_growth_mult(&self) -> f64 {
+        self.growth_mult
+    }
+
+    pub fn set_growth_mult(&mut self, mult: f64) {
+        if mult != self.growth_mult {
+            self.growth_mult = mult;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    pub fn get_allocation_factor(&self) -> f64 {
+        self.allocation_factor
+    }
+
+    pub fn get_cfactor(&self) -> f64 {
+        self.cfactor
+    }
+
+    // Scales a load given by the service transformer kVA or by its kWh, the
+    // allocation factor or the CFactor; other loads stay as given (Pascal
+    // AllocationFactor and CFactor set by the meters). Returns whether the
+    // load changed.
+    pub fn allocate(&mut self, factor: f64) -> bool {
+        match self.spec {
+            LoadSpec::XfKva => self.allocation_factor *= factor,
+            LoadSpec::Kwh => self.cfactor *= factor,
+            _ => return false,
+        }
+        self.set_nominal_power();
+        self.ckt.invalidate_yprim();
+        true
+    }
+
+    // kW, kvar, kVA and pf from whichever of them were given
+    fn set_nominal_power(&mut self) {
+        match self.spec {
+            LoadSpec::KwPf => {}
+            LoadSpec::KwKvar => {
+                let kva = self.kw.hypot(self.kvar);
+                self.pf = if kva == 0.0 { 1.0 } else { self.kw / kva };
+                if self.kw * self.kvar < 0.0 {
+                    self.pf = -self.pf;
+                }
+            }
+            LoadSpec::KvaPf => self.kw = self.kva * self.pf.abs(),
+            LoadSpec::XfKva => self.kw = self.allocation_factor * self.xfkva * self.pf.abs(),
+            LoadSpec::Kwh => self.kw = self.kwh / (self.kwh_days * 24.0) * self.cfactor,
+        }
+        if self.spec != LoadSpec::KwKvar {
+            self.kvar = if self.pf.abs() >= 1.0 {
+                0.0
+            } else {
+                (self.kw * (1.0 / (self.pf * self.pf) - 1.0).sqrt()).copysign(self.pf)
+            };
+        }
+        self.kva = self.kw.hypot(self.kvar);
+    }
+
+    // Voltage across each phase of the load at 1 per unit
+    fn vbase(&self) -> f64 {
+        let nphases = self.ckt.nphases();
+        if self.connection == Connection::Wye && (nphases == 2 || nphases == 3) {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+
+    // Watts and vars of each phase at 1 per unit, load multiplier and growth
+    // included
+    fn phase_power(&self) -> Complex64 {
+        let mult = self.load_mult * self.growth_mult;
+        Complex64::new(self.kw, self.kvar) * 1000.0 * mult / self.ckt.nphases() as f64
+    }
+
+    // Admittance of each phase drawing the nominal power at 1 per unit
+    fn nominal_admittance(&self) -> Complex64 {
+        let vbase = self.vbase();
+        self.phase_power().conj() / (vbase * vbase)
+    }
+
+    // Conductors a phase of the load is connected between
+    fn phase_ends(&self, phase: usize) -> (usize, usize) {
+        let nphases = self.ckt.nphases();
+        match self.connection {
+            Connection::Wye => (phase, nphases),
+            Connection::Delta if nphases == 1 => (0, 1),
+            Connection::Delta => (phase, (phase + 1) % nphases),
+        }
+    }
+
+    fn set_conductors(&mut self) {
+        let nphases = self.ckt.nphases();
+        let nconds = match self.connection {
+            Connection::Wye => nphases + 1,
+            Connection::Delta if nphases == 1 => 2,
+            Connection::Delta => nphases,
+        };
+        if nconds != self.ckt.nconds() {
+            self.ckt.set_conductors(nconds);
+        }
+    }
+
+    // Multiplier of a power that varies with the voltage as `variation`
+    // within vminpu..vmaxpu and as a constant impedance outside
+    fn within_limits(&self, vpu: f64, variation: impl Fn(f64) -> f64) -> f64 {
+        let limited = vpu.clamp(self.vminpu, self.vmaxpu);
+        let ratio = vpu / limited;
+        variation(limited) * ratio * ratio
+    }
+
+    // Current drawn by one phase of the load across voltage `v`, following
+    // the load model
+    fn phase_current(&self, v: Complex64) -> Complex64 {
+        let vmag = v.norm();
+        if vmag == 0.0 {
+            return Complex64::new(0.0, 0.0);
+        }
+        let vpu = vmag / self.vbase();
+        if vpu <= self.vlowpu {
+            return self.nominal_admittance() * v;
+        }
+        let constant = |_: f64| 1.0;
+        let (p_mult, q_mult) = match self.model {
+            2 => (vpu * vpu, vpu * vpu),
+            3 | 7 => (self.within_limits(vpu, constant), vpu * vpu),
+            4 => (
+                self.within_limits(vpu, |v| v.powf(self.cvr_watts)),
+                self.within_limits(vpu, |v| v.powf(self.cvr_vars)),
+            ),
+            5 => {
+                let mult = self.within_limits(vpu, |v| v);
+                (mult, mult)
+            }
+            6 => (self.within_limits(vpu, constant), 1.0),
+            8 => {
+                if vpu < self.zipv[6] {
+                    return Complex64::new(0.0, 0.0);
+                }
+                let zip = |k: &[f64]| k[0] * vpu * vpu + k[1] * vpu + k[2];
+                (zip(&self.zipv[0..3]), zip(&self.zipv[3..6]))
+            }
+            _ => {
+                let mult = self.within_limits(vpu, constant);
+                (mult, mult)
+            }
+        };
+        let power = self.phase_power();
+        (Complex64::new(power.re * p_mult, power.im * q_mult) / v).conj()
+    }
+
+    fn check(&self) -> DssResult<()> {
+        if !(1..=8).contains(&self.model) {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Invalid load model {} for {}; must be 1 to 8",
+                    self.model,
+                    self.full_name()
+                ),
+            ));
+        }
+        if self.model == 8 && self.zipv.len() != 7 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has model 8 but {} ZIPV coefficients; 7 are needed",
+                    self.full_name(),
+                    self.zipv.len()
+                ),
+            ));
+        }
+        if self.pf == 0.0 || self.pf.abs() > 1.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("Invalid power factor {} for {}", self.pf, self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DssObject for Load {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = LOAD_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - LOAD_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_conductors();
+            }
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "kv" => self.kv = parser.make_double()?,
+            "kw" => {
+                self.kw = parser.make_double()?;
+                if self.spec != LoadSpec::KwKvar {
+                    self.spec = LoadSpec::KwPf;
+                }
+            }
+            "pf" => {
+                self.pf = parser.make_double()?;
+                if self.spec == LoadSpec::KwKvar {
+                    self.spec = LoadSpec::KwPf;
+                }
+            }
+            "model" => self.model = parser.make_integer()?.max(0) as usize,
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            "growth" => self.growth = parser.get_token().to_lowercase(),
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.connection = Connection::from_name(name);
+                self.set_conductors();
+            }
+            "kvar" => {
+                self.kvar = parser.make_double()?;
+                self.spec = LoadSpec::KwKvar;
+            }
+            "rneut" => self.rneut = parser.make_double()?,
+            "xneut" => self.xneut = parser.make_double()?,
+            "status" => {
+                self.status =
+                    LoadStatus::from_name(read_choice(parser, LoadStatus::NAMES, "status")?)
+            }
+            "class" => self.load_class = parser.make_integer()?,
+            "vminpu" => self.vminpu = parser.make_double()?,
+            "vmaxpu" => self.vmaxpu = parser.make_double()?,
+            "vminnorm" => self.vminnorm = parser.make_double()?,
+            "vminemerg" => self.vminemerg = parser.make_double()?,
+            "xfkva" => {
+                self.xfkva = parser.make_double()?;
+                self.spec = LoadSpec::XfKva;
+            }
+            "allocationfactor" => {
+                self.allocation_factor = parser.make_double()?;
+                self.spec = LoadSpec::XfKva;
+            }
+            "kva" => {
+                self.kva = parser.make_double()?;
+                self.spec = LoadSpec::KvaPf;
+            }
+            "%mean" => self.pct_mean = parser.make_double()?,
+            "%stddev" => self.pct_stddev = parser.make_double()?,
+            "cvrwatts" => self.cvr_watts = parser.make_double()?,
+            "cvrvars" => self.cvr_vars = parser.make_double()?,
+            "kwh" => {
+                self.kwh = parser.make_double()?;
+                self.spec = LoadSpec::Kwh;
+            }
+            "kwhdays" => {
+                self.kwh_days = parser.make_double()?;
+                self.spec = LoadSpec::Kwh;
+            }
+            "cfactor" => {
+                self.cfactor = parser.make_double()?;
+                self.spec = LoadSpec::Kwh;
+            }
+            "cvrcurve" => self.cvr_curve = parser.get_token().to_lowercase(),
+            "numcust" => self.num_cust = parser.make_integer()?.max(0) as usize,
+            "zipv" => self.zipv = read_doubles(parser)?,
+            "%seriesrl" => self.pct_series_rl = parser.make_double()?,
+            "relweight" => self.rel_weight = parser.make_double()?,
+            "vlowpu" => self.vlowpu = parser.make_double()?,
+            "puxharm" => self.pu_xharm = parser.make_double()?,
+            "xrharm" => self.xr_harm = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.check()?;
+        self.set_nominal_power();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Load {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // The nominal admittance of each phase between its two conductors, its
+    // susceptance scaled to the frequency as an inductance, then the
+    // neutral impedance of a wye load
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nconds = self.ckt.nconds();
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let y_nominal = self.nominal_admittance();
+        let y = Complex64::new(y_nominal.re, y_nominal.im / freq_mult);
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            yprim.add(a, a, y);
+            yprim.add(b, b, y);
+            yprim.add(a, b, -y);
+            yprim.add(b, a, -y);
+        }
+        if self.connection == Connection::Wye && self.rneut >= 0.0 {
+            let z = Complex64::new(self.rneut, self.xneut * freq_mult);
+            let y_neutral = if z.norm() == 0.0 {
+                Complex64::new(SOLID_NEUTRAL, 0.0)
+            } else {
+                z.inv()
+            };
+            yprim.add(nconds - 1, nconds - 1, y_neutral);
+        }
+        self.y_phase = y;
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+
+    // What Yprim draws through each phase less what the load model draws,
+    // so that Yprim less the injection leaves the model's current
+    fn get_injection_currents(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let mut injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        if self.ckt.get_yprim().is_none() {
+            return injection;
+        }
+        let v = self.terminal_voltages(voltages);
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            let across = v[a] - v[b];
+            let current = self.y_phase * across - self.phase_current(across);
+            injection[a] += current;
+            injection[b] -= current;
+        }
+        injection
+    }
+}
+
+impl PcElement for Load {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+}
+
+impl DssClass for LoadClass {
+    fn name(&self) -> &'static str {
+        "Load"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Load::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close, edited, first_nodes, placed};
+
+    fn new_load(properties: &str) -> DssResult<Load> {
+        let load = edited(
+            Load::new("ld1"),
+            &LoadClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        // node 0 is ground, the phases take nodes 1 onwards
+        let nodes = first_nodes(load.nphases());
+        placed(load, &[&nodes])
+    }
+
+    // Power drawn by a single-phase wye load at `vpu` of 1 kV
+    fn power_at(load: &Load, vpu: f64) -> Complex64 {
+        let voltages = [Complex64::new(0.0, 0.0), Complex64::new(1000.0 * vpu, 0.0)];
+        load.total_power(&voltages)
+    }
+
+    #[test]
+    fn test_nominal_power() {
+        let load = new_load("kw=10 pf=0.8").unwrap();
+        assert!((load.get_kvar() - 7.5).abs() < 1e-12);
+        assert!((load.get_kva() - 12.5).abs() < 1e-12);
+        let load = new_load("kw=10 pf=-0.8").unwrap();
+        assert!((load.get_kvar() + 7.5).abs() < 1e-12);
+        let load = new_load("kw=3 kvar=-4").unwrap();
+        assert!((load.get_pf() + 0.6).abs() < 1e-12);
+        let load = new_load("kva=10 pf=0.6").unwrap();
+        assert!((load.get_kw() - 6.0).abs() < 1e-12);
+        let load = new_load("xfkva=50 allocationfactor=0.4 pf=1").unwrap();
+        assert_eq!((load.get_kw(), load.get_kvar()), (20.0, 0.0));
+        let load = new_load("kwh=720 kwhdays=30 cfactor=4 pf=1").unwrap();
+        assert!((load.get_kw() - 4.0).abs() < 1e-12);
+
+        assert!(new_load("pf=0").is_err());
+        assert!(new_load("model=9").is_err());
+        assert!(new_load("model=8 zipv=[1 0 0]").is_err());
+    }
+
+    #[test]
+    fn test_models() {
+        let base = "phases=1 kv=1 kw=1 kvar=1";
+        let load = new_load(&format!("{} model=1", base)).unwrap();
+        assert_close(power_at(&load, 1.0), Complex64::new(1000.0, 1000.0), 1e-6);
+        assert_close(power_at(&load, 0.97), Complex64::new(1000.0, 1000.0), 1e-6);
+        // constant impedance below vminpu, matching at the transition
+        let below = (0.8_f64 / 0.95).powi(2) * 1000.0;
+        assert_close(power_at(&load, 0.8), Complex64::new(below, below), 1e-6);
+        // nominal impedance below vlowpu
+        assert_close(power_at(&load, 0.4), Complex64::new(160.0, 160.0), 1e-6);
+
+        let load = new_load(&format!("{} model=2", base)).unwrap();
+        assert_close(power_at(&load, 0.97), Complex64::new(940.9, 940.9), 1e-6);
+        let load = new_load(&format!("{} model=3", base)).unwrap();
+        assert_close(power_at(&load, 0.97), Complex64::new(1000.0, 940.9), 1e-6);
+        let load = new_load(&format!("{} model=4 cvrwatts=0.8 cvrvars=3", base)).unwrap();
+        let expected = Complex64::new(1000.0 * 0.97_f64.powf(0.8), 1000.0 * 0.97_f64.powi(3));
+        assert_close(power_at(&load, 0.97), expected, 1e-6);
+        let load = new_load(&format!("{} model=5", base)).unwrap();
+        assert_close(power_at(&load, 0.97), Complex64::new(970.0, 970.0), 1e-6);
+        let load = new_load(&format!("{} model=6", base)).unwrap();
+        assert_close(power_at(&load, 0.8), Complex64::new(below, 1000.0), 1e-6);
+        let load = new_load(&format!("{} model=7", base)).unwrap();
+        assert_close(power_at(&load, 0.97), Complex64::new(1000.0, 940.9), 1e-6);
+
+        let load = new_load(&format!("{} model=8 zipv=[0.5 0.5 0 0 0 1 0.7]", base)).unwrap();
+        let p = 1000.0 * (0.5 * 0.9 * 0.9 + 0.5 * 0.9);
+        assert_close(power_at(&load, 0.9), Complex64::new(p, 1000.0), 1e-6);
+        assert_close(power_at(&load, 0.6), Complex64::new(0.0, 0.0), 1e-6);
+    }
+
+    #[test]
+    fn test_three_phase_connections() {
+        let a = Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI / 3.0);
+        let vln = 12470.0 / 3.0_f64.sqrt();
+        let voltages = [
+            Complex64::new(0.0, 0.0),
+            Complex64::new(vln, 0.0),
+            a * vln,
+            a * a * vln,
+        ];
+        for conn in ["wye", "delta"] {
+            let load = new_load(&format!("kw=300 kvar=100 conn={}", conn)).unwrap();
+            assert_close(load.total_power(&voltages), Complex64::new(3e5, 1e5), 1e-6);
+            // at nominal voltage the model and Yprim agree
+            let injection = load.get_injection_currents(&voltages);
+            assert!(injection.iter().all(|current| current.norm() < 1e-9));
+        }
+        let delta = new_load("conn=delta").unwrap();
+        assert_eq!(delta.nconds(), 3);
+        let single = new_load("phases=1 conn=delta").unwrap();
+        assert_eq!(single.nconds(), 2);
+
+        // the load multiplier scales the power drawn
+        let mut load = new_load("kw=300 kvar=100").unwrap();
+        load.set_load_mult(0.5);
+        load.calc_yprim(60.0).unwrap();
+        assert_close(
+            load.total_power(&voltages),
+            Complex64::new(1.5e5, 0.5e5),
+            1e-6,
+        );
+    }
+}
@@ -0,0 +1,799 @@
+// LoadShape (Pascal TLoadShape): multipliers over time for the loads,
+// generators and storage that name the shape as their daily, yearly or duty
+// curve. Points come at a fixed interval or at the hours given, typed in as
+// arrays or read from files; Q multipliers are optional and follow the P
+// multipliers when absent. Values between points are interpolated or held.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::{DSSParser, SplitMix64};
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::class::DssClass;
+use crate::classes::generator::Generator;
+use crate::classes::isource::Isource;
+use crate::classes::load::Load;
+use crate::classes::pv_system::PVSystem;
+use crate::classes::shape_data::{
+    FileFormat, Interpolation, ShapeFile, mean_and_std_dev, value_at,
+};
+use crate::classes::storage::Storage;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, interpret_yes_no, read_choice, read_doubles};
+
+const ACTION_NAMES: &[&str] = &["normalize"];
+
+static PROPERTIES: [PropertyDef; 21] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Max number of points to expect in load shape vectors. This gets reset to the number of multiplier values found (in files only) if less than specified. 0 takes the number of multipliers given.",
+    },
+    PropertyDef {
+        name: "interval",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time interval for fixed interval data, hrs. Default = 1. If Interval = 0 then time data (in hours) may be at either regular or irregular intervals and time value must be specified using either the Hour property or input files. See \"interpolation\" for the values between points.  \n\nSee also \"sinterval\" and \"minterval\".",
+    },
+    PropertyDef {
+        name: "mult",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of multiplier values for active power (P) or other key value (such as pu V for Vsource). \n\nYou can also use the syntax: \n\nmult = (file=filename)     !for text file one value per line\nmult = (dblfile=filename)  !for packed file of doubles\nmult = (sngfile=filename)  !for packed file of singles \nmult = (file=MyCSVFile.CSV, col=3, header=yes)  !for multicolumn CSV files \n\nNote: this property will reset Npts if the  number of values in the files are fewer.\n\nSame as Pmult",
+    },
+    PropertyDef {
+        name: "hour",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of hour values. Only necessary to define for variable interval data (Interval=0). If you set Interval>0 to denote fixed interval data, DO NOT USE THIS PROPERTY. You can also use the syntax: \nhour = (file=filename)     !for text file one value per line\nhour = (dblfile=filename)  !for packed file of doubles\nhour = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "mean",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Mean of the active power multipliers.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently. Used for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "stddev",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Standard deviation of active power multipliers.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently.Is overwritten if you subsequently read in a curve\n\nUsed for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of active power load curve data to a CSV text file containing (hour, mult) points, or simply (mult) values for fixed time interval data, one per line. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sngfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of active power load curve data to a binary file of singles containing (hour, mult) points, or simply (mult) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "dblfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of active power load curve data to a binary file of doubles containing (hour, mult) points, or simply (mult) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(ACTION_NAMES),
+        default: "",
+        help: "{NORMALIZE} NORMALIZE is only action currently supported. Scales the P and Q curves so the peak of each is 1.0 (or Pbase and Qbase when given).",
+    },
+    PropertyDef {
+        name: "qmult",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of multiplier values for reactive power (Q).  You can also use the syntax: \nqmult = (file=filename)     !for text file one value per line\nqmult = (dblfile=filename)  !for packed file of doubles\nqmult = (sngfile=filename)  !for packed file of singles \nqmult = (file=MyCSVFile.CSV, col=4, header=yes)  !for multicolumn CSV files ",
+    },
+    PropertyDef {
+        name: "useactual",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No* | True | False*} If true, signifies to Load, Generator, Vsource, or other objects to use the return value as the actual kW, kvar, kV, or other value rather than a multiplier. Nominally for AMI Load data but may be used for other functions.",
+    },
+    PropertyDef {
+        name: "pmax",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "kW value at the time of max power. Is automatically set upon reading in a loadshape. Use this property to override the value automatically computed or to retrieve the value computed.",
+    },
+    PropertyDef {
+        name: "qmax",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "kvar value at the time of max kW power. Is automatically set upon reading in a loadshape. Use this property to override the value automatically computed or to retrieve the value computed.",
+    },
+    PropertyDef {
+        name: "sinterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in SECONDS. Alternate way to specify Interval property.",
+    },
+    PropertyDef {
+        name: "minterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in MINUTES. Alternate way to specify Interval property.",
+    },
+    PropertyDef {
+        name: "pbase",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Base P value for normalization. Default is zero, meaning the peak will be used.",
+    },
+    PropertyDef {
+        name: "qbase",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Base Q value for normalization. Default is zero, meaning the peak will be used.",
+    },
+    PropertyDef {
+        name: "pmult",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Synonym for \"mult\".",
+    },
+    PropertyDef {
+        name: "pqcsvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input to a CSV text file containing (active, reactive) power (P, Q) multiplier pairs, one per row. \nIf the interval=0, there should be 3 items on each line: (hour, Pmult, Qmult)",
+    },
+    PropertyDef {
+        name: "interpolation",
+        kind: PropertyKind::Choice(Interpolation::NAMES),
+        default: "avg",
+        help: "{AVG* | EDGE} Defines the interpolation method used between points. AVG interpolates linearly between the points around the time asked for; EDGE holds the value of the last point until the next one.",
+    },
+];
+
+// What a file read at the next resolve goes to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileTarget {
+    Mult,
+    QMult,
+    Hours,
+    // (hour,) mult, as read by csvfile=, sngfile= and dblfile=
+    Curve,
+    // (hour,) mult, qmult, as read by pqcsvfile=
+    PqCurve,
+}
+
+#[derive(Debug)]
+pub struct LoadShapeClass;
+
+#[derive(Debug, Clone)]
+pub struct LoadShape {
+    base: ObjectBase,
+    // Number of points; 0 to take as many as the multipliers given
+    npts: usize,
+    // Hours between points; 0 when the points come at the hours given
+    interval: f64,
+    mult: Vec<f64>,
+    qmult: Vec<f64>,
+    hours: Vec<f64>,
+    // Given by mean= and stddev=; computed from the multipliers otherwise
+    mean: Option<f64>,
+    std_dev: Option<f64>,
+    use_actual: bool,
+    // Given by pmax= and qmax=; taken at the peak P otherwise
+    p_max: Option<f64>,
+    q_max: Option<f64>,
+    p_base: f64,
+    q_base: f64,
+    interpolation: Interpolation,
+    // File named by the property set last, read once the circuit gives the
+    // directory it is in
+    pending_file: Option<(FileTarget, ShapeFile)>,
+}
+
+impl LoadShape {
+    pub fn new(name: &str) -> Self {
+        LoadShape {
+            base: ObjectBase::new("LoadShape", name, &PROPERTIES),
+            npts: 0,
+            interval: 1.0,
+            mult: Vec::new(),
+            qmult: Vec::new(),
+            hours: Vec::new(),
+            mean: None,
+            std_dev: None,
+            use_actual: false,
+            p_max: None,
+            q_max: None,
+            p_base: 0.0,
+            q_base: 0.0,
+            interpolation: Interpolation::Avg,
+            pending_file: None,
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        match self.npts {
+            0 => self.mult.len(),
+            npts => npts,
+        }
+    }
+
+    // Hours between points; 0 for points at the hours given
+    pub fn get_interval(&self) -> f64 {
+        self.interval
+    }
+
+    pub fn mult(&self) -> &[f64] {
+        &self.mult
+    }
+
+    // Empty when the Q multipliers follow the P multipliers
+    pub fn qmult(&self) -> &[f64] {
+        &self.qmult
+    }
+
+    pub fn hours(&self) -> &[f64] {
+        &self.hours
+    }
+
+    pub fn is_use_actual(&self) -> bool {
+        self.use_actual
+    }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn get_mean(&self) -> f64 {
+        self.mean.unwrap_or_else(|| mean_and_std_dev(&self.mult).0)
+    }
+
+    pub fn get_std_dev(&self) -> f64 {
+        self.std_dev
+            .unwrap_or_else(|| mean_and_std_dev(&self.mult).1)
+    }
+
+    // P multiplier of largest magnitude
+    pub fn get_p_max(&self) -> f64 {
+        self.p_max
+            .unwrap_or_else(|| self.peak_index().map_or(0.0, |i| self.mult[i]))
+    }
+
+    // Q multiplier at the time of the peak P
+    pub fn get_q_max(&self) -> f64 {
+        self.q_max.unwrap_or_else(|| {
+            self.peak_index()
+                .and_then(|i| self.qmult.get(i))
+                .copied()
+                .unwrap_or(0.0)
+        })
+    }
+
+    // P and Q multipliers at an hour of the solution (Pascal GetMult); the
+    // shape repeats after its last point. A shape without points gives 1.
+    pub fn get_mult(&self, hour: f64) -> Complex64 {
+        if self.mult.is_empty() {
+            return Complex64::new(1.0, 1.0);
+        }
+        let hours = (self.interval == 0.0).then_some(self.hours.as_slice());
+        value_at(
+            hour,
+            self.mult.len(),
+            self.interval,
+            hours,
+            self.interpolation,
+            |i| {
+                let p = self.mult[i];
+                Complex64::new(p, self.qmult.get(i).copied().unwrap_or(p))
+            },
+        )
+    }
+
+    // Scales the curves so the peak of each is 1, or to Pbase and Qbase when
+    // those are given; the values are multipliers afterwards
+    pub fn normalize(&mut self) {
+        normalize(&mut self.mult, self.p_base);
+        normalize(&mut self.qmult, self.q_base);
+        self.use_actual = false;
+        self.p_max = None;
+        self.q_max = None;
+    }
+
+    fn peak_index(&self) -> Option<usize> {
+        (0..self.mult.len()).max_by(|&a, &b| self.mult[a].abs().total_cmp(&self.mult[b].abs()))
+    }
+
+    // Takes the multipliers of a file read
+    fn take_records(&mut self, target: FileTarget, records: Vec<Vec<f64>>) {
+        let column = |k: usize| records.iter().map(|record| record[k]).collect::<Vec<f64>>();
+        let timed = usize::from(self.interval == 0.0);
+        match target {
+            FileTarget::Mult => self.mult = column(0),
+            FileTarget::QMult => self.qmult = column(0),
+            FileTarget::Hours => self.hours = column(0),
+            FileTarget::Curve | FileTarget::PqCurve => {
+                if timed == 1 {
+                    self.hours = column(0);
+                }
+                self.mult = column(timed);
+                if target == FileTarget::PqCurve {
+                    self.qmult = column(timed + 1);
+                }
+            }
+        }
+        // fewer values than npts make the shape shorter
+        if self.npts > 0 && records.len() < self.npts {
+            self.npts = records.len();
+        }
+        self.mean = None;
+        self.std_dev = None;
+    }
+
+    fn file_width(&self, target: FileTarget) -> usize {
+        let timed = usize::from(self.interval == 0.0);
+        match target {
+            FileTarget::Mult | FileTarget::QMult | FileTarget::Hours => 1,
+            FileTarget::Curve => 1 + timed,
+            FileTarget::PqCurve => 2 + timed,
+        }
+    }
+
+    // An array property, or the file it names
+    fn read_array(&mut self, target: FileTarget, parser: &mut DSSParser) -> DssResult<Vec<f64>> {
+        match ShapeFile::from_array(parser.get_token()) {
+            Some(file) => {
+                self.pending_file = Some((target, file));
+                Ok(Vec::new())
+            }
+            None => read_doubles(parser),
+        }
+    }
+}
+
+// The shapes of the elements a time mode follows (Pascal SolveDaily,
+// SolveYearly and SolveDuty)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeMode {
+    Daily,
+    Yearly,
+    Duty,
+}
+
+impl ShapeMode {
+    // The shape named for the mode; yearly and duty fall back to the daily
+    // shape when not given
+    fn pick<'a>(self, daily: &'a str, yearly: &'a str, duty: &'a str) -> &'a str {
+        let own = match self {
+            ShapeMode::Daily => daily,
+            ShapeMode::Yearly => yearly,
+            ShapeMode::Duty => duty,
+        };
+        if own.is_empty() { daily } else { own }
+    }
+}
+
+// How a Monte Carlo solution draws the load multipliers (Pascal GAUSSIAN,
+// UNIFORM and LOGNORMAL; None leaves the loads alone)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RandomType {
+    None,
+    #[default]
+    Gaussian,
+    Uniform,
+    LogNormal,
+}
+
+impl RandomType {
+    pub const NAMES: &'static [&'static str] = &["none", "gaussian", "uniform", "lognormal"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(RandomType::None),
+            "gaussian" => Some(RandomType::Gaussian),
+            "uniform" => Some(RandomType::Uniform),
+            "lognormal" => Some(RandomType::LogNormal),
+            _ => None,
+        }
+    }
+}
+
+// A random multiplier for every enabled load (Pascal TLoadObj.Randomize).
+// The gaussian and lognormal draws center on the mean of the load's yearly
+// shape, or on its %mean and %stddev without one; uniform draws fall in
+// [0, 1). The draws come in the order of the loads, so a generator seeded
+// the same gives the same multipliers.
+pub fn random_load_mults(
+    circuit: &Circuit,
+    kind: RandomType,
+    rng: &mut SplitMix64,
+) -> Vec<(usize, f64)> {
+    if kind == RandomType::None {
+        return Vec::new();
+    }
+    let mut mults = Vec::new();
+    for &id in circuit.class_elements("Load") {
+        let Some(load) = circuit
+            .element(id)
+            .and_then(|element| element.as_any().downcast_ref::<Load>())
+            .filter(|load| load.ckt_base().is_enabled())
+        else {
+            continue;
+        };
+        let (mean, std_dev) = match circuit
+            .find_object_as::<LoadShape>("LoadShape", load.get_yearly())
+            .filter(|_| !load.get_yearly().is_empty())
+        {
+            Some(shape) => (shape.get_mean(), shape.get_std_dev()),
+            None => (load.get_pct_mean() / 100.0, load.get_pct_stddev() / 100.0),
+        };
+        let mult = match kind {
+            RandomType::Gaussian => mean + std_dev * rng.next_normal(),
+            RandomType::Uniform => rng.next_f64(),
+            RandomType::LogNormal => rng.next_normal().exp() * mean,
+            RandomType::None => 1.0,
+        };
+        mults.push((id, mult));
+    }
+    mults
+}
+
+fn shape_mult(circuit: &Circuit, name: &str, hour: f64) -> Option<Complex64> {
+    if name.is_empty() {
+        return None;
+    }
+    circuit
+        .find_object_as::<LoadShape>("LoadShape", name)
+        .map(|shape| shape.get_mult(hour))
+}
+
+// Sets the loads, generators, PV systems, storage and current sources to
+// their shapes at an hour of the solution. Loads are scaled by `load_mult`
+// on top; elements without a shape stay at their ratings. `step_hours` is
+// the length of the time step, for the storage dispatch.
+pub fn apply_load_shapes(
+    circuit: &mut Circuit,
+    mode: ShapeMode,
+    hour: f64,
+    load_mult: f64,
+    step_hours: f64,
+) {
+    for id in 0..circuit.elements().len() {
+        let Some(element) = circuit.element(id) else {
+            continue;
+        };
+        let any = element.as_any();
+        let mult = if let Some(load) = any.downcast_ref::<Load>() {
+            let name = mode.pick(load.get_daily(), load.get_yearly(), load.get_duty());
+            shape_mult(circuit, name, hour)
+        } else if let Some(generator) = any.downcast_ref::<Generator>() {
+            let name = mode.pick(
+                generator.get_daily(),
+                generator.get_yearly(),
+                generator.get_duty(),
+            );
+            // a duty cycle may start partway into its shape
+            let start = match mode {
+                ShapeMode::Duty => generator.get_duty_start(),
+                _ => 0.0,
+            };
+            shape_mult(circuit, name, hour + start)
+        } else if let Some(pv) = any.downcast_ref::<PVSystem>() {
+            shape_mult(
+                circuit,
+                mode.pick(pv.get_daily(), pv.get_yearly(), pv.get_duty()),
+                hour,
+            )
+        } else if let Some(storage) = any.downcast_ref::<Storage>() {
+            let name = mode.pick(
+                storage.get_daily(),
+                storage.get_yearly(),
+                storage.get_duty(),
+            );
+            shape_mult(circuit, name, hour)
+        } else if let Some(source) = any.downcast_ref::<Isource>() {
+            let name = mode.pick(source.get_daily(), source.get_yearly(), source.get_duty());
+            shape_mult(circuit, name, hour)
+        } else {
+            continue;
+        };
+
+        let Some(element) = circuit.element_mut(id) else {
+            continue;
+        };
+        let any = element.as_any_mut();
+        let p = mult.map(|mult| mult.re);
+        if let Some(load) = any.downcast_mut::<Load>() {
+            load.set_load_mult(load_mult * p.unwrap_or(1.0));
+        } else if let Some(generator) = any.downcast_mut::<Generator>() {
+            generator.set_gen_mult(p.unwrap_or(1.0));
+        } else if let Some(pv) = any.downcast_mut::<PVSystem>() {
+            pv.set_irradiance_mult(p.unwrap_or(1.0));
+        } else if let Some(storage) = any.downcast_mut::<Storage>() {
+            // without a shape of its own, storage follows the load level
+            storage.dispatch(p.unwrap_or(load_mult), hour.rem_euclid(24.0), step_hours);
+        } else if let Some(source) = any.downcast_mut::<Isource>() {
+            source.set_amps_mult(p.unwrap_or(1.0));
+        }
+    }
+}
+
+// Divides the values by base, or by the largest magnitude when base is 0
+fn normalize(values: &mut [f64], base: f64) {
+    let base = match base {
+        0.0 => values
+            .iter()
+            .fold(0.0, |max: f64, value| max.max(value.abs())),
+        base => base,
+    };
+    if base != 0.0 {
+        values.iter_mut().for_each(|value| *value /= base);
+    }
+}
+
+impl DssObject for LoadShape {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let name = PROPERTIES[index].name;
+        match name {
+            "npts" => self.npts = parser.make_integer()?.max(0) as usize,
+            "interval" => self.interval = parser.make_double()?.max(0.0),
+            "sinterval" => self.interval = parser.make_double()?.max(0.0) / 3600.0,
+            "minterval" => self.interval = parser.make_double()?.max(0.0) / 60.0,
+            "mult" | "pmult" => {
+                self.mult = self.read_array(FileTarget::Mult, parser)?;
+                self.mean = None;
+                self.std_dev = None;
+            }
+            "qmult" => self.qmult = self.read_array(FileTarget::QMult, parser)?,
+            "hour" => self.hours = self.read_array(FileTarget::Hours, parser)?,
+            "mean" => self.mean = Some(parser.make_double()?),
+            "stddev" => self.std_dev = Some(parser.make_double()?),
+            "csvfile" | "sngfile" | "dblfile" | "pqcsvfile" => {
+                let format = match name {
+                    "sngfile" => FileFormat::Sng,
+                    "dblfile" => FileFormat::Dbl,
+                    _ => FileFormat::Csv,
+                };
+                let target = match name {
+                    "pqcsvfile" => FileTarget::PqCurve,
+                    _ => FileTarget::Curve,
+                };
+                let file = ShapeFile::new(format, parser.get_token());
+                self.pending_file = Some((target, file));
+            }
+            "action" => {
+                read_choice(parser, ACTION_NAMES, "action")?;
+                self.normalize();
+            }
+            "useactual" => self.use_actual = interpret_yes_no(parser.get_token()),
+            "pmax" => self.p_max = Some(parser.make_double()?),
+            "qmax" => self.q_max = Some(parser.make_double()?),
+            "pbase" => self.p_base = parser.make_double()?,
+            "qbase" => self.q_base = parser.make_double()?,
+            "interpolation" => {
+                let name = read_choice(parser, Interpolation::NAMES, "interpolation")?;
+                self.interpolation = Interpolation::from_name(name);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Files are read here, relative to the directory of the circuit
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some((target, file)) = self.pending_file.take() else {
+            return Ok(());
+        };
+        let records = file.read(circuit.get_current_dir(), self.file_width(target))?;
+        self.take_records(target, records);
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.npts > 0 {
+            self.mult.resize(self.npts, 0.0);
+            for values in [&mut self.qmult, &mut self.hours] {
+                if !values.is_empty() {
+                    values.resize(self.npts, 0.0);
+                }
+            }
+        }
+        if self.interval == 0.0 && self.hours.len() < self.mult.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has interval=0 and needs an hour for each of its {} points",
+                    self.full_name(),
+                    self.mult.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "npts" => self.num_points().to_string(),
+            "mean" => self.get_mean().to_string(),
+            "stddev" => self.get_std_dev().to_string(),
+            "pmax" => self.get_p_max().to_string(),
+            "qmax" => self.get_q_max().to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for LoadShapeClass {
+    fn name(&self) -> &'static str {
+        "LoadShape"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(LoadShape::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{add, edit, edited};
+
+    fn new_shape(properties: &str, circuit: &Circuit) -> DssResult<LoadShape> {
+        edited(LoadShape::new("ls1"), &LoadShapeClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_fixed_interval() {
+        let circuit = Circuit::new("test");
+        let shape = new_shape("npts=4 interval=1 mult=[0.5 1 0.75 0.25]", &circuit).unwrap();
+        assert_eq!(shape.get_mult(1.0), Complex64::new(0.5, 0.5));
+        assert_eq!(shape.get_mult(2.0).re, 1.0);
+        // halfway between the points at 2 and 3 h
+        assert!((shape.get_mult(2.5).re - 0.875).abs() < 1e-12);
+        // the shape repeats; hour 0 is the last point
+        assert_eq!(shape.get_mult(0.0).re, 0.25);
+        assert_eq!(shape.get_mult(6.0).re, 1.0);
+        assert!((shape.get_mean() - 0.625).abs() < 1e-12);
+        assert_eq!(shape.get_p_max(), 1.0);
+
+        let shape = new_shape(
+            "minterval=30 mult=[1 2 3] qmult=[4 5 6] interpolation=edge",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(shape.num_points(), 3);
+        assert_eq!(shape.get_mult(1.25), Complex64::new(2.0, 5.0));
+        assert_eq!(shape.get_q_max(), 6.0);
+        assert_eq!(shape.get_property(0), "3");
+    }
+
+    #[test]
+    fn test_variable_interval() {
+        let circuit = Circuit::new("test");
+        let mut shape = new_shape(
+            "npts=3 interval=0 hour=[0 2 6] mult=[1 3 5] pbase=10",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(shape.get_mult(1.0).re, 2.0);
+        assert_eq!(shape.get_mult(4.0).re, 4.0);
+        // past the last hour the shape starts over
+        assert_eq!(shape.get_mult(7.0).re, 2.0);
+
+        shape.normalize();
+        assert_eq!(shape.mult(), [0.1, 0.3, 0.5]);
+
+        let err = new_shape("interval=0 mult=[1 2]", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("dss_core_loadshape_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut circuit = Circuit::new("test");
+        circuit.set_current_dir(&dir);
+        std::fs::write(dir.join("pq.csv"), "p,q\n1,0.5\n2,1\n4,2\n").unwrap();
+        std::fs::write(dir.join("hm.csv"), "0, 1\n1, 2\n").unwrap();
+        let singles: Vec<u8> = [0.5_f32, 1.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("m.sng"), singles).unwrap();
+        let doubles: Vec<u8> = [0.25_f64, 0.75]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("m.dbl"), doubles).unwrap();
+
+        let shape = new_shape("mult=(file=pq.csv, col=2, header=yes)", &circuit).unwrap();
+        assert_eq!(shape.mult(), [0.5, 1.0, 2.0]);
+        let shape = new_shape("pqcsvfile=pq.csv", &circuit);
+        // the header is not a number
+        assert_eq!(shape.unwrap_err().number(), codes::SYNTAX_ERROR);
+        let mut shape = new_shape("interval=0 csvfile=hm.csv", &circuit).unwrap();
+        assert_eq!(
+            (shape.hours(), shape.mult()),
+            (&[0.0, 1.0][..], &[1.0, 2.0][..])
+        );
+
+        edit(
+            &mut shape,
+            &LoadShapeClass,
+            "interval=1 sngfile=m.sng qmult=(dblfile=m.dbl) action=normalize",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(shape.mult(), [0.5, 1.0]);
+        assert_eq!(shape.qmult(), [1.0 / 3.0, 1.0]);
+
+        let err = new_shape("dblfile=missing.dbl", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::FILE_ERROR);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_load_shapes() {
+        let mut circuit = Circuit::new("test");
+        let shape = new_shape("npts=2 interval=1 mult=[0.5 0.8]", &circuit).unwrap();
+        circuit.add_element(Box::new(shape));
+        for (class, name, properties) in [
+            ("load", "ld1", "kw=10 daily=ls1"),
+            ("load", "ld2", "kw=10"),
+            ("generator", "g1", "kw=10 yearly=ls1"),
+        ] {
+            add(&mut circuit, class, name, properties);
+        }
+        let load = |circuit: &Circuit, name: &str| {
+            circuit
+                .find_object_as::<Load>("load", name)
+                .unwrap()
+                .get_load_mult()
+        };
+        let generator = |circuit: &Circuit| {
+            circuit
+                .find_object_as::<Generator>("generator", "g1")
+                .unwrap()
+                .get_gen_mult()
+        };
+
+        apply_load_shapes(&mut circuit, ShapeMode::Daily, 2.0, 2.0, 1.0);
+        assert_eq!(load(&circuit, "ld1"), 1.6);
+        // no shape: the load multiplier alone
+        assert_eq!(load(&circuit, "ld2"), 2.0);
+        assert_eq!(generator(&circuit), 1.0);
+
+        // yearly takes the daily shape of the load when it has no yearly one
+        apply_load_shapes(&mut circuit, ShapeMode::Yearly, 1.0, 1.0, 1.0);
+        assert_eq!(load(&circuit, "ld1"), 0.5);
+        assert_eq!(generator(&circuit), 0.5);
+    }
+}
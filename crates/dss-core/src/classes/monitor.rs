@@ -0,0 +1,807 @@
+// Monitor (Pascal TMonitor): records a terminal of an element at every
+// sample into a byte stream laid out as OpenDSS writes it, so tools that
+// read OpenDSS monitor streams read these too. The stream starts with a
+// header: signature 43756, version 1, the number of channels and the mode
+// as 32-bit integers, then the channel names in a 256-byte string. Each
+// record that follows holds the hour, the seconds and one value per channel
+// as 32-bit floats.
+//
+// The low four bits of the mode choose what is recorded; adding 16 records
+// sequence quantities instead of phases, 32 magnitudes only and 64 only the
+// positive sequence or the phase average, for voltages, currents and powers.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::capacitor::Capacitor;
+use crate::classes::storage::Storage;
+use crate::classes::transformer::Transformer;
+use crate::cmatrix::CMatrix;
+use crate::meter_element::{MeterElement, MeterElementBase, SolutionState};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+
+const MONITOR_PROPERTIES: [PropertyDef; 7] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name (Full Object name) of element to which the monitor is connected.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the monitor is connected. 1 or 2, typically. For monitoring states, attach monitor to terminal 1.",
+    },
+    PropertyDef {
+        name: "mode",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Bitmask integer designating the values the monitor is to capture: \n0 = Voltages and currents at designated terminal\n1 = Powers at designated terminal\n2 = Tap Position (Transformer Device only)\n3 = State Variables (PCElements only)\n4 = Flicker level and severity index (Pst) for voltages. No adders apply.\n    Flicker level at simulation time step, Pst at 10-minute time step.\n5 = Solution variables (Iterations, etc).\nNormally, these would be actual phasor quantities from solution.\n6 = Capacitor Switching (Capacitors only)\n7 = Storage state vars (Storage device only)\n8 = All winding currents (Transformer device only)\n9 = Losses, watts and var (of monitored device)\n10 = All Winding voltages (Transformer device only)\n\nNormally, these would be actual phasor quantities from solution.\nCombine mode with adders below to achieve other results for terminal quantities:\n+16 = Sequence quantities\n+32 = Magnitude only\n+64 = Positive sequence only or avg of all phases\n\nMix adder to obtain desired results. For example:\nMode=112 will save positive sequence voltage and current magnitudes only\nMode=48 will save all sequence voltages and currents, but magnitude only.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(MonitorAction::NAMES),
+        default: "",
+        help: "{Clear | Save | Take | Process | Reset}\n(C)lears or (S)aves current buffer.\n(T)ake action takes a sample.\n(P)rocesses the data taken so far (e.g. Pst for mode 4).\n(R)eset is the same as Clear.\n\nNote that monitors are automatically reset (cleared) when the Set Mode= command is issued. Otherwise, the user must explicitly reset all monitors (reset monitors command) or individual monitors with the Clear action.",
+    },
+    PropertyDef {
+        name: "residual",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes/True | No/False} Default = No.  Include Residual cbannel (sum of all phases) for voltage and current. Does not apply to sequence quantity modes or power modes.",
+    },
+    PropertyDef {
+        name: "vipolar",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True | No/False} Default = YES. Report voltage and current in polar form (Mag/Angle). (default)  Otherwise, it will be real and imaginary.",
+    },
+    PropertyDef {
+        name: "ppolar",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True | No/False} Default = YES. Report power in Apparent power, S, in polar form (Mag/Angle).(default)  Otherwise, is P and Q",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 9] = concat_properties(&MONITOR_PROPERTIES, &CKT_PROPERTIES);
+
+// Stream header (Pascal MonitorStream)
+const SIGNATURE: i32 = 43756;
+const VERSION: i32 = 1;
+const HEADER_STRING_LEN: usize = 256;
+const HEADER_LEN: usize = 16 + HEADER_STRING_LEN;
+
+// Bits of the mode
+const MODE_MASK: u32 = 15;
+const SEQUENCE_MASK: u32 = 16;
+const MAGNITUDE_MASK: u32 = 32;
+const POS_SEQ_ONLY_MASK: u32 = 64;
+
+// What the action property asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorAction {
+    Clear,
+    Save,
+    Take,
+    Process,
+    Reset,
+}
+
+impl MonitorAction {
+    pub const NAMES: &'static [&'static str] = &["clear", "save", "take", "process", "reset"];
+
+    // Action of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "save" => MonitorAction::Save,
+            "take" => MonitorAction::Take,
+            "process" => MonitorAction::Process,
+            "reset" => MonitorAction::Reset,
+            _ => MonitorAction::Clear,
+        }
+    }
+}
+
+// Channel names and values of one sample, built together
+#[derive(Default)]
+struct Record {
+    names: Vec<String>,
+    values: Vec<f64>,
+}
+
+impl Record {
+    fn push(&mut self, name: String, value: f64) {
+        self.names.push(name);
+        self.values.push(value);
+    }
+
+    // A phasor as magnitude and angle in degrees, or real and imaginary
+    fn push_phasor(&mut self, name: &str, angle: &str, value: Complex64, polar: bool) {
+        if polar {
+            self.push(name.to_string(), value.norm());
+            self.push(angle.to_string(), value.arg() * 180.0 / PI);
+        } else {
+            self.push(format!("{}.re", name), value.re);
+            self.push(format!("{}.im", name), value.im);
+        }
+    }
+}
+
+// Zero, positive and negative sequence of the first three phases
+fn phase_to_seq(phases: &[Complex64]) -> [Complex64; 3] {
+    let zero = Complex64::new(0.0, 0.0);
+    let [a, b, c] = [0, 1, 2].map(|i| phases.get(i).copied().unwrap_or(zero));
+    let op = Complex64::from_polar(1.0, 2.0 * PI / 3.0);
+    [
+        (a + b + c) / 3.0,
+        (a + op * b + op * op * c) / 3.0,
+        (a + op * op * b + op * c) / 3.0,
+    ]
+}
+
+// C's %-.6g, as OpenDSS writes the values of a monitor export
+fn format_g(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}", value);
+    }
+    let exponent = value.abs().log10().floor() as i32;
+    let text = if (-4..6).contains(&exponent) {
+        format!("{:.*}", (5 - exponent).max(0) as usize, value)
+    } else {
+        format!("{:.5e}", value)
+    };
+    // trailing zeros of the mantissa go, as %g drops them
+    match text.split_once('e') {
+        Some((mantissa, exp)) => {
+            let exp: i32 = exp.parse().unwrap_or(0);
+            format!("{}e{:+03}", trim_zeros(mantissa), exp)
+        }
+        None => trim_zeros(&text).to_string(),
+    }
+}
+
+fn trim_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
+
+#[derive(Debug)]
+pub struct MonitorClass;
+
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    // The monitored element and terminal
+    meter: MeterElementBase,
+    mode: u32,
+    residual: bool,
+    vi_polar: bool,
+    p_polar: bool,
+    // Channel names, fixed by the first record after a reset
+    channels: Vec<String>,
+    stream: Vec<u8>,
+    // Records of a harmonic study carry the frequency and harmonic in place
+    // of the hour and seconds
+    harmonic: bool,
+    // Take action waiting for the solution to sample the monitor
+    take_requested: bool,
+}
+
+impl Monitor {
+    pub fn new(name: &str) -> Self {
+        Monitor {
+            base: ObjectBase::new("Monitor", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            meter: MeterElementBase::new(),
+            mode: 0,
+            residual: false,
+            vi_polar: true,
+            p_polar: true,
+            channels: Vec::new(),
+            stream: Vec::new(),
+            harmonic: false,
+            take_requested: false,
+        }
+    }
+
+    pub fn get_mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    // The recorded stream, header and records, as OpenDSS lays it out
+    pub fn stream(&self) -> &[u8] {
+        &self.stream
+    }
+
+    pub fn sample_count(&self) -> usize {
+        let record_len = (self.channels.len() + 2) * 4;
+        self.stream.len().saturating_sub(HEADER_LEN) / record_len
+    }
+
+    // Whether a Take action waits for the next sample
+    pub fn is_take_requested(&self) -> bool {
+        self.take_requested
+    }
+
+    // Hour, seconds and channel values of each record of the stream
+    pub fn records(&self) -> Vec<(f32, f32, Vec<f32>)> {
+        let Some(data) = self.stream.get(HEADER_LEN..) else {
+            return Vec::new();
+        };
+        data.chunks_exact((self.channels.len() + 2) * 4)
+            .map(|record| {
+                let mut values = record
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+                let hour = values.next().unwrap_or(0.0);
+                let sec = values.next().unwrap_or(0.0);
+                (hour, sec, values.collect())
+            })
+            .collect()
+    }
+
+    // The records as the CSV file of Export Monitors
+    pub fn to_csv(&self) -> String {
+        let time = if self.harmonic {
+            "Freq, Harmonic"
+        } else {
+            "hour, t(sec)"
+        };
+        let mut lines = vec![format!("{}, {}", time, self.channels.join(", "))];
+        for (hour, sec, values) in self.records() {
+            let mut line = if self.harmonic {
+                format!("{}, {}", format_g(hour as f64), format_g(sec as f64))
+            } else {
+                format!("{}, {:.5}", hour as i32, sec)
+            };
+            for value in values {
+                line.push_str(", ");
+                line.push_str(&format_g(value as f64));
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    fn write_header(&mut self) {
+        self.stream.clear();
+        for value in [
+            SIGNATURE,
+            VERSION,
+            self.channels.len() as i32,
+            self.mode as i32,
+        ] {
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut names = self.channels.join(", ").into_bytes();
+        names.resize(HEADER_STRING_LEN, 0);
+        self.stream.extend_from_slice(&names);
+    }
+
+    // Why the monitored element cannot give what the mode records, if so
+    fn check_element(&self, element: &dyn DssObject) -> Option<&'static str> {
+        let ckt = element.as_ckt_element()?;
+        match self.mode & MODE_MASK {
+            2 | 8 | 10 if !element.as_any().is::<Transformer>() => Some("a Transformer"),
+            3 if ckt.as_pc_element().is_none() => Some("a power conversion element"),
+            6 if !element.as_any().is::<Capacitor>() => Some("a Capacitor"),
+            7 if !element.as_any().is::<Storage>() => Some("a Storage element"),
+            9 if ckt.as_pd_element().is_none() => Some("a power delivery element"),
+            _ => None,
+        }
+    }
+
+    fn measure(
+        &self,
+        element: &dyn DssObject,
+        voltages: &[Complex64],
+        state: &SolutionState,
+    ) -> Record {
+        let mut record = Record::default();
+        let Some(ckt) = element.as_ckt_element() else {
+            return record;
+        };
+        let nconds = ckt.nconds();
+        let start = (self.meter.get_terminal() - 1) * nconds;
+        let terminal = |values: Vec<Complex64>| -> Vec<Complex64> {
+            values.into_iter().skip(start).take(nconds).collect()
+        };
+        match self.mode & MODE_MASK {
+            0 => {
+                let v = terminal(ckt.terminal_voltages(voltages));
+                let i = terminal(ckt.get_currents(voltages));
+                self.push_vi(&mut record, "V", &v);
+                self.push_vi(&mut record, "I", &i);
+            }
+            1 => {
+                let v = terminal(ckt.terminal_voltages(voltages));
+                let i = terminal(ckt.get_currents(voltages));
+                self.push_powers(&mut record, &v, &i);
+            }
+            2 => {
+                if let Some(transformer) = element.as_any().downcast_ref::<Transformer>() {
+                    let winding = self.meter.get_terminal() - 1;
+                    record.push("Tap (pu)".to_string(), transformer.get_tap(winding));
+                }
+            }
+            3 => {
+                if let Some(pc) = ckt.as_pc_element() {
+                    for (name, value) in pc.variable_names().into_iter().zip(pc.variables()) {
+                        record.push(name, value);
+                    }
+                }
+            }
+            // The flicker meter works on the phase voltage magnitudes; the
+            // severity index is not derived from them
+            4 => {
+                let v = terminal(ckt.terminal_voltages(voltages));
+                for (k, v) in v.iter().take(ckt.nphases()).enumerate() {
+                    record.push(format!("V{}", k + 1), v.norm());
+                }
+            }
+            5 => {
+                record.push("Frequency".to_string(), state.frequency);
+                record.push("Iterations".to_string(), state.iterations as f64);
+                record.push("LoadMultiplier".to_string(), state.load_mult);
+                record.push("Converged".to_string(), state.converged as u8 as f64);
+                record.push("Delta_V".to_string(), state.max_delta_v);
+                record.push(
+                    "ControlIteration".to_string(),
+                    state.control_iteration as f64,
+                );
+            }
+            6 => {
+                if let Some(capacitor) = element.as_any().downcast_ref::<Capacitor>() {
+                    for (k, closed) in capacitor.get_states().into_iter().enumerate() {
+                        record.push(format!("Step_{}", k + 1), closed as u8 as f64);
+                    }
+                }
+            }
+            7 => {
+                if let Some(storage) = element.as_any().downcast_ref::<Storage>() {
+                    record.push("kW output".to_string(), storage.get_kw());
+                    record.push("kvar output".to_string(), storage.get_kvar());
+                    record.push("kWh Stored".to_string(), storage.get_kwh_stored());
+                    record.push("Pct Stored".to_string(), storage.get_pct_stored());
+                    record.push("State".to_string(), storage.get_state().value());
+                }
+            }
+            8 | 10 => {
+                let (values, quantity) = match self.mode & MODE_MASK {
+                    8 => (ckt.get_currents(voltages), "I"),
+                    _ => (ckt.terminal_voltages(voltages), "V"),
+                };
+                for (w, winding) in values.chunks(nconds.max(1)).enumerate() {
+                    for (k, value) in winding.iter().take(ckt.nphases()).enumerate() {
+                        record.push(format!("W{}-{}{}", w + 1, quantity, k + 1), value.norm());
+                    }
+                }
+            }
+            9 => {
+                let losses = ckt.total_power(voltages) / 1000.0;
+                // the part lost in the phase conductors
+                let v = ckt.terminal_voltages(voltages);
+                let i = ckt.get_currents(voltages);
+                let phase_losses: Complex64 = v
+                    .iter()
+                    .zip(&i)
+                    .enumerate()
+                    .filter(|(k, _)| k % nconds.max(1) < ckt.nphases())
+                    .map(|(_, (v, i))| v * i.conj())
+                    .sum::<Complex64>()
+                    / 1000.0;
+                record.push("LossesP".to_string(), losses.re);
+                record.push("LossesQ".to_string(), losses.im);
+                record.push("PhLossesP".to_string(), phase_losses.re);
+                record.push("PhLossesQ".to_string(), phase_losses.im);
+            }
+            _ => {}
+        }
+        record
+    }
+
+    // Voltages or currents of the terminal as the mode bits ask
+    fn push_vi(&self, record: &mut Record, quantity: &str, values: &[Complex64]) {
+        let magnitude = self.mode & MAGNITUDE_MASK != 0;
+        let pos_only = self.mode & POS_SEQ_ONLY_MASK != 0;
+        if self.mode & SEQUENCE_MASK != 0 {
+            let seq = phase_to_seq(values);
+            let range = if pos_only { 1..2 } else { 0..3 };
+            for k in range {
+                record.push(format!("{}{}", quantity, k), seq[k].norm());
+            }
+            return;
+        }
+        if pos_only {
+            let count = values.len().max(1) as f64;
+            let average = values.iter().map(|value| value.norm()).sum::<f64>() / count;
+            record.push(quantity.to_string(), average);
+            return;
+        }
+        let residual: Complex64 = values.iter().sum();
+        let named = values
+            .iter()
+            .enumerate()
+            .map(|(k, value)| ((k + 1).to_string(), *value))
+            .chain(self.residual.then(|| ("N".to_string(), residual)));
+        for (suffix, value) in named {
+            let name = format!("{}{}", quantity, suffix);
+            if magnitude {
+                record.push(name, value.norm());
+            } else {
+                let angle = format!("{}Angle{}", quantity, suffix);
+                record.push_phasor(&name, &angle, value, self.vi_polar);
+            }
+        }
+    }
+
+    // Powers of the terminal in kVA as the mode bits ask
+    fn push_powers(&self, record: &mut Record, v: &[Complex64], i: &[Complex64]) {
+        let magnitude = self.mode & MAGNITUDE_MASK != 0;
+        let pos_only = self.mode & POS_SEQ_ONLY_MASK != 0;
+        let powers: Vec<(String, Complex64)> = if self.mode & SEQUENCE_MASK != 0 {
+            let (v012, i012) = (phase_to_seq(v), phase_to_seq(i));
+            let range = if pos_only { 1..2 } else { 0..3 };
+            range
+                .map(|k| (k.to_string(), 3.0 * v012[k] * i012[k].conj() / 1000.0))
+                .collect()
+        } else if pos_only {
+            let total = v
+                .iter()
+                .zip(i)
+                .map(|(v, i)| v * i.conj())
+                .sum::<Complex64>();
+            vec![(String::new(), total / 1000.0)]
+        } else {
+            v.iter()
+                .zip(i)
+                .enumerate()
+                .map(|(k, (v, i))| ((k + 1).to_string(), v * i.conj() / 1000.0))
+                .collect()
+        };
+        for (suffix, power) in powers {
+            if magnitude {
+                record.push(format!("S{} (kVA)", suffix), power.norm());
+            } else if self.p_polar {
+                record.push(format!("S{} (kVA)", suffix), power.norm());
+                record.push(format!("Ang{}", suffix), power.arg() * 180.0 / PI);
+            } else {
+                record.push(format!("P{} (kW)", suffix), power.re);
+                record.push(format!("Q{} (kvar)", suffix), power.im);
+            }
+        }
+    }
+}
+
+impl DssObject for Monitor {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = MONITOR_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - MONITOR_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => self.meter.set_element(parser.get_token()),
+            "terminal" => self
+                .meter
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "mode" => {
+                self.mode = parser.make_integer()?.max(0) as u32;
+                MeterElement::reset(self);
+            }
+            "action" => {
+                match MonitorAction::from_name(read_choice(parser, MonitorAction::NAMES, "action")?)
+                {
+                    MonitorAction::Clear | MonitorAction::Reset => MeterElement::reset(self),
+                    MonitorAction::Take => self.take_requested = true,
+                    // The stream is kept in memory and the flicker severity is
+                    // not derived, so there is nothing to save or process
+                    MonitorAction::Save | MonitorAction::Process => {}
+                }
+            }
+            "residual" => self.residual = interpret_yes_no(parser.get_token()),
+            "vipolar" => self.vi_polar = interpret_yes_no(parser.get_token()),
+            "ppolar" => self.p_polar = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let name = MONITOR_PROPERTIES.get(index).map(|property| property.name);
+        if !matches!(name, Some("element" | "mode")) || self.meter.get_element().is_empty() {
+            return Ok(());
+        }
+        let element = self.meter.get_element();
+        let Some(id) = self.meter.find_element(circuit) else {
+            return Err(DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!(
+                    "Monitored element \"{}\" not found for {}",
+                    element,
+                    self.full_name()
+                ),
+            ));
+        };
+        let what = circuit
+            .element(id)
+            .and_then(|element| self.check_element(element));
+        match what {
+            Some(what) => Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} in mode {} needs {} to monitor; \"{}\" is not one",
+                    self.full_name(),
+                    self.mode,
+                    what,
+                    element
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Monitor {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_meter_element(&self) -> Option<&dyn MeterElement> {
+        Some(self)
+    }
+
+    fn as_meter_element_mut(&mut self) -> Option<&mut dyn MeterElement> {
+        Some(self)
+    }
+
+    // Meters are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl MeterElement for Monitor {
+    fn meter_base(&self) -> &MeterElementBase {
+        &self.meter
+    }
+
+    fn meter_base_mut(&mut self) -> &mut MeterElementBase {
+        &mut self.meter
+    }
+
+    fn take_sample(&mut self, circuit: &Circuit, voltages: &[Complex64], state: &SolutionState) {
+        self.take_requested = false;
+        let Some(element) = self
+            .meter
+            .find_element(circuit)
+            .and_then(|id| circuit.element(id))
+        else {
+            return;
+        };
+        let record = self.measure(element, voltages, state);
+        if self.stream.is_empty() {
+            self.channels = record.names;
+            self.harmonic = state.harmonic.is_some();
+            self.write_header();
+        }
+        // a record keeps the size of the header whatever the element became
+        let mut values = record.values;
+        values.resize(self.channels.len(), 0.0);
+        let time = match state.harmonic {
+            Some(harmonic) => [state.frequency as f32, harmonic as f32],
+            None => [state.hour as f32, state.sec as f32],
+        };
+        for value in time
+            .into_iter()
+            .chain(values.into_iter().map(|value| value as f32))
+        {
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn reset(&mut self) {
+        self.channels.clear();
+        self.stream.clear();
+    }
+}
+
+impl DssClass for MonitorClass {
+    fn name(&self) -> &'static str {
+        "Monitor"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Monitor::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::line::{Line, LineClass};
+    use crate::test_util::{self, edited, placed};
+
+    // A 3-phase line of 1+j1 ohm from nodes 1-3 to nodes 4-6
+    fn circuit_with_line() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("l1"),
+            &LineClass,
+            "r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(line, &[&[1, 2, 3], &[4, 5, 6]]).unwrap()));
+        circuit
+    }
+
+    fn edit(monitor: &mut Monitor, properties: &str, circuit: &Circuit) -> DssResult<()> {
+        test_util::edit(monitor, &MonitorClass, properties, circuit)
+    }
+
+    // 1000 V balanced at the sending end, 10 V less at the receiving end
+    fn voltages() -> Vec<Complex64> {
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let phases = [Complex64::new(1.0, 0.0), a, a * a];
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        voltages.extend(phases.map(|p| p * 1000.0));
+        voltages.extend(phases.map(|p| p * 990.0));
+        voltages
+    }
+
+    fn at(hour: i32, sec: f64) -> SolutionState {
+        SolutionState {
+            hour,
+            sec,
+            ..SolutionState::default()
+        }
+    }
+
+    #[test]
+    fn test_stream_and_export() {
+        let circuit = circuit_with_line();
+        let mut monitor = Monitor::new("m1");
+        edit(&mut monitor, "element=line.l1 terminal=1 mode=0", &circuit).unwrap();
+        monitor.take_sample(&circuit, &voltages(), &at(0, 1.5));
+        monitor.take_sample(&circuit, &voltages(), &at(1, 0.0));
+
+        let names = ["V1", "VAngle1", "V2", "VAngle2", "V3", "VAngle3"];
+        assert_eq!(&monitor.channels()[..6], names);
+        assert_eq!(monitor.channels()[6..8], ["I1", "IAngle1"]);
+
+        // header as OpenDSS writes it
+        let stream = monitor.stream();
+        let int = |at: usize| i32::from_le_bytes(stream[at..at + 4].try_into().unwrap());
+        assert_eq!([int(0), int(4), int(8), int(12)], [43756, 1, 12, 0]);
+        assert!(stream[16..].starts_with(b"V1, VAngle1, V2, VAngle2,"));
+        assert_eq!(stream.len(), HEADER_LEN + 2 * 14 * 4);
+        assert_eq!(monitor.sample_count(), 2);
+
+        let records = monitor.records();
+        assert_eq!((records[1].0, records[1].1), (1.0, 0.0));
+        let values = &records[0].2;
+        assert!((values[0] - 1000.0).abs() < 1e-3);
+        assert!((values[6] - 50.0_f32.sqrt()).abs() < 1e-4);
+        assert!((values[7] + 45.0).abs() < 1e-4);
+
+        let csv = monitor.to_csv();
+        let mut lines = csv.lines();
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .starts_with("hour, t(sec), V1, VAngle1, V2")
+        );
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .starts_with("0, 1.50000, 1000, 0, 1000, -120, 1000, 120, 7.07107, -45,")
+        );
+        assert_eq!(format_g(0.000012345678), "1.23457e-05");
+        assert_eq!(format_g(2500000.0), "2.5e+06");
+
+        // a new mode starts a new stream
+        edit(&mut monitor, "mode=112", &circuit).unwrap();
+        assert!(monitor.stream().is_empty());
+        monitor.take_sample(&circuit, &voltages(), &at(0, 0.0));
+        assert_eq!(monitor.channels(), ["V1", "I1"]);
+        assert!((monitor.records()[0].2[0] - 1000.0).abs() < 1e-3);
+
+        // 5 - j5 A per phase
+        edit(&mut monitor, "mode=1 ppolar=no", &circuit).unwrap();
+        monitor.take_sample(&circuit, &voltages(), &at(0, 0.0));
+        assert_eq!(monitor.channels()[..2], ["P1 (kW)", "Q1 (kvar)"]);
+        let values = &monitor.records()[0].2;
+        assert!((values[0] - 5.0).abs() < 1e-5 && (values[1] - 5.0).abs() < 1e-5);
+        edit(&mut monitor, "mode=65 ppolar=yes", &circuit).unwrap();
+        monitor.take_sample(&circuit, &voltages(), &at(0, 0.0));
+        assert_eq!(monitor.channels(), ["S (kVA)", "Ang"]);
+        assert!((monitor.records()[0].2[0] - 15.0 * 2.0_f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_actions_and_element_checks() {
+        let circuit = circuit_with_line();
+        let mut monitor = Monitor::new("m1");
+        edit(&mut monitor, "element=line.l1 mode=9", &circuit).unwrap();
+        monitor.take_sample(&circuit, &voltages(), &at(0, 0.0));
+        assert_eq!(
+            monitor.channels(),
+            ["LossesP", "LossesQ", "PhLossesP", "PhLossesQ"]
+        );
+        // 3 x 50 A squared through 1+j1 ohm
+        assert!((monitor.records()[0].2[0] - 0.15).abs() < 1e-6);
+
+        edit(&mut monitor, "action=take", &circuit).unwrap();
+        assert!(monitor.is_take_requested());
+        monitor.take_sample(&circuit, &voltages(), &at(0, 0.0));
+        assert!(!monitor.is_take_requested());
+        assert_eq!(monitor.sample_count(), 2);
+        edit(&mut monitor, "action=c", &circuit).unwrap();
+        assert_eq!(monitor.sample_count(), 0);
+
+        let err = edit(&mut monitor, "mode=2", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+        let err = edit(&mut monitor, "mode=0 element=line.l2", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+}
@@ -0,0 +1,217 @@
+// PriceShape (Pascal TPriceShape): a price curve over time, named by the
+// storage controller and the storage elements that dispatch on price; the
+// price decides when they charge and discharge in a time series simulation.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::shape_data::ShapeData;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind};
+
+static PROPERTIES: [PropertyDef; 11] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Max number of points to expect in price shape vectors. This gets reset to the number of Price values found if less than specified.",
+    },
+    PropertyDef {
+        name: "interval",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time interval for fixed interval data, hrs. Default = 1. If Interval = 0 then time data (in hours) may be at irregular intervals and time value must be specified using either the Hour property or input files. Then values are interpolated when Interval=0, but not for fixed interval data.  \n\nSee also \"sinterval\" and \"minterval\".",
+    },
+    PropertyDef {
+        name: "price",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of price values.  Units should be compatible with the object using the data. You can also use the syntax: \nprice = (file=filename)     !for text file one value per line\nprice = (dblfile=filename)  !for packed file of doubles\nprice = (sngfile=filename)  !for packed file of singles \n\nNote: this property will reset Npts if the  number of values in the files are fewer.",
+    },
+    PropertyDef {
+        name: "hour",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of hour values. Only necessary to define this property for variable interval data. If the data are fixed interval, do not use this property. You can also use the syntax: \nhour = (file=filename)     !for text file one value per line\nhour = (dblfile=filename)  !for packed file of doubles\nhour = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "mean",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Mean of the Price curve values.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently. Used for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "stddev",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Standard deviation of the Prices.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently.Is overwritten if you subsequently read in a curve\n\nUsed for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  Price curve data to a csv file containing (hour, Price) points, or simply (Price) values for fixed time interval data, one per line. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sngfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  Price curve data to a binary file of singles containing (hour, Price) points, or simply (Price) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "dblfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  Price curve data to a binary file of doubles containing (hour, Price) points, or simply (Price) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sinterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in SECONDS. Alternate way to specify Interval property.",
+    },
+    PropertyDef {
+        name: "minterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in MINUTES. Alternate way to specify Interval property.",
+    },
+];
+
+#[derive(Debug)]
+pub struct PriceShapeClass;
+
+#[derive(Debug, Clone)]
+pub struct PriceShape {
+    base: ObjectBase,
+    shape: ShapeData,
+}
+
+impl PriceShape {
+    pub fn new(name: &str) -> Self {
+        PriceShape {
+            base: ObjectBase::new("PriceShape", name, &PROPERTIES),
+            shape: ShapeData::new(),
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.shape.num_points()
+    }
+
+    pub fn get_interval(&self) -> f64 {
+        self.shape.get_interval()
+    }
+
+    pub fn prices(&self) -> &[f64] {
+        self.shape.values()
+    }
+
+    pub fn hours(&self) -> &[f64] {
+        self.shape.hours()
+    }
+
+    pub fn get_mean(&self) -> f64 {
+        self.shape.get_mean()
+    }
+
+    pub fn get_std_dev(&self) -> f64 {
+        self.shape.get_std_dev()
+    }
+
+    // Price at an hour of the solution (Pascal GetPrice); the
+    // curve repeats after its last point. None without points.
+    pub fn get_price(&self, hour: f64) -> Option<f64> {
+        self.shape.get_value(hour)
+    }
+}
+
+impl DssObject for PriceShape {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "price" => self.shape.set_property("values", parser),
+            name => self.shape.set_property(name, parser),
+        }
+    }
+
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        self.shape.resolve(circuit)
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        let name = self.full_name();
+        self.shape.recalc(&name)
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "npts" => self.num_points().to_string(),
+            "mean" => self.get_mean().to_string(),
+            "stddev" => self.get_std_dev().to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for PriceShapeClass {
+    fn name(&self) -> &'static str {
+        "PriceShape"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(PriceShape::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::edited;
+
+    fn new_shape(properties: &str, circuit: &Circuit) -> DssResult<PriceShape> {
+        edited(PriceShape::new("p1"), &PriceShapeClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_price() {
+        let circuit = Circuit::new("test");
+        let shape = new_shape("npts=4 price=[10 30 20]", &circuit).unwrap();
+        assert_eq!(shape.prices(), [10.0, 30.0, 20.0, 0.0]);
+        assert_eq!(shape.get_price(2.5), Some(30.0));
+        // hour 0 and 4 are the end of the last interval
+        assert_eq!(shape.get_price(4.0), Some(0.0));
+        assert_eq!(shape.get_mean(), 15.0);
+
+        let shape = new_shape("interval=0 hour=[0 10] price=[10 30] mean=12", &circuit).unwrap();
+        assert_eq!(shape.get_price(5.0), Some(20.0));
+        assert_eq!(shape.get_property_value("mean").as_deref(), Some("12"));
+    }
+}
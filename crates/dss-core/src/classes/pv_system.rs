@@ -0,0 +1,948 @@
+// PVSystem (Pascal TPVSystem): a photovoltaic array and its inverter,
+// connected like a generator. The array makes Pmpp at 1 kW/m² and 25 °C;
+// the irradiance scaled by the shape of the solution mode and the P-T
+// factor for the panel temperature give the DC power, and the efficiency
+// for that power the AC power. The inverter switches on above %cutin of its
+// kVA and off below %cutout, and holds kW and kvar within its rating.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::classes::xy_curve::{XYCurve, find_xy_curve};
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+
+const PV_SYSTEM_PROPERTIES: [PropertyDef; 32] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of Phases, this PVSystem element.  Power is evenly divided among phases.",
+    },
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Bus to which the PVSystem element is connected.  May include specific node specification.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "Nominal rated (1.0 per unit) voltage, kV, for PVSystem element. For 2- and 3-phase PVSystem elements, specify phase-phase kV. Otherwise, specify actual kV across each branch of the PVSystem element. If 1-phase wye (star or LN), specify phase-neutral kV. If 1-phase delta or phase-phase connected, specify phase-phase kV.",
+    },
+    PropertyDef {
+        name: "irradiance",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Get/set the present irradiance value in kW/sq-m. Used as base value for shape multipliers. Generally entered as peak value for the time period of interest and the yearly, daily, and duty load shape objects are defined as per unit multipliers (just like Loads/Generators).",
+    },
+    PropertyDef {
+        name: "pmpp",
+        kind: PropertyKind::Double,
+        default: "500",
+        help: "Get/set the rated max power of the PV array for 1.0 kW/sq-m irradiance and a user-selected array temperature. The P-TCurve should be defined relative to the selected array temperature.",
+    },
+    PropertyDef {
+        name: "%pmpp",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Upper limit on active power as a percentage of Pmpp.",
+    },
+    PropertyDef {
+        name: "temperature",
+        kind: PropertyKind::Double,
+        default: "25",
+        help: "Get/set the present Temperature. Used as fixed value corresponding to PTCurve property. A multiplier is obtained from the Pmpp-Temp curve and applied to the nominal Pmpp from the irradiance to determine the net array output.",
+    },
+    PropertyDef {
+        name: "pf",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Nominally, the power factor for the output power. Default is 1.0. Setting this property will cause the inverter to operate in constant power factor mode.Enter negative when kW and kvar have opposite signs.A positive power factor signifies that the PVSystem element produces vars as is typical for a generator.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye|LN|delta|LL}.  Default is wye.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Get/set the present kvar value.  Setting this property forces the inverter to operate in constant kvar mode.",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "500",
+        help: "kVA rating of inverter. Used as the base for Dynamics mode and Harmonics mode values.",
+    },
+    PropertyDef {
+        name: "%cutin",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "% cut-in power -- % of kVA rating of inverter. When the inverter is OFF, the power from the array must be greater than this for the inverter to turn on.",
+    },
+    PropertyDef {
+        name: "%cutout",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "% cut-out power -- % of kVA rating of inverter. When the inverter is ON, the inverter turns OFF when the power from the array drops below this value.",
+    },
+    PropertyDef {
+        name: "effcurve",
+        kind: PropertyKind::Object("XYCurve"),
+        default: "",
+        help: "An XYCurve object, previously defined, that describes the PER UNIT efficiency vs PER UNIT of rated kVA for the inverter. Inverter output power is discounted by the multiplier obtained from this curve.",
+    },
+    PropertyDef {
+        name: "p-tcurve",
+        kind: PropertyKind::Object("XYCurve"),
+        default: "",
+        help: "An XYCurve object, previously defined, that describes the PV array PER UNIT Pmpp vs Temperature curve. Temperature units must agree with the Temperature property and the Temperature shapes used for simulations. The Pmpp values are specified in per unit of the Pmpp value for 1 kW/sq-m irradiance. The value for the temperature at which Pmpp is defined should be 1.0.  The net array power is determined by the irradiance * Pmpp * f(Temperature)",
+    },
+    PropertyDef {
+        name: "%r",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Equivalent percent internal resistance, ohms. Default is 50%. Placed in series with internal voltage source for harmonics and dynamics modes. (Limits fault current to about 2 pu if not current limited -- see LimitCurrent)",
+    },
+    PropertyDef {
+        name: "%x",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Equivalent percent internal reactance, ohms. Default is 0%. Placed in series with internal voltage source for harmonics and dynamics modes. ",
+    },
+    PropertyDef {
+        name: "model",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Integer code (default=1) for the model to use for power output variation with voltage. Valid values are: 1:PVSystem element injects a CONSTANT kW at specified power factor. 2:PVSystem element is modeled as a CONSTANT ADMITTANCE. 3:Compute load injection from User-written Model.",
+    },
+    PropertyDef {
+        name: "vminpu",
+        kind: PropertyKind::Double,
+        default: "0.9",
+        help: "Default = 0.90.  Minimum per unit voltage for which the Model is assumed to apply. Below this value, the load model reverts to a constant impedance model except for Dynamics model. In Dynamics mode, the current magnitude is limited to the value the power flow would compute for this voltage.",
+    },
+    PropertyDef {
+        name: "vmaxpu",
+        kind: PropertyKind::Double,
+        default: "1.1",
+        help: "Default = 1.10.  Maximum per unit voltage for which the Model is assumed to apply. Above this value, the load model reverts to a constant impedance model.",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for yearly simulations.  Must be previously defined as a Loadshape object. If this is not specified, the Daily dispatch shape, if any, is repeated during Yearly solution modes. In the default dispatch mode, the PVSystem element uses this loadshape to trigger State changes.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for daily simulations.  Must be previously defined as a Loadshape object of 24 hrs, typically.  In the default dispatch mode, the PVSystem element uses this loadshape to trigger State changes.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Load shape to use for duty cycle dispatch simulations such as for solar ramp rate studies. Must be previously defined as a Loadshape object. Typically would have time intervals of 1-5 seconds. Designate the number of points to solve using the Set Number=xxxx command. If there are fewer points in the actual shape, the shape is assumed to repeat.",
+    },
+    PropertyDef {
+        name: "tyearly",
+        kind: PropertyKind::Object("TShape"),
+        default: "",
+        help: "Temperature shape to use for yearly simulations.  Must be previously defined as a TShape object. If this is not specified, the Daily dispatch shape, if any, is repeated during Yearly solution modes. The PVSystem element uses this TShape to determine the Pmpp from the Pmpp vs T curve. Units must agree with the Pmpp vs T curve.",
+    },
+    PropertyDef {
+        name: "tdaily",
+        kind: PropertyKind::Object("TShape"),
+        default: "",
+        help: "Temperature shape to use for daily simulations.  Must be previously defined as a TShape object of 24 hrs, typically.  The PVSystem element uses this TShape to determine the Pmpp from the Pmpp vs T curve. Units must agree with the Pmpp vs T curve.",
+    },
+    PropertyDef {
+        name: "tduty",
+        kind: PropertyKind::Object("TShape"),
+        default: "",
+        help: "Temperature shape to use for duty cycle dispatch simulations such as for solar ramp rate studies. Must be previously defined as a TShape object. Typically would have time intervals of 1-5 seconds. Designate the number of points to solve using the Set Number=xxxx command. If there are fewer points in the actual shape, the shape is assumed to repeat. The PVSystem model uses this TShape to determine the Pmpp from the Pmpp vs T curve. Units must agree with the Pmpp vs T curve.",
+    },
+    PropertyDef {
+        name: "class",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "An arbitrary integer number representing the class of PVSystem element so that PVSystem values may be segregated by class.",
+    },
+    PropertyDef {
+        name: "varfollowinverter",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "Boolean variable (Yes|No) or (True|False). Defaults to False which indicates that the reactive power generation/absorption does not respect the inverter status.When set to True, the PVSystem reactive power generation/absorption will cease when the inverter status is off, due to panel kW dropping below %Cutout.  The reactive power generation/absorption will begin again when the panel kW is above %Cutin.  When set to False, the PVSystem will generate/absorb reactive power regardless of the status of the inverter.",
+    },
+    PropertyDef {
+        name: "wattpriority",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes/No*/True/False} Set inverter to watt priority instead of the default var priority",
+    },
+    PropertyDef {
+        name: "pfpriority",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "If set to true, priority is given to power factor and WattPriority is neglected. It works only if operating in either constant PF or constant kvar modes. Defaults to False.",
+    },
+    PropertyDef {
+        name: "kvarmax",
+        kind: PropertyKind::Double,
+        default: "500",
+        help: "Indicates the maximum reactive power GENERATION (un-signed numerical variable in kvar) for the inverter (as an un-signed value). Defaults to kVA rating of the inverter.",
+    },
+    PropertyDef {
+        name: "kvarmaxabs",
+        kind: PropertyKind::Double,
+        default: "500",
+        help: "Indicates the maximum reactive power ABSORPTION (un-signed numerical variable in kvar) for the inverter (as an un-signed value). Defaults to kVA rating of the inverter.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 35] = concat_properties(&PV_SYSTEM_PROPERTIES, &PC_PROPERTIES);
+
+#[derive(Debug)]
+pub struct PVSystemClass;
+
+#[derive(Debug, Clone)]
+pub struct PVSystem {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    connection: Connection,
+    kv: f64,
+    irradiance: f64,
+    pmpp: f64,
+    pct_pmpp: f64,
+    temperature: f64,
+    pf: f64,
+    kvar: f64,
+    // kvar was given last rather than pf
+    kvar_given: bool,
+    kva: f64,
+    pct_cutin: f64,
+    pct_cutout: f64,
+    eff_curve: String,
+    pt_curve: String,
+    // The curves named, as looked up when given
+    eff_xy: Option<XYCurve>,
+    pt_xy: Option<XYCurve>,
+    pct_r: f64,
+    pct_x: f64,
+    model: usize,
+    vminpu: f64,
+    vmaxpu: f64,
+    yearly: String,
+    daily: String,
+    duty: String,
+    t_yearly: String,
+    t_daily: String,
+    t_duty: String,
+    pv_class: i32,
+    var_follow_inverter: bool,
+    watt_priority: bool,
+    pf_priority: bool,
+    kvar_max: f64,
+    kvar_max_abs: f64,
+    // kvarmax and kvarmaxabs were given rather than following kva
+    kvar_max_given: bool,
+    kvar_max_abs_given: bool,
+    // Set by the solution from the shapes and curves
+    shape_mult: f64,
+    pt_factor: f64,
+    efficiency: f64,
+    inverter_on: bool,
+    // Admittance of each phase in Yprim, as last built
+    y_phase: Complex64,
+}
+
+impl PVSystem {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 1);
+        ckt.set_conductors(4);
+        PVSystem {
+            base: ObjectBase::new("PVSystem", name, &PROPERTIES),
+            ckt,
+            pc: PcElementBase::new("default"),
+            connection: Connection::Wye,
+            kv: 12.47,
+            irradiance: 1.0,
+            pmpp: 500.0,
+            pct_pmpp: 100.0,
+            temperature: 25.0,
+            pf: 1.0,
+            kvar: 0.0,
+            kvar_given: false,
+            kva: 500.0,
+            pct_cutin: 20.0,
+            pct_cutout: 20.0,
+            eff_curve: String::new(),
+            pt_curve: String::new(),
+            eff_xy: None,
+            pt_xy: None,
+            pct_r: 50.0,
+            pct_x: 0.0,
+            model: 1,
+            vminpu: 0.9,
+            vmaxpu: 1.1,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            t_yearly: String::new(),
+            t_daily: String::new(),
+            t_duty: String::new(),
+            pv_class: 1,
+            var_follow_inverter: false,
+            watt_priority: false,
+            pf_priority: false,
+            kvar_max: 500.0,
+            kvar_max_abs: 500.0,
+            kvar_max_given: false,
+            kvar_max_abs_given: false,
+            shape_mult: 1.0,
+            pt_factor: 1.0,
+            efficiency: 1.0,
+            inverter_on: true,
+            y_phase: Complex64::new(0.0, 0.0),
+        }
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_kva(&self) -> f64 {
+        self.kva
+    }
+
+    pub fn get_pmpp(&self) -> f64 {
+        self.pmpp
+    }
+
+    pub fn get_pct_pmpp(&self) -> f64 {
+        self.pct_pmpp
+    }
+
+    pub fn get_irradiance(&self) -> f64 {
+        self.irradiance
+    }
+
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn get_model(&self) -> usize {
+        self.model
+    }
+
+    pub fn get_class(&self) -> i32 {
+        self.pv_class
+    }
+
+    pub fn get_eff_curve(&self) -> &str {
+        &self.eff_curve
+    }
+
+    pub fn get_pt_curve(&self) -> &str {
+        &self.pt_curve
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    pub fn get_t_yearly(&self) -> &str {
+        &self.t_yearly
+    }
+
+    pub fn get_t_daily(&self) -> &str {
+        &self.t_daily
+    }
+
+    pub fn get_t_duty(&self) -> &str {
+        &self.t_duty
+    }
+
+    pub fn is_inverter_on(&self) -> bool {
+        self.inverter_on
+    }
+
+    // Irradiance multiplier from the shape of the solution mode
+    pub fn set_irradiance_mult(&mut self, mult: f64) {
+        self.shape_mult = mult;
+        self.update_curves();
+        self.update_inverter();
+        self.ckt.invalidate_yprim();
+    }
+
+    // Panel temperature from the temperature shape of the solution mode,
+    // with the P-T curve's factor at it (1 without a curve)
+    pub fn set_temperature(&mut self, temperature: f64, pt_factor: f64) {
+        self.temperature = temperature;
+        self.pt_factor = pt_factor;
+        self.update_inverter();
+        self.ckt.invalidate_yprim();
+    }
+
+    // Per unit efficiency from the efficiency curve at the present per unit
+    // panel power (1 without a curve)
+    pub fn set_efficiency(&mut self, efficiency: f64) {
+        self.efficiency = efficiency;
+        self.ckt.invalidate_yprim();
+    }
+
+    // Holds the output at `kvar`, as the kvar property does; InvControl
+    // moves it along its volt-var curve
+    pub fn set_kvar(&mut self, kvar: f64) {
+        self.kvar = kvar;
+        self.kvar_given = true;
+        self.ckt.invalidate_yprim();
+    }
+
+    // Limits the output to `pct` of Pmpp, as the %pmpp property does
+    pub fn set_pct_pmpp(&mut self, pct: f64) {
+        self.pct_pmpp = pct;
+        self.ckt.invalidate_yprim();
+    }
+
+    // DC power of the array at the present irradiance and temperature, kW
+    pub fn get_panel_kw(&self) -> f64 {
+        self.irradiance * self.shape_mult * self.pmpp * self.pt_factor
+    }
+
+    // Panel power in per unit of the inverter kVA, where the efficiency
+    // curve is read
+    pub fn get_panel_pu(&self) -> f64 {
+        self.get_panel_kw() / self.kva
+    }
+
+    // kW delivered to the grid at the present conditions
+    pub fn get_kw(&self) -> f64 {
+        self.power_out().re
+    }
+
+    // kvar delivered to the grid at the present conditions
+    pub fn get_kvar(&self) -> f64 {
+        self.power_out().im
+    }
+
+    // Impedance behind the internal voltage for dynamics and harmonics,
+    // ohms each phase
+    pub fn internal_impedance(&self) -> Complex64 {
+        let nphases = self.ckt.nphases() as f64;
+        let zbase = self.vbase() * self.vbase() / (self.kva * 1000.0 / nphases);
+        Complex64::new(self.pct_r, self.pct_x) / 100.0 * zbase
+    }
+
+    // Factor of Pmpp at a panel temperature from the P-T curve (1 without a
+    // curve)
+    pub fn pt_factor_at(&self, temperature: f64) -> f64 {
+        self.pt_xy
+            .as_ref()
+            .map_or(1.0, |curve| curve.get_y(temperature))
+    }
+
+    // Efficiency at a per unit panel power from the efficiency curve (1
+    // without a curve)
+    pub fn efficiency_at(&self, panel_pu: f64) -> f64 {
+        self.eff_xy
+            .as_ref()
+            .map_or(1.0, |curve| curve.get_y(panel_pu))
+    }
+
+    // Reads the P-T factor at the present temperature and then the
+    // efficiency at the panel power off the curves given
+    fn update_curves(&mut self) {
+        if self.pt_xy.is_some() {
+            self.pt_factor = self.pt_factor_at(self.temperature);
+        }
+        if self.eff_xy.is_some() {
+            self.efficiency = self.efficiency_at(self.get_panel_pu());
+        }
+    }
+
+    // Switches the inverter on above the cut-in power and off below the
+    // cut-out power
+    fn update_inverter(&mut self) {
+        let pct = self.get_panel_pu() * 100.0;
+        if self.inverter_on && pct < self.pct_cutout {
+            self.inverter_on = false;
+        } else if !self.inverter_on && pct >= self.pct_cutin {
+            self.inverter_on = true;
+        }
+    }
+
+    // kW and kvar out of the element: the panel power after the efficiency
+    // within %pmpp, and the kvar of the pf or kvar setting within kvarmax,
+    // then both within the kVA with watts, vars or the pf given priority
+    fn power_out(&self) -> Complex64 {
+        let kw = if self.inverter_on {
+            (self.get_panel_kw() * self.efficiency)
+                .min(self.pmpp * self.pct_pmpp / 100.0)
+                .max(0.0)
+        } else {
+            0.0
+        };
+        if !self.inverter_on && (self.var_follow_inverter || !self.kvar_given) {
+            return Complex64::new(0.0, 0.0);
+        }
+        let kvar = if self.kvar_given {
+            self.kvar
+        } else if self.pf.abs() >= 1.0 || self.pf == 0.0 {
+            0.0
+        } else {
+            (kw * (1.0 / (self.pf * self.pf) - 1.0).sqrt()).copysign(self.pf)
+        };
+        let kvar = kvar.clamp(-self.kvar_max_abs, self.kvar_max);
+        if kw * kw + kvar * kvar <= self.kva * self.kva {
+            return Complex64::new(kw, kvar);
+        }
+        if self.pf_priority {
+            let scale = self.kva / kw.hypot(kvar);
+            Complex64::new(kw * scale, kvar * scale)
+        } else if self.watt_priority {
+            let kw = kw.min(self.kva);
+            let limit = (self.kva * self.kva - kw * kw).sqrt();
+            Complex64::new(kw, kvar.clamp(-limit, limit))
+        } else {
+            let kvar = kvar.clamp(-self.kva, self.kva);
+            Complex64::new((self.kva * self.kva - kvar * kvar).sqrt().min(kw), kvar)
+        }
+    }
+
+    fn vbase(&self) -> f64 {
+        let nphases = self.ckt.nphases();
+        if self.connection == Connection::Wye && (nphases == 2 || nphases == 3) {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+
+    // Watts and vars each phase draws at 1 per unit
+    fn phase_power(&self) -> Complex64 {
+        -self.power_out() * 1000.0 / self.ckt.nphases() as f64
+    }
+
+    // Admittance drawing the present power at nominal voltage; Yprim uses it
+    // and the injection currents do the rest
+    fn nominal_admittance(&self) -> Complex64 {
+        let vbase = self.vbase();
+        self.phase_power().conj() / (vbase * vbase)
+    }
+
+    fn phase_ends(&self, phase: usize) -> (usize, usize) {
+        let nphases = self.ckt.nphases();
+        match self.connection {
+            Connection::Wye => (phase, nphases),
+            Connection::Delta if nphases == 1 => (0, 1),
+            Connection::Delta => (phase, (phase + 1) % nphases),
+        }
+    }
+
+    fn set_conductors(&mut self) {
+        let nphases = self.ckt.nphases();
+        let nconds = match self.connection {
+            Connection::Wye => nphases + 1,
+            Connection::Delta if nphases == 1 => 2,
+            Connection::Delta => nphases,
+        };
+        if nconds != self.ckt.nconds() {
+            self.ckt.set_conductors(nconds);
+        }
+    }
+
+    // Current into one phase across voltage `v`: constant power within
+    // vminpu..vmaxpu, constant impedance outside and for model 2
+    fn phase_current(&self, v: Complex64) -> Complex64 {
+        let vmag = v.norm();
+        if vmag == 0.0 {
+            return Complex64::new(0.0, 0.0);
+        }
+        let vpu = vmag / self.vbase();
+        let mult = if self.model == 2 {
+            vpu * vpu
+        } else {
+            let limited = vpu.clamp(self.vminpu, self.vmaxpu);
+            (vpu / limited) * (vpu / limited)
+        };
+        (self.phase_power() * mult / v).conj()
+    }
+
+    fn check(&self) -> DssResult<()> {
+        if self.model == 3 {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!(
+                    "User-written PVSystem models are not supported ({})",
+                    self.full_name()
+                ),
+            ));
+        }
+        if !(1..=2).contains(&self.model) {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Invalid PVSystem model {} for {}; must be 1 or 2",
+                    self.model,
+                    self.full_name()
+                ),
+            ));
+        }
+        if self.kva <= 0.0 || self.pmpp <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs kva and pmpp > 0", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DssObject for PVSystem {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = PV_SYSTEM_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - PV_SYSTEM_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_conductors();
+            }
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "kv" => self.kv = parser.make_double()?,
+            "irradiance" => self.irradiance = parser.make_double()?,
+            "pmpp" => self.pmpp = parser.make_double()?,
+            "%pmpp" => self.pct_pmpp = parser.make_double()?,
+            "temperature" => self.temperature = parser.make_double()?,
+            "pf" => {
+                self.pf = parser.make_double()?;
+                self.kvar_given = false;
+            }
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.connection = Connection::from_name(name);
+                self.set_conductors();
+            }
+            "kvar" => {
+                self.kvar = parser.make_double()?;
+                self.kvar_given = true;
+            }
+            "kva" => {
+                self.kva = parser.make_double()?;
+                if !self.kvar_max_given {
+                    self.kvar_max = self.kva;
+                }
+                if !self.kvar_max_abs_given {
+                    self.kvar_max_abs = self.kva;
+                }
+            }
+            "%cutin" => self.pct_cutin = parser.make_double()?,
+            "%cutout" => self.pct_cutout = parser.make_double()?,
+            "effcurve" => self.eff_curve = parser.get_token().to_lowercase(),
+            "p-tcurve" => self.pt_curve = parser.get_token().to_lowercase(),
+            "%r" => self.pct_r = parser.make_double()?,
+            "%x" => self.pct_x = parser.make_double()?,
+            "model" => self.model = parser.make_integer()?.max(0) as usize,
+            "vminpu" => self.vminpu = parser.make_double()?,
+            "vmaxpu" => self.vmaxpu = parser.make_double()?,
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            "tyearly" => self.t_yearly = parser.get_token().to_lowercase(),
+            "tdaily" => self.t_daily = parser.get_token().to_lowercase(),
+            "tduty" => self.t_duty = parser.get_token().to_lowercase(),
+            "class" => self.pv_class = parser.make_integer()?,
+            "varfollowinverter" => self.var_follow_inverter = interpret_yes_no(parser.get_token()),
+            "wattpriority" => self.watt_priority = interpret_yes_no(parser.get_token()),
+            "pfpriority" => self.pf_priority = interpret_yes_no(parser.get_token()),
+            "kvarmax" => {
+                self.kvar_max = parser.make_double()?.abs();
+                self.kvar_max_given = true;
+            }
+            "kvarmaxabs" => {
+                self.kvar_max_abs = parser.make_double()?.abs();
+                self.kvar_max_abs_given = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The efficiency and P-T curves must be XYCurves already defined
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some(property) = PV_SYSTEM_PROPERTIES.get(index) else {
+            return Ok(());
+        };
+        let (name, curve) = match property.name {
+            "effcurve" => (&self.eff_curve, &mut self.eff_xy),
+            "p-tcurve" => (&self.pt_curve, &mut self.pt_xy),
+            _ => return Ok(()),
+        };
+        *curve = match name.as_str() {
+            "" => None,
+            name => Some(find_xy_curve(circuit, name, &self.base.full_name())?.clone()),
+        };
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.check()?;
+        self.update_curves();
+        self.update_inverter();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for PVSystem {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // The admittance of the present power between the two conductors of
+    // each phase, its susceptance scaled to the frequency
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let y_nominal = self.nominal_admittance();
+        let y = Complex64::new(y_nominal.re, y_nominal.im / freq_mult);
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            yprim.add(a, a, y);
+            yprim.add(b, b, y);
+            yprim.add(a, b, -y);
+            yprim.add(b, a, -y);
+        }
+        self.y_phase = y;
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+
+    // What Yprim draws through each phase less what the model draws
+    fn get_injection_currents(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let mut injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        if self.ckt.get_yprim().is_none() {
+            return injection;
+        }
+        let v = self.terminal_voltages(voltages);
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            let across = v[a] - v[b];
+            let current = self.y_phase * across - self.phase_current(across);
+            injection[a] += current;
+            injection[b] -= current;
+        }
+        injection
+    }
+}
+
+impl PcElement for PVSystem {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+}
+
+impl DssClass for PVSystemClass {
+    fn name(&self) -> &'static str {
+        "PVSystem"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(PVSystem::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::xy_curve::XYCurveClass;
+    use crate::test_util::{assert_close, edit, edited, first_nodes, placed};
+
+    fn new_pv(properties: &str) -> DssResult<PVSystem> {
+        let pv = edited(
+            PVSystem::new("pv1"),
+            &PVSystemClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        let nodes = first_nodes(pv.nphases());
+        placed(pv, &[&nodes])
+    }
+
+    #[test]
+    fn test_output() {
+        let base = "phases=1 kv=1 pmpp=10 kva=12 irradiance=0.8";
+        let mut pv = new_pv(base).unwrap();
+        assert!((pv.get_kw() - 8.0).abs() < 1e-12);
+        pv.calc_yprim(60.0).unwrap();
+        let voltages = [Complex64::new(0.0, 0.0), Complex64::new(1000.0, 0.0)];
+        assert_close(
+            pv.total_power(&voltages),
+            Complex64::new(-8000.0, 0.0),
+            1e-6,
+        );
+
+        // hot panels and the inverter losses
+        pv.set_temperature(45.0, 0.9);
+        pv.set_efficiency(0.95);
+        assert!((pv.get_kw() - 8.0 * 0.9 * 0.95).abs() < 1e-12);
+
+        // %pmpp caps the output
+        let mut capped = new_pv(&format!("{} %pmpp=50", base)).unwrap();
+        assert!((capped.get_kw() - 5.0).abs() < 1e-12);
+        capped.set_irradiance_mult(0.5);
+        assert!((capped.get_kw() - 4.0).abs() < 1e-12);
+
+        assert!(new_pv("model=3").is_err());
+        assert!(new_pv("pmpp=0").is_err());
+    }
+
+    #[test]
+    fn test_curves() {
+        let mut circuit = Circuit::new("test");
+        for (name, points) in [
+            ("eff", "points=[0.1 0.86 0.2 0.9 1 0.98]"),
+            ("pt", "points=[25 1 75 0.8]"),
+        ] {
+            let curve = edited(XYCurve::new(name), &XYCurveClass, points, &circuit).unwrap();
+            circuit.add_element(Box::new(curve));
+        }
+        let mut pv = edited(
+            PVSystem::new("pv1"),
+            &PVSystemClass,
+            "phases=1 kv=1 pmpp=10 kva=10 temperature=50 effcurve=eff p-tcurve=pt",
+            &circuit,
+        )
+        .unwrap();
+        // 9 kW off the panels at 50 degrees, 0.9 pu of the kVA
+        assert!((pv.pt_factor_at(50.0) - 0.9).abs() < 1e-12);
+        assert!((pv.get_kw() - 9.0 * 0.97).abs() < 1e-12);
+
+        let err = edit(&mut pv, &PVSystemClass, "effcurve=nowhere", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_kva_limit() {
+        let base = "phases=1 kv=1 pmpp=10 kva=10";
+        // var priority gives up watts
+        let pv = new_pv(&format!("{} kvar=6", base)).unwrap();
+        assert!((pv.get_kw() - 8.0).abs() < 1e-12);
+        assert!((pv.get_kvar() - 6.0).abs() < 1e-12);
+
+        let pv = new_pv(&format!("{} kvar=-6 wattpriority=yes", base)).unwrap();
+        assert!((pv.get_kw() - 10.0).abs() < 1e-12);
+        assert!(pv.get_kvar().abs() < 1e-12);
+
+        let pv = new_pv(&format!("{} pf=0.8 pfpriority=yes", base)).unwrap();
+        assert!((pv.get_kw() - 8.0).abs() < 1e-12);
+        assert!((pv.get_kvar() - 6.0).abs() < 1e-12);
+
+        let pv = new_pv(&format!("{} kva=20 kvar=-6 kvarmaxabs=4", base)).unwrap();
+        assert!((pv.get_kvar() + 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cutin_cutout() {
+        let mut pv = new_pv("phases=1 kv=1 pmpp=10 kva=10 %cutin=30 %cutout=10 kvar=2").unwrap();
+        pv.set_irradiance_mult(0.05);
+        assert!(!pv.is_inverter_on());
+        assert_eq!(pv.get_kw(), 0.0);
+        // vars carry on with the inverter off unless they follow it
+        assert_eq!(pv.get_kvar(), 2.0);
+
+        // between cut-out and cut-in it stays off until past cut-in
+        pv.set_irradiance_mult(0.2);
+        assert!(!pv.is_inverter_on());
+        pv.set_irradiance_mult(0.3);
+        assert!(pv.is_inverter_on());
+        pv.set_irradiance_mult(0.2);
+        assert!(pv.is_inverter_on());
+        assert!((pv.get_kw() - 2.0).abs() < 1e-12);
+
+        let mut follow =
+            new_pv("phases=1 kv=1 pmpp=10 kva=10 kvar=2 varfollowinverter=yes").unwrap();
+        follow.set_irradiance_mult(0.1);
+        assert_eq!(follow.get_kvar(), 0.0);
+    }
+}
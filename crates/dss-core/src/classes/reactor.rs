@@ -0,0 +1,641 @@
+// Reactor (Pascal TReactor): a series or shunt reactor given by its rating
+// in kvar and kV, by R and X or an inductance in each phase, by R and X
+// matrices or by sequence impedances. With only bus1 given it is a grounded
+// shunt; a one-phase reactor on a neutral bus makes a grounding reactor.
+// Delta reactors sit between the phases of a single terminal.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::bus::parse_bus_spec;
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::classes::line::read_matrix;
+use crate::classes::vsource::sequence_to_phase;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice, read_complex,
+};
+
+const REACTOR_PROPERTIES: [PropertyDef; 17] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of first bus. Examples: bus1=busname bus1=busname.1.2.3 Bus2 property will default to this bus, node 0, unless previously specified. Only Bus1 need be specified for a Yg shunt reactor.",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of 2nd bus. Defaults to all phases connected to first bus, node 0, (Shunt Wye Connection) except when Bus2 is specifically defined. Not necessary to specify for delta (LL) connection",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Double,
+        default: "1200",
+        help: "Total kvar, all phases.  Evenly divided among phases. Only determines X. Specify R separately",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "For 2, 3-phase, kV phase-phase. Otherwise specify actual coil rating.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye | delta |LN |LL}  Default is wye, which is equivalent to LN. If Delta, then only one terminal.",
+    },
+    PropertyDef {
+        name: "rmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Resistance matrix, lower triangle, ohms at base frequency. Order of the matrix is the number of phases. Mutually exclusive to specifying parameters by kvar, R, X.",
+    },
+    PropertyDef {
+        name: "xmatrix",
+        kind: PropertyKind::Matrix,
+        default: "",
+        help: "Reactance matrix, lower triangle, ohms at base frequency. Order of the matrix is the number of phases. Mutually exclusive to specifying parameters by kvar, R, X.",
+    },
+    PropertyDef {
+        name: "parallel",
+        kind: PropertyKind::Bool,
+        default: "No",
+        help: "{Yes | No}  Default=No. Indicates whether Rmatrix and Xmatrix are to be considered in parallel. Default is series. For other models, specify R and Rp.",
+    },
+    PropertyDef {
+        name: "r",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Resistance (in series with reactance), each phase, ohms. This property applies to REACTOR specified by either kvar or X. See also help on Z.",
+    },
+    PropertyDef {
+        name: "x",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Reactance, each phase, ohms at base frequency. See also help on Z and LmH properties.",
+    },
+    PropertyDef {
+        name: "rp",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Resistance in parallel with R and X (the entire branch). Assumed infinite if not specified.",
+    },
+    PropertyDef {
+        name: "z1",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Positive-sequence impedance, ohms, as a 2-element array representing a complex number. Example: Z1=[1, 2]  ! represents 1 + j2 If defined, Z1, Z2, and Z0 are used to define the impedance matrix of the REACTOR. Z1 MUST BE DEFINED TO USE THIS OPTION FOR DEFINING THE MATRIX. Side Effect: Sets Z2 = Z1 and Z0 = Z1 unless they are subsequently specified.",
+    },
+    PropertyDef {
+        name: "z2",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Negative-sequence impedance, ohms, as a 2-element array representing a complex number. Example: Z2=[1, 2]  ! represents 1 + j2 Used to define the impedance matrix of the REACTOR if Z1 is also specified. Note: Z2 defaults to Z1 if it is not specifically defined. If Z2 is not equal to Z1, the impedance matrix is asymmetrical.",
+    },
+    PropertyDef {
+        name: "z0",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Zero-sequence impedance, ohms, as a 2-element array representing a complex number. Example: Z0=[3, 4]  ! represents 3 + j4 Used to define the impedance matrix of the REACTOR if Z1 is also specified. Note: Z0 defaults to Z1 if it is not specifically defined. ",
+    },
+    PropertyDef {
+        name: "z",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Alternative way of defining R and X properties. Enter a 2-element array representing R +jX in ohms. Example: Z=[5  10]   ! equivalent to R=5  X=10 ",
+    },
+    PropertyDef {
+        name: "lmh",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Inductance, mH. Alternate way to define the reactance, X, property.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 24] = concat_properties(&REACTOR_PROPERTIES, &PD_PROPERTIES);
+
+// How the impedance was last given (Pascal SpecType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReactorSpec {
+    KvarKv,
+    Ohms,
+    Inductance,
+    Matrix,
+    Sequence,
+}
+
+#[derive(Debug)]
+pub struct ReactorClass;
+
+#[derive(Debug, Clone)]
+pub struct Reactor {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pd: PdElementBase,
+    kvar: f64,
+    kv: f64,
+    connection: Connection,
+    r: f64,
+    x: f64,
+    // Parallel resistance, 0 when none
+    rp: f64,
+    l_mh: f64,
+    rmatrix: Option<CMatrix>,
+    xmatrix: Option<CMatrix>,
+    parallel: bool,
+    z1: Complex64,
+    z2: Complex64,
+    z0: Complex64,
+    z2_given: bool,
+    z0_given: bool,
+    spec: ReactorSpec,
+    bus2_given: bool,
+}
+
+impl Reactor {
+    pub fn new(name: &str) -> Self {
+        let mut reactor = Reactor {
+            base: ObjectBase::new("Reactor", name, &PROPERTIES),
+            ckt: CktElementBase::new(3, 2),
+            pd: PdElementBase::new(),
+            kvar: 1200.0,
+            kv: 12.47,
+            connection: Connection::Wye,
+            r: 0.0,
+            x: 0.0,
+            rp: 0.0,
+            l_mh: 0.0,
+            rmatrix: None,
+            xmatrix: None,
+            parallel: false,
+            z1: Complex64::new(0.0, 0.0),
+            z2: Complex64::new(0.0, 0.0),
+            z0: Complex64::new(0.0, 0.0),
+            z2_given: false,
+            z0_given: false,
+            spec: ReactorSpec::KvarKv,
+            bus2_given: false,
+        };
+        reactor.set_reactance();
+        reactor
+    }
+
+    pub fn get_kvar(&self) -> f64 {
+        self.kvar
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn get_r(&self) -> f64 {
+        self.r
+    }
+
+    // Reactance of each phase, ohms at base frequency
+    pub fn get_x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn is_parallel(&self) -> bool {
+        self.parallel
+    }
+
+    // X from the rating or the inductance, or the rating from X
+    fn set_reactance(&mut self) {
+        let omega = 2.0 * std::f64::consts::PI * self.ckt.get_base_frequency();
+        let nphases = self.ckt.nphases() as f64;
+        let phase_kv = if self.connection == Connection::Wye && nphases > 1.0 {
+            self.kv / 3.0_f64.sqrt()
+        } else {
+            self.kv
+        };
+        match self.spec {
+            ReactorSpec::KvarKv => self.x = phase_kv * phase_kv * 1000.0 / (self.kvar / nphases),
+            ReactorSpec::Inductance => self.x = omega * self.l_mh / 1000.0,
+            _ => {}
+        }
+        if matches!(self.spec, ReactorSpec::Ohms | ReactorSpec::Inductance) && self.x != 0.0 {
+            self.kvar = phase_kv * phase_kv * 1000.0 / self.x * nphases;
+        }
+        if self.x != 0.0 {
+            self.l_mh = self.x / omega * 1000.0;
+        }
+    }
+
+    // Series impedance of each phase at the frequency multiplier
+    fn phase_admittances(&self, freq_mult: f64) -> DssResult<CMatrix> {
+        let nphases = self.ckt.nphases();
+        let singular = || {
+            DssError::new(
+                codes::SINGULAR_MATRIX,
+                &format!("Impedance of {} is singular", self.full_name()),
+            )
+        };
+        let mut y = match (self.spec, &self.rmatrix, &self.xmatrix) {
+            (ReactorSpec::Matrix, rmatrix, xmatrix) => {
+                let at = |matrix: &Option<CMatrix>, i, j| {
+                    matrix.as_ref().map_or(0.0, |m: &CMatrix| m.get(i, j).re)
+                };
+                let mut r = CMatrix::new(nphases);
+                let mut x = CMatrix::new(nphases);
+                for i in 0..nphases {
+                    for j in 0..nphases {
+                        r.set(i, j, Complex64::new(at(rmatrix, i, j), 0.0));
+                        x.set(i, j, Complex64::new(0.0, at(xmatrix, i, j) * freq_mult));
+                    }
+                }
+                if self.parallel {
+                    // each that is given, in parallel
+                    let mut y = CMatrix::new(nphases);
+                    for (given, mut z) in [(rmatrix.is_some(), r), (xmatrix.is_some(), x)] {
+                        if given {
+                            if !z.invert() {
+                                return Err(singular());
+                            }
+                            y.add_matrix(&z);
+                        }
+                    }
+                    y
+                } else {
+                    r.add_matrix(&x);
+                    if !r.invert() {
+                        return Err(singular());
+                    }
+                    r
+                }
+            }
+            (ReactorSpec::Sequence, _, _) => {
+                let at_frequency = |z: Complex64| Complex64::new(z.re, z.im * freq_mult);
+                let mut z = sequence_to_phase(
+                    nphases,
+                    at_frequency(self.z0),
+                    at_frequency(self.z1),
+                    at_frequency(self.z2),
+                );
+                if !z.invert() {
+                    return Err(singular());
+                }
+                z
+            }
+            _ => {
+                let z = Complex64::new(self.r, self.x * freq_mult);
+                if z.norm() == 0.0 {
+                    return Err(singular());
+                }
+                let mut y = CMatrix::new(nphases);
+                for phase in 0..nphases {
+                    y.set(phase, phase, z.inv());
+                }
+                y
+            }
+        };
+        if self.rp > 0.0 {
+            for phase in 0..nphases {
+                y.add(phase, phase, Complex64::new(1.0 / self.rp, 0.0));
+            }
+        }
+        Ok(y)
+    }
+
+    // Bus2 follows bus1 with its nodes grounded unless given
+    fn set_default_bus2(&mut self) {
+        if self.bus2_given || self.connection == Connection::Delta {
+            return;
+        }
+        let bus1 = self.ckt.get_bus(0);
+        let name = bus1.split('.').next().unwrap_or("").to_string();
+        if !name.is_empty() {
+            let bus2 = format!("{}{}", name, ".0".repeat(self.ckt.nphases()));
+            self.ckt.set_bus(1, &bus2);
+        }
+    }
+
+    // Terminals and conductors for the connection
+    fn set_connection(&mut self, connection: Connection) {
+        self.connection = connection;
+        let nphases = self.ckt.nphases();
+        match connection {
+            Connection::Delta => {
+                self.ckt.set_terminals(1);
+                self.ckt
+                    .set_conductors(if nphases == 1 { 2 } else { nphases });
+            }
+            Connection::Wye => {
+                self.ckt.set_terminals(2);
+                self.ckt.set_conductors(nphases);
+            }
+        }
+    }
+}
+
+impl DssObject for Reactor {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = REACTOR_PROPERTIES.get(index) else {
+            return self.set_pd_property(index - REACTOR_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "bus2" => {
+                self.ckt.set_bus(1, parser.get_token());
+                self.bus2_given = true;
+            }
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_connection(self.connection);
+                self.rmatrix = None;
+                self.xmatrix = None;
+                if self.spec == ReactorSpec::Matrix {
+                    self.spec = ReactorSpec::KvarKv;
+                }
+            }
+            "kvar" => {
+                self.kvar = parser.make_double()?;
+                self.spec = ReactorSpec::KvarKv;
+            }
+            "kv" => self.kv = parser.make_double()?,
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.set_connection(Connection::from_name(name));
+            }
+            "rmatrix" | "xmatrix" => {
+                let mut matrix = CMatrix::new(self.ckt.nphases());
+                read_matrix(parser, &mut matrix, 1.0, false)?;
+                if property.name == "rmatrix" {
+                    self.rmatrix = Some(matrix);
+                } else {
+                    self.xmatrix = Some(matrix);
+                }
+                self.spec = ReactorSpec::Matrix;
+            }
+            "parallel" => self.parallel = interpret_yes_no(parser.get_token()),
+            "r" => {
+                self.r = parser.make_double()?;
+                if self.spec != ReactorSpec::KvarKv {
+                    self.spec = ReactorSpec::Ohms;
+                }
+            }
+            "x" => {
+                self.x = parser.make_double()?;
+                self.spec = ReactorSpec::Ohms;
+            }
+            "rp" => self.rp = parser.make_double()?,
+            "z1" => {
+                self.z1 = read_complex(parser, "z1")?;
+                self.spec = ReactorSpec::Sequence;
+            }
+            "z2" => {
+                self.z2 = read_complex(parser, "z2")?;
+                self.z2_given = true;
+                self.spec = ReactorSpec::Sequence;
+            }
+            "z0" => {
+                self.z0 = read_complex(parser, "z0")?;
+                self.z0_given = true;
+                self.spec = ReactorSpec::Sequence;
+            }
+            "z" => {
+                let z = read_complex(parser, "z")?;
+                self.r = z.re;
+                self.x = z.im;
+                self.spec = ReactorSpec::Ohms;
+            }
+            "lmh" => {
+                self.l_mh = parser.make_double()?;
+                self.spec = ReactorSpec::Inductance;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.spec == ReactorSpec::KvarKv && (self.kvar <= 0.0 || self.kv <= 0.0) {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs kvar and kv > 0", self.full_name()),
+            ));
+        }
+        if self.spec == ReactorSpec::Sequence {
+            if !self.z2_given {
+                self.z2 = self.z1;
+            }
+            if !self.z0_given {
+                self.z0 = self.z1;
+            }
+        }
+        self.set_reactance();
+        self.set_default_bus2();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Reactor {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        Some(self)
+    }
+
+    // The phase admittances between the two terminals or, for delta,
+    // between the phases of the one terminal
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nphases = self.ckt.nphases();
+        let y = self.phase_admittances(frequency / self.ckt.get_base_frequency())?;
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        match self.connection {
+            Connection::Wye => {
+                for i in 0..nphases {
+                    for j in 0..nphases {
+                        let value = y.get(i, j);
+                        yprim.set(i, j, value);
+                        yprim.set(i + nphases, j + nphases, value);
+                        yprim.set(i, j + nphases, -value);
+                        yprim.set(i + nphases, j, -value);
+                    }
+                }
+            }
+            Connection::Delta => {
+                for phase in 0..nphases {
+                    let (a, b) = if nphases == 1 {
+                        (0, 1)
+                    } else {
+                        (phase, (phase + 1) % nphases)
+                    };
+                    let value = y.get(phase, phase);
+                    yprim.add(a, a, value);
+                    yprim.add(b, b, value);
+                    yprim.add(a, b, -value);
+                    yprim.add(b, a, -value);
+                }
+            }
+        }
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+}
+
+impl PdElement for Reactor {
+    fn pd_base(&self) -> &PdElementBase {
+        &self.pd
+    }
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase {
+        &mut self.pd
+    }
+
+    // A delta reactor or one whose second terminal is all grounded
+    fn is_shunt(&self) -> bool {
+        if self.connection == Connection::Delta {
+            return true;
+        }
+        parse_bus_spec(self.ckt.get_bus(1), self.ckt.nphases() as u32)
+            .map(|(_, nodes)| nodes.iter().all(|&node| node == 0))
+            .unwrap_or(true)
+    }
+}
+
+impl DssClass for ReactorClass {
+    fn name(&self) -> &'static str {
+        "Reactor"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Reactor::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close_rel, edited, first_nodes, placed};
+
+    fn new_reactor(properties: &str) -> DssResult<Reactor> {
+        let reactor = edited(
+            Reactor::new("r1"),
+            &ReactorClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        let nodes = first_nodes(reactor.nconds());
+        placed(reactor, &[&nodes])
+    }
+
+    #[test]
+    fn test_kvar_and_ohms() {
+        let reactor = new_reactor("bus1=b kvar=1200 kv=12.47 r=1").unwrap();
+        let x = 12.47 * 12.47 * 1000.0 / 1200.0;
+        assert!((reactor.get_x() - x).abs() < 1e-9);
+        assert_eq!(reactor.get_bus(1), "b.0.0.0");
+        assert!(reactor.is_shunt());
+        let y = reactor.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(1.0, x).inv(), 1e-9);
+        assert_close_rel(y.get(0, 3), -Complex64::new(1.0, x).inv(), 1e-9);
+
+        // a series reactor at the 3rd harmonic, with Rp across it
+        let mut series = new_reactor("bus1=a bus2=b phases=1 z=[0.5 2] rp=100").unwrap();
+        assert!(!series.is_shunt());
+        series.calc_yprim(180.0).unwrap();
+        let y = series.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(0.5, 6.0).inv() + 0.01, 1e-9);
+
+        let lmh = new_reactor("phases=1 lmh=10").unwrap();
+        assert!((lmh.get_x() - 2.0 * std::f64::consts::PI * 60.0 * 0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_neutral_grounding() {
+        let reactor = new_reactor("bus1=sub.4 phases=1 x=5").unwrap();
+        assert_eq!(reactor.get_bus(1), "sub.0");
+        assert!(reactor.is_shunt());
+        let y = reactor.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(0.0, -0.2), 1e-9);
+
+        let delta = new_reactor("conn=delta x=10").unwrap();
+        assert_eq!((delta.nterms(), delta.nconds()), (1, 3));
+        let y = delta.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(0.0, -0.2), 1e-9);
+        assert_close_rel(y.get(0, 1), Complex64::new(0.0, 0.1), 1e-9);
+    }
+
+    #[test]
+    fn test_matrix_and_sequence() {
+        let series = new_reactor("phases=2 rmatrix=[2 | 0 2] xmatrix=[4 | 0 4]").unwrap();
+        let y = series.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(2.0, 4.0).inv(), 1e-9);
+
+        let parallel =
+            new_reactor("phases=2 rmatrix=[2 | 0 2] xmatrix=[4 | 0 4] parallel=yes").unwrap();
+        let y = parallel.ckt_base().get_yprim().unwrap();
+        assert_close_rel(y.get(0, 0), Complex64::new(0.5, -0.25), 1e-9);
+
+        // zero-sequence current sees Z0 only
+        let sequence = new_reactor("z1=[1 10] z0=[3 30]").unwrap();
+        let y = sequence.ckt_base().get_yprim().unwrap();
+        let v = [Complex64::new(1.0, 0.0); 3];
+        let mut v_all = v.to_vec();
+        v_all.extend([Complex64::new(0.0, 0.0); 3]);
+        let current = y.mv_mult(&v_all)[0];
+        assert_close_rel(current, Complex64::new(3.0, 30.0).inv(), 1e-9);
+    }
+}
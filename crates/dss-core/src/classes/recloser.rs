@@ -0,0 +1,813 @@
+// Recloser (Pascal TRecloser): an overcurrent recloser watching a terminal
+// of one element and opening a terminal of another, by default the same
+// one. Its first numfast trips follow the fast phase and ground curves, the
+// rest the delayed ones; after each trip it recloses after the next of its
+// reclose intervals until it has opened shots times and locks out. The fast
+// trips give a temporary fault downstream the chance to clear before a fuse
+// melts on the delayed curve: once the recloser opens, the fault's current
+// drops below its minamps and the solution takes it out, so the reclose
+// holds and the recloser resets after its reset time.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::relay::Curve;
+use crate::classes::tcc_curve::tcc_time;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice, read_doubles,
+};
+use crate::registry::ElementId;
+
+const RECLOSER_PROPERTIES: [PropertyDef; 23] = [
+    PropertyDef {
+        name: "monitoredobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line, transformer, load, or generator, to which the Recloser's PT and/or CT are connected. This is the \"monitored\" element. There is no default; must be specified.",
+    },
+    PropertyDef {
+        name: "monitoredterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the Recloser is connected. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "switchedobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of circuit element switch that the Recloser controls. Specify the full object name.Defaults to the same as the Monitored element. This is the \"controlled\" element.",
+    },
+    PropertyDef {
+        name: "switchedterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the controlled element in which the switch is controlled by the Recloser. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "numfast",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of Fast (fuse saving) operations.  Default is 1. (See \"Shots\")",
+    },
+    PropertyDef {
+        name: "phasefast",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "a",
+        help: "Name of the TCC Curve object that determines the Phase Fast trip.  Must have been previously defined as a TCC_Curve object. Default is \"A\". Multiplying the current values in the curve by the \"phasetrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "phasedelayed",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "d",
+        help: "Name of the TCC Curve object that determines the Phase Delayed trip.  Must have been previously defined as a TCC_Curve object. Default is \"D\".Multiplying the current values in the curve by the \"phasetrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "groundfast",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "Name of the TCC Curve object that determines the Ground Fast trip.  Must have been previously defined as a TCC_Curve object. Default is none (ignored). Multiplying the current values in the curve by the \"groundtrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "grounddelayed",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "Name of the TCC Curve object that determines the Ground Delayed trip.  Must have been previously defined as a TCC_Curve object. Default is none (ignored).Multiplying the current values in the curve by the \"groundtrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "phasetrip",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Multiplier or actual phase amps for the phase TCC curve.  Defaults to 1.0.",
+    },
+    PropertyDef {
+        name: "groundtrip",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Multiplier or actual ground amps (3I0) for the ground TCC curve.  Defaults to 1.0.",
+    },
+    PropertyDef {
+        name: "phaseinst",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Actual amps for instantaneous phase trip which is assumed to happen in 0.01 sec + Delay Time. Default is 0.0, which signifies no inst trip. ",
+    },
+    PropertyDef {
+        name: "groundinst",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Actual amps for instantaneous ground trip which is assumed to happen in 0.01 sec + Delay Time.Default is 0.0, which signifies no inst trip.",
+    },
+    PropertyDef {
+        name: "reset",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Reset time in sec for Recloser.  Default is 15. ",
+    },
+    PropertyDef {
+        name: "shots",
+        kind: PropertyKind::Integer,
+        default: "4",
+        help: "Total Number of fast and delayed shots to lockout.  Default is 4. This is one more than the number of reclose intervals.",
+    },
+    PropertyDef {
+        name: "recloseintervals",
+        kind: PropertyKind::Doubles,
+        default: "(0.5, 2.0, 2.0)",
+        help: "Array of reclose intervals.  Default for Recloser is (0.5, 2.0, 2.0) seconds. A locked out Recloser must be closed manually (action=close).",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Fixed delay time (sec) added to Recloser trip time. Default is 0.0. Used to represent breaker time or any other delay.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(ACTION_NAMES),
+        default: "",
+        help: "{Trip/Open | Close}  Action that overrides the Recloser control. Simulates manual control on recloser \"Trip\" or \"Open\" causes the controlled element to open and lock out. \"Close\" causes the controlled element to close and the Recloser to reset to its first operation.",
+    },
+    PropertyDef {
+        name: "tdphfast",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Phase Fast trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "tdgrfast",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Ground Fast trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "tdphdelayed",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Phase Delayed trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "tdgrdelayed",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Ground Delayed trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True* | No/False} Default is Yes for Recloser. Write trips, recloses and resets to EventLog.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 25] = concat_properties(&RECLOSER_PROPERTIES, &CKT_PROPERTIES);
+
+// Values of the action property
+const ACTION_NAMES: &[&str] = &["trip", "open", "close"];
+
+// Codes of the queued actions
+const ACTION_OPEN: i32 = 1;
+const ACTION_CLOSE: i32 = 2;
+const ACTION_RESET: i32 = 3;
+
+// Proxy of an action commanded by the action property rather than picked up
+const COMMANDED: usize = 1;
+
+// Time of an instantaneous trip, seconds
+const INST_TIME: f64 = 0.01;
+
+#[derive(Debug)]
+pub struct RecloserClass;
+
+#[derive(Debug, Clone)]
+pub struct Recloser {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    // Full name, lower case; empty for the monitored element
+    switched_obj: String,
+    // From 1
+    switched_term: usize,
+    num_fast: usize,
+    phase_fast: Curve,
+    phase_delayed: Curve,
+    ground_fast: Curve,
+    ground_delayed: Curve,
+    phase_trip: f64,
+    ground_trip: f64,
+    phase_inst: f64,
+    ground_inst: f64,
+    reset_time: f64,
+    shots: usize,
+    reclose_intervals: Vec<f64>,
+    delay: f64,
+    td_ph_fast: f64,
+    td_gr_fast: f64,
+    td_ph_delayed: f64,
+    td_gr_delayed: f64,
+    event_log: bool,
+    // Action given by the action property, carried out at the next sample
+    command: Option<i32>,
+    // The queued action and its code
+    pending: Option<(usize, i32)>,
+    // Trips since the recloser last reset
+    operations: usize,
+    locked_out: bool,
+}
+
+impl Recloser {
+    pub fn new(name: &str) -> Self {
+        Recloser {
+            base: ObjectBase::new("Recloser", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            switched_obj: String::new(),
+            switched_term: 1,
+            num_fast: 1,
+            phase_fast: Curve::named("a"),
+            phase_delayed: Curve::named("d"),
+            ground_fast: Curve::default(),
+            ground_delayed: Curve::default(),
+            phase_trip: 1.0,
+            ground_trip: 1.0,
+            phase_inst: 0.0,
+            ground_inst: 0.0,
+            reset_time: 15.0,
+            shots: 4,
+            reclose_intervals: vec![0.5, 2.0, 2.0],
+            delay: 0.0,
+            td_ph_fast: 1.0,
+            td_gr_fast: 1.0,
+            td_ph_delayed: 1.0,
+            td_gr_delayed: 1.0,
+            event_log: true,
+            command: None,
+            pending: None,
+            operations: 0,
+            locked_out: false,
+        }
+    }
+
+    // Full name of the element the recloser opens
+    pub fn get_switched_obj(&self) -> &str {
+        if self.switched_obj.is_empty() {
+            self.control.get_element()
+        } else {
+            &self.switched_obj
+        }
+    }
+
+    pub fn is_locked_out(&self) -> bool {
+        self.locked_out
+    }
+
+    pub fn get_operations(&self) -> usize {
+        self.operations
+    }
+
+    pub fn get_phase_fast(&self) -> &str {
+        &self.phase_fast.name
+    }
+
+    pub fn get_phase_delayed(&self) -> &str {
+        &self.phase_delayed.name
+    }
+
+    pub fn get_ground_fast(&self) -> &str {
+        &self.ground_fast.name
+    }
+
+    pub fn get_ground_delayed(&self) -> &str {
+        &self.ground_delayed.name
+    }
+
+    // Points (multiple of pickup, seconds) of the curves, as resolved from
+    // the named TCC curves
+    pub fn set_phase_fast(&mut self, points: Vec<(f64, f64)>) {
+        self.phase_fast.points = points;
+    }
+
+    pub fn set_phase_delayed(&mut self, points: Vec<(f64, f64)>) {
+        self.phase_delayed.points = points;
+    }
+
+    pub fn set_ground_fast(&mut self, points: Vec<(f64, f64)>) {
+        self.ground_fast.points = points;
+    }
+
+    pub fn set_ground_delayed(&mut self, points: Vec<(f64, f64)>) {
+        self.ground_delayed.points = points;
+    }
+
+    // Seconds until the recloser trips on the present currents, by the fast
+    // curves for its first numfast operations and the delayed ones after
+    fn trip_time(&self, element: &dyn CktElement, voltages: &[Complex64]) -> Option<f64> {
+        let first = (self.control.get_terminal() - 1) * element.nconds();
+        let currents = element.get_currents(voltages);
+        let phases: Vec<Complex64> = (0..element.nphases())
+            .filter_map(|phase| currents.get(first + phase).copied())
+            .collect();
+        let phase_max = phases.iter().map(|i| i.norm()).fold(0.0, f64::max);
+        let residual = phases.iter().sum::<Complex64>().norm();
+        let fast = self.operations < self.num_fast;
+        let (phase_curve, phase_td, ground_curve, ground_td) = if fast {
+            (
+                &self.phase_fast,
+                self.td_ph_fast,
+                &self.ground_fast,
+                self.td_gr_fast,
+            )
+        } else {
+            (
+                &self.phase_delayed,
+                self.td_ph_delayed,
+                &self.ground_delayed,
+                self.td_gr_delayed,
+            )
+        };
+        let phase = element_time(
+            phase_max,
+            self.phase_inst,
+            phase_curve,
+            self.phase_trip,
+            phase_td,
+        );
+        let ground = element_time(
+            residual,
+            self.ground_inst,
+            ground_curve,
+            self.ground_trip,
+            ground_td,
+        );
+        let time = match (phase, ground) {
+            (Some(phase), Some(ground)) => Some(phase.min(ground)),
+            (phase, ground) => phase.or(ground),
+        };
+        time.map(|time| time + self.delay)
+    }
+
+    // Seconds before the recloser closes after its latest trip
+    fn reclose_interval(&self) -> f64 {
+        self.reclose_intervals
+            .get(self.operations.saturating_sub(1))
+            .or(self.reclose_intervals.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn switched_element(&self, circuit: &Circuit) -> Option<ElementId> {
+        find_by_full_name(circuit, self.get_switched_obj())
+    }
+
+    fn log(&self, queue: &mut ControlQueue, action: &str) {
+        if self.event_log {
+            queue.log(&self.full_name(), action);
+        }
+    }
+}
+
+// Trip time of the phase or ground element: instantaneous above the inst
+// setting, else from the curve at the multiple of the pickup
+fn element_time(amps: f64, inst: f64, curve: &Curve, pickup: f64, time_dial: f64) -> Option<f64> {
+    if inst > 0.0 && amps >= inst {
+        return Some(INST_TIME);
+    }
+    if !curve.is_set() || pickup <= 0.0 {
+        return None;
+    }
+    tcc_time(&curve.points, amps / pickup).map(|time| time * time_dial)
+}
+
+impl DssObject for Recloser {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = RECLOSER_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - RECLOSER_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "monitoredobj" => self.control.set_element(parser.get_token()),
+            "monitoredterm" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "switchedobj" => self.switched_obj = parser.get_token().to_lowercase(),
+            "switchedterm" => self.switched_term = parser.make_integer()?.max(1) as usize,
+            "numfast" => self.num_fast = parser.make_integer()?.max(0) as usize,
+            "phasefast" => self.phase_fast = Curve::named(&parser.get_token().to_lowercase()),
+            "phasedelayed" => self.phase_delayed = Curve::named(&parser.get_token().to_lowercase()),
+            "groundfast" => self.ground_fast = Curve::named(&parser.get_token().to_lowercase()),
+            "grounddelayed" => {
+                self.ground_delayed = Curve::named(&parser.get_token().to_lowercase())
+            }
+            "phasetrip" => self.phase_trip = parser.make_double()?,
+            "groundtrip" => self.ground_trip = parser.make_double()?,
+            "phaseinst" => self.phase_inst = parser.make_double()?,
+            "groundinst" => self.ground_inst = parser.make_double()?,
+            "reset" => self.reset_time = parser.make_double()?,
+            "shots" => self.shots = parser.make_integer()?.max(1) as usize,
+            "recloseintervals" => self.reclose_intervals = read_doubles(parser)?,
+            "delay" => self.delay = parser.make_double()?,
+            "action" => {
+                let name = read_choice(parser, ACTION_NAMES, "action")?;
+                self.command = Some(match name {
+                    "close" => ACTION_CLOSE,
+                    _ => ACTION_OPEN,
+                });
+            }
+            "tdphfast" => self.td_ph_fast = parser.make_double()?,
+            "tdgrfast" => self.td_gr_fast = parser.make_double()?,
+            "tdphdelayed" => self.td_ph_delayed = parser.make_double()?,
+            "tdgrdelayed" => self.td_gr_delayed = parser.make_double()?,
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The curves must be TCC curves already defined
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let owner = self.full_name();
+        let curve = match RECLOSER_PROPERTIES.get(index).map(|property| property.name) {
+            Some("phasefast") => &mut self.phase_fast,
+            Some("phasedelayed") => &mut self.phase_delayed,
+            Some("groundfast") => &mut self.ground_fast,
+            Some("grounddelayed") => &mut self.ground_delayed,
+            _ => return Ok(()),
+        };
+        curve.resolve(circuit, &owner)
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Recloser {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for Recloser {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Arms a trip when the recloser picks up, takes it back when it drops
+    // out, and once the switch has held closed arms the reset of its shots
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if let Some(code) = self.command.take() {
+            if let Some((handle, _)) = self.pending {
+                queue.delete(handle);
+            }
+            self.pending = Some((queue.push(0.0, code, COMMANDED, id), code));
+            return;
+        }
+        if self.locked_out {
+            return;
+        }
+        let Some(switched) = self
+            .switched_element(circuit)
+            .and_then(|switched| circuit.element(switched)?.as_ckt_element())
+        else {
+            return;
+        };
+        if !switched
+            .ckt_base()
+            .is_terminal_closed(self.switched_term - 1)
+        {
+            return;
+        }
+        let Some(element) = self
+            .control
+            .find_element(circuit)
+            .and_then(|element| circuit.element(element)?.as_ckt_element())
+        else {
+            return;
+        };
+        match (self.trip_time(element, voltages), self.pending) {
+            (Some(_), Some((_, ACTION_OPEN))) => {}
+            (Some(time), pending) => {
+                if let Some((handle, _)) = pending {
+                    queue.delete(handle);
+                }
+                self.pending = Some((queue.push(time, ACTION_OPEN, 0, id), ACTION_OPEN));
+            }
+            (None, Some((handle, ACTION_OPEN))) => {
+                queue.delete(handle);
+                self.pending = None;
+                self.log(queue, "Reset");
+            }
+            (None, None) if self.operations > 0 => {
+                let handle = queue.push(self.reset_time, ACTION_RESET, 0, id);
+                self.pending = Some((handle, ACTION_RESET));
+            }
+            (None, _) => {}
+        }
+    }
+
+    // Opens or closes the switched terminal, or resets the shots
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        self.pending = None;
+        if action.code == ACTION_RESET {
+            self.operations = 0;
+            self.log(queue, "Shots reset");
+            return;
+        }
+        let Some(switched) = self.switched_element(circuit) else {
+            return;
+        };
+        let terminal = self.switched_term - 1;
+        let Some(element) = circuit
+            .element_mut(switched)
+            .and_then(|element| element.as_ckt_element_mut())
+        else {
+            return;
+        };
+        match action.code {
+            ACTION_OPEN => {
+                element.ckt_base_mut().set_closed(terminal, None, false);
+                if action.proxy == COMMANDED {
+                    self.locked_out = true;
+                    self.log(queue, "Opened by command, locked out");
+                    return;
+                }
+                self.operations += 1;
+                if self.operations >= self.shots {
+                    self.locked_out = true;
+                    self.log(queue, "Opened, locked out");
+                } else {
+                    let interval = self.reclose_interval();
+                    let handle = queue.push(interval, ACTION_CLOSE, 0, action.owner);
+                    self.pending = Some((handle, ACTION_CLOSE));
+                    self.log(queue, "Opened");
+                }
+            }
+            ACTION_CLOSE => {
+                element.ckt_base_mut().set_closed(terminal, None, true);
+                if action.proxy == COMMANDED {
+                    self.locked_out = false;
+                    self.operations = 0;
+                    self.log(queue, "Closed by command, reset");
+                } else {
+                    self.log(queue, "Closed");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self, circuit: &mut Circuit) {
+        if let Some(element) = self
+            .switched_element(circuit)
+            .and_then(|switched| circuit.element_mut(switched))
+            .and_then(|element| element.as_ckt_element_mut())
+        {
+            element
+                .ckt_base_mut()
+                .set_closed(self.switched_term - 1, None, true);
+        }
+        self.pending = None;
+        self.operations = 0;
+        self.locked_out = false;
+    }
+}
+
+impl DssClass for RecloserClass {
+    fn name(&self) -> &'static str {
+        "Recloser"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Recloser::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::classes::fault::{Fault, FaultClass};
+    use crate::classes::line::{Line, LineClass};
+    use crate::test_util::{edit, edited, placed};
+
+    // A 3-phase line of 1+1j ohm per phase from nodes 1-3 to nodes 4-6
+    // and a temporary fault from node 4 to ground
+    fn faulted_circuit() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("l1"),
+            &LineClass,
+            "r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(line, &[&[1, 2, 3], &[4, 5, 6]]).unwrap()));
+        let fault = edited(
+            Fault::new("f1"),
+            &FaultClass,
+            "bus1=b2.1 r=0.01 temporary=yes minamps=10",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(fault, &[&[4], &[0]]).unwrap()));
+        circuit
+    }
+
+    // Yprims as the next solution would build them
+    fn rebuild(circuit: &mut Circuit) {
+        for (class_name, name) in [("line", "l1"), ("fault", "f1")] {
+            let id = circuit.find_element(class_name, name).unwrap();
+            let element = circuit
+                .element_mut(id)
+                .unwrap()
+                .as_ckt_element_mut()
+                .unwrap();
+            element.calc_yprim(60.0).unwrap();
+        }
+    }
+
+    fn new_recloser(properties: &str, circuit: &Circuit) -> Recloser {
+        let mut recloser = edited(
+            Recloser::new("r1"),
+            &RecloserClass,
+            &format!("monitoredobj=line.l1 phasetrip=100 {}", properties),
+            circuit,
+        )
+        .unwrap();
+        recloser.set_phase_fast(vec![(1.0, 0.5), (10.0, 0.05)]);
+        recloser.set_phase_delayed(vec![(1.0, 20.0), (10.0, 2.0)]);
+        recloser
+    }
+
+    // Balanced 7.2 kV at the sending end; the receiving end of phase 1 at
+    // `v1` volts and the others carrying no current
+    fn voltages(v1: f64) -> Vec<Complex64> {
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let sending = [Complex64::from(7200.0), a * 7200.0, a * a * 7200.0];
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        voltages.extend(sending);
+        voltages.extend(sending);
+        voltages[4] = Complex64::from(v1);
+        voltages
+    }
+
+    fn line_closed(circuit: &Circuit) -> bool {
+        circuit
+            .find_object_as::<Line>("line", "l1")
+            .unwrap()
+            .ckt_base()
+            .is_terminal_closed(0)
+    }
+
+    fn run_to(recloser: &mut Recloser, circuit: &mut Circuit, queue: &mut ControlQueue, sec: f64) {
+        queue.set_time(0, sec);
+        while let Some(action) = queue.pop_due() {
+            recloser.do_pending_action(&action, circuit, &[], queue);
+        }
+        rebuild(circuit);
+    }
+
+    fn event_actions(queue: &ControlQueue) -> Vec<&str> {
+        queue
+            .events()
+            .events()
+            .iter()
+            .map(|event| event.action.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_fast_trip_clears_temporary_fault() {
+        let mut circuit = faulted_circuit();
+        let mut recloser = new_recloser("", &circuit);
+        let mut queue = ControlQueue::new();
+        // 7200 / sqrt(2) = 5091 A in phase 1, over 50 times the pickup
+        recloser.sample(99, &circuit, &voltages(0.0), &mut queue);
+        let (_, due) = queue.next_time().unwrap();
+        assert!((due - 0.05).abs() < 1e-9);
+        run_to(&mut recloser, &mut circuit, &mut queue, 0.05);
+        assert!(!line_closed(&circuit));
+
+        // with the line open nothing feeds the fault and it goes out
+        let id = circuit.find_element("fault", "f1").unwrap();
+        let fault = circuit.element_mut(id).unwrap();
+        let fault = fault.as_any_mut().downcast_mut::<Fault>().unwrap();
+        let currents = fault.get_currents(&[Complex64::new(0.0, 0.0); 7]);
+        assert!(fault.check_clearing(&currents));
+
+        run_to(&mut recloser, &mut circuit, &mut queue, 0.55);
+        assert!(line_closed(&circuit));
+        let normal = voltages(7200.0);
+        recloser.sample(99, &circuit, &normal, &mut queue);
+        run_to(&mut recloser, &mut circuit, &mut queue, 15.55);
+        assert_eq!(recloser.get_operations(), 0);
+        assert_eq!(event_actions(&queue), ["Opened", "Closed", "Shots reset"]);
+    }
+
+    #[test]
+    fn test_permanent_fault_locks_out() {
+        let mut circuit = faulted_circuit();
+        let mut recloser = new_recloser("shots=3 recloseintervals=[1 5]", &circuit);
+        let mut queue = ControlQueue::new();
+        let fault = voltages(0.0);
+        recloser.sample(99, &circuit, &fault, &mut queue);
+        run_to(&mut recloser, &mut circuit, &mut queue, 0.05);
+        run_to(&mut recloser, &mut circuit, &mut queue, 1.05);
+        assert!(line_closed(&circuit));
+
+        // the delayed curve from the second shot on
+        recloser.sample(99, &circuit, &fault, &mut queue);
+        let (_, due) = queue.next_time().unwrap();
+        assert!((due - 3.05).abs() < 1e-9);
+        run_to(&mut recloser, &mut circuit, &mut queue, 3.05);
+        assert!(!line_closed(&circuit));
+        run_to(&mut recloser, &mut circuit, &mut queue, 8.05);
+        recloser.sample(99, &circuit, &fault, &mut queue);
+        run_to(&mut recloser, &mut circuit, &mut queue, 10.05);
+        assert!(!line_closed(&circuit));
+        assert!(recloser.is_locked_out());
+        assert_eq!(
+            event_actions(&queue),
+            ["Opened", "Closed", "Opened", "Closed", "Opened, locked out"]
+        );
+
+        edit(&mut recloser, &RecloserClass, "action=close", &circuit).unwrap();
+        recloser.sample(99, &circuit, &fault, &mut queue);
+        run_to(&mut recloser, &mut circuit, &mut queue, 10.05);
+        assert!(line_closed(&circuit));
+        assert!(!recloser.is_locked_out());
+    }
+}
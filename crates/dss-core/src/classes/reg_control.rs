@@ -0,0 +1,685 @@
+// RegControl (Pascal TRegControl): the control of a voltage regulator or
+// load tap changer. It watches one phase of a transformer winding, or a
+// remote bus, through a PT of ptratio, less the line-drop compensation R
+// and X carry at the CT current, and when that voltage leaves the band about
+// vreg it moves the taps toward it: after delay seconds (shorter the further
+// out with inversetime), then after tapdelay for each further change, at
+// most maxtapchange steps at a time and within the winding's tap range. A
+// reversible regulator takes the rev* settings while power flows backward.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::bus::parse_bus_spec;
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::transformer::Transformer;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, interpret_yes_no};
+use crate::registry::ElementId;
+
+const REG_CONTROL_PROPERTIES: [PropertyDef; 20] = [
+    PropertyDef {
+        name: "transformer",
+        kind: PropertyKind::Object("Transformer"),
+        default: "",
+        help: "Name of Transformer or AutoTrans element to which the RegControl is connected. Do not specify the full object name; \"Transformer\" or \"AutoTrans\" is assumed for the object class.  Example:Transformer=Xfmr1",
+    },
+    PropertyDef {
+        name: "winding",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the winding of the transformer element that the RegControl is monitoring. 1 or 2, typically.  Side Effect: Sets TAPWINDING property to the same winding.",
+    },
+    PropertyDef {
+        name: "vreg",
+        kind: PropertyKind::Double,
+        default: "120",
+        help: "Voltage regulator setting, in VOLTS, for the winding being controlled.  Multiplying this value times the ptratio should yield the voltage across the WINDING of the controlled transformer. Default is 120.0",
+    },
+    PropertyDef {
+        name: "band",
+        kind: PropertyKind::Double,
+        default: "3",
+        help: "Bandwidth in VOLTS for the controlled bus (see help for ptratio property).  Default is 3.0",
+    },
+    PropertyDef {
+        name: "ptratio",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Ratio of the PT that converts the controlled winding voltage to the regulator control voltage. Default is 60.  If the winding is Wye, the line-to-neutral voltage is used.  Else, the line-to-line voltage is used. SIDE EFFECT: Also sets RemotePTRatio property.",
+    },
+    PropertyDef {
+        name: "ctprim",
+        kind: PropertyKind::Double,
+        default: "300",
+        help: "Rating, in Amperes, of the primary CT rating for which the line amps convert to control rated amps.The typical default secondary ampere rating is 0.2 Amps (check with manufacturer specs). Current at which the LDC voltages match the R and X settings.",
+    },
+    PropertyDef {
+        name: "r",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "R setting on the line drop compensator in the regulator, expressed in VOLTS.",
+    },
+    PropertyDef {
+        name: "x",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "X setting on the line drop compensator in the regulator, expressed in VOLTS.",
+    },
+    PropertyDef {
+        name: "bus",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Name of a bus (busname.nodename) in the system to use as the controlled bus instead of the bus to which the transformer winding is connected or the R and X line drop compensator settings.  Do not specify this value if you wish to use the line drop compensator settings.  Default is null string. Assumes the base voltage for this bus is the same as the transformer winding base specified. Be careful to properly specify this value.",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Time delay, in seconds, from when the voltage goes out of band to when the tap changing begins. This is used to determine which regulator control will act first. Default is 15.  You may specify any floating point number to achieve a model of whatever condition is necessary.",
+    },
+    PropertyDef {
+        name: "reversible",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes |No*} Indicates whether or not the regulator can be switched to regulate in the reverse direction. Default is No.Typically applies only to line regulators and not to LTC on a substation transformer.",
+    },
+    PropertyDef {
+        name: "revvreg",
+        kind: PropertyKind::Double,
+        default: "120",
+        help: "Voltage setting in volts for operation in the reverse direction.",
+    },
+    PropertyDef {
+        name: "revband",
+        kind: PropertyKind::Double,
+        default: "3",
+        help: "Bandwidth for operating in the reverse direction.",
+    },
+    PropertyDef {
+        name: "revr",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "R line drop compensator setting for reverse direction.",
+    },
+    PropertyDef {
+        name: "revx",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "X line drop compensator setting for reverse direction.",
+    },
+    PropertyDef {
+        name: "tapdelay",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Delay in sec between tap changes. Default is 2. This is how long it takes between changes after the first change.",
+    },
+    PropertyDef {
+        name: "maxtapchange",
+        kind: PropertyKind::Integer,
+        default: "16",
+        help: "Maximum allowable tap change per control iteration in STATIC control mode.  Default is 16. Set this to 1 to better approximate actual control action. Set this to 0 to fix the tap in the current position.",
+    },
+    PropertyDef {
+        name: "inversetime",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No* } Default is no.  The time delay is adjusted inversely proportional to the amount the voltage is outside the band down to 10%.",
+    },
+    PropertyDef {
+        name: "tapwinding",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Winding containing the actual taps, if different than the WINDING property. Defaults to the same winding as specified by the WINDING property.",
+    },
+    PropertyDef {
+        name: "vlimit",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Voltage Limit for bus to which regulated winding is connected (e.g. first customer). Default is 0.0. Set to a value greater then zero to activate this function.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 22] = concat_properties(&REG_CONTROL_PROPERTIES, &CKT_PROPERTIES);
+
+// The one action the control queues: move the taps by pending_taps
+const ACTION_TAP: i32 = 1;
+
+#[derive(Debug)]
+pub struct RegControlClass;
+
+#[derive(Debug, Clone)]
+pub struct RegControl {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    transformer: String,
+    // From 1, as given
+    winding: usize,
+    tap_winding: usize,
+    vreg: f64,
+    band: f64,
+    pt_ratio: f64,
+    ct_prim: f64,
+    r: f64,
+    x: f64,
+    bus: String,
+    delay: f64,
+    reversible: bool,
+    rev_vreg: f64,
+    rev_band: f64,
+    rev_r: f64,
+    rev_x: f64,
+    tap_delay: f64,
+    max_tap_change: usize,
+    inverse_time: bool,
+    vlimit: f64,
+    // Steps the queued action will move, positive raising the voltage
+    pending_taps: i32,
+    pending: Option<usize>,
+    // A change has been made since the voltage was last in band, so the
+    // next waits only tapdelay
+    tapped: bool,
+}
+
+impl RegControl {
+    pub fn new(name: &str) -> Self {
+        RegControl {
+            base: ObjectBase::new("RegControl", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            transformer: String::new(),
+            winding: 1,
+            tap_winding: 1,
+            vreg: 120.0,
+            band: 3.0,
+            pt_ratio: 60.0,
+            ct_prim: 300.0,
+            r: 0.0,
+            x: 0.0,
+            bus: String::new(),
+            delay: 15.0,
+            reversible: false,
+            rev_vreg: 120.0,
+            rev_band: 3.0,
+            rev_r: 0.0,
+            rev_x: 0.0,
+            tap_delay: 2.0,
+            max_tap_change: 16,
+            inverse_time: false,
+            vlimit: 0.0,
+            pending_taps: 0,
+            pending: None,
+            tapped: false,
+        }
+    }
+
+    pub fn get_transformer(&self) -> &str {
+        &self.transformer
+    }
+
+    pub fn get_winding(&self) -> usize {
+        self.winding
+    }
+
+    pub fn get_tap_winding(&self) -> usize {
+        self.tap_winding
+    }
+
+    pub fn get_vreg(&self) -> f64 {
+        self.vreg
+    }
+
+    pub fn get_band(&self) -> f64 {
+        self.band
+    }
+
+    pub fn get_pt_ratio(&self) -> f64 {
+        self.pt_ratio
+    }
+
+    // Voltage the regulator sees, on the 120 V base of vreg, and whether the
+    // power is flowing backward through a reversible regulator
+    pub fn control_voltage(
+        &self,
+        transformer: &Transformer,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+    ) -> (f64, bool) {
+        let nconds = transformer.nconds();
+        let first = (self.winding - 1) * nconds;
+        let v = transformer.terminal_voltages(voltages);
+        let reversed = self.reversible && transformer.terminal_powers(voltages)[0].re < 0.0;
+        if !self.bus.is_empty()
+            && let Ok((name, nodes)) = parse_bus_spec(&self.bus, 1)
+            && let Some(node_ref) = circuit.bus_list().node_ref(&name, nodes[0])
+        {
+            let vbus = voltages.get(node_ref).copied().unwrap_or_default();
+            return (vbus.norm() / self.pt_ratio, reversed);
+        }
+        // phase 1 to neutral of a wye winding, to phase 2 of a delta
+        let neutral = if nconds > transformer.nphases() {
+            v[first + transformer.nphases()]
+        } else if transformer.nphases() > 1 {
+            v[first + 1]
+        } else {
+            Complex64::new(0.0, 0.0)
+        };
+        let vwinding = (v[first] - neutral) / self.pt_ratio;
+        let (r, x) = match reversed {
+            true => (self.rev_r, self.rev_x),
+            false => (self.r, self.x),
+        };
+        if r == 0.0 && x == 0.0 {
+            return (vwinding.norm(), reversed);
+        }
+        // the current out of the winding toward the load, on the CT rating
+        let current = -transformer.get_currents(voltages)[first] / self.ct_prim;
+        ((vwinding - current * Complex64::new(r, x)).norm(), reversed)
+    }
+
+    // Per unit tap of one step of the tap winding
+    fn tap_step(transformer: &Transformer, winding: usize) -> f64 {
+        let (min_tap, max_tap, num_taps) = transformer.tap_range(winding);
+        (max_tap - min_tap) / num_taps as f64
+    }
+
+    // Steps that bring the voltage back to vreg, positive raising it; none
+    // within the band, and down only once past vlimit
+    fn taps_needed(
+        &self,
+        transformer: &Transformer,
+        vcontrol: f64,
+        vlocal: f64,
+        reversed: bool,
+    ) -> i32 {
+        let (vreg, band) = match reversed {
+            true => (self.rev_vreg, self.rev_band),
+            false => (self.vreg, self.band),
+        };
+        let step = Self::tap_step(transformer, self.tap_winding - 1);
+        // volts on the PT secondary at 1 per unit of the winding
+        let nphases = transformer.nphases();
+        let kv = transformer.get_kv(self.winding - 1);
+        let vbase = match nphases {
+            1 => kv * 1000.0,
+            _ => kv * 1000.0 / 3.0_f64.sqrt(),
+        } / self.pt_ratio;
+        let limit = self.max_tap_change as i32;
+        if self.vlimit > 0.0 && vlocal > self.vlimit {
+            let over = ((vlocal - self.vlimit) / vbase / step).ceil().max(1.0) as i32;
+            return -over.min(limit);
+        }
+        let vdiff = vreg - vcontrol;
+        if vdiff.abs() <= band / 2.0 {
+            return 0;
+        }
+        let taps = (vdiff / vbase / step).round() as i32;
+        let taps = if taps == 0 {
+            vdiff.signum() as i32
+        } else {
+            taps
+        };
+        let taps = taps.clamp(-limit, limit);
+        // the taps of the other winding move the other way
+        if self.tap_winding != self.winding {
+            -taps
+        } else {
+            taps
+        }
+    }
+
+    // Seconds to wait before the next change
+    fn next_delay(&self, vcontrol: f64, reversed: bool) -> f64 {
+        if self.tapped {
+            return self.tap_delay;
+        }
+        let (vreg, band) = match reversed {
+            true => (self.rev_vreg, self.rev_band),
+            false => (self.vreg, self.band),
+        };
+        if self.inverse_time && band > 0.0 {
+            let outside = ((vcontrol - vreg).abs() - band / 2.0) / (band / 2.0);
+            self.delay / outside.max(0.1)
+        } else {
+            self.delay
+        }
+    }
+
+    fn find_transformer<'a>(&self, circuit: &'a Circuit) -> Option<(ElementId, &'a Transformer)> {
+        let id = circuit.find_element("transformer", &self.transformer)?;
+        let transformer = circuit
+            .element(id)?
+            .as_any()
+            .downcast_ref::<Transformer>()?;
+        Some((id, transformer))
+    }
+}
+
+impl DssObject for RegControl {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = REG_CONTROL_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - REG_CONTROL_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "transformer" => {
+                let name = parser.get_token().to_lowercase();
+                self.control.set_element(&format!("transformer.{}", name));
+                self.transformer = name;
+            }
+            "winding" => {
+                self.winding = parser.make_integer()?.max(1) as usize;
+                self.tap_winding = self.winding;
+                self.control.set_terminal(self.winding);
+            }
+            "vreg" => self.vreg = parser.make_double()?,
+            "band" => self.band = parser.make_double()?,
+            "ptratio" => self.pt_ratio = parser.make_double()?,
+            "ctprim" => self.ct_prim = parser.make_double()?,
+            "r" => self.r = parser.make_double()?,
+            "x" => self.x = parser.make_double()?,
+            "bus" => self.bus = parser.get_token().to_lowercase(),
+            "delay" => self.delay = parser.make_double()?,
+            "reversible" => self.reversible = interpret_yes_no(parser.get_token()),
+            "revvreg" => self.rev_vreg = parser.make_double()?,
+            "revband" => self.rev_band = parser.make_double()?,
+            "revr" => self.rev_r = parser.make_double()?,
+            "revx" => self.rev_x = parser.make_double()?,
+            "tapdelay" => self.tap_delay = parser.make_double()?,
+            "maxtapchange" => self.max_tap_change = parser.make_integer()?.max(0) as usize,
+            "inversetime" => self.inverse_time = interpret_yes_no(parser.get_token()),
+            "tapwinding" => self.tap_winding = parser.make_integer()?.max(1) as usize,
+            "vlimit" => self.vlimit = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.pt_ratio <= 0.0 || self.ct_prim <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs ptratio and ctprim > 0", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for RegControl {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for RegControl {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Queues a tap change when out of band and takes back a queued one once
+    // the voltage is back in band
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let Some((_, transformer)) = self.find_transformer(circuit) else {
+            return;
+        };
+        if self.winding > transformer.nwindings() || self.tap_winding > transformer.nwindings() {
+            return;
+        }
+        let (vcontrol, reversed) = self.control_voltage(transformer, circuit, voltages);
+        let first = (self.winding - 1) * transformer.nconds();
+        let vlocal = transformer.terminal_voltages(voltages)[first].norm() / self.pt_ratio;
+        let taps = self.taps_needed(transformer, vcontrol, vlocal, reversed);
+        if taps == 0 {
+            if let Some(handle) = self.pending.take() {
+                queue.delete(handle);
+            }
+            self.tapped = false;
+            return;
+        }
+        self.pending_taps = taps;
+        if self.pending.is_none() {
+            let delay = self.next_delay(vcontrol, reversed);
+            self.pending = Some(queue.push(delay, ACTION_TAP, 0, id));
+        }
+    }
+
+    // Moves the taps by the steps the last sample called for
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if action.code != ACTION_TAP {
+            return;
+        }
+        self.pending = None;
+        let Some((id, _)) = self.find_transformer(circuit) else {
+            return;
+        };
+        let Some(transformer) = circuit
+            .element_mut(id)
+            .and_then(|element| element.as_any_mut().downcast_mut::<Transformer>())
+        else {
+            return;
+        };
+        let winding = self.tap_winding - 1;
+        let step = Self::tap_step(transformer, winding);
+        let before = transformer.get_tap(winding);
+        transformer.set_tap(winding, before + self.pending_taps as f64 * step);
+        let after = transformer.get_tap(winding);
+        if after != before {
+            self.tapped = true;
+            queue.log(
+                &self.full_name(),
+                &format!(
+                    "Changed {} taps to {:.5}",
+                    ((after - before) / step).round() as i32,
+                    after
+                ),
+            );
+        } else {
+            queue.log(&self.full_name(), "At tap limit");
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.pending = None;
+        self.pending_taps = 0;
+        self.tapped = false;
+    }
+}
+
+impl DssClass for RegControlClass {
+    fn name(&self) -> &'static str {
+        "RegControl"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(RegControl::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::transformer::TransformerClass;
+    use crate::test_util::{edited, placed};
+
+    // A circuit with a 1-phase 7.2 kV regulator, winding 1 on node 1 and
+    // winding 2 on node 2, both grounded
+    fn circuit_with_regulator() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let transformer = edited(
+            Transformer::new("reg1"),
+            &TransformerClass,
+            "phases=1 windings=2 kvs=[7.2 7.2] kvas=[1000 1000] xhl=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(transformer, &[&[1, 0], &[2, 0]]).unwrap()));
+        circuit
+    }
+
+    fn new_control(properties: &str, circuit: &Circuit) -> RegControl {
+        edited(
+            RegControl::new("reg1"),
+            &RegControlClass,
+            properties,
+            circuit,
+        )
+        .unwrap()
+    }
+
+    // Winding 2 at `v2` volts in phase with winding 1 at 7200 V
+    fn voltages(v2: f64) -> Vec<Complex64> {
+        vec![
+            Complex64::new(0.0, 0.0),
+            Complex64::new(7200.0, 0.0),
+            Complex64::new(v2, 0.0),
+        ]
+    }
+
+    fn tap(circuit: &Circuit) -> f64 {
+        circuit
+            .find_object_as::<Transformer>("transformer", "reg1")
+            .unwrap()
+            .get_tap(1)
+    }
+
+    #[test]
+    fn test_tap_changes() {
+        let mut circuit = circuit_with_regulator();
+        let mut control = new_control(
+            "transformer=reg1 winding=2 vreg=122 band=2 ptratio=60 delay=30 tapdelay=5",
+            &circuit,
+        );
+        let mut queue = ControlQueue::new();
+
+        // 118 V is 4 V low: 0.0333 pu, five steps of 0.00625
+        control.sample(99, &circuit, &voltages(118.0 * 60.0), &mut queue);
+        assert_eq!(queue.next_time(), Some((0, 30.0)));
+        assert!(queue.pop_due().is_none());
+        queue.set_time(0, 30.0);
+        let action = queue.pop_due().unwrap();
+        control.do_pending_action(&action, &mut circuit, &[], &mut queue);
+        assert!((tap(&circuit) - (1.0 + 5.0 * 0.00625)).abs() < 1e-12);
+        assert_eq!(queue.events().len(), 1);
+
+        // a further change waits only tapdelay, and is taken back when
+        // the voltage comes into band first
+        control.sample(99, &circuit, &voltages(120.0 * 60.0), &mut queue);
+        assert_eq!(queue.next_time(), Some((0, 35.0)));
+        control.sample(99, &circuit, &voltages(121.5 * 60.0), &mut queue);
+        assert!(queue.is_empty());
+
+        // at most maxtapchange steps, never past maxtap
+        let mut control = new_control(
+            "transformer=reg1 winding=2 vreg=120 band=2 maxtapchange=20 delay=0",
+            &circuit,
+        );
+        control.sample(99, &circuit, &voltages(100.0 * 60.0), &mut queue);
+        let action = queue.pop_due().unwrap();
+        control.do_pending_action(&action, &mut circuit, &[], &mut queue);
+        assert!((tap(&circuit) - 1.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_line_drop_and_inverse_time() {
+        let circuit = circuit_with_regulator();
+        let transformer = circuit
+            .find_object_as::<Transformer>("transformer", "reg1")
+            .unwrap();
+        // R = 2 V at the 300 A CT rating
+        let control = new_control("transformer=reg1 winding=2 r=2 ctprim=300", &circuit);
+        // winding 2 sagging under load, so current flows out of it
+        let v = voltages(7100.0);
+        let current = -transformer.get_currents(&v)[2];
+        assert!(current.re > 0.0);
+        let (vcontrol, reversed) = control.control_voltage(transformer, &circuit, &v);
+        assert!(!reversed);
+        let expected = (Complex64::new(7100.0 / 60.0, 0.0) - current / 300.0 * 2.0).norm();
+        assert!((vcontrol - expected).abs() < 1e-9);
+        assert!(vcontrol < 7100.0 / 60.0);
+
+        // twice the half band outside waits half the delay
+        let control = new_control(
+            "transformer=reg1 winding=2 inversetime=yes delay=30",
+            &circuit,
+        );
+        assert!((control.next_delay(123.0 + 1.5, false) - 15.0).abs() < 1e-9);
+        assert_eq!(control.next_delay(120.0 - 1.5 - 0.01, false), 300.0);
+    }
+}
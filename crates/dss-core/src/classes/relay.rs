@@ -0,0 +1,992 @@
+// Relay (Pascal TRelay): a protective relay watching a terminal of one
+// element and opening a terminal of another, by default the same one. It
+// trips on phase or ground overcurrent against TCC curves, on over- or
+// undervoltage, on reverse power, or on negative sequence current (46) or
+// voltage (47). A trip is queued when the relay picks up and taken back if
+// it drops out first; after a trip the relay recloses after each of its
+// reclose intervals until it has opened shots times and locks out. A relay
+// closed long enough without tripping resets its count of operations.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::tcc_curve::{find_tcc_points, overvoltage_time, tcc_time, undervoltage_time};
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice, read_doubles,
+};
+use crate::registry::ElementId;
+
+const RELAY_PROPERTIES: [PropertyDef; 27] = [
+    PropertyDef {
+        name: "monitoredobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line, transformer, load, or generator, to which the relay's PT and/or CT are connected. This is the \"monitored\" element. There is no default; must be specified.",
+    },
+    PropertyDef {
+        name: "monitoredterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the Relay is connected. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "switchedobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of circuit element switch that the Relay controls. Specify the full object name.Defaults to the same as the Monitored element. This is the \"controlled\" element.",
+    },
+    PropertyDef {
+        name: "switchedterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the controlled element in which the switch is controlled by the Relay. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "type",
+        kind: PropertyKind::Choice(RelayType::NAMES),
+        default: "current",
+        help: "One of a legal relay type:  Current  Voltage  Reversepower  46 (neg seq current)  47 (neg seq voltage) Default is overcurrent relay (Current) Specify the curve and pickup settings appropriate for each type. Voltage relays are based on the maximum and minimum voltage of all phases.",
+    },
+    PropertyDef {
+        name: "phasecurve",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "Name of the TCC Curve object that determines the phase trip.  Must have been previously defined as a TCC_Curve object. Default is none (ignored). For overcurrent relay, multiplying the current values in the curve by the \"phasetrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "groundcurve",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "Name of the TCC Curve object that determines the ground trip.  Must have been previously defined as a TCC_Curve object. Default is none (ignored). For overcurrent relay, multiplying the current values in the curve by the \"groundtrip\" value gives the actual current.",
+    },
+    PropertyDef {
+        name: "phasetrip",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Multiplier or actual phase amps for the phase TCC curve.  Defaults to 1.0.",
+    },
+    PropertyDef {
+        name: "groundtrip",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Multiplier or actual ground amps (3I0) for the ground TCC curve.  Defaults to 1.0.",
+    },
+    PropertyDef {
+        name: "tdphase",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Phase trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "tdground",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time dial for Ground trip curve. Multiplier on time axis of specified curve. Default=1.0.",
+    },
+    PropertyDef {
+        name: "phaseinst",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Actual  amps (Mag) or kW for instantaneous phase trip which is assumed to happen in 0.01 sec + Delay Time. Default is 0.0, which signifies no inst trip. Use this value for specifying the Reverse Power threshold (kW) for reverse power relays.",
+    },
+    PropertyDef {
+        name: "groundinst",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Actual  amps (Mag) for instantaneous ground trip which is assumed to happen in 0.01 sec + Delay Time.Default is 0.0, which signifies no inst trip.",
+    },
+    PropertyDef {
+        name: "reset",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Reset time in sec for relay.  Default is 15. If this much time passes between the last pickup event, and the relay has not locked out, the operation counter resets.",
+    },
+    PropertyDef {
+        name: "shots",
+        kind: PropertyKind::Integer,
+        default: "4",
+        help: "Number of shots to lockout.  Default is 4. This is one more than the number of reclose intervals.",
+    },
+    PropertyDef {
+        name: "recloseintervals",
+        kind: PropertyKind::Doubles,
+        default: "(0.5, 2.0, 2.0)",
+        help: "Array of reclose intervals. If none, specify \"NONE\". Default for overcurrent relay is (0.5, 2.0, 2.0) seconds. Default for a voltage relay is (5.0). In a voltage relay, this is  seconds after restoration of voltage that the reclose occurs. Reverse power relay is one shot to lockout, so this is ignored.  A locked out relay must be closed manually (set action=close).",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Trip time delay (sec) for DEFINITE TIME relays. Default is 0.0 for current and voltage relays.  If >0 then this value is used instead of curves.  Used by 47 relays and reverse power relays.",
+    },
+    PropertyDef {
+        name: "overvoltcurve",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "TCC Curve object to use for overvoltage relay.  Curve is assumed to be defined with per unit voltage values. Voltage base should be defined for the relay. Default is none (ignored).",
+    },
+    PropertyDef {
+        name: "undervoltcurve",
+        kind: PropertyKind::Object("TCC_Curve"),
+        default: "",
+        help: "TCC Curve object to use for undervoltage relay.  Curve is assumed to be defined with per unit voltage values. Voltage base should be defined for the relay. Default is none (ignored).",
+    },
+    PropertyDef {
+        name: "kvbase",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Voltage base (kV) for the relay. Specify line-line for 3 phase devices); line-neutral for 1-phase devices.  Relay assumes the number of phases of the monitored element.  Default is 0.0, which results in assuming the voltage values in the \"TCC\" curve are specified in actual line-to-neutral volts.",
+    },
+    PropertyDef {
+        name: "47%pickup",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Percent voltage pickup for 47 relay (Neg seq voltage). Default is 2. Specify also base voltage (kvbase) and delay time value.",
+    },
+    PropertyDef {
+        name: "46baseamps",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Base current, Amps, for 46 relay (neg seq current).  Used for establishing pickup and per unit I-squared-t.",
+    },
+    PropertyDef {
+        name: "46%pickup",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Percent pickup current for 46 relay (neg seq current).  Default is 20.0.   When current exceeds this value * BaseAmps, I-squared-t calc starts.",
+    },
+    PropertyDef {
+        name: "46isqt",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Negative Sequence I-squared-t trip value for 46 relay (neg seq current).  Default is 1 (test setting). Trip time = 46isqt / (I2 in per unit of 46baseamps)^2.",
+    },
+    PropertyDef {
+        name: "breakertime",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Fixed delay time (sec) added to relay time. Default is 0.0. Designed to represent breaker time or some other delay after a trip decision is made.Use Delay property for setting a fixed trip time delay.Added to trip time of current and voltage relays. Could use in combination with inst trip value to obtain a definite time overcurrent relay.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(ACTION_NAMES),
+        default: "",
+        help: "{Trip/Open | Close}  Action that overrides the relay control. Simulates manual control on breaker. \"Trip\" or \"Open\" causes the controlled element to open and lock out. \"Close\" causes the controlled element to close and the relay to reset to its first operation.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "yes",
+        help: "{Yes/True* | No/False} Default is Yes for Relay. Write trips, reclose and reset events to EventLog.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 29] = concat_properties(&RELAY_PROPERTIES, &CKT_PROPERTIES);
+
+// Values of the action property
+const ACTION_NAMES: &[&str] = &["trip", "open", "close"];
+
+// Codes of the queued actions (Pascal CTRL_OPEN, CTRL_CLOSE, CTRL_RESET)
+const ACTION_OPEN: i32 = 1;
+const ACTION_CLOSE: i32 = 2;
+const ACTION_RESET: i32 = 3;
+
+// Proxy of an action commanded by the action property rather than picked up
+const COMMANDED: usize = 1;
+
+// Time of an instantaneous trip, seconds
+const INST_TIME: f64 = 0.01;
+
+// What the relay trips on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayType {
+    Current,
+    Voltage,
+    ReversePower,
+    // 46
+    NegSeqCurrent,
+    // 47
+    NegSeqVoltage,
+}
+
+impl RelayType {
+    pub const NAMES: &'static [&'static str] = &["current", "voltage", "reversepower", "46", "47"];
+
+    // Type of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "voltage" => RelayType::Voltage,
+            "reversepower" => RelayType::ReversePower,
+            "46" => RelayType::NegSeqCurrent,
+            "47" => RelayType::NegSeqVoltage,
+            _ => RelayType::Current,
+        }
+    }
+}
+
+// A curve by the name it is given and the points it has been resolved to
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Curve {
+    pub(crate) name: String,
+    pub(crate) points: Vec<(f64, f64)>,
+}
+
+impl Curve {
+    pub(crate) fn named(name: &str) -> Self {
+        Curve {
+            name: name.to_string(),
+            points: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        !self.points.is_empty()
+    }
+
+    // Looks up the points of the named TCC curve for the device `owner`
+    pub(crate) fn resolve(&mut self, circuit: &Circuit, owner: &str) -> DssResult<()> {
+        self.points = match self.name.as_str() {
+            "" => Vec::new(),
+            name => find_tcc_points(circuit, name, owner)?,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct RelayClass;
+
+#[derive(Debug, Clone)]
+pub struct Relay {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    // Full name, lower case; empty for the monitored element
+    switched_obj: String,
+    // From 1
+    switched_term: usize,
+    relay_type: RelayType,
+    phase_curve: Curve,
+    ground_curve: Curve,
+    phase_trip: f64,
+    ground_trip: f64,
+    td_phase: f64,
+    td_ground: f64,
+    phase_inst: f64,
+    ground_inst: f64,
+    reset_time: f64,
+    shots: usize,
+    reclose_intervals: Vec<f64>,
+    delay: f64,
+    overvolt_curve: Curve,
+    undervolt_curve: Curve,
+    kv_base: f64,
+    pct_pickup47: f64,
+    base_amps46: f64,
+    pct_pickup46: f64,
+    isqt46: f64,
+    breaker_time: f64,
+    event_log: bool,
+    // Action given by the action property, carried out at the next sample
+    command: Option<i32>,
+    // The queued action and its code
+    pending: Option<(usize, i32)>,
+    // Trips since the relay last reset
+    operations: usize,
+    locked_out: bool,
+}
+
+impl Relay {
+    pub fn new(name: &str) -> Self {
+        Relay {
+            base: ObjectBase::new("Relay", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            switched_obj: String::new(),
+            switched_term: 1,
+            relay_type: RelayType::Current,
+            phase_curve: Curve::default(),
+            ground_curve: Curve::default(),
+            phase_trip: 1.0,
+            ground_trip: 1.0,
+            td_phase: 1.0,
+            td_ground: 1.0,
+            phase_inst: 0.0,
+            ground_inst: 0.0,
+            reset_time: 15.0,
+            shots: 4,
+            reclose_intervals: vec![0.5, 2.0, 2.0],
+            delay: 0.0,
+            overvolt_curve: Curve::default(),
+            undervolt_curve: Curve::default(),
+            kv_base: 0.0,
+            pct_pickup47: 2.0,
+            base_amps46: 100.0,
+            pct_pickup46: 20.0,
+            isqt46: 1.0,
+            breaker_time: 0.0,
+            event_log: true,
+            command: None,
+            pending: None,
+            operations: 0,
+            locked_out: false,
+        }
+    }
+
+    pub fn get_type(&self) -> RelayType {
+        self.relay_type
+    }
+
+    // Full name of the element the relay opens
+    pub fn get_switched_obj(&self) -> &str {
+        if self.switched_obj.is_empty() {
+            self.control.get_element()
+        } else {
+            &self.switched_obj
+        }
+    }
+
+    pub fn is_locked_out(&self) -> bool {
+        self.locked_out
+    }
+
+    pub fn get_operations(&self) -> usize {
+        self.operations
+    }
+
+    pub fn get_phase_curve(&self) -> &str {
+        &self.phase_curve.name
+    }
+
+    pub fn get_ground_curve(&self) -> &str {
+        &self.ground_curve.name
+    }
+
+    // Points (multiple of pickup, seconds) of the phase curve, as resolved
+    // from the named TCC curve
+    pub fn set_phase_curve(&mut self, points: Vec<(f64, f64)>) {
+        self.phase_curve.points = points;
+    }
+
+    pub fn set_ground_curve(&mut self, points: Vec<(f64, f64)>) {
+        self.ground_curve.points = points;
+    }
+
+    // Points (per unit voltage, seconds) of the voltage curves
+    pub fn set_overvolt_curve(&mut self, points: Vec<(f64, f64)>) {
+        self.overvolt_curve.points = points;
+    }
+
+    pub fn set_undervolt_curve(&mut self, points: Vec<(f64, f64)>) {
+        self.undervolt_curve.points = points;
+    }
+
+    // Seconds until the relay trips on what it sees now, if it has picked up
+    fn trip_time(&self, element: &dyn CktElement, voltages: &[Complex64]) -> Option<f64> {
+        let first = (self.control.get_terminal() - 1) * element.nconds();
+        let nphases = element.nphases();
+        let time = match self.relay_type {
+            RelayType::Current => {
+                let currents = element.get_currents(voltages);
+                let phases: Vec<Complex64> = (0..nphases)
+                    .filter_map(|phase| currents.get(first + phase).copied())
+                    .collect();
+                let phase_max = phases.iter().map(|i| i.norm()).fold(0.0, f64::max);
+                let residual = phases.iter().sum::<Complex64>().norm();
+                let phase = self.overcurrent_time(
+                    phase_max,
+                    self.phase_inst,
+                    &self.phase_curve,
+                    self.phase_trip,
+                    self.td_phase,
+                );
+                let ground = self.overcurrent_time(
+                    residual,
+                    self.ground_inst,
+                    &self.ground_curve,
+                    self.ground_trip,
+                    self.td_ground,
+                );
+                match (phase, ground) {
+                    (Some(phase), Some(ground)) => Some(phase.min(ground)),
+                    (phase, ground) => phase.or(ground),
+                }
+            }
+            RelayType::Voltage => {
+                let vbase = self.phase_vbase(nphases);
+                let v = element.terminal_voltages(voltages);
+                let vpu: Vec<f64> = (0..nphases)
+                    .filter_map(|phase| v.get(first + phase))
+                    .map(|v| v.norm() / vbase)
+                    .collect();
+                let vmax = vpu.iter().copied().fold(0.0, f64::max);
+                let vmin = vpu.iter().copied().reduce(f64::min).unwrap_or(0.0);
+                let over = self
+                    .overvolt_curve
+                    .is_set()
+                    .then(|| overvoltage_time(&self.overvolt_curve.points, vmax))
+                    .flatten();
+                let under = self
+                    .undervolt_curve
+                    .is_set()
+                    .then(|| undervoltage_time(&self.undervolt_curve.points, vmin))
+                    .flatten();
+                match (over, under) {
+                    (Some(over), Some(under)) => Some(over.min(under)),
+                    (over, under) => over.or(under),
+                }
+                .map(|time| time + self.delay)
+            }
+            RelayType::ReversePower => {
+                let power = element
+                    .terminal_powers(voltages)
+                    .get(self.control.get_terminal() - 1)
+                    .copied()
+                    .unwrap_or_default();
+                (power.re < 0.0 && -power.re > self.phase_inst * 1000.0).then_some(self.delay)
+            }
+            RelayType::NegSeqCurrent => {
+                let currents = element.get_currents(voltages);
+                let i2 = negative_sequence(currents.get(first..first + nphases)?)?;
+                let i2pu = i2 / self.base_amps46;
+                (i2pu * 100.0 >= self.pct_pickup46 && i2pu > 0.0)
+                    .then(|| self.isqt46 / (i2pu * i2pu))
+            }
+            RelayType::NegSeqVoltage => {
+                let vbase = self.phase_vbase(nphases);
+                let v = element.terminal_voltages(voltages);
+                let v2 = negative_sequence(v.get(first..first + nphases)?)?;
+                (v2 / vbase * 100.0 >= self.pct_pickup47).then_some(self.delay)
+            }
+        };
+        time.map(|time| time + self.breaker_time)
+    }
+
+    // Trip time of a phase or ground element: instantaneous above the inst
+    // setting, else from the curve at the multiple of the pickup
+    fn overcurrent_time(
+        &self,
+        amps: f64,
+        inst: f64,
+        curve: &Curve,
+        pickup: f64,
+        time_dial: f64,
+    ) -> Option<f64> {
+        if inst > 0.0 && amps >= inst {
+            return Some(INST_TIME + self.delay);
+        }
+        if !curve.is_set() || pickup <= 0.0 {
+            return None;
+        }
+        let time = tcc_time(&curve.points, amps / pickup)?;
+        Some(if self.delay > 0.0 {
+            self.delay
+        } else {
+            time * time_dial
+        })
+    }
+
+    // Line-to-neutral base volts; without kvbase, curves are in volts
+    fn phase_vbase(&self, nphases: usize) -> f64 {
+        let vbase = self.kv_base * 1000.0;
+        if vbase <= 0.0 {
+            1.0
+        } else if nphases > 1 {
+            vbase / 3.0_f64.sqrt()
+        } else {
+            vbase
+        }
+    }
+
+    // Seconds before the relay recloses after its latest trip
+    fn reclose_interval(&self) -> f64 {
+        self.reclose_intervals
+            .get(self.operations.saturating_sub(1))
+            .or(self.reclose_intervals.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn switched_element(&self, circuit: &Circuit) -> Option<ElementId> {
+        find_by_full_name(circuit, self.get_switched_obj())
+    }
+
+    fn log(&self, queue: &mut ControlQueue, action: &str) {
+        if self.event_log {
+            queue.log(&self.full_name(), action);
+        }
+    }
+}
+
+// Magnitude of the negative sequence of three phase values
+fn negative_sequence(phases: &[Complex64]) -> Option<f64> {
+    let [a, b, c] = phases else {
+        return None;
+    };
+    let op = Complex64::from_polar(1.0, 2.0 * PI / 3.0);
+    Some((a + op * op * b + op * c).norm() / 3.0)
+}
+
+impl DssObject for Relay {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = RELAY_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - RELAY_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "monitoredobj" => self.control.set_element(parser.get_token()),
+            "monitoredterm" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "switchedobj" => self.switched_obj = parser.get_token().to_lowercase(),
+            "switchedterm" => self.switched_term = parser.make_integer()?.max(1) as usize,
+            "type" => {
+                let name = read_choice(parser, RelayType::NAMES, "type")?;
+                self.relay_type = RelayType::from_name(name);
+                if self.relay_type == RelayType::Voltage {
+                    self.reclose_intervals = vec![5.0];
+                }
+            }
+            "phasecurve" => self.phase_curve.name = parser.get_token().to_lowercase(),
+            "groundcurve" => self.ground_curve.name = parser.get_token().to_lowercase(),
+            "phasetrip" => self.phase_trip = parser.make_double()?,
+            "groundtrip" => self.ground_trip = parser.make_double()?,
+            "tdphase" => self.td_phase = parser.make_double()?,
+            "tdground" => self.td_ground = parser.make_double()?,
+            "phaseinst" => self.phase_inst = parser.make_double()?,
+            "groundinst" => self.ground_inst = parser.make_double()?,
+            "reset" => self.reset_time = parser.make_double()?,
+            "shots" => self.shots = parser.make_integer()?.max(1) as usize,
+            "recloseintervals" => {
+                self.reclose_intervals = if parser.get_token().eq_ignore_ascii_case("none") {
+                    Vec::new()
+                } else {
+                    read_doubles(parser)?
+                }
+            }
+            "delay" => self.delay = parser.make_double()?,
+            "overvoltcurve" => self.overvolt_curve.name = parser.get_token().to_lowercase(),
+            "undervoltcurve" => self.undervolt_curve.name = parser.get_token().to_lowercase(),
+            "kvbase" => self.kv_base = parser.make_double()?,
+            "47%pickup" => self.pct_pickup47 = parser.make_double()?,
+            "46baseamps" => self.base_amps46 = parser.make_double()?,
+            "46%pickup" => self.pct_pickup46 = parser.make_double()?,
+            "46isqt" => self.isqt46 = parser.make_double()?,
+            "breakertime" => self.breaker_time = parser.make_double()?,
+            "action" => {
+                let name = read_choice(parser, ACTION_NAMES, "action")?;
+                self.command = Some(match name {
+                    "close" => ACTION_CLOSE,
+                    _ => ACTION_OPEN,
+                });
+            }
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The curves must be TCC curves already defined
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        let owner = self.full_name();
+        let curve = match RELAY_PROPERTIES.get(index).map(|property| property.name) {
+            Some("phasecurve") => &mut self.phase_curve,
+            Some("groundcurve") => &mut self.ground_curve,
+            Some("overvoltcurve") => &mut self.overvolt_curve,
+            Some("undervoltcurve") => &mut self.undervolt_curve,
+            _ => return Ok(()),
+        };
+        curve.resolve(circuit, &owner)
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.relay_type == RelayType::NegSeqCurrent && self.base_amps46 <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs 46baseamps > 0", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Relay {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for Relay {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Arms a trip when the relay picks up, takes it back when it drops out,
+    // and once the switch has held closed arms the reset of its operations
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if let Some(code) = self.command.take() {
+            if let Some((handle, _)) = self.pending {
+                queue.delete(handle);
+            }
+            self.pending = Some((queue.push(0.0, code, COMMANDED, id), code));
+            return;
+        }
+        if self.locked_out {
+            return;
+        }
+        let Some(switched) = self
+            .switched_element(circuit)
+            .and_then(|switched| circuit.element(switched)?.as_ckt_element())
+        else {
+            return;
+        };
+        if !switched
+            .ckt_base()
+            .is_terminal_closed(self.switched_term - 1)
+        {
+            return;
+        }
+        let Some(element) = self
+            .control
+            .find_element(circuit)
+            .and_then(|element| circuit.element(element)?.as_ckt_element())
+        else {
+            return;
+        };
+        match (self.trip_time(element, voltages), self.pending) {
+            (Some(_), Some((_, ACTION_OPEN))) => {}
+            (Some(time), pending) => {
+                if let Some((handle, _)) = pending {
+                    queue.delete(handle);
+                }
+                self.pending = Some((queue.push(time, ACTION_OPEN, 0, id), ACTION_OPEN));
+            }
+            (None, Some((handle, ACTION_OPEN))) => {
+                queue.delete(handle);
+                self.pending = None;
+                self.log(queue, "Reset");
+            }
+            (None, None) if self.operations > 0 => {
+                let handle = queue.push(self.reset_time, ACTION_RESET, 0, id);
+                self.pending = Some((handle, ACTION_RESET));
+            }
+            (None, _) => {}
+        }
+    }
+
+    // Opens or closes the switched terminal, or resets the operations
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        self.pending = None;
+        if action.code == ACTION_RESET {
+            self.operations = 0;
+            return;
+        }
+        let Some(switched) = self.switched_element(circuit) else {
+            return;
+        };
+        let terminal = self.switched_term - 1;
+        let Some(element) = circuit
+            .element_mut(switched)
+            .and_then(|element| element.as_ckt_element_mut())
+        else {
+            return;
+        };
+        match action.code {
+            ACTION_OPEN => {
+                element.ckt_base_mut().set_closed(terminal, None, false);
+                if action.proxy == COMMANDED {
+                    self.locked_out = true;
+                    self.log(queue, "Opened by command, locked out");
+                    return;
+                }
+                self.operations += 1;
+                if self.operations >= self.shots || self.reclose_intervals.is_empty() {
+                    self.locked_out = true;
+                    self.log(queue, "Opened, locked out");
+                } else {
+                    let interval = self.reclose_interval();
+                    let handle = queue.push(interval, ACTION_CLOSE, 0, action.owner);
+                    self.pending = Some((handle, ACTION_CLOSE));
+                    self.log(queue, "Opened");
+                }
+            }
+            ACTION_CLOSE => {
+                element.ckt_base_mut().set_closed(terminal, None, true);
+                if action.proxy == COMMANDED {
+                    self.locked_out = false;
+                    self.operations = 0;
+                    self.log(queue, "Closed by command, reset");
+                } else {
+                    self.log(queue, "Closed");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self, circuit: &mut Circuit) {
+        if let Some(element) = self
+            .switched_element(circuit)
+            .and_then(|switched| circuit.element_mut(switched))
+            .and_then(|element| element.as_ckt_element_mut())
+        {
+            element
+                .ckt_base_mut()
+                .set_closed(self.switched_term - 1, None, true);
+        }
+        self.pending = None;
+        self.operations = 0;
+        self.locked_out = false;
+    }
+}
+
+impl DssClass for RelayClass {
+    fn name(&self) -> &'static str {
+        "Relay"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Relay::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::line::{Line, LineClass};
+    use crate::test_util::{balanced, edit, edited, placed, run_to};
+
+    // A 3-phase line from nodes 1-3 to nodes 4-6
+    fn circuit_with_line() -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("l1"),
+            &LineClass,
+            "r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(line, &[&[1, 2, 3], &[4, 5, 6]]).unwrap()));
+        circuit
+    }
+
+    fn new_relay(properties: &str, circuit: &Circuit) -> Relay {
+        edited(
+            Relay::new("r1"),
+            &RelayClass,
+            &format!("monitoredobj=line.l1 {}", properties),
+            circuit,
+        )
+        .unwrap()
+    }
+
+    // Sending end at `v` volts per phase, receiving end `drop` volts lower
+    fn voltages(v: [Complex64; 3], drop: f64) -> Vec<Complex64> {
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        voltages.extend(v);
+        voltages.extend(v.iter().map(|v| v * (1.0 - drop / v.norm())));
+        voltages
+    }
+
+    fn line_closed(circuit: &Circuit) -> bool {
+        circuit
+            .find_object_as::<Line>("line", "l1")
+            .unwrap()
+            .ckt_base()
+            .is_terminal_closed(0)
+    }
+
+    // Moves the queue on to `sec`, carries out what has come due and
+    // rebuilds the line's Yprim as the next solution would
+    fn operate(relay: &mut Relay, circuit: &mut Circuit, queue: &mut ControlQueue, sec: f64) {
+        run_to(relay, circuit, queue, sec);
+        let id = circuit.find_element("line", "l1").unwrap();
+        let line = circuit
+            .element_mut(id)
+            .unwrap()
+            .as_ckt_element_mut()
+            .unwrap();
+        line.calc_yprim(60.0).unwrap();
+    }
+
+    #[test]
+    fn test_overcurrent_reclose_and_lockout() {
+        let mut circuit = circuit_with_line();
+        let mut relay = new_relay(
+            "phasetrip=100 tdphase=2 shots=2 recloseintervals=[1]",
+            &circuit,
+        );
+        relay.set_phase_curve(vec![(1.0, 10.0), (10.0, 1.0), (20.0, 0.5)]);
+        // 1+1j ohm, 200 * sqrt(2) volts: 200 A, twice the pickup
+        let fault = voltages(balanced(7200.0), 200.0 * 2.0_f64.sqrt());
+        let mut queue = ControlQueue::new();
+        relay.sample(99, &circuit, &fault, &mut queue);
+        let expected = 2.0 * tcc_time(&relay.phase_curve.points, 2.0).unwrap();
+        assert!((expected - 2.0 * 10.0_f64.powf(1.0 - 2.0_f64.log10())).abs() < 1e-9);
+        let (_, due) = queue.next_time().unwrap();
+        assert!((due - expected).abs() < 1e-9);
+
+        // the fault clears before the trip: the relay resets
+        relay.sample(99, &circuit, &voltages(balanced(7200.0), 1.0), &mut queue);
+        assert!(queue.is_empty());
+
+        relay.sample(99, &circuit, &fault, &mut queue);
+        operate(&mut relay, &mut circuit, &mut queue, expected);
+        assert!(!line_closed(&circuit));
+        operate(&mut relay, &mut circuit, &mut queue, expected + 1.0);
+        assert!(line_closed(&circuit));
+        assert_eq!(relay.get_operations(), 1);
+
+        // the second trip is the last shot
+        relay.sample(99, &circuit, &fault, &mut queue);
+        operate(&mut relay, &mut circuit, &mut queue, 2.0 * expected + 1.0);
+        assert!(!line_closed(&circuit));
+        assert!(relay.is_locked_out());
+        assert!(queue.is_empty());
+        let actions: Vec<&str> = queue
+            .events()
+            .events()
+            .iter()
+            .map(|event| event.action.as_str())
+            .collect();
+        assert_eq!(actions, ["Reset", "Opened", "Closed", "Opened, locked out"]);
+
+        // closing by command resets the relay
+        edit(&mut relay, &RelayClass, "action=close", &circuit).unwrap();
+        relay.sample(99, &circuit, &fault, &mut queue);
+        operate(&mut relay, &mut circuit, &mut queue, 2.0 * expected + 1.0);
+        assert!(line_closed(&circuit));
+        assert!(!relay.is_locked_out());
+        assert_eq!(relay.get_operations(), 0);
+    }
+
+    #[test]
+    fn test_trip_times() {
+        let circuit = circuit_with_line();
+        let line = circuit
+            .element(circuit.find_element("line", "l1").unwrap())
+            .unwrap()
+            .as_ckt_element()
+            .unwrap();
+        let normal = voltages(balanced(7200.0), 1.0);
+
+        // instantaneous phase trip with breaker time
+        let relay = new_relay("phaseinst=150 breakertime=0.05", &circuit);
+        let fault = voltages(balanced(7200.0), 200.0 * 2.0_f64.sqrt());
+        assert!((relay.trip_time(line, &fault).unwrap() - 0.06).abs() < 1e-9);
+        assert_eq!(relay.trip_time(line, &normal), None);
+
+        // 12.47 kV base: trips at 1.1 pu in 2 s, not at 1.0 pu
+        let mut relay = new_relay("type=voltage kvbase=12.47", &circuit);
+        relay.set_overvolt_curve(vec![(1.05, 10.0), (1.15, 0.0)]);
+        relay.set_undervolt_curve(vec![(0.5, 0.1), (0.9, 5.0)]);
+        let vln = 12470.0 / 3.0_f64.sqrt();
+        let high = voltages(balanced(1.1 * vln), 1.0);
+        assert!((relay.trip_time(line, &high).unwrap() - 5.0).abs() < 1e-6);
+        assert_eq!(relay.trip_time(line, &voltages(balanced(vln), 1.0)), None);
+        assert!(
+            relay
+                .trip_time(line, &voltages(balanced(0.5 * vln), 1.0))
+                .unwrap()
+                < 0.2
+        );
+
+        // power flowing back into terminal 1
+        let relay = new_relay("type=reversepower phaseinst=100 delay=0.5", &circuit);
+        let backfeed = voltages(balanced(7200.0), -20.0);
+        assert_eq!(relay.trip_time(line, &backfeed), Some(0.5));
+        assert_eq!(relay.trip_time(line, &normal), None);
+
+        // one phase carrying all the current is a third negative sequence
+        let relay = new_relay("type=46 46baseamps=100 46%pickup=20 46isqt=9", &circuit);
+        let mut unbalanced = voltages(balanced(7200.0), 0.0);
+        unbalanced[4] -= 300.0 * 2.0_f64.sqrt();
+        let time = relay.trip_time(line, &unbalanced).unwrap();
+        assert!((time - 9.0).abs() < 1e-6);
+        assert_eq!(relay.trip_time(line, &normal), None);
+
+        let relay = new_relay("type=47 kvbase=12.47 47%pickup=5 delay=2", &circuit);
+        let mut sagged = voltages(balanced(vln), 0.0);
+        sagged[1] *= 0.7;
+        assert_eq!(relay.trip_time(line, &sagged), Some(2.0));
+        assert_eq!(relay.trip_time(line, &voltages(balanced(vln), 0.0)), None);
+    }
+}
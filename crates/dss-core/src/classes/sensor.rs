@@ -0,0 +1,492 @@
+// Sensor (Pascal TSensor): measured voltages, currents or powers at a
+// terminal of a branch, for load allocation and state estimation. Powers
+// given as kW and kvar stand for the line currents they make at the voltage
+// base. Each sample takes the solved magnitudes at the terminal and the
+// weighted square error of the solution against the measurements.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::cmatrix::CMatrix;
+use crate::meter_element::{MeterElement, MeterElementBase, SolutionState};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice, read_doubles,
+};
+
+// Connections of the voltage sensor; LN is wye and LL delta
+const CONN_NAMES: &[&str] = &["wye", "delta", "ln", "ll"];
+
+const SENSOR_PROPERTIES: [PropertyDef; 12] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name (Full Object name) of element to which the Sensor is connected.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the Sensor is connected. 1 or 2, typically. Default is 1.",
+    },
+    PropertyDef {
+        name: "kvbase",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "Voltage base for the sensor, in kV. If connected to a 2- or 3-phase terminal, specify L-L voltage. For 1-phase devices specify L-N or actual 1-phase voltage. Like many other DSS devices, default is 12.47kV.",
+    },
+    PropertyDef {
+        name: "clear",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{ Yes | No }. Clear=Yes clears sensor values. Should be issued before putting in a new set of measurements.",
+    },
+    PropertyDef {
+        name: "kvs",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of Voltages (kV) measured by the voltage sensor. For Delta-connected sensors, Line-Line voltages are expected. For Wye, Line-Neutral are expected.",
+    },
+    PropertyDef {
+        name: "currents",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of Currents (amps) measured by the current sensor. Specify this or power quantities; not both.",
+    },
+    PropertyDef {
+        name: "kws",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of Active power (kW) measurements at the sensor. Is converted into Currents along with q=[...]\nWill override any currents=[...] specification.",
+    },
+    PropertyDef {
+        name: "kvars",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of Reactive power (kvar) measurements at the sensor. Is converted into Currents along with p=[...]",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(CONN_NAMES),
+        default: "wye",
+        help: "Voltage sensor Connection: { wye | delta | LN | LL }.  Default is wye. Applies to voltage measurement only. \nCurrents are always assumed to be line currents.\nIf wye or LN, voltage is assumed measured line-neutral; otherwise, line-line.",
+    },
+    PropertyDef {
+        name: "deltadirection",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "{1 or -1}  Default is 1:  1-2, 2-3, 3-1.  For reverse rotation, enter -1. Any positive or negative entry will suffice.",
+    },
+    PropertyDef {
+        name: "%error",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Assumed percent error in the measurement. Default is 1.",
+    },
+    PropertyDef {
+        name: "weight",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Weighting factor: Default is 1.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 14] = concat_properties(&SENSOR_PROPERTIES, &CKT_PROPERTIES);
+
+#[derive(Debug)]
+pub struct SensorClass;
+
+#[derive(Debug, Clone)]
+pub struct Sensor {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    // The sensed element and terminal
+    meter: MeterElementBase,
+    kv_base: f64,
+    // Measurements, one per phase; empty when not measured
+    kvs: Vec<f64>,
+    currents: Vec<f64>,
+    kws: Vec<f64>,
+    kvars: Vec<f64>,
+    connection: Connection,
+    // Rotation of the line-line voltages, 1 for 1-2, 2-3, 3-1 or -1
+    delta_direction: i32,
+    pct_error: f64,
+    weight: f64,
+    // Magnitudes of the last sample, one per phase
+    calc_voltages: Vec<f64>,
+    calc_currents: Vec<f64>,
+    sq_error: f64,
+}
+
+impl Sensor {
+    pub fn new(name: &str) -> Self {
+        Sensor {
+            base: ObjectBase::new("Sensor", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            meter: MeterElementBase::new(),
+            kv_base: 12.47,
+            kvs: Vec::new(),
+            currents: Vec::new(),
+            kws: Vec::new(),
+            kvars: Vec::new(),
+            connection: Connection::Wye,
+            delta_direction: 1,
+            pct_error: 1.0,
+            weight: 1.0,
+            calc_voltages: Vec::new(),
+            calc_currents: Vec::new(),
+            sq_error: 0.0,
+        }
+    }
+
+    pub fn get_kv_base(&self) -> f64 {
+        self.kv_base
+    }
+
+    // Measured voltages, kV
+    pub fn get_kvs(&self) -> &[f64] {
+        &self.kvs
+    }
+
+    // Measured line currents, amps; from kWs and kvars when those are given
+    pub fn get_measured_currents(&self) -> &[f64] {
+        &self.currents
+    }
+
+    pub fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    // Voltages and currents of the last sample, kV and amps
+    pub fn calc_voltages(&self) -> &[f64] {
+        &self.calc_voltages
+    }
+
+    pub fn calc_currents(&self) -> &[f64] {
+        &self.calc_currents
+    }
+
+    // Weighted square error of the last sample against the measurements
+    pub fn get_sq_error(&self) -> f64 {
+        self.sq_error
+    }
+
+    fn clear(&mut self) {
+        self.kvs.clear();
+        self.currents.clear();
+        self.kws.clear();
+        self.kvars.clear();
+    }
+
+    // Voltage the sensor measures per phase at 1 per unit, kV
+    fn phase_kv_base(&self) -> f64 {
+        match self.ckt.nphases() {
+            1 => self.kv_base,
+            _ => self.kv_base / 3.0_f64.sqrt(),
+        }
+    }
+
+    // Sum over the measured phases of the squared errors, in units of the
+    // measurement error
+    fn square_error(&self, calc: &[f64], measured: &[f64]) -> f64 {
+        calc.iter()
+            .zip(measured)
+            .filter(|(_, measured)| **measured != 0.0)
+            .map(|(calc, measured)| {
+                let tolerance = measured.abs() * self.pct_error / 100.0;
+                ((calc - measured) / tolerance).powi(2)
+            })
+            .sum()
+    }
+}
+
+impl DssObject for Sensor {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = SENSOR_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - SENSOR_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => self.meter.set_element(parser.get_token()),
+            "terminal" => self
+                .meter
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "kvbase" => self.kv_base = parser.make_double()?,
+            "clear" if interpret_yes_no(parser.get_token()) => self.clear(),
+            "kvs" => self.kvs = read_doubles(parser)?,
+            "currents" => self.currents = read_doubles(parser)?,
+            "kws" => self.kws = read_doubles(parser)?,
+            "kvars" => self.kvars = read_doubles(parser)?,
+            "conn" => {
+                self.connection = match read_choice(parser, CONN_NAMES, "conn")? {
+                    "delta" | "ll" => Connection::Delta,
+                    _ => Connection::Wye,
+                }
+            }
+            "deltadirection" => {
+                self.delta_direction = if parser.make_integer()? < 0 { -1 } else { 1 }
+            }
+            "%error" => self.pct_error = parser.make_double()?,
+            "weight" => self.weight = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        if SENSOR_PROPERTIES.get(index).map(|p| p.name) != Some("element") {
+            return Ok(());
+        }
+        let Some(id) = self.meter.find_element(circuit) else {
+            return Err(DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!(
+                    "Sensed element \"{}\" not found for {}",
+                    self.meter.get_element(),
+                    self.full_name()
+                ),
+            ));
+        };
+        // the sensor has as many phases as the element it senses
+        if let Some(element) = circuit.element(id).and_then(|e| e.as_ckt_element()) {
+            self.ckt.set_phases(element.nphases());
+        }
+        Ok(())
+    }
+
+    // Powers given stand for the currents they make at the voltage base
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.pct_error <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs a %error above 0", self.full_name()),
+            ));
+        }
+        if !self.kws.is_empty() {
+            let kv = self.phase_kv_base();
+            let phases = self.kws.len().max(self.kvars.len());
+            self.currents = (0..phases)
+                .map(|k| {
+                    let kw = self.kws.get(k).copied().unwrap_or(0.0);
+                    let kvar = self.kvars.get(k).copied().unwrap_or(0.0);
+                    kw.hypot(kvar) / kv
+                })
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Sensor {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_meter_element(&self) -> Option<&dyn MeterElement> {
+        Some(self)
+    }
+
+    fn as_meter_element_mut(&mut self) -> Option<&mut dyn MeterElement> {
+        Some(self)
+    }
+
+    // Meters are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl MeterElement for Sensor {
+    fn meter_base(&self) -> &MeterElementBase {
+        &self.meter
+    }
+
+    fn meter_base_mut(&mut self) -> &mut MeterElementBase {
+        &mut self.meter
+    }
+
+    fn take_sample(&mut self, circuit: &Circuit, voltages: &[Complex64], _state: &SolutionState) {
+        let Some(element) = self
+            .meter
+            .find_element(circuit)
+            .and_then(|id| circuit.element(id))
+            .and_then(|element| element.as_ckt_element())
+        else {
+            return;
+        };
+        let nconds = element.nconds();
+        let nphases = element.nphases();
+        let start = (self.meter.get_terminal() - 1) * nconds;
+        let v: Vec<Complex64> = element
+            .terminal_voltages(voltages)
+            .into_iter()
+            .skip(start)
+            .take(nphases)
+            .collect();
+        self.calc_voltages = match self.connection {
+            Connection::Delta if nphases > 1 => (0..nphases)
+                .map(|k| {
+                    let next = (k as i32 + self.delta_direction).rem_euclid(nphases as i32);
+                    (v[k] - v[next as usize]).norm() / 1000.0
+                })
+                .collect(),
+            _ => v.iter().map(|v| v.norm() / 1000.0).collect(),
+        };
+        self.calc_currents = element
+            .get_currents(voltages)
+            .into_iter()
+            .skip(start)
+            .take(nphases)
+            .map(|i| i.norm())
+            .collect();
+        self.sq_error = self.weight
+            * (self.square_error(&self.calc_voltages, &self.kvs)
+                + self.square_error(&self.calc_currents, &self.currents));
+    }
+
+    fn reset(&mut self) {
+        self.calc_voltages.clear();
+        self.calc_currents.clear();
+        self.sq_error = 0.0;
+    }
+}
+
+impl DssClass for SensorClass {
+    fn name(&self) -> &'static str {
+        "Sensor"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Sensor::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::classes::line::{Line, LineClass};
+    use crate::test_util::{edit, edited, placed};
+
+    // a - l1 - b; 10 V drop over 1 + j1 ohm per phase
+    fn feeder() -> (Circuit, Vec<Complex64>) {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("l1"),
+            &LineClass,
+            "bus1=a bus2=b r1=1 x1=1 r0=1 x0=1 c1=0 c0=0 length=1",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(line, &[&[1, 2, 3], &[4, 5, 6]]).unwrap()));
+        let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        for v in [1000.0, 990.0] {
+            voltages.extend([Complex64::new(v, 0.0), a * v, a * a * v]);
+        }
+        (circuit, voltages)
+    }
+
+    fn new_sensor(properties: &str, circuit: &Circuit) -> DssResult<Sensor> {
+        edited(Sensor::new("s1"), &SensorClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_currents_from_powers() {
+        let (circuit, _) = feeder();
+        let sensor = new_sensor(
+            "element=line.l1 kvbase=12.47 kws=[300 300 300] kvars=[400 400 400]",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(sensor.nphases(), 3);
+        let expected = 500.0 / (12.47 / 3.0_f64.sqrt());
+        for current in sensor.get_measured_currents() {
+            assert!((current - expected).abs() < 1e-9);
+        }
+
+        let err = new_sensor("element=line.nowhere", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+        let err = new_sensor("element=line.l1 %error=0", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn test_sample() {
+        let (circuit, voltages) = feeder();
+        // 5 - j5 A per phase is 7.07 A; the sensor reads 1% high
+        let measured = 1.01 * 50.0_f64.sqrt();
+        let mut sensor = new_sensor(
+            &format!(
+                "element=line.l1 kvs=[1 1 1] currents=[{0} {0} {0}] weight=2",
+                measured
+            ),
+            &circuit,
+        )
+        .unwrap();
+        sensor.take_sample(&circuit, &voltages, &SolutionState::default());
+        for v in sensor.calc_voltages() {
+            assert!((v - 1.0).abs() < 1e-12);
+        }
+        for i in sensor.calc_currents() {
+            assert!((i - 50.0_f64.sqrt()).abs() < 1e-9);
+        }
+        // each current is off by about its error
+        let per_phase = (0.01 / 1.01_f64) / 0.01;
+        assert!((sensor.get_sq_error() - 2.0 * 3.0 * per_phase.powi(2)).abs() < 1e-9);
+
+        edit(&mut sensor, &SensorClass, "conn=delta clear=yes", &circuit).unwrap();
+        sensor.take_sample(&circuit, &voltages, &SolutionState::default());
+        for v in sensor.calc_voltages() {
+            assert!((v - 3.0_f64.sqrt()).abs() < 1e-9);
+        }
+        assert_eq!(sensor.get_sq_error(), 0.0);
+    }
+}
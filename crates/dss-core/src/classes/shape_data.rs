@@ -0,0 +1,383 @@
+// Machinery shared by the shapes (load, temperature and price shapes):
+// values read from text or packed binary files, the lookup of a value at an
+// hour of the solution, and the single valued curve of TShape and
+// PriceShape with the properties they have in common.
+
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::property::{interpret_yes_no, read_doubles};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Avg,
+    Edge,
+}
+
+impl Interpolation {
+    pub const NAMES: &[&str] = &["avg", "edge"];
+
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "edge" => Interpolation::Edge,
+            _ => Interpolation::Avg,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FileFormat {
+    // Text, values separated by commas or blanks, one record per line
+    Csv,
+    // Packed 4-byte floats
+    Sng,
+    // Packed 8-byte floats
+    Dbl,
+}
+
+// A file of shape values, as named by csvfile=, sngfile=, dblfile= or inside
+// an array, e.g. "mult=(file=MyCSVFile.CSV, col=3, header=yes)"
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ShapeFile {
+    pub format: FileFormat,
+    pub name: String,
+    // Column of the first value of a record in a text file, from 1
+    pub column: usize,
+    // The first line of a text file holds the column names
+    pub header: bool,
+}
+
+impl ShapeFile {
+    pub(crate) fn new(format: FileFormat, name: &str) -> Self {
+        ShapeFile {
+            format,
+            name: name.to_string(),
+            column: 1,
+            header: false,
+        }
+    }
+
+    // The file an array value names, if it names one
+    pub(crate) fn from_array(value: &str) -> Option<Self> {
+        let mut file: Option<ShapeFile> = None;
+        let (mut column, mut header) = (1, false);
+        for part in value.split(|c: char| c.is_whitespace() || c == ',') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key.to_lowercase().as_str() {
+                "file" => file = Some(ShapeFile::new(FileFormat::Csv, value)),
+                "sngfile" => file = Some(ShapeFile::new(FileFormat::Sng, value)),
+                "dblfile" => file = Some(ShapeFile::new(FileFormat::Dbl, value)),
+                "col" | "column" => column = value.parse().unwrap_or(1).max(1),
+                "header" => header = interpret_yes_no(value),
+                _ => {}
+            }
+        }
+        file.map(|file| ShapeFile {
+            column,
+            header,
+            ..file
+        })
+    }
+
+    // Records of `width` values each; a text file is read from the column
+    // given, a binary file is taken as records packed one after another
+    pub(crate) fn read(&self, dir: &Path, width: usize) -> DssResult<Vec<Vec<f64>>> {
+        let path = dir.join(&self.name);
+        let file_error = |err: std::io::Error| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!(
+                    "Shape file \"{}\" could not be read: {}",
+                    path.display(),
+                    err
+                ),
+            )
+        };
+        let values: Vec<f64> = match self.format {
+            FileFormat::Csv => {
+                let text = std::fs::read_to_string(&path).map_err(file_error)?;
+                let mut records = Vec::new();
+                let lines = text.lines().enumerate().skip(usize::from(self.header));
+                for (number, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+                    let fields: Vec<&str> = line
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|field| !field.is_empty())
+                        .collect();
+                    for k in 0..width {
+                        let field = fields.get(self.column - 1 + k).copied().unwrap_or("");
+                        let value = field.parse::<f64>().map_err(|_| {
+                            DssError::new(
+                                codes::SYNTAX_ERROR,
+                                &format!(
+                                    "Invalid value \"{}\" on line {} of \"{}\"",
+                                    field,
+                                    number + 1,
+                                    path.display()
+                                ),
+                            )
+                        })?;
+                        records.push(value);
+                    }
+                }
+                records
+            }
+            FileFormat::Sng => std::fs::read(&path)
+                .map_err(file_error)?
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as f64)
+                .collect(),
+            FileFormat::Dbl => std::fs::read(&path)
+                .map_err(file_error)?
+                .chunks_exact(8)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect(),
+        };
+        Ok(values.chunks_exact(width).map(<[f64]>::to_vec).collect())
+    }
+}
+
+// Mean and sample standard deviation
+pub(crate) fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    let n = values.len();
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / n as f64;
+    if n == 1 {
+        return (mean, 0.0);
+    }
+    let sum_sq: f64 = values.iter().map(|value| (value - mean).powi(2)).sum();
+    (mean, (sum_sq / (n - 1) as f64).sqrt())
+}
+
+// Value at an hour of the solution (Pascal GetMult, GetTemperature), from n
+// points at a fixed interval or at the hours given; the curve repeats after
+// its last point. n must be at least 1.
+pub(crate) fn value_at<T>(
+    hour: f64,
+    n: usize,
+    interval: f64,
+    hours: Option<&[f64]>,
+    interpolation: Interpolation,
+    value: impl Fn(usize) -> T,
+) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let between = |before: T, after: T, fraction: f64| match interpolation {
+        Interpolation::Edge => before,
+        Interpolation::Avg => before + (after - before) * fraction,
+    };
+    if n == 1 {
+        return value(0);
+    }
+    let Some(hours) = hours else {
+        // point i is at the end of interval i, so the last point also stands
+        // at hour 0
+        let period = n as f64 * interval;
+        let position = hour.rem_euclid(period) / interval;
+        let steps = (position + 1e-9).floor();
+        let before = (steps as usize + n - 1) % n;
+        let fraction = (position - steps).max(0.0);
+        return between(value(before), value((before + 1) % n), fraction);
+    };
+    let last = hours[n - 1];
+    let hour = if last > 0.0 && hour > last {
+        hour - (hour / last).trunc() * last
+    } else {
+        hour
+    };
+    match hours[..n].iter().position(|&h| h >= hour) {
+        None => value(n - 1),
+        Some(0) => value(0),
+        Some(i) if hours[i] == hour => value(i),
+        Some(i) => {
+            let fraction = (hour - hours[i - 1]) / (hours[i] - hours[i - 1]);
+            between(value(i - 1), value(i), fraction)
+        }
+    }
+}
+
+// What a file read at the next resolve goes to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileTarget {
+    Values,
+    Hours,
+    // (hour,) value, as read by csvfile=, sngfile= and dblfile=
+    Curve,
+}
+
+// A curve of one value over time (Pascal TTShape and TPriceShape share it
+// property for property); the class owning it names the values
+#[derive(Debug, Clone)]
+pub(crate) struct ShapeData {
+    // Number of points; 0 to take as many as the values given
+    npts: usize,
+    // Hours between points; 0 when the points come at the hours given
+    interval: f64,
+    values: Vec<f64>,
+    hours: Vec<f64>,
+    // Given by mean= and stddev=; computed from the values otherwise
+    mean: Option<f64>,
+    std_dev: Option<f64>,
+    pending_file: Option<(FileTarget, ShapeFile)>,
+}
+
+impl ShapeData {
+    pub(crate) fn new() -> Self {
+        ShapeData {
+            npts: 0,
+            interval: 1.0,
+            values: Vec::new(),
+            hours: Vec::new(),
+            mean: None,
+            std_dev: None,
+            pending_file: None,
+        }
+    }
+
+    pub(crate) fn num_points(&self) -> usize {
+        match self.npts {
+            0 => self.values.len(),
+            npts => npts,
+        }
+    }
+
+    pub(crate) fn get_interval(&self) -> f64 {
+        self.interval
+    }
+
+    pub(crate) fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub(crate) fn hours(&self) -> &[f64] {
+        &self.hours
+    }
+
+    pub(crate) fn get_mean(&self) -> f64 {
+        self.mean
+            .unwrap_or_else(|| mean_and_std_dev(&self.values).0)
+    }
+
+    pub(crate) fn get_std_dev(&self) -> f64 {
+        self.std_dev
+            .unwrap_or_else(|| mean_and_std_dev(&self.values).1)
+    }
+
+    // Value at an hour; interpolated between points at the hours given,
+    // held between points at a fixed interval. None without points.
+    pub(crate) fn get_value(&self, hour: f64) -> Option<f64> {
+        if self.values.is_empty() {
+            return None;
+        }
+        let (hours, interpolation) = match self.interval {
+            0.0 => (Some(self.hours.as_slice()), Interpolation::Avg),
+            _ => (None, Interpolation::Edge),
+        };
+        Some(value_at(
+            hour,
+            self.values.len(),
+            self.interval,
+            hours,
+            interpolation,
+            |i| self.values[i],
+        ))
+    }
+
+    // Sets one of the shared properties; "values" stands for the array the
+    // class names (temp=, price=)
+    pub(crate) fn set_property(&mut self, name: &str, parser: &mut DSSParser) -> DssResult<()> {
+        match name {
+            "npts" => self.npts = parser.make_integer()?.max(0) as usize,
+            "interval" => self.interval = parser.make_double()?.max(0.0),
+            "sinterval" => self.interval = parser.make_double()?.max(0.0) / 3600.0,
+            "minterval" => self.interval = parser.make_double()?.max(0.0) / 60.0,
+            "values" => {
+                self.values = self.read_array(FileTarget::Values, parser)?;
+                self.mean = None;
+                self.std_dev = None;
+            }
+            "hour" => self.hours = self.read_array(FileTarget::Hours, parser)?,
+            "mean" => self.mean = Some(parser.make_double()?),
+            "stddev" => self.std_dev = Some(parser.make_double()?),
+            "csvfile" | "sngfile" | "dblfile" => {
+                let format = match name {
+                    "sngfile" => FileFormat::Sng,
+                    "dblfile" => FileFormat::Dbl,
+                    _ => FileFormat::Csv,
+                };
+                let file = ShapeFile::new(format, parser.get_token());
+                self.pending_file = Some((FileTarget::Curve, file));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Reads the file named by the property set last, if any
+    pub(crate) fn resolve(&mut self, circuit: &Circuit) -> DssResult<()> {
+        let Some((target, file)) = self.pending_file.take() else {
+            return Ok(());
+        };
+        let timed = usize::from(self.interval == 0.0);
+        let width = match target {
+            FileTarget::Curve => 1 + timed,
+            _ => 1,
+        };
+        let records = file.read(circuit.get_current_dir(), width)?;
+        let column = |k: usize| records.iter().map(|record| record[k]).collect::<Vec<f64>>();
+        match target {
+            FileTarget::Values => self.values = column(0),
+            FileTarget::Hours => self.hours = column(0),
+            FileTarget::Curve => {
+                if timed == 1 {
+                    self.hours = column(0);
+                }
+                self.values = column(timed);
+            }
+        }
+        if self.npts > 0 && records.len() < self.npts {
+            self.npts = records.len();
+        }
+        self.mean = None;
+        self.std_dev = None;
+        Ok(())
+    }
+
+    pub(crate) fn recalc(&mut self, full_name: &str) -> DssResult<()> {
+        if self.npts > 0 {
+            self.values.resize(self.npts, 0.0);
+            if !self.hours.is_empty() {
+                self.hours.resize(self.npts, 0.0);
+            }
+        }
+        if self.interval == 0.0 && self.hours.len() < self.values.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has interval=0 and needs an hour for each of its {} points",
+                    full_name,
+                    self.values.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_array(&mut self, target: FileTarget, parser: &mut DSSParser) -> DssResult<Vec<f64>> {
+        match ShapeFile::from_array(parser.get_token()) {
+            Some(file) => {
+                self.pending_file = Some((target, file));
+                Ok(Vec::new())
+            }
+            None => read_doubles(parser),
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Spectrum (Pascal TSpectrum): the harmonic content of the current a power
+// conversion element injects, as magnitudes in percent of the fundamental
+// and angles in degrees. Angles are kept relative to the fundamental so the
+// spectrum can be applied to a fundamental current at any angle. The
+// spectra OpenDSS defines in every circuit are built in; a Spectrum object
+// of the same name replaces them.
+
+use std::any::Any;
+use std::borrow::Cow;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::shape_data::{FileFormat, ShapeFile};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_doubles};
+
+// Name, harmonics, percent magnitudes and angles of a built in spectrum
+type BuiltinSpectrum = (&'static str, &'static [f64], &'static [f64], &'static [f64]);
+
+// The spectra every circuit starts with
+const BUILTIN_SPECTRA: &[BuiltinSpectrum] = &[
+    (
+        "default",
+        &[1.0, 3.0, 5.0, 7.0, 9.0, 11.0, 13.0],
+        &[100.0, 33.0, 20.0, 14.0, 11.0, 9.0, 7.0],
+        &[0.0; 7],
+    ),
+    (
+        "defaultload",
+        &[1.0, 3.0, 5.0, 7.0, 9.0, 11.0, 13.0],
+        &[100.0, 1.5, 20.0, 14.0, 1.0, 9.0, 7.0],
+        &[0.0, 180.0, 180.0, 180.0, 180.0, 180.0, 180.0],
+    ),
+    (
+        "defaultgen",
+        &[1.0, 3.0, 5.0, 7.0, 9.0, 11.0, 13.0],
+        &[100.0, 5.0, 3.0, 1.5, 1.0, 0.7, 0.5],
+        &[0.0; 7],
+    ),
+    ("defaultvsource", &[1.0], &[100.0], &[0.0]),
+    ("linear", &[1.0], &[100.0], &[0.0]),
+];
+
+static PROPERTIES: [PropertyDef; 5] = [
+    PropertyDef {
+        name: "numharm",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Number of frequencies in this spectrum. (See CSVFile)",
+    },
+    PropertyDef {
+        name: "harmonic",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of harmonic values. You can also use the syntax\nharmonic = (file=filename)     !for text file one value per line\nharmonic = (dblfile=filename)  !for packed file of doubles\nharmonic = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "%mag",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of magnitude values, assumed to be in PERCENT. You can also use the syntax\n%mag = (file=filename)     !for text file one value per line\n%mag = (dblfile=filename)  !for packed file of doubles\n%mag = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "angle",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of phase angle values, degrees.You can also use the syntax\nangle = (file=filename)     !for text file one value per line\nangle = (dblfile=filename)  !for packed file of doubles\nangle = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "File of spectrum points with (harmonic, magnitude-percent, angle) values, one set of 3 per line, in CSV format. If fewer than NUMHARM frequencies found in the file, NUMHARM is set to the smaller value.",
+    },
+];
+
+// What a file read at the next resolve goes to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileTarget {
+    Harmonics,
+    Magnitudes,
+    Angles,
+    // harmonic, %mag, angle
+    Spectrum,
+}
+
+#[derive(Debug)]
+pub struct SpectrumClass;
+
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    base: ObjectBase,
+    // Number of harmonics; 0 to take as many as given
+    num_harm: usize,
+    harmonics: Vec<f64>,
+    pct_mags: Vec<f64>,
+    // Degrees, as given
+    angles: Vec<f64>,
+    // Per unit magnitude and angle of each harmonic, the fundamental turned
+    // to angle 0 (Pascal MultArray)
+    mults: Vec<Complex64>,
+    pending_file: Option<(FileTarget, ShapeFile)>,
+}
+
+impl Spectrum {
+    pub fn new(name: &str) -> Self {
+        Spectrum {
+            base: ObjectBase::new("Spectrum", name, &PROPERTIES),
+            num_harm: 0,
+            harmonics: Vec::new(),
+            pct_mags: Vec::new(),
+            angles: Vec::new(),
+            mults: Vec::new(),
+            pending_file: None,
+        }
+    }
+
+    // One of the spectra every circuit has, by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let &(name, harmonics, pct_mags, angles) = BUILTIN_SPECTRA
+            .iter()
+            .find(|(builtin, ..)| builtin.eq_ignore_ascii_case(name))?;
+        let mut spectrum = Spectrum::new(name);
+        spectrum.harmonics = harmonics.to_vec();
+        spectrum.pct_mags = pct_mags.to_vec();
+        spectrum.angles = angles.to_vec();
+        spectrum.set_mults();
+        Some(spectrum)
+    }
+
+    pub fn num_harm(&self) -> usize {
+        self.harmonics.len()
+    }
+
+    pub fn harmonics(&self) -> &[f64] {
+        &self.harmonics
+    }
+
+    pub fn pct_mags(&self) -> &[f64] {
+        &self.pct_mags
+    }
+
+    pub fn angles(&self) -> &[f64] {
+        &self.angles
+    }
+
+    // Per unit multiplier of the fundamental current at a harmonic (Pascal
+    // GetMult); 0 for harmonics not in the spectrum
+    pub fn get_mult(&self, harmonic: f64) -> Complex64 {
+        self.harmonics
+            .iter()
+            .position(|h| (h - harmonic).abs() < 0.01)
+            .map_or(Complex64::new(0.0, 0.0), |i| self.mults[i])
+    }
+
+    // Turns all angles so the fundamental is at zero; a harmonic h moves h
+    // times as far as the fundamental does (Pascal SetMultArray)
+    fn set_mults(&mut self) {
+        let fundamental = self
+            .harmonics
+            .iter()
+            .position(|&h| h.round() == 1.0)
+            .map_or(0.0, |i| self.angles[i]);
+        self.mults = self
+            .harmonics
+            .iter()
+            .zip(&self.pct_mags)
+            .zip(&self.angles)
+            .map(|((h, mag), angle)| {
+                Complex64::from_polar(mag / 100.0, (angle - h * fundamental).to_radians())
+            })
+            .collect();
+    }
+}
+
+// The spectrum an element names: the circuit's, else a built in one
+pub fn find_spectrum<'a>(circuit: &'a Circuit, name: &str) -> Option<Cow<'a, Spectrum>> {
+    match circuit.find_object_as::<Spectrum>("Spectrum", name) {
+        Some(spectrum) => Some(Cow::Borrowed(spectrum)),
+        None => Spectrum::builtin(name).map(Cow::Owned),
+    }
+}
+
+impl DssObject for Spectrum {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let name = PROPERTIES[index].name;
+        let target = match name {
+            "numharm" => {
+                self.num_harm = parser.make_integer()?.max(0) as usize;
+                return Ok(());
+            }
+            "harmonic" => FileTarget::Harmonics,
+            "%mag" => FileTarget::Magnitudes,
+            "angle" => FileTarget::Angles,
+            _ => {
+                let file = ShapeFile::new(FileFormat::Csv, parser.get_token());
+                self.pending_file = Some((FileTarget::Spectrum, file));
+                return Ok(());
+            }
+        };
+        let values = match ShapeFile::from_array(parser.get_token()) {
+            Some(file) => {
+                self.pending_file = Some((target, file));
+                return Ok(());
+            }
+            None => read_doubles(parser)?,
+        };
+        match target {
+            FileTarget::Harmonics => self.harmonics = values,
+            FileTarget::Magnitudes => self.pct_mags = values,
+            _ => self.angles = values,
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some((target, file)) = self.pending_file.take() else {
+            return Ok(());
+        };
+        let width = match target {
+            FileTarget::Spectrum => 3,
+            _ => 1,
+        };
+        let records = file.read(circuit.get_current_dir(), width)?;
+        let column = |k: usize| records.iter().map(|record| record[k]).collect::<Vec<f64>>();
+        match target {
+            FileTarget::Harmonics => self.harmonics = column(0),
+            FileTarget::Magnitudes => self.pct_mags = column(0),
+            FileTarget::Angles => self.angles = column(0),
+            FileTarget::Spectrum => {
+                self.harmonics = column(0);
+                self.pct_mags = column(1);
+                self.angles = column(2);
+                // a shorter file makes the spectrum shorter
+                if self.num_harm > records.len() {
+                    self.num_harm = records.len();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.num_harm > 0 {
+            self.harmonics.truncate(self.num_harm);
+            self.pct_mags.truncate(self.num_harm);
+        }
+        let n = self.harmonics.len();
+        if self.pct_mags.len() != n {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has {} harmonics but {} magnitudes",
+                    self.full_name(),
+                    n,
+                    self.pct_mags.len()
+                ),
+            ));
+        }
+        // angles not given are 0
+        self.angles.resize(n, 0.0);
+        self.set_mults();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "numharm" => self.num_harm().to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for SpectrumClass {
+    fn name(&self) -> &'static str {
+        "Spectrum"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Spectrum::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::edited;
+
+    fn new_spectrum(properties: &str, circuit: &Circuit) -> DssResult<Spectrum> {
+        edited(Spectrum::new("s1"), &SpectrumClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_mult() {
+        let circuit = Circuit::new("test");
+        // the fundamental at 30 degrees turns the 5th by 150
+        let spectrum =
+            new_spectrum("harmonic=[1 5 7] %mag=[100 20 10] angle=[30 180]", &circuit).unwrap();
+        assert_eq!(spectrum.get_mult(1.0), Complex64::new(1.0, 0.0));
+        let fifth = Complex64::from_polar(0.2, 30.0_f64.to_radians());
+        assert!((spectrum.get_mult(5.0) - fifth).norm() < 1e-12);
+        let seventh = Complex64::from_polar(0.1, (-210.0_f64).to_radians());
+        assert!((spectrum.get_mult(7.0) - seventh).norm() < 1e-12);
+        assert_eq!(spectrum.get_mult(3.0), Complex64::new(0.0, 0.0));
+
+        let err = new_spectrum("harmonic=[1 3] %mag=[100]", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn test_builtin_and_file() {
+        let dir = std::env::temp_dir().join(format!("dss_core_spectrum_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut circuit = Circuit::new("test");
+        circuit.set_current_dir(&dir);
+        std::fs::write(dir.join("spectrum.csv"), "1, 100, 0\n3, 50, 90\n5, 25, 0\n").unwrap();
+
+        let load = find_spectrum(&circuit, "DefaultLoad").unwrap();
+        assert_eq!(load.num_harm(), 7);
+        assert!((load.get_mult(3.0) - Complex64::new(-0.015, 0.0)).norm() < 1e-12);
+        assert!(find_spectrum(&circuit, "nowhere").is_none());
+
+        let spectrum = new_spectrum("numharm=2 csvfile=spectrum.csv", &circuit).unwrap();
+        assert_eq!(spectrum.harmonics(), [1.0, 3.0]);
+        assert!((spectrum.get_mult(3.0) - Complex64::new(0.0, 0.5)).norm() < 1e-12);
+        circuit.add_element(Box::new(spectrum));
+        let spectrum = Spectrum::new("defaultload");
+        circuit.add_element(Box::new(spectrum));
+        assert_eq!(
+            find_spectrum(&circuit, "defaultload").unwrap().num_harm(),
+            0
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,988 @@
+// Storage (Pascal TStorage): a battery or other energy store behind an
+// inverter, connected like a generator. It is idling, charging at %charge
+// of its rated kW or discharging at %discharge, and keeps account of the
+// kWh stored between reserve and full. The dispatch mode decides the state
+// each time step: triggers on the load shape, load level or price, the shape
+// itself in follow mode, or a StorageController in external mode.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, read_choice};
+
+const STORAGE_PROPERTIES: [PropertyDef; 32] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of Phases, this Storage element.  Power is evenly divided among phases.",
+    },
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Bus to which the Storage element is connected.  May include specific node specification.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "Nominal rated (1.0 per unit) voltage, kV, for Storage element. For 2- and 3-phase Storage elements, specify phase-phase kV. Otherwise, specify actual kV across each branch of the Storage element. If wye (star), specify phase-neutral kV. If delta or phase-phase connected, specify phase-phase kV.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "={wye|LN|delta|LL}.  Default is wye.",
+    },
+    PropertyDef {
+        name: "kw",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Get/set the requested kW value. Final kW is subjected to the inverter ratings. A positive value denotes that the Storage element is injecting active power into the grid. A negative value indicates that the Storage element is being charged (absorbing active power from the grid).",
+    },
+    PropertyDef {
+        name: "pf",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Nominally, the power factor for discharging (acting as a generator). Default is 1.0. Enter negative for leading power factor (when kW and kvar have opposite signs.) A positive power factor signifies kw and kvar at the same direction.",
+    },
+    PropertyDef {
+        name: "kvar",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Get/set the requested kvar value. Final kvar is subjected to the inverter ratings. Sets inverter to operate in constant kvar mode.",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "25",
+        help: "Indicates the inverter nameplate capability (in kVA). Used as the base for Dynamics mode and Harmonics mode values.",
+    },
+    PropertyDef {
+        name: "kwrated",
+        kind: PropertyKind::Double,
+        default: "25",
+        help: "kW rating of power output. Base for Loadshapes when DispMode=Follow. Sets kVA property if it has not been specified yet. Defaults to 25.",
+    },
+    PropertyDef {
+        name: "kwhrated",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Rated Storage capacity in kWh. Default is 50.",
+    },
+    PropertyDef {
+        name: "kwhstored",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Present amount of energy stored, kWh. Default is same as kWh rated.",
+    },
+    PropertyDef {
+        name: "%stored",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Present amount of energy stored, % of rated kWh. Default is 100.",
+    },
+    PropertyDef {
+        name: "%reserve",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Percentage of rated kWh Storage capacity to be held in reserve for normal operation. Default = 20. This is treated as the minimum energy discharge level unless there is an emergency. For emergency operation set this property lower. Cannot be less than zero.",
+    },
+    PropertyDef {
+        name: "state",
+        kind: PropertyKind::Choice(StorageState::NAMES),
+        default: "idling",
+        help: "{IDLING | CHARGING | DISCHARGING}  Get/Set present operational state. In DISCHARGING mode, the Storage element acts as a generator and the kW property is positive. The element continues discharging at the scheduled output power level until the Storage reaches the reserve value. Then the state reverts to IDLING. In the CHARGING state, the Storage element behaves like a Load and the kW property is negative. The element continues to charge until the max Storage kWh is reached and then switches to IDLING state. In IDLING state, the element draws the idling losses plus the associated inverter losses.",
+    },
+    PropertyDef {
+        name: "%discharge",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Discharge rate (output power) in percent of rated kW. Default = 100.",
+    },
+    PropertyDef {
+        name: "%charge",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Charging rate (input power) in Percent of rated kW. Default = 100.",
+    },
+    PropertyDef {
+        name: "%effcharge",
+        kind: PropertyKind::Double,
+        default: "90",
+        help: "Percentage efficiency for CHARGING the Storage element. Default = 90.",
+    },
+    PropertyDef {
+        name: "%effdischarge",
+        kind: PropertyKind::Double,
+        default: "90",
+        help: "Percentage efficiency for DISCHARGING the Storage element. Default = 90.",
+    },
+    PropertyDef {
+        name: "%idlingkw",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Percentage of rated kW consumed by idling losses. Default = 1.",
+    },
+    PropertyDef {
+        name: "%r",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Equivalent percentage internal resistance, ohms. Default is 0. Placed in series with internal voltage source for harmonics and dynamics modes. Use a combination of %IdlingkW, %EffCharge and %EffDischarge to account for losses in power flow modes.",
+    },
+    PropertyDef {
+        name: "%x",
+        kind: PropertyKind::Double,
+        default: "50",
+        help: "Equivalent percentage internal reactance, ohms. Default is 50%. Placed in series with internal voltage source for harmonics and dynamics modes. (Limits fault current to 2 pu.",
+    },
+    PropertyDef {
+        name: "model",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Integer code (default=1) for the model to be used for power output variation with voltage. Valid values are: 1:Storage element injects/absorbs a CONSTANT power. 2:Storage element is modeled as a CONSTANT IMPEDANCE. 3:Compute load injection from User-written Model.",
+    },
+    PropertyDef {
+        name: "vminpu",
+        kind: PropertyKind::Double,
+        default: "0.9",
+        help: "Default = 0.90.  Minimum per unit voltage for which the Model is assumed to apply. Below this value, the load model reverts to a constant impedance model.",
+    },
+    PropertyDef {
+        name: "vmaxpu",
+        kind: PropertyKind::Double,
+        default: "1.1",
+        help: "Default = 1.10.  Maximum per unit voltage for which the Model is assumed to apply. Above this value, the load model reverts to a constant impedance model.",
+    },
+    PropertyDef {
+        name: "dispmode",
+        kind: PropertyKind::Choice(StorageDispatch::NAMES),
+        default: "default",
+        help: "{DEFAULT | FOLLOW | EXTERNAL | LOADLEVEL | PRICE } Default = \"DEFAULT\". Dispatch mode. In DEFAULT mode, Storage element state is triggered to discharge or charge at the specified rate by the loadshape curve corresponding to the solution mode. In FOLLOW mode the kW output of the Storage element follows the active loadshape multiplier until Storage is either exhausted or full. The element discharges for positive values and charges for negative values.  The loadshape is based on rated kW. In EXTERNAL mode, Storage element state is controlled by an external Storagecontroller. This mode is automatically set if this Storage element is included in the element list of a StorageController element. For the other two dispatch modes, the Storage element state is controlled by either the global default Loadlevel value or the price level.",
+    },
+    PropertyDef {
+        name: "dischargetrigger",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Dispatch trigger value for discharging the Storage. If = 0.0 the Storage element state is changed by the State command or by a StorageController object. If <> 0  the Storage element state is set to DISCHARGING when this trigger level is EXCEEDED by either the specified Loadshape curve value or the price signal or global Loadlevel value, depending on dispatch mode. See State property.",
+    },
+    PropertyDef {
+        name: "chargetrigger",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Dispatch trigger value for charging the Storage. If = 0.0 the Storage element state is changed by the State command or StorageController object.  If <> 0  the Storage element state is set to CHARGING when this trigger level is GREATER than either the specified Loadshape curve value or the price signal or global Loadlevel value, depending on dispatch mode. See State property.",
+    },
+    PropertyDef {
+        name: "timechargetrig",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Time of day in fractional hours (0230 = 2.5) at which Storage element will automatically go into charge state. Default is 2.0.  Enter a negative time value to disable this feature.",
+    },
+    PropertyDef {
+        name: "class",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "An arbitrary integer number representing the class of Storage element so that Storage values may be segregated by class.",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for yearly simulations.  Must be previously defined as a Loadshape object. If this is not specified, the Daily dispatch shape, if any, is repeated during Yearly solution modes. In the default dispatch mode, the Storage element uses this loadshape to trigger State changes.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Dispatch shape to use for daily simulations.  Must be previously defined as a Loadshape object of 24 hrs, typically.  In the default dispatch mode, the Storage element uses this loadshape to trigger State changes.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "Load shape to use for duty cycle dispatch simulations such as for solar ramp rate studies. Must be previously defined as a Loadshape object. Typically would have time intervals of 1-5 seconds. Designate the number of points to solve using the Set Number=xxxx command. If there are fewer points in the actual shape, the shape is assumed to repeat.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 35] = concat_properties(&STORAGE_PROPERTIES, &PC_PROPERTIES);
+
+// Operating state of the store (Pascal STORE_IDLING and the like)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageState {
+    Idling,
+    Charging,
+    Discharging,
+}
+
+impl StorageState {
+    pub const NAMES: &'static [&'static str] = &["idling", "charging", "discharging"];
+
+    // State of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "charging" => StorageState::Charging,
+            "discharging" => StorageState::Discharging,
+            _ => StorageState::Idling,
+        }
+    }
+
+    // Value of the state as OpenDSS reports it: -1 charging, 0 idling and
+    // 1 discharging
+    pub fn value(self) -> f64 {
+        match self {
+            StorageState::Charging => -1.0,
+            StorageState::Idling => 0.0,
+            StorageState::Discharging => 1.0,
+        }
+    }
+}
+
+// What sets the state each time step (Pascal DispatchMode of TStorage)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageDispatch {
+    Default,
+    Follow,
+    External,
+    LoadLevel,
+    Price,
+}
+
+impl StorageDispatch {
+    pub const NAMES: &'static [&'static str] =
+        &["default", "follow", "external", "loadlevel", "price"];
+
+    // Mode of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "follow" => StorageDispatch::Follow,
+            "external" => StorageDispatch::External,
+            "loadlevel" => StorageDispatch::LoadLevel,
+            "price" => StorageDispatch::Price,
+            _ => StorageDispatch::Default,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StorageClass;
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    connection: Connection,
+    kv: f64,
+    pf: f64,
+    kvar: f64,
+    // kvar was given last rather than pf
+    kvar_given: bool,
+    kva: f64,
+    kva_given: bool,
+    kw_rated: f64,
+    kwh_rated: f64,
+    kwh_stored: f64,
+    pct_reserve: f64,
+    state: StorageState,
+    pct_discharge: f64,
+    pct_charge: f64,
+    pct_eff_charge: f64,
+    pct_eff_discharge: f64,
+    pct_idling_kw: f64,
+    pct_r: f64,
+    pct_x: f64,
+    model: usize,
+    vminpu: f64,
+    vmaxpu: f64,
+    dispatch_mode: StorageDispatch,
+    discharge_trigger: f64,
+    charge_trigger: f64,
+    time_charge_trigger: f64,
+    storage_class: i32,
+    yearly: String,
+    daily: String,
+    duty: String,
+    // Admittance of each phase in Yprim, as last built
+    y_phase: Complex64,
+}
+
+impl Storage {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 1);
+        ckt.set_conductors(4);
+        Storage {
+            base: ObjectBase::new("Storage", name, &PROPERTIES),
+            ckt,
+            pc: PcElementBase::new("default"),
+            connection: Connection::Wye,
+            kv: 12.47,
+            pf: 1.0,
+            kvar: 0.0,
+            kvar_given: false,
+            kva: 25.0,
+            kva_given: false,
+            kw_rated: 25.0,
+            kwh_rated: 50.0,
+            kwh_stored: 50.0,
+            pct_reserve: 20.0,
+            state: StorageState::Idling,
+            pct_discharge: 100.0,
+            pct_charge: 100.0,
+            pct_eff_charge: 90.0,
+            pct_eff_discharge: 90.0,
+            pct_idling_kw: 1.0,
+            pct_r: 0.0,
+            pct_x: 50.0,
+            model: 1,
+            vminpu: 0.9,
+            vmaxpu: 1.1,
+            dispatch_mode: StorageDispatch::Default,
+            discharge_trigger: 0.0,
+            charge_trigger: 0.0,
+            time_charge_trigger: 2.0,
+            storage_class: 1,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            y_phase: Complex64::new(0.0, 0.0),
+        }
+    }
+
+    pub fn get_kv(&self) -> f64 {
+        self.kv
+    }
+
+    pub fn get_kva(&self) -> f64 {
+        self.kva
+    }
+
+    pub fn get_kw_rated(&self) -> f64 {
+        self.kw_rated
+    }
+
+    pub fn get_kwh_rated(&self) -> f64 {
+        self.kwh_rated
+    }
+
+    pub fn get_kwh_stored(&self) -> f64 {
+        self.kwh_stored
+    }
+
+    pub fn get_pct_stored(&self) -> f64 {
+        self.kwh_stored / self.kwh_rated * 100.0
+    }
+
+    pub fn get_pct_reserve(&self) -> f64 {
+        self.pct_reserve
+    }
+
+    pub fn get_state(&self) -> StorageState {
+        self.state
+    }
+
+    pub fn get_dispatch_mode(&self) -> StorageDispatch {
+        self.dispatch_mode
+    }
+
+    // A StorageController takes over the dispatch of its fleet
+    pub fn set_dispatch_mode(&mut self, mode: StorageDispatch) {
+        self.dispatch_mode = mode;
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        self.connection
+    }
+
+    pub fn get_model(&self) -> usize {
+        self.model
+    }
+
+    pub fn get_class(&self) -> i32 {
+        self.storage_class
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    pub fn get_pct_discharge(&self) -> f64 {
+        self.pct_discharge
+    }
+
+    pub fn get_pct_charge(&self) -> f64 {
+        self.pct_charge
+    }
+
+    // kW delivered to the grid in the present state; negative charging
+    pub fn get_kw(&self) -> f64 {
+        -self.power_in().re
+    }
+
+    // kvar delivered to the grid in the present state
+    pub fn get_kvar(&self) -> f64 {
+        -self.power_in().im
+    }
+
+    // Changes the state, idling instead of discharging an exhausted store or
+    // charging a full one; Yprim follows before the next solution
+    pub fn set_state(&mut self, state: StorageState) {
+        let state = match state {
+            StorageState::Discharging if self.kwh_stored <= self.kwh_reserve() => {
+                StorageState::Idling
+            }
+            StorageState::Charging if self.kwh_stored >= self.kwh_rated => StorageState::Idling,
+            state => state,
+        };
+        if state != self.state {
+            self.state = state;
+            self.ckt.invalidate_yprim();
+        }
+    }
+
+    // Requests an output in kW, discharging when positive and charging when
+    // negative, as the kw property does
+    pub fn set_kw(&mut self, kw: f64) {
+        if kw > 0.0 {
+            self.set_pct_discharge(kw / self.kw_rated * 100.0);
+            self.set_state(StorageState::Discharging);
+        } else if kw < 0.0 {
+            self.set_pct_charge(-kw / self.kw_rated * 100.0);
+            self.set_state(StorageState::Charging);
+        } else {
+            self.set_state(StorageState::Idling);
+        }
+    }
+
+    // Holds the output at `kvar`, as the kvar property does
+    pub fn set_kvar(&mut self, kvar: f64) {
+        self.kvar = kvar;
+        self.kvar_given = true;
+        self.ckt.invalidate_yprim();
+    }
+
+    pub fn set_pct_discharge(&mut self, pct: f64) {
+        self.pct_discharge = pct;
+        self.ckt.invalidate_yprim();
+    }
+
+    pub fn set_pct_charge(&mut self, pct: f64) {
+        self.pct_charge = pct;
+        self.ckt.invalidate_yprim();
+    }
+
+    // Sets the state for the time step from the dispatch mode (Pascal
+    // CheckStorageDispatch): `level` is the value of the dispatch shape, the
+    // load level or the price as the mode takes it, `hour` the time of day
+    // and `step_hours` the length of the step. External storage is left to
+    // its controller.
+    pub fn dispatch(&mut self, level: f64, hour: f64, step_hours: f64) {
+        match self.dispatch_mode {
+            StorageDispatch::External => {}
+            StorageDispatch::Follow => self.set_kw(level * self.kw_rated),
+            StorageDispatch::Default | StorageDispatch::LoadLevel | StorageDispatch::Price => {
+                if self.discharge_trigger != 0.0 && level >= self.discharge_trigger {
+                    self.set_state(StorageState::Discharging);
+                } else if self.charge_trigger != 0.0 && level <= self.charge_trigger {
+                    self.set_state(StorageState::Charging);
+                } else if self.time_charge_trigger >= 0.0 {
+                    let since = (hour - self.time_charge_trigger).rem_euclid(24.0);
+                    if since < step_hours.max(1e-9) {
+                        self.set_state(StorageState::Charging);
+                    }
+                }
+            }
+        }
+    }
+
+    // Energy moved over a time step in the present state, after the
+    // efficiencies; the store idles once it reaches reserve or full (Pascal
+    // UpdateStorage)
+    pub fn update_storage(&mut self, step_hours: f64) {
+        let rate = match self.state {
+            StorageState::Charging => self.power_in().re * self.pct_eff_charge / 100.0,
+            StorageState::Discharging => self.power_in().re / (self.pct_eff_discharge / 100.0),
+            StorageState::Idling => return,
+        };
+        self.kwh_stored += rate * step_hours;
+        if self.kwh_stored >= self.kwh_rated {
+            self.kwh_stored = self.kwh_rated;
+            self.set_state(StorageState::Idling);
+        } else if self.kwh_stored <= self.kwh_reserve() {
+            self.kwh_stored = self.kwh_stored.max(self.kwh_reserve());
+            self.set_state(StorageState::Idling);
+        }
+    }
+
+    // Impedance behind the internal voltage for dynamics and harmonics,
+    // ohms each phase
+    pub fn internal_impedance(&self) -> Complex64 {
+        let nphases = self.ckt.nphases() as f64;
+        let zbase = self.vbase() * self.vbase() / (self.kva * 1000.0 / nphases);
+        Complex64::new(self.pct_r, self.pct_x) / 100.0 * zbase
+    }
+
+    fn kwh_reserve(&self) -> f64 {
+        self.kwh_rated * self.pct_reserve / 100.0
+    }
+
+    // kW and kvar into the element in the present state, the kvar within
+    // what the inverter rating leaves
+    fn power_in(&self) -> Complex64 {
+        let kw = match self.state {
+            StorageState::Discharging => -self.kw_rated * self.pct_discharge / 100.0,
+            StorageState::Charging => self.kw_rated * self.pct_charge / 100.0,
+            StorageState::Idling => self.kw_rated * self.pct_idling_kw / 100.0,
+        };
+        let kw = kw.clamp(-self.kva, self.kva);
+        let kvar_out = if self.kvar_given {
+            self.kvar
+        } else if self.pf.abs() >= 1.0 || self.state == StorageState::Idling {
+            0.0
+        } else {
+            (kw.abs() * (1.0 / (self.pf * self.pf) - 1.0).sqrt()).copysign(self.pf)
+        };
+        let limit = (self.kva * self.kva - kw * kw).max(0.0).sqrt();
+        Complex64::new(kw, -kvar_out.clamp(-limit, limit))
+    }
+
+    fn vbase(&self) -> f64 {
+        let nphases = self.ckt.nphases();
+        if self.connection == Connection::Wye && (nphases == 2 || nphases == 3) {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+
+    // Watts and vars each phase draws at 1 per unit
+    fn phase_power(&self) -> Complex64 {
+        self.power_in() * 1000.0 / self.ckt.nphases() as f64
+    }
+
+    // Admittance drawing the present power at nominal voltage; Yprim uses it
+    // and the injection currents do the rest
+    fn nominal_admittance(&self) -> Complex64 {
+        let vbase = self.vbase();
+        self.phase_power().conj() / (vbase * vbase)
+    }
+
+    fn phase_ends(&self, phase: usize) -> (usize, usize) {
+        let nphases = self.ckt.nphases();
+        match self.connection {
+            Connection::Wye => (phase, nphases),
+            Connection::Delta if nphases == 1 => (0, 1),
+            Connection::Delta => (phase, (phase + 1) % nphases),
+        }
+    }
+
+    fn set_conductors(&mut self) {
+        let nphases = self.ckt.nphases();
+        let nconds = match self.connection {
+            Connection::Wye => nphases + 1,
+            Connection::Delta if nphases == 1 => 2,
+            Connection::Delta => nphases,
+        };
+        if nconds != self.ckt.nconds() {
+            self.ckt.set_conductors(nconds);
+        }
+    }
+
+    // Current into one phase across voltage `v`: constant power within
+    // vminpu..vmaxpu, constant impedance outside and for model 2
+    fn phase_current(&self, v: Complex64) -> Complex64 {
+        let vmag = v.norm();
+        if vmag == 0.0 {
+            return Complex64::new(0.0, 0.0);
+        }
+        let vpu = vmag / self.vbase();
+        let mult = if self.model == 2 {
+            vpu * vpu
+        } else {
+            let limited = vpu.clamp(self.vminpu, self.vmaxpu);
+            (vpu / limited) * (vpu / limited)
+        };
+        (self.phase_power() * mult / v).conj()
+    }
+
+    fn check(&self) -> DssResult<()> {
+        if self.model == 3 {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!(
+                    "User-written storage models are not supported ({})",
+                    self.full_name()
+                ),
+            ));
+        }
+        if !(1..=2).contains(&self.model) {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Invalid storage model {} for {}; must be 1 or 2",
+                    self.model,
+                    self.full_name()
+                ),
+            ));
+        }
+        if self.kwh_rated <= 0.0 || self.kw_rated <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs kwrated and kwhrated > 0", self.full_name()),
+            ));
+        }
+        if self.pct_reserve < 0.0 || self.pct_eff_charge <= 0.0 || self.pct_eff_discharge <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("Invalid reserve or efficiency for {}", self.full_name()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Moves the energy of every enabled storage element over a finished time
+// step (Pascal TStorage.UpdateAll)
+pub fn update_all_storage(circuit: &mut Circuit, step_hours: f64) {
+    for id in circuit.class_elements("Storage").to_vec() {
+        if let Some(storage) = circuit
+            .element_mut(id)
+            .and_then(|element| element.as_any_mut().downcast_mut::<Storage>())
+            .filter(|storage| storage.ckt.is_enabled())
+        {
+            storage.update_storage(step_hours);
+        }
+    }
+}
+
+impl DssObject for Storage {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = STORAGE_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - STORAGE_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+                self.set_conductors();
+            }
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "kv" => self.kv = parser.make_double()?,
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.connection = Connection::from_name(name);
+                self.set_conductors();
+            }
+            "kw" => {
+                let kw = parser.make_double()?;
+                self.set_kw(kw);
+            }
+            "pf" => {
+                self.pf = parser.make_double()?;
+                self.kvar_given = false;
+            }
+            "kvar" => {
+                self.kvar = parser.make_double()?;
+                self.kvar_given = true;
+            }
+            "kva" => {
+                self.kva = parser.make_double()?;
+                self.kva_given = true;
+            }
+            "kwrated" => {
+                self.kw_rated = parser.make_double()?;
+                if !self.kva_given {
+                    self.kva = self.kw_rated;
+                }
+            }
+            "kwhrated" => self.kwh_rated = parser.make_double()?,
+            "kwhstored" => self.kwh_stored = parser.make_double()?,
+            "%stored" => self.kwh_stored = parser.make_double()? * self.kwh_rated / 100.0,
+            "%reserve" => self.pct_reserve = parser.make_double()?,
+            "state" => {
+                let name = read_choice(parser, StorageState::NAMES, "state")?;
+                self.set_state(StorageState::from_name(name));
+            }
+            "%discharge" => self.pct_discharge = parser.make_double()?,
+            "%charge" => self.pct_charge = parser.make_double()?,
+            "%effcharge" => self.pct_eff_charge = parser.make_double()?,
+            "%effdischarge" => self.pct_eff_discharge = parser.make_double()?,
+            "%idlingkw" => self.pct_idling_kw = parser.make_double()?,
+            "%r" => self.pct_r = parser.make_double()?,
+            "%x" => self.pct_x = parser.make_double()?,
+            "model" => self.model = parser.make_integer()?.max(0) as usize,
+            "vminpu" => self.vminpu = parser.make_double()?,
+            "vmaxpu" => self.vmaxpu = parser.make_double()?,
+            "dispmode" => {
+                let name = read_choice(parser, StorageDispatch::NAMES, "dispmode")?;
+                self.dispatch_mode = StorageDispatch::from_name(name);
+            }
+            "dischargetrigger" => self.discharge_trigger = parser.make_double()?,
+            "chargetrigger" => self.charge_trigger = parser.make_double()?,
+            "timechargetrig" => self.time_charge_trigger = parser.make_double()?,
+            "class" => self.storage_class = parser.make_integer()?,
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.check()?;
+        self.kwh_stored = self.kwh_stored.clamp(0.0, self.kwh_rated);
+        // the stored energy may have been moved under the state
+        self.set_state(self.state);
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Storage {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // The admittance of the present power between the two conductors of
+    // each phase, its susceptance scaled to the frequency
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let y_nominal = self.nominal_admittance();
+        let y = Complex64::new(y_nominal.re, y_nominal.im / freq_mult);
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            yprim.add(a, a, y);
+            yprim.add(b, b, y);
+            yprim.add(a, b, -y);
+            yprim.add(b, a, -y);
+        }
+        self.y_phase = y;
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+
+    // What Yprim draws through each phase less what the model draws
+    fn get_injection_currents(&self, voltages: &[Complex64]) -> Vec<Complex64> {
+        let mut injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        if self.ckt.get_yprim().is_none() {
+            return injection;
+        }
+        let v = self.terminal_voltages(voltages);
+        for phase in 0..self.ckt.nphases() {
+            let (a, b) = self.phase_ends(phase);
+            let across = v[a] - v[b];
+            let current = self.y_phase * across - self.phase_current(across);
+            injection[a] += current;
+            injection[b] -= current;
+        }
+        injection
+    }
+}
+
+impl PcElement for Storage {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+
+    fn variable_names(&self) -> Vec<String> {
+        ["kWh", "State", "kWOut", "kvarOut"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn variables(&self) -> Vec<f64> {
+        vec![
+            self.kwh_stored,
+            self.state.value(),
+            self.get_kw(),
+            self.get_kvar(),
+        ]
+    }
+}
+
+impl DssClass for StorageClass {
+    fn name(&self) -> &'static str {
+        "Storage"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Storage::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close, edited, first_nodes, placed};
+
+    fn new_storage(properties: &str) -> DssResult<Storage> {
+        let storage = edited(
+            Storage::new("s1"),
+            &StorageClass,
+            properties,
+            &Circuit::new("test"),
+        )?;
+        let nodes = first_nodes(storage.nphases());
+        placed(storage, &[&nodes])
+    }
+
+    fn voltages_at(vpu: f64) -> [Complex64; 2] {
+        [Complex64::new(0.0, 0.0), Complex64::new(1000.0 * vpu, 0.0)]
+    }
+
+    #[test]
+    fn test_states() {
+        let base = "phases=1 kv=1 kwrated=10 kwhrated=40";
+        let mut storage = new_storage(base).unwrap();
+        assert_eq!(storage.get_state(), StorageState::Idling);
+        // idling losses only
+        assert_close(
+            storage.total_power(&voltages_at(1.0)),
+            Complex64::new(100.0, 0.0),
+            1e-6,
+        );
+
+        storage.set_kw(5.0);
+        assert_eq!(storage.get_state(), StorageState::Discharging);
+        storage.calc_yprim(60.0).unwrap();
+        assert_close(
+            storage.total_power(&voltages_at(1.0)),
+            Complex64::new(-5000.0, 0.0),
+            1e-6,
+        );
+
+        // full, so it idles rather than charge
+        storage.set_state(StorageState::Charging);
+        assert_eq!(storage.get_state(), StorageState::Idling);
+
+        let mut storage = new_storage(&format!("{} %stored=50 kw=-8 pf=0.8", base)).unwrap();
+        assert_eq!(storage.get_state(), StorageState::Charging);
+        assert!((storage.get_kw() + 8.0).abs() < 1e-12);
+        assert!((storage.get_kvar() - 6.0).abs() < 1e-9);
+        storage.calc_yprim(60.0).unwrap();
+        assert_close(
+            storage.total_power(&voltages_at(1.0)),
+            Complex64::new(8000.0, -6000.0),
+            1e-6,
+        );
+
+        assert!(new_storage("model=3").is_err());
+        assert!(new_storage("kwhrated=0").is_err());
+    }
+
+    #[test]
+    fn test_energy_accounting() {
+        let mut storage =
+            new_storage("phases=1 kv=1 kwrated=10 kwhrated=40 %stored=50 state=charging").unwrap();
+        storage.update_storage(1.0);
+        assert!((storage.get_kwh_stored() - 29.0).abs() < 1e-9);
+        for _ in 0..2 {
+            storage.update_storage(1.0);
+        }
+        assert_eq!(storage.get_kwh_stored(), 40.0);
+        assert_eq!(storage.get_state(), StorageState::Idling);
+
+        storage.set_state(StorageState::Discharging);
+        storage.update_storage(2.0);
+        assert!((storage.get_kwh_stored() - (40.0 - 20.0 / 0.9)).abs() < 1e-9);
+        storage.update_storage(2.0);
+        // stops at the 20% reserve
+        assert_eq!(storage.get_kwh_stored(), 8.0);
+        assert_eq!(storage.get_state(), StorageState::Idling);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut follow = new_storage("kwrated=10 %stored=50 dispmode=follow").unwrap();
+        follow.dispatch(-0.5, 12.0, 1.0);
+        assert_eq!(follow.get_state(), StorageState::Charging);
+        assert!((follow.get_kw() + 5.0).abs() < 1e-12);
+        follow.dispatch(0.0, 13.0, 1.0);
+        assert_eq!(follow.get_state(), StorageState::Idling);
+
+        let mut triggered =
+            new_storage("%stored=50 dischargetrigger=0.8 chargetrigger=0.3").unwrap();
+        triggered.dispatch(0.9, 12.0, 1.0);
+        assert_eq!(triggered.get_state(), StorageState::Discharging);
+        triggered.dispatch(0.2, 13.0, 1.0);
+        assert_eq!(triggered.get_state(), StorageState::Charging);
+
+        let mut timed = new_storage("%stored=50").unwrap();
+        timed.dispatch(0.5, 1.0, 0.5);
+        assert_eq!(timed.get_state(), StorageState::Idling);
+        timed.dispatch(0.5, 2.25, 0.5);
+        assert_eq!(timed.get_state(), StorageState::Charging);
+
+        let mut external = new_storage("%stored=50 dispmode=external").unwrap();
+        external.dispatch(-1.0, 2.0, 1.0);
+        assert_eq!(external.get_state(), StorageState::Idling);
+    }
+}
@@ -0,0 +1,758 @@
+// StorageController (Pascal TStorageController): dispatches a fleet of
+// Storage elements, all of them unless an element list names some. In
+// peak-shave mode it discharges the fleet to hold the power through the
+// watched element at kwtarget, in time mode it discharges at a time of day
+// and in schedule mode along a ramp up, flat top and ramp down from that
+// time. It charges at a time of day or, in peakshavelow mode, to keep the
+// power up to kwtargetlow. Fleet output is shared out by the weights, and
+// the storage it dispatches is switched to external dispatch.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::storage::{Storage, StorageDispatch, StorageState};
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names, read_choice,
+    read_doubles,
+};
+use crate::registry::ElementId;
+
+const STORAGE_CONTROLLER_PROPERTIES: [PropertyDef; 19] = [
+    PropertyDef {
+        name: "element",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Full object name of the circuit element, typically a line or transformer, which the control is monitoring. There is no default; Must be specified.",
+    },
+    PropertyDef {
+        name: "terminal",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Number of the terminal of the circuit element to which the StorageController control is connected. 1 or 2, typically.  Default is 1. Make sure to select the proper direction on the power for the respective dispatch mode.",
+    },
+    PropertyDef {
+        name: "kwtarget",
+        kind: PropertyKind::Double,
+        default: "8000",
+        help: "kW/kamps target for Discharging. The Storage element fleet is dispatched to try to hold the power in band at least until the Storage is depleted. The selection of power or current depends on the Discharge mode (PeakShave->kW, I-PeakShave->kamps).",
+    },
+    PropertyDef {
+        name: "kwtargetlow",
+        kind: PropertyKind::Double,
+        default: "4000",
+        help: "kW/kamps target for Charging. The Storage element fleet is dispatched to try to hold the power in band at least until the Storage is fully charged. The selection of power or current depends on the charge mode (PeakShavelow->kW, I-PeakShavelow->kamps).",
+    },
+    PropertyDef {
+        name: "%kwband",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Bandwidth (% of Target kW/kamps) of the dead band around the kW/kamps target value. Default is 2% (+/-1%).No dispatch changes are attempted if the power in the monitored terminal stays within this band.",
+    },
+    PropertyDef {
+        name: "%kwbandlow",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Bandwidth (% of kWTargetLow) of the dead band around the kW/kamps low target value. Default is 2% (+/-1%).No charging is attempted if the power in the monitored terminal stays within this band.",
+    },
+    PropertyDef {
+        name: "elementlist",
+        kind: PropertyKind::Objects("Storage"),
+        default: "",
+        help: "Array list of Storage elements to be controlled.  If not specified, all Storage elements in the circuit not presently dispatched by another controller are assumed dispatched by this controller.",
+    },
+    PropertyDef {
+        name: "weights",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of proportional weights corresponding to each Storage element in the ElementList. The needed kW or kvar to get back to center band is dispatched to each Storage element according to these weights. Default is to set all weights to 1.0.",
+    },
+    PropertyDef {
+        name: "modedischarge",
+        kind: PropertyKind::Choice(DischargeMode::NAMES),
+        default: "peakshave",
+        help: "{PeakShave* | Time | Schedule} Mode of operation for the DISCHARGE FUNCTION of this controller. In PeakShave mode (Default), the control attempts to discharge Storage to keep power in the monitored element below the kWTarget. In Time mode, the Storage discharge is turned on at the specified %RatekW at the specified discharge trigger time in fractional hours. In Schedule mode, the Tup, TFlat, and Tdn properties specify the up ramp duration, flat duration, and down ramp duration for the schedule. The schedule start time is set by TimeDischargeTrigger and the rate of discharge for the flat part is determined by %RatekW.",
+    },
+    PropertyDef {
+        name: "modecharge",
+        kind: PropertyKind::Choice(ChargeMode::NAMES),
+        default: "time",
+        help: "{Time* | PeakShaveLow} Mode of operation for the CHARGE FUNCTION of this controller. In Time mode (default), the Storage charge FUNCTION is triggered at the specified %RateCharge at the specified charge trigger time in fractional hours. In PeakShaveLow mode, the charge operation will charge the Storage fleet when the power at a monitored element is below a specified KW target (kWTarget_low). The Storage will charge as much power as necessary to keep the power within the deadband around kWTarget_low.",
+    },
+    PropertyDef {
+        name: "timedischargetrigger",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Default time of day (hr) for initiating Discharging of the fleet. During Follow or Time mode discharging is triggered at a fixed time each day at this hour. If Discharge mode is set to PeakShave and this value is >= 0, Discharging is initiated at this time if the fleet is not already discharging. Set this to a negative value to ignore. Default is -1 (ignored).",
+    },
+    PropertyDef {
+        name: "timechargetrigger",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Default time of day (hr) for initiating charging in Time control mode. Set this to a negative value to ignore. Default is 2.0.  (0200).When this value is >0 the Storage fleet is set to charging at this time regardless of other control criteria to make sure Storage is topped off for the next discharge cycle.",
+    },
+    PropertyDef {
+        name: "%ratekw",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Sets the kW discharge rate in % of rated capacity for each element of the fleet. Applies to TIME control mode, SCHEDULE mode, or anytime discharging is triggered by time.",
+    },
+    PropertyDef {
+        name: "%ratecharge",
+        kind: PropertyKind::Double,
+        default: "20",
+        help: "Sets the kW charging rate in % of rated capacity for each element of the fleet. Applies to TIME control mode and anytime charging mode is entered due to a time trigger.",
+    },
+    PropertyDef {
+        name: "%reserve",
+        kind: PropertyKind::Double,
+        default: "25",
+        help: "Use this property to change the % reserve for each Storage element under control of this controller. This might be used, for example, to allow deeper discharges of Storage or in case of emergency operation to use the remainder of the Storage element.",
+    },
+    PropertyDef {
+        name: "tup",
+        kind: PropertyKind::Double,
+        default: "0.25",
+        help: "Duration, hrs, of upramp part for SCHEDULE mode. Default is 0.25.",
+    },
+    PropertyDef {
+        name: "tflat",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Duration, hrs, of flat part for SCHEDULE mode. Default is 2.0.",
+    },
+    PropertyDef {
+        name: "tdn",
+        kind: PropertyKind::Double,
+        default: "0.25",
+        help: "Duration, hrs, of downramp part for SCHEDULE mode. Default is 0.25.",
+    },
+    PropertyDef {
+        name: "eventlog",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes/True | No/False} Default is No. Log control actions to Eventlog.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 21] =
+    concat_properties(&STORAGE_CONTROLLER_PROPERTIES, &CKT_PROPERTIES);
+
+// The one action the controller queues: dispatch the fleet at pending_kw
+const ACTION_DISPATCH: i32 = 1;
+
+// How the fleet is discharged (Pascal DischargeMode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DischargeMode {
+    PeakShave,
+    Time,
+    Schedule,
+}
+
+impl DischargeMode {
+    pub const NAMES: &'static [&'static str] = &["peakshave", "time", "schedule"];
+
+    // Mode of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "time" => DischargeMode::Time,
+            "schedule" => DischargeMode::Schedule,
+            _ => DischargeMode::PeakShave,
+        }
+    }
+}
+
+// How the fleet is charged (Pascal ChargeMode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeMode {
+    Time,
+    PeakShaveLow,
+}
+
+impl ChargeMode {
+    pub const NAMES: &'static [&'static str] = &["time", "peakshavelow"];
+
+    // Mode of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "peakshavelow" => ChargeMode::PeakShaveLow,
+            _ => ChargeMode::Time,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StorageControllerClass;
+
+#[derive(Debug, Clone)]
+pub struct StorageController {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    control: ControlElementBase,
+    kw_target: f64,
+    kw_target_low: f64,
+    pct_kw_band: f64,
+    pct_kw_band_low: f64,
+    element_list: Vec<String>,
+    weights: Vec<f64>,
+    discharge_mode: DischargeMode,
+    charge_mode: ChargeMode,
+    time_discharge_trigger: f64,
+    time_charge_trigger: f64,
+    pct_rate_kw: f64,
+    pct_rate_charge: f64,
+    pct_reserve: f64,
+    t_up: f64,
+    t_flat: f64,
+    t_dn: f64,
+    event_log: bool,
+    // Fleet output the queued dispatch will set, kW; negative charging
+    pending_kw: f64,
+    pending: Option<usize>,
+    // Time of day of the last sample, hours
+    last_hour: Option<f64>,
+}
+
+impl StorageController {
+    pub fn new(name: &str) -> Self {
+        StorageController {
+            base: ObjectBase::new("StorageController", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            kw_target: 8000.0,
+            kw_target_low: 4000.0,
+            pct_kw_band: 2.0,
+            pct_kw_band_low: 2.0,
+            element_list: Vec::new(),
+            weights: Vec::new(),
+            discharge_mode: DischargeMode::PeakShave,
+            charge_mode: ChargeMode::Time,
+            time_discharge_trigger: -1.0,
+            time_charge_trigger: 2.0,
+            pct_rate_kw: 20.0,
+            pct_rate_charge: 20.0,
+            pct_reserve: 25.0,
+            t_up: 0.25,
+            t_flat: 2.0,
+            t_dn: 0.25,
+            event_log: false,
+            pending_kw: 0.0,
+            pending: None,
+            last_hour: None,
+        }
+    }
+
+    pub fn get_kw_target(&self) -> f64 {
+        self.kw_target
+    }
+
+    pub fn get_kw_target_low(&self) -> f64 {
+        self.kw_target_low
+    }
+
+    pub fn get_discharge_mode(&self) -> DischargeMode {
+        self.discharge_mode
+    }
+
+    pub fn get_charge_mode(&self) -> ChargeMode {
+        self.charge_mode
+    }
+
+    // Storage elements of the fleet with their weights
+    pub fn fleet(&self, circuit: &Circuit) -> Vec<(ElementId, f64)> {
+        let ids: Vec<ElementId> = if self.element_list.is_empty() {
+            circuit.class_elements("storage").to_vec()
+        } else {
+            self.element_list
+                .iter()
+                .filter_map(|name| match name.contains('.') {
+                    true => find_by_full_name(circuit, name),
+                    false => circuit.find_element("storage", name),
+                })
+                .collect()
+        };
+        ids.into_iter()
+            .enumerate()
+            .filter(|(_, id)| storage(circuit, *id).is_some())
+            .map(|(index, id)| (id, self.weights.get(index).copied().unwrap_or(1.0)))
+            .collect()
+    }
+
+    // Whether the time of day `trigger` fell since the last sample
+    fn crossed(&self, trigger: f64, hour: f64) -> bool {
+        if trigger < 0.0 {
+            return false;
+        }
+        match self.last_hour {
+            None => (hour - trigger).abs() < 1e-9,
+            Some(last) => {
+                let since_last = (hour - last).rem_euclid(24.0);
+                let since_trigger = (hour - trigger).rem_euclid(24.0);
+                since_last > 0.0 && since_trigger < since_last
+            }
+        }
+    }
+
+    // Percent of rated kW of the schedule at a time of day
+    fn schedule_pct(&self, hour: f64) -> f64 {
+        if self.time_discharge_trigger < 0.0 {
+            return 0.0;
+        }
+        let t = (hour - self.time_discharge_trigger).rem_euclid(24.0);
+        let pct = self.pct_rate_kw;
+        if t < self.t_up {
+            pct * t / self.t_up
+        } else if t < self.t_up + self.t_flat {
+            pct
+        } else if t < self.t_up + self.t_flat + self.t_dn {
+            pct * (1.0 - (t - self.t_up - self.t_flat) / self.t_dn)
+        } else {
+            0.0
+        }
+    }
+
+    // Fleet output the modes call for given the power through the watched
+    // terminal, or None to leave it as it is
+    fn desired_kw(&self, power_kw: Option<f64>, fleet: &FleetState, hour: f64) -> Option<f64> {
+        let discharge = match self.discharge_mode {
+            DischargeMode::PeakShave => power_kw.and_then(|p| {
+                let band = self.kw_target * self.pct_kw_band / 200.0;
+                if p > self.kw_target + band && fleet.can_discharge {
+                    Some((fleet.kw.max(0.0) + p - self.kw_target).min(fleet.kw_rated))
+                } else if fleet.kw > 0.0 && p < self.kw_target - band {
+                    Some((fleet.kw - (self.kw_target - p)).max(0.0))
+                } else if self.crossed(self.time_discharge_trigger, hour) && fleet.can_discharge {
+                    Some(fleet.kw_rated * self.pct_rate_kw / 100.0)
+                } else {
+                    None
+                }
+            }),
+            DischargeMode::Time => (self.crossed(self.time_discharge_trigger, hour)
+                && fleet.can_discharge)
+                .then_some(fleet.kw_rated * self.pct_rate_kw / 100.0),
+            DischargeMode::Schedule => {
+                let kw = fleet.kw_rated * self.schedule_pct(hour) / 100.0;
+                let wanted = if fleet.can_discharge { kw } else { 0.0 };
+                ((wanted > 0.0 || fleet.kw > 0.0)
+                    && (wanted - fleet.kw).abs() > 1e-3 * fleet.kw_rated)
+                    .then_some(wanted)
+            }
+        };
+        if discharge.is_some() || fleet.kw > 0.0 {
+            return discharge;
+        }
+        match self.charge_mode {
+            ChargeMode::Time => self
+                .crossed(self.time_charge_trigger, hour)
+                .then_some(-fleet.kw_rated * self.pct_rate_charge / 100.0),
+            ChargeMode::PeakShaveLow => power_kw.and_then(|p| {
+                let band = self.kw_target_low * self.pct_kw_band_low / 200.0;
+                if p < self.kw_target_low - band {
+                    Some((fleet.kw - (self.kw_target_low - p)).max(-fleet.kw_rated))
+                } else if fleet.kw < 0.0 && p > self.kw_target_low + band {
+                    Some((fleet.kw + p - self.kw_target_low).min(0.0))
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+}
+
+// What the controller needs to know of its fleet
+struct FleetState {
+    // Output now, kW; negative charging
+    kw: f64,
+    kw_rated: f64,
+    // Stored energy above the controller's reserve
+    can_discharge: bool,
+}
+
+// Output a storage element was dispatched at; idling losses are not dispatch
+fn dispatched_kw(storage: &Storage) -> f64 {
+    match storage.get_state() {
+        StorageState::Idling => 0.0,
+        _ => storage.get_kw(),
+    }
+}
+
+fn storage(circuit: &Circuit, id: ElementId) -> Option<&Storage> {
+    circuit.element(id)?.as_any().downcast_ref::<Storage>()
+}
+
+impl DssObject for StorageController {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = STORAGE_CONTROLLER_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - STORAGE_CONTROLLER_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "element" => self.control.set_element(parser.get_token()),
+            "terminal" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "kwtarget" => self.kw_target = parser.make_double()?,
+            "kwtargetlow" => self.kw_target_low = parser.make_double()?,
+            "%kwband" => self.pct_kw_band = parser.make_double()?,
+            "%kwbandlow" => self.pct_kw_band_low = parser.make_double()?,
+            "elementlist" => self.element_list = parse_names(parser.get_token()),
+            "weights" => self.weights = read_doubles(parser)?,
+            "modedischarge" => {
+                let name = read_choice(parser, DischargeMode::NAMES, "modedischarge")?;
+                self.discharge_mode = DischargeMode::from_name(name);
+            }
+            "modecharge" => {
+                let name = read_choice(parser, ChargeMode::NAMES, "modecharge")?;
+                self.charge_mode = ChargeMode::from_name(name);
+            }
+            "timedischargetrigger" => self.time_discharge_trigger = parser.make_double()?,
+            "timechargetrigger" => self.time_charge_trigger = parser.make_double()?,
+            "%ratekw" => self.pct_rate_kw = parser.make_double()?,
+            "%ratecharge" => self.pct_rate_charge = parser.make_double()?,
+            "%reserve" => self.pct_reserve = parser.make_double()?,
+            "tup" => self.t_up = parser.make_double()?,
+            "tflat" => self.t_flat = parser.make_double()?,
+            "tdn" => self.t_dn = parser.make_double()?,
+            "eventlog" => self.event_log = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if !self.weights.is_empty()
+            && !self.element_list.is_empty()
+            && self.weights.len() != self.element_list.len()
+        {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has {} weights for {} storage elements",
+                    self.full_name(),
+                    self.weights.len(),
+                    self.element_list.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for StorageController {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for StorageController {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Works out the fleet output the modes call for and queues a dispatch
+    // if it differs from the present one
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        let fleet = self.fleet(circuit);
+        let hour = queue.hour_of_day();
+        if fleet.is_empty() {
+            self.last_hour = Some(hour);
+            return;
+        }
+        let members: Vec<&Storage> = fleet
+            .iter()
+            .filter_map(|(id, _)| storage(circuit, *id))
+            .collect();
+        let kwh_reserve: f64 = members
+            .iter()
+            .map(|s| s.get_kwh_rated() * self.pct_reserve / 100.0)
+            .sum();
+        let state = FleetState {
+            kw: members.iter().map(|s| dispatched_kw(s)).sum(),
+            kw_rated: members.iter().map(|s| s.get_kw_rated()).sum(),
+            can_discharge: members.iter().map(|s| s.get_kwh_stored()).sum::<f64>() > kwh_reserve,
+        };
+        let power_kw = self
+            .control
+            .find_element(circuit)
+            .and_then(|element| circuit.element(element)?.as_ckt_element())
+            .and_then(|element| {
+                let powers = element.terminal_powers(voltages);
+                powers
+                    .get(self.control.get_terminal() - 1)
+                    .map(|s| s.re / 1000.0)
+            });
+        let desired = self.desired_kw(power_kw, &state, hour);
+        self.last_hour = Some(hour);
+        let Some(kw) = desired else {
+            return;
+        };
+        self.pending_kw = kw;
+        if self.pending.is_none() {
+            self.pending = Some(queue.push(0.0, ACTION_DISPATCH, 0, id));
+        }
+    }
+
+    // Shares the pending fleet output out by the weights
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if action.code != ACTION_DISPATCH {
+            return;
+        }
+        self.pending = None;
+        let fleet = self.fleet(circuit);
+        let total_weight: f64 = fleet.iter().map(|(_, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return;
+        }
+        for (id, weight) in fleet {
+            if let Some(storage) = circuit
+                .element_mut(id)
+                .and_then(|element| element.as_any_mut().downcast_mut::<Storage>())
+            {
+                storage.set_dispatch_mode(StorageDispatch::External);
+                storage.set_kw(self.pending_kw * weight / total_weight);
+            }
+        }
+        if self.event_log {
+            let what = match self.pending_kw {
+                kw if kw > 0.0 => format!("Fleet discharging at {:.1} kW", kw),
+                kw if kw < 0.0 => format!("Fleet charging at {:.1} kW", -kw),
+                _ => "Fleet idling".to_string(),
+            };
+            queue.log(&self.full_name(), &what);
+        }
+    }
+
+    fn reset(&mut self, _circuit: &mut Circuit) {
+        self.pending = None;
+        self.last_hour = None;
+    }
+}
+
+impl DssClass for StorageControllerClass {
+    fn name(&self) -> &'static str {
+        "StorageController"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(StorageController::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::load::{Load, LoadClass};
+    use crate::test_util::{add, balanced_nodes, edited, placed};
+
+    // A circuit with a 3-phase load of `kw` on nodes 1-3 and two 1000 kW
+    // storage elements, the first weighted 3 to 1
+    fn circuit_with_fleet(kw: f64) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let load = edited(
+            Load::new("feeder"),
+            &LoadClass,
+            &format!("kw={} pf=1", kw),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(placed(load, &[&[1, 2, 3, 0]]).unwrap()));
+        for name in ["s1", "s2"] {
+            add(
+                &mut circuit,
+                "storage",
+                name,
+                "kwrated=1000 kwhrated=4000 %stored=50",
+            );
+        }
+        circuit
+    }
+
+    fn new_controller(properties: &str, circuit: &Circuit) -> StorageController {
+        edited(
+            StorageController::new("sc"),
+            &StorageControllerClass,
+            properties,
+            circuit,
+        )
+        .unwrap()
+    }
+
+    fn balanced() -> Vec<Complex64> {
+        balanced_nodes(12470.0 / 3.0_f64.sqrt())
+    }
+
+    // Samples and carries out what comes due at the present time
+    fn run(controller: &mut StorageController, circuit: &mut Circuit, queue: &mut ControlQueue) {
+        controller.sample(99, circuit, &balanced(), queue);
+        while let Some(action) = queue.pop_due() {
+            controller.do_pending_action(&action, circuit, &balanced(), queue);
+        }
+    }
+
+    fn fleet_kw(circuit: &Circuit) -> Vec<f64> {
+        circuit
+            .class_elements("storage")
+            .iter()
+            .map(|&id| dispatched_kw(storage(circuit, id).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_shave() {
+        let mut circuit = circuit_with_fleet(9000.0);
+        let mut controller = new_controller(
+            "element=load.feeder kwtarget=8000 elementlist=[s1 s2] weights=[3 1] eventlog=yes",
+            &circuit,
+        );
+        let mut queue = ControlQueue::new();
+        queue.set_time(12, 0.0);
+        run(&mut controller, &mut circuit, &mut queue);
+        let kw = fleet_kw(&circuit);
+        assert!((kw[0] - 750.0).abs() < 1e-6 && (kw[1] - 250.0).abs() < 1e-6);
+        let id = circuit.find_element("storage", "s1").unwrap();
+        assert_eq!(
+            storage(&circuit, id).unwrap().get_dispatch_mode(),
+            StorageDispatch::External
+        );
+        assert_eq!(queue.events().len(), 1);
+
+        // inside the band nothing more happens
+        let mut circuit_in_band = circuit_with_fleet(8050.0);
+        run(&mut controller, &mut circuit_in_band, &mut queue);
+        assert_eq!(fleet_kw(&circuit_in_band), [0.0, 0.0]);
+
+        // well below the target the discharge backs off
+        let mut low = circuit_with_fleet(7000.0);
+        for &id in circuit.class_elements("storage") {
+            let kw = storage(&circuit, id).unwrap().get_kw();
+            low.element_mut(id)
+                .and_then(|e| e.as_any_mut().downcast_mut::<Storage>())
+                .unwrap()
+                .set_kw(kw);
+        }
+        run(&mut controller, &mut low, &mut queue);
+        assert_eq!(fleet_kw(&low), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_time_and_schedule() {
+        let mut circuit = circuit_with_fleet(1000.0);
+        let mut controller = new_controller(
+            "element=load.feeder modedischarge=time timedischargetrigger=17 %ratekw=50",
+            &circuit,
+        );
+        let mut queue = ControlQueue::new();
+        queue.set_time(16, 0.0);
+        run(&mut controller, &mut circuit, &mut queue);
+        assert_eq!(fleet_kw(&circuit), [0.0, 0.0]);
+        queue.set_time(17, 0.0);
+        run(&mut controller, &mut circuit, &mut queue);
+        assert_eq!(fleet_kw(&circuit), [500.0, 500.0]);
+
+        // charging at 02:00 the next morning
+        let id = circuit.find_element("storage", "s1").unwrap();
+        for &id in circuit.class_elements("storage").to_vec().iter() {
+            circuit
+                .element_mut(id)
+                .and_then(|e| e.as_any_mut().downcast_mut::<Storage>())
+                .unwrap()
+                .set_kw(0.0);
+        }
+        queue.set_time(26, 0.0);
+        run(&mut controller, &mut circuit, &mut queue);
+        assert_eq!(
+            storage(&circuit, id).unwrap().get_state(),
+            StorageState::Charging
+        );
+        assert_eq!(fleet_kw(&circuit), [-200.0, -200.0]);
+
+        let mut circuit = circuit_with_fleet(1000.0);
+        let mut controller = new_controller(
+            "modedischarge=schedule timedischargetrigger=10 tup=1 tflat=2 tdn=1 %ratekw=40",
+            &circuit,
+        );
+        let mut queue = ControlQueue::new();
+        for (hour, expected) in [(10.5, 200.0), (12.0, 400.0), (13.5, 200.0), (15.0, 0.0)] {
+            queue.set_time(0, hour * 3600.0);
+            run(&mut controller, &mut circuit, &mut queue);
+            let kw: f64 = fleet_kw(&circuit).iter().sum();
+            assert!((kw - 2.0 * expected).abs() < 1e-6, "{} at {}", kw, hour);
+        }
+    }
+}
@@ -0,0 +1,511 @@
+// SwtControl (Pascal TSwtControl): operates a terminal of a Line declared as
+// a switch. An action opens or closes it after the control's delay, through
+// the control queue; setting the state forces it at the next control pass
+// instead. A locked switch holds its present state against both, and
+// against the reset to its normal state a new solution brings, until it is
+// unlocked or reset by the reset property.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::line::Line;
+use crate::cmatrix::CMatrix;
+use crate::control_element::{ControlElement, ControlElementBase, find_by_full_name};
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, read_choice,
+};
+use crate::registry::ElementId;
+
+const SWT_CONTROL_PROPERTIES: [PropertyDef; 8] = [
+    PropertyDef {
+        name: "switchedobj",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of circuit element switch that the SwtControl operates. Specify the full object class and name.",
+    },
+    PropertyDef {
+        name: "switchedterm",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Terminal number of the controlled element switch. 1 or 2, typically.  Default is 1.",
+    },
+    PropertyDef {
+        name: "action",
+        kind: PropertyKind::Choice(SwitchState::NAMES),
+        default: "",
+        help: "{Open | Close}  After specified delay time, and if not locked, causes the controlled switch to open or close. ",
+    },
+    PropertyDef {
+        name: "lock",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No} Delayed action. Sends CTRL_LOCK or CTRL_UNLOCK message to control queue. After delay time, controlled switch is locked in its present open / close state or unlocked. Switch will not respond to either manual (Action) or automatic (APIs) control or internal OpenDSS Reset when locked.",
+    },
+    PropertyDef {
+        name: "delay",
+        kind: PropertyKind::Double,
+        default: "120",
+        help: "Operating time delay (sec) of the switch. Defaults to 120.",
+    },
+    PropertyDef {
+        name: "normal",
+        kind: PropertyKind::Choice(SwitchState::NAMES),
+        default: "closed",
+        help: "{Open | Closed] Normal state of the switch. If not Locked, the switch reverts to this state for reset, change of mode, etc. Defaults to first Action or State specified if not specifically declared.",
+    },
+    PropertyDef {
+        name: "state",
+        kind: PropertyKind::Choice(SwitchState::NAMES),
+        default: "closed",
+        help: "{Open | Closed] Present state of the switch. Upon setting, immediately forces state of switch.",
+    },
+    PropertyDef {
+        name: "reset",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "{Yes | No} If Yes, forces Reset of switch to Normal state and removes Lock independently of any internal reset command for mode change, etc.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 10] = concat_properties(&SWT_CONTROL_PROPERTIES, &CKT_PROPERTIES);
+
+// Codes of the queued actions
+const ACTION_OPEN: i32 = 1;
+const ACTION_CLOSE: i32 = 2;
+const ACTION_LOCK: i32 = 3;
+const ACTION_UNLOCK: i32 = 4;
+
+// Position of the switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchState {
+    Open,
+    Closed,
+}
+
+impl SwitchState {
+    // "close" abbreviates "closed"
+    pub const NAMES: &'static [&'static str] = &["open", "closed"];
+
+    // State of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "open" => SwitchState::Open,
+            _ => SwitchState::Closed,
+        }
+    }
+
+    fn code(self) -> i32 {
+        match self {
+            SwitchState::Open => ACTION_OPEN,
+            SwitchState::Closed => ACTION_CLOSE,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SwtControlClass;
+
+#[derive(Debug, Clone)]
+pub struct SwtControl {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    // The switched element and terminal
+    control: ControlElementBase,
+    delay: f64,
+    locked: bool,
+    normal: SwitchState,
+    // Normal given by its property rather than taken from the first action
+    normal_given: bool,
+    present: SwitchState,
+    // Changes asked for by the properties, carried out at the next sample
+    action: Option<SwitchState>,
+    forced: Option<SwitchState>,
+    lock_change: Option<bool>,
+    reset_requested: bool,
+    // The queued operation
+    pending: Option<usize>,
+}
+
+impl SwtControl {
+    pub fn new(name: &str) -> Self {
+        SwtControl {
+            base: ObjectBase::new("SwtControl", name, &PROPERTIES),
+            ckt: CktElementBase::new(1, 0),
+            control: ControlElementBase::new(),
+            delay: 120.0,
+            locked: false,
+            normal: SwitchState::Closed,
+            normal_given: false,
+            present: SwitchState::Closed,
+            action: None,
+            forced: None,
+            lock_change: None,
+            reset_requested: false,
+            pending: None,
+        }
+    }
+
+    pub fn get_state(&self) -> SwitchState {
+        self.present
+    }
+
+    pub fn get_normal(&self) -> SwitchState {
+        self.normal
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    // The first action or state stands for the normal state unless it is
+    // given
+    fn default_normal(&mut self, state: SwitchState) {
+        if !self.normal_given {
+            self.normal = state;
+            self.normal_given = true;
+        }
+    }
+
+    fn operate(&mut self, circuit: &mut Circuit, state: SwitchState) -> bool {
+        let terminal = self.control.get_terminal() - 1;
+        let Some(element) = self
+            .control
+            .find_element(circuit)
+            .and_then(|switched| circuit.element_mut(switched))
+            .and_then(|element| element.as_ckt_element_mut())
+        else {
+            return false;
+        };
+        element
+            .ckt_base_mut()
+            .set_closed(terminal, None, state == SwitchState::Closed);
+        self.present = state;
+        true
+    }
+}
+
+impl DssObject for SwtControl {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = SWT_CONTROL_PROPERTIES.get(index) else {
+            return self
+                .ckt
+                .set_property(index - SWT_CONTROL_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "switchedobj" => self.control.set_element(parser.get_token()),
+            "switchedterm" => self
+                .control
+                .set_terminal(parser.make_integer()?.max(1) as usize),
+            "action" => {
+                let state =
+                    SwitchState::from_name(read_choice(parser, SwitchState::NAMES, "action")?);
+                self.default_normal(state);
+                self.action = Some(state);
+            }
+            "lock" => self.lock_change = Some(interpret_yes_no(parser.get_token())),
+            "delay" => self.delay = parser.make_double()?,
+            "normal" => {
+                self.normal =
+                    SwitchState::from_name(read_choice(parser, SwitchState::NAMES, "normal")?);
+                self.normal_given = true;
+            }
+            "state" => {
+                let state =
+                    SwitchState::from_name(read_choice(parser, SwitchState::NAMES, "state")?);
+                self.default_normal(state);
+                self.forced = Some(state);
+            }
+            "reset" => self.reset_requested = interpret_yes_no(parser.get_token()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The switched element must be a line declared as a switch
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        if SWT_CONTROL_PROPERTIES
+            .get(index)
+            .map(|property| property.name)
+            != Some("switchedobj")
+        {
+            return Ok(());
+        }
+        let element = self.control.get_element();
+        let id = find_by_full_name(circuit, element).ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!(
+                    "Switched object \"{}\" not found for {}",
+                    element,
+                    self.full_name()
+                ),
+            )
+        })?;
+        let is_switch = circuit
+            .element(id)
+            .and_then(|element| element.as_any().downcast_ref::<Line>())
+            .is_some_and(Line::is_switch);
+        if !is_switch {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} must operate a line declared as a switch, not \"{}\"",
+                    self.full_name(),
+                    element
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for SwtControl {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_control_element(&self) -> Option<&dyn ControlElement> {
+        Some(self)
+    }
+
+    fn as_control_element_mut(&mut self) -> Option<&mut dyn ControlElement> {
+        Some(self)
+    }
+
+    // Controls are not connected
+    fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+        self.ckt.set_yprim(CMatrix::new(0));
+        Ok(())
+    }
+}
+
+impl ControlElement for SwtControl {
+    fn control_base(&self) -> &ControlElementBase {
+        &self.control
+    }
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase {
+        &mut self.control
+    }
+
+    // Queues what the properties have asked for: a reset or forced state
+    // at once, an action or a change of lock after the delay
+    fn sample(
+        &mut self,
+        id: ElementId,
+        _circuit: &Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if std::mem::take(&mut self.reset_requested) {
+            self.locked = false;
+            self.forced = Some(self.normal);
+        }
+        if let Some(state) = self.forced.take() {
+            if self.locked {
+                queue.log(&self.full_name(), "Locked, state not forced");
+            } else {
+                if let Some(handle) = self.pending.take() {
+                    queue.delete(handle);
+                }
+                queue.push(0.0, state.code(), 0, id);
+            }
+        }
+        if let Some(state) = self.action.take() {
+            if self.locked {
+                queue.log(&self.full_name(), "Locked, action ignored");
+            } else {
+                if let Some(handle) = self.pending.take() {
+                    queue.delete(handle);
+                }
+                self.pending = Some(queue.push(self.delay, state.code(), 0, id));
+            }
+        }
+        if let Some(lock) = self.lock_change.take() {
+            let code = if lock { ACTION_LOCK } else { ACTION_UNLOCK };
+            queue.push(self.delay, code, 0, id);
+        }
+    }
+
+    // Operates the switch unless it has been locked since, or changes the
+    // lock
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        _voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    ) {
+        if self.pending == Some(action.handle) {
+            self.pending = None;
+        }
+        let message = match action.code {
+            ACTION_LOCK => {
+                self.locked = true;
+                "Locked"
+            }
+            ACTION_UNLOCK => {
+                self.locked = false;
+                "Unlocked"
+            }
+            _ if self.locked => return,
+            ACTION_OPEN if self.operate(circuit, SwitchState::Open) => "Opened",
+            ACTION_CLOSE if self.operate(circuit, SwitchState::Closed) => "Closed",
+            _ => return,
+        };
+        queue.log(&self.full_name(), message);
+    }
+
+    // Back to the normal state unless locked
+    fn reset(&mut self, circuit: &mut Circuit) {
+        self.pending = None;
+        if !self.locked {
+            self.operate(circuit, self.normal);
+        }
+    }
+}
+
+impl DssClass for SwtControlClass {
+    fn name(&self) -> &'static str {
+        "SwtControl"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(SwtControl::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::line::LineClass;
+    use crate::test_util::{self, edited, run_to};
+
+    fn circuit_with_switch(switch: &str) -> Circuit {
+        let mut circuit = Circuit::new("test");
+        let line = edited(
+            Line::new("sw1"),
+            &LineClass,
+            &format!("switch={}", switch),
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(line));
+        circuit
+    }
+
+    fn edit(control: &mut SwtControl, properties: &str, circuit: &Circuit) -> DssResult<()> {
+        test_util::edit(control, &SwtControlClass, properties, circuit)
+    }
+
+    fn switch_closed(circuit: &Circuit) -> bool {
+        circuit
+            .find_object_as::<Line>("line", "sw1")
+            .unwrap()
+            .ckt_base()
+            .is_terminal_closed(0)
+    }
+
+    #[test]
+    fn test_delayed_action_and_lock() {
+        let mut circuit = circuit_with_switch("yes");
+        let mut control = SwtControl::new("s1");
+        edit(
+            &mut control,
+            "switchedobj=line.sw1 delay=10 action=open",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(control.get_normal(), SwitchState::Open);
+        let mut queue = ControlQueue::new();
+        control.sample(99, &circuit, &[], &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 9.0);
+        assert!(switch_closed(&circuit));
+        run_to(&mut control, &mut circuit, &mut queue, 10.0);
+        assert!(!switch_closed(&circuit));
+        assert_eq!(control.get_state(), SwitchState::Open);
+
+        // locked after the delay, the switch ignores what follows
+        edit(&mut control, "lock=yes", &circuit).unwrap();
+        control.sample(99, &circuit, &[], &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 20.0);
+        assert!(control.is_locked());
+        edit(&mut control, "state=closed", &circuit).unwrap();
+        control.sample(99, &circuit, &[], &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 20.0);
+        assert!(!switch_closed(&circuit));
+        control.reset(&mut circuit);
+        assert!(!switch_closed(&circuit));
+
+        // the reset property unlocks it and returns it to normal at once
+        edit(&mut control, "normal=closed reset=yes", &circuit).unwrap();
+        control.sample(99, &circuit, &[], &mut queue);
+        run_to(&mut control, &mut circuit, &mut queue, 20.0);
+        assert!(switch_closed(&circuit));
+        assert!(!control.is_locked());
+        let actions: Vec<&str> = queue
+            .events()
+            .events()
+            .iter()
+            .map(|event| event.action.as_str())
+            .collect();
+        assert_eq!(
+            actions,
+            ["Opened", "Locked", "Locked, state not forced", "Closed"]
+        );
+    }
+
+    #[test]
+    fn test_needs_a_switch() {
+        let circuit = circuit_with_switch("no");
+        let mut control = SwtControl::new("s1");
+        assert!(edit(&mut control, "switchedobj=line.sw1", &circuit).is_err());
+        assert!(edit(&mut control, "switchedobj=line.missing", &circuit).is_err());
+        let circuit = circuit_with_switch("yes");
+        assert!(edit(&mut control, "switchedobj=line.sw1", &circuit).is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+// TShape (Pascal TTShape): a temperature curve over time, named by the
+// tyearly, tdaily and tduty properties of PVSystem and Storage for the
+// temperature of the panels or cells during a time series simulation.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::shape_data::ShapeData;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind};
+
+static PROPERTIES: [PropertyDef; 11] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Max number of points to expect in temperature shape vectors. This gets reset to the number of Temperature values found if less than specified.",
+    },
+    PropertyDef {
+        name: "interval",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Time interval for fixed interval data, hrs. Default = 1. If Interval = 0 then time data (in hours) may be at irregular intervals and time value must be specified using either the Hour property or input files. Then values are interpolated when Interval=0, but not for fixed interval data.  \n\nSee also \"sinterval\" and \"minterval\".",
+    },
+    PropertyDef {
+        name: "temp",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of temperature values.  Units should be compatible with the object using the data. You can also use the syntax: \ntemp = (file=filename)     !for text file one value per line\ntemp = (dblfile=filename)  !for packed file of doubles\ntemp = (sngfile=filename)  !for packed file of singles \n\nNote: this property will reset Npts if the  number of values in the files are fewer.",
+    },
+    PropertyDef {
+        name: "hour",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of hour values. Only necessary to define this property for variable interval data. If the data are fixed interval, do not use this property. You can also use the syntax: \nhour = (file=filename)     !for text file one value per line\nhour = (dblfile=filename)  !for packed file of doubles\nhour = (sngfile=filename)  !for packed file of singles ",
+    },
+    PropertyDef {
+        name: "mean",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Mean of the temperature curve values.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently. Used for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "stddev",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Standard deviation of the temperatures.  This is computed on demand the first time a value is needed.  However, you may set it to another value independently.Is overwritten if you subsequently read in a curve\n\nUsed for Monte Carlo load simulations.",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  temperature curve data to a csv file containing (hour, Temp) points, or simply (Temp) values for fixed time interval data, one per line. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sngfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  temperature curve data to a binary file of singles containing (hour, Temp) points, or simply (Temp) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "dblfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  temperature curve data to a binary file of doubles containing (hour, Temp) points, or simply (Temp) values for fixed time interval data, packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sinterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in SECONDS. Alternate way to specify Interval property.",
+    },
+    PropertyDef {
+        name: "minterval",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Specify fixed interval in MINUTES. Alternate way to specify Interval property.",
+    },
+];
+
+#[derive(Debug)]
+pub struct TShapeClass;
+
+#[derive(Debug, Clone)]
+pub struct TShape {
+    base: ObjectBase,
+    shape: ShapeData,
+}
+
+impl TShape {
+    pub fn new(name: &str) -> Self {
+        TShape {
+            base: ObjectBase::new("TShape", name, &PROPERTIES),
+            shape: ShapeData::new(),
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.shape.num_points()
+    }
+
+    pub fn get_interval(&self) -> f64 {
+        self.shape.get_interval()
+    }
+
+    pub fn temperatures(&self) -> &[f64] {
+        self.shape.values()
+    }
+
+    pub fn hours(&self) -> &[f64] {
+        self.shape.hours()
+    }
+
+    pub fn get_mean(&self) -> f64 {
+        self.shape.get_mean()
+    }
+
+    pub fn get_std_dev(&self) -> f64 {
+        self.shape.get_std_dev()
+    }
+
+    // Temperature at an hour of the solution (Pascal GetTemperature); the
+    // curve repeats after its last point. None without points.
+    pub fn get_temperature(&self, hour: f64) -> Option<f64> {
+        self.shape.get_value(hour)
+    }
+}
+
+impl DssObject for TShape {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "temp" => self.shape.set_property("values", parser),
+            name => self.shape.set_property(name, parser),
+        }
+    }
+
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        self.shape.resolve(circuit)
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        let name = self.full_name();
+        self.shape.recalc(&name)
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "npts" => self.num_points().to_string(),
+            "mean" => self.get_mean().to_string(),
+            "stddev" => self.get_std_dev().to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for TShapeClass {
+    fn name(&self) -> &'static str {
+        "TShape"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(TShape::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_common::codes;
+
+    use super::*;
+    use crate::test_util::edited;
+
+    fn new_shape(properties: &str, circuit: &Circuit) -> DssResult<TShape> {
+        edited(TShape::new("t1"), &TShapeClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_temperature() {
+        let dir = std::env::temp_dir().join(format!("dss_core_tshape_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut circuit = Circuit::new("test");
+        circuit.set_current_dir(&dir);
+        std::fs::write(dir.join("temps.csv"), "0, 20\n6, 25\n12, 40\n").unwrap();
+
+        let shape = new_shape("interval=0 csvfile=temps.csv", &circuit).unwrap();
+        assert_eq!(shape.num_points(), 3);
+        assert_eq!(shape.get_temperature(3.0), Some(22.5));
+        assert_eq!(shape.get_temperature(12.0), Some(40.0));
+        assert_eq!(shape.get_property(4), "28.333333333333332");
+
+        let shape = new_shape("sinterval=1800 temp=[10 20]", &circuit).unwrap();
+        assert_eq!(shape.get_interval(), 0.5);
+        // fixed interval data are not interpolated
+        assert_eq!(shape.get_temperature(0.75), Some(10.0));
+        assert_eq!(new_shape("", &circuit).unwrap().get_temperature(1.0), None);
+
+        let err = new_shape("temp=(file=nowhere.csv)", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::FILE_ERROR);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
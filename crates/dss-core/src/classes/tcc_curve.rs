@@ -0,0 +1,299 @@
+// TCC_Curve (Pascal TTCC_Curve): a time-current characteristic of a
+// protective device, as seconds to operate against current. Relay, Fuse and
+// Recloser scale the current values by their pickup (or rated) current and
+// read the time at multiples of it, interpolating on log-log scales. Relay
+// reads its voltage curves off the same objects, linear in per unit volts.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_doubles};
+
+static PROPERTIES: [PropertyDef; 3] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Number of points to expect in time-current arrays.",
+    },
+    PropertyDef {
+        name: "c_array",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of current (or voltage) values corresponding to time values (see help on T_Array).",
+    },
+    PropertyDef {
+        name: "t_array",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Array of time values in sec. Typical array syntax: \nt_array = (1, 2, 3, 4, ...)\n\nCan also substitute a file designation: \nt_array =  (file=filename)\n\nThe specified file has one value per line.",
+    },
+];
+
+#[derive(Debug)]
+pub struct TCCCurveClass;
+
+#[derive(Debug, Clone)]
+pub struct TCCCurve {
+    base: ObjectBase,
+    // Number of points; 0 to take as many as given
+    npts: usize,
+    c_values: Vec<f64>,
+    t_values: Vec<f64>,
+}
+
+impl TCCCurve {
+    pub fn new(name: &str) -> Self {
+        TCCCurve {
+            base: ObjectBase::new("TCC_Curve", name, &PROPERTIES),
+            npts: 0,
+            c_values: Vec::new(),
+            t_values: Vec::new(),
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.c_values.len()
+    }
+
+    // The (current, seconds) points, as the devices keep them
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        self.c_values
+            .iter()
+            .copied()
+            .zip(self.t_values.iter().copied())
+            .collect()
+    }
+
+    // Seconds to operate at a multiple of the pickup (Pascal GetTCCTime);
+    // none below the first current
+    pub fn get_tcc_time(&self, multiple: f64) -> Option<f64> {
+        tcc_time(&self.points(), multiple)
+    }
+
+    // The reverse: the multiple of the pickup that operates in the given
+    // seconds, on the same log-log scales; none for times longer than the
+    // first point's, the last current for times shorter than the last's
+    pub fn get_multiple(&self, time: f64) -> Option<f64> {
+        // times fall as the current rises: by time ascending
+        let reversed: Vec<(f64, f64)> = self.points().iter().rev().map(|&(c, t)| (t, c)).collect();
+        let &(shortest, last_multiple) = reversed.first()?;
+        let &(longest, _) = reversed.last()?;
+        match time {
+            time if time > longest => None,
+            time if time <= shortest => Some(last_multiple),
+            time => tcc_time(&reversed, time),
+        }
+    }
+
+    // Seconds to operate at a per unit overvoltage (Pascal GetOVTime)
+    pub fn get_ov_time(&self, vpu: f64) -> Option<f64> {
+        overvoltage_time(&self.points(), vpu)
+    }
+
+    // Seconds to operate at a per unit undervoltage (Pascal GetUVTime)
+    pub fn get_uv_time(&self, vpu: f64) -> Option<f64> {
+        undervoltage_time(&self.points(), vpu)
+    }
+}
+
+// The points of the curve a device names, or an error naming the device
+pub(crate) fn find_tcc_points(
+    circuit: &Circuit,
+    name: &str,
+    owner: &str,
+) -> DssResult<Vec<(f64, f64)>> {
+    circuit
+        .find_object_as::<TCCCurve>("TCC_Curve", name)
+        .map(TCCCurve::points)
+        .ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("TCC Curve object \"{}\" not found for {}", name, owner),
+            )
+        })
+}
+
+// Time of a TCC curve (Pascal GetTCCTime) at a multiple of the pickup; none
+// below the first point, the time of the last beyond it, and interpolated on
+// log-log scales between
+pub(crate) fn tcc_time(points: &[(f64, f64)], multiple: f64) -> Option<f64> {
+    let &(first, first_time) = points.first()?;
+    if multiple < first {
+        return None;
+    }
+    if multiple == first {
+        return Some(first_time);
+    }
+    let Some(i) = points.iter().position(|&(c, _)| c >= multiple) else {
+        return points.last().map(|&(_, time)| time);
+    };
+    let (c0, t0) = points[i - 1];
+    let (c1, t1) = points[i];
+    let fraction = (multiple.ln() - c0.ln()) / (c1.ln() - c0.ln());
+    Some((t0.ln() + fraction * (t1.ln() - t0.ln())).exp())
+}
+
+// Time of an overvoltage curve (Pascal GetOVTime); none at or below the
+// first point
+pub(crate) fn overvoltage_time(points: &[(f64, f64)], vpu: f64) -> Option<f64> {
+    let &(first, _) = points.first()?;
+    if vpu <= first {
+        return None;
+    }
+    interpolate(points, vpu)
+}
+
+// Time of an undervoltage curve (Pascal GetUVTime); none at or above the
+// last point
+pub(crate) fn undervoltage_time(points: &[(f64, f64)], vpu: f64) -> Option<f64> {
+    let &(last, _) = points.last()?;
+    if vpu >= last {
+        return None;
+    }
+    interpolate(points, vpu)
+}
+
+// Linear between points, held at the ends
+fn interpolate(points: &[(f64, f64)], x: f64) -> Option<f64> {
+    let &(first, first_y) = points.first()?;
+    if x <= first {
+        return Some(first_y);
+    }
+    let Some(i) = points.iter().position(|&(px, _)| px >= x) else {
+        return points.last().map(|&(_, y)| y);
+    };
+    let (x0, y0) = points[i - 1];
+    let (x1, y1) = points[i];
+    Some(y0 + (x - x0) / (x1 - x0) * (y1 - y0))
+}
+
+impl DssObject for TCCCurve {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match PROPERTIES[index].name {
+            "npts" => self.npts = parser.make_integer()?.max(0) as usize,
+            "c_array" => self.c_values = read_doubles(parser)?,
+            "t_array" => self.t_values = read_doubles(parser)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.npts > 0 {
+            self.c_values.truncate(self.npts);
+            self.t_values.truncate(self.npts);
+        }
+        if self.c_values.len() != self.t_values.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has {} current values but {} time values",
+                    self.full_name(),
+                    self.c_values.len(),
+                    self.t_values.len()
+                ),
+            ));
+        }
+        // the log-log scales need positive values
+        if self
+            .c_values
+            .iter()
+            .chain(&self.t_values)
+            .any(|&v| v <= 0.0)
+        {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} needs positive current and time values",
+                    self.full_name()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "npts" => self.num_points().to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for TCCCurveClass {
+    fn name(&self) -> &'static str {
+        "TCC_Curve"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(TCCCurve::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::edited;
+
+    fn new_curve(properties: &str) -> DssResult<TCCCurve> {
+        edited(
+            TCCCurve::new("tlink"),
+            &TCCCurveClass,
+            properties,
+            &Circuit::new("test"),
+        )
+    }
+
+    #[test]
+    fn test_times() {
+        let curve = new_curve("npts=3 c_array=[2 4 8] t_array=[16 4 1]").unwrap();
+        assert_eq!(curve.get_tcc_time(1.5), None);
+        assert_eq!(curve.get_tcc_time(2.0), Some(16.0));
+        // time falls with the square of the current between the points
+        assert!((curve.get_tcc_time(2.0 * 2.0_f64.sqrt()).unwrap() - 8.0).abs() < 1e-9);
+        assert_eq!(curve.get_tcc_time(20.0), Some(1.0));
+
+        assert!((curve.get_multiple(8.0).unwrap() - 2.0 * 2.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(curve.get_multiple(16.0), Some(2.0));
+        assert_eq!(curve.get_multiple(0.5), Some(8.0));
+        assert_eq!(curve.get_multiple(20.0), None);
+
+        let curve = new_curve("c_array=[1.1 1.2] t_array=[10 2]").unwrap();
+        assert_eq!(curve.get_ov_time(1.0), None);
+        assert!((curve.get_ov_time(1.15).unwrap() - 6.0).abs() < 1e-9);
+        assert_eq!(curve.get_uv_time(1.2), None);
+
+        assert!(new_curve("c_array=[1 2] t_array=[1]").is_err());
+        assert!(new_curve("c_array=[0 2] t_array=[1 1]").is_err());
+    }
+}
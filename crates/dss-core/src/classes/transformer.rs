@@ -0,0 +1,1228 @@
+// Transformer (Pascal TTransf): a multi-phase transformer of any number of
+// windings, one terminal each. Every winding has one coil per phase, wye
+// coils between a phase and the terminal's neutral conductor, delta coils
+// between two phases. The coils are coupled through the short-circuit
+// reactances between winding pairs and the winding resistances; a winding
+// is selected with wdg= and bus=, conn=, kv= and so on then apply to it.
+// The data other than the connections may come from an XfmrCode.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::{CktElement, CktElementBase, Connection};
+use crate::class::DssClass;
+use crate::classes::xfmr_code::XfmrCode;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase, copy_like};
+use crate::pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+use crate::property::{
+    PropertyDef, PropertyKind, concat_properties, interpret_yes_no, parse_names, read_choice,
+    read_doubles,
+};
+
+const TRANSFORMER_PROPERTIES: [PropertyDef; 44] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases this transformer.",
+    },
+    PropertyDef {
+        name: "windings",
+        kind: PropertyKind::Integer,
+        default: "2",
+        help: "Number of windings, this transformers. (Also is the number of terminals) Set this property before defining the windings.",
+    },
+    PropertyDef {
+        name: "wdg",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Set this = to the number of the winding you wish to define. Then set the values for this winding.",
+    },
+    PropertyDef {
+        name: "bus",
+        kind: PropertyKind::Bus,
+        default: "",
+        help: "Bus connection spec for this winding.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "Connection of this winding {wye*, Delta, LN, LL}.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "For 2-or 3-phase, enter phase-phase kV rating. Otherwise, kV rating of the actual winding.",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "1000",
+        help: "Base kVA rating of the winding. Side effect: forces change of max normal and emerg kVA ratings.",
+    },
+    PropertyDef {
+        name: "tap",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per unit tap that this winding is on.",
+    },
+    PropertyDef {
+        name: "%r",
+        kind: PropertyKind::Double,
+        default: "0.2",
+        help: "Percent resistance this winding. (half of total for a 2-winding).",
+    },
+    PropertyDef {
+        name: "rneut",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Default = -1. Neutral resistance of wye (star)-connected winding in actual ohms. If entered as a negative value, the neutral is assumed to be open, or floating.",
+    },
+    PropertyDef {
+        name: "xneut",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Neutral reactance of wye(star)-connected winding in actual ohms. May be + or -.",
+    },
+    PropertyDef {
+        name: "buses",
+        kind: PropertyKind::Buses,
+        default: "",
+        help: "Use this to specify all the bus connections at once using an array. Example: New Transformer.T1 buses=\"Hibus, lowbus\"",
+    },
+    PropertyDef {
+        name: "conns",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Use this to specify all the Winding connections at once using an array. Example: New Transformer.T1 buses=\"Hibus, lowbus\" ~ conns=(delta, wye)",
+    },
+    PropertyDef {
+        name: "kvs",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the kV ratings of all windings at once using an array. Example: New Transformer.T1 buses=\"Hibus, lowbus\" ~ conns=(delta, wye) ~ kvs=(115, 12.47)",
+    },
+    PropertyDef {
+        name: "kvas",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the kVA ratings of all windings at once using an array.",
+    },
+    PropertyDef {
+        name: "taps",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the p.u. tap of all windings at once using an array.",
+    },
+    PropertyDef {
+        name: "xhl",
+        kind: PropertyKind::Double,
+        default: "7",
+        help: "Use this to specify the percent reactance, H-L (winding 1 to winding 2). Use for 2- or 3-winding transformers. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xht",
+        kind: PropertyKind::Double,
+        default: "35",
+        help: "Use this to specify the percent reactance, H-T (winding 1 to winding 3). Use for 3-winding transformers only. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xlt",
+        kind: PropertyKind::Double,
+        default: "30",
+        help: "Use this to specify the percent reactance, L-T (winding 2 to winding 3). Use for 3-winding transformers only. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xscarray",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the percent reactance between all pairs of windings as an array. All values are on the kVA base of winding 1. The order of the values is: (x12 13 14... 23 24.. 34 ..)",
+    },
+    PropertyDef {
+        name: "thermal",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Thermal time constant of the transformer in hours. Typically about 2.",
+    },
+    PropertyDef {
+        name: "n",
+        kind: PropertyKind::Double,
+        default: "0.8",
+        help: "n Exponent for thermal properties in IEEE C57. Typically 0.8.",
+    },
+    PropertyDef {
+        name: "m",
+        kind: PropertyKind::Double,
+        default: "0.8",
+        help: "m Exponent for thermal properties in IEEE C57. Typically 0.9 - 1.0",
+    },
+    PropertyDef {
+        name: "flrise",
+        kind: PropertyKind::Double,
+        default: "65",
+        help: "Temperature rise, deg C, for full load. Default is 65.",
+    },
+    PropertyDef {
+        name: "hsrise",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Hot spot temperature rise, deg C. Default is 15.",
+    },
+    PropertyDef {
+        name: "%loadloss",
+        kind: PropertyKind::Double,
+        default: "0.4",
+        help: "Percent load loss at full load. The %R of the High and Low windings (1 and 2) are adjusted to agree at rated kVA loading.",
+    },
+    PropertyDef {
+        name: "%noloadloss",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Percent no load losses at rated excitation voltage. Default is zero. Converts to a resistance in parallel with the magnetizing impedance.",
+    },
+    PropertyDef {
+        name: "normhkva",
+        kind: PropertyKind::Double,
+        default: "1100",
+        help: "Normal maximum kVA rating of H winding (winding 1). Usually 100% - 110% of maximum nameplate rating, depending on load shape. Defaults to 110% of kVA rating of Winding 1.",
+    },
+    PropertyDef {
+        name: "emerghkva",
+        kind: PropertyKind::Double,
+        default: "1500",
+        help: "Emergency (contingency) kVA rating of H winding (winding 1). Usually 140% - 150% of maximum nameplate rating, depending on load shape. Defaults to 150% of kVA rating of Winding 1.",
+    },
+    PropertyDef {
+        name: "sub",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "={Yes|No} Designates whether this transformer is to be considered a substation. Default is No.",
+    },
+    PropertyDef {
+        name: "maxtap",
+        kind: PropertyKind::Double,
+        default: "1.1",
+        help: "Max per unit tap for the active winding. Default is 1.10",
+    },
+    PropertyDef {
+        name: "mintap",
+        kind: PropertyKind::Double,
+        default: "0.9",
+        help: "Min per unit tap for the active winding. Default is 0.90",
+    },
+    PropertyDef {
+        name: "numtaps",
+        kind: PropertyKind::Integer,
+        default: "32",
+        help: "Total number of taps between min and max tap. Default is 32 (16 raise and 16 lower taps about the neutral position).",
+    },
+    PropertyDef {
+        name: "subname",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Substation Name. Optional. Default is null. If specified, printed on plots",
+    },
+    PropertyDef {
+        name: "%imag",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Percent magnetizing current. Default=0.0. Magnetizing branch is in parallel with windings in each phase.",
+    },
+    PropertyDef {
+        name: "ppm_antifloat",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Default=1 ppm. Parts per million of transformer winding VA rating connected to GROUND to protect against accidentally floating a winding without a reference. If positive then the effect is adding a very large reactance to ground.",
+    },
+    PropertyDef {
+        name: "%rs",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this property to specify all the winding %resistances using an array. Example: New Transformer.T1 buses=\"Hibus, lowbus\" ~ %Rs=(0.2  0.3)",
+    },
+    PropertyDef {
+        name: "bank",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Name of the bank this transformer is part of, for CIM, MultiSpeak, and other interfaces.",
+    },
+    PropertyDef {
+        name: "xfmrcode",
+        kind: PropertyKind::Object("XfmrCode"),
+        default: "",
+        help: "Name of a library entry for transformer properties. The named XfmrCode must already be defined.",
+    },
+    PropertyDef {
+        name: "xrconst",
+        kind: PropertyKind::Bool,
+        default: "no",
+        help: "={Yes|No} Default is NO. Signifies whether or not the X/R is assumed contant for harmonic studies.",
+    },
+    PropertyDef {
+        name: "x12",
+        kind: PropertyKind::Double,
+        default: "7",
+        help: "Alternative to XHL for specifying the percent reactance from winding 1 to winding 2.",
+    },
+    PropertyDef {
+        name: "x13",
+        kind: PropertyKind::Double,
+        default: "35",
+        help: "Alternative to XHT for specifying the percent reactance from winding 1 to winding 3.",
+    },
+    PropertyDef {
+        name: "x23",
+        kind: PropertyKind::Double,
+        default: "30",
+        help: "Alternative to XLT for specifying the percent reactance from winding 2 to winding 3.",
+    },
+    PropertyDef {
+        name: "leadlag",
+        kind: PropertyKind::Choice(&["lag", "lead", "ansi", "euro"]),
+        default: "lag",
+        help: "{Lead | Lag (default) | ANSI (default) | Euro } Designation in mixed Delta-wye connections the relationship between HV to LV winding. Default is ANSI 30 deg lag, e.g., Dy1 of Yd1 vector group. To get typical European Dy11 connection, specify either \"lead\" or \"Euro\"",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 51] = concat_properties(&TRANSFORMER_PROPERTIES, &PD_PROPERTIES);
+
+// Admittance of a neutral given as zero ohms
+const SOLID_NEUTRAL: f64 = 1.0e6;
+
+#[derive(Debug, Clone)]
+struct Winding {
+    connection: Connection,
+    kv: f64,
+    kva: f64,
+    tap: f64,
+    pct_r: f64,
+    rneut: f64,
+    xneut: f64,
+    max_tap: f64,
+    min_tap: f64,
+    num_taps: usize,
+}
+
+impl Winding {
+    fn new() -> Self {
+        Winding {
+            connection: Connection::Wye,
+            kv: 12.47,
+            kva: 1000.0,
+            tap: 1.0,
+            pct_r: 0.2,
+            rneut: -1.0,
+            xneut: 0.0,
+            max_tap: 1.1,
+            min_tap: 0.9,
+            num_taps: 32,
+        }
+    }
+
+    // Rated voltage across one coil
+    fn coil_volts(&self, nphases: usize) -> f64 {
+        if self.connection == Connection::Wye && nphases > 1 {
+            self.kv * 1000.0 / 3.0_f64.sqrt()
+        } else {
+            self.kv * 1000.0
+        }
+    }
+}
+
+impl Default for Winding {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The electrical data of a transformer, which an XfmrCode holds for the
+// transformers naming it. Properties are taken by name, the two classes
+// ordering their tables differently.
+#[derive(Debug, Clone)]
+pub(crate) struct TransformerData {
+    nphases: usize,
+    windings: Vec<Winding>,
+    // 0-based winding wdg= selected
+    active: usize,
+    // Per unit reactances between winding pairs, 1-2, 1-3, .. 1-n, 2-3, ..
+    xsc: Vec<f64>,
+    pct_imag: f64,
+    pct_noload_loss: f64,
+    ppm_antifloat: f64,
+    // Ratings of winding 1; 110% and 150% of its kVA unless given
+    norm_hkva: Option<f64>,
+    emerg_hkva: Option<f64>,
+    thermal: f64,
+    n_thermal: f64,
+    m_thermal: f64,
+    fl_rise: f64,
+    hs_rise: f64,
+}
+
+impl TransformerData {
+    pub(crate) fn new() -> Self {
+        let mut data = TransformerData {
+            nphases: 3,
+            windings: Vec::new(),
+            active: 0,
+            xsc: Vec::new(),
+            pct_imag: 0.0,
+            pct_noload_loss: 0.0,
+            ppm_antifloat: 1.0,
+            norm_hkva: None,
+            emerg_hkva: None,
+            thermal: 2.0,
+            n_thermal: 0.8,
+            m_thermal: 0.8,
+            fl_rise: 65.0,
+            hs_rise: 15.0,
+        };
+        data.set_windings(2);
+        data
+    }
+
+    pub(crate) fn nphases(&self) -> usize {
+        self.nphases
+    }
+
+    pub(crate) fn nwindings(&self) -> usize {
+        self.windings.len()
+    }
+
+    // Normal and emergency kVA of winding 1
+    pub(crate) fn ratings(&self) -> (f64, f64) {
+        let kva = self.windings[0].kva;
+        (
+            self.norm_hkva.unwrap_or(1.1 * kva),
+            self.emerg_hkva.unwrap_or(1.5 * kva),
+        )
+    }
+
+    fn pair_index(&self, i: usize, j: usize) -> usize {
+        let n = self.windings.len();
+        i * (2 * n - i - 1) / 2 + (j - i - 1)
+    }
+
+    // Sets the winding count (Pascal SetNumWindings); windings added take
+    // the defaults and the reactances of existing pairs are kept
+    fn set_windings(&mut self, nwindings: usize) {
+        let old = std::mem::take(&mut self.xsc);
+        let old_count = self.windings.len();
+        self.windings.resize_with(nwindings, Winding::new);
+        for i in 0..nwindings {
+            for j in i + 1..nwindings {
+                let x = if j < old_count {
+                    old[i * (2 * old_count - i - 1) / 2 + (j - i - 1)]
+                } else {
+                    match (i, j) {
+                        (0, 1) => 0.07,
+                        (0, 2) => 0.35,
+                        _ => 0.30,
+                    }
+                };
+                self.xsc.push(x);
+            }
+        }
+        self.active = self.active.min(nwindings - 1);
+    }
+
+    // Sets the reactance of a pair in percent; pairs the transformer does
+    // not have are ignored
+    fn set_pct_x(&mut self, i: usize, j: usize, pct: f64) {
+        if j < self.windings.len() {
+            let index = self.pair_index(i, j);
+            self.xsc[index] = pct / 100.0;
+        }
+    }
+
+    // Values of an array property, one per winding at most
+    fn read_winding_values(
+        &self,
+        parser: &mut DSSParser,
+        property: &str,
+        owner: &str,
+    ) -> DssResult<Vec<f64>> {
+        let values = read_doubles(parser)?;
+        self.check_winding_count(values.len(), property, owner)?;
+        Ok(values)
+    }
+
+    pub(crate) fn check_winding_count(
+        &self,
+        count: usize,
+        property: &str,
+        owner: &str,
+    ) -> DssResult<()> {
+        if count > self.windings.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} values given for \"{}\" of {} with {} windings",
+                    count,
+                    property,
+                    owner,
+                    self.windings.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // Takes the property `name` from the parser; `owner` names the
+    // transformer or code for the errors
+    pub(crate) fn set_property(
+        &mut self,
+        name: &str,
+        parser: &mut DSSParser,
+        owner: &str,
+    ) -> DssResult<()> {
+        let active = self.active;
+        match name {
+            "phases" => self.nphases = parser.make_integer()?.max(1) as usize,
+            "windings" => {
+                let nwindings = parser.make_integer()?;
+                if nwindings < 2 {
+                    return Err(DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!("Invalid number of windings ({}) for {}", nwindings, owner),
+                    ));
+                }
+                self.set_windings(nwindings as usize);
+            }
+            "wdg" => {
+                let winding = parser.make_integer()?;
+                if winding < 1 || winding as usize > self.windings.len() {
+                    return Err(DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!(
+                            "Winding {} is out of range for {} with {} windings",
+                            winding,
+                            owner,
+                            self.windings.len()
+                        ),
+                    ));
+                }
+                self.active = winding as usize - 1;
+            }
+            "conn" => {
+                let name = read_choice(parser, Connection::NAMES, "conn")?;
+                self.windings[active].connection = Connection::from_name(name);
+            }
+            "kv" => self.windings[active].kv = parser.make_double()?,
+            "kva" => self.windings[active].kva = parser.make_double()?,
+            "tap" => self.windings[active].tap = parser.make_double()?,
+            "%r" => self.windings[active].pct_r = parser.make_double()?,
+            "rneut" => self.windings[active].rneut = parser.make_double()?,
+            "xneut" => self.windings[active].xneut = parser.make_double()?,
+            "conns" => {
+                let token = parser.get_token().to_string();
+                let names = parse_names(&token);
+                self.check_winding_count(names.len(), "conns", owner)?;
+                for (winding, name) in names.iter().enumerate() {
+                    parser.set_token(name);
+                    let name = read_choice(parser, Connection::NAMES, "conns")?;
+                    self.windings[winding].connection = Connection::from_name(name);
+                }
+                parser.set_token(&token);
+            }
+            "kvs" => {
+                let values = self.read_winding_values(parser, "kvs", owner)?;
+                for (winding, kv) in self.windings.iter_mut().zip(values) {
+                    winding.kv = kv;
+                }
+            }
+            "kvas" => {
+                let values = self.read_winding_values(parser, "kvas", owner)?;
+                for (winding, kva) in self.windings.iter_mut().zip(values) {
+                    winding.kva = kva;
+                }
+            }
+            "taps" => {
+                let values = self.read_winding_values(parser, "taps", owner)?;
+                for (winding, tap) in self.windings.iter_mut().zip(values) {
+                    winding.tap = tap;
+                }
+            }
+            "%rs" => {
+                let values = self.read_winding_values(parser, "%rs", owner)?;
+                for (winding, pct_r) in self.windings.iter_mut().zip(values) {
+                    winding.pct_r = pct_r;
+                }
+            }
+            "xhl" | "x12" => self.set_pct_x(0, 1, parser.make_double()?),
+            "xht" | "x13" => self.set_pct_x(0, 2, parser.make_double()?),
+            "xlt" | "x23" => self.set_pct_x(1, 2, parser.make_double()?),
+            "xscarray" => {
+                let values = read_doubles(parser)?;
+                if values.len() > self.xsc.len() {
+                    return Err(DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!(
+                            "{} reactances given for {} with {} winding pairs",
+                            values.len(),
+                            owner,
+                            self.xsc.len()
+                        ),
+                    ));
+                }
+                for (x, pct) in self.xsc.iter_mut().zip(values) {
+                    *x = pct / 100.0;
+                }
+            }
+            "thermal" => self.thermal = parser.make_double()?,
+            "n" => self.n_thermal = parser.make_double()?,
+            "m" => self.m_thermal = parser.make_double()?,
+            "flrise" => self.fl_rise = parser.make_double()?,
+            "hsrise" => self.hs_rise = parser.make_double()?,
+            "%loadloss" => {
+                let pct = parser.make_double()?;
+                self.windings[0].pct_r = pct / 2.0;
+                self.windings[1].pct_r = pct / 2.0;
+            }
+            "%noloadloss" => self.pct_noload_loss = parser.make_double()?,
+            "normhkva" => self.norm_hkva = Some(parser.make_double()?),
+            "emerghkva" => self.emerg_hkva = Some(parser.make_double()?),
+            "maxtap" => self.windings[active].max_tap = parser.make_double()?,
+            "mintap" => self.windings[active].min_tap = parser.make_double()?,
+            "numtaps" => self.windings[active].num_taps = parser.make_integer()?.max(1) as usize,
+            "%imag" => self.pct_imag = parser.make_double()?,
+            "ppm_antifloat" => self.ppm_antifloat = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Default for TransformerData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct TransformerClass;
+
+#[derive(Debug, Clone)]
+pub struct Transformer {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pd: PdElementBase,
+    data: TransformerData,
+    xfmr_code: String,
+    is_sub: bool,
+    sub_name: String,
+    bank: String,
+    xr_const: bool,
+    // Low voltage side of a delta-wye bank 30 degrees ahead of the high
+    // rather than behind
+    lead: bool,
+}
+
+impl Transformer {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 2);
+        ckt.set_conductors(4);
+        let mut transformer = Transformer {
+            base: ObjectBase::new("Transformer", name, &PROPERTIES),
+            ckt,
+            pd: PdElementBase::new(),
+            data: TransformerData::new(),
+            xfmr_code: String::new(),
+            is_sub: false,
+            sub_name: String::new(),
+            bank: String::new(),
+            xr_const: false,
+            lead: false,
+        };
+        transformer.set_ratings();
+        transformer
+    }
+
+    pub fn nwindings(&self) -> usize {
+        self.data.nwindings()
+    }
+
+    pub fn get_kv(&self, winding: usize) -> f64 {
+        self.data.windings[winding].kv
+    }
+
+    // Rated kV of each winding
+    pub fn winding_kvs(&self) -> Vec<f64> {
+        self.data
+            .windings
+            .iter()
+            .map(|winding| winding.kv)
+            .collect()
+    }
+
+    pub fn get_kva(&self, winding: usize) -> f64 {
+        self.data.windings[winding].kva
+    }
+
+    pub fn get_connection(&self, winding: usize) -> Connection {
+        self.data.windings[winding].connection
+    }
+
+    pub fn get_tap(&self, winding: usize) -> f64 {
+        self.data.windings[winding].tap
+    }
+
+    // Moves a winding's tap, kept within its limits
+    pub fn set_tap(&mut self, winding: usize, tap: f64) {
+        let winding = &mut self.data.windings[winding];
+        winding.tap = tap.clamp(winding.min_tap, winding.max_tap);
+        self.ckt.invalidate_yprim();
+    }
+
+    // Min and max per unit tap of a winding and the number of steps between
+    pub fn tap_range(&self, winding: usize) -> (f64, f64, usize) {
+        let winding = &self.data.windings[winding];
+        (winding.min_tap, winding.max_tap, winding.num_taps)
+    }
+
+    // Percent reactance between windings `i` and `j` on winding 1's kVA
+    pub fn get_pct_x(&self, i: usize, j: usize) -> f64 {
+        let (i, j) = (i.min(j), i.max(j));
+        self.data.xsc[self.data.pair_index(i, j)] * 100.0
+    }
+
+    pub fn get_xfmr_code(&self) -> &str {
+        &self.xfmr_code
+    }
+
+    pub fn is_sub(&self) -> bool {
+        self.is_sub
+    }
+
+    pub fn get_sub_name(&self) -> &str {
+        &self.sub_name
+    }
+
+    pub fn get_bank(&self) -> &str {
+        &self.bank
+    }
+
+    // Phases and terminals follow the data
+    fn sync_terminals(&mut self) {
+        let nphases = self.data.nphases();
+        if nphases != self.ckt.nphases() {
+            self.ckt.set_phases(nphases);
+            self.ckt.set_conductors(nphases + 1);
+        }
+        if self.data.nwindings() != self.ckt.nterms() {
+            self.ckt.set_terminals(self.data.nwindings());
+        }
+    }
+
+    // Takes over the data of a code (Pascal FetchXfmrCode); properties set
+    // after xfmrcode= still override it
+    fn fetch_xfmr_code(&mut self, code: &XfmrCode) {
+        self.data = code.data().clone();
+        self.sync_terminals();
+    }
+
+    // Normal and emergency amps of winding 1 from its kVA ratings
+    fn set_ratings(&mut self) {
+        let (norm_hkva, emerg_hkva) = self.data.ratings();
+        let winding = &self.data.windings[0];
+        let kv = if self.ckt.nphases() > 1 {
+            winding.kv * 3.0_f64.sqrt()
+        } else {
+            winding.kv
+        };
+        self.pd.set_norm_amps(norm_hkva / kv);
+        self.pd.set_emerg_amps(emerg_hkva / kv);
+    }
+
+    // Conductors of the winding's terminal the coil of `phase` spans.
+    // Delta coils are turned so that, with the default lag, the windings
+    // after the first are 30 degrees behind it when their connections
+    // differ (ANSI Dy1 and Yd1).
+    fn coil_ends(&self, winding: usize, phase: usize) -> (usize, usize) {
+        let nphases = self.ckt.nphases();
+        match self.data.windings[winding].connection {
+            Connection::Wye => (phase, nphases),
+            Connection::Delta if nphases == 1 => (0, 1),
+            Connection::Delta => {
+                if (winding == 0) == self.lead {
+                    (phase, (phase + 1) % nphases)
+                } else {
+                    (phase, (phase + nphases - 1) % nphases)
+                }
+            }
+        }
+    }
+
+    // Core losses and magnetizing admittance across a coil of winding 1
+    fn no_load_admittance(&self) -> Complex64 {
+        let nphases = self.ckt.nphases();
+        let winding = &self.data.windings[0];
+        let va = winding.kva * 1000.0 / nphases as f64;
+        let volts = winding.coil_volts(nphases);
+        Complex64::new(self.data.pct_noload_loss, -self.data.pct_imag) / 100.0 * va
+            / (volts * volts)
+    }
+
+    // Admittances between the ends of all the coils of one phase, two ends
+    // per winding (Pascal CalcY_Terminal): the short-circuit impedances from
+    // winding 1 on a one-volt, one-turn base, then scaled by each coil's
+    // voltage and tap
+    fn coil_admittances(&self, frequency: f64) -> DssResult<CMatrix> {
+        let windings = &self.data.windings;
+        let nwindings = windings.len();
+        let nphases = self.ckt.nphases();
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        let va_base = windings[0].kva * 1000.0 / nphases as f64;
+        let zsc = |i: usize, j: usize| {
+            let r = (windings[i].pct_r + windings[j].pct_r) / 100.0;
+            Complex64::new(r, self.data.xsc[self.data.pair_index(i, j)] * freq_mult) / va_base
+        };
+        let mut yb = CMatrix::new(nwindings - 1);
+        for i in 1..nwindings {
+            yb.set(i - 1, i - 1, zsc(0, i));
+            for j in 1..i {
+                yb.set_sym(i - 1, j - 1, (zsc(0, i) + zsc(0, j) - zsc(j, i)) / 2.0);
+            }
+        }
+        if !yb.invert() {
+            return Err(DssError::new(
+                codes::SINGULAR_MATRIX,
+                &format!(
+                    "Short-circuit impedances are singular for {}",
+                    self.full_name()
+                ),
+            ));
+        }
+
+        // each loop runs through coil 1 and back through coil k + 1
+        let incidence = |k: usize, end: usize| match end {
+            0 => 1.0,
+            1 => -1.0,
+            _ if end / 2 == k + 1 => [-1.0, 1.0][end % 2],
+            _ => 0.0,
+        };
+        let turns: Vec<f64> = windings
+            .iter()
+            .map(|winding| winding.coil_volts(nphases) * winding.tap)
+            .collect();
+        let order = 2 * nwindings;
+        let mut y = CMatrix::new(order);
+        for p in 0..order {
+            for q in 0..order {
+                let mut sum = Complex64::new(0.0, 0.0);
+                for k in 0..nwindings - 1 {
+                    for l in 0..nwindings - 1 {
+                        sum += yb.get(k, l) * incidence(k, p) * incidence(l, q);
+                    }
+                }
+                y.set(p, q, sum / (turns[p / 2] * turns[q / 2]));
+            }
+        }
+
+        let no_load = self.no_load_admittance();
+        y.add(0, 0, no_load);
+        y.add(1, 1, no_load);
+        y.add(0, 1, -no_load);
+        y.add(1, 0, -no_load);
+        Ok(y)
+    }
+}
+
+impl DssObject for Transformer {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = TRANSFORMER_PROPERTIES.get(index) else {
+            return self.set_pd_property(index - TRANSFORMER_PROPERTIES.len(), parser);
+        };
+        match property.name {
+            "bus" => self.ckt.set_bus(self.data.active, parser.get_token()),
+            "buses" => {
+                let buses = parse_names(parser.get_token());
+                self.data
+                    .check_winding_count(buses.len(), "buses", &self.full_name())?;
+                for (winding, bus) in buses.iter().enumerate() {
+                    self.ckt.set_bus(winding, bus);
+                }
+            }
+            "xfmrcode" => self.xfmr_code = parser.get_token().to_lowercase(),
+            "sub" => self.is_sub = interpret_yes_no(parser.get_token()),
+            "subname" => self.sub_name = parser.get_token().to_string(),
+            "bank" => self.bank = parser.get_token().to_string(),
+            "xrconst" => self.xr_const = interpret_yes_no(parser.get_token()),
+            "leadlag" => {
+                let choice = read_choice(parser, &["lag", "lead", "ansi", "euro"], "leadlag")?;
+                self.lead = matches!(choice, "lead" | "euro");
+            }
+            name => {
+                let owner = self.full_name();
+                self.data.set_property(name, parser, &owner)?;
+                self.sync_terminals();
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, index: usize, circuit: &Circuit) -> DssResult<()> {
+        if TRANSFORMER_PROPERTIES
+            .get(index)
+            .map(|property| property.name)
+            != Some("xfmrcode")
+        {
+            return Ok(());
+        }
+        let code = circuit
+            .find_object_as::<XfmrCode>("XfmrCode", &self.xfmr_code)
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::OBJECT_NOT_FOUND,
+                    &format!(
+                        "Transformer code \"{}\" not found for {}",
+                        self.xfmr_code,
+                        self.full_name()
+                    ),
+                )
+            })?;
+        self.fetch_xfmr_code(code);
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.set_ratings();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    // The properties of the windings follow the selector set before them,
+    // which setting them again one by one would lose
+    fn make_like(&mut self, other: &dyn DssObject, _circuit: &Circuit) -> DssResult<()> {
+        copy_like(self, other);
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Transformer {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pd_element(&self) -> Option<&dyn PdElement> {
+        Some(self)
+    }
+
+    // The coil admittances of each phase placed between the conductors the
+    // coils span, then the neutral impedances and the anti-float reactors
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nphases = self.ckt.nphases();
+        let nconds = self.ckt.nconds();
+        let coils = self.coil_admittances(frequency)?;
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for phase in 0..nphases {
+            let nodes: Vec<usize> = (0..self.data.nwindings())
+                .flat_map(|winding| {
+                    let (a, b) = self.coil_ends(winding, phase);
+                    [winding * nconds + a, winding * nconds + b]
+                })
+                .collect();
+            for (p, &row) in nodes.iter().enumerate() {
+                for (q, &col) in nodes.iter().enumerate() {
+                    yprim.add(row, col, coils.get(p, q));
+                }
+            }
+        }
+
+        let freq_mult = frequency / self.ckt.get_base_frequency();
+        for (i, winding) in self.data.windings.iter().enumerate() {
+            if winding.connection == Connection::Wye && winding.rneut >= 0.0 {
+                let z = Complex64::new(winding.rneut, winding.xneut * freq_mult);
+                let y = if z.norm() == 0.0 {
+                    Complex64::new(SOLID_NEUTRAL, 0.0)
+                } else {
+                    z.inv()
+                };
+                yprim.add(i * nconds + nphases, i * nconds + nphases, y);
+            }
+            let volts = winding.coil_volts(nphases);
+            let va = winding.kva * 1000.0 / nphases as f64;
+            let y_ppm = Complex64::new(0.0, -self.data.ppm_antifloat * 1e-6 * va / (volts * volts));
+            for phase in 0..nphases {
+                yprim.add(i * nconds + phase, i * nconds + phase, y_ppm);
+            }
+        }
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+}
+
+impl PdElement for Transformer {
+    fn pd_base(&self) -> &PdElementBase {
+        &self.pd
+    }
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase {
+        &mut self.pd
+    }
+
+    // The no-load part is what the core takes across the coils of winding 1
+    fn losses(&self, voltages: &[Complex64]) -> (Complex64, Complex64, Complex64) {
+        let total = self.total_power(voltages);
+        let terminal = self.terminal_voltages(voltages);
+        let y = self.no_load_admittance();
+        let no_load: Complex64 = (0..self.ckt.nphases())
+            .map(|phase| {
+                let (a, b) = self.coil_ends(0, phase);
+                let v = terminal[a] - terminal[b];
+                v * (v * y).conj()
+            })
+            .sum();
+        (total, total - no_load, no_load)
+    }
+}
+
+impl DssClass for TransformerClass {
+    fn name(&self) -> &'static str {
+        "Transformer"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Transformer::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::xfmr_code::XfmrCodeClass;
+    use crate::test_util::{assert_close_rel, edit, edited, placed};
+
+    fn new_transformer(properties: &str) -> Transformer {
+        let transformer = edited(
+            Transformer::new("t1"),
+            &TransformerClass,
+            properties,
+            &Circuit::new("test"),
+        )
+        .unwrap();
+        placed(transformer, &[]).unwrap()
+    }
+
+    // Yprim with the `grounded` nodes removed and the `open` ones reduced
+    // out, leaving the driving-point admittances of the rest
+    fn reduced(transformer: &Transformer, grounded: &[usize], open: &[usize]) -> CMatrix {
+        let yprim = transformer.ckt_base().get_yprim().unwrap();
+        let kept: Vec<usize> = (0..yprim.order())
+            .filter(|node| !grounded.contains(node))
+            .collect();
+        let mut y = CMatrix::new(kept.len());
+        for (i, &row) in kept.iter().enumerate() {
+            for (j, &col) in kept.iter().enumerate() {
+                y.set(i, j, yprim.get(row, col));
+            }
+        }
+        let mut open: Vec<usize> = open
+            .iter()
+            .map(|node| kept.iter().position(|k| k == node).unwrap())
+            .collect();
+        open.sort_unstable();
+        for &node in open.iter().rev() {
+            y = y.kron(node);
+        }
+        y
+    }
+
+    #[test]
+    fn test_short_circuit_impedance() {
+        let transformer = new_transformer(
+            "phases=1 windings=2 buses=[a b] kvs=[7.2 0.24] kvas=[50 50] xhl=2 %loadloss=2",
+        );
+        assert_eq!((transformer.nconds(), transformer.nterms()), (2, 2));
+        assert_eq!(transformer.get_bus(1), "b");
+        // driven from the high side, the low side shorted
+        let y = reduced(&transformer, &[1, 2, 3], &[]);
+        let zbase = 7200.0 * 7200.0 / 50_000.0;
+        assert_close_rel(y.get(0, 0).inv(), Complex64::new(0.02, 0.02) * zbase, 1e-4);
+        // ratings from 110% and 150% of the kVA
+        assert!((transformer.pd_base().get_norm_amps() - 55.0 / 7.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_three_windings() {
+        let transformer = new_transformer(
+            "phases=1 windings=3 buses=[a b c] kvs=[7.2 0.12 0.12] kvas=[25 25 25] \
+             %rs=[0.6 1.2 1.2] xscarray=[2.04 2.04 1.36]",
+        );
+        assert!((transformer.get_pct_x(2, 1) - 1.36).abs() < 1e-12);
+        // winding 2 driven, winding 3 shorted, winding 1 open
+        let y = reduced(&transformer, &[1, 3, 4, 5], &[0]);
+        let zbase = 120.0 * 120.0 / 25_000.0;
+        assert_close_rel(
+            y.get(0, 0).inv(),
+            Complex64::new(0.024, 0.0136) * zbase,
+            1e-6,
+        );
+
+        let mut transformer = Transformer::new("t2");
+        let circuit = Circuit::new("test");
+        assert!(edit(&mut transformer, &TransformerClass, "windings=1", &circuit).is_err());
+        assert!(edit(&mut transformer, &TransformerClass, "kvs=[1 2 3]", &circuit).is_err());
+    }
+
+    #[test]
+    fn test_xfmr_code() {
+        let mut circuit = Circuit::new("test");
+        let mut code = XfmrCodeClass.new_object("ct50");
+        edit(
+            code.as_mut(),
+            &XfmrCodeClass,
+            "phases=1 windings=3 kvs=[7.2 0.12 0.12] kvas=[50 50 50] xscarray=[2.04 2.04 1.36]",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(code);
+
+        let mut transformer = edited(
+            Transformer::new("t1"),
+            &TransformerClass,
+            "xfmrcode=ct50 buses=[a b.1.0 b.0.2] wdg=2 tap=1.05",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(transformer.get_xfmr_code(), "ct50");
+        assert_eq!((transformer.nphases(), transformer.nconds()), (1, 2));
+        assert_eq!(transformer.nwindings(), 3);
+        assert_eq!(transformer.get_bus(2), "b.0.2");
+        assert_eq!(transformer.winding_kvs(), [7.2, 0.12, 0.12]);
+        assert_eq!(
+            (transformer.get_tap(0), transformer.get_tap(1)),
+            (1.0, 1.05)
+        );
+        assert!((transformer.get_pct_x(1, 2) - 1.36).abs() < 1e-12);
+        assert!((transformer.pd_base().get_norm_amps() - 55.0 / 7.2).abs() < 1e-9);
+
+        let err = edit(
+            &mut transformer,
+            &TransformerClass,
+            "xfmrcode=missing",
+            &circuit,
+        )
+        .unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_like() {
+        let mut circuit = Circuit::new("test");
+        let transformer = edited(
+            Transformer::new("t1"),
+            &TransformerClass,
+            "phases=1 wdg=1 bus=a kv=7.2 wdg=2 bus=b kv=0.24 tap=1.025",
+            &circuit,
+        )
+        .unwrap();
+        circuit.add_element(Box::new(transformer));
+
+        let copy = edited(
+            Transformer::new("t2"),
+            &TransformerClass,
+            "like=t1 wdg=2 bus=c",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(copy.name(), "t2");
+        assert_eq!(copy.winding_kvs(), [7.2, 0.24]);
+        assert_eq!((copy.get_bus(0), copy.get_bus(1)), ("a", "c"));
+        assert_eq!(copy.get_tap(1), 1.025);
+    }
+
+    // Open-circuit low side voltages of a bank for balanced high side ones
+    fn low_side_voltages(transformer: &Transformer) -> Vec<Complex64> {
+        let yprim = transformer.ckt_base().get_yprim().unwrap();
+        let high = Complex64::from_polar(12_470.0 / 3.0_f64.sqrt(), 0.0);
+        let shift = Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI / 3.0);
+        let vp = [high, high * shift, high * shift * shift];
+        let mut yss = CMatrix::new(3);
+        let mut injection = vec![Complex64::new(0.0, 0.0); 3];
+        for (i, current) in injection.iter_mut().enumerate() {
+            for (j, v) in vp.iter().enumerate() {
+                yss.set(i, j, yprim.get(4 + i, 4 + j));
+                *current -= yprim.get(4 + i, j) * v;
+            }
+        }
+        assert!(yss.invert());
+        yss.mv_mult(&injection)
+    }
+
+    #[test]
+    fn test_delta_wye_phase_shift() {
+        let transformer = new_transformer("conns=[delta wye] kvs=[12.47 0.208] kvas=[500 500]");
+        let va = low_side_voltages(&transformer)[0];
+        assert!((va.norm() - 208.0 / 3.0_f64.sqrt()).abs() < 1e-3);
+        assert!((va.arg().to_degrees() + 30.0).abs() < 1e-3);
+
+        let transformer =
+            new_transformer("conns=[delta wye] kvs=[12.47 0.208] kvas=[500 500] leadlag=euro");
+        let va = low_side_voltages(&transformer)[0];
+        assert!((va.arg().to_degrees() - 30.0).abs() < 1e-3);
+
+        let transformer = new_transformer("conns=[wye delta] kvs=[12.47 0.208]");
+        let vab = {
+            let v = low_side_voltages(&transformer);
+            v[0] - v[1]
+        };
+        assert!((vab.norm() - 208.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_no_load_losses() {
+        let transformer = new_transformer(
+            "phases=1 buses=[a b] kvs=[7.2 0.24] kvas=[50 50] %noloadloss=0.5 %imag=1",
+        );
+        // high side at rated voltage, the low side open
+        let mut voltages = vec![Complex64::new(0.0, 0.0); 5];
+        voltages[1] = Complex64::new(7200.0, 0.0);
+        let transformer = placed(transformer, &[&[1, 0], &[2, 0]]).unwrap();
+        let (_, _, no_load) = transformer.losses(&voltages);
+        assert_close_rel(no_load, Complex64::new(250.0, 500.0), 1e-9);
+    }
+}
@@ -0,0 +1,154 @@
+// TSData (Pascal TTSData): a tape shielded cable, the core surrounded by
+// insulation and a helically wrapped copper tape that acts as one equivalent
+// conductor.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::class::DssClass;
+use crate::classes::cable_data::{CABLE_PROPERTIES, CableData};
+use crate::line_constants::{Conductor, Shield};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties};
+
+// Resistivity of the copper tape, ohm-m
+const RHO_COPPER: f64 = 2.3718e-8;
+
+static PROPERTIES: [PropertyDef; 18] = concat_properties(
+    &[
+        PropertyDef {
+            name: "diashield",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Diameter over tape shield; same units as radius; no default.",
+        },
+        PropertyDef {
+            name: "tapelayer",
+            kind: PropertyKind::Double,
+            default: "-1",
+            help: "Tape shield thickness; same units as radius; no default.",
+        },
+        PropertyDef {
+            name: "tapelap",
+            kind: PropertyKind::Double,
+            default: "20",
+            help: "Tape Lap in percent.",
+        },
+    ],
+    &CABLE_PROPERTIES,
+);
+
+#[derive(Debug)]
+pub struct TSDataClass;
+
+#[derive(Debug, Clone)]
+pub struct TSData {
+    base: ObjectBase,
+    cable: CableData,
+    dia_shield: f64,
+    tape_layer: f64,
+    tape_lap: f64,
+}
+
+impl TSData {
+    pub fn new(name: &str) -> Self {
+        TSData {
+            base: ObjectBase::new("TSData", name, &PROPERTIES),
+            cable: CableData::new(),
+            dia_shield: -1.0,
+            tape_layer: -1.0,
+            tape_lap: 20.0,
+        }
+    }
+
+    pub fn cable(&self) -> &CableData {
+        &self.cable
+    }
+
+    // The cable at (x, h) in meters, its tape as the shield
+    pub fn to_conductor(&self, x: f64, h: f64, name: &str) -> DssResult<Conductor> {
+        self.cable.check(name)?;
+        if self.tape_layer <= 0.0
+            || 2.0 * self.tape_layer >= self.dia_shield
+            || self.tape_lap >= 100.0
+        {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("The tape shield of {} is not given", name),
+            ));
+        }
+        let core = self.cable.conductor();
+        let meters = self.cable.meters();
+        let (diameter, thickness) = (self.dia_shield * meters, self.tape_layer * meters);
+        let radius = (diameter - thickness) / 2.0;
+        Ok(Conductor {
+            x,
+            h,
+            rac: core.rac_per_meter(),
+            gmr: core.gmr_meters(),
+            capradius: core.capradius_meters(),
+            shield: Some(Shield {
+                rac: RHO_COPPER
+                    / (PI * diameter * thickness * (50.0 / (100.0 - self.tape_lap)).sqrt()),
+                gmr: radius,
+                radius,
+                strands: None,
+                capacitance: self.cable.insulation_capacitance(),
+            }),
+        })
+    }
+}
+
+impl DssObject for TSData {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match index {
+            0 => self.dia_shield = parser.make_double()?,
+            1 => self.tape_layer = parser.make_double()?,
+            2 => self.tape_lap = parser.make_double()?,
+            _ => return self.cable.set_property(index - 3, parser),
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.cable.fill_defaults();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for TSDataClass {
+    fn name(&self) -> &'static str {
+        "TSData"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(TSData::new(name))
+    }
+}
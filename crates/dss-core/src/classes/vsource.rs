@@ -0,0 +1,873 @@
+// Vsource (Pascal TVsource): the Thevenin equivalent of the system feeding
+// the circuit, a balanced voltage behind a sequence impedance between its
+// two terminals. The second terminal defaults to the first bus with every
+// node grounded. The impedance is given as short-circuit MVA or current
+// with X/R ratios, as ohms or as per unit on baseMVA, whichever came last.
+// Every circuit has one, "Vsource.source", which New Circuit edits.
+
+use std::any::Any;
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CktElement, CktElementBase};
+use crate::class::DssClass;
+use crate::classes::isource::ScanType;
+use crate::cmatrix::CMatrix;
+use crate::object::{DssObject, ObjectBase};
+use crate::pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+use crate::property::{PropertyDef, PropertyKind, concat_properties, read_choice, read_complex};
+
+const VSOURCE_PROPERTIES: [PropertyDef; 31] = [
+    PropertyDef {
+        name: "bus1",
+        kind: PropertyKind::Bus,
+        default: "sourcebus",
+        help: "Name of bus to which the main terminal (1) is connected. bus1=busname bus1=busname.1.2.3. The VSOURCE object is a two-terminal voltage source (thevenin equivalent). Bus2 defaults to Bus1 with all phases connected to ground (node 0) unless previously specified. This is a Yg connection. If you want something different, define the Bus2 property explicitly.",
+    },
+    PropertyDef {
+        name: "basekv",
+        kind: PropertyKind::Double,
+        default: "115",
+        help: "Base Source kV, usually phase-phase (L-L) unless you are making a positive-sequence model or 1-phase modelin which case, it will be phase-neutral (L-N) kV.",
+    },
+    PropertyDef {
+        name: "pu",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per unit of the base voltage that the source is actually operating at. \"pu=1.05\"",
+    },
+    PropertyDef {
+        name: "angle",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Phase angle in degrees of first phase: e.g.,Angle=10.3",
+    },
+    PropertyDef {
+        name: "frequency",
+        kind: PropertyKind::Double,
+        default: "60",
+        help: "Source frequency.  Defaults to system default base frequency.",
+    },
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases.  Defaults to 3.",
+    },
+    PropertyDef {
+        name: "mvasc3",
+        kind: PropertyKind::Double,
+        default: "2000",
+        help: "MVA Short circuit, 3-phase fault. Default = 2000. Z1 is determined by squaring the base kv and dividing by this value. For single-phase source, this value is not used.",
+    },
+    PropertyDef {
+        name: "mvasc1",
+        kind: PropertyKind::Double,
+        default: "2100",
+        help: "MVA Short Circuit, 1-phase fault. Default = 2100. The \"single-phase impedance\", Zs, is determined by squaring the base kV and dividing by this value. Then Z0 is determined by Z0 = 3Zs - 2Z1.  For 1-phase sources, Zs is used directly. Use X0R0 to define X/R ratio for 1-phase source.",
+    },
+    PropertyDef {
+        name: "x1r1",
+        kind: PropertyKind::Double,
+        default: "4",
+        help: "Positive-sequence  X/R ratio. Default = 4.",
+    },
+    PropertyDef {
+        name: "x0r0",
+        kind: PropertyKind::Double,
+        default: "3",
+        help: "Zero-sequence X/R ratio.Default = 3.",
+    },
+    PropertyDef {
+        name: "isc3",
+        kind: PropertyKind::Double,
+        default: "10041",
+        help: "Alternate method of defining the source impedance. 3-phase short circuit current, amps.  Default is 10000.",
+    },
+    PropertyDef {
+        name: "isc1",
+        kind: PropertyKind::Double,
+        default: "10543",
+        help: "Alternate method of defining the source impedance. single-phase short circuit current, amps.  Default is 10500.",
+    },
+    PropertyDef {
+        name: "r1",
+        kind: PropertyKind::Double,
+        default: "1.6038",
+        help: "Alternate method of defining the source impedance. Positive-sequence resistance, ohms.  Default is 1.65.",
+    },
+    PropertyDef {
+        name: "x1",
+        kind: PropertyKind::Double,
+        default: "6.4151",
+        help: "Alternate method of defining the source impedance. Positive-sequence reactance, ohms.  Default is 6.6.",
+    },
+    PropertyDef {
+        name: "r0",
+        kind: PropertyKind::Double,
+        default: "1.9028",
+        help: "Alternate method of defining the source impedance. Zero-sequence resistance, ohms.  Default is 1.9.",
+    },
+    PropertyDef {
+        name: "x0",
+        kind: PropertyKind::Double,
+        default: "5.7083",
+        help: "Alternate method of defining the source impedance. Zero-sequence reactance, ohms.  Default is 5.7.",
+    },
+    PropertyDef {
+        name: "scantype",
+        kind: PropertyKind::Choice(ScanType::NAMES),
+        default: "pos",
+        help: "{pos*| zero | none} Maintain specified sequence for harmonic frequency scan. Default is positive sequence. Otherwise, angle between phases rotates with harmonic.",
+    },
+    PropertyDef {
+        name: "sequence",
+        kind: PropertyKind::Choice(Sequence::NAMES),
+        default: "pos",
+        help: "{pos*| neg | zero} Set the phase angles for the specified symmetrical component sequence for non-harmonic solution modes. Default is positive sequence. ",
+    },
+    PropertyDef {
+        name: "bus2",
+        kind: PropertyKind::Bus,
+        default: "sourcebus.0.0.0",
+        help: "Name of bus to which 2nd terminal is connected. bus2=busname bus2=busname.1.2.3 Default is Bus1.0.0.0 (grounded wye connection)",
+    },
+    PropertyDef {
+        name: "z1",
+        kind: PropertyKind::Doubles,
+        default: "[1.6038, 6.4151]",
+        help: "Positive-sequence equivalent source impedance, ohms, as a 2-element array representing a complex number. Example: Z1=[1, 2]  ! represents 1 + j2 Used to define the impedance matrix of the VSOURCE if Z1MVA, etc. not specified. Side Effect: Sets Z2 = Z1 unless Z2 is subsequently specified.",
+    },
+    PropertyDef {
+        name: "z0",
+        kind: PropertyKind::Doubles,
+        default: "[1.9028, 5.7083]",
+        help: "Zero-sequence equivalent source impedance, ohms, as a 2-element array representing a complex number. Example: Z0=[3, 4]  ! represents 3 + j4 Used to define the impedance matrix of the VSOURCE if Z1MVA, etc. not specified.",
+    },
+    PropertyDef {
+        name: "z2",
+        kind: PropertyKind::Doubles,
+        default: "[1.6038, 6.4151]",
+        help: "Negative-sequence equivalent source impedance, ohms, as a 2-element array representing a complex number. Example: Z2=[1, 2]  ! represents 1 + j2 Used to define the impedance matrix of the VSOURCE if Z1MVA, etc. not specified. Normally Z2 = Z1.",
+    },
+    PropertyDef {
+        name: "puz1",
+        kind: PropertyKind::Doubles,
+        default: "[0.0121, 0.0485]",
+        help: "2-element array: e.g., [1  2]. An alternate way to specify Z1. See Z1 property. Per-unit positive-sequence impedance on base of Vsource BasekV and BaseMVA.",
+    },
+    PropertyDef {
+        name: "puz0",
+        kind: PropertyKind::Doubles,
+        default: "[0.0144, 0.0432]",
+        help: "2-element array: e.g., [1  2]. An alternate way to specify Z0. See Z0 property. Per-unit zero-sequence impedance on base of Vsource BasekV and BaseMVA.",
+    },
+    PropertyDef {
+        name: "puz2",
+        kind: PropertyKind::Doubles,
+        default: "[0.0121, 0.0485]",
+        help: "2-element array: e.g., [1  2]. An alternate way to specify Z2. See Z2 property. Per-unit negative-sequence impedance on base of Vsource BasekV and BaseMVA.",
+    },
+    PropertyDef {
+        name: "basemva",
+        kind: PropertyKind::Double,
+        default: "100",
+        help: "Default value is 100. Base used to convert values specifiied with puZ1, puZ0, and puZ2 properties to ohms on kV base specified by BasekV property.",
+    },
+    PropertyDef {
+        name: "yearly",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit voltage for YEARLY-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual L-N kV. Must be previously defined as a LOADSHAPE object. Is set to the Daily load shape when Daily is defined.  The daily load shape is repeated in this case. Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "daily",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit voltage for DAILY-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual L-N kV. Must be previously defined as a LOADSHAPE object. Sets Yearly curve if it is not already defined.   Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "duty",
+        kind: PropertyKind::Object("LoadShape"),
+        default: "",
+        help: "LOADSHAPE object to use for the per-unit voltage for DUTYCYCLE-mode simulations. Set the Mult property of the LOADSHAPE to the pu curve. Qmult is not used. If UseActual=Yes then the Mult curve should be actual L-N kV. Must be previously defined as a LOADSHAPE object. Defaults to Daily load shape when Daily is defined.   Set to NONE to reset to no loadahape for Yearly mode. The default is no variation.",
+    },
+    PropertyDef {
+        name: "model",
+        kind: PropertyKind::Choice(&["thevenin", "ideal"]),
+        default: "thevenin",
+        help: "{Thevenin* | Ideal}  Specifies whether the Vsource is to be considered a Thevenin short circuit model or a quasi-ideal voltage source. If Thevenin, the Vsource uses the impedances defined for all calculations. If \"Ideal\", the model uses a small impedance on the diagonal of the impedance matrix for the fundamental base frequency power flow only. Then switches to actual Thevenin model for other frequencies. ",
+    },
+    PropertyDef {
+        name: "puzideal",
+        kind: PropertyKind::Doubles,
+        default: "[1e-6, 0.001]",
+        help: "2-element array: e.g., [1  2]. The pu impedance to use for the quasi-ideal voltage source model. Should be a very small impedances. Default is [1e-6, 0.001]. Per-unit impedance on base of Vsource BasekV and BaseMVA. If too small, solution may not work. Be sure to check the voltage values and powers.",
+    },
+];
+
+static PROPERTIES: [PropertyDef; 34] = concat_properties(&VSOURCE_PROPERTIES, &PC_PROPERTIES);
+
+// Phase rotation of the source voltages (Pascal SequenceType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    Positive,
+    Negative,
+    Zero,
+}
+
+impl Sequence {
+    pub const NAMES: &'static [&'static str] = &["pos", "neg", "zero"];
+
+    // Sequence of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "neg" => Sequence::Negative,
+            "zero" => Sequence::Zero,
+            _ => Sequence::Positive,
+        }
+    }
+}
+
+// Which properties the impedance was last given by (Pascal ZSpecType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZSpec {
+    ShortCircuit,
+    Ohms,
+    PerUnit,
+}
+
+#[derive(Debug)]
+pub struct VsourceClass;
+
+#[derive(Debug, Clone)]
+pub struct Vsource {
+    base: ObjectBase,
+    ckt: CktElementBase,
+    pc: PcElementBase,
+    base_kv: f64,
+    pu: f64,
+    angle: f64,
+    frequency: f64,
+    mvasc3: f64,
+    mvasc1: f64,
+    x1r1: f64,
+    x0r0: f64,
+    z1: Complex64,
+    z0: Complex64,
+    z2: Complex64,
+    // Z2 was given apart from Z1
+    z2_given: bool,
+    pu_z1: Complex64,
+    pu_z0: Complex64,
+    pu_z2: Complex64,
+    base_mva: f64,
+    z_spec: ZSpec,
+    scan_type: ScanType,
+    sequence: Sequence,
+    bus2_given: bool,
+    yearly: String,
+    daily: String,
+    duty: String,
+    ideal: bool,
+    pu_z_ideal: Complex64,
+    // Frequency Yprim was last built at; the source drives only at its own
+    yprim_frequency: f64,
+}
+
+impl Vsource {
+    pub fn new(name: &str) -> Self {
+        let mut ckt = CktElementBase::new(3, 2);
+        ckt.set_bus(0, "sourcebus");
+        ckt.set_bus(1, "sourcebus.0.0.0");
+        let mut source = Vsource {
+            base: ObjectBase::new("Vsource", name, &PROPERTIES),
+            ckt,
+            pc: PcElementBase::new("defaultvsource"),
+            base_kv: 115.0,
+            pu: 1.0,
+            angle: 0.0,
+            frequency: 60.0,
+            mvasc3: 2000.0,
+            mvasc1: 2100.0,
+            x1r1: 4.0,
+            x0r0: 3.0,
+            z1: Complex64::new(0.0, 0.0),
+            z0: Complex64::new(0.0, 0.0),
+            z2: Complex64::new(0.0, 0.0),
+            z2_given: false,
+            pu_z1: Complex64::new(0.0, 0.0),
+            pu_z0: Complex64::new(0.0, 0.0),
+            pu_z2: Complex64::new(0.0, 0.0),
+            base_mva: 100.0,
+            z_spec: ZSpec::ShortCircuit,
+            scan_type: ScanType::Positive,
+            sequence: Sequence::Positive,
+            bus2_given: false,
+            yearly: String::new(),
+            daily: String::new(),
+            duty: String::new(),
+            ideal: false,
+            pu_z_ideal: Complex64::new(1e-6, 0.001),
+            yprim_frequency: 60.0,
+        };
+        source.set_impedances();
+        source
+    }
+
+    pub fn get_base_kv(&self) -> f64 {
+        self.base_kv
+    }
+
+    pub fn get_pu(&self) -> f64 {
+        self.pu
+    }
+
+    // Sets the operating voltage, as the voltage shapes do
+    pub fn set_pu(&mut self, pu: f64) {
+        self.pu = pu;
+    }
+
+    pub fn get_angle(&self) -> f64 {
+        self.angle
+    }
+
+    pub fn get_frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn get_scan_type(&self) -> ScanType {
+        self.scan_type
+    }
+
+    pub fn get_mvasc3(&self) -> f64 {
+        self.mvasc3
+    }
+
+    pub fn get_mvasc1(&self) -> f64 {
+        self.mvasc1
+    }
+
+    pub fn get_isc3(&self) -> f64 {
+        self.mvasc3 * 1000.0 / (3.0_f64.sqrt() * self.base_kv)
+    }
+
+    pub fn get_isc1(&self) -> f64 {
+        self.mvasc1 * 1000.0 / (3.0_f64.sqrt() * self.base_kv)
+    }
+
+    // Sequence impedances in ohms: zero, positive, negative
+    pub fn sequence_impedances(&self) -> (Complex64, Complex64, Complex64) {
+        (self.z0, self.z1, self.z2)
+    }
+
+    pub fn get_yearly(&self) -> &str {
+        &self.yearly
+    }
+
+    pub fn get_daily(&self) -> &str {
+        &self.daily
+    }
+
+    pub fn get_duty(&self) -> &str {
+        &self.duty
+    }
+
+    // Open-circuit voltage of each phase
+    pub fn source_voltages(&self) -> Vec<Complex64> {
+        let nphases = self.ckt.nphases();
+        let vmag = if nphases == 1 {
+            self.base_kv * self.pu * 1000.0
+        } else {
+            self.base_kv * self.pu * 1000.0 / 2.0 / (PI / nphases as f64).sin()
+        };
+        let step = match self.sequence {
+            Sequence::Positive => -2.0 * PI / nphases as f64,
+            Sequence::Negative => 2.0 * PI / nphases as f64,
+            Sequence::Zero => 0.0,
+        };
+        (0..nphases)
+            .map(|phase| Complex64::from_polar(vmag, self.angle.to_radians() + step * phase as f64))
+            .collect()
+    }
+
+    // Voltages at a harmonic, given the spectrum multiplier for it; the scan
+    // type decides whether the phases keep their sequence
+    pub fn harmonic_voltages(&self, harmonic: f64, multiplier: Complex64) -> Vec<Complex64> {
+        let fundamental = self.source_voltages();
+        let nphases = self.ckt.nphases();
+        let step = match self.scan_type {
+            ScanType::Positive => -2.0 * PI / nphases as f64,
+            ScanType::Zero => 0.0,
+            ScanType::None => -2.0 * PI / nphases as f64 * harmonic,
+        };
+        let vmag = fundamental.first().map_or(0.0, |v| v.norm()) * multiplier.norm();
+        let first = harmonic * self.angle.to_radians() + multiplier.arg();
+        (0..nphases)
+            .map(|phase| Complex64::from_polar(vmag, first + step * phase as f64))
+            .collect()
+    }
+
+    fn zbase(&self) -> f64 {
+        self.base_kv * self.base_kv / self.base_mva
+    }
+
+    // The sequence impedances and short-circuit levels from whichever of
+    // them were given (Pascal RecalcElementData)
+    fn set_impedances(&mut self) {
+        let kv2 = self.base_kv * self.base_kv;
+        match self.z_spec {
+            ZSpec::ShortCircuit => {
+                let z1_mvasc = if self.ckt.nphases() == 1 {
+                    self.mvasc1
+                } else {
+                    self.mvasc3
+                };
+                let x1 = kv2 / z1_mvasc / (1.0 + 1.0 / (self.x1r1 * self.x1r1)).sqrt();
+                self.z1 = Complex64::new(x1 / self.x1r1, x1);
+                if self.ckt.nphases() == 1 {
+                    self.z0 = self.z1;
+                } else {
+                    // |2 Z1 + Z0| = 3 kV² / MVAsc1 with R0 = X0 / x0r0
+                    let k = 3.0 * kv2 / self.mvasc1;
+                    let (r, x) = (2.0 * self.z1.re, 2.0 * self.z1.im);
+                    let a = 1.0 + 1.0 / (self.x0r0 * self.x0r0);
+                    let b = 2.0 * (r / self.x0r0 + x);
+                    let c = r * r + x * x - k * k;
+                    let x0 = (-b + (b * b - 4.0 * a * c).max(0.0).sqrt()) / (2.0 * a);
+                    self.z0 = Complex64::new(x0 / self.x0r0, x0);
+                }
+                self.z2 = self.z1;
+            }
+            ZSpec::Ohms => {
+                if !self.z2_given {
+                    self.z2 = self.z1;
+                }
+            }
+            ZSpec::PerUnit => {
+                let zbase = self.zbase();
+                self.z1 = self.pu_z1 * zbase;
+                self.z0 = self.pu_z0 * zbase;
+                self.z2 = if self.z2_given {
+                    self.pu_z2 * zbase
+                } else {
+                    self.z1
+                };
+            }
+        }
+        if self.z_spec != ZSpec::ShortCircuit {
+            self.mvasc3 = kv2 / self.z1.norm();
+            self.mvasc1 = 3.0 * kv2 / (2.0 * self.z1 + self.z0).norm();
+            self.x1r1 = self.z1.im / self.z1.re;
+            self.x0r0 = self.z0.im / self.z0.re;
+        }
+        let zbase = self.zbase();
+        self.pu_z1 = self.z1 / zbase;
+        self.pu_z0 = self.z0 / zbase;
+        self.pu_z2 = self.z2 / zbase;
+    }
+
+    // Phase impedance matrix of the sequence impedances at the given
+    // frequency multiplier
+    fn phase_impedances(&self, freq_mult: f64) -> CMatrix {
+        let at_frequency = |z: Complex64| Complex64::new(z.re, z.im * freq_mult);
+        let nphases = self.ckt.nphases();
+        let mut z = CMatrix::new(nphases);
+        if self.ideal && freq_mult == 1.0 {
+            let z_ideal = self.pu_z_ideal * self.zbase();
+            for phase in 0..nphases {
+                z.set(phase, phase, z_ideal);
+            }
+            return z;
+        }
+        sequence_to_phase(
+            nphases,
+            at_frequency(self.z0),
+            at_frequency(self.z1),
+            at_frequency(self.z2),
+        )
+    }
+
+    // Bus2 follows bus1 with its nodes grounded unless given
+    fn set_default_bus2(&mut self) {
+        if self.bus2_given {
+            return;
+        }
+        let bus1 = self.ckt.get_bus(0);
+        let name = bus1.split('.').next().unwrap_or("").to_string();
+        let bus2 = format!("{}{}", name, ".0".repeat(self.ckt.nphases()));
+        self.ckt.set_bus(1, &bus2);
+    }
+}
+
+// Phase impedance matrix of sequence impedances; three phases take the full
+// transformation so that Z2 may differ from Z1, others only Z0 and Z1
+pub(crate) fn sequence_to_phase(
+    nphases: usize,
+    z0: Complex64,
+    z1: Complex64,
+    z2: Complex64,
+) -> CMatrix {
+    let mut z = CMatrix::new(nphases);
+    if nphases == 3 {
+        let a = Complex64::from_polar(1.0, 2.0 * PI / 3.0);
+        let powers = [Complex64::new(1.0, 0.0), a, a * a];
+        for i in 0..3 {
+            for j in 0..3 {
+                // A diag(Z0, Z1, Z2) A⁻¹: Z1 turns by a^(j-i), Z2 by a^(i-j)
+                let value =
+                    (z0 + z1 * powers[(3 + j - i) % 3] + z2 * powers[(3 + i - j) % 3]) / 3.0;
+                z.set(i, j, value);
+            }
+        }
+    } else {
+        let zs = (2.0 * z1 + z0) / 3.0;
+        let zm = (z0 - z1) / 3.0;
+        for i in 0..nphases {
+            for j in 0..nphases {
+                z.set(i, j, if i == j { zs } else { zm });
+            }
+        }
+    }
+    z
+}
+
+impl DssObject for Vsource {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let Some(property) = VSOURCE_PROPERTIES.get(index) else {
+            return self.set_pc_property(index - VSOURCE_PROPERTIES.len(), parser);
+        };
+        let sqrt3 = 3.0_f64.sqrt();
+        match property.name {
+            "bus1" => self.ckt.set_bus(0, parser.get_token()),
+            "basekv" => self.base_kv = parser.make_double()?,
+            "pu" => self.pu = parser.make_double()?,
+            "angle" => self.angle = parser.make_double()?,
+            "frequency" => self.frequency = parser.make_double()?,
+            "phases" => {
+                let phases = parser.make_integer()?.max(1) as usize;
+                self.ckt.set_phases(phases);
+            }
+            "mvasc3" => {
+                self.mvasc3 = parser.make_double()?;
+                self.z_spec = ZSpec::ShortCircuit;
+            }
+            "mvasc1" => {
+                self.mvasc1 = parser.make_double()?;
+                self.z_spec = ZSpec::ShortCircuit;
+            }
+            "x1r1" => self.x1r1 = parser.make_double()?,
+            "x0r0" => self.x0r0 = parser.make_double()?,
+            "isc3" => {
+                self.mvasc3 = sqrt3 * self.base_kv * parser.make_double()? / 1000.0;
+                self.z_spec = ZSpec::ShortCircuit;
+            }
+            "isc1" => {
+                self.mvasc1 = sqrt3 * self.base_kv * parser.make_double()? / 1000.0;
+                self.z_spec = ZSpec::ShortCircuit;
+            }
+            "r1" | "x1" | "r0" | "x0" => {
+                let value = parser.make_double()?;
+                match property.name {
+                    "r1" => self.z1.re = value,
+                    "x1" => self.z1.im = value,
+                    "r0" => self.z0.re = value,
+                    _ => self.z0.im = value,
+                }
+                self.z_spec = ZSpec::Ohms;
+            }
+            "scantype" => {
+                self.scan_type =
+                    ScanType::from_name(read_choice(parser, ScanType::NAMES, "scantype")?)
+            }
+            "sequence" => {
+                self.sequence =
+                    Sequence::from_name(read_choice(parser, Sequence::NAMES, "sequence")?)
+            }
+            "bus2" => {
+                self.ckt.set_bus(1, parser.get_token());
+                self.bus2_given = true;
+            }
+            "z1" => {
+                self.z1 = read_complex(parser, "z1")?;
+                self.z_spec = ZSpec::Ohms;
+            }
+            "z0" => {
+                self.z0 = read_complex(parser, "z0")?;
+                self.z_spec = ZSpec::Ohms;
+            }
+            "z2" => {
+                self.z2 = read_complex(parser, "z2")?;
+                self.z2_given = true;
+                self.z_spec = ZSpec::Ohms;
+            }
+            "puz1" => {
+                self.pu_z1 = read_complex(parser, "puz1")?;
+                self.z_spec = ZSpec::PerUnit;
+            }
+            "puz0" => {
+                self.pu_z0 = read_complex(parser, "puz0")?;
+                self.z_spec = ZSpec::PerUnit;
+            }
+            "puz2" => {
+                self.pu_z2 = read_complex(parser, "puz2")?;
+                self.z2_given = true;
+                self.z_spec = ZSpec::PerUnit;
+            }
+            "basemva" => self.base_mva = parser.make_double()?,
+            "yearly" => self.yearly = parser.get_token().to_lowercase(),
+            "daily" => self.daily = parser.get_token().to_lowercase(),
+            "duty" => self.duty = parser.get_token().to_lowercase(),
+            "model" => {
+                self.ideal = read_choice(parser, &["thevenin", "ideal"], "model")? == "ideal"
+            }
+            "puzideal" => self.pu_z_ideal = read_complex(parser, "puzideal")?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.base_kv <= 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("{} needs basekv > 0", self.full_name()),
+            ));
+        }
+        self.set_impedances();
+        if self.z1.norm() == 0.0 {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has a zero positive-sequence impedance",
+                    self.full_name()
+                ),
+            ));
+        }
+        self.set_default_bus2();
+        self.ckt.invalidate_yprim();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        Some(self)
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        Some(self)
+    }
+}
+
+impl CktElement for Vsource {
+    fn ckt_base(&self) -> &CktElementBase {
+        &self.ckt
+    }
+
+    fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+        &mut self.ckt
+    }
+
+    fn as_pc_element(&self) -> Option<&dyn PcElement> {
+        Some(self)
+    }
+
+    fn as_pc_element_mut(&mut self) -> Option<&mut dyn PcElement> {
+        Some(self)
+    }
+
+    // The inverse of the phase impedances between the two terminals
+    fn calc_yprim(&mut self, frequency: f64) -> DssResult<()> {
+        let nphases = self.ckt.nphases();
+        let mut y = self.phase_impedances(frequency / self.frequency);
+        if !y.invert() {
+            return Err(DssError::new(
+                codes::SINGULAR_MATRIX,
+                &format!("Impedance matrix of {} is singular", self.full_name()),
+            ));
+        }
+        let mut yprim = CMatrix::new(self.ckt.y_order());
+        for i in 0..nphases {
+            for j in 0..nphases {
+                let value = y.get(i, j);
+                yprim.set(i, j, value);
+                yprim.set(i + nphases, j + nphases, value);
+                yprim.set(i, j + nphases, -value);
+                yprim.set(i + nphases, j, -value);
+            }
+        }
+        self.yprim_frequency = frequency;
+        self.ckt.set_yprim(yprim);
+        Ok(())
+    }
+
+    // The Norton current of the source voltages, into terminal 1 and out
+    // of terminal 2; none away from the source frequency
+    fn get_injection_currents(&self, _voltages: &[Complex64]) -> Vec<Complex64> {
+        let injection = vec![Complex64::new(0.0, 0.0); self.ckt.y_order()];
+        let Some(yprim) = self.ckt.get_yprim() else {
+            return injection;
+        };
+        if (self.yprim_frequency - self.frequency).abs() > 1e-6 * self.frequency {
+            return injection;
+        }
+        let mut e = self.source_voltages();
+        e.resize(self.ckt.y_order(), Complex64::new(0.0, 0.0));
+        yprim.mv_mult(&e)
+    }
+}
+
+impl PcElement for Vsource {
+    fn pc_base(&self) -> &PcElementBase {
+        &self.pc
+    }
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase {
+        &mut self.pc
+    }
+}
+
+impl DssClass for VsourceClass {
+    fn name(&self) -> &'static str {
+        "Vsource"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(Vsource::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::test_util::{assert_close, edited, first_nodes, placed};
+
+    fn new_source(properties: &str) -> Vsource {
+        let source = edited(
+            Vsource::new("source"),
+            &VsourceClass,
+            properties,
+            &Circuit::new("test"),
+        )
+        .unwrap();
+        // the phases of terminal 1 on nodes 1 to 3, terminal 2 grounded
+        let nodes = first_nodes(source.nphases());
+        placed(source, &[&nodes]).unwrap()
+    }
+
+    #[test]
+    fn test_short_circuit_levels() {
+        let source = new_source("basekv=115");
+        let (z0, z1, z2) = source.sequence_impedances();
+        assert_close(z1, Complex64::new(1.6038, 6.4151), 1e-4);
+        assert_eq!(z2, z1);
+        // the single-phase level and X/R ratio hold for Z0
+        assert!(((2.0 * z1 + z0).norm() - 3.0 * 115.0 * 115.0 / 2100.0).abs() < 1e-9);
+        assert!((z0.im / z0.re - 3.0).abs() < 1e-9);
+        assert!((source.get_isc3() - 10040.9).abs() < 0.1);
+
+        let source = new_source("basekv=12.47 isc3=5000 isc1=4000");
+        assert!((source.get_mvasc3() - 3.0_f64.sqrt() * 12.47 * 5.0).abs() < 1e-9);
+
+        let source = new_source("basekv=115 r1=1 x1=10 r0=2 x0=20");
+        assert!((source.get_mvasc3() - 115.0 * 115.0 / 101.0_f64.sqrt()).abs() < 1e-9);
+        let source = new_source("basekv=115 basemva=100 puz1=[0.01 0.1] puz0=[0.02 0.2]");
+        assert_close(
+            source.sequence_impedances().1,
+            Complex64::new(1.3225, 13.225),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn test_thevenin_equivalent() {
+        let source = new_source("basekv=115 pu=1.05 angle=30");
+        let e = source.source_voltages();
+        let vln = 1.05 * 115e3 / 3.0_f64.sqrt();
+        assert_close(e[0], Complex64::from_polar(vln, PI / 6.0), 1e-6);
+        assert_close(
+            e[1],
+            Complex64::from_polar(vln, PI / 6.0 - 2.0 * PI / 3.0),
+            1e-6,
+        );
+
+        // no current at the open-circuit voltages
+        let mut voltages = vec![Complex64::new(0.0, 0.0)];
+        voltages.extend(&e);
+        let currents = source.get_currents(&voltages);
+        assert!(currents.iter().all(|current| current.norm() < 1e-6));
+        // the short-circuit current out of a bolted fault
+        let currents = source.get_currents(&[Complex64::new(0.0, 0.0); 4]);
+        let isc = 1.05 * source.get_isc3();
+        assert!((currents[0].norm() - isc).abs() < 1e-6 * isc);
+        assert_close(currents[3], -currents[0], 1e-6);
+
+        // no drive away from the source frequency
+        let mut harmonic = new_source("basekv=115");
+        harmonic.calc_yprim(300.0).unwrap();
+        let injection = harmonic.get_injection_currents(&voltages);
+        assert!(injection.iter().all(|current| current.norm() == 0.0));
+    }
+
+    #[test]
+    fn test_sequence_impedance_matrix() {
+        let source = new_source("z1=[1 4] z0=[3 9] z2=[0.5 2]");
+        let z = source.phase_impedances(1.0);
+        let a = Complex64::from_polar(1.0, 2.0 * PI / 3.0);
+        // each sequence current sees its own impedance
+        for (currents, expected) in [
+            ([1.0.into(), a * a, a], Complex64::new(1.0, 4.0)),
+            ([1.0.into(), a, a * a], Complex64::new(0.5, 2.0)),
+            (
+                [1.0.into(), 1.0.into(), 1.0.into()],
+                Complex64::new(3.0, 9.0),
+            ),
+        ] {
+            let v = z.mv_mult(&currents);
+            assert_close(v[0], expected, 1e-12);
+        }
+
+        let ideal = new_source("basekv=115 model=ideal");
+        let y = ideal.ckt_base().get_yprim().unwrap();
+        let expected = (Complex64::new(1e-6, 0.001) * 132.25).inv();
+        assert_close(y.get(0, 0), expected, 1e-6 * expected.norm());
+        assert_eq!(y.get(0, 1), Complex64::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_default_bus2() {
+        let source = new_source("bus1=b.1.2.3");
+        assert_eq!(source.get_bus(1), "b.0.0.0");
+        let source = new_source("bus2=c bus1=b phases=1");
+        assert_eq!(source.get_bus(1), "c");
+        let source = new_source("bus1=b phases=1");
+        assert_eq!(source.get_bus(1), "b.0");
+    }
+}
@@ -0,0 +1,308 @@
+// WireData (Pascal TWireData): an overhead conductor, described by its
+// resistance, GMR and radius, for computing line constants from the
+// physical construction of a line. The conductor data is shared with the
+// cable classes.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::class::DssClass;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_choice};
+use crate::units::LengthUnit;
+
+// Properties every conductor class has (Pascal TConductorData)
+pub const CONDUCTOR_PROPERTIES: [PropertyDef; 11] = [
+    PropertyDef {
+        name: "rdc",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "dc Resistance, ohms per unit length (see Runits). Defaults to Rac/1.02 if not specified.",
+    },
+    PropertyDef {
+        name: "rac",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Resistance at 60 Hz per unit length. Defaults to 1.02*Rdc if not specified.",
+    },
+    PropertyDef {
+        name: "runits",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "none",
+        help: "Length units for resistance: ohms per {mi|kft|km|m|Ft|in|cm|mm}.",
+    },
+    PropertyDef {
+        name: "gmrac",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "GMR at 60 Hz. Defaults to .7788*radius if not specified.",
+    },
+    PropertyDef {
+        name: "gmrunits",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "none",
+        help: "Units for GMR: {mi|kft|km|m|Ft|in|cm|mm}.",
+    },
+    PropertyDef {
+        name: "radius",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Outside radius of conductor. Defaults to GMR/0.7788 if not specified.",
+    },
+    PropertyDef {
+        name: "radunits",
+        kind: PropertyKind::Choice(LengthUnit::NAMES),
+        default: "none",
+        help: "Units for outside radius: {mi|kft|km|m|Ft|in|cm|mm}.",
+    },
+    PropertyDef {
+        name: "normamps",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Normal ampacity, amperes. Defaults to Emergency amps/1.5 if not specified.",
+    },
+    PropertyDef {
+        name: "emergamps",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Emergency ampacity, amperes. Defaults to 1.5 * Normal Amps if not specified.",
+    },
+    PropertyDef {
+        name: "diam",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Diameter; Alternative method for entering radius.",
+    },
+    PropertyDef {
+        name: "capradius",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Equivalent conductor radius for capacitance calcs. Specify this for bundled conductors. Defaults to same value as radius.",
+    },
+];
+
+// Resistance, GMR and radius of a conductor, each in units of its own;
+// -1 stands for not given, as in OpenDSS
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConductorData {
+    rdc: f64,
+    rac: f64,
+    r_units: LengthUnit,
+    gmr: f64,
+    gmr_units: LengthUnit,
+    radius: f64,
+    radius_units: LengthUnit,
+    norm_amps: f64,
+    emerg_amps: f64,
+    capradius: f64,
+}
+
+impl ConductorData {
+    pub fn new() -> Self {
+        ConductorData {
+            rdc: -1.0,
+            rac: -1.0,
+            r_units: LengthUnit::None,
+            gmr: -1.0,
+            gmr_units: LengthUnit::None,
+            radius: -1.0,
+            radius_units: LengthUnit::None,
+            norm_amps: -1.0,
+            emerg_amps: -1.0,
+            capradius: -1.0,
+        }
+    }
+
+    // Takes property `index` of CONDUCTOR_PROPERTIES from the parser
+    pub fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let units = |parser: &DSSParser| -> DssResult<LengthUnit> {
+            let name = CONDUCTOR_PROPERTIES[index].name;
+            Ok(LengthUnit::from_name(read_choice(
+                parser,
+                LengthUnit::NAMES,
+                name,
+            )?))
+        };
+        match CONDUCTOR_PROPERTIES[index].name {
+            "rdc" => self.rdc = parser.make_double()?,
+            "rac" => self.rac = parser.make_double()?,
+            "runits" => self.r_units = units(parser)?,
+            "gmrac" => self.gmr = parser.make_double()?,
+            "gmrunits" => self.gmr_units = units(parser)?,
+            "radius" => self.radius = parser.make_double()?,
+            "radunits" => self.radius_units = units(parser)?,
+            "normamps" => self.norm_amps = parser.make_double()?,
+            "emergamps" => self.emerg_amps = parser.make_double()?,
+            "diam" => self.radius = parser.make_double()? / 2.0,
+            "capradius" => self.capradius = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Fills in the values not given from the others
+    pub fn fill_defaults(&mut self) {
+        if self.rac < 0.0 && self.rdc > 0.0 {
+            self.rac = self.rdc * 1.02;
+        }
+        if self.rdc < 0.0 && self.rac > 0.0 {
+            self.rdc = self.rac / 1.02;
+        }
+        if self.gmr < 0.0 && self.radius > 0.0 {
+            self.gmr = 0.7788 * self.radius;
+            self.gmr_units = self.radius_units;
+        }
+        if self.radius < 0.0 && self.gmr > 0.0 {
+            self.radius = self.gmr / 0.7788;
+            self.radius_units = self.gmr_units;
+        }
+        if self.norm_amps < 0.0 && self.emerg_amps > 0.0 {
+            self.norm_amps = self.emerg_amps / 1.5;
+        }
+        if self.emerg_amps < 0.0 && self.norm_amps > 0.0 {
+            self.emerg_amps = self.norm_amps * 1.5;
+        }
+    }
+
+    // Problems that keep the conductor out of a line constants calculation
+    pub fn check(&self, name: &str) -> DssResult<()> {
+        let missing = if self.rac <= 0.0 {
+            Some("resistance")
+        } else if self.gmr <= 0.0 {
+            Some("GMR")
+        } else if self.radius <= 0.0 {
+            Some("radius")
+        } else {
+            None
+        };
+        match missing {
+            Some(value) => Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!("The {} of {} must be positive", value, name),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Ac resistance in ohms per meter
+    pub fn rac_per_meter(&self) -> f64 {
+        self.rac / self.r_units.to_meters()
+    }
+
+    pub fn rdc_per_meter(&self) -> f64 {
+        self.rdc / self.r_units.to_meters()
+    }
+
+    pub fn gmr_meters(&self) -> f64 {
+        self.gmr * self.gmr_units.to_meters()
+    }
+
+    pub fn radius_meters(&self) -> f64 {
+        self.radius * self.radius_units.to_meters()
+    }
+
+    // Radius for capacitance, the outside radius unless given
+    pub fn capradius_meters(&self) -> f64 {
+        if self.capradius > 0.0 {
+            self.capradius * self.radius_units.to_meters()
+        } else {
+            self.radius_meters()
+        }
+    }
+
+    // Units of the radius and of the other dimensions given with it
+    pub fn get_radius_units(&self) -> LengthUnit {
+        self.radius_units
+    }
+
+    pub fn get_gmr_units(&self) -> LengthUnit {
+        self.gmr_units
+    }
+
+    pub fn get_r_units(&self) -> LengthUnit {
+        self.r_units
+    }
+
+    pub fn get_norm_amps(&self) -> f64 {
+        self.norm_amps
+    }
+
+    pub fn get_emerg_amps(&self) -> f64 {
+        self.emerg_amps
+    }
+}
+
+impl Default for ConductorData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct WireDataClass;
+
+#[derive(Debug, Clone)]
+pub struct WireData {
+    base: ObjectBase,
+    conductor: ConductorData,
+}
+
+impl WireData {
+    pub fn new(name: &str) -> Self {
+        WireData {
+            base: ObjectBase::new("WireData", name, &CONDUCTOR_PROPERTIES),
+            conductor: ConductorData::new(),
+        }
+    }
+
+    pub fn conductor(&self) -> &ConductorData {
+        &self.conductor
+    }
+}
+
+impl DssObject for WireData {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        self.conductor.set_property(index, parser)
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        self.conductor.fill_defaults();
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for WireDataClass {
+    fn name(&self) -> &'static str {
+        "WireData"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &CONDUCTOR_PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(WireData::new(name))
+    }
+}
@@ -0,0 +1,313 @@
+// XfmrCode (Pascal TXfmrCode): transformer data defined once and shared by
+// the transformers naming it with xfmrcode=. It has the properties of a
+// Transformer other than its connections to buses.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::Connection;
+use crate::class::DssClass;
+use crate::classes::transformer::TransformerData;
+use crate::object::{DssObject, ObjectBase, copy_like};
+use crate::property::{PropertyDef, PropertyKind};
+
+static PROPERTIES: [PropertyDef; 36] = [
+    PropertyDef {
+        name: "phases",
+        kind: PropertyKind::Integer,
+        default: "3",
+        help: "Number of phases this transformer code.",
+    },
+    PropertyDef {
+        name: "windings",
+        kind: PropertyKind::Integer,
+        default: "2",
+        help: "Number of windings, this transformer code. Set this property before defining the windings.",
+    },
+    PropertyDef {
+        name: "wdg",
+        kind: PropertyKind::Integer,
+        default: "1",
+        help: "Set this = to the number of the winding you wish to define. Then set the values for this winding.",
+    },
+    PropertyDef {
+        name: "conn",
+        kind: PropertyKind::Choice(Connection::NAMES),
+        default: "wye",
+        help: "Connection of this winding {wye*, Delta, LN, LL}.",
+    },
+    PropertyDef {
+        name: "kv",
+        kind: PropertyKind::Double,
+        default: "12.47",
+        help: "For 2-or 3-phase, enter phase-phase kV rating. Otherwise, kV rating of the actual winding.",
+    },
+    PropertyDef {
+        name: "kva",
+        kind: PropertyKind::Double,
+        default: "1000",
+        help: "Base kVA rating of the winding. Side effect: forces change of max normal and emerg kVA ratings.",
+    },
+    PropertyDef {
+        name: "tap",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Per unit tap that this winding is on.",
+    },
+    PropertyDef {
+        name: "%r",
+        kind: PropertyKind::Double,
+        default: "0.2",
+        help: "Percent resistance this winding. (half of total for a 2-winding).",
+    },
+    PropertyDef {
+        name: "rneut",
+        kind: PropertyKind::Double,
+        default: "-1",
+        help: "Default = -1. Neutral resistance of wye (star)-connected winding in actual ohms. If entered as a negative value, the neutral is assumed to be open, or floating.",
+    },
+    PropertyDef {
+        name: "xneut",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Neutral reactance of wye(star)-connected winding in actual ohms. May be + or -.",
+    },
+    PropertyDef {
+        name: "conns",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Use this to specify all the Winding connections at once using an array. Example: New XfmrCode.T1 conns=(delta, wye)",
+    },
+    PropertyDef {
+        name: "kvs",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the kV ratings of all windings at once using an array. Example: New XfmrCode.T1 conns=(delta, wye) kvs=(115, 12.47)",
+    },
+    PropertyDef {
+        name: "kvas",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the kVA ratings of all windings at once using an array.",
+    },
+    PropertyDef {
+        name: "taps",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the p.u. tap of all windings at once using an array.",
+    },
+    PropertyDef {
+        name: "xhl",
+        kind: PropertyKind::Double,
+        default: "7",
+        help: "Use this to specify the percent reactance, H-L (winding 1 to winding 2). Use for 2- or 3-winding transformers. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xht",
+        kind: PropertyKind::Double,
+        default: "35",
+        help: "Use this to specify the percent reactance, H-T (winding 1 to winding 3). Use for 3-winding transformers only. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xlt",
+        kind: PropertyKind::Double,
+        default: "30",
+        help: "Use this to specify the percent reactance, L-T (winding 2 to winding 3). Use for 3-winding transformers only. On the kVA base of winding 1.",
+    },
+    PropertyDef {
+        name: "xscarray",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this to specify the percent reactance between all pairs of windings as an array. All values are on the kVA base of winding 1. The order of the values is: (x12 13 14... 23 24.. 34 ..)",
+    },
+    PropertyDef {
+        name: "thermal",
+        kind: PropertyKind::Double,
+        default: "2",
+        help: "Thermal time constant of the transformer in hours. Typically about 2.",
+    },
+    PropertyDef {
+        name: "n",
+        kind: PropertyKind::Double,
+        default: "0.8",
+        help: "n Exponent for thermal properties in IEEE C57. Typically 0.8.",
+    },
+    PropertyDef {
+        name: "m",
+        kind: PropertyKind::Double,
+        default: "0.8",
+        help: "m Exponent for thermal properties in IEEE C57. Typically 0.9 - 1.0",
+    },
+    PropertyDef {
+        name: "flrise",
+        kind: PropertyKind::Double,
+        default: "65",
+        help: "Temperature rise, deg C, for full load. Default is 65.",
+    },
+    PropertyDef {
+        name: "hsrise",
+        kind: PropertyKind::Double,
+        default: "15",
+        help: "Hot spot temperature rise, deg C. Default is 15.",
+    },
+    PropertyDef {
+        name: "%loadloss",
+        kind: PropertyKind::Double,
+        default: "0.4",
+        help: "Percent load loss at full load. The %R of the High and Low windings (1 and 2) are adjusted to agree at rated kVA loading.",
+    },
+    PropertyDef {
+        name: "%noloadloss",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Percent no load losses at rated excitation voltage. Default is zero. Converts to a resistance in parallel with the magnetizing impedance.",
+    },
+    PropertyDef {
+        name: "normhkva",
+        kind: PropertyKind::Double,
+        default: "1100",
+        help: "Normal maximum kVA rating of H winding (winding 1). Usually 100% - 110% of maximum nameplate rating, depending on load shape. Defaults to 110% of kVA rating of Winding 1.",
+    },
+    PropertyDef {
+        name: "emerghkva",
+        kind: PropertyKind::Double,
+        default: "1500",
+        help: "Emergency (contingency) kVA rating of H winding (winding 1). Usually 140% - 150% of maximum nameplate rating, depending on load shape. Defaults to 150% of kVA rating of Winding 1.",
+    },
+    PropertyDef {
+        name: "maxtap",
+        kind: PropertyKind::Double,
+        default: "1.1",
+        help: "Max per unit tap for the active winding. Default is 1.10",
+    },
+    PropertyDef {
+        name: "mintap",
+        kind: PropertyKind::Double,
+        default: "0.9",
+        help: "Min per unit tap for the active winding. Default is 0.90",
+    },
+    PropertyDef {
+        name: "numtaps",
+        kind: PropertyKind::Integer,
+        default: "32",
+        help: "Total number of taps between min and max tap. Default is 32 (16 raise and 16 lower taps about the neutral position).",
+    },
+    PropertyDef {
+        name: "%imag",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Percent magnetizing current. Default=0.0. Magnetizing branch is in parallel with windings in each phase.",
+    },
+    PropertyDef {
+        name: "ppm_antifloat",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Default=1 ppm. Parts per million of transformer winding VA rating connected to GROUND to protect against accidentally floating a winding without a reference. If positive then the effect is adding a very large reactance to ground.",
+    },
+    PropertyDef {
+        name: "%rs",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Use this property to specify all the winding %resistances using an array. Example: New XfmrCode.T1 %Rs=(0.2  0.3)",
+    },
+    PropertyDef {
+        name: "x12",
+        kind: PropertyKind::Double,
+        default: "7",
+        help: "Alternative to XHL for specifying the percent reactance from winding 1 to winding 2.",
+    },
+    PropertyDef {
+        name: "x13",
+        kind: PropertyKind::Double,
+        default: "35",
+        help: "Alternative to XHT for specifying the percent reactance from winding 1 to winding 3.",
+    },
+    PropertyDef {
+        name: "x23",
+        kind: PropertyKind::Double,
+        default: "30",
+        help: "Alternative to XLT for specifying the percent reactance from winding 2 to winding 3.",
+    },
+];
+
+#[derive(Debug)]
+pub struct XfmrCodeClass;
+
+#[derive(Debug, Clone)]
+pub struct XfmrCode {
+    base: ObjectBase,
+    data: TransformerData,
+}
+
+impl XfmrCode {
+    pub fn new(name: &str) -> Self {
+        XfmrCode {
+            base: ObjectBase::new("XfmrCode", name, &PROPERTIES),
+            data: TransformerData::new(),
+        }
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.data.nphases()
+    }
+
+    pub fn nwindings(&self) -> usize {
+        self.data.nwindings()
+    }
+
+    pub(crate) fn data(&self) -> &TransformerData {
+        &self.data
+    }
+}
+
+impl DssObject for XfmrCode {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let owner = self.full_name();
+        self.data
+            .set_property(PROPERTIES[index].name, parser, &owner)
+    }
+
+    // The properties of the windings follow the selector set before them,
+    // which setting them again one by one would lose
+    fn make_like(&mut self, other: &dyn DssObject, _circuit: &Circuit) -> DssResult<()> {
+        copy_like(self, other);
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl DssClass for XfmrCodeClass {
+    fn name(&self) -> &'static str {
+        "XfmrCode"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(XfmrCode::new(name))
+    }
+}
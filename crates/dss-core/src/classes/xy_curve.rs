@@ -0,0 +1,438 @@
+// XYcurve (Pascal TXYcurve): a piecewise linear curve of y against x, as
+// InvControl reads its volt-var and volt-watt characteristics and PVSystem
+// its efficiency and P-T curves. Points come as arrays or files, the x
+// values ascending; a lookup outside the points extends the first or last
+// segment. Scale and shift factors apply to the values going in and out.
+
+use std::any::Any;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::classes::shape_data::{FileFormat, ShapeFile};
+use crate::object::{DssObject, ObjectBase};
+use crate::property::{PropertyDef, PropertyKind, read_doubles};
+
+static PROPERTIES: [PropertyDef; 13] = [
+    PropertyDef {
+        name: "npts",
+        kind: PropertyKind::Integer,
+        default: "0",
+        help: "Max number of points to expect in curve. This could get reset to the actual number of points defined if less than specified.",
+    },
+    PropertyDef {
+        name: "points",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "One way to enter the points in a curve. Enter x and y values as one array in the order [x1, y1, x2, y2, ...]. For example:\n\nPoints=[1,100 2,200 3, 300] \n\nValues separated by commas or white space. Zero fills arrays if insufficient number of values.",
+    },
+    PropertyDef {
+        name: "yarray",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Alternate way to enter Y values. Enter an array of Y values corresponding to the X values.  You can also use the syntax: \nYarray = (file=filename)     !for text file one value per line\nYarray = (dblfile=filename)  !for packed file of doubles\nYarray = (sngfile=filename)  !for packed file of singles \n\nNote: this property will reset Npts to a smaller value if the  number of values in the files are fewer.",
+    },
+    PropertyDef {
+        name: "xarray",
+        kind: PropertyKind::Doubles,
+        default: "",
+        help: "Alternate way to enter X values. Enter an array of X values corresponding to the Y values.  You can also use the syntax: \nXarray = (file=filename)     !for text file one value per line\nXarray = (dblfile=filename)  !for packed file of doubles\nXarray = (sngfile=filename)  !for packed file of singles \n\nNote: this property will reset Npts to a smaller value if the  number of values in the files are fewer.",
+    },
+    PropertyDef {
+        name: "csvfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  X-Y curve data to a CSV file containing X, Y points one per line. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "sngfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  X-Y curve data to a binary file of SINGLES containing X, Y points packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "dblfile",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "Switch input of  X-Y  curve data to a binary file of DOUBLES containing X, Y points packed one after another. NOTE: This action may reset the number of points to a lower value.",
+    },
+    PropertyDef {
+        name: "x",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Enter a value and then retrieve the interpolated Y value from the Y property. On input shifted then scaled to original curve. Scaled then shifted on output.",
+    },
+    PropertyDef {
+        name: "y",
+        kind: PropertyKind::Double,
+        default: "",
+        help: "Enter a value and then retrieve the interpolated X value from the X property. On input shifted then scaled to original curve. Scaled then shifted on output.",
+    },
+    PropertyDef {
+        name: "xshift",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Shift X property values (in/out) by this amount of offset. Default = 0. Does not change original definition of arrays.",
+    },
+    PropertyDef {
+        name: "yshift",
+        kind: PropertyKind::Double,
+        default: "0",
+        help: "Shift Y property values (in/out) by this amount of offset. Default = 0. Does not change original definition of arrays.",
+    },
+    PropertyDef {
+        name: "xscale",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Scale X property values (in/out) by this factor. Default = 1.0. Does not change original definition of arrays.",
+    },
+    PropertyDef {
+        name: "yscale",
+        kind: PropertyKind::Double,
+        default: "1",
+        help: "Scale Y property values (in/out) by this factor. Default = 1.0. Does not change original definition of arrays.",
+    },
+];
+
+// What a file read at the next resolve goes to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileTarget {
+    X,
+    Y,
+    // x, y
+    Points,
+}
+
+// Which of x and y the other is found from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lookup {
+    YFromX,
+    XFromY,
+}
+
+#[derive(Debug)]
+pub struct XYCurveClass;
+
+#[derive(Debug, Clone)]
+pub struct XYCurve {
+    base: ObjectBase,
+    // Number of points; 0 to take as many as given
+    npts: usize,
+    // As given, before scale and shift
+    x_values: Vec<f64>,
+    y_values: Vec<f64>,
+    x_shift: f64,
+    y_shift: f64,
+    x_scale: f64,
+    y_scale: f64,
+    // The x and y properties, out of the curve
+    x: f64,
+    y: f64,
+    lookup: Lookup,
+    pending_file: Option<(FileTarget, ShapeFile)>,
+}
+
+impl XYCurve {
+    pub fn new(name: &str) -> Self {
+        XYCurve {
+            base: ObjectBase::new("XYcurve", name, &PROPERTIES),
+            npts: 0,
+            x_values: Vec::new(),
+            y_values: Vec::new(),
+            x_shift: 0.0,
+            y_shift: 0.0,
+            x_scale: 1.0,
+            y_scale: 1.0,
+            x: 0.0,
+            y: 0.0,
+            lookup: Lookup::YFromX,
+            pending_file: None,
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.x_values.len().min(self.y_values.len())
+    }
+
+    // The points with scale and shift applied
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        (0..self.num_points()).map(|i| self.point(i)).collect()
+    }
+
+    // Y at an x (Pascal GetYValue); straight on from the first or last
+    // segment beyond the ends, 0 without points
+    pub fn get_y(&self, x: f64) -> f64 {
+        let n = self.num_points();
+        match n {
+            0 => 0.0,
+            1 => self.point(0).1,
+            _ => {
+                // the segment ending at the first point at or past x
+                let end = (1..n).find(|&i| self.point(i).0 >= x).unwrap_or(n - 1);
+                let ((x0, y0), (x1, y1)) = (self.point(end - 1), self.point(end));
+                along(x, x0, y0, x1, y1)
+            }
+        }
+    }
+
+    // X at a y (Pascal GetXValue), for curves monotonic in y; straight on
+    // from the first or last segment beyond the ends
+    pub fn get_x(&self, y: f64) -> f64 {
+        let n = self.num_points();
+        match n {
+            0 => 0.0,
+            1 => self.point(0).0,
+            _ => {
+                let rising = self.point(n - 1).1 >= self.point(0).1;
+                let end = (1..n)
+                    .find(|&i| {
+                        let yi = self.point(i).1;
+                        if rising { yi >= y } else { yi <= y }
+                    })
+                    .unwrap_or(n - 1);
+                let ((x0, y0), (x1, y1)) = (self.point(end - 1), self.point(end));
+                along(y, y0, x0, y1, x1)
+            }
+        }
+    }
+
+    // The x and y properties: the last value given and the other read off
+    // the curve
+    pub fn get_x_value(&self) -> f64 {
+        self.x
+    }
+
+    pub fn get_y_value(&self) -> f64 {
+        self.y
+    }
+
+    fn point(&self, i: usize) -> (f64, f64) {
+        (
+            self.x_values[i] * self.x_scale + self.x_shift,
+            self.y_values[i] * self.y_scale + self.y_shift,
+        )
+    }
+}
+
+// Value at `at` on the line through (a0, b0) and (a1, b1)
+fn along(at: f64, a0: f64, b0: f64, a1: f64, b1: f64) -> f64 {
+    if a1 == a0 {
+        return b1;
+    }
+    b0 + (b1 - b0) * (at - a0) / (a1 - a0)
+}
+
+// The curve an element names, or an error naming the property
+pub fn find_xy_curve<'a>(circuit: &'a Circuit, name: &str, owner: &str) -> DssResult<&'a XYCurve> {
+    circuit
+        .find_object_as::<XYCurve>("XYcurve", name)
+        .ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("XYCurve \"{}\" not found for {}", name, owner),
+            )
+        })
+}
+
+impl DssObject for XYCurve {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let name = PROPERTIES[index].name;
+        match name {
+            "npts" => self.npts = parser.make_integer()?.max(0) as usize,
+            "points" => {
+                let values = read_doubles(parser)?;
+                self.x_values = values.iter().step_by(2).copied().collect();
+                self.y_values = values.iter().skip(1).step_by(2).copied().collect();
+                // an odd value out gets a y of 0
+                self.y_values.resize(self.x_values.len(), 0.0);
+            }
+            "xarray" | "yarray" => {
+                let target = match name {
+                    "xarray" => FileTarget::X,
+                    _ => FileTarget::Y,
+                };
+                let values = match ShapeFile::from_array(parser.get_token()) {
+                    Some(file) => {
+                        self.pending_file = Some((target, file));
+                        return Ok(());
+                    }
+                    None => read_doubles(parser)?,
+                };
+                match target {
+                    FileTarget::X => self.x_values = values,
+                    _ => self.y_values = values,
+                }
+            }
+            "csvfile" | "sngfile" | "dblfile" => {
+                let format = match name {
+                    "sngfile" => FileFormat::Sng,
+                    "dblfile" => FileFormat::Dbl,
+                    _ => FileFormat::Csv,
+                };
+                let file = ShapeFile::new(format, parser.get_token());
+                self.pending_file = Some((FileTarget::Points, file));
+            }
+            "x" => {
+                self.x = parser.make_double()?;
+                self.lookup = Lookup::YFromX;
+            }
+            "y" => {
+                self.y = parser.make_double()?;
+                self.lookup = Lookup::XFromY;
+            }
+            "xshift" => self.x_shift = parser.make_double()?,
+            "yshift" => self.y_shift = parser.make_double()?,
+            "xscale" => self.x_scale = parser.make_double()?,
+            "yscale" => self.y_scale = parser.make_double()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve(&mut self, _index: usize, circuit: &Circuit) -> DssResult<()> {
+        let Some((target, file)) = self.pending_file.take() else {
+            return Ok(());
+        };
+        let width = match target {
+            FileTarget::Points => 2,
+            _ => 1,
+        };
+        let records = file.read(circuit.get_current_dir(), width)?;
+        let column = |k: usize| records.iter().map(|record| record[k]).collect::<Vec<f64>>();
+        match target {
+            FileTarget::X => self.x_values = column(0),
+            FileTarget::Y => self.y_values = column(0),
+            FileTarget::Points => {
+                self.x_values = column(0);
+                self.y_values = column(1);
+            }
+        }
+        if self.npts > records.len() {
+            self.npts = records.len();
+        }
+        Ok(())
+    }
+
+    fn recalc(&mut self) -> DssResult<()> {
+        if self.npts > 0 {
+            self.x_values.resize(self.npts, 0.0);
+            self.y_values.resize(self.npts, 0.0);
+        }
+        if self.x_values.len() != self.y_values.len() {
+            return Err(DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "{} has {} x values but {} y values",
+                    self.full_name(),
+                    self.x_values.len(),
+                    self.y_values.len()
+                ),
+            ));
+        }
+        match self.lookup {
+            Lookup::YFromX => self.y = self.get_y(self.x),
+            Lookup::XFromY => self.x = self.get_x(self.y),
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_property(&self, index: usize) -> String {
+        match PROPERTIES[index].name {
+            "npts" => self.num_points().to_string(),
+            "x" => self.x.to_string(),
+            "y" => self.y.to_string(),
+            _ => self.base.get_value(index).to_string(),
+        }
+    }
+}
+
+impl DssClass for XYCurveClass {
+    fn name(&self) -> &'static str {
+        "XYcurve"
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &PROPERTIES
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(XYCurve::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::edited;
+
+    fn new_curve(properties: &str, circuit: &Circuit) -> DssResult<XYCurve> {
+        edited(XYCurve::new("c1"), &XYCurveClass, properties, circuit)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let circuit = Circuit::new("test");
+        let curve = new_curve("points=[0 0, 1 10, 3 20] x=2", &circuit).unwrap();
+        assert_eq!(curve.get_y_value(), 15.0);
+        assert_eq!(curve.get_y(0.5), 5.0);
+        // beyond the ends the end segments go on
+        assert_eq!(curve.get_y(-1.0), -10.0);
+        assert_eq!(curve.get_y(5.0), 30.0);
+        assert_eq!(curve.get_x(12.5), 1.5);
+
+        let curve = new_curve(
+            "xarray=[0 1] yarray=[1 0] xscale=2 yshift=1 y=1.5",
+            &circuit,
+        )
+        .unwrap();
+        assert_eq!(curve.points(), [(0.0, 2.0), (2.0, 1.0)]);
+        assert_eq!(curve.get_x_value(), 1.0);
+        assert_eq!(curve.get_property(7), "1");
+
+        let err = new_curve("xarray=[0 1 2] yarray=[1 0]", &circuit).unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("dss_core_xycurve_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut circuit = Circuit::new("test");
+        circuit.set_current_dir(&dir);
+        std::fs::write(dir.join("eff.csv"), "0.1, 0.86\n0.2, 0.9\n1.0, 0.97\n").unwrap();
+        let doubles: Vec<u8> = [25.0_f64, 1.0, 75.0, 0.8]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("pt.dbl"), doubles).unwrap();
+
+        let curve = new_curve("npts=4 csvfile=eff.csv", &circuit).unwrap();
+        assert_eq!(curve.num_points(), 3);
+        assert!((curve.get_y(0.6) - 0.935).abs() < 1e-12);
+        let curve = new_curve("dblfile=pt.dbl", &circuit).unwrap();
+        assert!((curve.get_y(50.0) - 0.9).abs() < 1e-12);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
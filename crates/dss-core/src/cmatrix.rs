@@ -0,0 +1,220 @@
+// Dense square complex matrix (Pascal TcMatrix), used for element Yprim and
+// impedance matrices. Indices are 0-based; storage is row by row.
+
+use std::fmt;
+
+use num_complex::Complex64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CMatrix {
+    order: usize,
+    values: Vec<Complex64>,
+}
+
+impl CMatrix {
+    pub fn new(order: usize) -> Self {
+        CMatrix {
+            order,
+            values: vec![Complex64::new(0.0, 0.0); order * order],
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Complex64 {
+        self.values[i * self.order + j]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: Complex64) {
+        self.values[i * self.order + j] = value;
+    }
+
+    pub fn add(&mut self, i: usize, j: usize, value: Complex64) {
+        self.values[i * self.order + j] += value;
+    }
+
+    // Sets both (i, j) and (j, i)
+    pub fn set_sym(&mut self, i: usize, j: usize, value: Complex64) {
+        self.set(i, j, value);
+        self.set(j, i, value);
+    }
+
+    // Adds to both (i, j) and (j, i); once on the diagonal
+    pub fn add_sym(&mut self, i: usize, j: usize, value: Complex64) {
+        self.add(i, j, value);
+        if i != j {
+            self.add(j, i, value);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(Complex64::new(0.0, 0.0));
+    }
+
+    pub fn scale(&mut self, factor: Complex64) {
+        for value in &mut self.values {
+            *value *= factor;
+        }
+    }
+
+    // Adds another matrix of the same order
+    pub fn add_matrix(&mut self, other: &CMatrix) {
+        for (value, other) in self.values.iter_mut().zip(&other.values) {
+            *value += other;
+        }
+    }
+
+    // Matrix times vector
+    pub fn mv_mult(&self, vector: &[Complex64]) -> Vec<Complex64> {
+        (0..self.order)
+            .map(|i| {
+                self.values[i * self.order..(i + 1) * self.order]
+                    .iter()
+                    .zip(vector)
+                    .map(|(a, b)| a * b)
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Copy with row and column `eliminate` removed by Kron reduction,
+    // Z' = Zpp - Zpn Znn^-1 Znp, as for a neutral held at zero voltage
+    pub fn kron(&self, eliminate: usize) -> CMatrix {
+        let n = self.order;
+        let znn = self.get(eliminate, eliminate);
+        let kept: Vec<usize> = (0..n).filter(|&i| i != eliminate).collect();
+        let mut reduced = CMatrix::new(n - 1);
+        for (i, &row) in kept.iter().enumerate() {
+            for (j, &col) in kept.iter().enumerate() {
+                let value =
+                    self.get(row, col) - self.get(row, eliminate) * self.get(eliminate, col) / znn;
+                reduced.set(i, j, value);
+            }
+        }
+        reduced
+    }
+
+    // Inverts in place by Gauss-Jordan elimination with partial pivoting;
+    // false, leaving the matrix unusable, when it is singular
+    pub fn invert(&mut self) -> bool {
+        let n = self.order;
+        let mut inverse = CMatrix::new(n);
+        for i in 0..n {
+            inverse.set(i, i, Complex64::new(1.0, 0.0));
+        }
+        for col in 0..n {
+            let pivot = (col..n)
+                .max_by(|&a, &b| self.get(a, col).norm().total_cmp(&self.get(b, col).norm()))
+                .unwrap_or(col);
+            if self.get(pivot, col).norm() == 0.0 {
+                return false;
+            }
+            if pivot != col {
+                for j in 0..n {
+                    self.values.swap(pivot * n + j, col * n + j);
+                    inverse.values.swap(pivot * n + j, col * n + j);
+                }
+            }
+            let scale = Complex64::new(1.0, 0.0) / self.get(col, col);
+            for j in 0..n {
+                self.values[col * n + j] *= scale;
+                inverse.values[col * n + j] *= scale;
+            }
+            for row in 0..n {
+                let factor = self.get(row, col);
+                if row == col || factor.norm() == 0.0 {
+                    continue;
+                }
+                for j in 0..n {
+                    let a = self.get(col, j);
+                    let b = inverse.get(col, j);
+                    self.values[row * n + j] -= factor * a;
+                    inverse.values[row * n + j] -= factor * b;
+                }
+            }
+        }
+        *self = inverse;
+        true
+    }
+}
+
+// One row per line, "re+jim" values separated by spaces
+impl fmt::Display for CMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..self.order {
+            let row: Vec<String> = (0..self.order)
+                .map(|j| {
+                    let value = self.get(i, j);
+                    format!("{:.6}{:+.6}j", value.re, value.im)
+                })
+                .collect();
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(re: f64, im: f64) -> Complex64 {
+        Complex64::new(re, im)
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut matrix = CMatrix::new(2);
+        matrix.set(0, 0, c(0.0, 1.0));
+        matrix.set_sym(0, 1, c(2.0, 0.0));
+        matrix.set(1, 1, c(1.0, 1.0));
+        let original = matrix.clone();
+        assert!(matrix.invert());
+
+        // original times inverse gives the identity
+        for col in 0..2 {
+            let column: Vec<Complex64> = (0..2).map(|row| matrix.get(row, col)).collect();
+            let product = original.mv_mult(&column);
+            for (row, value) in product.iter().enumerate() {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((value - c(expected, 0.0)).norm() < 1e-12);
+            }
+        }
+
+        let mut singular = CMatrix::new(2);
+        singular.set(0, 0, c(1.0, 0.0));
+        assert!(!singular.invert());
+    }
+
+    #[test]
+    fn test_kron() {
+        let mut matrix = CMatrix::new(3);
+        for i in 0..3 {
+            matrix.set(i, i, c(2.0, 0.0));
+        }
+        matrix.set_sym(0, 2, c(1.0, 0.0));
+        matrix.set_sym(1, 2, c(1.0, 0.0));
+        let reduced = matrix.kron(2);
+        assert_eq!(reduced.order(), 2);
+        assert_eq!(reduced.get(0, 0), c(1.5, 0.0));
+        assert_eq!(reduced.get(0, 1), c(-0.5, 0.0));
+    }
+
+    #[test]
+    fn test_add_sym_and_display() {
+        let mut matrix = CMatrix::new(2);
+        matrix.add_sym(0, 1, c(-1.0, 0.5));
+        matrix.add_sym(1, 1, c(2.0, 0.0));
+        assert_eq!(matrix.get(1, 0), c(-1.0, 0.5));
+        assert_eq!(matrix.get(1, 1), c(2.0, 0.0));
+        assert_eq!(
+            matrix.to_string(),
+            "0.000000+0.000000j -1.000000+0.500000j\n-1.000000+0.500000j 2.000000+0.000000j"
+        );
+    }
+}
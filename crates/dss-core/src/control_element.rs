@@ -0,0 +1,95 @@
+// Control elements (Pascal TControlElem): regulators, capacitor controls,
+// relays and the like, which watch an element of the circuit and act on one.
+// They carry no current; after each solution they sample the circuit and
+// queue the actions they call for, and the solution hands each action back
+// when it comes due. A control acting on the circuit is taken out of it
+// while it acts, so it may change any other element.
+
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::control_queue::{ControlAction, ControlQueue};
+use crate::registry::ElementId;
+
+// The element a control watches; control implementations embed one next to
+// their CktElementBase
+#[derive(Debug, Clone)]
+pub struct ControlElementBase {
+    // Full name, lower case, e.g. "line.l1"
+    element: String,
+    // Terminal of the element, from 1
+    terminal: usize,
+}
+
+impl ControlElementBase {
+    pub fn new() -> Self {
+        ControlElementBase {
+            element: String::new(),
+            terminal: 1,
+        }
+    }
+
+    pub fn get_element(&self) -> &str {
+        &self.element
+    }
+
+    pub fn set_element(&mut self, element: &str) {
+        self.element = element.to_lowercase();
+    }
+
+    pub fn get_terminal(&self) -> usize {
+        self.terminal
+    }
+
+    pub fn set_terminal(&mut self, terminal: usize) {
+        self.terminal = terminal.max(1);
+    }
+
+    // The watched element in the circuit
+    pub fn find_element(&self, circuit: &Circuit) -> Option<ElementId> {
+        find_by_full_name(circuit, &self.element)
+    }
+}
+
+impl Default for ControlElementBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Element of a full name such as "Line.l1"
+pub fn find_by_full_name(circuit: &Circuit, full_name: &str) -> Option<ElementId> {
+    let (class_name, name) = full_name.split_once('.')?;
+    circuit.find_element(class_name, name)
+}
+
+pub trait ControlElement: CktElement {
+    fn control_base(&self) -> &ControlElementBase;
+
+    fn control_base_mut(&mut self) -> &mut ControlElementBase;
+
+    // Looks at the solved circuit and queues the actions it calls for;
+    // `id` is the control's own handle, the owner of what it pushes
+    // (Pascal Sample)
+    fn sample(
+        &mut self,
+        id: ElementId,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    );
+
+    // Carries out an action it queued earlier (Pascal DoPendingAction)
+    fn do_pending_action(
+        &mut self,
+        action: &ControlAction,
+        circuit: &mut Circuit,
+        voltages: &[Complex64],
+        queue: &mut ControlQueue,
+    );
+
+    // Back to the state before any action, for a new solution (Pascal
+    // Reset)
+    fn reset(&mut self, _circuit: &mut Circuit) {}
+}
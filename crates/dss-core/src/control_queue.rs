@@ -0,0 +1,189 @@
+// Control queue (Pascal TControlQueue): the actions control elements have
+// asked for, each due at a time of the solution, and the events they have
+// logged. Controls push an action with a delay from the present time; the
+// solution pops the actions that have come due and hands each back to the
+// control that pushed it.
+
+use dss_common::{Event, EventLog};
+
+use crate::registry::ElementId;
+
+// One pending action; the code and proxy mean what the owner makes of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlAction {
+    pub hour: i32,
+    pub sec: f64,
+    pub code: i32,
+    pub proxy: usize,
+    pub owner: ElementId,
+    // Handle the action can be deleted by before it is due
+    pub handle: usize,
+}
+
+impl ControlAction {
+    fn seconds(&self) -> f64 {
+        self.hour as f64 * 3600.0 + self.sec
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlQueue {
+    // In order of time, actions at the same time in the order pushed
+    actions: Vec<ControlAction>,
+    hour: i32,
+    sec: f64,
+    next_handle: usize,
+    events: EventLog,
+}
+
+impl ControlQueue {
+    pub fn new() -> Self {
+        ControlQueue {
+            actions: Vec::new(),
+            hour: 0,
+            sec: 0.0,
+            next_handle: 1,
+            events: EventLog::new(),
+        }
+    }
+
+    // Present time of the solution, which delays are counted from
+    pub fn get_time(&self) -> (i32, f64) {
+        (self.hour, self.sec)
+    }
+
+    pub fn set_time(&mut self, hour: i32, sec: f64) {
+        (self.hour, self.sec) = normalize(hour, sec);
+    }
+
+    // Time of day of the present time, hours
+    pub fn hour_of_day(&self) -> f64 {
+        (self.hour as f64 + self.sec / 3600.0).rem_euclid(24.0)
+    }
+
+    // Queues an action `delay` seconds from now; returns its handle
+    pub fn push(&mut self, delay: f64, code: i32, proxy: usize, owner: ElementId) -> usize {
+        self.push_at(self.hour, self.sec + delay, code, proxy, owner)
+    }
+
+    // Queues an action at a given time; returns its handle
+    pub fn push_at(
+        &mut self,
+        hour: i32,
+        sec: f64,
+        code: i32,
+        proxy: usize,
+        owner: ElementId,
+    ) -> usize {
+        let (hour, sec) = normalize(hour, sec);
+        let action = ControlAction {
+            hour,
+            sec,
+            code,
+            proxy,
+            owner,
+            handle: self.next_handle,
+        };
+        self.next_handle += 1;
+        let at = action.seconds();
+        let position = self.actions.partition_point(|other| other.seconds() <= at);
+        self.actions.insert(position, action);
+        self.next_handle - 1
+    }
+
+    // Takes back an action before it is due; false if it is not queued
+    pub fn delete(&mut self, handle: usize) -> bool {
+        let before = self.actions.len();
+        self.actions.retain(|action| action.handle != handle);
+        self.actions.len() != before
+    }
+
+    // Takes the earliest action due at or before the present time
+    pub fn pop_due(&mut self) -> Option<ControlAction> {
+        let now = self.hour as f64 * 3600.0 + self.sec;
+        match self.actions.first() {
+            Some(action) if action.seconds() <= now => Some(self.actions.remove(0)),
+            _ => None,
+        }
+    }
+
+    // Time of the earliest action, due or not
+    pub fn next_time(&self) -> Option<(i32, f64)> {
+        self.actions.first().map(|action| (action.hour, action.sec))
+    }
+
+    pub fn actions(&self) -> &[ControlAction] {
+        &self.actions
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+    }
+
+    // Logs what an element did, at the present time
+    pub fn log(&mut self, element: &str, action: &str) {
+        self.events.log(self.hour, self.sec, element, action);
+    }
+
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    // The events logged since the last call, for the executive's event log
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let events = self.events.events().to_vec();
+        self.events.clear();
+        events
+    }
+}
+
+impl Default for ControlQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Seconds within the hour
+fn normalize(hour: i32, sec: f64) -> (i32, f64) {
+    let carry = (sec / 3600.0).floor();
+    (hour + carry as i32, sec - carry * 3600.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_and_delete() {
+        let mut queue = ControlQueue::new();
+        queue.set_time(1, 3590.0);
+        let late = queue.push(30.0, 1, 0, 7);
+        let first = queue.push(5.0, 2, 0, 8);
+        queue.push(30.0, 3, 0, 9);
+        assert_eq!(queue.next_time(), Some((1, 3595.0)));
+        assert_eq!(queue.actions()[1].hour, 2);
+        assert_eq!(queue.actions()[1].sec, 20.0);
+
+        assert!(queue.pop_due().is_none());
+        queue.set_time(2, 20.0);
+        assert_eq!(queue.pop_due().unwrap().handle, first);
+        assert!(queue.delete(late));
+        assert!(!queue.delete(late));
+        // same time, pushed later
+        assert_eq!(queue.pop_due().unwrap().code, 3);
+        assert!(queue.is_empty());
+
+        queue.log("CapControl.c1", "Closed");
+        assert_eq!(queue.take_events()[0].hour, 2);
+        assert!(queue.events().is_empty());
+        assert_eq!(queue.hour_of_day(), 2.0 + 20.0 / 3600.0);
+    }
+}
@@ -0,0 +1,157 @@
+// Stand-in for classes whose property tables are not written yet: objects
+// keep whatever properties they are given, by name or, for values given
+// without a name, by position ("#1", "#2", ...). Scripts using these classes
+// can still be loaded, edited, queried and saved.
+
+use std::any::Any;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::class::DssClass;
+use crate::object::{DssObject, ObjectBase};
+use crate::property::PropertyDef;
+
+#[derive(Debug)]
+pub struct GenericClass {
+    name: &'static str,
+}
+
+impl GenericClass {
+    pub const fn new(name: &'static str) -> Self {
+        GenericClass { name }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericObject {
+    base: ObjectBase,
+    // Names and values in the order first set
+    values: Vec<(String, String)>,
+}
+
+impl GenericObject {
+    pub fn set_value(&mut self, name: &str, value: &str) {
+        match self
+            .values
+            .iter_mut()
+            .find(|(prop, _)| prop.eq_ignore_ascii_case(name))
+        {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.values.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+impl DssObject for GenericObject {
+    fn base(&self) -> &ObjectBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ObjectBase {
+        &mut self.base
+    }
+
+    fn set_property(&mut self, _index: usize, _parser: &mut DSSParser) -> DssResult<()> {
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    // Exact names only, as there is no table to expand abbreviations from
+    fn get_property_value(&self, name: &str) -> Option<String> {
+        self.values
+            .iter()
+            .find(|(prop, _)| prop.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    }
+
+    // Any name is taken, as the class has no table to check it against
+    fn set_property_value(&mut self, name: &str, value: &str, _circuit: &Circuit) -> DssResult<()> {
+        self.set_value(name, value);
+        Ok(())
+    }
+
+    fn property_values(&self) -> Vec<(String, String)> {
+        self.values.clone()
+    }
+}
+
+impl DssClass for GenericClass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn properties(&self) -> &'static [PropertyDef] {
+        &[]
+    }
+
+    fn new_object(&self, name: &str) -> Box<dyn DssObject> {
+        Box::new(GenericObject {
+            base: ObjectBase::new(self.name, name, &[]),
+            values: Vec::new(),
+        })
+    }
+
+    fn edit(
+        &self,
+        object: &mut dyn DssObject,
+        parser: &mut DSSParser,
+        _circuit: &Circuit,
+    ) -> DssResult<()> {
+        let Some(object) = object.as_any_mut().downcast_mut::<GenericObject>() else {
+            return Ok(());
+        };
+        let mut position = 0;
+        loop {
+            let param_name = parser.next_param();
+            let value = parser.get_token().to_string();
+            if param_name.is_empty() && value.is_empty() {
+                break;
+            }
+            position += 1;
+            let name = if param_name.is_empty() {
+                format!("#{}", position)
+            } else {
+                param_name
+            };
+            object.set_value(&name, &value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generic_object() {
+        let class = GenericClass::new("Load");
+        let mut load = class.new_object("LD1");
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("kw=10 \"1 2\" mult=\"(1, 2)\" KW=12");
+        class
+            .edit(load.as_mut(), &mut parser, &Circuit::new("test"))
+            .unwrap();
+
+        assert_eq!(load.get_property_value("Kw").as_deref(), Some("12"));
+        assert_eq!(load.get_property_value("#2").as_deref(), Some("1 2"));
+        assert_eq!(load.get_property_value("k"), None);
+        assert_eq!(
+            load.to_script(),
+            "New Load.ld1 kw=12 \"1 2\" mult=\"(1, 2)\""
+        );
+    }
+}
@@ -1,14 +1,69 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+// Circuit model: the DSS classes, the objects defined from them and the
+// circuits they make up.
 
+mod bus;
+mod circuit;
+mod ckt_element;
+mod ckt_tree;
+mod class;
+mod classes;
+mod cmatrix;
+mod control_element;
+mod control_queue;
+mod generic;
+mod line_constants;
+mod meter_element;
+mod object;
+mod pc_element;
+mod pd_element;
+mod property;
+mod reduce_ckt;
+mod registry;
 #[cfg(test)]
-mod tests {
-    use super::*;
+pub(crate) mod test_util;
+mod units;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use bus::{Bus, BusList, parse_bus_spec};
+pub use circuit::{Circuit, wildcard_match};
+pub use ckt_element::{CKT_PROPERTIES, CktElement, CktElementBase, Connection};
+pub use ckt_tree::{CktTree, TreeBranch, isolated_elements};
+pub use class::DssClass;
+pub use classes::{
+    CNData, CNDataClass, CableData, CapControl, CapControlClass, CapControlType, Capacitor,
+    CapacitorClass, ChargeMode, ConductorData, DEFAULT_GROWTH_RATE, DischargeMode, DispatchMode,
+    EnergyMeter, EnergyMeterClass, ExpControl, ExpControlClass, Fault, FaultClass, Fuse, FuseClass,
+    GenDispatcher, GenDispatcherClass, Generator, GeneratorClass, GrowthShape, GrowthShapeClass,
+    Interpolation, InvControl, InvControlClass, InvControlMode, Isource, IsourceClass, Line,
+    LineClass, LineCode, LineCodeClass, LineGeometry, LineGeometryClass, LineSpacing,
+    LineSpacingClass, Load, LoadClass, LoadShape, LoadShapeClass, LoadStatus, MachineState,
+    MeterAction, MeterZone, Monitor, MonitorAction, MonitorClass, NUM_REGISTERS, PVSystem,
+    PVSystemClass, PriceShape, PriceShapeClass, REGISTER_NAMES, RandomType, Reactor, ReactorClass,
+    Recloser, RecloserClass, RefReactivePower, RegControl, RegControlClass, Relay, RelayClass,
+    RelayType, ScanType, Sensor, SensorClass, Sequence, ShapeMode, Spectrum, SpectrumClass,
+    Storage, StorageClass, StorageController, StorageControllerClass, StorageDispatch,
+    StorageState, SwitchState, SwtControl, SwtControlClass, TCCCurve, TCCCurveClass, TSData,
+    TSDataClass, TShape, TShapeClass, Transformer, TransformerClass, VoltWattAxis, VoltageRef,
+    Vsource, VsourceClass, WireData, WireDataClass, XYCurve, XYCurveClass, XfmrCode, XfmrCodeClass,
+    ZoneBranch, allocate_loads, apply_growth, apply_load_shapes, class_names, classes, find_class,
+    find_spectrum, find_xy_curve, growth_factor, random_load_mults, update_all_storage,
+};
+pub use cmatrix::CMatrix;
+pub use control_element::{ControlElement, ControlElementBase, find_by_full_name};
+pub use control_queue::{ControlAction, ControlQueue};
+pub use generic::{GenericClass, GenericObject};
+pub use line_constants::{Conductor, LineConstants, Shield};
+pub use meter_element::{MeterElement, MeterElementBase, SolutionState};
+pub use num_complex::Complex64;
+pub use object::{DssObject, ObjectBase, quote_value};
+pub use pc_element::{PC_PROPERTIES, PcElement, PcElementBase};
+pub use pd_element::{PD_PROPERTIES, PdElement, PdElementBase};
+pub use property::{
+    PropertyDef, PropertyKind, concat_properties, find_property, interpret_yes_no, parse_names,
+    read_choice, read_doubles,
+};
+pub use reduce_ckt::{
+    DEFAULT_ZMAG, ReduceOption, break_loops, eliminate_ends, eliminate_laterals,
+    eliminate_short_lines, eliminate_switches, merge_series_lines, reduce_circuit,
+};
+pub use registry::{ElementId, Registry};
+pub use units::LengthUnit;
@@ -0,0 +1,358 @@
+// Line constants from the construction of a line (Pascal TLineConstants,
+// TCNLineConstants, TTSLineConstants): the series impedance by Carson's
+// equations, simplified for power frequencies as in Kersting's modified form,
+// and the shunt capacitance from the potential coefficients of the conductors
+// and their images below ground. Cables add their concentric neutral or tape
+// shield as one more conductor each. All values are per meter.
+
+use std::f64::consts::PI;
+
+use dss_common::{DssError, DssResult, codes};
+use num_complex::Complex64;
+
+use crate::cmatrix::CMatrix;
+
+const MU0: f64 = 4.0e-7 * PI;
+pub(crate) const EPSILON0: f64 = 8.854_187_817e-12;
+
+// One conductor at horizontal position x and height h, in meters; cables
+// are below ground at negative heights
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conductor {
+    pub x: f64,
+    pub h: f64,
+    // Ac resistance in ohms per meter
+    pub rac: f64,
+    pub gmr: f64,
+    // Radius for capacitance
+    pub capradius: f64,
+    // Neutral or shield around a cable core
+    pub shield: Option<Shield>,
+}
+
+// The concentric neutral or tape shield of a cable as an equivalent
+// conductor around the core. It is grounded at both ends and always reduced
+// out; the cable's capacitance is between core and shield only.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shield {
+    // Ohms per meter
+    pub rac: f64,
+    pub gmr: f64,
+    // Distance from the core, the radius of the circle through the strands
+    pub radius: f64,
+    // Number of strands of a concentric neutral; None for a tape shield
+    pub strands: Option<f64>,
+    // Core to shield capacitance, F per meter
+    pub capacitance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineConstants {
+    conductors: Vec<Conductor>,
+    nphases: usize,
+    // Eliminate the conductors past the phases (neutrals) by Kron reduction
+    reduce: bool,
+}
+
+impl LineConstants {
+    pub fn new(conductors: Vec<Conductor>, nphases: usize, reduce: bool) -> Self {
+        LineConstants {
+            conductors,
+            nphases,
+            reduce,
+        }
+    }
+
+    pub fn nphases(&self) -> usize {
+        self.nphases
+    }
+
+    // Conductors of the calculated matrices
+    pub fn nconds(&self) -> usize {
+        if self.reduce {
+            self.nphases
+        } else {
+            self.conductors.len()
+        }
+    }
+
+    fn distance(&self, i: usize, j: usize) -> f64 {
+        let (a, b) = (&self.conductors[i], &self.conductors[j]);
+        (a.x - b.x).hypot(a.h - b.h)
+    }
+
+    // Distance from conductor i to the image of conductor j
+    fn image_distance(&self, i: usize, j: usize) -> f64 {
+        let (a, b) = (&self.conductors[i], &self.conductors[j]);
+        (a.x - b.x).hypot(a.h + b.h)
+    }
+
+    // Conductors that share a position, or bare ones not above ground
+    // Bare conductors may only be buried alongside cables
+    fn check(&self) -> DssResult<()> {
+        let cables = self.conductors.iter().any(|c| c.shield.is_some());
+        for (i, conductor) in self.conductors.iter().enumerate() {
+            if !cables && conductor.h <= 0.0 {
+                return Err(DssError::new(
+                    codes::SYNTAX_ERROR,
+                    &format!("Conductor {} is not above ground", i + 1),
+                ));
+            }
+            for j in 0..i {
+                if self.distance(i, j) <= 0.0 {
+                    return Err(DssError::new(
+                        codes::SYNTAX_ERROR,
+                        &format!(
+                            "Conductors {} and {} are at the same position",
+                            j + 1,
+                            i + 1
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Series impedance and shunt admittance per meter at `frequency` over
+    // earth of resistivity `rho` (ohm-m)
+    pub fn calc(&self, frequency: f64, rho: f64) -> DssResult<(CMatrix, CMatrix)> {
+        self.check()?;
+        let z = self.calc_z(frequency, rho);
+        let yc = self.calc_yc(frequency)?;
+        Ok((z, yc))
+    }
+
+    // The conductors, then the shields of the cables in order; the shields
+    // are reduced out, then the neutrals if asked
+    fn calc_z(&self, frequency: f64, rho: f64) -> CMatrix {
+        let n = self.conductors.len();
+        let shields: Vec<(usize, &Shield)> = self
+            .conductors
+            .iter()
+            .enumerate()
+            .filter_map(|(i, conductor)| conductor.shield.as_ref().map(|shield| (i, shield)))
+            .collect();
+        let omega = 2.0 * PI * frequency;
+        let l_factor = omega * MU0 / (2.0 * PI);
+        // earth return, with the equivalent depth 658.5 sqrt(rho / f)
+        let ze = Complex64::new(
+            omega * MU0 / 8.0,
+            l_factor * (658.5 * (rho / frequency).sqrt()).ln(),
+        );
+        let mutual = |d: f64| Complex64::new(0.0, l_factor * (1.0 / d).ln()) + ze;
+        // from a concentric neutral to a conductor at d from its cable
+        let from_shield = |shield: &Shield, d: f64| match shield.strands {
+            Some(k) => (d.powf(k) - shield.radius.powf(k)).powf(1.0 / k),
+            None => d,
+        };
+
+        let mut z = CMatrix::new(n + shields.len());
+        for i in 0..n {
+            let conductor = &self.conductors[i];
+            z.set(
+                i,
+                i,
+                Complex64::new(conductor.rac, l_factor * (1.0 / conductor.gmr).ln()) + ze,
+            );
+            for j in 0..i {
+                z.set_sym(i, j, mutual(self.distance(i, j)));
+            }
+        }
+        for (s, &(cable, shield)) in shields.iter().enumerate() {
+            let row = n + s;
+            z.set(
+                row,
+                row,
+                Complex64::new(shield.rac, l_factor * (1.0 / shield.gmr).ln()) + ze,
+            );
+            for j in 0..n {
+                let d = if j == cable {
+                    shield.radius
+                } else {
+                    from_shield(shield, self.distance(cable, j))
+                };
+                z.set_sym(row, j, mutual(d));
+            }
+            for (t, &(other, _)) in shields.iter().enumerate().take(s) {
+                z.set_sym(row, n + t, mutual(self.distance(cable, other)));
+            }
+        }
+
+        while z.order() > n {
+            z = z.kron(z.order() - 1);
+        }
+        if self.reduce {
+            while z.order() > self.nphases {
+                z = z.kron(z.order() - 1);
+            }
+        }
+        z
+    }
+
+    // Cables have their core to shield capacitance only; the bare
+    // conductors have theirs from potential coefficients among themselves
+    fn calc_yc(&self, frequency: f64) -> DssResult<CMatrix> {
+        let omega = 2.0 * PI * frequency;
+        let order = self.nconds();
+        let mut yc = CMatrix::new(order);
+        for (i, conductor) in self.conductors.iter().enumerate().take(order) {
+            if let Some(shield) = &conductor.shield {
+                yc.set(i, i, Complex64::new(0.0, omega * shield.capacitance));
+            }
+        }
+
+        // buried bare conductors are at earth potential
+        let bare: Vec<usize> = (0..self.conductors.len())
+            .filter(|&i| self.conductors[i].shield.is_none() && self.conductors[i].h > 0.0)
+            .collect();
+        if bare.is_empty() {
+            return Ok(yc);
+        }
+        let p_factor = 1.0 / (2.0 * PI * EPSILON0);
+        let mut p = CMatrix::new(bare.len());
+        for (a, &i) in bare.iter().enumerate() {
+            let conductor = &self.conductors[i];
+            p.set(
+                a,
+                a,
+                Complex64::new(
+                    p_factor * (2.0 * conductor.h / conductor.capradius).ln(),
+                    0.0,
+                ),
+            );
+            for (b, &j) in bare.iter().enumerate().take(a) {
+                let ratio = self.image_distance(i, j) / self.distance(i, j);
+                p.set_sym(a, b, Complex64::new(p_factor * ratio.ln(), 0.0));
+            }
+        }
+        // neutrals reduced out of the impedances go from the capacitances too
+        let kept = bare.iter().filter(|&&i| i < order).count();
+        while p.order() > kept {
+            p = p.kron(p.order() - 1);
+        }
+        if !p.invert() {
+            return Err(DssError::new(
+                codes::SINGULAR_MATRIX,
+                "Potential coefficient matrix is singular",
+            ));
+        }
+        for (a, &i) in bare.iter().enumerate().take(kept) {
+            for (b, &j) in bare.iter().enumerate().take(kept) {
+                yc.set(i, j, p.get(a, b) * Complex64::new(0.0, omega));
+            }
+        }
+        Ok(yc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEET: f64 = 0.3048;
+    const MILE: f64 = 1609.344;
+
+    // Kersting, Distribution System Modeling and Analysis, example 4.1:
+    // 336,400 26/7 ACSR phases and a 4/0 6/1 ACSR neutral
+    fn kersting_line(reduce: bool) -> LineConstants {
+        let phase = |x: f64| Conductor {
+            x: x * FEET,
+            h: 29.0 * FEET,
+            rac: 0.306 / MILE,
+            gmr: 0.0244 * FEET,
+            capradius: 0.0300 * FEET,
+            shield: None,
+        };
+        let neutral = Conductor {
+            x: 4.0 * FEET,
+            h: 25.0 * FEET,
+            rac: 0.592 / MILE,
+            gmr: 0.00814 * FEET,
+            capradius: 0.0199 * FEET,
+            shield: None,
+        };
+        LineConstants::new(vec![phase(0.0), phase(2.5), phase(7.0), neutral], 3, reduce)
+    }
+
+    #[test]
+    fn test_kersting_example() {
+        let (z, _) = kersting_line(true).calc(60.0, 100.0).unwrap();
+        assert_eq!(z.order(), 3);
+        let per_mile = |i: usize, j: usize| z.get(i, j) * MILE;
+        let expected = [
+            (0, 0, Complex64::new(0.4576, 1.0780)),
+            (0, 1, Complex64::new(0.1560, 0.5017)),
+            (0, 2, Complex64::new(0.1535, 0.3849)),
+            (1, 1, Complex64::new(0.4666, 1.0482)),
+            (1, 2, Complex64::new(0.1580, 0.4236)),
+            (2, 2, Complex64::new(0.4615, 1.0651)),
+        ];
+        for (i, j, value) in expected {
+            assert!(
+                (per_mile(i, j) - value).norm() < 1e-3,
+                "z{}{} = {}",
+                i,
+                j,
+                per_mile(i, j)
+            );
+        }
+    }
+
+    #[test]
+    fn test_unreduced_and_capacitance() {
+        let (z, yc) = kersting_line(false).calc(60.0, 100.0).unwrap();
+        assert_eq!(z.order(), 4);
+        // shunt admittance is capacitive, self terms positive, mutual negative
+        assert!(yc.get(0, 0).im > 0.0 && yc.get(0, 1).im < 0.0);
+        assert!(yc.get(0, 0).re.abs() < 1e-15);
+
+        let mut line = kersting_line(false);
+        line.conductors[1].x = 0.0;
+        assert!(line.calc(60.0, 100.0).is_err());
+    }
+
+    // Kersting example 4.2: three 250 kcmil AA cables with 13 #14 copper
+    // concentric neutral strands, 6 in apart
+    #[test]
+    fn test_concentric_neutral_cables() {
+        let inch = FEET / 12.0;
+        let radius = (1.29 - 0.0641) / 2.0 * inch;
+        let k = 13.0;
+        let cable = |x: f64| Conductor {
+            x: x * FEET,
+            h: -4.0 * FEET,
+            rac: 0.41 / MILE,
+            gmr: 0.0171 * FEET,
+            capradius: 0.567 / 2.0 * inch,
+            shield: Some(Shield {
+                rac: 14.8722 / k / MILE,
+                gmr: (0.00208 * FEET * k * radius.powf(k - 1.0)).powf(1.0 / k),
+                radius,
+                strands: Some(k),
+                capacitance: 1e-10,
+            }),
+        };
+        let line = LineConstants::new(vec![cable(0.0), cable(0.5), cable(1.0)], 3, false);
+        let (z, yc) = line.calc(60.0, 100.0).unwrap();
+        let per_mile = |i: usize, j: usize| z.get(i, j) * MILE;
+        let expected = [
+            (0, 0, Complex64::new(0.7981, 0.4463)),
+            (0, 1, Complex64::new(0.3188, 0.0334)),
+            (0, 2, Complex64::new(0.2848, -0.0138)),
+            (1, 1, Complex64::new(0.7890, 0.4041)),
+        ];
+        for (i, j, value) in expected {
+            assert!(
+                (per_mile(i, j) - value).norm() < 2e-3,
+                "z{}{} = {}",
+                i,
+                j,
+                per_mile(i, j)
+            );
+        }
+        // each cable's capacitance is to its own shield only
+        assert!((yc.get(0, 0).im - 2.0 * PI * 60.0 * 1e-10).abs() < 1e-15);
+        assert_eq!(yc.get(0, 1), Complex64::new(0.0, 0.0));
+    }
+}
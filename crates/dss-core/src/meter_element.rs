@@ -0,0 +1,105 @@
+// Meter elements (Pascal TMeterElement): monitors, energy meters and sensors,
+// which watch a terminal of an element of the circuit and record what flows
+// there. Like controls they carry no current; the solution hands each of
+// them the solved circuit to sample after every solution it counts.
+
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::control_element::find_by_full_name;
+use crate::registry::ElementId;
+
+// The element a meter watches; meter implementations embed one next to
+// their CktElementBase
+#[derive(Debug, Clone)]
+pub struct MeterElementBase {
+    // Full name, lower case, e.g. "line.l1"
+    element: String,
+    // Terminal of the element, from 1
+    terminal: usize,
+}
+
+impl MeterElementBase {
+    pub fn new() -> Self {
+        MeterElementBase {
+            element: String::new(),
+            terminal: 1,
+        }
+    }
+
+    pub fn get_element(&self) -> &str {
+        &self.element
+    }
+
+    pub fn set_element(&mut self, element: &str) {
+        self.element = element.to_lowercase();
+    }
+
+    pub fn get_terminal(&self) -> usize {
+        self.terminal
+    }
+
+    pub fn set_terminal(&mut self, terminal: usize) {
+        self.terminal = terminal.max(1);
+    }
+
+    // The metered element in the circuit
+    pub fn find_element(&self, circuit: &Circuit) -> Option<ElementId> {
+        find_by_full_name(circuit, &self.element)
+    }
+}
+
+impl Default for MeterElementBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What a meter may record of the solution itself besides the circuit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolutionState {
+    pub hour: i32,
+    pub sec: f64,
+    // Time step of the solution, seconds
+    pub interval: f64,
+    pub frequency: f64,
+    pub iterations: usize,
+    pub control_iteration: usize,
+    pub load_mult: f64,
+    pub converged: bool,
+    // Largest change of a node voltage in the last iteration, per unit
+    pub max_delta_v: f64,
+    // Harmonic solved in a harmonic study, None for a power flow
+    pub harmonic: Option<f64>,
+}
+
+impl Default for SolutionState {
+    fn default() -> Self {
+        SolutionState {
+            hour: 0,
+            sec: 0.0,
+            interval: 1.0,
+            frequency: 60.0,
+            iterations: 0,
+            control_iteration: 0,
+            load_mult: 1.0,
+            converged: true,
+            max_delta_v: 0.0,
+            harmonic: None,
+        }
+    }
+}
+
+pub trait MeterElement: CktElement {
+    fn meter_base(&self) -> &MeterElementBase;
+
+    fn meter_base_mut(&mut self) -> &mut MeterElementBase;
+
+    // Records the solved circuit at the time of the solution (Pascal
+    // TakeSample)
+    fn take_sample(&mut self, circuit: &Circuit, voltages: &[Complex64], state: &SolutionState);
+
+    // Forgets everything recorded, for a new run (Pascal ResetIt)
+    fn reset(&mut self) {}
+}
@@ -0,0 +1,240 @@
+// Objects defined by DSS script (Pascal TDSSObject). Every object keeps the
+// text of its property values, so they can be read back, dumped and saved
+// exactly as given; what the values mean is up to the object's class.
+
+use std::any::Any;
+use std::fmt;
+
+use dss_common::{DssError, DssResult, codes};
+use dss_parser::DSSParser;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::property::{PropertyDef, find_property};
+
+// Quotes a property value that would not survive tokenizing as is
+pub fn quote_value(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || "=,\"'()[]{}".contains(c));
+    if plain {
+        value.to_string()
+    } else if value.contains('"') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+// State every object has; class implementations embed one
+#[derive(Debug, Clone)]
+pub struct ObjectBase {
+    class_name: &'static str,
+    name: String,
+    properties: &'static [PropertyDef],
+    values: Vec<String>,
+    // Indices of the properties set, the most recently set last (Pascal
+    // PrpSequence); saving in this order recreates the object
+    sequence: Vec<usize>,
+}
+
+impl ObjectBase {
+    // Names are case-insensitive and stored in lower case, as in OpenDSS
+    pub fn new(class_name: &'static str, name: &str, properties: &'static [PropertyDef]) -> Self {
+        ObjectBase {
+            class_name,
+            name: name.to_lowercase(),
+            properties,
+            values: properties
+                .iter()
+                .map(|property| property.default.to_string())
+                .collect(),
+            sequence: Vec::new(),
+        }
+    }
+
+    pub fn class_name(&self) -> &'static str {
+        self.class_name
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn full_name(&self) -> String {
+        format!("{}.{}", self.class_name, self.name)
+    }
+
+    // Keeps the name when an object is overwritten with a copy of another
+    pub(crate) fn rename(&mut self, name: &str) {
+        self.name = name.to_lowercase();
+    }
+
+    pub fn properties(&self) -> &'static [PropertyDef] {
+        self.properties
+    }
+
+    pub fn get_value(&self, index: usize) -> &str {
+        self.values.get(index).map_or("", String::as_str)
+    }
+
+    pub fn set_value(&mut self, index: usize, value: &str) {
+        if let Some(stored) = self.values.get_mut(index) {
+            *stored = value.to_string();
+            self.sequence.retain(|&set| set != index);
+            self.sequence.push(index);
+        }
+    }
+
+    pub fn is_set(&self, index: usize) -> bool {
+        self.sequence.contains(&index)
+    }
+
+    pub fn sequence(&self) -> &[usize] {
+        &self.sequence
+    }
+}
+
+pub trait DssObject: fmt::Debug + Any {
+    fn base(&self) -> &ObjectBase;
+
+    fn base_mut(&mut self) -> &mut ObjectBase;
+
+    // Takes the value of property `index` from the parser's current token.
+    // The text is stored once this and resolve succeed.
+    fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()>;
+
+    // Looks up the objects property `index` names, if it names any (line
+    // codes, wire data, shapes), once the property is set
+    fn resolve(&mut self, _index: usize, _circuit: &Circuit) -> DssResult<()> {
+        Ok(())
+    }
+
+    // Brings derived data up to date after an edit (Pascal RecalcElementData)
+    fn recalc(&mut self) -> DssResult<()> {
+        Ok(())
+    }
+
+    // Takes the properties of `other`, an object of the same class, for
+    // like= (Pascal MakeLike): each property it has set is set again here
+    // from its text, in the order it was set. Classes whose properties
+    // depend on a selector set before them (wdg=, cond=) copy the object
+    // instead with copy_like.
+    fn make_like(&mut self, other: &dyn DssObject, circuit: &Circuit) -> DssResult<()> {
+        for &index in other.base().sequence() {
+            self.set_property_at(index, other.base().get_value(index), circuit)?;
+        }
+        Ok(())
+    }
+
+    fn clone_object(&self) -> Box<dyn DssObject>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    // The object as a circuit element, for objects connected to buses
+    fn as_ckt_element(&self) -> Option<&dyn CktElement> {
+        None
+    }
+
+    fn as_ckt_element_mut(&mut self) -> Option<&mut dyn CktElement> {
+        None
+    }
+
+    fn class_name(&self) -> &'static str {
+        self.base().class_name()
+    }
+
+    fn name(&self) -> &str {
+        self.base().name()
+    }
+
+    fn full_name(&self) -> String {
+        self.base().full_name()
+    }
+
+    // Value of property `index` as text; objects reporting computed values
+    // override this
+    fn get_property(&self, index: usize) -> String {
+        self.base().get_value(index).to_string()
+    }
+
+    // Value of a property given by name; abbreviations allowed
+    fn get_property_value(&self, name: &str) -> Option<String> {
+        find_property(self.base().properties(), name).map(|index| self.get_property(index))
+    }
+
+    // Sets property `index` from its text as an edit would, without
+    // bringing derived data up to date
+    fn set_property_at(&mut self, index: usize, value: &str, circuit: &Circuit) -> DssResult<()> {
+        let mut parser = DSSParser::new();
+        parser.set_token(value);
+        self.set_property(index, &mut parser)?;
+        self.resolve(index, circuit)?;
+        self.base_mut().set_value(index, value);
+        Ok(())
+    }
+
+    // Sets a property given by name from its text, as "Edit Class.name
+    // property=value" does
+    fn set_property_value(&mut self, name: &str, value: &str, circuit: &Circuit) -> DssResult<()> {
+        let index = find_property(self.base().properties(), name).ok_or_else(|| {
+            DssError::new(
+                codes::UNKNOWN_PROPERTY,
+                &format!("Unknown property \"{}\" for \"{}\"", name, self.full_name()),
+            )
+        })?;
+        self.set_property_at(index, value, circuit)?;
+        self.recalc()
+    }
+
+    // Names and values of the properties set, in the order to set them again
+    fn property_values(&self) -> Vec<(String, String)> {
+        let base = self.base();
+        base.sequence()
+            .iter()
+            .map(|&index| {
+                (
+                    base.properties()[index].name.to_string(),
+                    self.get_property(index),
+                )
+            })
+            .collect()
+    }
+
+    // Command that recreates the object: New Class.name with its properties
+    // in the order they were set. Values kept by position ("#2") are written
+    // without a name so they keep their position.
+    fn to_script(&self) -> String {
+        let mut script = format!("New {}", self.full_name());
+        for (name, value) in self.property_values() {
+            let value = quote_value(&value);
+            if name.starts_with('#') {
+                script.push_str(&format!(" {}", value));
+            } else {
+                script.push_str(&format!(" {}={}", name, value));
+            }
+        }
+        script
+    }
+}
+
+// Overwrites `object` with a copy of `other`, keeping its name; false when
+// `other` is of another type
+pub fn copy_like<T: DssObject + Clone>(object: &mut T, other: &dyn DssObject) -> bool {
+    let Some(other) = other.as_any().downcast_ref::<T>() else {
+        return false;
+    };
+    let name = object.name().to_string();
+    *object = other.clone();
+    object.base_mut().rename(&name);
+    true
+}
+
+impl Clone for Box<dyn DssObject> {
+    fn clone(&self) -> Self {
+        self.clone_object()
+    }
+}
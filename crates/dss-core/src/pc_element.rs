@@ -0,0 +1,142 @@
+// Power conversion elements (Pascal TPCElement): loads, generators, storage,
+// sources and the like, which turn electrical power into another form or
+// back. To the solution they are injection currents; for harmonic studies
+// each one injects its fundamental current scaled by its spectrum.
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CKT_PROPERTIES, CktElement};
+use crate::property::{PropertyDef, PropertyKind, concat_properties};
+
+// Properties every power conversion element has, after those of its class
+pub const PC_PROPERTIES: [PropertyDef; 3] = concat_properties(
+    &[PropertyDef {
+        name: "spectrum",
+        kind: PropertyKind::Object("Spectrum"),
+        default: "defaultload",
+        help: "Name of harmonic spectrum for this device.",
+    }],
+    &CKT_PROPERTIES,
+);
+
+// Spectrum and harmonic state; element implementations embed one next to
+// their CktElementBase
+#[derive(Debug, Clone)]
+pub struct PcElementBase {
+    spectrum: String,
+    // Currents at the fundamental frequency, saved from the last power flow
+    // before a harmonic study starts
+    fundamental_currents: Vec<Complex64>,
+}
+
+impl PcElementBase {
+    pub fn new(spectrum: &str) -> Self {
+        PcElementBase {
+            spectrum: spectrum.to_lowercase(),
+            fundamental_currents: Vec::new(),
+        }
+    }
+
+    pub fn get_spectrum(&self) -> &str {
+        &self.spectrum
+    }
+
+    pub fn set_spectrum(&mut self, spectrum: &str) {
+        self.spectrum = spectrum.to_lowercase();
+    }
+
+    pub fn fundamental_currents(&self) -> &[Complex64] {
+        &self.fundamental_currents
+    }
+
+    // Current injected at a harmonic, given the spectrum multiplier for it:
+    // the magnitude scaled by the multiplier, the angle moved by the
+    // multiplier's angle plus the harmonic times the fundamental angle
+    pub fn harmonic_currents(&self, harmonic: f64, multiplier: Complex64) -> Vec<Complex64> {
+        self.fundamental_currents
+            .iter()
+            .map(|current| {
+                Complex64::from_polar(
+                    current.norm() * multiplier.norm(),
+                    harmonic * current.arg() + multiplier.arg(),
+                )
+            })
+            .collect()
+    }
+}
+
+pub trait PcElement: CktElement {
+    fn pc_base(&self) -> &PcElementBase;
+
+    fn pc_base_mut(&mut self) -> &mut PcElementBase;
+
+    // Takes property `index` of PC_PROPERTIES from the parser
+    fn set_pc_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        match index {
+            0 => {
+                let spectrum = parser.get_token().to_string();
+                self.pc_base_mut().set_spectrum(&spectrum);
+                Ok(())
+            }
+            _ => self.ckt_base_mut().set_property(index - 1, parser),
+        }
+    }
+
+    // Moves the operating point toward the voltages of the last iteration
+    // before the next injection currents are taken; most elements have none
+    // to move (Pascal DoPVTypeGen and the like)
+    fn update_vars(&mut self, _voltages: &[Complex64]) {}
+
+    // Names of the state variables a monitor in mode 3 records (Pascal
+    // VariableName); most elements have none
+    fn variable_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // Present values of the state variables, in the order of their names
+    // (Pascal GetAllVariables)
+    fn variables(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    // Switches the element to its dynamics model, the state starting from
+    // the solved voltages (Pascal InitStateVars); most elements keep their
+    // power flow model
+    fn init_dynamics(&mut self, _voltages: &[Complex64]) {}
+
+    // Puts the power flow model back after a dynamics study
+    fn end_dynamics(&mut self) {}
+
+    // Moves the state over a time step of `step` seconds by the trapezoidal
+    // rule, at the voltages of the last iteration; iteration 0 is the first
+    // of the step (Pascal IntegrateStates)
+    fn integrate_states(&mut self, _voltages: &[Complex64], _step: f64, _iteration: usize) {}
+
+    // Saves the fundamental currents of the present solution as the base of
+    // the harmonic injections (Pascal InitHarmonics)
+    fn init_harmonics(&mut self, voltages: &[Complex64]) {
+        let currents = self.get_currents(voltages);
+        self.pc_base_mut().fundamental_currents = currents;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn test_harmonic_currents() {
+        let mut pc = PcElementBase::new("DefaultLoad");
+        assert_eq!(pc.get_spectrum(), "defaultload");
+        pc.fundamental_currents = vec![Complex64::from_polar(10.0, -FRAC_PI_2 / 3.0)];
+
+        // 5th harmonic at 20% with no phase shift of its own
+        let currents = pc.harmonic_currents(5.0, Complex64::new(0.2, 0.0));
+        let expected = Complex64::from_polar(2.0, -5.0 * FRAC_PI_2 / 3.0);
+        assert!((currents[0] - expected).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,296 @@
+// Power delivery elements (Pascal TPDElement): lines, transformers,
+// capacitors, reactors and the like, which carry power from one bus to
+// another. They have current ratings and reliability data, and what flows
+// into them over all terminals is their losses.
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::ckt_element::{CKT_PROPERTIES, CktElement};
+use crate::property::{PropertyDef, PropertyKind, concat_properties};
+
+// Properties every power delivery element has, after those of its class
+pub const PD_PROPERTIES: [PropertyDef; 7] = concat_properties(
+    &[
+        PropertyDef {
+            name: "normamps",
+            kind: PropertyKind::Double,
+            default: "400",
+            help: "Normal rated current, A.",
+        },
+        PropertyDef {
+            name: "emergamps",
+            kind: PropertyKind::Double,
+            default: "600",
+            help: "Maximum or emergency current rating, A.",
+        },
+        PropertyDef {
+            name: "faultrate",
+            kind: PropertyKind::Double,
+            default: "0.1",
+            help: "Failure rate per year.",
+        },
+        PropertyDef {
+            name: "pctperm",
+            kind: PropertyKind::Double,
+            default: "20",
+            help: "Percent of failures that become permanent.",
+        },
+        PropertyDef {
+            name: "repair",
+            kind: PropertyKind::Double,
+            default: "3",
+            help: "Hours to repair.",
+        },
+    ],
+    &CKT_PROPERTIES,
+);
+
+// Ratings and reliability data; element implementations embed one next to
+// their CktElementBase
+#[derive(Debug, Clone)]
+pub struct PdElementBase {
+    norm_amps: f64,
+    emerg_amps: f64,
+    fault_rate: f64,
+    pct_perm: f64,
+    hrs_to_repair: f64,
+}
+
+impl PdElementBase {
+    pub fn new() -> Self {
+        PdElementBase {
+            norm_amps: 400.0,
+            emerg_amps: 600.0,
+            fault_rate: 0.1,
+            pct_perm: 20.0,
+            hrs_to_repair: 3.0,
+        }
+    }
+
+    pub fn get_norm_amps(&self) -> f64 {
+        self.norm_amps
+    }
+
+    pub fn set_norm_amps(&mut self, amps: f64) {
+        self.norm_amps = amps;
+    }
+
+    pub fn get_emerg_amps(&self) -> f64 {
+        self.emerg_amps
+    }
+
+    pub fn set_emerg_amps(&mut self, amps: f64) {
+        self.emerg_amps = amps;
+    }
+
+    pub fn get_fault_rate(&self) -> f64 {
+        self.fault_rate
+    }
+
+    pub fn set_fault_rate(&mut self, rate: f64) {
+        self.fault_rate = rate;
+    }
+
+    pub fn get_pct_perm(&self) -> f64 {
+        self.pct_perm
+    }
+
+    pub fn set_pct_perm(&mut self, pct: f64) {
+        self.pct_perm = pct;
+    }
+
+    pub fn get_hrs_to_repair(&self) -> f64 {
+        self.hrs_to_repair
+    }
+
+    pub fn set_hrs_to_repair(&mut self, hours: f64) {
+        self.hrs_to_repair = hours;
+    }
+
+    // Permanent failures per year
+    pub fn permanent_fault_rate(&self) -> f64 {
+        self.fault_rate * self.pct_perm / 100.0
+    }
+}
+
+impl Default for PdElementBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait PdElement: CktElement {
+    fn pd_base(&self) -> &PdElementBase;
+
+    fn pd_base_mut(&mut self) -> &mut PdElementBase;
+
+    // Shunt elements (capacitors, reactors to ground) carry no power through
+    fn is_shunt(&self) -> bool {
+        false
+    }
+
+    // Takes property `index` of PD_PROPERTIES from the parser
+    fn set_pd_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+        let pd = self.pd_base_mut();
+        match index {
+            0 => pd.norm_amps = parser.make_double()?,
+            1 => pd.emerg_amps = parser.make_double()?,
+            2 => pd.fault_rate = parser.make_double()?,
+            3 => pd.pct_perm = parser.make_double()?,
+            4 => pd.hrs_to_repair = parser.make_double()?,
+            _ => return self.ckt_base_mut().set_property(index - 5, parser),
+        }
+        Ok(())
+    }
+
+    // Total, load and no-load losses in VA. Elements with a no-load part
+    // (transformer cores) split the total; the rest is all load losses.
+    fn losses(&self, voltages: &[Complex64]) -> (Complex64, Complex64, Complex64) {
+        let total = self.total_power(voltages);
+        (total, total, Complex64::new(0.0, 0.0))
+    }
+
+    // Largest phase current magnitude at a terminal
+    fn max_terminal_current(&self, voltages: &[Complex64], terminal: usize) -> f64 {
+        let nconds = self.nconds();
+        self.get_currents(voltages)
+            .iter()
+            .skip(terminal * nconds)
+            .take(self.nphases())
+            .map(|current| current.norm())
+            .fold(0.0, f64::max)
+    }
+
+    // Loading of the element as a percent of its normal or emergency rating,
+    // from the most heavily loaded terminal; 0 without a rating
+    fn pct_loading(&self, voltages: &[Complex64], emergency: bool) -> f64 {
+        let rating = if emergency {
+            self.pd_base().get_emerg_amps()
+        } else {
+            self.pd_base().get_norm_amps()
+        };
+        if rating <= 0.0 {
+            return 0.0;
+        }
+        let terminals = if self.is_shunt() { 1 } else { self.nterms() };
+        (0..terminals)
+            .map(|terminal| self.max_terminal_current(voltages, terminal))
+            .fold(0.0, f64::max)
+            / rating
+            * 100.0
+    }
+
+    fn is_overloaded(&self, voltages: &[Complex64], emergency: bool) -> bool {
+        self.pct_loading(voltages, emergency) > 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::ckt_element::CktElementBase;
+    use crate::cmatrix::CMatrix;
+    use crate::object::{DssObject, ObjectBase};
+
+    // Single-phase 1 ohm resistor with only the PD properties
+    #[derive(Debug, Clone)]
+    struct Resistor {
+        base: ObjectBase,
+        ckt: CktElementBase,
+        pd: PdElementBase,
+    }
+
+    impl DssObject for Resistor {
+        fn base(&self) -> &ObjectBase {
+            &self.base
+        }
+
+        fn base_mut(&mut self) -> &mut ObjectBase {
+            &mut self.base
+        }
+
+        fn set_property(&mut self, index: usize, parser: &mut DSSParser) -> DssResult<()> {
+            self.set_pd_property(index, parser)
+        }
+
+        fn clone_object(&self) -> Box<dyn DssObject> {
+            Box::new(self.clone())
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    impl CktElement for Resistor {
+        fn ckt_base(&self) -> &CktElementBase {
+            &self.ckt
+        }
+
+        fn ckt_base_mut(&mut self) -> &mut CktElementBase {
+            &mut self.ckt
+        }
+
+        fn calc_yprim(&mut self, _frequency: f64) -> DssResult<()> {
+            let mut yprim = CMatrix::new(2);
+            yprim.set(0, 0, Complex64::new(1.0, 0.0));
+            yprim.set(1, 1, Complex64::new(1.0, 0.0));
+            yprim.set_sym(0, 1, Complex64::new(-1.0, 0.0));
+            self.ckt.set_yprim(yprim);
+            Ok(())
+        }
+    }
+
+    impl PdElement for Resistor {
+        fn pd_base(&self) -> &PdElementBase {
+            &self.pd
+        }
+
+        fn pd_base_mut(&mut self) -> &mut PdElementBase {
+            &mut self.pd
+        }
+    }
+
+    #[test]
+    fn test_ratings_and_losses() {
+        let mut resistor = Resistor {
+            base: ObjectBase::new("Resistor", "r1", &PD_PROPERTIES),
+            ckt: CktElementBase::new(1, 2),
+            pd: PdElementBase::new(),
+        };
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("normamps=5 enabled=no");
+        parser.next_param();
+        resistor.set_property(0, &mut parser).unwrap();
+        parser.next_param();
+        resistor.set_property(6, &mut parser).unwrap();
+        assert_eq!(resistor.pd_base().get_norm_amps(), 5.0);
+        assert!(!resistor.ckt_base().is_enabled());
+        assert!((resistor.pd_base().permanent_fault_rate() - 0.02).abs() < 1e-12);
+
+        resistor.calc_yprim(60.0).unwrap();
+        resistor.ckt_base_mut().set_node_refs(0, &[1]);
+        resistor.ckt_base_mut().set_node_refs(1, &[2]);
+        let voltages = [
+            Complex64::new(0.0, 0.0),
+            Complex64::new(106.0, 0.0),
+            Complex64::new(100.0, 0.0),
+        ];
+        // 6 A through 1 ohm
+        let (total, load, no_load) = resistor.losses(&voltages);
+        assert!((total.re - 36.0).abs() < 1e-9);
+        assert_eq!(load, total);
+        assert_eq!(no_load, Complex64::new(0.0, 0.0));
+        assert!((resistor.pct_loading(&voltages, false) - 120.0).abs() < 1e-9);
+        assert!(resistor.is_overloaded(&voltages, false));
+        assert!(!resistor.is_overloaded(&voltages, true));
+    }
+}
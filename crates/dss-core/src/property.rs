@@ -0,0 +1,207 @@
+// Property metadata shared by every object of a class (Pascal PropertyName,
+// PropertyHelp). Values are kept as the text they were given in and turned
+// into numbers by the class that owns them.
+
+use dss_common::{CommandList, DssError, DssResult, codes};
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropertyKind {
+    Text,
+    Integer,
+    Double,
+    // Yes/No; anything starting with y or t is true
+    Bool,
+    // Array of numbers, e.g. "[1 2 3]"
+    Doubles,
+    // Square matrix given by rows, e.g. "[1 2 | 3 4]" or its lower triangle
+    Matrix,
+    // Bus name with optional node numbers, e.g. "b1.1.2"
+    Bus,
+    // One bus per terminal, e.g. "[b1 b2]"
+    Buses,
+    Choice(&'static [&'static str]),
+    // Name of an object of the given class, e.g. linecode=
+    Object(&'static str),
+    // Names of objects of the given class, e.g. wires=[acsr336 acsr336]
+    Objects(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PropertyDef {
+    pub name: &'static str,
+    pub kind: PropertyKind,
+    // Text a new object starts with; empty for none
+    pub default: &'static str,
+    pub help: &'static str,
+}
+
+// Joins two property tables at compile time, for classes that add their own
+// properties in front of those every element of a family has (Pascal
+// CountProperties). N must be the total length.
+pub const fn concat_properties<const A: usize, const B: usize, const N: usize>(
+    first: &[PropertyDef; A],
+    second: &[PropertyDef; B],
+) -> [PropertyDef; N] {
+    assert!(A + B == N, "property table length mismatch");
+    let mut joined = [PropertyDef {
+        name: "",
+        kind: PropertyKind::Text,
+        default: "",
+        help: "",
+    }; N];
+    let mut i = 0;
+    while i < A {
+        joined[i] = first[i];
+        i += 1;
+    }
+    while i < N {
+        joined[i] = second[i - A];
+        i += 1;
+    }
+    joined
+}
+
+// Index of a property; abbreviations allowed, the table order deciding
+// between candidates as for commands
+pub fn find_property(properties: &[PropertyDef], name: &str) -> Option<usize> {
+    properties
+        .iter()
+        .position(|property| property.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            let name = name.to_lowercase();
+            properties
+                .iter()
+                .position(|property| property.name.to_lowercase().starts_with(&name))
+        })
+        .filter(|_| !name.is_empty())
+}
+
+pub fn interpret_yes_no(text: &str) -> bool {
+    matches!(text.chars().next(), Some('y' | 'Y' | 't' | 'T'))
+}
+
+// Numbers of the array in the parser's current token; each element may be
+// an inline math expression
+pub fn read_doubles(parser: &mut DSSParser) -> DssResult<Vec<f64>> {
+    let token = parser.get_token().to_string();
+    let mut values = Vec::new();
+    for element in token.split(|c: char| c.is_whitespace() || c == ',') {
+        if element.is_empty() {
+            continue;
+        }
+        parser.set_token(element);
+        values.push(parser.make_double()?);
+    }
+    parser.set_token(&token);
+    Ok(values)
+}
+
+// Complex number given as a 2-element array [R, X]
+pub fn read_complex(parser: &mut DSSParser, property: &str) -> DssResult<Complex64> {
+    match read_doubles(parser)?[..] {
+        [re, im] => Ok(Complex64::new(re, im)),
+        _ => Err(DssError::new(
+            codes::SYNTAX_ERROR,
+            &format!("\"{}\" needs 2 values: [R, X]", property),
+        )),
+    }
+}
+
+// Names in an array value such as "[acsr336 acsr336]", lower case
+pub fn parse_names(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|name| !name.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// The choice the parser's current token abbreviates
+pub fn read_choice(
+    parser: &DSSParser,
+    choices: &'static [&'static str],
+    property: &str,
+) -> DssResult<&'static str> {
+    let token = parser.get_token();
+    CommandList::new(choices)
+        .get_command(token)
+        .map(|index| choices[index])
+        .ok_or_else(|| {
+            DssError::new(
+                codes::SYNTAX_ERROR,
+                &format!(
+                    "Invalid value \"{}\" for \"{}\"; expected one of {}",
+                    token,
+                    property,
+                    choices.join(", ")
+                ),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROPERTIES: &[PropertyDef] = &[
+        PropertyDef {
+            name: "kv",
+            kind: PropertyKind::Double,
+            default: "12.47",
+            help: "Nominal voltage in kV.",
+        },
+        PropertyDef {
+            name: "kw",
+            kind: PropertyKind::Double,
+            default: "10",
+            help: "Real power in kW.",
+        },
+        PropertyDef {
+            name: "kwh",
+            kind: PropertyKind::Double,
+            default: "0",
+            help: "Energy in kWh.",
+        },
+    ];
+
+    #[test]
+    fn test_find_property() {
+        assert_eq!(find_property(PROPERTIES, "KW"), Some(1));
+        assert_eq!(find_property(PROPERTIES, "kwh"), Some(2));
+        // the first candidate wins
+        assert_eq!(find_property(PROPERTIES, "k"), Some(0));
+        assert_eq!(find_property(PROPERTIES, "kvar"), None);
+        assert_eq!(find_property(PROPERTIES, ""), None);
+    }
+
+    #[test]
+    fn test_concat_properties() {
+        const FIRST: [PropertyDef; 1] = [PROPERTIES[0]];
+        const SECOND: [PropertyDef; 2] = [PROPERTIES[1], PROPERTIES[2]];
+        const JOINED: [PropertyDef; 3] = concat_properties(&FIRST, &SECOND);
+        assert_eq!(JOINED, PROPERTIES);
+    }
+
+    #[test]
+    fn test_read_values() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("mult=[1, 0.5 6] conn=del");
+        parser.next_param();
+        assert_eq!(read_doubles(&mut parser).unwrap(), [1.0, 0.5, 6.0]);
+        parser.next_param();
+        assert_eq!(
+            read_choice(&parser, &["wye", "delta"], "conn").unwrap(),
+            "delta"
+        );
+        parser.set_token("x");
+        assert!(read_choice(&parser, &["wye", "delta"], "conn").is_err());
+        assert_eq!(
+            parse_names("ACSR336, acsr336 4/0"),
+            ["acsr336", "acsr336", "4/0"]
+        );
+        assert!(interpret_yes_no("Yes") && interpret_yes_no("true"));
+        assert!(!interpret_yes_no("no"));
+    }
+}
@@ -0,0 +1,508 @@
+// Circuit reduction (Pascal ReduceAlgs): shrinks the part of a circuit a
+// CktTree covers so long simulations run faster. Elements taken out are
+// disabled rather than removed, and the edits go through the properties, so
+// a saved circuit is the reduced one. Each reduction returns the number of
+// elements it disabled; the bus list has to be made again afterwards.
+
+use dss_common::DssResult;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::ckt_tree::{CktTree, bus_name, bus_terminals};
+use crate::classes::Line;
+use crate::cmatrix::CMatrix;
+use crate::registry::ElementId;
+use crate::units::LengthUnit;
+
+// Lines below this impedance, in ohms, are short (Pascal default Zmag)
+pub const DEFAULT_ZMAG: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReduceOption {
+    // Merge lines in series with nothing else on the bus between them
+    Default,
+    // Eliminate lines with an impedance below the given ohms, moving what is
+    // on their far bus to their near bus
+    ShortLines(f64),
+    // Open the branches closing loops
+    BreakLoops,
+    // Move the line below each switch onto the switch's near bus
+    Switches,
+    // Eliminate the branches with nothing to serve below them
+    Ends,
+    // Move what single phase laterals serve to their taps, eliminating the
+    // laterals
+    Laterals,
+}
+
+impl ReduceOption {
+    pub const NAMES: &'static [&'static str] = &[
+        "default",
+        "shortlines",
+        "breakloops",
+        "switches",
+        "ends",
+        "laterals",
+    ];
+
+    pub fn from_name(name: &str, zmag: f64) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(ReduceOption::Default),
+            "shortlines" => Some(ReduceOption::ShortLines(zmag)),
+            "breakloops" => Some(ReduceOption::BreakLoops),
+            "switches" => Some(ReduceOption::Switches),
+            "ends" => Some(ReduceOption::Ends),
+            "laterals" => Some(ReduceOption::Laterals),
+            _ => None,
+        }
+    }
+}
+
+// Applies a reduction to the circuit the tree covers
+pub fn reduce_circuit(
+    circuit: &mut Circuit,
+    tree: &CktTree,
+    option: ReduceOption,
+) -> DssResult<usize> {
+    match option {
+        ReduceOption::Default => merge_series_lines(circuit, tree),
+        ReduceOption::ShortLines(zmag) => eliminate_short_lines(circuit, tree, zmag),
+        ReduceOption::BreakLoops => break_loops(circuit, tree),
+        ReduceOption::Switches => eliminate_switches(circuit, tree),
+        ReduceOption::Ends => eliminate_ends(circuit, tree),
+        ReduceOption::Laterals => eliminate_laterals(circuit, tree),
+    }
+}
+
+fn line(circuit: &Circuit, id: ElementId) -> Option<&Line> {
+    circuit
+        .element(id)
+        .and_then(|element| element.as_any().downcast_ref::<Line>())
+        .filter(|line| line.ckt_base().is_enabled())
+}
+
+// Sets properties of an element as an Edit command would
+fn edit(circuit: &mut Circuit, id: ElementId, properties: &[(&str, String)]) -> DssResult<()> {
+    let Some(mut element) = circuit.take_element(id) else {
+        return Ok(());
+    };
+    let result = properties
+        .iter()
+        .try_for_each(|(name, value)| element.set_property_value(name, value, circuit));
+    circuit.replace_element(id, element);
+    result
+}
+
+fn disable(circuit: &mut Circuit, id: ElementId) -> DssResult<()> {
+    edit(circuit, id, &[("enabled", "no".to_string())])
+}
+
+// Connects a terminal of an element to another bus spec, through its busN
+// property where it has one
+fn set_terminal_bus(
+    circuit: &mut Circuit,
+    id: ElementId,
+    terminal: usize,
+    spec: &str,
+) -> DssResult<()> {
+    let property = format!("bus{}", terminal + 1);
+    let Some(element) = circuit.element(id) else {
+        return Ok(());
+    };
+    if element
+        .base()
+        .properties()
+        .iter()
+        .any(|p| p.name == property)
+    {
+        return edit(circuit, id, &[(property.as_str(), spec.to_string())]);
+    }
+    if let Some(element) = circuit
+        .element_mut(id)
+        .and_then(|element| element.as_ckt_element_mut())
+    {
+        element.ckt_base_mut().set_bus(terminal, spec);
+    }
+    Ok(())
+}
+
+// Spec of bus `bus` on the nodes of `spec`, e.g. "a.1.2" for "b.1.2"
+fn with_nodes(bus: &str, spec: &str) -> String {
+    match spec.find('.') {
+        Some(dot) => format!("{}{}", bus, &spec[dot..]),
+        None => bus.to_string(),
+    }
+}
+
+// Moves every terminal on bus `from` but those of `except` to bus `to`,
+// keeping their nodes
+fn move_bus(circuit: &mut Circuit, from: &str, to: &str, except: ElementId) -> DssResult<()> {
+    let terminals: Vec<(ElementId, usize, String)> = bus_terminals(circuit)
+        .remove(from)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|&(id, _)| id != except)
+        .filter_map(|(id, terminal)| {
+            let spec = circuit.element(id)?.as_ckt_element()?.get_bus(terminal);
+            Some((id, terminal, with_nodes(to, spec)))
+        })
+        .collect();
+    for (id, terminal, spec) in terminals {
+        set_terminal_bus(circuit, id, terminal, &spec)?;
+    }
+    Ok(())
+}
+
+// Lower triangle of a matrix in the syntax of rmatrix and the like
+fn matrix_text(m: &CMatrix, value: impl Fn(usize, usize) -> f64) -> String {
+    let rows: Vec<String> = (0..m.order())
+        .map(|i| {
+            let row: Vec<String> = (0..=i).map(|j| value(i, j).to_string()).collect();
+            row.join(" ")
+        })
+        .collect();
+    rows.join(" | ")
+}
+
+// The properties making `first` the two lines in series (Pascal
+// TLineObj.MergeWith): the sum of the lengths for lines of the same code,
+// else the sum of the impedance matrices over a length of 1
+fn merged_properties(first: &Line, second: &Line) -> Option<Vec<(&'static str, String)>> {
+    let phases = first.ckt_base().nphases();
+    if phases != second.ckt_base().nphases() || first.is_switch() || second.is_switch() {
+        return None;
+    }
+    let by_constants =
+        |line: &Line| !line.get_geometry().is_empty() || !line.get_spacing().is_empty();
+    // impedances set after the code override it
+    if !first.get_line_code().is_empty()
+        && first.get_line_code() == second.get_line_code()
+        && first.z() == second.z()
+        && first.yc() == second.yc()
+        && !by_constants(first)
+        && !by_constants(second)
+    {
+        let conversion = second
+            .get_length_units()
+            .conversion(first.get_length_units());
+        let length = first.get_length() + second.get_length() * conversion;
+        return Some(vec![("length", length.to_string())]);
+    }
+    if by_constants(first) || by_constants(second) {
+        return None;
+    }
+    let mut z = first.total_z();
+    z.add_matrix(&second.total_z());
+    let mut yc = first.total_yc();
+    yc.add_matrix(&second.total_yc());
+    if z.order() != phases || yc.order() != phases {
+        return None;
+    }
+    let omega = 2.0 * std::f64::consts::PI * first.ckt_base().get_base_frequency();
+    Some(vec![
+        ("rmatrix", matrix_text(&z, |i, j| z.get(i, j).re)),
+        ("xmatrix", matrix_text(&z, |i, j| z.get(i, j).im)),
+        (
+            "cmatrix",
+            matrix_text(&yc, |i, j| yc.get(i, j).im / omega * 1e9),
+        ),
+        ("length", "1".to_string()),
+        ("units", LengthUnit::None.name().to_string()),
+    ])
+}
+
+// Terminal of a two terminal branch away from the root
+fn far_terminal(terminal: usize) -> usize {
+    1 - terminal.min(1)
+}
+
+// Merges lines in series where the bus between them connects nothing else
+// (Pascal DoReduceDefault); the upstream line takes the place of both
+pub fn merge_series_lines(circuit: &mut Circuit, tree: &CktTree) -> DssResult<usize> {
+    let at_bus = bus_terminals(circuit);
+    let branches = tree.branches();
+    let mut merged = vec![false; branches.len()];
+    let mut disabled = 0;
+    for index in 0..branches.len() {
+        if merged[index] {
+            continue;
+        }
+        let id = branches[index].id;
+        let far = far_terminal(branches[index].terminal);
+        let mut children = branches[index].children.clone();
+        while let [child] = children[..] {
+            let Some(first) = line(circuit, id) else {
+                break;
+            };
+            let Some(second) = line(circuit, branches[child].id) else {
+                break;
+            };
+            let shared = bus_name(first.get_bus(far));
+            if at_bus.get(&shared).map_or(0, Vec::len) != 2 {
+                break;
+            }
+            let Some(mut properties) = merged_properties(first, second) else {
+                break;
+            };
+            let beyond = second.get_bus(far_terminal(branches[child].terminal));
+            properties.push((if far == 0 { "bus1" } else { "bus2" }, beyond.to_string()));
+            edit(circuit, id, &properties)?;
+            disable(circuit, branches[child].id)?;
+            merged[child] = true;
+            disabled += 1;
+            children = branches[child].children.clone();
+        }
+    }
+    Ok(disabled)
+}
+
+// Eliminates the lines, switches aside, with a series impedance below
+// `zmag` ohms, moving all on their far bus to their near bus (Pascal
+// DoReduceShortLines). Branches at the root are kept.
+pub fn eliminate_short_lines(circuit: &mut Circuit, tree: &CktTree, zmag: f64) -> DssResult<usize> {
+    let mut disabled = 0;
+    for branch in tree.branches().iter().filter(|b| b.parent.is_some()) {
+        let Some(line) = line(circuit, branch.id) else {
+            continue;
+        };
+        let z = line.total_z();
+        let short = (0..z.order()).all(|i| z.get(i, i).norm() < zmag);
+        if line.is_switch() || z.order() == 0 || !short {
+            continue;
+        }
+        let near = bus_name(line.get_bus(branch.terminal));
+        let far = bus_name(line.get_bus(far_terminal(branch.terminal)));
+        if near == far {
+            continue;
+        }
+        move_bus(circuit, &far, &near, branch.id)?;
+        disable(circuit, branch.id)?;
+        disabled += 1;
+    }
+    Ok(disabled)
+}
+
+// Opens the loops by disabling the branches that close them (Pascal
+// DoBreakLoops)
+pub fn break_loops(circuit: &mut Circuit, tree: &CktTree) -> DssResult<usize> {
+    for &id in tree.loops() {
+        disable(circuit, id)?;
+    }
+    Ok(tree.loops().len())
+}
+
+// Eliminates the switches with a single line below them, moving the line
+// onto the switch's near bus (Pascal DoReduceSwitches)
+pub fn eliminate_switches(circuit: &mut Circuit, tree: &CktTree) -> DssResult<usize> {
+    let at_bus = bus_terminals(circuit);
+    let branches = tree.branches();
+    let mut disabled = 0;
+    for branch in branches.iter().filter(|b| b.parent.is_some()) {
+        let Some(switch) = line(circuit, branch.id).filter(|line| line.is_switch()) else {
+            continue;
+        };
+        let far = bus_name(switch.get_bus(far_terminal(branch.terminal)));
+        let near = bus_name(switch.get_bus(branch.terminal));
+        let [child] = branch.children[..] else {
+            continue;
+        };
+        let below = &branches[child];
+        let Some(next) = line(circuit, below.id) else {
+            continue;
+        };
+        if next.is_switch() || at_bus.get(&far).map_or(0, Vec::len) != 2 {
+            continue;
+        }
+        let spec = with_nodes(&near, next.get_bus(below.terminal));
+        set_terminal_bus(circuit, below.id, below.terminal, &spec)?;
+        disable(circuit, branch.id)?;
+        disabled += 1;
+    }
+    Ok(disabled)
+}
+
+// Eliminates the lines with nothing but other lines below them (Pascal
+// DoReduceDangling). Branches at the root are kept.
+pub fn eliminate_ends(circuit: &mut Circuit, tree: &CktTree) -> DssResult<usize> {
+    let branches = tree.branches();
+    // children come after their parents: from the end, each branch is
+    // decided after all below it
+    let mut dangling = vec![false; branches.len()];
+    for index in (0..branches.len()).rev() {
+        let branch = &branches[index];
+        dangling[index] = line(circuit, branch.id).is_some()
+            && branch.shunts.is_empty()
+            && branch.children.iter().all(|&child| dangling[child]);
+    }
+    let mut disabled = 0;
+    for (index, branch) in branches.iter().enumerate() {
+        let Some(parent) = branch.parent else {
+            continue;
+        };
+        if dangling[index] && !dangling[parent] {
+            for id in std::iter::once(branch.id).chain(tree.downstream(branch.id)) {
+                disable(circuit, id)?;
+                disabled += 1;
+            }
+        }
+    }
+    Ok(disabled)
+}
+
+// Moves the power conversion elements of each single phase lateral off a
+// polyphase line to the tap, and eliminates the lateral (Pascal
+// DoRemoveAll_1ph_Laterals)
+pub fn eliminate_laterals(circuit: &mut Circuit, tree: &CktTree) -> DssResult<usize> {
+    let branches = tree.branches();
+    let mut disabled = 0;
+    for branch in branches {
+        let Some(parent) = branch.parent else {
+            continue;
+        };
+        let Some(lateral) = line(circuit, branch.id) else {
+            continue;
+        };
+        let polyphase_tap = circuit
+            .element(branches[parent].id)
+            .and_then(|element| element.as_ckt_element())
+            .is_some_and(|element| element.nphases() > 1);
+        if lateral.ckt_base().nphases() != 1 || !polyphase_tap {
+            continue;
+        }
+        let tap = lateral.get_bus(branch.terminal).to_string();
+        for id in std::iter::once(branch.id).chain(tree.downstream(branch.id)) {
+            let shunts = tree
+                .find(id)
+                .map_or(&[][..], |index| &branches[index].shunts);
+            for &shunt in shunts {
+                set_terminal_bus(circuit, shunt, 0, &tap)?;
+            }
+            disable(circuit, id)?;
+            disabled += 1;
+        }
+    }
+    Ok(disabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::DssObject;
+    use crate::test_util::add;
+
+    fn enabled(circuit: &Circuit, id: ElementId) -> bool {
+        let element = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        element.ckt_base().is_enabled()
+    }
+
+    fn bus(circuit: &Circuit, id: ElementId, terminal: usize) -> String {
+        let element = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        element.get_bus(terminal).to_string()
+    }
+
+    #[test]
+    fn test_merge_series_lines() {
+        let mut circuit = Circuit::new("test");
+        add(&mut circuit, "linecode", "lc", "r1=0.1 x1=0.2");
+        add(&mut circuit, "linecode", "other", "r1=0.3 x1=0.3");
+        let l1 = add(
+            &mut circuit,
+            "line",
+            "l1",
+            "bus1=sourcebus bus2=b linecode=lc length=1",
+        );
+        let l2 = add(
+            &mut circuit,
+            "line",
+            "l2",
+            "bus1=b bus2=c linecode=lc length=2",
+        );
+        let l3 = add(
+            &mut circuit,
+            "line",
+            "l3",
+            "bus1=c bus2=d linecode=other length=1",
+        );
+        let l4 = add(&mut circuit, "line", "l4", "bus1=d bus2=e linecode=lc");
+        add(&mut circuit, "load", "ld1", "bus1=e");
+        let z = {
+            let mut z = line(&circuit, l1).unwrap().total_z();
+            for id in [l2, l3, l4] {
+                z.add_matrix(&line(&circuit, id).unwrap().total_z());
+            }
+            z
+        };
+
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(merge_series_lines(&mut circuit, &tree).unwrap(), 3);
+        assert!([l2, l3, l4].iter().all(|&id| !enabled(&circuit, id)));
+        assert_eq!(bus(&circuit, l1, 1), "e");
+        // l1 and l2 by length, then the matrices of l3 added
+        let merged = line(&circuit, l1).unwrap();
+        assert_eq!(merged.get_length(), 1.0);
+        assert!((merged.total_z().get(0, 0) - z.get(0, 0)).norm() < 1e-9);
+        assert!(merged.to_script().contains("rmatrix="));
+    }
+
+    #[test]
+    fn test_short_lines_and_switches() {
+        let mut circuit = Circuit::new("test");
+        add(&mut circuit, "line", "l1", "bus1=sourcebus bus2=b");
+        let short = add(
+            &mut circuit,
+            "line",
+            "jumper",
+            "bus1=b bus2=c.1.2.3 length=0.01",
+        );
+        let load = add(&mut circuit, "load", "ld1", "bus1=c.1.2.3");
+        let switch = add(&mut circuit, "line", "sw", "bus1=c bus2=d switch=yes");
+        let l2 = add(&mut circuit, "line", "l2", "bus1=d.1.2.3 bus2=e");
+
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(
+            eliminate_short_lines(&mut circuit, &tree, DEFAULT_ZMAG).unwrap(),
+            1
+        );
+        assert!(!enabled(&circuit, short));
+        assert_eq!(bus(&circuit, load, 0), "b.1.2.3");
+        assert_eq!(bus(&circuit, switch, 0), "b");
+
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(eliminate_switches(&mut circuit, &tree).unwrap(), 1);
+        assert!(!enabled(&circuit, switch));
+        assert_eq!(bus(&circuit, l2, 0), "b.1.2.3");
+    }
+
+    #[test]
+    fn test_loops_ends_and_laterals() {
+        let mut circuit = Circuit::new("test");
+        add(&mut circuit, "line", "l1", "bus1=sourcebus bus2=b");
+        add(&mut circuit, "line", "l2", "bus1=b bus2=c");
+        let l3 = add(&mut circuit, "line", "l3", "bus1=b bus2=d");
+        let tie = add(&mut circuit, "line", "tie", "bus1=c bus2=d");
+        let stub = add(&mut circuit, "line", "stub", "bus1=b bus2=x");
+        let stub2 = add(&mut circuit, "line", "stub2", "bus1=x bus2=y");
+        let lateral = add(&mut circuit, "line", "lat", "bus1=c.2 bus2=f.1 phases=1");
+        let lateral2 = add(&mut circuit, "line", "lat2", "bus1=f.1 bus2=g.1 phases=1");
+        let load = add(&mut circuit, "load", "ld1", "bus1=g.1 phases=1");
+
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(tree.loops(), [tie]);
+        assert_eq!(break_loops(&mut circuit, &tree).unwrap(), 1);
+        assert!(!enabled(&circuit, tie));
+
+        let tree = CktTree::from_sources(&circuit);
+        // with the tie open, l3 serves nothing either
+        assert_eq!(eliminate_ends(&mut circuit, &tree).unwrap(), 3);
+        assert!([l3, stub, stub2].iter().all(|&id| !enabled(&circuit, id)));
+
+        let tree = CktTree::from_sources(&circuit);
+        assert_eq!(eliminate_laterals(&mut circuit, &tree).unwrap(), 2);
+        assert!(!enabled(&circuit, lateral) && !enabled(&circuit, lateral2));
+        assert_eq!(bus(&circuit, load, 0), "c.2");
+        assert_eq!(
+            ReduceOption::from_name("Ends", DEFAULT_ZMAG),
+            Some(ReduceOption::Ends)
+        );
+    }
+}
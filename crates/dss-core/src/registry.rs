@@ -0,0 +1,102 @@
+// Name index of the objects of a circuit. Lookups by full name are hashed,
+// so finding "Load.l1" costs the same in a feeder with 100 000 elements as
+// in one with ten; each class also keeps the list of its own elements.
+
+use std::collections::HashMap;
+
+// Handle of an element: its position in the circuit, which does not change
+// while the circuit exists
+pub type ElementId = usize;
+
+#[derive(Debug, Clone)]
+pub struct Registry {
+    // Lowercase "class.name" to element
+    names: HashMap<String, ElementId>,
+    // Lowercase class name to its elements, in order of definition
+    classes: HashMap<String, Vec<ElementId>>,
+}
+
+fn key(class_name: &str, name: &str) -> String {
+    format!("{}.{}", class_name, name).to_lowercase()
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            names: HashMap::new(),
+            classes: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, class_name: &str, name: &str, id: ElementId) {
+        if self.names.insert(key(class_name, name), id).is_none() {
+            self.classes
+                .entry(class_name.to_lowercase())
+                .or_default()
+                .push(id);
+        }
+    }
+
+    pub fn remove(&mut self, class_name: &str, name: &str) -> Option<ElementId> {
+        let id = self.names.remove(&key(class_name, name))?;
+        if let Some(ids) = self.classes.get_mut(&class_name.to_lowercase()) {
+            ids.retain(|&other| other != id);
+        }
+        Some(id)
+    }
+
+    pub fn find(&self, class_name: &str, name: &str) -> Option<ElementId> {
+        self.names.get(&key(class_name, name)).copied()
+    }
+
+    // Elements of a class, in order of definition
+    pub fn class_elements(&self, class_name: &str) -> &[ElementId] {
+        self.classes
+            .get(&class_name.to_lowercase())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.classes.clear();
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::new();
+        registry.add("Load", "ld1", 0);
+        registry.add("Line", "L1", 1);
+        registry.add("load", "LD2", 2);
+        // adding a name again keeps it once
+        registry.add("Load", "ld1", 0);
+
+        assert_eq!(registry.find("LOAD", "Ld2"), Some(2));
+        assert_eq!(registry.find("Line", "ld1"), None);
+        assert_eq!(registry.class_elements("Load"), [0, 2]);
+        assert!(registry.class_elements("Capacitor").is_empty());
+        assert_eq!(registry.len(), 3);
+
+        assert_eq!(registry.remove("Load", "ld1"), Some(0));
+        assert_eq!(registry.class_elements("load"), [2]);
+        assert_eq!(registry.find("Load", "ld1"), None);
+    }
+}
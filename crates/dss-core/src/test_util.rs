@@ -0,0 +1,119 @@
+// Helpers shared by the unit tests of the element classes
+
+use std::f64::consts::PI;
+
+use dss_common::DssResult;
+use dss_parser::DSSParser;
+use num_complex::Complex64;
+
+use crate::circuit::Circuit;
+use crate::ckt_element::CktElement;
+use crate::class::DssClass;
+use crate::classes::find_class;
+use crate::control_element::ControlElement;
+use crate::control_queue::ControlQueue;
+use crate::object::DssObject;
+use crate::registry::ElementId;
+
+// Fails unless `actual` is within `tolerance` of `expected`
+pub(crate) fn assert_close(actual: Complex64, expected: Complex64, tolerance: f64) {
+    assert!(
+        (actual - expected).norm() < tolerance,
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+// Like `assert_close`, the tolerance scaled by |expected| once it exceeds 1
+pub(crate) fn assert_close_rel(actual: Complex64, expected: Complex64, tolerance: f64) {
+    assert_close(actual, expected, tolerance * expected.norm().max(1.0));
+}
+
+// Positive-sequence phase voltages a, b, c of `vln` volts
+pub(crate) fn balanced(vln: f64) -> [Complex64; 3] {
+    let a = Complex64::from_polar(1.0, -2.0 * PI / 3.0);
+    [vln.into(), a * vln, a * a * vln]
+}
+
+// The same preceded by the ground node, as the node voltages of a circuit
+pub(crate) fn balanced_nodes(vln: f64) -> Vec<Complex64> {
+    let mut nodes = vec![Complex64::new(0.0, 0.0)];
+    nodes.extend(balanced(vln));
+    nodes
+}
+
+// Lets the control carry out every action queued up to `sec` seconds
+pub(crate) fn run_to(
+    control: &mut impl ControlElement,
+    circuit: &mut Circuit,
+    queue: &mut ControlQueue,
+    sec: f64,
+) {
+    queue.set_time(0, sec);
+    while let Some(action) = queue.pop_due() {
+        control.do_pending_action(&action, circuit, &[], queue);
+    }
+}
+
+// `object` with `properties` applied by its class, as "Edit" does
+pub(crate) fn edited<T: DssObject>(
+    mut object: T,
+    class: &dyn DssClass,
+    properties: &str,
+    circuit: &Circuit,
+) -> DssResult<T> {
+    edit(&mut object, class, properties, circuit)?;
+    Ok(object)
+}
+
+// `properties` applied to `object` in place
+pub(crate) fn edit(
+    object: &mut dyn DssObject,
+    class: &dyn DssClass,
+    properties: &str,
+    circuit: &Circuit,
+) -> DssResult<()> {
+    let mut parser = DSSParser::new();
+    parser.set_cmd_string(properties);
+    class.edit(object, &mut parser, circuit)
+}
+
+// A new object of class `class_name` with `properties`, as "New" makes it
+pub(crate) fn new_object(
+    class_name: &str,
+    name: &str,
+    properties: &str,
+    circuit: &Circuit,
+) -> Box<dyn DssObject> {
+    let class = find_class(class_name).unwrap();
+    let mut object = class.new_object(name);
+    edit(object.as_mut(), class, properties, circuit).unwrap();
+    object
+}
+
+// The same added to `circuit`
+pub(crate) fn add(
+    circuit: &mut Circuit,
+    class_name: &str,
+    name: &str,
+    properties: &str,
+) -> ElementId {
+    let object = new_object(class_name, name, properties, circuit);
+    circuit.add_element(object)
+}
+
+// `element` with terminal i on `nodes[i]` and its Yprim built at 60 Hz;
+// the terminals left out stay on ground
+pub(crate) fn placed<T: CktElement>(mut element: T, nodes: &[&[usize]]) -> DssResult<T> {
+    for (terminal, refs) in nodes.iter().enumerate() {
+        element.ckt_base_mut().set_node_refs(terminal, refs);
+    }
+    element.calc_yprim(60.0)?;
+    Ok(element)
+}
+
+// Nodes 1 to `count`, node 0 being ground
+pub(crate) fn first_nodes(count: usize) -> Vec<usize> {
+    (1..=count).collect()
+}
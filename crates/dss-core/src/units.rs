@@ -0,0 +1,80 @@
+// Length units of line data (Pascal LineUnits). Impedances are per unit of
+// the units they were given in; "none" means the length is in the same units
+// as the impedances, whatever they are.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    None,
+    Miles,
+    Kft,
+    Km,
+    Meters,
+    Feet,
+    Inches,
+    Cm,
+    Mm,
+}
+
+impl LengthUnit {
+    // Names in property values, in OpenDSS order
+    pub const NAMES: &'static [&'static str] =
+        &["none", "mi", "kft", "km", "m", "ft", "in", "cm", "mm"];
+
+    const ALL: [LengthUnit; 9] = [
+        LengthUnit::None,
+        LengthUnit::Miles,
+        LengthUnit::Kft,
+        LengthUnit::Km,
+        LengthUnit::Meters,
+        LengthUnit::Feet,
+        LengthUnit::Inches,
+        LengthUnit::Cm,
+        LengthUnit::Mm,
+    ];
+
+    // Unit of a name as given by read_choice
+    pub fn from_name(name: &str) -> Self {
+        Self::NAMES
+            .iter()
+            .position(|unit| unit.eq_ignore_ascii_case(name))
+            .map_or(LengthUnit::None, |index| Self::ALL[index])
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    pub fn to_meters(self) -> f64 {
+        match self {
+            LengthUnit::None | LengthUnit::Meters => 1.0,
+            LengthUnit::Miles => 1609.344,
+            LengthUnit::Kft => 304.8,
+            LengthUnit::Km => 1000.0,
+            LengthUnit::Feet => 0.3048,
+            LengthUnit::Inches => 0.0254,
+            LengthUnit::Cm => 0.01,
+            LengthUnit::Mm => 0.001,
+        }
+    }
+
+    // Factor taking a length in `self` to `to`; 1 when either is none
+    pub fn conversion(self, to: LengthUnit) -> f64 {
+        if self == LengthUnit::None || to == LengthUnit::None {
+            return 1.0;
+        }
+        self.to_meters() / to.to_meters()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(LengthUnit::from_name("KFT"), LengthUnit::Kft);
+        assert_eq!(LengthUnit::Km.name(), "km");
+        assert!((LengthUnit::Miles.conversion(LengthUnit::Kft) - 5.28).abs() < 1e-12);
+        assert_eq!(LengthUnit::None.conversion(LengthUnit::Km), 1.0);
+    }
+}
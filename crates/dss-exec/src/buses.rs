@@ -0,0 +1,272 @@
+// Bus commands: MakeBusList collects the buses elements connect to and
+// numbers their nodes, CalcVoltageBases gives each bus the legal voltage base
+// closest to its nominal voltage, BusCoords reads bus positions and
+// Interpolate places the buses left without one.
+
+use dss_common::{DssError, DssResult, WarningKind, codes};
+use dss_core::{Circuit, CktTree, EnergyMeter};
+
+use crate::executive::Executive;
+
+// The legal base closest to `kv`, by ratio
+fn nearest_base(kv: f64, bases: &[f64]) -> Option<f64> {
+    bases
+        .iter()
+        .copied()
+        .filter(|base| *base > 0.0)
+        .min_by(|a, b| {
+            let error = |base: f64| (kv / base).ln().abs();
+            error(*a).total_cmp(&error(*b))
+        })
+}
+
+// The trees of the zone of a meter, of all meters for "All" or no name, or
+// beyond the sources in a circuit without meters
+pub(crate) fn zone_trees(circuit: &Circuit, name: &str, command: &str) -> DssResult<Vec<CktTree>> {
+    let meters: Vec<_> = match name {
+        "" => circuit.class_elements("EnergyMeter").to_vec(),
+        name if name.eq_ignore_ascii_case("all") => circuit.class_elements("EnergyMeter").to_vec(),
+        name => vec![circuit.find_element("EnergyMeter", name).ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("EnergyMeter \"{}\" not found for {}", name, command),
+            )
+        })?],
+    };
+    if meters.is_empty() {
+        return Ok(vec![CktTree::from_sources(circuit)]);
+    }
+    Ok(meters
+        .iter()
+        .filter_map(|&id| circuit.element(id))
+        .filter_map(|meter| meter.as_any().downcast_ref::<EnergyMeter>())
+        .map(|meter| meter.make_tree(circuit))
+        .collect())
+}
+
+impl Executive {
+    pub(crate) fn do_make_bus_list(&mut self) -> DssResult<String> {
+        self.active_circuit_mut()?.make_bus_list()?;
+        Ok(String::new())
+    }
+
+    pub(crate) fn do_calc_voltage_bases(&mut self) -> DssResult<String> {
+        let bases = self.options.get_doubles("voltagebases").to_vec();
+        let circuit = self.active_circuit_mut()?;
+        circuit.make_bus_list()?;
+        let kv = circuit.nominal_kv();
+
+        let mut unreached = Vec::new();
+        let buses = circuit.buses_mut();
+        for (bus, kv) in buses.iter_mut().zip(kv) {
+            match kv {
+                Some(kv) => bus.set_kv_base(nearest_base(kv, &bases).unwrap_or(kv)),
+                None => unreached.push(bus.name().to_string()),
+            }
+        }
+        if !unreached.is_empty() {
+            self.diagnostics.warn(
+                WarningKind::ValueIgnored,
+                &format!(
+                    "No voltage base found for buses not connected to a source: {}",
+                    unreached.join(", ")
+                ),
+            );
+        }
+        Ok(String::new())
+    }
+
+    // BusCoords [file=]name: one "bus, x, y" line per bus
+    pub(crate) fn do_bus_coords(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let path = self.get_current_dir().join(self.parser.get_token());
+        let text = std::fs::read_to_string(&path).map_err(|err| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!(
+                    "Bus coordinate file \"{}\" could not be read: {}",
+                    path.display(),
+                    err
+                ),
+            )
+        })?;
+
+        let circuit = self.active_circuit_mut()?;
+        circuit.make_bus_list()?;
+        let mut unknown = Vec::new();
+        for line in text.lines() {
+            let fields: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .collect();
+            let [name, x, y, ..] = fields[..] else {
+                continue;
+            };
+            let (Ok(x), Ok(y)) = (x.parse::<f64>(), y.parse::<f64>()) else {
+                continue;
+            };
+            match circuit.bus_list().find(name) {
+                Some(bus) => circuit.buses_mut()[bus].set_coords(x, y),
+                None => unknown.push(name.to_string()),
+            }
+        }
+        if !unknown.is_empty() {
+            self.diagnostics.warn(
+                WarningKind::ValueIgnored,
+                &format!(
+                    "Coordinates given for unknown buses: {}",
+                    unknown.join(", ")
+                ),
+            );
+        }
+        Ok(String::new())
+    }
+
+    // Interpolate [meter | All]: places the buses without coordinates in the
+    // zones of the meters, or beyond the sources in a circuit without meters
+    pub(crate) fn do_interpolate(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let name = self.parser.get_token().to_string();
+        let circuit = self.active_circuit_mut()?;
+        circuit.make_bus_list()?;
+        let trees = zone_trees(circuit, &name, "Interpolate")?;
+        for tree in trees {
+            tree.interpolate_coords(circuit);
+        }
+        Ok(String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::Bus;
+
+    use super::*;
+
+    #[test]
+    fn test_nearest_base() {
+        let bases = [0.48, 12.47, 115.0];
+        assert_eq!(nearest_base(12.0, &bases), Some(12.47));
+        assert_eq!(nearest_base(0.416, &bases), Some(0.48));
+        assert_eq!(nearest_base(69.0, &bases), Some(115.0));
+        assert_eq!(nearest_base(1.0, &[]), None);
+    }
+
+    #[test]
+    fn test_make_bus_list() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new line.l1 bus1=sourcebus bus2=B.1.2 phases=2")
+            .unwrap();
+        exec.execute("new load.ld1 bus1=b.2.0 phases=1").unwrap();
+        exec.execute("new load.ld2 bus1=c phases=1").unwrap();
+        exec.execute("makebuslist").unwrap();
+
+        let buses = exec.get_active_circuit().unwrap().buses();
+        let names: Vec<&str> = buses.iter().map(Bus::name).collect();
+        assert_eq!(names, ["sourcebus", "b", "c"]);
+        assert_eq!(buses[0].nodes(), [1, 2, 3]);
+        assert_eq!(buses[1].nodes(), [1, 2]);
+        assert_eq!(buses[2].nodes(), [1]);
+        // nodes are numbered in the order they are connected
+        let list = exec.get_active_circuit().unwrap().bus_list();
+        assert_eq!(list.num_nodes(), 6);
+        assert_eq!(list.node_ref("B", 2), Some(5));
+        assert_eq!(list.node_bus(6), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_bus_coords() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_buscoords_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("coords.csv"),
+            "sourcebus, 0, 0\nb 100.5 20\n! comment\nnowhere, 1, 1\n",
+        )
+        .unwrap();
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new line.l1 bus1=sourcebus bus2=b").unwrap();
+
+        let result = exec.execute("buscoords coords.csv").unwrap();
+        assert_eq!(
+            result.warnings[0].message,
+            "Coordinates given for unknown buses: nowhere"
+        );
+        // coordinates survive a new bus list
+        exec.execute("makebuslist").unwrap();
+        let buses = exec.get_active_circuit().unwrap().buses();
+        assert_eq!(buses[1].get_coords(), Some((100.5, 20.0)));
+        assert!(exec.execute("buscoords missing.csv").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_calc_voltage_bases() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=115",
+            "new transformer.sub buses=[sourcebus, sub] kvs=[115 12.47]",
+            "new line.l1 bus1=sub bus2=feeder",
+            "new transformer.t1 buses=[feeder, lv] kvs=[12.47 0.416]",
+            "new load.ld1 bus1=lv",
+            "new line.island bus1=x bus2=y",
+            "set voltagebases=[115 12.47 0.48]",
+        ] {
+            exec.execute(line).unwrap();
+        }
+
+        let result = exec.execute("calcvoltagebases").unwrap();
+        assert_eq!(
+            result.warnings[0].message,
+            "No voltage base found for buses not connected to a source: x, y"
+        );
+        let buses = exec.get_active_circuit().unwrap().buses();
+        let bases: Vec<(&str, f64)> = buses
+            .iter()
+            .map(|bus| (bus.name(), bus.get_kv_base()))
+            .collect();
+        assert_eq!(
+            bases,
+            [
+                ("sourcebus", 115.0),
+                ("sub", 12.47),
+                ("feeder", 12.47),
+                ("lv", 0.48),
+                ("x", 0.0),
+                ("y", 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_interpolate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("coords.csv"), "sourcebus, 0, 0\nd, 30, 60\n").unwrap();
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        for line in [
+            "new circuit.c1",
+            "new line.l1 bus1=sourcebus bus2=b",
+            "new line.l2 bus1=b bus2=c",
+            "new line.l3 bus1=c bus2=d",
+            "new line.l4 bus1=d bus2=e",
+            "buscoords coords.csv",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        assert!(exec.execute("interpolate m1").is_err());
+
+        exec.execute("interpolate").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let coords =
+            |bus: &str| circuit.buses()[circuit.bus_list().find(bus).unwrap()].get_coords();
+        // b and c are spread between sourcebus and d, e has nothing beyond
+        assert_eq!(coords("b"), Some((10.0, 20.0)));
+        assert_eq!(coords("c"), Some((20.0, 40.0)));
+        assert_eq!(coords("e"), Some((30.0, 60.0)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,583 @@
+// Executive commands (Pascal ExecCommands/ExecHelper). The table order is the
+// OpenDSS order, which decides what an ambiguous abbreviation resolves to.
+
+use dss_common::{DssError, DssResult, WarningKind, codes};
+
+use dss_core::{Circuit, DssClass, ElementId, find_class};
+use dss_parser::SplitMix64;
+use dss_solver::Solution;
+
+use crate::executive::{Executive, no_active_circuit};
+
+pub(crate) type Handler = fn(&mut Executive) -> DssResult<String>;
+
+pub(crate) struct CommandDef {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub handler: Handler,
+}
+
+pub(crate) const COMMANDS: &[CommandDef] = &[
+    CommandDef {
+        name: "New",
+        help: "Create a new object in the active circuit, e.g. New Line.L1 bus1=a bus2=b. New Circuit.name creates a circuit and makes it active.",
+        handler: Executive::do_new,
+    },
+    CommandDef {
+        name: "Edit",
+        help: "Edit an existing object, e.g. Edit Line.L1 length=2. The object becomes the active element.",
+        handler: Executive::do_edit,
+    },
+    CommandDef {
+        name: "More",
+        help: "Continue editing the active element, e.g. More kW=25 kvar=5.",
+        handler: Executive::do_more,
+    },
+    CommandDef {
+        name: "M",
+        help: "Same as More.",
+        handler: Executive::do_more,
+    },
+    CommandDef {
+        name: "~",
+        help: "Same as More; the usual way to continue a definition on the next line.",
+        handler: Executive::do_more,
+    },
+    CommandDef {
+        name: "Select",
+        help: "Make an element the active element, e.g. Select Line.L1 terminal=2. Without a class the active class is used.",
+        handler: Executive::do_select,
+    },
+    CommandDef {
+        name: "Save",
+        help: "Write the active circuit as DSS script: Save Circuit [dir=directory]. The directory gets Master.dss plus one file per class.",
+        handler: Executive::do_save,
+    },
+    CommandDef {
+        name: "Show",
+        help: "Show a report: Show Timings lists the time spent per command, Show EventLog the control actions taken.",
+        handler: Executive::do_show,
+    },
+    CommandDef {
+        name: "Solve",
+        help: "Solve the active circuit using the current options (see Set).",
+        handler: Executive::do_solve,
+    },
+    CommandDef {
+        name: "Compile",
+        help: "Run the script in a file and make its directory the current directory, e.g. Compile master.dss.",
+        handler: Executive::do_compile,
+    },
+    CommandDef {
+        name: "Set",
+        help: "Set solution options, e.g. Set mode=daily number=24. Type Help Options for the list.",
+        handler: Executive::do_set,
+    },
+    CommandDef {
+        name: "Dump",
+        help: "Show the properties of an element as script, e.g. Dump Line.L1 debug. Without a name the active element is shown, Dump * shows every element.",
+        handler: Executive::do_dump,
+    },
+    CommandDef {
+        name: "Redirect",
+        help: "Run the script in a file; the current directory is restored afterwards.",
+        handler: Executive::do_redirect,
+    },
+    CommandDef {
+        name: "Help",
+        help: "Show the commands, Help <command>, the options (Help Options) or the properties of a class (Help <class>).",
+        handler: Executive::do_help,
+    },
+    CommandDef {
+        name: "?",
+        help: "Show the value of a property, e.g. ? Load.L1.kW. Like every command, it leaves its output in @result.",
+        handler: Executive::do_query,
+    },
+    CommandDef {
+        name: "Clear",
+        help: "Remove the active circuit; options and aliases are kept.",
+        handler: Executive::do_clear,
+    },
+    CommandDef {
+        name: "ClearAll",
+        help: "Remove every circuit and restore the default options.",
+        handler: Executive::do_clear_all,
+    },
+    CommandDef {
+        name: "CalcVoltageBases",
+        help: "Give every bus the voltage base from Set voltagebases closest to its nominal voltage.",
+        handler: Executive::do_calc_voltage_bases,
+    },
+    CommandDef {
+        name: "MakeBusList",
+        help: "Rebuild the list of buses and their nodes from the element connections.",
+        handler: Executive::do_make_bus_list,
+    },
+    CommandDef {
+        name: "BusCoords",
+        help: "Read bus coordinates from a file with one \"bus, x, y\" line per bus, e.g. BusCoords coords.csv.",
+        handler: Executive::do_bus_coords,
+    },
+    CommandDef {
+        name: "Interpolate",
+        help: "Interpolate [meter | All]: place buses without coordinates evenly between the nearest buses up and down the meter zones that have them. Without meters the circuit is traced from the sources.",
+        handler: Executive::do_interpolate,
+    },
+    CommandDef {
+        name: "Reduce",
+        help: "Reduce [meter | All]: reduce the zone of a meter, or of all meters, with the reduction given by Set ReduceOption. A circuit without meters is reduced from the sources.",
+        handler: Executive::do_reduce,
+    },
+    CommandDef {
+        name: "History",
+        help: "List the command lines entered so far; !n runs line n again.",
+        handler: Executive::do_history,
+    },
+    CommandDef {
+        name: "Record",
+        help: "Write every command that succeeds from now on to a script file, e.g. Record session.dss. Stop ends the recording.",
+        handler: Executive::do_record,
+    },
+    CommandDef {
+        name: "Stop",
+        help: "Stop recording (see Record).",
+        handler: Executive::do_stop,
+    },
+    CommandDef {
+        name: "BatchEdit",
+        help: "Edit every element whose name matches a pattern with * and ? wildcards, e.g. BatchEdit Load.* kW=25.",
+        handler: Executive::do_batch_edit,
+    },
+    CommandDef {
+        name: "Get",
+        help: "Show the values of options, e.g. Get mode number.",
+        handler: Executive::do_get,
+    },
+    CommandDef {
+        name: "Export",
+        help: "Write a report to a file: Export EventLog [file] or Export Monitors <monitor> [file]. The file name defaults to <circuit>_EventLog.txt or <circuit>_Mon_<monitor>.csv.",
+        handler: Executive::do_export,
+    },
+    CommandDef {
+        name: "Undo",
+        help: "Take back the last New, Edit, More or BatchEdit command; Set undolevels=n keeps the last n.",
+        handler: Executive::do_undo,
+    },
+    CommandDef {
+        name: "Alias",
+        help: "Define a shorthand for the start of a command line, e.g. Alias pf \"solve mode=snapshot\". An empty text removes it; without arguments all aliases are listed.",
+        handler: Executive::do_alias,
+    },
+    CommandDef {
+        name: "If",
+        help: "Run a command when an inline math condition is nonzero, e.g. If {@result 1.05 >} then Set number=2 else Set number=3.",
+        handler: Executive::do_if,
+    },
+    CommandDef {
+        name: "ForEach",
+        help: "Run a command for each element matching a pattern, with the element name in @var, e.g. ForEach ld in Load.* do Edit @ld kW=10.",
+        handler: Executive::do_foreach,
+    },
+    CommandDef {
+        name: "Reset",
+        help: "Clear what meters have recorded: Reset Monitors, Reset Meters or Reset EventLog. Without an argument all of them are cleared.",
+        handler: Executive::do_reset,
+    },
+    CommandDef {
+        name: "Sample",
+        help: "Have every monitor and energy meter record the last solution.",
+        handler: Executive::do_sample,
+    },
+    CommandDef {
+        name: "AllocateLoads",
+        help: "Estimates the allocation factors for loads that are defined using the XFKVA property. Requires that energymeter objects be defined with the PEAKCURRENT property set. Loads that are not in the zone of an energymeter cannot be allocated. Sensors in a zone set the currents for the branches below them.",
+        handler: Executive::do_allocate_loads,
+    },
+];
+
+impl Executive {
+    // Class and name of "Class.name", or of a bare name in the active class
+    pub(crate) fn resolve_object_name(
+        &self,
+        object: &str,
+    ) -> DssResult<(&'static dyn DssClass, String)> {
+        let Some((class_name, name)) = object.split_once('.') else {
+            let class = self
+                .get_active_circuit()
+                .and_then(Circuit::get_active_class)
+                .and_then(find_class)
+                .ok_or_else(|| {
+                    DssError::new(
+                        codes::OBJECT_NOT_FOUND,
+                        &format!("Object name must be given as Class.name: \"{}\"", object),
+                    )
+                })?;
+            return Ok((class, object.to_string()));
+        };
+        if name.is_empty() {
+            return Err(DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("Object name missing: \"{}\"", object),
+            ));
+        }
+        let class = find_class(class_name).ok_or_else(|| {
+            DssError::new(
+                codes::UNKNOWN_CLASS,
+                &format!("Unknown object type: \"{}\"", class_name),
+            )
+        })?;
+        Ok((class, name.to_string()))
+    }
+
+    fn find_object(&mut self, object: &str) -> DssResult<ElementId> {
+        let (class, name) = self.resolve_object_name(object)?;
+        let circuit = self.active_circuit_mut()?;
+        circuit.find_element(class.name(), &name).ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("Object \"{}\" not found", object),
+            )
+        })
+    }
+
+    // Applies the rest of the command line to an element of the active
+    // circuit through its class
+    fn edit_properties(&mut self, id: ElementId) -> DssResult<()> {
+        let Some(active) = self.active_circuit else {
+            return Err(no_active_circuit());
+        };
+        let dir = self.get_current_dir().to_path_buf();
+        let circuit = &mut self.circuits[active];
+        circuit.set_current_dir(&dir);
+        let Some(mut element) = circuit.take_element(id) else {
+            return Ok(());
+        };
+        let result = match find_class(element.class_name()) {
+            Some(class) => class.edit(element.as_mut(), &mut self.parser, circuit),
+            None => Ok(()),
+        };
+        circuit.replace_element(id, element);
+        result
+    }
+
+    // Edits an element, which becomes the active element
+    fn edit_element(&mut self, id: ElementId) -> DssResult<()> {
+        if !self.parser.get_remainder().trim().is_empty() {
+            self.journal(id);
+        }
+        self.active_circuit_mut()?.set_active_element(id);
+        self.edit_properties(id)
+    }
+
+    pub(crate) fn do_new(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let object = self.parser.get_token().to_string();
+        if let Some((prefix, name)) = object.split_once('.')
+            && prefix.eq_ignore_ascii_case("circuit")
+        {
+            self.circuits.push(Circuit::new(name));
+            self.active_circuit = Some(self.circuits.len() - 1);
+            self.solution = Solution::new();
+            // the remaining parameters define the source
+            self.edit_element(0)?;
+            self.journal.clear();
+            return Ok(String::new());
+        }
+
+        let (class, name) = self.resolve_object_name(&object)?;
+        let circuit = self.active_circuit()?;
+        let existing = circuit.find_element(class.name(), &name);
+        let id = existing.unwrap_or(circuit.elements().len());
+        if existing.is_some() {
+            let full_name = format!("{}.{}", class.name(), name);
+            self.diagnostics.warn(
+                WarningKind::ValueIgnored,
+                &format!(
+                    "Duplicate new element definition: \"{}\". Element being redefined.",
+                    full_name
+                ),
+            );
+        }
+        self.journal(id);
+        self.active_circuit_mut()?
+            .add_element(class.new_object(&name));
+        self.edit_element(id)?;
+        Ok(String::new())
+    }
+
+    // Edit Class.name [props], or with properties only the active element
+    pub(crate) fn do_edit(&mut self) -> DssResult<String> {
+        let start = self.parser.get_position();
+        let param_name = self.parser.next_param();
+        let object = self.parser.get_token().to_string();
+        if object.is_empty() || !param_name.is_empty() {
+            let id = self.active_element_index()?;
+            self.parser.set_position(start);
+            self.edit_element(id)?;
+            return Ok(String::new());
+        }
+
+        let id = self.find_object(&object)?;
+        self.edit_element(id)?;
+        Ok(String::new())
+    }
+
+    // Select [element=]Class.name [terminal=]n
+    pub(crate) fn do_select(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let object = self.parser.get_token().to_string();
+        if object.is_empty() {
+            return Err(DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                "Select needs an element name",
+            ));
+        }
+        let index = self.find_object(&object)?;
+
+        self.parser.next_param();
+        let terminal = if self.parser.get_token().is_empty() {
+            1
+        } else {
+            self.parser.make_integer()?.max(1) as usize
+        };
+        let circuit = self.active_circuit_mut()?;
+        circuit.set_active_element(index);
+        circuit.set_active_terminal(terminal);
+        Ok(String::new())
+    }
+
+    fn active_element_index(&mut self) -> DssResult<ElementId> {
+        let circuit = self.active_circuit_mut()?;
+        circuit.get_active_element().ok_or_else(|| {
+            DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                "There is no active element to edit",
+            )
+        })
+    }
+
+    // More, M and ~: the remaining parameters go to the element last created,
+    // edited or selected
+    pub(crate) fn do_more(&mut self) -> DssResult<String> {
+        let id = self.active_element_index()?;
+        self.edit_element(id)?;
+        Ok(String::new())
+    }
+
+    // BatchEdit Class.pattern [props]; the active element is left alone
+    pub(crate) fn do_batch_edit(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let pattern = self.parser.get_token().to_string();
+        let pattern = if pattern.contains('.') {
+            pattern
+        } else {
+            let (class, name) = self.resolve_object_name(&pattern)?;
+            format!("{}.{}", class.name(), name)
+        };
+
+        // every element gets the same rest of the line
+        let start = self.parser.get_position();
+        let matches = self.active_circuit()?.find_matching(&pattern);
+        for &id in &matches {
+            self.journal(id);
+            self.parser.set_position(start);
+            self.edit_properties(id)?;
+        }
+        if matches.is_empty() {
+            self.diagnostics.warn(
+                WarningKind::ValueIgnored,
+                &format!("BatchEdit: no elements match \"{}\"", pattern),
+            );
+        }
+        Ok(String::new())
+    }
+
+    // ? Class.name.property; the object part may omit the class
+    pub(crate) fn do_query(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let query = self.parser.get_token().to_string();
+        let Some((object, property)) = query.rsplit_once('.') else {
+            return Err(DssError::new(
+                codes::UNKNOWN_PROPERTY,
+                &format!("Query must be given as Class.name.property: \"{}\"", query),
+            ));
+        };
+        self.get_property_value(object, property)
+    }
+
+    // Value of a property of an object of the active circuit, as the query
+    // command shows it
+    pub fn get_property_value(&mut self, object: &str, property: &str) -> DssResult<String> {
+        let id = self.find_object(object)?;
+        let circuit = self.active_circuit()?;
+        circuit
+            .element(id)
+            .and_then(|element| element.get_property_value(property))
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::UNKNOWN_PROPERTY,
+                    &format!("Property \"{}\" not found for \"{}\"", property, object),
+                )
+            })
+    }
+
+    // Sets a property of an object of the active circuit from its text, as
+    // "Edit Class.name property=value" does, Undo included
+    pub fn set_property_value(
+        &mut self,
+        object: &str,
+        property: &str,
+        value: &str,
+    ) -> DssResult<()> {
+        let id = self.find_object(object)?;
+        let dir = self.get_current_dir().to_path_buf();
+        // while a command line runs, the change joins its step
+        let running = self.journal.is_pending();
+        let circuit = self.active_circuit_mut()?;
+        circuit.set_current_dir(&dir);
+        let Some(mut element) = circuit.take_element(id) else {
+            return Ok(());
+        };
+        let kept = self.journal_taken(id, element.as_ref());
+        // the state to put back should the change fail, unless the journal
+        // has just kept it
+        let before = (!kept).then(|| element.clone_object());
+        let circuit = self.active_circuit_mut()?;
+        let result = element.set_property_value(property, value, circuit);
+        if result.is_err() {
+            // a failed change leaves the element as it was
+            let before = if kept {
+                self.journal.take_back(id)
+            } else {
+                before
+            };
+            if let Some(before) = before {
+                element = before;
+            }
+        } else if !running {
+            // no command line ends this change, so it is a step of its own
+            let levels = self.options.get_integer("undolevels").max(0) as usize;
+            self.journal
+                .end_step(&format!("Edit {} {}={}", object, property, value), levels);
+        }
+        self.active_circuit_mut()?.replace_element(id, element);
+        result
+    }
+
+    // Alias [name ["text"]]
+    pub(crate) fn do_alias(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let name = self.parser.get_token().to_string();
+        if name.is_empty() {
+            let listing: Vec<String> = self
+                .aliases
+                .iter()
+                .map(|(alias, text)| format!("Alias {} \"{}\"", alias, text))
+                .collect();
+            return Ok(listing.join("\n"));
+        }
+
+        // nothing after the name shows the alias; an empty text removes it
+        if self.parser.get_remainder().trim().is_empty() {
+            return self.get_alias(&name).map(str::to_string).ok_or_else(|| {
+                DssError::new(
+                    codes::UNKNOWN_COMMAND,
+                    &format!("Alias not found: \"{}\"", name),
+                )
+            });
+        }
+        self.parser.next_param();
+        let text = self.parser.get_token().to_string();
+
+        self.aliases
+            .retain(|(alias, _)| !alias.eq_ignore_ascii_case(&name));
+        if !text.is_empty() {
+            self.aliases.push((name, text));
+        }
+        Ok(String::new())
+    }
+
+    // Dump [Class.name | *] [debug]
+    pub(crate) fn do_dump(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let mut object = self.parser.get_token().to_string();
+        let debug = if object.eq_ignore_ascii_case("debug") {
+            object.clear();
+            true
+        } else {
+            self.parser.next_param();
+            self.parser.get_token().eq_ignore_ascii_case("debug")
+        };
+
+        let indices = if object == "*" {
+            (0..self.active_circuit_mut()?.elements().len()).collect()
+        } else if object.is_empty() {
+            vec![self.active_element_index()?]
+        } else {
+            vec![self.find_object(&object)?]
+        };
+
+        let circuit = self.active_circuit_mut()?;
+        let active = circuit.get_active_element();
+        let mut sections = Vec::new();
+        for index in indices {
+            let Some(element) = circuit.element(index) else {
+                continue;
+            };
+            let mut section = element.to_script();
+            if debug {
+                section.push_str(&format!(
+                    "\n! {} properties set, active: {}",
+                    element.property_values().len(),
+                    if active == Some(index) { "yes" } else { "no" }
+                ));
+                match element
+                    .as_ckt_element()
+                    .and_then(|e| e.ckt_base().get_yprim())
+                {
+                    Some(yprim) => {
+                        section.push_str("\n! Yprim:");
+                        for row in yprim.to_string().lines() {
+                            section.push_str(&format!("\n!   {}", row));
+                        }
+                    }
+                    None => section.push_str("\n! Yprim: not calculated"),
+                }
+            }
+            sections.push(section);
+        }
+        Ok(sections.join("\n"))
+    }
+
+    // The circuit is dropped here, with everything it owns; the most recently
+    // created remaining circuit becomes active
+    pub(crate) fn do_clear(&mut self) -> DssResult<String> {
+        if let Some(index) = self.active_circuit.take() {
+            drop(self.circuits.remove(index));
+        }
+        self.journal.clear();
+        self.solution = Solution::new();
+        self.active_circuit = self.circuits.len().checked_sub(1);
+        Ok(String::new())
+    }
+
+    pub(crate) fn do_clear_all(&mut self) -> DssResult<String> {
+        // replaced rather than cleared so the memory is given back too
+        self.circuits = Vec::new();
+        self.active_circuit = None;
+        self.solution = Solution::new();
+        self.options.reset();
+        self.rng = SplitMix64::new(0);
+        self.event_log.clear();
+        self.journal.clear();
+        Ok(String::new())
+    }
+
+    pub(crate) fn do_redirect(&mut self) -> DssResult<String> {
+        self.redirect(false)
+    }
+
+    pub(crate) fn do_compile(&mut self) -> DssResult<String> {
+        self.redirect(true)
+    }
+}
@@ -0,0 +1,230 @@
+// Script control flow beyond OpenDSS:
+//   If {condition} then command [else command]
+//   ForEach var in Class.pattern do command
+// Conditions are inline math; any nonzero result is true. ForEach stores the
+// full name of each matching element in @var before running the command; the
+// variable is named without its '@', since a defined @var would be expanded.
+
+use dss_common::{DssError, DssResult, codes};
+
+use crate::executive::{Executive, KEEP_RESULT};
+
+const BEGIN_QUOTES: &str = "(\"'[{";
+const END_QUOTES: &str = ")\"']}";
+
+fn syntax_error(message: &str) -> DssError {
+    DssError::new(codes::SYNTAX_ERROR, message)
+}
+
+// Splits `text` at the first standalone, unquoted occurrence of `keyword`
+// (case-insensitive). The parts are trimmed.
+fn split_keyword<'a>(text: &'a str, keyword: &str) -> (&'a str, Option<&'a str>) {
+    let mut closing: Vec<char> = Vec::new();
+    let mut at_word_start = true;
+    for (index, c) in text.char_indices() {
+        if let Some(&end) = closing.last() {
+            if c == end {
+                closing.pop();
+            } else if let Some(quote) = BEGIN_QUOTES.find(c) {
+                closing.push(END_QUOTES.as_bytes()[quote] as char);
+            }
+            continue;
+        }
+        if let Some(quote) = BEGIN_QUOTES.find(c) {
+            closing.push(END_QUOTES.as_bytes()[quote] as char);
+            at_word_start = false;
+            continue;
+        }
+
+        let rest = &text[index..];
+        let word_ends = rest
+            .get(keyword.len()..)
+            .is_some_and(|after| after.is_empty() || after.starts_with(char::is_whitespace));
+        if at_word_start
+            && word_ends
+            && rest.is_char_boundary(keyword.len())
+            && rest[..keyword.len()].eq_ignore_ascii_case(keyword)
+        {
+            return (text[..index].trim(), Some(rest[keyword.len()..].trim()));
+        }
+        at_word_start = c.is_whitespace();
+    }
+    (text.trim(), None)
+}
+
+impl Executive {
+    // Runs a command embedded in If or ForEach and returns its output, which
+    // is also left in @result for the next pass of a ForEach
+    fn run_embedded(&mut self, command: &str) -> DssResult<String> {
+        let (name, output) = self.process_command(command)?;
+        if !KEEP_RESULT.contains(&name) {
+            self.set_result(&output);
+        }
+        Ok(output)
+    }
+
+    pub(crate) fn do_if(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        if self.parser.get_token().is_empty() {
+            return Err(syntax_error(
+                "If needs a condition, e.g. If {@result 1 >} then ...",
+            ));
+        }
+        let condition = self.parser.make_double()?;
+
+        let remainder = self.parser.get_remainder();
+        let (before, Some(branches)) = split_keyword(&remainder, "then") else {
+            return Err(syntax_error("\"then\" expected after the If condition"));
+        };
+        if !before.is_empty() {
+            return Err(syntax_error(&format!(
+                "Unexpected \"{}\" before \"then\"",
+                before
+            )));
+        }
+
+        let (then_branch, else_branch) = split_keyword(branches, "else");
+        let branch = if condition != 0.0 {
+            Some(then_branch)
+        } else {
+            else_branch
+        };
+        match branch {
+            Some(command) if !command.is_empty() => self.run_embedded(command),
+            _ => Ok(String::new()),
+        }
+    }
+
+    pub(crate) fn do_foreach(&mut self) -> DssResult<String> {
+        const USAGE: &str = "ForEach var in Class.pattern do command";
+
+        self.parser.next_param();
+        let variable = self.parser.get_token().to_string();
+        self.parser.next_param();
+        let keyword = self.parser.get_token().to_string();
+        if variable.is_empty() || !keyword.eq_ignore_ascii_case("in") {
+            return Err(syntax_error(&format!("Expected {}", USAGE)));
+        }
+        self.parser.next_param();
+        let pattern = self.parser.get_token().to_string();
+        let remainder = self.parser.get_remainder();
+        let (before, Some(command)) = split_keyword(&remainder, "do") else {
+            return Err(syntax_error(&format!("Expected {}", USAGE)));
+        };
+        if pattern.is_empty() || !before.is_empty() {
+            return Err(syntax_error(&format!("Expected {}", USAGE)));
+        }
+
+        let pattern = if pattern.contains('.') {
+            pattern
+        } else {
+            let (class, name) = self.resolve_object_name(&pattern)?;
+            format!("{}.{}", class.name(), name)
+        };
+        let variable = format!("@{}", variable.trim_start_matches('@'));
+
+        // the command may change the circuit, so the names are taken first
+        let circuit = self.active_circuit_mut()?;
+        let names: Vec<String> = circuit
+            .find_matching(&pattern)
+            .into_iter()
+            .filter_map(|index| circuit.element(index).map(|element| element.full_name()))
+            .collect();
+
+        let mut outputs = Vec::new();
+        for name in names {
+            if let Some(vars) = self.parser.get_vars_mut() {
+                vars.add(&variable, &name);
+            }
+            let output = self.run_embedded(command)?;
+            if !output.is_empty() {
+                outputs.push(output);
+            }
+        }
+        Ok(outputs.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_keyword() {
+        assert_eq!(
+            split_keyword("then set number=2 else set number=3", "else"),
+            ("then set number=2", Some("set number=3"))
+        );
+        assert_eq!(split_keyword("x THEN y", "then"), ("x", Some("y")));
+        // inside quotes, or as part of a word, the keyword does not count
+        assert_eq!(
+            split_keyword("edit \"then x\" othen thenx", "then"),
+            ("edit \"then x\" othen thenx", None)
+        );
+        assert_eq!(
+            split_keyword("{a (b) do} do c", "do"),
+            ("{a (b) do}", Some("c"))
+        );
+        assert_eq!(split_keyword("a do", "do"), ("a", Some("")));
+    }
+
+    #[test]
+    fn test_if_else() {
+        let mut exec = Executive::new();
+        exec.execute("set number=10").unwrap();
+        exec.execute("if {2 1 >} then set number=2 else set number=3")
+            .unwrap();
+        assert_eq!(exec.execute("get number").unwrap().output, "2");
+        exec.execute("if (1 2 >) then set number=4 else set number=5")
+            .unwrap();
+        assert_eq!(exec.execute("get number").unwrap().output, "5");
+        // no else branch: nothing happens
+        exec.execute("if (0) then set number=6").unwrap();
+        assert_eq!(exec.execute("get number").unwrap().output, "5");
+
+        let result = exec.execute("if (1) then get number").unwrap();
+        assert_eq!(result.command, "If");
+        assert_eq!(result.output, "5");
+
+        let err = exec.execute("if (1) set number=1").unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+        let err = exec.execute("if (1 2 +) x then set number=1").unwrap_err();
+        assert_eq!(err.message(), "Unexpected \"x\" before \"then\"");
+    }
+
+    #[test]
+    fn test_if_with_variables() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1 kw=1.06").unwrap();
+        exec.execute("? load.ld1.kw").unwrap();
+        exec.execute("if {@result 1.05 >} then edit load.ld1 kw=1.05")
+            .unwrap();
+        assert_eq!(exec.execute("? load.ld1.kw").unwrap().output, "1.05");
+    }
+
+    #[test]
+    fn test_foreach() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        for name in ["a1", "a2", "b1"] {
+            exec.execute(&format!("new load.{} kw=10", name)).unwrap();
+        }
+
+        exec.execute("foreach ld in Load.a* do edit @ld kw=20")
+            .unwrap();
+        let result = exec.execute("foreach ld in load.* do ? @ld.kw").unwrap();
+        assert_eq!(result.command, "ForEach");
+        assert_eq!(result.output, "20\n20\n10");
+
+        // nested If inside ForEach
+        exec.execute("foreach x in load.* do if {1} then edit @x kvar=1")
+            .unwrap();
+        assert_eq!(exec.execute("? load.b1.kvar").unwrap().output, "1");
+
+        let err = exec.execute("foreach x load.* do ? @x.kw").unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+        let err = exec.execute("foreach x in load.*").unwrap_err();
+        assert_eq!(err.number(), codes::SYNTAX_ERROR);
+    }
+}
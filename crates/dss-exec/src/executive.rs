@@ -0,0 +1,607 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use dss_common::{
+    CommandList, Diagnostics, DssError, DssResult, EventLog, Warning, WarningKind, codes,
+};
+use dss_core::Circuit;
+use dss_parser::{DSSParser, ParserVar, SplitMix64};
+use dss_solver::Solution;
+
+use crate::commands::COMMANDS;
+use crate::history::Recording;
+use crate::options::Options;
+use crate::timings::Timings;
+use crate::undo::Journal;
+
+// Outcome of one command line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandResult {
+    // Canonical name of the command run; empty for blank and comment lines
+    pub command: String,
+    // Text produced by the command (Get, queries, reports...)
+    pub output: String,
+    pub warnings: Vec<Warning>,
+}
+
+// Commands that run other commands; @result keeps what the last of those
+// produced
+pub(crate) const KEEP_RESULT: &[&str] = &["Redirect", "Compile", "If", "ForEach"];
+
+pub(crate) fn no_active_circuit() -> DssError {
+    DssError::new(
+        codes::NO_ACTIVE_CIRCUIT,
+        "There is no active circuit! Create a circuit and retry.",
+    )
+}
+
+// Interprets DSS script one command line at a time (Pascal TExecutive). The
+// first token names the command, abbreviations allowed; command handlers
+// read their parameters from the shared parser.
+#[derive(Debug)]
+pub struct Executive {
+    pub(crate) parser: DSSParser,
+    pub(crate) commands: CommandList,
+    pub(crate) circuits: Vec<Circuit>,
+    pub(crate) active_circuit: Option<usize>,
+    pub(crate) options: Options,
+    pub(crate) diagnostics: Diagnostics,
+    // Current directory on top, one entry per script file being run
+    pub(crate) dir_stack: Vec<PathBuf>,
+    // User shorthands, name and replacement text, in definition order
+    pub(crate) aliases: Vec<(String, String)>,
+    // Command lines given to execute, oldest first
+    pub(crate) history: Vec<String>,
+    pub(crate) recording: Option<Recording>,
+    pub(crate) timings: Timings,
+    // Control actions taken during solutions
+    pub(crate) event_log: EventLog,
+    // Error of the last command line; None when it succeeded
+    pub(crate) last_error: Option<DssError>,
+    pub(crate) journal: Journal,
+    // Solution of the active circuit, kept between Solve commands
+    pub(crate) solution: Solution,
+    // Random numbers of the Monte Carlo modes, restarted by Set seed
+    pub(crate) rng: SplitMix64,
+}
+
+impl Executive {
+    pub fn new() -> Self {
+        let mut parser = DSSParser::new();
+        parser.set_vars(ParserVar::new());
+        let names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+
+        Executive {
+            parser,
+            commands: CommandList::new(&names),
+            circuits: Vec::new(),
+            active_circuit: None,
+            options: Options::new(),
+            diagnostics: Diagnostics::new(),
+            dir_stack: vec![std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))],
+            aliases: Vec::new(),
+            history: Vec::new(),
+            recording: None,
+            timings: Timings::new(),
+            event_log: EventLog::new(),
+            last_error: None,
+            journal: Journal::new(),
+            solution: Solution::new(),
+            rng: SplitMix64::new(0),
+        }
+    }
+
+    // Runs one command line. Besides returning it, the outcome is kept for
+    // scripts in @result (the output, or the error text) and for applications
+    // in last_error; blank and comment lines leave both alone.
+    pub fn execute(&mut self, cmd_line: &str) -> DssResult<CommandResult> {
+        let result = self.recall(cmd_line).and_then(|cmd_line| {
+            let result = self.process_command(&cmd_line);
+            self.log_command(&cmd_line, result.as_ref().ok().map(|(command, _)| *command));
+            let levels = self.options.get_integer("undolevels").max(0) as usize;
+            self.journal.end_step(&cmd_line, levels);
+            result
+        });
+
+        match &result {
+            Ok(("", _)) => {}
+            Ok((command, output)) => {
+                if !KEEP_RESULT.contains(command) {
+                    self.set_result(output);
+                }
+                self.last_error = None;
+            }
+            Err(err) => {
+                self.set_result(&format!("Error {}: {}", err.number(), err));
+                self.last_error = Some(err.clone());
+            }
+        }
+
+        let mut warnings = self.parser.take_warnings();
+        warnings.extend(self.diagnostics.take());
+        let (command, output) = result?;
+        Ok(CommandResult {
+            command: command.to_string(),
+            output,
+            warnings,
+        })
+    }
+
+    pub(crate) fn process_command(&mut self, cmd_line: &str) -> DssResult<(&'static str, String)> {
+        self.parser.set_cmd_string(cmd_line);
+        let mut param_name = self.parser.next_param();
+        let mut param = self.parser.get_token().to_string();
+        if param_name.is_empty() && param.is_empty() {
+            return Ok(("", String::new())); // blank line or comment
+        }
+
+        // an alias stands for the start of the line; full command names win
+        // over aliases, aliases over abbreviations. Expansion is not repeated.
+        if param_name.is_empty()
+            && !self
+                .commands
+                .names()
+                .any(|name| name.eq_ignore_ascii_case(&param))
+            && let Some(text) = self.get_alias(&param)
+        {
+            let expanded = format!("{} {}", text, self.parser.get_remainder().trim());
+            self.parser.set_cmd_string(&expanded);
+            param_name = self.parser.next_param();
+            param = self.parser.get_token().to_string();
+        }
+
+        // commands have no equal sign, so a named first parameter is not one
+        let index = if param_name.is_empty() {
+            self.commands.get_command(&param)
+        } else {
+            None
+        };
+        let Some(index) = index else {
+            return Err(DssError::new(
+                codes::UNKNOWN_COMMAND,
+                &format!("Unknown command: \"{}\"", cmd_line.trim()),
+            ));
+        };
+
+        let command = &COMMANDS[index];
+        if self.commands.is_ambiguous(&param) {
+            self.diagnostics.warn(
+                WarningKind::AmbiguousAbbreviation,
+                &format!("\"{}\" is ambiguous, assuming \"{}\"", param, command.name),
+            );
+        }
+        let start = Instant::now();
+        let output = (command.handler)(self);
+        self.timings.record(command.name, start.elapsed());
+        Ok((command.name, output?))
+    }
+
+    pub fn get_active_circuit(&self) -> Option<&Circuit> {
+        self.active_circuit.map(|index| &self.circuits[index])
+    }
+
+    pub(crate) fn active_circuit(&self) -> DssResult<&Circuit> {
+        self.get_active_circuit().ok_or_else(no_active_circuit)
+    }
+
+    pub(crate) fn active_circuit_mut(&mut self) -> DssResult<&mut Circuit> {
+        match self.active_circuit {
+            Some(index) => Ok(&mut self.circuits[index]),
+            None => Err(no_active_circuit()),
+        }
+    }
+
+    // Stores a command's result in the @result parser variable
+    pub(crate) fn set_result(&mut self, value: &str) {
+        if let Some(vars) = self.parser.get_vars_mut() {
+            vars.add("@result", value);
+        }
+    }
+
+    // Error of the last command line run, if it failed
+    pub fn last_error(&self) -> Option<&DssError> {
+        self.last_error.as_ref()
+    }
+
+    // OpenDSS error number of the last command line; 0 when it succeeded
+    pub fn error_number(&self) -> i32 {
+        self.last_error.as_ref().map_or(0, DssError::number)
+    }
+
+    pub fn get_alias(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map(|(_, text)| text.as_str())
+    }
+
+    pub fn get_event_log(&self) -> &EventLog {
+        &self.event_log
+    }
+
+    pub fn event_log_mut(&mut self) -> &mut EventLog {
+        &mut self.event_log
+    }
+
+    pub fn get_timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    // Command names in table order
+    pub fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.names()
+    }
+
+    pub fn get_parser(&self) -> &DSSParser {
+        &self.parser
+    }
+}
+
+impl Default for Executive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::Vsource;
+
+    use super::*;
+
+    #[test]
+    fn test_blank_and_comment_lines() {
+        let mut exec = Executive::new();
+        assert_eq!(exec.execute("").unwrap(), CommandResult::default());
+        assert_eq!(exec.execute("   ! comment").unwrap().command, "");
+        assert_eq!(exec.execute("// comment").unwrap().command, "");
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let mut exec = Executive::new();
+        let err = exec.execute("frobnicate now").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_COMMAND);
+        assert_eq!(err.message(), "Unknown command: \"frobnicate now\"");
+
+        // a property=value pair is never a command
+        assert!(exec.execute("new=1").is_err());
+    }
+
+    #[test]
+    fn test_new_and_edit() {
+        let mut exec = Executive::new();
+        let err = exec.execute("New Line.L1 bus1=a").unwrap_err();
+        assert_eq!(err.number(), codes::NO_ACTIVE_CIRCUIT);
+        let err = exec.execute("~ bus1=a").unwrap_err();
+        assert_eq!(err.number(), codes::NO_ACTIVE_CIRCUIT);
+
+        let result = exec
+            .execute("new circuit.Feeder basekv=12.47 pu=1.02")
+            .unwrap();
+        assert_eq!(result.command, "New");
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(circuit.name(), "feeder");
+        assert_eq!(
+            circuit.elements()[0]
+                .get_property_value("basekv")
+                .as_deref(),
+            Some("12.47")
+        );
+        // the circuit's properties go to its source
+        let source = circuit
+            .find_object_as::<Vsource>("Vsource", "source")
+            .unwrap();
+        assert_eq!((source.get_base_kv(), source.get_pu()), (12.47, 1.02));
+
+        exec.execute("New Line.L1 sourcebus bus2=b units=km")
+            .unwrap();
+        exec.execute("ed line.l1 bus2=c").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let line = &circuit.elements()[1];
+        assert_eq!(line.full_name(), "Line.l1");
+        assert_eq!(line.get_property_value("bus2").as_deref(), Some("c"));
+        assert_eq!(
+            line.get_property_value("bus1").as_deref(),
+            Some("sourcebus")
+        );
+        assert_eq!(circuit.get_active_element(), Some(1));
+
+        exec.execute("~ length=2.5").unwrap();
+        let result = exec.execute("more r1=0.1 x1=0.3").unwrap();
+        assert_eq!(result.command, "More");
+        exec.execute("m units=mi").unwrap();
+        let line = &exec.get_active_circuit().unwrap().elements()[1];
+        assert_eq!(line.get_property_value("length").as_deref(), Some("2.5"));
+        assert_eq!(line.get_property_value("x1").as_deref(), Some("0.3"));
+        assert_eq!(line.get_property_value("units").as_deref(), Some("mi"));
+
+        let err = exec.execute("New Lyne.L2").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_CLASS);
+        let err = exec.execute("Edit Line.L9 bus1=x").unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_select_and_active_class() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new line.l1 bus1=a").unwrap();
+        exec.execute("new line.l2 bus1=b").unwrap();
+        exec.execute("new load.ld1 kw=10").unwrap();
+
+        exec.execute("select line.l1 terminal=2").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(circuit.get_active_element(), Some(1));
+        assert_eq!(circuit.get_active_class(), Some("Line"));
+        assert_eq!(circuit.get_active_terminal(), 2);
+
+        // names without a class are in the active class
+        exec.execute("select l2").unwrap();
+        exec.execute("edit bus2=c").unwrap();
+        exec.execute("edit l1 length=3").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(
+            circuit.elements()[2].get_property_value("bus2").as_deref(),
+            Some("c")
+        );
+        assert_eq!(
+            circuit.elements()[1]
+                .get_property_value("length")
+                .as_deref(),
+            Some("3")
+        );
+        assert_eq!(circuit.get_active_terminal(), 1);
+
+        // "se" abbreviates Select and Set; Select comes first
+        let result = exec.execute("se load.ld1").unwrap();
+        assert_eq!(result.command, "Select");
+        assert_eq!(result.warnings[0].kind, WarningKind::AmbiguousAbbreviation);
+        assert_eq!(
+            exec.execute("select ld9").unwrap_err().number(),
+            codes::OBJECT_NOT_FOUND
+        );
+        assert_eq!(
+            exec.execute("select lyne.l1").unwrap_err().number(),
+            codes::UNKNOWN_CLASS
+        );
+    }
+
+    #[test]
+    fn test_batch_edit() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        for name in ["res1", "res2", "com1"] {
+            exec.execute(&format!("new load.{} kw=10", name)).unwrap();
+        }
+        exec.execute("new line.l1").unwrap();
+
+        exec.execute("batchedit load.* kw=25").unwrap();
+        exec.execute("batchedit Load.res? kvar=5").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let kw: Vec<_> = circuit.elements()[1..4]
+            .iter()
+            .map(|load| load.get_property_value("kw").unwrap())
+            .collect();
+        assert_eq!(kw, ["25", "25", "25"]);
+        assert_eq!(
+            circuit.elements()[2].get_property_value("kvar").as_deref(),
+            Some("5")
+        );
+        assert_eq!(
+            circuit.elements()[3].get_property_value("kvar").as_deref(),
+            Some("5.39742")
+        );
+        assert_eq!(
+            circuit.elements()[4].get_property_value("kw").as_deref(),
+            None
+        );
+        // the active element does not change
+        assert_eq!(circuit.get_active_element(), Some(4));
+
+        let result = exec.execute("batchedit line.x* length=1").unwrap();
+        assert_eq!(result.warnings[0].kind, WarningKind::ValueIgnored);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1 kw=10 kvar=2").unwrap();
+
+        let result = exec.execute("? Load.LD1.kW").unwrap();
+        assert_eq!(result.command, "?");
+        assert_eq!(result.output, "10");
+        let vars = exec.parser.get_vars_mut().unwrap();
+        assert!(vars.lookup("@result"));
+        assert_eq!(vars.get_value(), "10");
+        // the result can feed later commands
+        exec.execute("edit load.ld1 kvar=@result").unwrap();
+        assert_eq!(exec.execute("? ld1.kvar").unwrap().output, "10");
+
+        let err = exec.execute("? load.ld1.kwatts").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_PROPERTY);
+        assert_eq!(
+            err.message(),
+            "Property \"kwatts\" not found for \"load.ld1\""
+        );
+        let err = exec.execute("? load.ld2.kw").unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_property_values() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new linecode.lc1 r1=0.2 x1=0.4").unwrap();
+        exec.execute("new line.l1 bus1=a bus2=b").unwrap();
+
+        exec.set_property_value("line.l1", "LineCode", "lc1")
+            .unwrap();
+        exec.set_property_value("line.l1", "len", "2").unwrap();
+        assert_eq!(exec.get_property_value("line.l1", "length").unwrap(), "2");
+        assert_eq!(exec.execute("? line.l1.linecode").unwrap().output, "lc1");
+        let circuit = exec.active_circuit().unwrap();
+        let line = circuit.find_element("line", "l1").unwrap();
+        let saved = circuit.element(line).unwrap().to_script();
+        assert_eq!(saved, "New Line.l1 bus1=a bus2=b linecode=lc1 length=2");
+
+        let err = exec
+            .set_property_value("line.l1", "colour", "red")
+            .unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_PROPERTY);
+        let err = exec
+            .set_property_value("line.l1", "linecode", "lc9")
+            .unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_result_and_last_error() {
+        let mut exec = Executive::new();
+        let result = |exec: &Executive| {
+            let vars = exec.get_parser().get_vars().unwrap();
+            vars.get_var_string("@result")
+        };
+
+        exec.execute("set number=4").unwrap();
+        exec.execute("get number").unwrap();
+        assert_eq!(result(&exec), "@result. 4");
+        assert_eq!(exec.error_number(), 0);
+
+        assert!(exec.execute("new load.ld1").is_err());
+        assert_eq!(exec.error_number(), codes::NO_ACTIVE_CIRCUIT);
+        assert_eq!(
+            result(&exec),
+            "@result. Error 202: There is no active circuit! Create a circuit and retry."
+        );
+        // comments do not clear the error
+        exec.execute("! note").unwrap();
+        assert_eq!(exec.error_number(), codes::NO_ACTIVE_CIRCUIT);
+
+        exec.execute("new circuit.c1").unwrap();
+        assert!(exec.last_error().is_none());
+        assert_eq!(result(&exec), "@result. null");
+
+        // the command run by If leaves its output
+        exec.execute("if (1) then get number").unwrap();
+        assert_eq!(result(&exec), "@result. 4");
+        assert!(exec.execute("!99").is_err());
+        assert_eq!(exec.error_number(), codes::UNKNOWN_COMMAND);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut exec = Executive::new();
+        exec.execute("alias c1 \"new circuit.c1 basekv=12.47\"")
+            .unwrap();
+        exec.execute("alias ld \"new load.ld1\"").unwrap();
+        exec.execute("c1").unwrap();
+        let result = exec.execute("LD kw=10 kvar=2").unwrap();
+        assert_eq!(result.command, "New");
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(
+            circuit.elements()[1].get_property_value("kvar").as_deref(),
+            Some("2")
+        );
+
+        assert_eq!(
+            exec.execute("alias").unwrap().output,
+            "Alias c1 \"new circuit.c1 basekv=12.47\"\nAlias ld \"new load.ld1\""
+        );
+        assert_eq!(exec.execute("alias ld").unwrap().output, "new load.ld1");
+
+        // full command names cannot be hidden, abbreviations can
+        exec.execute("alias get \"set number=2\"").unwrap();
+        exec.execute("alias s \"set number=3\"").unwrap();
+        exec.execute("s").unwrap();
+        assert_eq!(exec.execute("get number").unwrap().output, "3");
+
+        exec.execute("alias ld \"\"").unwrap();
+        assert_eq!(exec.get_alias("ld"), None);
+        assert_eq!(
+            exec.execute("ld").unwrap_err().number(),
+            codes::UNKNOWN_COMMAND
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new line.l1 bus1=a bus2=b").unwrap();
+        exec.execute("new load.ld1 kw=10").unwrap();
+
+        let result = exec.execute("dump line.l1").unwrap();
+        assert_eq!(result.output, "New Line.l1 bus1=a bus2=b");
+        assert_eq!(
+            exec.execute("dump debug").unwrap().output,
+            "New Load.ld1 kw=10\n! 1 properties set, active: yes\n! Yprim: not calculated"
+        );
+        let output = exec.execute("dump *").unwrap().output;
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.starts_with("New Vsource.source"));
+    }
+
+    #[test]
+    fn test_set_get_and_clear() {
+        let mut exec = Executive::new();
+        exec.execute("set mode=snapshot Number=10").unwrap();
+        let result = exec.execute("get number mode").unwrap();
+        assert_eq!(result.command, "Get");
+        assert_eq!(result.output, "10 snapshot");
+        assert_eq!(
+            exec.execute("get nothing").unwrap_err().number(),
+            codes::UNKNOWN_OPTION
+        );
+
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new circuit.c2").unwrap();
+        exec.execute("clear").unwrap();
+        assert_eq!(exec.get_active_circuit().unwrap().name(), "c1");
+        // options survive Clear but not ClearAll
+        assert_eq!(exec.execute("get number").unwrap().output, "10");
+        exec.execute("new circuit.c3").unwrap();
+        exec.execute("clearall").unwrap();
+        assert!(exec.get_active_circuit().is_none());
+        assert_eq!(exec.execute("get number").unwrap().output, "1");
+        exec.execute("new circuit.c4").unwrap();
+        exec.execute("clear").unwrap();
+        assert!(exec.get_active_circuit().is_none());
+        assert_eq!(
+            exec.execute("solve").unwrap_err().number(),
+            codes::NO_ACTIVE_CIRCUIT
+        );
+    }
+
+    #[test]
+    fn test_redirect_runs_each_line() {
+        let path =
+            std::env::temp_dir().join(format!("dss_exec_redirect_{}.dss", std::process::id()));
+        std::fs::write(
+            &path,
+            "! test script\nnew circuit.r1\n\nnew load.ld1 kw=10\n~ kvar=3\nset number=5\n",
+        )
+        .unwrap();
+
+        let mut exec = Executive::new();
+        let result = exec
+            .execute(&format!("redirect \"{}\"", path.display()))
+            .unwrap();
+        assert_eq!(result.command, "Redirect");
+        assert_eq!(exec.execute("get number").unwrap().output, "5");
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(
+            circuit.elements()[1].get_property_value("kw").as_deref(),
+            Some("10")
+        );
+        assert_eq!(
+            circuit.elements()[1].get_property_value("kvar").as_deref(),
+            Some("3")
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let err = exec.execute("compile nowhere.dss").unwrap_err();
+        assert_eq!(err.number(), codes::FILE_ERROR);
+    }
+}
@@ -0,0 +1,126 @@
+// Export command: writes reports to files in the current directory (Pascal
+// ExportOptions). Returns the name of the file written.
+
+use dss_common::{CommandList, DssError, DssResult, codes};
+use dss_core::Monitor;
+
+use crate::executive::Executive;
+
+const EXPORTS: &[&str] = &["EventLog", "Monitors"];
+
+impl Executive {
+    pub(crate) fn do_export(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let what = self.parser.get_token().to_string();
+        let index = CommandList::new(EXPORTS)
+            .get_command(&what)
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::NOT_IMPLEMENTED,
+                    &format!(
+                        "Export \"{}\" is not supported; available: {}",
+                        what,
+                        EXPORTS.join(", ")
+                    ),
+                )
+            })?;
+        // Export Monitors names the monitor before the file
+        let monitor = match EXPORTS[index] {
+            "Monitors" => {
+                self.parser.next_param();
+                Some(self.parser.get_token().to_lowercase())
+            }
+            _ => None,
+        };
+        self.parser.next_param();
+        let file_name = self.parser.get_token().to_string();
+
+        let (default_name, contents) = match EXPORTS[index] {
+            "EventLog" => ("EventLog.txt".to_string(), self.event_log.to_text()),
+            "Monitors" => {
+                let name = monitor.unwrap_or_default();
+                let circuit = self.active_circuit_mut()?;
+                let monitor = circuit
+                    .find_object_as::<Monitor>("monitor", &name)
+                    .ok_or_else(|| {
+                        DssError::new(
+                            codes::OBJECT_NOT_FOUND,
+                            &format!("Monitor \"{}\" not found", name),
+                        )
+                    })?;
+                (format!("Mon_{}.csv", name), monitor.to_csv())
+            }
+            _ => unreachable!(),
+        };
+        let file_name = if file_name.is_empty() {
+            match self.get_active_circuit() {
+                Some(circuit) => format!("{}_{}", circuit.name(), default_name),
+                None => default_name,
+            }
+        } else {
+            file_name
+        };
+
+        let path = self.get_current_dir().join(file_name);
+        std::fs::write(&path, contents + "\n").map_err(|err| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!("Error writing \"{}\": {}", path.display(), err),
+            )
+        })?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_event_log() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        exec.execute("new circuit.feeder").unwrap();
+        exec.event_log_mut()
+            .log(2, 0.0, "RegControl.reg1", "CHANGED 1 TAPS TO 1.00625.");
+
+        let path = exec.execute("export event").unwrap().output;
+        assert_eq!(path, dir.join("feeder_EventLog.txt").to_string_lossy());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Hour=2, Sec=0, ControlIter=0, Element=RegControl.reg1, Action=CHANGED 1 TAPS TO 1.00625.\n"
+        );
+        let path = exec.execute("export eventlog events.txt").unwrap().output;
+        assert!(path.ends_with("events.txt"));
+
+        let err = exec.execute("export voltages").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_monitor() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_monitor_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        exec.execute("new circuit.feeder").unwrap();
+        exec.execute("new line.l1 bus1=a bus2=b").unwrap();
+        exec.execute("new monitor.m1 element=line.l1 mode=1")
+            .unwrap();
+
+        let path = exec.execute("export monitors m1").unwrap().output;
+        assert_eq!(path, dir.join("feeder_Mon_m1.csv").to_string_lossy());
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("hour, t(sec)")
+        );
+
+        let err = exec.execute("export monitors m2").unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
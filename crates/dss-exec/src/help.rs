@@ -0,0 +1,87 @@
+// Help command: listings generated from the command and option tables
+
+use dss_common::{DssError, DssResult, codes};
+
+use dss_core::find_class;
+
+use crate::commands::COMMANDS;
+use crate::executive::Executive;
+use crate::options::OPTIONS;
+
+// One "name  text" line per entry, names padded to a common width
+fn format_table<'a>(entries: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let entries: Vec<_> = entries.collect();
+    let width = entries
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    entries
+        .iter()
+        .map(|(name, text)| format!("{:<width$}  {}", name, text, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Executive {
+    pub(crate) fn do_help(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let topic = self.parser.get_token().to_string();
+        if topic.is_empty() {
+            return Ok(format_table(
+                COMMANDS.iter().map(|command| (command.name, command.help)),
+            ));
+        }
+
+        if topic.eq_ignore_ascii_case("options") {
+            return Ok(format_table(
+                OPTIONS.iter().map(|option| (option.name, option.help)),
+            ));
+        }
+        if let Some(index) = self.commands.get_command(&topic) {
+            let command = &COMMANDS[index];
+            return Ok(format!("{}: {}", command.name, command.help));
+        }
+        if let Some(class) = find_class(&topic) {
+            return Ok(format!(
+                "No property descriptions are available for class \"{}\"",
+                class.name()
+            ));
+        }
+        Err(DssError::new(
+            codes::UNKNOWN_COMMAND,
+            &format!("No help available for \"{}\"", topic),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_listings() {
+        let mut exec = Executive::new();
+        let output = exec.execute("help").unwrap().output;
+        assert_eq!(output.lines().count(), COMMANDS.len());
+        let first = output.lines().next().unwrap();
+        assert!(first.starts_with("New "));
+        assert!(first.ends_with("New Circuit.name creates a circuit and makes it active."));
+
+        let output = exec.execute("help red").unwrap().output;
+        assert!(output.starts_with("Redirect: Run the script"));
+
+        let output = exec.execute("help options").unwrap().output;
+        assert_eq!(output.lines().count(), OPTIONS.len());
+        assert!(output.contains("maxiterations    Maximum number of iterations"));
+
+        assert!(
+            exec.execute("help line")
+                .unwrap()
+                .output
+                .contains("\"Line\"")
+        );
+        let err = exec.execute("help nothing").unwrap_err();
+        assert_eq!(err.message(), "No help available for \"nothing\"");
+    }
+}
@@ -0,0 +1,191 @@
+// Command history of an interactive session: History lists the command lines
+// entered so far, "!n" runs entry n again, and Record/Stop copy every command
+// that succeeds into a script file.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use dss_common::{DssError, DssResult, WarningKind, codes};
+
+use crate::executive::Executive;
+
+// Commands about the session itself, which are not worth recording
+const NOT_RECORDED: &[&str] = &["", "History", "Record", "Stop"];
+
+#[derive(Debug)]
+pub(crate) struct Recording {
+    path: PathBuf,
+    file: File,
+}
+
+// Entry number of a "!n" recall line
+fn recall_number(cmd_line: &str) -> Option<&str> {
+    let number = cmd_line.trim().strip_prefix('!')?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some(number)
+}
+
+impl Executive {
+    // The line to run for `cmd_line`: itself, or the history entry it recalls
+    pub(crate) fn recall(&self, cmd_line: &str) -> DssResult<String> {
+        let Some(number) = recall_number(cmd_line) else {
+            return Ok(cmd_line.to_string());
+        };
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|index| self.history.get(index))
+            .cloned()
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::UNKNOWN_COMMAND,
+                    &format!("History entry {} not found", number),
+                )
+            })
+    }
+
+    // Called for every line run through execute
+    pub(crate) fn log_command(&mut self, cmd_line: &str, command: Option<&str>) {
+        if cmd_line.trim().is_empty() {
+            return;
+        }
+        self.history.push(cmd_line.trim().to_string());
+
+        let Some(command) = command else {
+            return;
+        };
+        if NOT_RECORDED.contains(&command) {
+            return;
+        }
+        if let Some(recording) = &mut self.recording
+            && let Err(err) = writeln!(recording.file, "{}", cmd_line.trim())
+        {
+            let message = format!(
+                "Recording to \"{}\" stopped: {}",
+                recording.path.display(),
+                err
+            );
+            self.recording = None;
+            self.diagnostics.warn(WarningKind::ValueIgnored, &message);
+        }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub(crate) fn do_history(&mut self) -> DssResult<String> {
+        let lines: Vec<String> = self
+            .history
+            .iter()
+            .enumerate()
+            .map(|(index, line)| format!("{:>4}  {}", index + 1, line))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    // Record file: later commands are written to the file, which is replaced
+    pub(crate) fn do_record(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let file_name = self.parser.get_token().to_string();
+        if file_name.is_empty() {
+            return Err(DssError::new(codes::FILE_ERROR, "Record needs a file name"));
+        }
+        let path = self.get_current_dir().join(file_name);
+        let file = File::create(&path).map_err(|err| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!("Cannot record to \"{}\": {}", path.display(), err),
+            )
+        })?;
+        self.recording = Some(Recording { path, file });
+        Ok(String::new())
+    }
+
+    // Stop [recording]; returns the name of the file written
+    pub(crate) fn do_stop(&mut self) -> DssResult<String> {
+        match self.recording.take() {
+            Some(recording) => Ok(recording.path.to_string_lossy().into_owned()),
+            None => Ok(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recall_number() {
+        assert_eq!(recall_number("!12"), Some("12"));
+        assert_eq!(recall_number("  !3 "), Some("3"));
+        // comments are not recalls
+        assert_eq!(recall_number("! 3"), None);
+        assert_eq!(recall_number("!note"), None);
+        assert_eq!(recall_number("!"), None);
+    }
+
+    #[test]
+    fn test_history_and_recall() {
+        let mut exec = Executive::new();
+        exec.execute("set number=2").unwrap();
+        exec.execute("get number").unwrap();
+        exec.execute("set number=7").unwrap();
+        assert_eq!(exec.execute("!2").unwrap().output, "7");
+        exec.execute("!1").unwrap();
+        assert_eq!(exec.execute("get number").unwrap().output, "2");
+        assert!(exec.execute("bogus").is_err());
+
+        assert_eq!(
+            exec.history(),
+            [
+                "set number=2",
+                "get number",
+                "set number=7",
+                "get number",
+                "set number=2",
+                "get number",
+                "bogus"
+            ]
+        );
+        let listing = exec.execute("history").unwrap().output;
+        assert!(listing.starts_with("   1  set number=2\n   2  get number"));
+
+        let err = exec.execute("!99").unwrap_err();
+        assert_eq!(err.message(), "History entry 99 not found");
+    }
+
+    #[test]
+    fn test_record_and_stop() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_record_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+
+        exec.execute("set number=1").unwrap();
+        exec.execute("record session.dss").unwrap();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("! comment").unwrap();
+        assert!(exec.execute("new lyne.l1").is_err());
+        exec.execute("new load.ld1 kw=5").unwrap();
+        exec.execute("history").unwrap();
+        let result = exec.execute("stop recording").unwrap();
+        exec.execute("set number=3").unwrap();
+
+        let path = dir.join("session.dss");
+        assert_eq!(result.output, path.to_string_lossy());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "new circuit.c1\nnew load.ld1 kw=5\n"
+        );
+
+        // the recording replays
+        let mut replay = Executive::new();
+        replay
+            .execute(&format!("redirect \"{}\"", path.display()))
+            .unwrap();
+        assert_eq!(replay.execute("? load.ld1.kw").unwrap().output, "5");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
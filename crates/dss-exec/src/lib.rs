@@ -0,0 +1,22 @@
+// DSS script executive: command dispatch over the parser and circuit model
+
+mod buses;
+mod commands;
+mod control_flow;
+mod executive;
+mod export;
+mod help;
+mod history;
+mod meters;
+mod options;
+mod reduce;
+mod save;
+mod script;
+mod show;
+mod solve;
+mod timings;
+mod undo;
+
+pub use executive::{CommandResult, Executive};
+pub use options::{ChoiceAlias, OPTIONS, OptionDef, OptionKind, OptionValue, Options};
+pub use timings::{TimingCategory, TimingEntry, Timings};
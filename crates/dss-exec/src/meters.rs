@@ -0,0 +1,131 @@
+// Meter commands: Reset clears what monitors and energy meters recorded (and
+// the event log), Sample has every meter record the last solution and
+// AllocateLoads fits the loads to the currents the meters and sensors measure.
+
+use dss_common::{CommandList, DssError, DssResult, codes};
+use dss_core::{SolutionState, allocate_loads};
+
+use crate::executive::Executive;
+
+const RESETS: &[&str] = &["Monitors", "Meters", "EventLog"];
+
+// Solutions and allocations before the final solution (Pascal allocates
+// twice)
+const ALLOCATION_PASSES: usize = 2;
+
+impl Executive {
+    pub(crate) fn do_reset(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let what = self.parser.get_token().to_string();
+        if what.is_empty() {
+            self.active_circuit_mut()?.reset_meters(None);
+            self.event_log.clear();
+            return Ok(String::new());
+        }
+        let index = CommandList::new(RESETS).get_command(&what).ok_or_else(|| {
+            DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!(
+                    "Reset \"{}\" is not supported; available: {}",
+                    what,
+                    RESETS.join(", ")
+                ),
+            )
+        })?;
+        match RESETS[index] {
+            "Monitors" => self.active_circuit_mut()?.reset_meters(Some("Monitor")),
+            "Meters" => self.active_circuit_mut()?.reset_meters(Some("EnergyMeter")),
+            "EventLog" => self.event_log.clear(),
+            _ => unreachable!(),
+        }
+        Ok(String::new())
+    }
+
+    pub(crate) fn do_sample(&mut self) -> DssResult<String> {
+        let circuit = self.active_circuit_mut()?;
+        if circuit.get_node_voltages().is_empty() {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                "The circuit has not been solved; there is nothing to sample",
+            ));
+        }
+        circuit.sample_meters(&SolutionState::default());
+        Ok(String::new())
+    }
+
+    pub(crate) fn do_allocate_loads(&mut self) -> DssResult<String> {
+        for _ in 0..ALLOCATION_PASSES {
+            self.do_solve()?;
+            self.allocate_solved_loads()?;
+        }
+        self.do_solve()
+    }
+
+    // One allocation against the last solution
+    fn allocate_solved_loads(&mut self) -> DssResult<()> {
+        let circuit = self.active_circuit_mut()?;
+        let voltages = circuit.get_node_voltages().to_vec();
+        if voltages.is_empty() {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                "The circuit has not been solved; loads cannot be allocated",
+            ));
+        }
+        allocate_loads(circuit, &voltages);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::{Complex64, EnergyMeter, Monitor};
+
+    use super::*;
+
+    #[test]
+    fn test_sample_and_reset() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.feeder").unwrap();
+        exec.execute("new line.l1 bus1=a bus2=b r1=1 x1=0 r0=1 x0=0 c1=0 c0=0 length=1")
+            .unwrap();
+        exec.execute("new load.ld1 bus1=b phases=3").unwrap();
+        exec.execute("new monitor.m1 element=line.l1").unwrap();
+        exec.execute("new energymeter.e1 element=line.l1").unwrap();
+        let err = exec.execute("sample").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+
+        exec.execute("makebuslist").unwrap();
+        let circuit = exec.active_circuit_mut().unwrap();
+        let count = circuit.num_nodes() + 1;
+        circuit.set_node_voltages(vec![Complex64::new(1.0, 0.0); count]);
+        exec.execute("sample").unwrap();
+        exec.execute("sample").unwrap();
+
+        let monitor = |exec: &Executive| {
+            let circuit = exec.get_active_circuit().unwrap();
+            circuit
+                .find_object_as::<Monitor>("monitor", "m1")
+                .unwrap()
+                .sample_count()
+        };
+        assert_eq!(monitor(&exec), 2);
+        let zone = |exec: &Executive| {
+            let circuit = exec.get_active_circuit().unwrap();
+            let meter = circuit
+                .find_object_as::<EnergyMeter>("energymeter", "e1")
+                .unwrap();
+            meter
+                .zone()
+                .map(|zone| (zone.branches.len(), zone.loads.len()))
+        };
+        assert_eq!(zone(&exec), Some((1, 1)));
+
+        exec.execute("reset meters").unwrap();
+        assert_eq!(monitor(&exec), 2);
+        assert_eq!(zone(&exec), None);
+        exec.execute("reset").unwrap();
+        assert_eq!(monitor(&exec), 0);
+        let err = exec.execute("reset faults").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+    }
+}
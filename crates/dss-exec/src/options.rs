@@ -0,0 +1,644 @@
+// Solution options changed by Set and read back by Get (Pascal ExecOptions).
+// Each option has a fixed type; names may be abbreviated and the table order
+// decides between candidates, as for commands.
+
+use std::fmt;
+
+use dss_common::{CommandList, DssError, DssResult, codes};
+use dss_core::{DEFAULT_ZMAG, RandomType, ReduceOption, apply_growth};
+use dss_parser::SplitMix64;
+use dss_solver::AddType;
+
+use crate::executive::Executive;
+
+// Step size Set mode=dynamics starts from, seconds
+const DYNAMICS_STEP: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptionKind {
+    Text,
+    Integer,
+    Double,
+    Bool,
+    // Seconds; values may carry an s, m or h suffix, e.g. "15m"
+    Duration,
+    DoubleArray,
+    // The values, then other names some values go by, e.g. ("harmonic",
+    // &["harmonics"])
+    Choice(&'static [&'static str], &'static [ChoiceAlias]),
+}
+
+pub type ChoiceAlias = (&'static str, &'static [&'static str]);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Text(String),
+    Integer(i32),
+    Double(f64),
+    Bool(bool),
+    DoubleArray(Vec<f64>),
+    Choice(&'static str),
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionValue::Text(text) => write!(f, "{}", text),
+            OptionValue::Integer(value) => write!(f, "{}", value),
+            OptionValue::Double(value) => write!(f, "{}", value),
+            OptionValue::Bool(true) => write!(f, "Yes"),
+            OptionValue::Bool(false) => write!(f, "No"),
+            OptionValue::DoubleArray(values) => {
+                let values: Vec<String> = values.iter().map(f64::to_string).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            OptionValue::Choice(choice) => write!(f, "{}", choice),
+        }
+    }
+}
+
+pub struct OptionDef {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: OptionKind,
+    pub default: fn() -> OptionValue,
+}
+
+pub const SOLUTION_MODES: &[&str] = &[
+    "snapshot",
+    "daily",
+    "yearly",
+    "dutycycle",
+    "montecarlo1",
+    "montecarlo2",
+    "montecarlo3",
+    "faultstudy",
+    "loadduration1",
+    "loadduration2",
+    "dynamics",
+    "harmonic",
+    "autoadd",
+];
+// scripts written for OpenDSS often spell the mode harmonics
+pub const SOLUTION_MODE_ALIASES: &[ChoiceAlias] = &[("harmonic", &["harmonics"])];
+pub const CONTROL_MODES: &[&str] = &["static", "event", "time", "off"];
+pub const ALGORITHMS: &[&str] = &["normal", "newton"];
+pub const LOAD_MODELS: &[&str] = &["powerflow", "admittance"];
+pub const EARTH_MODELS: &[&str] = &["carson", "fullcarson", "deri"];
+
+pub const OPTIONS: &[OptionDef] = &[
+    OptionDef {
+        name: "mode",
+        help: "Solution mode: snapshot, daily, yearly, dutycycle, montecarlo1-3, faultstudy, loadduration1-2, dynamics, harmonic or autoadd.",
+        kind: OptionKind::Choice(SOLUTION_MODES, SOLUTION_MODE_ALIASES),
+        default: || OptionValue::Choice("snapshot"),
+    },
+    OptionDef {
+        name: "number",
+        help: "Number of solutions or time steps to perform for each Solve command.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(1),
+    },
+    OptionDef {
+        name: "stepsize",
+        help: "Time step size in seconds; append s, m or h for seconds, minutes or hours, e.g. 15m.",
+        kind: OptionKind::Duration,
+        default: || OptionValue::Double(3600.0),
+    },
+    OptionDef {
+        name: "hour",
+        help: "Start hour for the solution.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(0),
+    },
+    OptionDef {
+        name: "sec",
+        help: "Seconds from the start of the current hour.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(0.0),
+    },
+    OptionDef {
+        name: "year",
+        help: "Year of the study, used for growth multipliers.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(0),
+    },
+    OptionDef {
+        name: "frequency",
+        help: "Base frequency of the solution in Hz.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(60.0),
+    },
+    OptionDef {
+        name: "harmonics",
+        help: "Harmonics a harmonic solution is made at, e.g. [3 5 7], or ALL for every harmonic in the spectra of the sources and loads.",
+        kind: OptionKind::DoubleArray,
+        default: || OptionValue::DoubleArray(Vec::new()),
+    },
+    OptionDef {
+        name: "voltagebases",
+        help: "Array of legal line-to-line base voltages in kV, e.g. [115, 12.47, 0.48].",
+        kind: OptionKind::DoubleArray,
+        default: || OptionValue::DoubleArray(vec![0.208, 0.48, 12.47, 24.9, 34.5, 115.0, 230.0]),
+    },
+    OptionDef {
+        name: "tolerance",
+        help: "Convergence tolerance of the iterative solution, in per unit.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(0.0001),
+    },
+    OptionDef {
+        name: "maxiterations",
+        help: "Maximum number of iterations for a power flow solution.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(15),
+    },
+    OptionDef {
+        name: "maxcontroliter",
+        help: "Maximum number of control iterations per solution.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(10),
+    },
+    OptionDef {
+        name: "controlmode",
+        help: "Control action mode: static, event, time or off.",
+        kind: OptionKind::Choice(CONTROL_MODES, &[]),
+        default: || OptionValue::Choice("static"),
+    },
+    OptionDef {
+        name: "algorithm",
+        help: "Power flow algorithm: normal (current injection) or newton.",
+        kind: OptionKind::Choice(ALGORITHMS, &[]),
+        default: || OptionValue::Choice("normal"),
+    },
+    OptionDef {
+        name: "loadmodel",
+        help: "How loads enter the solution: powerflow (injection currents) or admittance.",
+        kind: OptionKind::Choice(LOAD_MODELS, &[]),
+        default: || OptionValue::Choice("powerflow"),
+    },
+    OptionDef {
+        name: "loadmult",
+        help: "Global multiplier applied to the kW and kvar of all loads.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(1.0),
+    },
+    OptionDef {
+        name: "random",
+        help: "How the Monte Carlo modes draw load multipliers: gaussian, uniform, lognormal or none.",
+        kind: OptionKind::Choice(RandomType::NAMES, &[]),
+        default: || OptionValue::Choice("gaussian"),
+    },
+    OptionDef {
+        name: "seed",
+        help: "Seed of the random numbers for the Monte Carlo modes; setting it restarts the sequence, so a study can be repeated exactly.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(0),
+    },
+    OptionDef {
+        name: "ldcurve",
+        help: "Load shape used as the load duration curve of the loadduration modes.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text(String::new()),
+    },
+    OptionDef {
+        name: "earthmodel",
+        help: "Earth return model for line constants: carson, fullcarson or deri.",
+        kind: OptionKind::Choice(EARTH_MODELS, &[]),
+        default: || OptionValue::Choice("deri"),
+    },
+    OptionDef {
+        name: "trapezoidal",
+        help: "Use trapezoidal integration for energy meters (Yes/No).",
+        kind: OptionKind::Bool,
+        default: || OptionValue::Bool(false),
+    },
+    OptionDef {
+        name: "allowduplicates",
+        help: "Allow new elements with the name of an existing one (Yes/No).",
+        kind: OptionKind::Bool,
+        default: || OptionValue::Bool(false),
+    },
+    OptionDef {
+        name: "undolevels",
+        help: "Number of New, Edit, More and BatchEdit commands Undo can take back; 0 turns the journal off.",
+        kind: OptionKind::Integer,
+        default: || OptionValue::Integer(10),
+    },
+    OptionDef {
+        name: "defaultdaily",
+        help: "Default daily load shape name.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text("default".to_string()),
+    },
+    OptionDef {
+        name: "defaultyearly",
+        help: "Default yearly load shape name.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text("default".to_string()),
+    },
+    OptionDef {
+        name: "casename",
+        help: "Name of the case, used for output file names.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text(String::new()),
+    },
+    OptionDef {
+        name: "datapath",
+        help: "Directory where results are written.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text(String::new()),
+    },
+    OptionDef {
+        name: "reduceoption",
+        help: "Reduction the Reduce command applies: default (merge lines in series), shortlines (eliminate lines below Zmag ohms), breakloops, switches (merge switches into the line below), ends (eliminate branches serving nothing) or laterals (move single phase laterals' loads to their taps).",
+        kind: OptionKind::Choice(ReduceOption::NAMES, &[]),
+        default: || OptionValue::Choice("default"),
+    },
+    OptionDef {
+        name: "zmag",
+        help: "Impedance in ohms below which ReduceOption=shortlines eliminates a line.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(DEFAULT_ZMAG),
+    },
+    OptionDef {
+        name: "autobuslist",
+        help: "Buses AutoAdd tries, e.g. [b1 b2 b3]; every bus when empty.",
+        kind: OptionKind::Text,
+        default: || OptionValue::Text(String::new()),
+    },
+    OptionDef {
+        name: "addtype",
+        help: "What AutoAdd places: generator or capacitor.",
+        kind: OptionKind::Choice(AddType::NAMES, &[]),
+        default: || OptionValue::Choice("generator"),
+    },
+    OptionDef {
+        name: "genkw",
+        help: "Size of the generators AutoAdd places, kW.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(1000.0),
+    },
+    OptionDef {
+        name: "genpf",
+        help: "Power factor of the generators AutoAdd places.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(1.0),
+    },
+    OptionDef {
+        name: "capkvar",
+        help: "Size of the capacitors AutoAdd places, kvar.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(600.0),
+    },
+    OptionDef {
+        name: "lossweight",
+        help: "Weight of the loss reduction in the AutoAdd figure of merit.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(1.0),
+    },
+    OptionDef {
+        name: "ueweight",
+        help: "Weight of the EEN reduction in the AutoAdd figure of merit.",
+        kind: OptionKind::Double,
+        default: || OptionValue::Double(1.0),
+    },
+];
+
+// Current value of every option, in table order
+#[derive(Debug, Clone)]
+pub struct Options {
+    names: CommandList,
+    values: Vec<OptionValue>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        let names: Vec<&str> = OPTIONS.iter().map(|option| option.name).collect();
+        Options {
+            names: CommandList::new(&names),
+            values: OPTIONS.iter().map(|option| (option.default)()).collect(),
+        }
+    }
+
+    // Index of an option; abbreviations allowed
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.get_command(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        self.find(name).map(|index| &self.values[index])
+    }
+
+    // Stores a value of the option's own type; anything else is rejected
+    pub fn set(&mut self, name: &str, value: OptionValue) -> DssResult<()> {
+        let index = self.find(name).ok_or_else(|| unknown_option(name))?;
+        let matches = matches!(
+            (&OPTIONS[index].kind, &value),
+            (OptionKind::Text, OptionValue::Text(_))
+                | (OptionKind::Integer, OptionValue::Integer(_))
+                | (
+                    OptionKind::Double | OptionKind::Duration,
+                    OptionValue::Double(_)
+                )
+                | (OptionKind::Bool, OptionValue::Bool(_))
+                | (OptionKind::DoubleArray, OptionValue::DoubleArray(_))
+                | (OptionKind::Choice(..), OptionValue::Choice(_))
+        );
+        if !matches {
+            return Err(DssError::new(
+                codes::UNKNOWN_OPTION,
+                &format!("Invalid value for option \"{}\"", OPTIONS[index].name),
+            ));
+        }
+        self.values[index] = value;
+        Ok(())
+    }
+
+    pub fn get_text(&self, name: &str) -> &str {
+        match self.get(name) {
+            Some(OptionValue::Text(text)) => text,
+            Some(OptionValue::Choice(choice)) => choice,
+            _ => "",
+        }
+    }
+
+    pub fn get_integer(&self, name: &str) -> i32 {
+        match self.get(name) {
+            Some(OptionValue::Integer(value)) => *value,
+            _ => 0,
+        }
+    }
+
+    pub fn get_double(&self, name: &str) -> f64 {
+        match self.get(name) {
+            Some(OptionValue::Double(value)) => *value,
+            Some(OptionValue::Integer(value)) => *value as f64,
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(OptionValue::Bool(true)))
+    }
+
+    pub fn get_doubles(&self, name: &str) -> &[f64] {
+        match self.get(name) {
+            Some(OptionValue::DoubleArray(values)) => values,
+            _ => &[],
+        }
+    }
+
+    // Restores the default of every option
+    pub fn reset(&mut self) {
+        *self = Options::new();
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unknown_option(name: &str) -> DssError {
+    DssError::new(
+        codes::UNKNOWN_OPTION,
+        &format!("Unknown option: \"{}\"", name),
+    )
+}
+
+// Yes/No values: anything starting with y or t is true (Pascal InterpretYesNo)
+fn interpret_yes_no(text: &str) -> bool {
+    matches!(text.chars().next(), Some('y' | 'Y' | 't' | 'T'))
+}
+
+// Splits a time step such as "15m" into its number and scale to seconds
+fn split_duration(text: &str) -> (&str, f64) {
+    let text = text.trim();
+    match text.chars().last() {
+        Some('s' | 'S') => (&text[..text.len() - 1], 1.0),
+        Some('m' | 'M') => (&text[..text.len() - 1], 60.0),
+        Some('h' | 'H') => (&text[..text.len() - 1], 3600.0),
+        _ => (text, 1.0),
+    }
+}
+
+impl Executive {
+    // Converts the parser's current token to the type of the option
+    fn read_option_value(&mut self, kind: OptionKind, name: &str) -> DssResult<OptionValue> {
+        let token = self.parser.get_token().to_string();
+        let value = match kind {
+            OptionKind::Text => OptionValue::Text(token),
+            OptionKind::Integer => OptionValue::Integer(self.parser.make_integer()?),
+            OptionKind::Double => OptionValue::Double(self.parser.make_double()?),
+            OptionKind::Bool => OptionValue::Bool(interpret_yes_no(&token)),
+            OptionKind::Duration => {
+                let (number, scale) = split_duration(&token);
+                self.parser.set_token(number);
+                OptionValue::Double(self.parser.make_double()? * scale)
+            }
+            // Harmonics=ALL is the empty list: every harmonic of the spectra
+            OptionKind::DoubleArray if name == "harmonics" && token.eq_ignore_ascii_case("all") => {
+                OptionValue::DoubleArray(Vec::new())
+            }
+            OptionKind::DoubleArray => {
+                let mut values = Vec::new();
+                for element in token.split(|c: char| c.is_whitespace() || c == ',') {
+                    if element.is_empty() {
+                        continue;
+                    }
+                    self.parser.set_token(element);
+                    values.push(self.parser.make_double()?);
+                }
+                OptionValue::DoubleArray(values)
+            }
+            OptionKind::Choice(choices, aliases) => {
+                let alias = aliases.iter().find(|(_, names)| {
+                    names.iter().any(|alias| alias.eq_ignore_ascii_case(&token))
+                });
+                if let Some(&(choice, _)) = alias {
+                    return Ok(OptionValue::Choice(choice));
+                }
+                let list = CommandList::new(choices);
+                let index = list.get_command(&token).ok_or_else(|| {
+                    DssError::new(
+                        codes::UNKNOWN_OPTION,
+                        &format!("Unknown value \"{}\" for option \"{}\"", token, name),
+                    )
+                })?;
+                OptionValue::Choice(choices[index])
+            }
+        };
+        Ok(value)
+    }
+
+    pub(crate) fn do_set(&mut self) -> DssResult<String> {
+        loop {
+            let name = self.parser.next_param();
+            if self.parser.get_token().is_empty() {
+                break;
+            }
+            if name.is_empty() {
+                return Err(DssError::new(
+                    codes::UNKNOWN_OPTION,
+                    &format!(
+                        "Option name missing for value \"{}\"",
+                        self.parser.get_token()
+                    ),
+                ));
+            }
+
+            let index = self
+                .options
+                .find(&name)
+                .ok_or_else(|| unknown_option(&name))?;
+            let option = &OPTIONS[index];
+            let value = self.read_option_value(option.kind, option.name)?;
+            self.options.set(option.name, value)?;
+            if matches!(option.name, "mode" | "year") {
+                self.apply_load_growth();
+            }
+            if option.name == "seed" {
+                self.rng = SplitMix64::new(self.options.get_integer("seed") as u64);
+            }
+            if option.name == "mode" && self.options.get_text("mode") == "dynamics" {
+                self.options
+                    .set("stepsize", OptionValue::Double(DYNAMICS_STEP))?;
+            }
+        }
+        Ok(String::new())
+    }
+
+    // Loads grow to the year of the study in yearly mode; in the other
+    // modes they stay at their base year
+    pub(crate) fn apply_load_growth(&mut self) {
+        let year = match self.options.get_text("mode") {
+            "yearly" => self.options.get_integer("year").max(0) as u32,
+            _ => 0,
+        };
+        if let Ok(circuit) = self.active_circuit_mut() {
+            apply_growth(circuit, year);
+        }
+    }
+
+    pub(crate) fn do_get(&mut self) -> DssResult<String> {
+        let mut values = Vec::new();
+        loop {
+            self.parser.next_param();
+            let name = self.parser.get_token().to_string();
+            if name.is_empty() {
+                break;
+            }
+            let value = self
+                .options
+                .get(&name)
+                .ok_or_else(|| unknown_option(&name))?;
+            values.push(value.to_string());
+        }
+        Ok(values.join(" "))
+    }
+
+    pub fn get_options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::Load;
+
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_typed_access() {
+        let options = Options::new();
+        assert_eq!(options.get_text("mode"), "snapshot");
+        assert_eq!(options.get_integer("maxiterations"), 15);
+        assert_eq!(options.get_double("tolerance"), 0.0001);
+        assert_eq!(options.get_double("freq"), 60.0);
+        assert!(!options.get_bool("trapezoidal"));
+        assert_eq!(options.get_doubles("voltagebases")[2], 12.47);
+        assert_eq!(options.get("bogus"), None);
+    }
+
+    #[test]
+    fn test_set_rejects_wrong_type() {
+        let mut options = Options::new();
+        assert!(options.set("number", OptionValue::Double(2.0)).is_err());
+        options.set("number", OptionValue::Integer(24)).unwrap();
+        assert_eq!(options.get_integer("number"), 24);
+        options.reset();
+        assert_eq!(options.get_integer("number"), 1);
+    }
+
+    #[test]
+    fn test_set_and_get_commands() {
+        let mut exec = Executive::new();
+        exec.execute("set mode=daily number=24 stepsize=15m tol=(1 10000 /)")
+            .unwrap();
+        exec.execute("set voltagebases=[115, 12.47 0.48] trap=yes casename=IEEE13")
+            .unwrap();
+
+        let options = exec.get_options();
+        assert_eq!(options.get_text("mode"), "daily");
+        assert_eq!(options.get_integer("number"), 24);
+        assert_eq!(options.get_double("stepsize"), 900.0);
+        assert_eq!(options.get_double("tolerance"), 0.0001);
+        assert_eq!(options.get_doubles("voltagebases"), [115.0, 12.47, 0.48]);
+        assert!(options.get_bool("trapezoidal"));
+
+        let result = exec.execute("get mode voltagebases trap casename").unwrap();
+        assert_eq!(result.output, "daily [115, 12.47, 0.48] Yes IEEE13");
+        assert_eq!(exec.execute("get stepsize").unwrap().output, "900");
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let mut exec = Executive::new();
+        let err = exec.execute("set nosuchoption=1").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_OPTION);
+        let err = exec.execute("set mode=sideways").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Unknown value \"sideways\" for option \"mode\""
+        );
+        // modes not implemented are not offered
+        let err = exec.execute("set mode=peakday").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_OPTION);
+        let err = exec.execute("set number=lots").unwrap_err();
+        assert_eq!(err.number(), codes::CONVERSION_ERROR);
+        assert!(exec.execute("set 5").is_err());
+
+        // choices may be abbreviated too
+        exec.execute("set controlmode=ev algo=newt").unwrap();
+        assert_eq!(exec.get_options().get_text("controlmode"), "event");
+        assert_eq!(exec.get_options().get_text("algorithm"), "newton");
+    }
+
+    #[test]
+    fn test_yearly_growth() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.feeder").unwrap();
+        exec.execute("new growthshape.g1 year=[1 3] mult=[1.1 1.2]")
+            .unwrap();
+        exec.execute("new load.ld1 bus1=b kw=10 growth=g1").unwrap();
+        let growth = |exec: &Executive| {
+            exec.get_active_circuit()
+                .unwrap()
+                .find_object_as::<Load>("load", "ld1")
+                .unwrap()
+                .get_growth_mult()
+        };
+        exec.execute("set year=3").unwrap();
+        assert_eq!(growth(&exec), 1.0);
+        exec.execute("set mode=yearly").unwrap();
+        assert!((growth(&exec) - 1.1 * 1.1 * 1.2).abs() < 1e-12);
+        exec.execute("set mode=snapshot").unwrap();
+        assert_eq!(growth(&exec), 1.0);
+    }
+}
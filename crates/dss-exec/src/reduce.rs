@@ -0,0 +1,72 @@
+// Reduce command (Pascal DoReduceCmd): shrinks the meter zones, or the whole
+// circuit when it has no meters, with the reduction Set ReduceOption picks.
+
+use dss_common::{DssError, DssResult, codes};
+use dss_core::{ReduceOption, reduce_circuit};
+
+use crate::buses::zone_trees;
+use crate::executive::Executive;
+
+impl Executive {
+    // Reduce [meter | All]
+    pub(crate) fn do_reduce(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let name = self.parser.get_token().to_string();
+        let choice = self.options.get_text("reduceoption");
+        let option =
+            ReduceOption::from_name(choice, self.options.get_double("zmag")).ok_or_else(|| {
+                DssError::new(
+                    codes::SYNTAX_ERROR,
+                    &format!("Unknown reduce option \"{}\"", choice),
+                )
+            })?;
+        let circuit = self.active_circuit_mut()?;
+        for tree in zone_trees(circuit, &name, "Reduce")? {
+            reduce_circuit(circuit, &tree, option)?;
+        }
+        circuit.make_bus_list()?;
+        Ok(String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(exec: &Executive, name: &str) -> bool {
+        let circuit = exec.get_active_circuit().unwrap();
+        let id = circuit.find_element("Line", name).unwrap();
+        let line = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        line.ckt_base().is_enabled()
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1",
+            "new linecode.lc r1=0.1 x1=0.2",
+            "new line.l1 bus1=sourcebus bus2=b linecode=lc length=1",
+            "new line.l2 bus1=b bus2=c linecode=lc length=2",
+            "new line.l3 bus1=c bus2=d linecode=lc length=0.1",
+            "new line.stub bus1=c bus2=x linecode=lc",
+            "new load.ld1 bus1=d",
+            "new energymeter.m1 element=line.l1",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        assert!(exec.execute("reduce m2").is_err());
+
+        exec.execute("set reduceoption=ends").unwrap();
+        exec.execute("reduce m1").unwrap();
+        assert!(!enabled(&exec, "stub"));
+
+        exec.execute("set reduceoption=default").unwrap();
+        exec.execute("reduce").unwrap();
+        assert!(!enabled(&exec, "l2") && !enabled(&exec, "l3"));
+        assert_eq!(exec.execute("? line.l1.length").unwrap().output, "3.1");
+        let buses = exec.get_active_circuit().unwrap().buses();
+        let names: Vec<&str> = buses.iter().map(|bus| bus.name()).collect();
+        assert_eq!(names, ["sourcebus", "d"]);
+    }
+}
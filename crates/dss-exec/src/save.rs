@@ -0,0 +1,178 @@
+// Save Circuit: writes the active circuit back out as DSS script, one file per
+// class plus a master file that redirects to them (Pascal ExecHelper
+// DoSaveCmd / TDSSCircuit.Save). Only properties that were set are written,
+// so compiling the master file rebuilds the same circuit.
+
+use std::path::PathBuf;
+
+use dss_common::{DssError, DssResult, codes};
+
+use dss_core::{Circuit, class_names};
+
+use crate::executive::Executive;
+use crate::options::Options;
+
+pub(crate) const MASTER_FILE: &str = "Master.dss";
+
+// File name and contents of every file making up the saved circuit; the
+// master file comes first
+pub(crate) fn circuit_files(circuit: &Circuit, options: &Options) -> Vec<(String, String)> {
+    let mut master = vec!["Clear".to_string()];
+    let mut files = Vec::new();
+
+    // the circuit's source is defined by New Circuit itself
+    let source = &circuit.elements()[0];
+    let source_script = source.to_script();
+    let properties = &source_script[format!("New {}", source.full_name()).len()..];
+    master.push(format!("New Circuit.{}{}", circuit.name(), properties));
+
+    // classes in registration order, so codes and shapes come before the
+    // elements that refer to them
+    for class_name in class_names() {
+        let scripts: Vec<String> = circuit
+            .class_elements(class_name)
+            .iter()
+            .filter(|&&id| id != 0)
+            .map(|&id| circuit.elements()[id].to_script())
+            .collect();
+        if scripts.is_empty() {
+            continue;
+        }
+        let file_name = format!("{}.dss", class_name);
+        master.push(format!("Redirect {}", file_name));
+        files.push((file_name, scripts.join("\n") + "\n"));
+    }
+
+    let voltage_bases = options.get_doubles("voltagebases");
+    if !voltage_bases.is_empty() {
+        let bases: Vec<String> = voltage_bases.iter().map(f64::to_string).collect();
+        master.push(format!("Set voltagebases=[{}]", bases.join(" ")));
+    }
+
+    files.insert(0, (MASTER_FILE.to_string(), master.join("\n") + "\n"));
+    files
+}
+
+impl Executive {
+    // Save Circuit [dir=directory]; the default directory is named after the
+    // circuit and made in the current directory
+    pub(crate) fn do_save(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let what = self.parser.get_token().to_string();
+        if !"circuit".starts_with(&what.to_lowercase()) || what.is_empty() {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!("Save \"{}\" is not supported; use Save Circuit", what),
+            ));
+        }
+
+        let mut dir = None;
+        loop {
+            let name = self.parser.next_param();
+            let value = self.parser.get_token().to_string();
+            if value.is_empty() {
+                break;
+            }
+            if name.is_empty() || "dir".starts_with(&name.to_lowercase()) {
+                dir = Some(value);
+            }
+        }
+
+        let circuit = self.active_circuit()?;
+        let dir = self
+            .get_current_dir()
+            .join(dir.unwrap_or_else(|| circuit.name().to_string()));
+        let files = circuit_files(circuit, &self.options);
+
+        let file_error = |path: &PathBuf, err: std::io::Error| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!("Error saving \"{}\": {}", path.display(), err),
+            )
+        };
+        std::fs::create_dir_all(&dir).map_err(|err| file_error(&dir, err))?;
+        for (file_name, contents) in &files {
+            let path = dir.join(file_name);
+            std::fs::write(&path, contents).map_err(|err| file_error(&path, err))?;
+        }
+        Ok(dir.join(MASTER_FILE).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(exec: &mut Executive) {
+        for line in [
+            "new circuit.feeder basekv=12.47 pu=1.02",
+            "new linecode.lc1 r1=0.1 x1=0.3",
+            "new line.l1 bus1=sourcebus bus2=b linecode=lc1 length=2",
+            "new load.ld1 bus1=b kw=100 daily=\"daily shape\"",
+            "new line.l2 bus1=b bus2=c",
+            "set voltagebases=[12.47, 0.48]",
+        ] {
+            exec.execute(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_circuit_files() {
+        let mut exec = Executive::new();
+        build(&mut exec);
+        let files = circuit_files(exec.get_active_circuit().unwrap(), exec.get_options());
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["Master.dss", "LineCode.dss", "Line.dss", "Load.dss"]
+        );
+        assert_eq!(
+            files[0].1,
+            "Clear\nNew Circuit.feeder basekv=12.47 pu=1.02\n\
+             Redirect LineCode.dss\nRedirect Line.dss\nRedirect Load.dss\n\
+             Set voltagebases=[12.47 0.48]\n"
+        );
+        assert_eq!(
+            files[3].1,
+            "New Load.ld1 bus1=b kw=100 daily=\"daily shape\"\n"
+        );
+    }
+
+    #[test]
+    fn test_save_and_recompile() {
+        let dir = std::env::temp_dir().join(format!("dss_exec_save_{}", std::process::id()));
+        let mut exec = Executive::new();
+        build(&mut exec);
+        exec.set_current_dir(&dir);
+        let result = exec.execute("save circuit dir=saved").unwrap();
+        assert_eq!(
+            result.output,
+            dir.join("saved").join(MASTER_FILE).to_string_lossy()
+        );
+
+        let mut copy = Executive::new();
+        copy.execute(&format!("compile \"{}\"", result.output))
+            .unwrap();
+        // same elements, grouped by class in the copy
+        let scripts = |exec: &Executive| {
+            let mut scripts: Vec<String> = exec
+                .get_active_circuit()
+                .unwrap()
+                .elements()
+                .iter()
+                .map(|element| element.to_script())
+                .collect();
+            scripts.sort();
+            scripts
+        };
+        assert_eq!(scripts(&copy), scripts(&exec));
+        assert_eq!(
+            copy.execute("get voltagebases").unwrap().output,
+            "[12.47, 0.48]"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = exec.execute("save voltages").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+    }
+}
@@ -0,0 +1,230 @@
+// Running script files (Redirect and Compile).
+//
+// Relative file names resolve against the current directory of the
+// executive. While a file runs, its own directory is current, so nested
+// redirects resolve relative to the file that contains them; afterwards
+// Redirect returns to the previous directory while Compile stays in the
+// directory of the compiled file.
+
+use std::path::{Path, PathBuf};
+
+use dss_common::{DssError, DssResult, codes};
+
+use crate::executive::Executive;
+
+impl Executive {
+    pub(crate) fn redirect(&mut self, is_compile: bool) -> DssResult<String> {
+        self.parser.next_param();
+        let file_name = self.parser.get_token().to_string();
+        let path = self.resolve_script_path(&file_name)?;
+        let script = std::fs::read_to_string(&path).map_err(|err| {
+            DssError::new(
+                codes::FILE_ERROR,
+                &format!(
+                    "Redirect file \"{}\" could not be read: {}",
+                    path.display(),
+                    err
+                ),
+            )
+        })?;
+
+        let variable = if is_compile {
+            "@lastcompilefile"
+        } else {
+            "@lastredirectfile"
+        };
+        if let Some(vars) = self.parser.get_vars_mut() {
+            vars.add(variable, &path.to_string_lossy());
+        }
+
+        let script_dir = path
+            .parent()
+            .map_or_else(|| self.get_current_dir().to_path_buf(), Path::to_path_buf);
+        self.dir_stack.push(script_dir);
+        let result = self.run_script(&script, &path);
+        let script_dir = self.dir_stack.pop();
+        if is_compile && let Some(dir) = script_dir {
+            self.set_current_dir(&dir);
+        }
+        result
+    }
+
+    // A missing file is retried with the ".dss" extension, as in OpenDSS
+    fn resolve_script_path(&self, file_name: &str) -> DssResult<PathBuf> {
+        if file_name.is_empty() {
+            return Err(DssError::new(
+                codes::FILE_ERROR,
+                "Redirect file name missing",
+            ));
+        }
+
+        let path = self.get_current_dir().join(file_name);
+        if path.is_file() {
+            return Ok(path);
+        }
+        let with_extension = self.get_current_dir().join(format!("{}.dss", file_name));
+        if with_extension.is_file() {
+            return Ok(with_extension);
+        }
+        Err(DssError::new(
+            codes::FILE_ERROR,
+            &format!("Redirect file not found: \"{}\"", path.display()),
+        ))
+    }
+
+    // Executes the lines of a script, skipping /* ... */ block comments and
+    // stopping at the first error, which is reported with its file and line
+    fn run_script(&mut self, script: &str, path: &Path) -> DssResult<String> {
+        let mut output = Vec::new();
+        let mut in_block_comment = false;
+
+        for (index, line) in script.lines().enumerate() {
+            let trimmed = line.trim_start();
+            if in_block_comment {
+                in_block_comment = !trimmed.contains("*/");
+                continue;
+            }
+            if trimmed.starts_with("/*") {
+                in_block_comment = !trimmed.contains("*/");
+                continue;
+            }
+
+            let result = self.execute(line).map_err(|err| match err.context() {
+                Some(_) => err,
+                None => {
+                    let context = format!("{}, line {}", path.display(), index + 1);
+                    err.with_context(&context)
+                }
+            })?;
+            for warning in result.warnings {
+                self.diagnostics.push(warning);
+            }
+            if !result.output.is_empty() {
+                output.push(result.output);
+            }
+        }
+        Ok(output.join("\n"))
+    }
+
+    // Directory relative file names are resolved against
+    pub fn get_current_dir(&self) -> &Path {
+        self.dir_stack
+            .last()
+            .map_or(Path::new("."), PathBuf::as_path)
+    }
+
+    pub fn set_current_dir(&mut self, dir: &Path) {
+        let dir = self.get_current_dir().join(dir);
+        match self.dir_stack.last_mut() {
+            Some(current) => *current = dir,
+            None => self.dir_stack.push(dir),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh directory under the system temp dir, unique per test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dss_exec_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn last_var(exec: &mut Executive, name: &str) -> String {
+        let vars = exec.parser.get_vars_mut().unwrap();
+        vars.lookup(name);
+        vars.get_value()
+    }
+
+    #[test]
+    fn test_nested_redirect_paths() {
+        let dir = temp_dir("nested");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            dir.join("master.dss"),
+            "new circuit.c\nredirect sub/loads.dss\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("sub/loads.dss"), "redirect more\nnew load.b\n").unwrap();
+        std::fs::write(dir.join("sub/more.dss"), "new load.a\n").unwrap();
+
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        exec.execute("redirect master.dss").unwrap();
+
+        let names: Vec<String> = exec
+            .get_active_circuit()
+            .unwrap()
+            .elements()
+            .iter()
+            .map(|element| element.full_name())
+            .collect();
+        assert_eq!(names, ["Vsource.source", "Load.a", "Load.b"]);
+        assert_eq!(exec.get_current_dir(), dir);
+        assert_eq!(
+            last_var(&mut exec, "@lastredirectfile"),
+            dir.join("sub").join("more.dss").to_string_lossy()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compile_changes_directory() {
+        let dir = temp_dir("compile");
+        std::fs::create_dir_all(dir.join("feeder")).unwrap();
+        std::fs::write(dir.join("feeder/master.dss"), "new circuit.f\n").unwrap();
+        std::fs::write(dir.join("feeder/extra.dss"), "new load.x\n").unwrap();
+
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        exec.execute("compile feeder/master").unwrap();
+        assert_eq!(exec.get_current_dir(), dir.join("feeder"));
+        assert_eq!(
+            last_var(&mut exec, "@lastcompilefile"),
+            dir.join("feeder/master.dss").to_string_lossy()
+        );
+
+        // relative to the compiled file's directory now
+        exec.execute("redirect extra.dss").unwrap();
+        assert_eq!(exec.get_active_circuit().unwrap().elements().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_location_and_block_comments() {
+        let dir = temp_dir("errors");
+        std::fs::write(
+            dir.join("bad.dss"),
+            "new circuit.c\n/* new load.skipped\n   still a comment */\nnew load.ok\nnew bogus.x\nnew load.never\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("outer.dss"), "\nredirect bad.dss\n").unwrap();
+
+        let mut exec = Executive::new();
+        exec.set_current_dir(&dir);
+        let err = exec.execute("redirect outer.dss").unwrap_err();
+        assert_eq!(err.number(), codes::UNKNOWN_CLASS);
+        // the innermost file is reported
+        let expected = format!("{}, line 5", dir.join("bad.dss").display());
+        assert_eq!(err.context(), Some(expected.as_str()));
+
+        let names: Vec<&str> = exec
+            .get_active_circuit()
+            .unwrap()
+            .elements()
+            .iter()
+            .map(|element| element.name())
+            .collect();
+        assert_eq!(names, ["source", "ok"]);
+        // the directory stack unwinds after an error
+        assert_eq!(exec.get_current_dir(), dir);
+
+        let err = exec.execute("redirect").unwrap_err();
+        assert_eq!(err.number(), codes::FILE_ERROR);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+// Show command: reports about the session and the circuit (Pascal
+// ShowOptions). The report name may be abbreviated.
+
+use dss_common::{CommandList, DssError, DssResult, codes};
+
+use dss_core::{Bus, CktElement, Load, isolated_elements};
+
+use crate::executive::{Executive, no_active_circuit};
+
+const REPORTS: &[&str] = &["Timings", "EventLog", "Isolated", "Faults", "Losses"];
+
+impl Executive {
+    pub(crate) fn do_show(&mut self) -> DssResult<String> {
+        self.parser.next_param();
+        let report = self.parser.get_token().to_string();
+        let index = CommandList::new(REPORTS)
+            .get_command(&report)
+            .ok_or_else(|| {
+                DssError::new(
+                    codes::NOT_IMPLEMENTED,
+                    &format!(
+                        "Show \"{}\" is not supported; available: {}",
+                        report,
+                        REPORTS.join(", ")
+                    ),
+                )
+            })?;
+
+        match REPORTS[index] {
+            "Timings" => Ok(self.timings.report()),
+            "EventLog" => Ok(self.event_log.to_text()),
+            "Isolated" => self.show_isolated(),
+            "Faults" => self.show_faults(),
+            "Losses" => self.show_losses(),
+            _ => unreachable!(),
+        }
+    }
+
+    // Enabled circuit elements the sources do not reach, one per line
+    fn show_isolated(&self) -> DssResult<String> {
+        let circuit = self.get_active_circuit().ok_or_else(no_active_circuit)?;
+        let names: Vec<String> = isolated_elements(circuit)
+            .into_iter()
+            .filter_map(|id| circuit.element(id))
+            .map(|element| element.full_name())
+            .collect();
+        Ok(names.join("\n"))
+    }
+
+    // Fault currents of every bus from the last fault study (Pascal
+    // ShowFaultStudy): all nodes to ground at once, each node to ground
+    // alone, and each node to the next
+    fn show_faults(&self) -> DssResult<String> {
+        let circuit = self.get_active_circuit().ok_or_else(no_active_circuit)?;
+        let buses: Vec<&Bus> = circuit
+            .buses()
+            .iter()
+            .filter(|bus| bus.get_zsc().is_some())
+            .collect();
+        if buses.is_empty() {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                "There are no fault currents to show; solve with mode=faultstudy first",
+            ));
+        }
+        let name = |bus: &Bus| format!("\"{}\"", bus.name().to_uppercase());
+
+        let mut lines = vec![
+            "FAULT STUDY REPORT".to_string(),
+            String::new(),
+            "ALL-Node Fault Currents".to_string(),
+            String::new(),
+            "Bus                      Node 1     Node 2     Node 3  ...  (Amps)".to_string(),
+            String::new(),
+        ];
+        for &bus in &buses {
+            let currents: Vec<String> = bus
+                .all_node_fault_currents()
+                .iter()
+                .map(|current| format!("{:>10.0}", current.norm()))
+                .collect();
+            lines.push(format!("{:<20} {}", name(bus), currents.join(" ")));
+        }
+
+        lines.extend(
+            [
+                "",
+                "ONE-Node to ground Faults",
+                "",
+                "Bus                   Node       Amps",
+                "",
+            ]
+            .map(String::from),
+        );
+        for &bus in &buses {
+            for (node, current) in bus.nodes().iter().zip(bus.node_fault_currents()) {
+                lines.push(format!(
+                    "{:<20} {:>5} {:>10.0}",
+                    name(bus),
+                    node,
+                    current.norm()
+                ));
+            }
+        }
+
+        lines.extend(
+            [
+                "",
+                "Adjacent Node-Node Faults",
+                "",
+                "Bus                   Node-Node  Amps",
+                "",
+            ]
+            .map(String::from),
+        );
+        for &bus in &buses {
+            for ((from, to), current) in bus.node_node_fault_currents() {
+                let nodes = format!("{}-{}", from, to);
+                lines.push(format!(
+                    "{:<20} {:>5} {:>10.0}",
+                    name(bus),
+                    nodes,
+                    current.norm()
+                ));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    // Losses of every power delivery element at the last solution, with
+    // the line and transformer totals and the losses as a share of the
+    // load (Pascal ShowLosses)
+    fn show_losses(&self) -> DssResult<String> {
+        let circuit = self.get_active_circuit().ok_or_else(no_active_circuit)?;
+        let voltages = circuit.get_node_voltages();
+        if voltages.len() != circuit.num_nodes() + 1 {
+            return Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                "There are no losses to show; solve the circuit first",
+            ));
+        }
+        let mut lines = vec![
+            "LOSSES REPORT".to_string(),
+            String::new(),
+            "Power Delivery Element Loss Report".to_string(),
+            String::new(),
+            "Element                  kW Losses    % of Power   kvar Losses".to_string(),
+            String::new(),
+        ];
+        for id in 0..circuit.elements().len() {
+            let Some((losses, _, _)) = circuit.element_losses(id) else {
+                continue;
+            };
+            let Some(element) = circuit.element(id).and_then(|e| e.as_ckt_element()) else {
+                continue;
+            };
+            // of the power into the first terminal. Shunt capacitors and
+            // reactors pass almost no real power, and their few watts of
+            // losses against it would read as about 100 %.
+            let power = element
+                .terminal_powers(voltages)
+                .first()
+                .copied()
+                .unwrap_or_default();
+            let percent = if power.re.abs() > 0.01 * power.norm() {
+                losses.re / power.re * 100.0
+            } else {
+                0.0
+            };
+            lines.push(format!(
+                "{:<22} {:>11.3} {:>13.2} {:>13.3}",
+                format!("\"{}\"", element.full_name()),
+                losses.re / 1000.0,
+                percent,
+                losses.im / 1000.0
+            ));
+        }
+
+        let total = circuit.losses().re / 1000.0;
+        let load_kw: f64 = circuit
+            .class_elements("Load")
+            .iter()
+            .filter_map(|&id| circuit.element(id))
+            .filter_map(|element| element.as_any().downcast_ref::<Load>())
+            .filter(|load| load.ckt_base().is_enabled())
+            .map(|load| load.total_power(voltages).re / 1000.0)
+            .fold(0.0, |sum, kw| sum + kw);
+        let percent = if load_kw > 0.0 {
+            total / load_kw * 100.0
+        } else {
+            0.0
+        };
+        lines.extend([
+            String::new(),
+            format!(
+                "LINE LOSSES=        {:>11.1} kW",
+                circuit.class_losses("Line").re / 1000.0
+            ),
+            format!(
+                "TRANSFORMER LOSSES= {:>11.1} kW",
+                circuit.class_losses("Transformer").re / 1000.0
+            ),
+            String::new(),
+            format!("TOTAL LOSSES=       {:>11.1} kW", total),
+            String::new(),
+            format!("TOTAL LOAD POWER =  {:>11.1} kW", load_kw),
+            format!("Percent Losses for Circuit = {:.2} %", percent),
+        ]);
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_timings() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1").unwrap();
+        exec.execute("if (1) then set number=2").unwrap();
+        let report = exec.execute("show tim").unwrap().output;
+        let commands: Vec<&str> = report
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        assert_eq!(commands, ["New", "Set", "Totals:"]);
+        assert!(report.contains("New                      2"));
+
+        exec.event_log_mut()
+            .log(0, 0.0, "Capacitor.c1", "**CLOSED**");
+        assert_eq!(
+            exec.execute("show eventlog").unwrap().output,
+            "Hour=0, Sec=0, ControlIter=0, Element=Capacitor.c1, Action=**CLOSED**"
+        );
+
+        exec.execute("new line.l1 bus1=sourcebus bus2=b").unwrap();
+        exec.execute("new line.island bus1=x bus2=y").unwrap();
+        // ld1 is on its default bus, which nothing connects to the source
+        assert_eq!(
+            exec.execute("show iso").unwrap().output,
+            "Load.ld1\nLine.island"
+        );
+
+        let err = exec.execute("show nothing").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+    }
+
+    #[test]
+    fn test_show_faults() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47 mvasc3=200 mvasc1=150",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new fault.f1 bus1=b phases=1",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        let err = exec.execute("show faults").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+
+        exec.execute("solve mode=faultstudy").unwrap();
+        let report = exec.execute("show faults").unwrap().output;
+        assert!(report.starts_with("FAULT STUDY REPORT"));
+        // the three phase short circuit of the source
+        let isc3 = 200e6 / (3.0_f64.sqrt() * 12470.0);
+        let line = report
+            .lines()
+            .find(|line| line.starts_with("\"SOURCEBUS\""))
+            .unwrap();
+        let currents: Vec<f64> = line
+            .split_whitespace()
+            .skip(1)
+            .map(|amps| amps.parse().unwrap())
+            .collect();
+        assert_eq!(currents.len(), 3);
+        assert!(
+            currents
+                .iter()
+                .all(|amps| (amps - isc3).abs() < 0.01 * isc3),
+            "{}",
+            report
+        );
+        assert!(report.contains("\"B\"                    1-2"));
+
+        // the study leaves the fault in the circuit, though not in the study
+        let circuit = exec.get_active_circuit().unwrap();
+        let id = circuit.find_element("Fault", "f1").unwrap();
+        let fault = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        assert!(fault.ckt_base().is_enabled());
+        let b = circuit
+            .buses()
+            .iter()
+            .find(|bus| bus.name() == "b")
+            .unwrap();
+        let vpu = b.get_vbus()[0].norm() / (12470.0 / 3.0_f64.sqrt());
+        assert!((vpu - 1.0).abs() < 1e-3);
+        // a line away the currents are lower
+        let slg = b.node_fault_currents()[0].norm();
+        assert!(slg > 0.0 && slg < isc3);
+    }
+
+    #[test]
+    fn test_show_losses() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=a r1=0.5 x1=1 r0=1.5 x0=3 length=2",
+            "new transformer.t1 buses=[a b] kvs=[12.47 4.16] kvas=[3000 3000] %loadloss=1 %noloadloss=0.2",
+            "new load.ld1 bus1=b kv=4.16 kw=2000 pf=0.9",
+            "new capacitor.c1 bus1=b kv=4.16 kvar=600",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        let err = exec.execute("show losses").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_IMPLEMENTED);
+
+        exec.execute("solve").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let voltages = circuit.get_node_voltages();
+        // what the source puts in and the load does not use is lost
+        let source = circuit.element(0).unwrap().as_ckt_element().unwrap();
+        let id = circuit.find_element("Load", "ld1").unwrap();
+        let load = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        let lost = -source.terminal_powers(voltages)[0] - load.total_power(voltages);
+        assert!((circuit.losses() - lost).norm() < 1e-3 * lost.norm());
+        let lines = circuit.class_losses("Line");
+        let transformers = circuit.class_losses("Transformer");
+        // the capacitor's vars count as losses too, as in OpenDSS
+        let capacitors = circuit.class_losses("Capacitor");
+        assert!((lines + transformers + capacitors - circuit.losses()).norm() < 1e-6);
+        assert!(capacitors.re.abs() < 1.0 && capacitors.im < 0.0);
+        assert!(lines.re > 0.0 && transformers.re > 0.0);
+        let total = format!(
+            "TOTAL LOSSES=       {:>11.1} kW",
+            circuit.losses().re / 1000.0
+        );
+
+        let report = exec.execute("show losses").unwrap().output;
+        assert!(report.starts_with("LOSSES REPORT"));
+        assert!(report.contains("\"Line.l1\""));
+        assert!(report.contains("\"Transformer.t1\""));
+        assert!(report.contains(&total), "{}", report);
+        let row = |name: &str| -> Vec<f64> {
+            let line = report.lines().find(|line| line.starts_with(name)).unwrap();
+            line.split_whitespace()
+                .skip(1)
+                .map(|value| value.parse().unwrap())
+                .collect()
+        };
+        // the transformer loses a little of what it passes on
+        let transformer = row("\"Transformer.t1\"");
+        assert!(transformer[1] > 0.0 && transformer[1] < 5.0, "{}", report);
+        // the capacitor passes no real power to lose a share of
+        let capacitor = row("\"Capacitor.c1\"");
+        assert!(capacitor[0].abs() < 1e-3, "{}", report);
+        assert_eq!(capacitor[1], 0.0);
+    }
+}
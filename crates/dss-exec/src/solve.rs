@@ -0,0 +1,860 @@
+// Solve command (Pascal DoSolveCmd): takes options as Set does, then solves
+// the active circuit in the solution mode. The solution keeps its matrix and
+// voltages between commands, so a circuit solved again after small changes
+// starts from where it was. The time modes step from the time of the options
+// and leave it at the last step, so the next Solve carries on. A harmonic
+// solution leaves the voltages of the last harmonic in the circuit. The
+// machines stay in their dynamics models from one dynamics Solve to the
+// next, and leave them for a Solve in any other mode. The Monte Carlo modes
+// draw from the generator Set seed restarts, so a study run again from the
+// same seed repeats exactly.
+
+use dss_common::{DssError, DssResult, codes};
+use dss_core::{
+    Circuit, CktElement, Fault, Load, LoadShape, RandomType, ShapeMode, SolutionState,
+    apply_load_shapes, random_load_mults, update_all_storage,
+};
+use dss_parser::SplitMix64;
+use dss_solver::{AddType, Algorithm, AutoAdd, ControlMode, spectrum_harmonics};
+
+use crate::executive::{Executive, no_active_circuit};
+use crate::options::OptionValue;
+
+const HOURS_PER_YEAR: f64 = 8760.0;
+
+// Scales every load by the global load multiplier
+fn apply_load_mult(circuit: &mut Circuit, mult: f64) {
+    for id in circuit.class_elements("Load").to_vec() {
+        if let Some(load) = circuit
+            .element_mut(id)
+            .and_then(|load| load.as_any_mut().downcast_mut::<Load>())
+        {
+            load.set_load_mult(mult);
+        }
+    }
+}
+
+// Scales the loads by their Monte Carlo draws, on top of what they are at
+fn scale_loads(circuit: &mut Circuit, mults: &[(usize, f64)]) {
+    for &(id, mult) in mults {
+        if let Some(load) = circuit
+            .element_mut(id)
+            .and_then(|load| load.as_any_mut().downcast_mut::<Load>())
+        {
+            load.set_load_mult(load.get_load_mult() * mult);
+        }
+    }
+}
+
+// A resistance for every enabled fault, drawn about its own by its %stddev
+fn randomize_faults(circuit: &mut Circuit, rng: &mut SplitMix64) {
+    for id in circuit.class_elements("Fault").to_vec() {
+        if let Some(fault) = circuit
+            .element_mut(id)
+            .and_then(|fault| fault.as_any_mut().downcast_mut::<Fault>())
+            .filter(|fault| fault.ckt_base().is_enabled())
+        {
+            fault.randomize(rng.next_normal());
+        }
+    }
+}
+
+fn steps_not_converged(failed: i32, number: i32) -> DssError {
+    DssError::new(
+        codes::NOT_CONVERGED,
+        &format!(
+            "The solution did not converge at {} of {} time steps",
+            failed, number
+        ),
+    )
+}
+
+impl Executive {
+    // Solve [option=value ...]
+    pub(crate) fn do_solve(&mut self) -> DssResult<String> {
+        self.active_circuit_mut()?;
+        self.do_set()?;
+        // loads defined since the year was set grow too
+        self.apply_load_growth();
+        self.configure_solution();
+        if self.options.get_text("mode") != "dynamics" {
+            let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+            self.solution.end_dynamics(&mut self.circuits[index]);
+        }
+        let result = match self.options.get_text("mode") {
+            "snapshot" => self.solve_snapshot(),
+            "daily" => self.solve_time_series(ShapeMode::Daily),
+            "yearly" => self.solve_time_series(ShapeMode::Yearly),
+            "dutycycle" => self.solve_time_series(ShapeMode::Duty),
+            "montecarlo1" => self.solve_monte_carlo1(),
+            "montecarlo2" => self.solve_monte_carlo2(),
+            "montecarlo3" => self.solve_monte_carlo3(),
+            "loadduration1" => self.solve_load_duration(true),
+            "loadduration2" => self.solve_load_duration(false),
+            "dynamics" => self.solve_dynamics(),
+            "faultstudy" => self.solve_fault_study(),
+            "harmonic" => self.solve_harmonics(),
+            "autoadd" => self.solve_auto_add(),
+            mode => Err(DssError::new(
+                codes::NOT_IMPLEMENTED,
+                &format!("Solution mode \"{}\" is not available yet", mode),
+            )),
+        };
+        let events = self.solution.control_queue_mut().take_events();
+        for event in events {
+            self.event_log.push(event);
+        }
+        result
+    }
+
+    fn configure_solution(&mut self) {
+        let algorithm =
+            Algorithm::from_name(self.options.get_text("algorithm")).unwrap_or_default();
+        self.solution.set_algorithm(algorithm);
+        let control_mode =
+            ControlMode::from_name(self.options.get_text("controlmode")).unwrap_or_default();
+        self.solution.set_control_mode(control_mode);
+        self.solution
+            .set_frequency(self.options.get_double("frequency"));
+        self.solution
+            .set_tolerance(self.options.get_double("tolerance"));
+        self.solution
+            .set_max_iterations(self.options.get_integer("maxiterations").max(1) as usize);
+    }
+
+    fn max_control_iterations(&self) -> usize {
+        self.options.get_integer("maxcontroliter").max(1) as usize
+    }
+
+    fn solve_snapshot(&mut self) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let max_control = self.max_control_iterations();
+        self.solution.set_time(
+            self.options.get_integer("hour"),
+            self.options.get_double("sec"),
+        );
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        apply_load_mult(circuit, load_mult);
+        if !self.solution.solve_with_controls(circuit, max_control)? {
+            return Err(DssError::new(
+                codes::NOT_CONVERGED,
+                &format!(
+                    "Solution did not converge in {} iterations; largest voltage change {:.6} pu",
+                    self.solution.get_iterations(),
+                    self.solution.get_max_delta_v()
+                ),
+            ));
+        }
+        Ok(String::new())
+    }
+
+    // Number solutions, each a step of stepsize past the last. `prepare`
+    // sets the circuit up for a solution from its index and time in hours;
+    // then the circuit is solved with its controls, the monitors and meters
+    // sampled and the storage moved on.
+    fn solve_steps(
+        &mut self,
+        number: i32,
+        mut prepare: impl FnMut(&mut Circuit, &mut SplitMix64, i32, f64),
+    ) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let max_control = self.max_control_iterations();
+        let step = self.options.get_double("stepsize");
+        let step_hours = step / 3600.0;
+        let (mut hour, mut sec) = (
+            self.options.get_integer("hour"),
+            self.options.get_double("sec"),
+        );
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        let mut failed = 0;
+        for n in 0..number {
+            self.solution.set_time(hour, sec + step);
+            (hour, sec) = self.solution.get_time();
+            prepare(circuit, &mut self.rng, n, self.solution.hours());
+            if !self.solution.solve_with_controls(circuit, max_control)? {
+                failed += 1;
+            }
+            circuit.sample_meters(&SolutionState {
+                interval: step,
+                load_mult,
+                ..self.solution.state()
+            });
+            update_all_storage(circuit, step_hours);
+        }
+        self.options.set("hour", OptionValue::Integer(hour))?;
+        self.options.set("sec", OptionValue::Double(sec))?;
+        if failed > 0 {
+            return Err(steps_not_converged(failed, number));
+        }
+        Ok(String::new())
+    }
+
+    // The elements follow their shapes for the time of each step (Pascal
+    // SolveDaily, SolveYearly and SolveDuty)
+    fn solve_time_series(&mut self, mode: ShapeMode) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let step_hours = self.options.get_double("stepsize") / 3600.0;
+        let number = self.options.get_integer("number").max(0);
+        self.solve_steps(number, |circuit, _, _, hours| {
+            apply_load_shapes(circuit, mode, hours, load_mult, step_hours);
+        })
+    }
+
+    fn random_type(&self) -> RandomType {
+        RandomType::from_name(self.options.get_text("random")).unwrap_or_default()
+    }
+
+    // Number snapshots at the load multiplier, each with a new draw of the
+    // loads and fault resistances (Pascal SolveMonte1)
+    fn solve_monte_carlo1(&mut self) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let number = self.options.get_integer("number").max(0);
+        let kind = self.random_type();
+        self.solve_steps(number, |circuit, rng, _, _| {
+            apply_load_mult(circuit, load_mult);
+            scale_loads(circuit, &random_load_mults(circuit, kind, rng));
+            if kind != RandomType::None {
+                randomize_faults(circuit, rng);
+            }
+        })
+    }
+
+    // Number days stepped through the daily shapes, the loads drawn again
+    // at the start of each day (Pascal SolveMonte2)
+    fn solve_monte_carlo2(&mut self) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let step = self.options.get_double("stepsize");
+        let per_day = ((24.0 * 3600.0 / step).round() as i32).max(1);
+        let number = self.options.get_integer("number").max(0);
+        let kind = self.random_type();
+        let mut mults = Vec::new();
+        self.solve_steps(number * per_day, |circuit, rng, n, hours| {
+            if n % per_day == 0 {
+                mults = random_load_mults(circuit, kind, rng);
+                if kind != RandomType::None {
+                    randomize_faults(circuit, rng);
+                }
+            }
+            apply_load_shapes(circuit, ShapeMode::Daily, hours, load_mult, step / 3600.0);
+            scale_loads(circuit, &mults);
+        })
+    }
+
+    // Number solutions at random hours of the yearly shapes, the fault
+    // resistances drawn again for each (Pascal SolveMonte3)
+    fn solve_monte_carlo3(&mut self) -> DssResult<String> {
+        let load_mult = self.options.get_double("loadmult");
+        let step_hours = self.options.get_double("stepsize") / 3600.0;
+        let number = self.options.get_integer("number").max(0);
+        let kind = self.random_type();
+        self.solve_steps(number, |circuit, rng, _, _| {
+            let hour = (rng.next_f64() * HOURS_PER_YEAR).floor();
+            apply_load_shapes(circuit, ShapeMode::Yearly, hour, load_mult, step_hours);
+            if kind != RandomType::None {
+                randomize_faults(circuit, rng);
+            }
+        })
+    }
+
+    // Every point of the load duration curve as the load multiplier, over
+    // the daily shapes at each step of a day (Pascal SolveLD1) or at the
+    // present time only (SolveLD2). The meters take each solution as an
+    // interval of the curve.
+    fn solve_load_duration(&mut self, whole_day: bool) -> DssResult<String> {
+        let max_control = self.max_control_iterations();
+        let step = self.options.get_double("stepsize");
+        let step_hours = step / 3600.0;
+        let (mut hour, mut sec) = (
+            self.options.get_integer("hour"),
+            self.options.get_double("sec"),
+        );
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        let name = self.options.get_text("ldcurve");
+        let Some(curve) = circuit
+            .find_object_as::<LoadShape>("LoadShape", name)
+            .filter(|_| !name.is_empty())
+        else {
+            return Err(DssError::new(
+                codes::OBJECT_NOT_FOUND,
+                &format!("Load duration curve \"{}\" not found", name),
+            ));
+        };
+        let mults = curve.mult().to_vec();
+        let interval = match curve.get_interval() {
+            interval if interval > 0.0 => interval * 3600.0,
+            _ => step,
+        };
+        let steps = if whole_day {
+            ((24.0 * 3600.0 / step).round() as i32).max(1)
+        } else {
+            1
+        };
+        self.solution.set_time(hour, sec);
+        let mut failed = 0;
+        for _ in 0..steps {
+            if whole_day {
+                self.solution.set_time(hour, sec + step);
+                (hour, sec) = self.solution.get_time();
+            }
+            for &mult in &mults {
+                apply_load_shapes(
+                    circuit,
+                    ShapeMode::Daily,
+                    self.solution.hours(),
+                    mult,
+                    step_hours,
+                );
+                if !self.solution.solve_with_controls(circuit, max_control)? {
+                    failed += 1;
+                }
+                circuit.sample_meters(&SolutionState {
+                    interval,
+                    load_mult: mult,
+                    ..self.solution.state()
+                });
+            }
+        }
+        self.options.set("hour", OptionValue::Integer(hour))?;
+        self.options.set("sec", OptionValue::Double(sec))?;
+        if failed > 0 {
+            return Err(steps_not_converged(failed, steps * mults.len() as i32));
+        }
+        Ok(String::new())
+    }
+
+    // Number steps of stepsize in dynamics, the monitors and meters sampled
+    // at each. The first dynamics Solve starts the machines from a power
+    // flow at the time of the options.
+    fn solve_dynamics(&mut self) -> DssResult<String> {
+        let number = self.options.get_integer("number").max(0);
+        let step = self.options.get_double("stepsize");
+        let (mut hour, mut sec) = (
+            self.options.get_integer("hour"),
+            self.options.get_double("sec"),
+        );
+        if !self.solution.is_dynamic() {
+            self.solve_snapshot()?;
+            let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+            self.solution.init_dynamics(&mut self.circuits[index]);
+        }
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        let mut failed = 0;
+        for _ in 0..number {
+            self.solution.set_time(hour, sec + step);
+            (hour, sec) = self.solution.get_time();
+            if !self.solution.solve_dynamic_step(circuit, step)? {
+                failed += 1;
+            }
+            circuit.sample_meters(&SolutionState {
+                interval: step,
+                ..self.solution.state()
+            });
+        }
+        self.options.set("hour", OptionValue::Integer(hour))?;
+        self.options.set("sec", OptionValue::Double(sec))?;
+        if failed > 0 {
+            return Err(steps_not_converged(failed, number));
+        }
+        Ok(String::new())
+    }
+
+    // Short circuit matrices of every bus for Show Faults
+    fn solve_fault_study(&mut self) -> DssResult<String> {
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        self.solution.solve_fault_study(&mut self.circuits[index])?;
+        Ok(String::new())
+    }
+
+    // Solves the fundamental, then the circuit at each harmonic of the
+    // harmonics option, the monitors sampling every one (Pascal
+    // SolveHarmonic)
+    fn solve_harmonics(&mut self) -> DssResult<String> {
+        self.solve_snapshot()?;
+        let mut harmonics = self.options.get_doubles("harmonics").to_vec();
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        if harmonics.is_empty() {
+            harmonics = spectrum_harmonics(circuit);
+        }
+        self.solution.init_harmonics(circuit);
+        for harmonic in harmonics {
+            self.solution.solve_harmonic(circuit, harmonic)?;
+            circuit.sample_class_meters(Some("Monitor"), &self.solution.state());
+        }
+        Ok(String::new())
+    }
+
+    // Places number generators or capacitors, one at a time, each at the
+    // candidate bus that improves the circuit most (Pascal AutoAdd mode).
+    // Reports how the circuit did with the element at every bus tried; Undo
+    // takes the placements back.
+    fn solve_auto_add(&mut self) -> DssResult<String> {
+        let settings = AutoAdd {
+            add_type: AddType::from_name(self.options.get_text("addtype")).unwrap_or_default(),
+            gen_kw: self.options.get_double("genkw"),
+            gen_pf: self.options.get_double("genpf"),
+            cap_kvar: self.options.get_double("capkvar"),
+            loss_weight: self.options.get_double("lossweight"),
+            ue_weight: self.options.get_double("ueweight"),
+            buses: self
+                .options
+                .get_text("autobuslist")
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|bus| !bus.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+        let number = self.options.get_integer("number").max(0) as usize;
+        let load_mult = self.options.get_double("loadmult");
+        let max_control = self.max_control_iterations();
+        self.solution.set_time(
+            self.options.get_integer("hour"),
+            self.options.get_double("sec"),
+        );
+        let index = self.active_circuit.ok_or_else(no_active_circuit)?;
+        let circuit = &mut self.circuits[index];
+        apply_load_mult(circuit, load_mult);
+        let journaled = self.options.get_integer("undolevels") > 0;
+        let journal = &mut self.journal;
+        let results = self.solution.auto_add(
+            circuit,
+            &settings,
+            number,
+            max_control,
+            |circuit, element| {
+                // the journal keeps elements from before they are added
+                if journaled {
+                    journal.keep_added(circuit);
+                }
+                circuit.add_element(element);
+            },
+        )?;
+
+        let mut lines = Vec::new();
+        for result in &results {
+            if let Some(best) = result.best() {
+                lines.push(format!("{} at bus {}", result.element, best.bus));
+            }
+            lines.push(format!(
+                "Base losses {:.3} kW, EEN {:.3} kWh",
+                result.base_kw_losses, result.base_een
+            ));
+            lines.push("Bus, kW Losses, EEN (kWh), Improvement".to_string());
+            for candidate in &result.candidates {
+                lines.push(format!(
+                    "{}, {:.3}, {:.3}, {:.6}",
+                    candidate.bus, candidate.kw_losses, candidate.een, candidate.improvement
+                ));
+            }
+            lines.push(String::new());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::{Capacitor, Complex64, EnergyMeter, Generator, Monitor};
+
+    use super::*;
+
+    fn load_power(exec: &Executive, name: &str) -> Complex64 {
+        let circuit = exec.get_active_circuit().unwrap();
+        let id = circuit.find_element("Load", name).unwrap();
+        let load = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        load.total_power(circuit.get_node_voltages())
+    }
+
+    #[test]
+    fn test_solve_snapshot() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47 pu=1.0",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.3 x1=0.6 r0=0.6 x0=1.8 length=2",
+            "new load.ld1 bus1=b kv=12.47 kw=1000 pf=0.9",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("solve mode=snapshot tolerance=0.000001")
+            .unwrap();
+        assert_eq!(exec.execute("get mode").unwrap().output, "snapshot");
+
+        // the constant power load draws its rating at the lower voltage
+        let kvar = 1000.0 * (1.0 / 0.81 - 1.0_f64).sqrt();
+        let power = load_power(&exec, "ld1") / 1000.0;
+        assert!((power - Complex64::new(1000.0, kvar)).norm() < 0.1);
+        let circuit = exec.get_active_circuit().unwrap();
+        let b = circuit.bus_list().node_ref("b", 1).unwrap();
+        let vpu = circuit.get_node_voltages()[b].norm() / (12470.0 / 3.0_f64.sqrt());
+        assert!(vpu < 1.0 && vpu > 0.95);
+
+        exec.execute("solve loadmult=0.5").unwrap();
+        let power = load_power(&exec, "ld1") / 1000.0;
+        assert!((power.re - 500.0).abs() < 0.1);
+
+        // Newton arrives at the same voltages
+        exec.execute("set algorithm=newton loadmult=1").unwrap();
+        exec.execute("solve").unwrap();
+        let power = load_power(&exec, "ld1") / 1000.0;
+        assert!((power - Complex64::new(1000.0, kvar)).norm() < 0.1);
+        exec.execute("set algorithm=normal").unwrap();
+
+        exec.execute("set maxiterations=1 tolerance=0.0000000001 loadmult=1")
+            .unwrap();
+        let err = exec.execute("solve").unwrap_err();
+        assert_eq!(err.number(), codes::NOT_CONVERGED);
+    }
+
+    #[test]
+    fn test_solve_daily() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new loadshape.day npts=24 interval=1 mult=[0.5 0.5 0.5 0.5 0.5 0.5 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 0.5 0.5]",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new load.ld1 bus1=b kv=12.47 kw=100 pf=1 daily=day",
+            "new monitor.m1 element=load.ld1 mode=1",
+            "new energymeter.e1 element=line.l1",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("set mode=daily number=8 stepsize=1h").unwrap();
+        exec.execute("solve").unwrap();
+        // the first step is at hour 1, the eighth at hour 8
+        assert_eq!(exec.execute("get hour").unwrap().output, "8");
+        assert!((load_power(&exec, "ld1").re - 100000.0).abs() < 10.0);
+
+        exec.execute("solve number=16").unwrap();
+        assert_eq!(exec.execute("get hour").unwrap().output, "24");
+        assert!((load_power(&exec, "ld1").re - 50000.0).abs() < 10.0);
+
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 24);
+        // 8 hours at half load, 16 at full, and a little for the line
+        let meter = circuit
+            .find_object_as::<EnergyMeter>("energymeter", "e1")
+            .unwrap();
+        let kwh = meter.get_register("kWh").unwrap();
+        assert!(kwh > 2000.0 && kwh < 2010.0, "{}", kwh);
+    }
+
+    #[test]
+    fn test_solve_harmonics() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new load.ld1 bus1=b kv=12.47 kw=1000 pf=0.9",
+            "new monitor.m1 element=line.l1 terminal=2",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("solve mode=harmonic harmonics=[1 5 7]")
+            .unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 3);
+        let csv = monitor.to_csv();
+        assert!(csv.starts_with("Freq, Harmonic"), "{}", csv);
+        assert!(csv.lines().nth(2).unwrap().starts_with("300, 5"), "{}", csv);
+        // the 5th at the load is a few percent of the fundamental
+        let b = circuit.bus_list().node_ref("b", 1).unwrap();
+        let v5 = monitor_voltage(&csv, 2);
+        let v1 = monitor_voltage(&csv, 1);
+        assert!(v5 > 0.0 && v5 < 0.1 * v1, "{} {}", v5, v1);
+        assert!(circuit.get_node_voltages()[b].norm() > 0.0);
+
+        // every harmonic of the spectra when none are given
+        exec.execute("reset monitors").unwrap();
+        exec.execute("solve harmonics=all").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 7);
+
+        // a snapshot after it is back at the fundamental
+        exec.execute("solve mode=snapshot").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let vpu = circuit.get_node_voltages()[b].norm() / (12470.0 / 3.0_f64.sqrt());
+        assert!(vpu > 0.95 && vpu < 1.0);
+
+        // harmonics is taken for the harmonic mode
+        exec.execute("reset monitors").unwrap();
+        exec.execute("solve mode=harmonics harmonics=[1 5]")
+            .unwrap();
+        assert_eq!(exec.options.get_text("mode"), "harmonic");
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 2);
+        exec.execute("set mode=snapshot").unwrap();
+        exec.execute("set mode=Harmonics").unwrap();
+        assert_eq!(exec.options.get_text("mode"), "harmonic");
+    }
+
+    // First voltage magnitude of a row of a monitor's csv
+    fn monitor_voltage(csv: &str, row: usize) -> f64 {
+        csv.lines()
+            .nth(row)
+            .unwrap()
+            .split(',')
+            .nth(2)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_solve_dynamics() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new load.ld1 bus1=b kv=12.47 kw=2000 pf=0.9",
+            "new generator.g1 bus1=b kv=12.47 kw=1000 pf=0.9 h=0.5",
+            "new monitor.m1 element=generator.g1 mode=3",
+            "new fault.f1 bus1=b phases=3 r=0.001 ontime=0.05",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("set mode=dynamics number=40").unwrap();
+        assert_eq!(exec.execute("get stepsize").unwrap().output, "0.001");
+        let machine = |exec: &Executive| {
+            let circuit = exec.get_active_circuit().unwrap();
+            let generator = circuit
+                .find_object_as::<Generator>("generator", "g1")
+                .unwrap();
+            (generator.is_dynamic(), generator.machine_state().clone())
+        };
+
+        // until the fault the machine runs at synchronous speed
+        exec.execute("solve").unwrap();
+        let (dynamic, state) = machine(&exec);
+        assert!(dynamic);
+        assert!(state.speed.abs() < 1e-3, "{}", state.speed);
+        let theta = state.theta;
+
+        // the fault takes its output and the rotor runs ahead
+        exec.execute("solve").unwrap();
+        let (_, state) = machine(&exec);
+        assert!(state.speed > 0.1 && state.theta > theta, "{:?}", state);
+        assert!(
+            (exec
+                .execute("get sec")
+                .unwrap()
+                .output
+                .parse::<f64>()
+                .unwrap()
+                - 0.08)
+                .abs()
+                < 1e-9
+        );
+
+        // cleared, the swing carries on from where it was
+        exec.execute("edit fault.f1 enabled=no").unwrap();
+        exec.execute("solve").unwrap();
+
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 120);
+        let csv = monitor.to_csv();
+        assert!(
+            csv.starts_with("hour, t(sec), Frequency, Theta (Deg)"),
+            "{}",
+            csv
+        );
+        let frequency: f64 = csv
+            .lines()
+            .nth(60)
+            .unwrap()
+            .split(", ")
+            .nth(2)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(frequency > 60.0, "{}", csv);
+
+        // another mode puts the power flow model back
+        exec.execute("solve mode=snapshot").unwrap();
+        assert!(!machine(&exec).0);
+    }
+
+    #[test]
+    fn test_control_modes() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.3 x1=0.6 r0=0.6 x0=1.8 length=2",
+            "new load.ld1 bus1=b kv=12.47 kw=3000 pf=0.85",
+            "new capacitor.c1 bus1=b kv=12.47 kvar=600 states=[0]",
+            "new capcontrol.cc1 element=line.l1 terminal=2 capacitor=c1 type=voltage onsetting=7500 offsetting=7600 ptratio=1 delay=30",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        let closed = |exec: &Executive| {
+            let circuit = exec.get_active_circuit().unwrap();
+            let capacitor = circuit
+                .find_object_as::<Capacitor>("capacitor", "c1")
+                .unwrap();
+            capacitor.get_states()[0]
+        };
+
+        exec.execute("solve controlmode=off").unwrap();
+        assert!(!closed(&exec));
+        assert!(exec.solution.control_queue().is_empty());
+
+        // the bank waits out its delay
+        exec.execute("solve controlmode=time").unwrap();
+        assert!(!closed(&exec));
+        assert_eq!(exec.solution.control_queue().len(), 1);
+        exec.execute("solve sec=40").unwrap();
+        assert!(closed(&exec));
+        assert!(exec.solution.control_queue().is_empty());
+
+        // events move the time on to the switching
+        exec.execute("edit capacitor.c1 states=[0]").unwrap();
+        exec.execute("solve controlmode=event sec=0").unwrap();
+        assert!(closed(&exec));
+        assert_eq!(exec.solution.get_time(), (0, 30.0));
+
+        // static switches at once and stays at its time
+        exec.execute("edit capacitor.c1 states=[0]").unwrap();
+        exec.execute("solve controlmode=static").unwrap();
+        assert!(closed(&exec));
+        assert_eq!(exec.solution.get_time(), (0, 0.0));
+        assert_eq!(exec.solution.get_control_iteration(), 1);
+    }
+
+    fn random_powers(exec: &mut Executive, seed: u64) -> Vec<f64> {
+        exec.execute(&format!("set seed={}", seed)).unwrap();
+        (0..10)
+            .map(|_| {
+                exec.execute("solve").unwrap();
+                load_power(exec, "ld1").re / 1000.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_solve_monte_carlo() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new load.ld1 bus1=b kv=12.47 kw=100 pf=1 %mean=80 %stddev=20",
+            "new monitor.m1 element=load.ld1 mode=1",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("set mode=montecarlo1 number=1").unwrap();
+        let first = random_powers(&mut exec, 42);
+        // the same seed repeats the study, another one does not
+        let same = |powers: Vec<f64>| powers.iter().zip(&first).all(|(a, b)| (a - b).abs() < 1e-6);
+        assert!(same(random_powers(&mut exec, 42)));
+        assert!(!same(random_powers(&mut exec, 7)));
+        let mean = first.iter().sum::<f64>() / first.len() as f64;
+        assert!((mean - 80.0).abs() < 20.0, "{}", mean);
+        assert!(first.iter().any(|&p| (p - first[0]).abs() > 1.0));
+
+        exec.execute("set random=uniform").unwrap();
+        assert!(
+            random_powers(&mut exec, 1)
+                .iter()
+                .all(|&p| (0.0..100.0).contains(&p))
+        );
+        exec.execute("set random=none").unwrap();
+        assert!(
+            random_powers(&mut exec, 1)
+                .iter()
+                .all(|&p| (p - 100.0).abs() < 0.1)
+        );
+
+        // two days of hourly steps
+        exec.execute("reset monitors").unwrap();
+        exec.execute("set random=gaussian mode=montecarlo2 number=2 stepsize=1h")
+            .unwrap();
+        exec.execute("solve").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        let monitor = circuit.find_object_as::<Monitor>("monitor", "m1").unwrap();
+        assert_eq!(monitor.sample_count(), 48);
+    }
+
+    #[test]
+    fn test_solve_load_duration() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new loadshape.ld npts=3 interval=1 mult=[1 0.8 0.5]",
+            "new line.l1 bus1=sourcebus bus2=b r1=0.1 x1=0.2 r0=0.3 x0=0.6 length=1",
+            "new load.ld1 bus1=b kv=12.47 kw=100 pf=1",
+            "new energymeter.e1 element=line.l1",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        let err = exec.execute("solve mode=loadduration2").unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+
+        // an hour at each point of the curve
+        exec.execute("solve ldcurve=ld").unwrap();
+        assert!((load_power(&exec, "ld1").re - 50000.0).abs() < 10.0);
+        let circuit = exec.get_active_circuit().unwrap();
+        let meter = circuit
+            .find_object_as::<EnergyMeter>("energymeter", "e1")
+            .unwrap();
+        let kwh = meter.get_register("kWh").unwrap();
+        assert!(kwh > 230.0 && kwh < 231.0, "{}", kwh);
+
+        exec.execute("reset meters").unwrap();
+        exec.execute("solve mode=loadduration1").unwrap();
+        assert_eq!(exec.execute("get hour").unwrap().output, "24");
+        let circuit = exec.get_active_circuit().unwrap();
+        let meter = circuit
+            .find_object_as::<EnergyMeter>("energymeter", "e1")
+            .unwrap();
+        let kwh = meter.get_register("kWh").unwrap();
+        assert!(kwh > 24.0 * 230.0 && kwh < 24.0 * 231.0, "{}", kwh);
+    }
+
+    #[test]
+    fn test_solve_auto_add() {
+        let mut exec = Executive::new();
+        for line in [
+            "new circuit.c1 basekv=12.47",
+            "new line.l1 bus1=sourcebus bus2=b1 r1=0.5 x1=1 r0=1.5 x0=3 length=2",
+            "new line.l2 bus1=b1 bus2=b2 r1=0.5 x1=1 r0=1.5 x0=3 length=2",
+            "new load.ld1 bus1=b2 kv=12.47 kw=2000 pf=0.9",
+        ] {
+            exec.execute(line).unwrap();
+        }
+        exec.execute("solve").unwrap();
+        let output = exec
+            .execute("solve mode=autoadd genkw=1000 autobuslist=[b1 b2]")
+            .unwrap()
+            .output;
+        // the generator does most good next to the load
+        assert!(
+            output.starts_with("Generator.gadd1 at bus b2"),
+            "{}",
+            output
+        );
+        assert_eq!(
+            output.lines().filter(|line| line.starts_with("b")).count(),
+            2
+        );
+        let circuit = exec.get_active_circuit().unwrap();
+        let id = circuit.find_element("Generator", "gadd1").unwrap();
+        let generator = circuit.element(id).unwrap().as_ckt_element().unwrap();
+        assert_eq!(generator.ckt_base().get_bus(0), "b2.1.2.3");
+
+        exec.execute("undo").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        assert!(circuit.find_element("Generator", "gadd1").is_none());
+
+        let err = exec.execute("solve autobuslist=[nowhere]").unwrap_err();
+        assert_eq!(err.number(), codes::OBJECT_NOT_FOUND);
+    }
+}
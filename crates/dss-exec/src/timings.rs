@@ -0,0 +1,164 @@
+// Time spent per command, reported by Show Timings. Commands that only run
+// other commands (Redirect, Compile, If, ForEach) are not timed themselves, so
+// a compiled script shows up as the commands it contains.
+
+use std::time::Duration;
+
+// What a command spends its time on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingCategory {
+    // Reading definitions and changing the model
+    Parse,
+    Solve,
+    // Writing results and scripts out
+    Export,
+}
+
+impl TimingCategory {
+    pub fn of(command: &str) -> TimingCategory {
+        match command {
+            "Solve" | "CalcVoltageBases" => TimingCategory::Solve,
+            "Save" | "Dump" | "Show" | "Export" => TimingCategory::Export,
+            _ => TimingCategory::Parse,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimingCategory::Parse => "parse",
+            TimingCategory::Solve => "solve",
+            TimingCategory::Export => "export",
+        }
+    }
+}
+
+pub(crate) const UNTIMED: &[&str] = &["", "Redirect", "Compile", "If", "ForEach"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingEntry {
+    pub command: &'static str,
+    pub count: u32,
+    pub total: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Timings {
+    // In order of first use
+    entries: Vec<TimingEntry>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Timings {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, command: &'static str, elapsed: Duration) {
+        if UNTIMED.contains(&command) {
+            return;
+        }
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.command == command)
+        {
+            Some(entry) => {
+                entry.count += 1;
+                entry.total += elapsed;
+            }
+            None => self.entries.push(TimingEntry {
+                command,
+                count: 1,
+                total: elapsed,
+            }),
+        }
+    }
+
+    pub fn entries(&self) -> &[TimingEntry] {
+        &self.entries
+    }
+
+    pub fn total(&self, category: TimingCategory) -> Duration {
+        self.entries
+            .iter()
+            .filter(|entry| TimingCategory::of(entry.command) == category)
+            .map(|entry| entry.total)
+            .sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn report(&self) -> String {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mut lines = vec![format!(
+            "{:<18}{:>8}{:>14}{:>12}",
+            "Command", "Count", "Total (ms)", "Mean (ms)"
+        )];
+        for entry in &self.entries {
+            lines.push(format!(
+                "{:<18}{:>8}{:>14.3}{:>12.3}",
+                entry.command,
+                entry.count,
+                ms(entry.total),
+                ms(entry.total) / f64::from(entry.count)
+            ));
+        }
+        let categories: Vec<String> = [
+            TimingCategory::Parse,
+            TimingCategory::Solve,
+            TimingCategory::Export,
+        ]
+        .iter()
+        .map(|category| format!("{} {:.3} ms", category.name(), ms(self.total(*category))))
+        .collect();
+        lines.push(format!("Totals: {}", categories.join(", ")));
+        lines.join("\n")
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_totals() {
+        let mut timings = Timings::new();
+        timings.record("New", Duration::from_millis(2));
+        timings.record("New", Duration::from_millis(4));
+        timings.record("Save", Duration::from_millis(1));
+        timings.record("Redirect", Duration::from_millis(100));
+
+        assert_eq!(timings.entries().len(), 2);
+        assert_eq!(timings.entries()[0].count, 2);
+        assert_eq!(
+            timings.total(TimingCategory::Parse),
+            Duration::from_millis(6)
+        );
+        assert_eq!(
+            timings.total(TimingCategory::Export),
+            Duration::from_millis(1)
+        );
+        assert_eq!(timings.total(TimingCategory::Solve), Duration::ZERO);
+
+        let report = timings.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[1],
+            "New                      2         6.000       3.000"
+        );
+        assert_eq!(
+            lines[3],
+            "Totals: parse 6.000 ms, solve 0.000 ms, export 1.000 ms"
+        );
+    }
+}
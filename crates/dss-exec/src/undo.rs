@@ -0,0 +1,310 @@
+// Undo journal for the commands that change elements (New, Edit, More and
+// BatchEdit) and for set_property_value. Before an element changes for the
+// first time in a command line, its previous state is kept; Undo puts back
+// the states kept for the last command line and the active element it had.
+// The number of command lines kept is set by the undolevels option, 0
+// turning the journal off. The journal only covers the active circuit and is
+// dropped when that changes.
+
+use dss_common::{DssResult, WarningKind};
+
+use dss_core::{Circuit, DssObject, ElementId};
+
+use crate::executive::Executive;
+
+// Everything one command line changed
+#[derive(Debug, Clone)]
+pub(crate) struct UndoStep {
+    command: String,
+    // Element index and its state before the change; None for elements the
+    // command added
+    changes: Vec<(ElementId, Option<Box<dyn DssObject>>)>,
+    active_element: Option<usize>,
+    active_class: Option<&'static str>,
+    active_terminal: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Journal {
+    steps: Vec<UndoStep>,
+    // Step of the command line being run, until execute ends it
+    pending: Option<UndoStep>,
+}
+
+impl Journal {
+    pub(crate) fn new() -> Self {
+        Journal {
+            steps: Vec::new(),
+            pending: None,
+        }
+    }
+
+    // Keeps the state of an element about to change, once per command line;
+    // `before` is its state, None for an element about to be added. True
+    // when this call kept it
+    fn keep(&mut self, circuit: &Circuit, index: usize, before: Option<&dyn DssObject>) -> bool {
+        let step = self.pending.get_or_insert_with(|| UndoStep {
+            command: String::new(),
+            changes: Vec::new(),
+            active_element: circuit.get_active_element(),
+            active_class: circuit.get_active_class(),
+            active_terminal: circuit.get_active_terminal(),
+        });
+        if step.changes.iter().all(|(changed, _)| *changed != index) {
+            step.changes
+                .push((index, before.map(|element| element.clone_object())));
+            return true;
+        }
+        false
+    }
+
+    // Keeps an element about to be added to `circuit`, for commands that
+    // add elements of their own
+    pub(crate) fn keep_added(&mut self, circuit: &Circuit) {
+        self.keep(circuit, circuit.elements().len(), None);
+    }
+
+    // Takes back the state kept for an element whose change failed, to put
+    // it back; the pending step goes with it when nothing else is kept
+    pub(crate) fn take_back(&mut self, index: usize) -> Option<Box<dyn DssObject>> {
+        let step = self.pending.as_mut()?;
+        let position = step
+            .changes
+            .iter()
+            .position(|(changed, _)| *changed == index)?;
+        let (_, before) = step.changes.remove(position);
+        if step.changes.is_empty() {
+            self.pending = None;
+        }
+        before
+    }
+
+    // True while a command line has a step open
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // Ends the step of the command line just run, keeping at most `levels`
+    pub(crate) fn end_step(&mut self, command: &str, levels: usize) {
+        let Some(mut step) = self.pending.take() else {
+            return;
+        };
+        if levels == 0 {
+            self.steps.clear();
+            return;
+        }
+        step.command = command.trim().to_string();
+        self.steps.push(step);
+        if self.steps.len() > levels {
+            self.steps.drain(..self.steps.len() - levels);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.steps.clear();
+        self.pending = None;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executive {
+    // Called before element `index` of the active circuit changes; an index
+    // past the end is an element about to be added. True when this call kept
+    // its state
+    pub(crate) fn journal(&mut self, index: usize) -> bool {
+        if self.options.get_integer("undolevels") <= 0 {
+            return false;
+        }
+        match self.active_circuit {
+            Some(active) => {
+                let circuit = &self.circuits[active];
+                self.journal.keep(circuit, index, circuit.element(index))
+            }
+            None => false,
+        }
+    }
+
+    // As journal, for element `index` taken out of the active circuit to be
+    // changed
+    pub(crate) fn journal_taken(&mut self, index: usize, element: &dyn DssObject) -> bool {
+        if self.options.get_integer("undolevels") <= 0 {
+            return false;
+        }
+        match self.active_circuit {
+            Some(active) => self
+                .journal
+                .keep(&self.circuits[active], index, Some(element)),
+            None => false,
+        }
+    }
+
+    // Number of command lines Undo can take back
+    pub fn undo_levels(&self) -> usize {
+        self.journal.len()
+    }
+
+    // Undo: reverts the last command line that changed elements and returns it
+    pub(crate) fn do_undo(&mut self) -> DssResult<String> {
+        let Some(step) = self.journal.steps.pop() else {
+            self.diagnostics
+                .warn(WarningKind::ValueIgnored, "Nothing to undo");
+            return Ok(String::new());
+        };
+        let circuit = self.active_circuit_mut()?;
+        for (index, before) in step.changes.into_iter().rev() {
+            match before {
+                Some(element) => circuit.replace_element(index, element),
+                // added elements are taken back newest first, so each is
+                // the last one
+                None => {
+                    circuit.remove_last_element();
+                }
+            }
+        }
+        if let Some(index) = step.active_element {
+            circuit.set_active_element(index);
+        }
+        if let Some(class_name) = step.active_class {
+            circuit.set_active_class(class_name);
+        }
+        circuit.set_active_terminal(step.active_terminal);
+        // the elements put back bring their old Yprims
+        self.solution.invalidate();
+        Ok(step.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kw(exec: &Executive, load: &str) -> Option<String> {
+        let circuit = exec.get_active_circuit().unwrap();
+        let index = circuit.find_element("Load", load)?;
+        circuit.elements()[index].get_property_value("kw")
+    }
+
+    #[test]
+    fn test_undo_edits() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1 kw=10").unwrap();
+        exec.execute("new load.ld2 kw=20").unwrap();
+        exec.execute("select load.ld1").unwrap();
+
+        exec.execute("batchedit load.* kw=0").unwrap();
+        assert_eq!(kw(&exec, "ld2").as_deref(), Some("0"));
+        let result = exec.execute("undo").unwrap();
+        assert_eq!(result.output, "batchedit load.* kw=0");
+        assert_eq!(kw(&exec, "ld1").as_deref(), Some("10"));
+        assert_eq!(kw(&exec, "ld2").as_deref(), Some("20"));
+
+        exec.execute("edit load.ld2 kw=25 pf=0.9").unwrap();
+        exec.execute("undo").unwrap();
+        let circuit = exec.get_active_circuit().unwrap();
+        assert_eq!(circuit.elements()[2].property_values().len(), 1);
+        // the active element is back to the selected one
+        assert_eq!(circuit.get_active_element(), Some(1));
+
+        exec.execute("undo").unwrap();
+        assert_eq!(kw(&exec, "ld2"), None);
+        assert_eq!(exec.get_active_circuit().unwrap().elements().len(), 2);
+        exec.execute("undo").unwrap();
+        let result = exec.execute("undo").unwrap();
+        assert_eq!(result.warnings[0].message, "Nothing to undo");
+        assert_eq!(exec.get_active_circuit().unwrap().elements().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_property_value() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new load.ld1 kw=10").unwrap();
+
+        exec.set_property_value("load.ld1", "kw", "15").unwrap();
+        assert_eq!(kw(&exec, "ld1").as_deref(), Some("15"));
+        let result = exec.execute("undo").unwrap();
+        assert_eq!(result.output, "Edit load.ld1 kw=15");
+        assert_eq!(kw(&exec, "ld1").as_deref(), Some("10"));
+        // the step before it is untouched
+        exec.execute("undo").unwrap();
+        assert_eq!(kw(&exec, "ld1"), None);
+
+        // a failed change leaves no step behind
+        exec.execute("new load.ld1 kw=10").unwrap();
+        let levels = exec.undo_levels();
+        assert!(exec.set_property_value("load.ld1", "nosuch", "1").is_err());
+        assert_eq!(exec.undo_levels(), levels);
+        assert!(!exec.journal.is_pending());
+    }
+
+    #[test]
+    fn test_failed_property_value() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("new linecode.lc1 r1=0.2").unwrap();
+        exec.execute("new line.l1 bus1=a bus2=b linecode=lc1")
+            .unwrap();
+        let levels = exec.undo_levels();
+
+        let err = exec
+            .set_property_value("line.l1", "linecode", "lc9")
+            .unwrap_err();
+        assert_eq!(err.message(), "Line code \"lc9\" not found for Line.l1");
+        // the line keeps its line code and Undo history
+        let value = exec.get_property_value("line.l1", "linecode").unwrap();
+        assert_eq!(value, "lc1");
+        assert_eq!(exec.undo_levels(), levels);
+        assert!(!exec.journal.is_pending());
+        let result = exec.execute("undo").unwrap();
+        assert_eq!(result.output, "new line.l1 bus1=a bus2=b linecode=lc1");
+
+        // with the journal off, the line is put back just the same
+        exec.execute("new line.l2 bus1=a bus2=b linecode=lc1")
+            .unwrap();
+        exec.execute("set undolevels=0").unwrap();
+        assert!(
+            exec.set_property_value("line.l2", "linecode", "lc9")
+                .is_err()
+        );
+        let value = exec.get_property_value("line.l2", "linecode").unwrap();
+        assert_eq!(value, "lc1");
+    }
+
+    #[test]
+    fn test_undo_levels() {
+        let mut exec = Executive::new();
+        exec.execute("new circuit.c1").unwrap();
+        exec.execute("set undolevels=2").unwrap();
+        for kw in [1, 2, 3] {
+            exec.execute(&format!("new load.ld1 kw={}", kw)).unwrap();
+        }
+        // redefining an element is undone to its previous definition
+        exec.execute("undo").unwrap();
+        assert_eq!(kw(&exec, "ld1").as_deref(), Some("2"));
+        assert_eq!(exec.undo_levels(), 1);
+
+        // commands that change nothing do not use up a level
+        exec.execute("? load.ld1.kw").unwrap();
+        exec.execute("undo").unwrap();
+        assert_eq!(kw(&exec, "ld1").as_deref(), Some("1"));
+
+        exec.execute("set undolevels=0").unwrap();
+        exec.execute("edit load.ld1 kw=9").unwrap();
+        assert_eq!(exec.undo_levels(), 0);
+
+        exec.execute("set undolevels=5").unwrap();
+        exec.execute("edit load.ld1 kw=8").unwrap();
+        exec.execute("new circuit.c2").unwrap();
+        assert_eq!(exec.undo_levels(), 0);
+    }
+}
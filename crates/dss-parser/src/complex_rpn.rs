@@ -0,0 +1,478 @@
+// Complex counterpart of the RPN calculator, for phasor and impedance math
+// such as "{0.1 0.3 rect 3 *}" or "{1 -30 polar a *}". Angles follow the angle
+// mode (degrees by default); polar entry takes the magnitude in y and the
+// angle in x.
+
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+
+use num_complex::Complex64;
+
+use crate::rpn::{AngleMode, NonFinitePolicy, RpnError, RpnResult, Stack};
+
+#[derive(Debug)]
+pub struct ComplexRPNCalculator {
+    stack: Stack<Complex64>, // stack[0] is the x register
+    depth: usize,
+    checked: bool,
+    angle_mode: AngleMode,
+    registers: HashMap<String, Complex64>,
+    non_finite_policy: NonFinitePolicy,
+    clamped: Vec<String>,
+}
+
+impl ComplexRPNCalculator {
+    const ZERO: Complex64 = Complex64::new(0.0, 0.0);
+    const SQRT_3: f64 = 1.732_050_807_568_877_2;
+
+    pub fn new() -> Self {
+        ComplexRPNCalculator {
+            stack: Stack::new(Self::ZERO),
+            depth: 0,
+            checked: false,
+            angle_mode: AngleMode::Degrees,
+            registers: HashMap::new(),
+            non_finite_policy: NonFinitePolicy::Propagate,
+            clamped: Vec::new(),
+        }
+    }
+
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
+    pub fn get_non_finite_policy(&self) -> NonFinitePolicy {
+        self.non_finite_policy
+    }
+
+    pub fn set_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.non_finite_policy = policy;
+    }
+
+    pub fn take_clamped(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.clamped)
+    }
+
+    pub fn get_angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+    }
+
+    fn angle_in(&self, angle: f64) -> f64 {
+        match self.angle_mode {
+            AngleMode::Degrees => angle.to_radians(),
+            AngleMode::Radians => angle,
+        }
+    }
+
+    fn angle_out(&self, angle: f64) -> f64 {
+        match self.angle_mode {
+            AngleMode::Degrees => angle.to_degrees(),
+            AngleMode::Radians => angle,
+        }
+    }
+
+    fn require(&self, count: usize, op: &'static str) -> RpnResult {
+        if self.checked && self.depth < count {
+            return Err(RpnError::StackUnderflow { op: op.to_string() });
+        }
+        Ok(())
+    }
+
+    fn require_nonzero(&self, value: Complex64) -> RpnResult {
+        if self.checked && value == Self::ZERO {
+            return Err(RpnError::DivisionByZero);
+        }
+        Ok(())
+    }
+
+    // The whole stack, bottom first and x last
+    pub fn stack(&self) -> &[Complex64] {
+        self.stack.as_slice()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get_x(&self) -> Complex64 {
+        self.stack[0]
+    }
+
+    pub fn get_y(&self) -> Complex64 {
+        self.stack[1]
+    }
+
+    pub fn set_x(&mut self, value: Complex64) {
+        self.roll_up();
+        self.stack[0] = value;
+    }
+
+    pub fn roll_up(&mut self) {
+        self.stack.roll_up();
+        self.depth += 1;
+    }
+
+    pub fn roll_down(&mut self) {
+        self.stack.roll_down();
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn clear_stack(&mut self) {
+        self.stack = Stack::new(Self::ZERO);
+        self.depth = 0;
+    }
+
+    fn binary(
+        &mut self,
+        op: &'static str,
+        f: impl Fn(Complex64, Complex64) -> Complex64,
+    ) -> RpnResult {
+        self.require(2, op)?;
+        self.stack[1] = f(self.stack[1], self.stack[0]);
+        self.roll_down();
+        Ok(())
+    }
+
+    fn unary(&mut self, op: &'static str, f: impl Fn(Complex64) -> Complex64) -> RpnResult {
+        self.require(1, op)?;
+        self.stack[0] = f(self.stack[0]);
+        Ok(())
+    }
+
+    pub fn add(&mut self) -> RpnResult {
+        self.binary("+", |y, x| y + x)
+    }
+
+    pub fn subtract(&mut self) -> RpnResult {
+        self.binary("-", |y, x| y - x)
+    }
+
+    pub fn multiply(&mut self) -> RpnResult {
+        self.binary("*", |y, x| y * x)
+    }
+
+    pub fn divide(&mut self) -> RpnResult {
+        self.require_nonzero(self.stack[0])?;
+        self.binary("/", |y, x| y / x)
+    }
+
+    pub fn inv(&mut self) -> RpnResult {
+        self.require_nonzero(self.stack[0])?;
+        self.unary("inv", |x| x.inv())
+    }
+
+    pub fn negate(&mut self) -> RpnResult {
+        self.unary("neg", |x| -x)
+    }
+
+    pub fn sqrt(&mut self) -> RpnResult {
+        self.unary("sqrt", |x| x.sqrt())
+    }
+
+    pub fn square(&mut self) -> RpnResult {
+        self.unary("sqr", |x| x * x)
+    }
+
+    pub fn y_to_the_x_power(&mut self) -> RpnResult {
+        self.binary("^", |y, x| y.powc(x))
+    }
+
+    pub fn etothex(&mut self) -> RpnResult {
+        self.unary("exp", |x| x.exp())
+    }
+
+    pub fn nat_log(&mut self) -> RpnResult {
+        self.require_nonzero(self.stack[0])?;
+        self.unary("ln", |x| x.ln())
+    }
+
+    pub fn conj(&mut self) -> RpnResult {
+        self.unary("conj", |x| x.conj())
+    }
+
+    // The following replace x by a real number
+    pub fn real_part(&mut self) -> RpnResult {
+        self.unary("re", |x| Complex64::new(x.re, 0.0))
+    }
+
+    pub fn imag_part(&mut self) -> RpnResult {
+        self.unary("im", |x| Complex64::new(x.im, 0.0))
+    }
+
+    pub fn magnitude(&mut self) -> RpnResult {
+        self.unary("mag", |x| Complex64::new(x.norm(), 0.0))
+    }
+
+    pub fn arg(&mut self) -> RpnResult {
+        self.require(1, "arg")?;
+        self.stack[0] = Complex64::new(self.angle_out(self.stack[0].arg()), 0.0);
+        Ok(())
+    }
+
+    // Combines the real parts of y (magnitude) and x (angle) into y∠x
+    pub fn polar(&mut self) -> RpnResult {
+        self.require(2, "polar")?;
+        let angle = self.angle_in(self.stack[0].re);
+        self.stack[1] = Complex64::from_polar(self.stack[1].re, angle);
+        self.roll_down();
+        Ok(())
+    }
+
+    // Combines the real parts of y and x into y + jx
+    pub fn rect(&mut self) -> RpnResult {
+        self.binary("rect", |y, x| Complex64::new(y.re, x.re))
+    }
+
+    pub fn swap_xy(&mut self) -> RpnResult {
+        self.require(2, "swap")?;
+        self.stack.swap(0, 1);
+        Ok(())
+    }
+
+    pub fn enter_j(&mut self) {
+        self.set_x(Complex64::i());
+    }
+
+    // The symmetrical components operator a = 1∠120°
+    pub fn enter_a(&mut self) {
+        self.set_x(Complex64::from_polar(1.0, 2.0 * PI / 3.0));
+    }
+
+    pub fn store(&mut self, register: &str) {
+        self.registers
+            .insert(register.to_lowercase(), self.stack[0]);
+    }
+
+    pub fn recall(&mut self, register: &str) {
+        let value = self
+            .registers
+            .get(&register.to_lowercase())
+            .copied()
+            .unwrap_or(Self::ZERO);
+        self.set_x(value);
+    }
+
+    // Executes a single token and applies the non-finite policy to both parts
+    // of the resulting x
+    pub fn command(&mut self, token: &str) -> RpnResult {
+        self.execute(token)?;
+
+        let x = self.stack[0];
+        if x.is_finite() {
+            return Ok(());
+        }
+        match self.non_finite_policy {
+            NonFinitePolicy::Propagate => Ok(()),
+            NonFinitePolicy::Error => Err(RpnError::NonFinite {
+                token: token.to_string(),
+                value: if x.re.is_finite() { x.im } else { x.re },
+            }),
+            NonFinitePolicy::Clamp => {
+                self.stack[0] =
+                    Complex64::new(NonFinitePolicy::clamp(x.re), NonFinitePolicy::clamp(x.im));
+                self.clamped.push(token.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn execute(&mut self, token: &str) -> RpnResult {
+        if let Ok(number) = token.parse::<f64>() {
+            if self.checked && !number.is_finite() {
+                return Err(RpnError::NonFinite {
+                    token: token.to_string(),
+                    value: number,
+                });
+            }
+            self.set_x(Complex64::new(number, 0.0));
+            return Ok(());
+        }
+
+        match token.to_lowercase().as_str() {
+            "+" => self.add()?,
+            "-" => self.subtract()?,
+            "*" => self.multiply()?,
+            "/" => self.divide()?,
+            "inv" => self.inv()?,
+            "neg" => self.negate()?,
+            "sqrt" => self.sqrt()?,
+            "sqr" => self.square()?,
+            "^" => self.y_to_the_x_power()?,
+            "exp" => self.etothex()?,
+            "ln" => self.nat_log()?,
+            "conj" => self.conj()?,
+            "re" => self.real_part()?,
+            "im" => self.imag_part()?,
+            "mag" | "abs" => self.magnitude()?,
+            "arg" => self.arg()?,
+            "polar" => self.polar()?,
+            "rect" => self.rect()?,
+            "swap" => self.swap_xy()?,
+            "rollup" => self.roll_up(),
+            "rolldn" => self.roll_down(),
+            "clst" => self.clear_stack(),
+            "j" | "i" => self.enter_j(),
+            "a" => self.enter_a(),
+            "pi" => self.set_x(Complex64::new(PI, 0.0)),
+            "e" => self.set_x(Complex64::new(E, 0.0)),
+            "sqrt3" => self.set_x(Complex64::new(Self::SQRT_3, 0.0)),
+            "rad" => self.set_angle_mode(AngleMode::Radians),
+            "deg" => self.set_angle_mode(AngleMode::Degrees),
+            _ => {
+                return Err(RpnError::InvalidEntry {
+                    token: token.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn eval(&mut self, expr: &str) -> Result<Complex64, RpnError> {
+        self.eval_with(expr, |token| token.to_string())
+    }
+
+    // See `RPNCalculator::eval_with`
+    pub fn eval_with(
+        &mut self,
+        expr: &str,
+        mut expand: impl FnMut(&str) -> String,
+    ) -> Result<Complex64, RpnError> {
+        let mut register_command: Option<&'static str> = None;
+
+        for token in expr.split_whitespace() {
+            if let Some(command) = register_command.take() {
+                match command {
+                    "sto" => self.store(token),
+                    _ => self.recall(token),
+                }
+            } else if token.eq_ignore_ascii_case("sto") {
+                register_command = Some("sto");
+            } else if token.eq_ignore_ascii_case("rcl") {
+                register_command = Some("rcl");
+            } else {
+                for token in expand(token).split_whitespace() {
+                    self.command(token)?;
+                }
+            }
+        }
+
+        if let Some(op) = register_command {
+            return Err(RpnError::MissingRegister { op });
+        }
+
+        Ok(self.get_x())
+    }
+}
+
+impl Default for ComplexRPNCalculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-10;
+
+    fn assert_close(actual: Complex64, expected: Complex64) {
+        assert!(
+            (actual - expected).norm() < EPSILON,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let mut calc = ComplexRPNCalculator::new();
+
+        // (1 + j2) * (3 - j1) = 5 + j5
+        let value = calc.eval("1 2 rect 3 -1 rect *").unwrap();
+        assert_close(value, Complex64::new(5.0, 5.0));
+
+        // (5 + j5) / (3 - j1) = 1 + j2
+        let value = calc.eval("3 -1 rect /").unwrap();
+        assert_close(value, Complex64::new(1.0, 2.0));
+
+        assert_close(calc.eval("conj").unwrap(), Complex64::new(1.0, -2.0));
+        assert_close(calc.eval("clst -4 sqrt").unwrap(), Complex64::new(0.0, 2.0));
+        assert_close(calc.eval("2 j * 1 +").unwrap(), Complex64::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn test_polar_and_parts() {
+        let mut calc = ComplexRPNCalculator::new();
+
+        let value = calc.eval("2 30 polar").unwrap();
+        assert_close(value, Complex64::new(3f64.sqrt(), 1.0));
+
+        assert_close(calc.eval("sto v arg").unwrap(), Complex64::new(30.0, 0.0));
+        assert_close(calc.eval("rcl v mag").unwrap(), Complex64::new(2.0, 0.0));
+        assert_close(
+            calc.eval("rcl v re").unwrap(),
+            Complex64::new(3f64.sqrt(), 0.0),
+        );
+        assert_close(calc.eval("rcl v im").unwrap(), Complex64::new(1.0, 0.0));
+
+        calc.set_angle_mode(AngleMode::Radians);
+        assert_close(calc.eval("1 pi polar").unwrap(), Complex64::new(-1.0, 0.0));
+    }
+
+    #[test]
+    fn test_sequence_operator() {
+        let mut calc = ComplexRPNCalculator::new();
+
+        // 1 + a + a^2 = 0
+        let value = calc.eval("1 a + a sqr +").unwrap();
+        assert_close(value, Complex64::new(0.0, 0.0));
+
+        // a^3 = 1
+        assert_close(calc.eval("a 3 ^").unwrap(), Complex64::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn test_checked_errors() {
+        let mut calc = ComplexRPNCalculator::new();
+        calc.set_checked(true);
+
+        assert!(matches!(
+            calc.eval("1 +"),
+            Err(RpnError::StackUnderflow { .. })
+        ));
+        assert_eq!(calc.eval("clst 1 0 /"), Err(RpnError::DivisionByZero));
+        assert!(matches!(
+            calc.eval("foo"),
+            Err(RpnError::InvalidEntry { .. })
+        ));
+        assert!(matches!(
+            calc.eval("clst inf"),
+            Err(RpnError::NonFinite { .. })
+        ));
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        let mut calc = ComplexRPNCalculator::new();
+        calc.set_non_finite_policy(NonFinitePolicy::Error);
+        assert!(matches!(
+            calc.eval("1 0 /"),
+            Err(RpnError::NonFinite { .. })
+        ));
+
+        calc.set_non_finite_policy(NonFinitePolicy::Clamp);
+        let value = calc.eval("clst 1 0 /").unwrap();
+        assert!(value.is_finite());
+    }
+}
@@ -0,0 +1,164 @@
+// Entry points for fuzzing the parser (enabled by the `arbitrary` feature).
+//
+// A `ParserInput` is a command string, a set of variables and a sequence of
+// parser calls; `run` replays it against a fresh parser. Any input, however
+// malformed, must come back with values or errors, never a panic.
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{DSSParser, ParserVar};
+
+// Keeps a single input from running for too long
+const MAX_OPS: usize = 256;
+
+#[derive(Debug, Clone, Arbitrary)]
+pub enum ParserOp {
+    NextParam,
+    MakeString,
+    MakeInteger,
+    MakeDouble,
+    MakeComplex,
+    ParseAsBusName,
+    ParseAsVector(u8),
+    ParseAsMatrix(u8),
+    ParseAsSymMatrix(u8),
+    GetRemainder,
+    SetPosition(u16),
+    SetDelimiters(String),
+    SetAutoIncrement(bool),
+    SetCheckedMath(bool),
+    SetInfixMath(bool),
+}
+
+#[derive(Debug, Clone, Arbitrary)]
+pub struct ParserInput {
+    pub variables: Vec<(String, String)>,
+    pub command: String,
+    pub ops: Vec<ParserOp>,
+}
+
+pub fn run(input: &ParserInput) {
+    let mut vars = ParserVar::new();
+    for (name, value) in &input.variables {
+        vars.add(name, value);
+    }
+
+    let mut parser = DSSParser::new();
+    parser.set_vars(vars);
+    parser.set_cmd_string(&input.command);
+
+    for op in input.ops.iter().take(MAX_OPS) {
+        let result = match op {
+            ParserOp::NextParam => {
+                parser.next_param();
+                Ok(())
+            }
+            ParserOp::MakeString => {
+                parser.make_string();
+                Ok(())
+            }
+            ParserOp::MakeInteger => parser.make_integer().map(drop),
+            ParserOp::MakeDouble => parser.make_double().map(drop),
+            ParserOp::MakeComplex => parser.make_complex().map(drop),
+            ParserOp::ParseAsBusName => {
+                let token = parser.get_token().to_string();
+                parser.parse_as_bus_name(&token);
+                Ok(())
+            }
+            ParserOp::ParseAsVector(size) => {
+                parser.parse_as_vector(*size as usize);
+                Ok(())
+            }
+            ParserOp::ParseAsMatrix(order) => parser.parse_as_matrix(*order as usize).map(drop),
+            ParserOp::ParseAsSymMatrix(order) => {
+                parser.parse_as_sym_matrix(*order as usize).map(drop)
+            }
+            ParserOp::GetRemainder => {
+                parser.get_remainder();
+                Ok(())
+            }
+            ParserOp::SetPosition(pos) => {
+                parser.set_position(*pos as usize);
+                Ok(())
+            }
+            ParserOp::SetDelimiters(delims) => {
+                parser.set_delimiters(delims);
+                Ok(())
+            }
+            ParserOp::SetAutoIncrement(auto_inc) => {
+                parser.set_auto_increment(*auto_inc);
+                Ok(())
+            }
+            ParserOp::SetCheckedMath(checked) => {
+                parser.set_checked_math(*checked);
+                Ok(())
+            }
+            ParserOp::SetInfixMath(infix) => {
+                parser.set_infix_math(*infix);
+                Ok(())
+            }
+        };
+
+        // rendering must cope with whatever span the error carries
+        if let Err(err) = result {
+            parser.render_error(&err);
+        }
+    }
+    parser.take_warnings();
+}
+
+// Runs raw fuzzer bytes: structured input when they decode as one, otherwise
+// the bytes as a command string read parameter by parameter
+pub fn run_bytes(data: &[u8]) {
+    let mut unstructured = Unstructured::new(data);
+    if let Ok(input) = ParserInput::arbitrary(&mut unstructured) {
+        run(&input);
+    }
+
+    let command = String::from_utf8_lossy(data).into_owned();
+    let ops = [
+        ParserOp::NextParam,
+        ParserOp::MakeDouble,
+        ParserOp::MakeComplex,
+    ]
+    .iter()
+    .cycle()
+    .take(MAX_OPS)
+    .cloned()
+    .collect();
+    run(&ParserInput {
+        variables: Vec::new(),
+        command,
+        ops,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SplitMix64;
+
+    #[test]
+    fn test_random_inputs_do_not_panic() {
+        // seeded, so the inputs are the same on every run
+        let mut rng = SplitMix64::new(0x5eed);
+        let mut next = || rng.next_u64();
+
+        // bias the bytes towards characters the tokenizer treats specially
+        const SPECIAL: &[u8] = b"@=,.^|!/ \t\"'([{)]}01234.e-+*~";
+        for _ in 0..500 {
+            let len = (next() % 96) as usize;
+            let data: Vec<u8> = (0..len)
+                .map(|_| {
+                    let r = next();
+                    if r % 3 == 0 {
+                        SPECIAL[(r >> 8) as usize % SPECIAL.len()]
+                    } else {
+                        (r >> 16) as u8
+                    }
+                })
+                .collect();
+            run_bytes(&data);
+        }
+    }
+}
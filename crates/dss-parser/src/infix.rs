@@ -0,0 +1,352 @@
+// Infix front end for the RPN calculator.
+//
+// Converts expressions like "(3+4)*sqrt(2)" into RPN token streams ("3 4 + 2
+// sqrt *") with the shunting-yard algorithm, so every operation, constant and
+// user function of the calculator is available with conventional notation.
+//
+// Operators, lowest to highest precedence:
+//   ||   &&   < > <= >= ==   + -   * / %   unary - + !   ^ (right associative)
+// An identifier followed by "(" is a function call whose comma separated
+// arguments are pushed in order, e.g. "atan2(y, x)" or "hypot(r, x)". Any other
+// identifier is a calculator command taking no arguments (pi, sqrt3, rand...).
+
+use crate::rpn::RpnError;
+
+// Guards against variables whose definitions refer to each other
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug)]
+enum StackItem {
+    Op(Operator),
+    LParen,
+    Func(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    rpn: &'static str,
+    precedence: u8,
+    right_assoc: bool,
+}
+
+impl Operator {
+    fn binary(op: &str) -> Option<Self> {
+        let (rpn, precedence, right_assoc) = match op {
+            "||" => ("or", 1, false),
+            "&&" => ("and", 2, false),
+            "<" => ("<", 3, false),
+            ">" => (">", 3, false),
+            "<=" => ("<=", 3, false),
+            ">=" => (">=", 3, false),
+            "==" => ("==", 3, false),
+            "+" => ("+", 4, false),
+            "-" => ("-", 4, false),
+            "*" => ("*", 5, false),
+            "/" => ("/", 5, false),
+            "%" => ("mod", 5, false),
+            "^" => ("^", 7, true),
+            _ => return None,
+        };
+        Some(Operator {
+            rpn,
+            precedence,
+            right_assoc,
+        })
+    }
+
+    // Prefix operators; `None` in the outer option means not a prefix operator,
+    // `Some(None)` a prefix operator with no effect (unary plus)
+    fn unary(op: &str) -> Option<Option<Self>> {
+        let rpn = match op {
+            "-" => "neg",
+            "!" => "not",
+            "+" => return Some(None),
+            _ => return None,
+        };
+        Some(Some(Operator {
+            rpn,
+            precedence: 6,
+            right_assoc: true,
+        }))
+    }
+}
+
+fn syntax_error(message: &str, expr: &str) -> RpnError {
+    RpnError::InvalidExpression {
+        message: format!("{} in \"{}\"", message, expr.trim()),
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, RpnError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let ch = chars[pos];
+        if ch.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let start = pos;
+        if ch.is_ascii_digit()
+            || (ch == '.' && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            // exponent, e.g. 1.5e-3
+            if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+                let mut end = pos + 1;
+                if end < chars.len() && (chars[end] == '+' || chars[end] == '-') {
+                    end += 1;
+                }
+                if end < chars.len() && chars[end].is_ascii_digit() {
+                    pos = end;
+                    while pos < chars.len() && chars[pos].is_ascii_digit() {
+                        pos += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Number(chars[start..pos].iter().collect()));
+        } else if ch.is_alphabetic() || ch == '_' || ch == '@' {
+            pos += 1;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else {
+            let next = chars.get(pos + 1).copied();
+            let (token, len) = match (ch, next) {
+                ('(', _) => (Token::LParen, 1),
+                (')', _) => (Token::RParen, 1),
+                (',', _) => (Token::Comma, 1),
+                ('<', Some('=')) => (Token::Op("<="), 2),
+                ('>', Some('=')) => (Token::Op(">="), 2),
+                ('=', Some('=')) => (Token::Op("=="), 2),
+                ('&', Some('&')) => (Token::Op("&&"), 2),
+                ('|', Some('|')) => (Token::Op("||"), 2),
+                ('<', _) => (Token::Op("<"), 1),
+                ('>', _) => (Token::Op(">"), 1),
+                ('+', _) => (Token::Op("+"), 1),
+                ('-', _) => (Token::Op("-"), 1),
+                ('*', _) => (Token::Op("*"), 1),
+                ('/', _) => (Token::Op("/"), 1),
+                ('%', _) => (Token::Op("%"), 1),
+                ('^', _) => (Token::Op("^"), 1),
+                ('!', _) => (Token::Op("!"), 1),
+                _ => {
+                    return Err(syntax_error(
+                        &format!("Unexpected character '{}'", ch),
+                        expr,
+                    ));
+                }
+            };
+            tokens.push(token);
+            pos += len;
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn convert(
+    expr: &str,
+    expand: &mut dyn FnMut(&str) -> String,
+    depth: usize,
+    output: &mut Vec<String>,
+) -> Result<(), RpnError> {
+    if depth > MAX_EXPANSION_DEPTH {
+        return Err(syntax_error("Variables nested too deeply", expr));
+    }
+
+    let tokens = tokenize(expr)?;
+    let mut stack: Vec<StackItem> = Vec::new();
+    let mut expect_operand = true;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Number(number) => {
+                if !expect_operand {
+                    return Err(syntax_error(&format!("Unexpected number {}", number), expr));
+                }
+                output.push(number.clone());
+                expect_operand = false;
+            }
+            Token::Ident(name) => {
+                if !expect_operand {
+                    return Err(syntax_error(&format!("Unexpected name {}", name), expr));
+                }
+                if tokens.get(i + 1) == Some(&Token::LParen) {
+                    stack.push(StackItem::Func(name.clone()));
+                    expect_operand = true;
+                    continue;
+                }
+
+                let expansion = expand(name);
+                if expansion != *name {
+                    convert(&expansion, expand, depth + 1, output)?;
+                } else {
+                    output.push(name.clone());
+                }
+                expect_operand = false;
+            }
+            Token::Op(op) if expect_operand => match Operator::unary(op) {
+                Some(Some(operator)) => stack.push(StackItem::Op(operator)),
+                Some(None) => {}
+                None => {
+                    return Err(syntax_error(
+                        &format!("Missing operand before '{}'", op),
+                        expr,
+                    ));
+                }
+            },
+            Token::Op(op) => {
+                let Some(operator) = Operator::binary(op) else {
+                    return Err(syntax_error(&format!("Unexpected operator '{}'", op), expr));
+                };
+                while let Some(StackItem::Op(top)) = stack.last() {
+                    if top.precedence > operator.precedence
+                        || (top.precedence == operator.precedence && !operator.right_assoc)
+                    {
+                        output.push(top.rpn.to_string());
+                        stack.pop();
+                    } else {
+                        break;
+                    }
+                }
+                stack.push(StackItem::Op(operator));
+                expect_operand = true;
+            }
+            Token::LParen => {
+                stack.push(StackItem::LParen);
+                expect_operand = true;
+            }
+            Token::Comma | Token::RParen => {
+                loop {
+                    match stack.pop() {
+                        Some(StackItem::Op(op)) => output.push(op.rpn.to_string()),
+                        Some(StackItem::LParen) => break,
+                        _ => return Err(syntax_error("Mismatched parentheses", expr)),
+                    }
+                }
+                if *token == Token::Comma {
+                    // still inside the argument list
+                    stack.push(StackItem::LParen);
+                    expect_operand = true;
+                } else {
+                    if let Some(StackItem::Func(_)) = stack.last()
+                        && let Some(StackItem::Func(name)) = stack.pop()
+                    {
+                        output.push(name);
+                    }
+                    expect_operand = false;
+                }
+            }
+        }
+    }
+
+    if expect_operand && !tokens.is_empty() {
+        return Err(syntax_error("Incomplete expression", expr));
+    }
+
+    while let Some(item) = stack.pop() {
+        match item {
+            StackItem::Op(op) => output.push(op.rpn.to_string()),
+            _ => return Err(syntax_error("Mismatched parentheses", expr)),
+        }
+    }
+
+    Ok(())
+}
+
+// Converts an infix expression into an equivalent RPN expression
+pub fn infix_to_rpn(expr: &str) -> Result<String, RpnError> {
+    infix_to_rpn_with(expr, |name| name.to_string())
+}
+
+// Like `infix_to_rpn`, passing every name through `expand` first. A name that
+// expands to something else is parsed as a parenthesized infix sub-expression;
+// the parser uses this to substitute variables.
+pub fn infix_to_rpn_with(
+    expr: &str,
+    mut expand: impl FnMut(&str) -> String,
+) -> Result<String, RpnError> {
+    let mut output = Vec::new();
+    convert(expr, &mut expand, 0, &mut output)?;
+    Ok(output.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_eq!(infix_to_rpn("1 + 2 * 3").unwrap(), "1 2 3 * +");
+        assert_eq!(infix_to_rpn("(1 + 2) * 3").unwrap(), "1 2 + 3 *");
+        assert_eq!(infix_to_rpn("8 - 4 - 2").unwrap(), "8 4 - 2 -");
+        assert_eq!(infix_to_rpn("2 ^ 3 ^ 2").unwrap(), "2 3 2 ^ ^");
+        assert_eq!(infix_to_rpn("8760 % 24").unwrap(), "8760 24 mod");
+        assert_eq!(
+            infix_to_rpn("1 < 2 && 3 >= 2 || 0").unwrap(),
+            "1 2 < 3 2 >= and 0 or"
+        );
+    }
+
+    #[test]
+    fn test_unary_operators() {
+        assert_eq!(infix_to_rpn("-2 ^ 2").unwrap(), "2 2 ^ neg");
+        assert_eq!(infix_to_rpn("2 ^ -1").unwrap(), "2 1 neg ^");
+        assert_eq!(infix_to_rpn("3 * -(1 + 1)").unwrap(), "3 1 1 + neg *");
+        assert_eq!(infix_to_rpn("+4").unwrap(), "4");
+        assert_eq!(infix_to_rpn("!(1 > 2)").unwrap(), "1 2 > not");
+    }
+
+    #[test]
+    fn test_functions_and_constants() {
+        assert_eq!(infix_to_rpn("(3+4)*sqrt(2)").unwrap(), "3 4 + 2 sqrt *");
+        assert_eq!(infix_to_rpn("atan2(1, 2 * 3)").unwrap(), "1 2 3 * atan2");
+        assert_eq!(infix_to_rpn("12.47 / sqrt3").unwrap(), "12.47 sqrt3 /");
+        assert_eq!(infix_to_rpn("rand()").unwrap(), "rand");
+        assert_eq!(infix_to_rpn("1.5e-3 * 2E2").unwrap(), "1.5e-3 2E2 *");
+    }
+
+    #[test]
+    fn test_variable_expansion() {
+        let rpn = infix_to_rpn_with("@kv * 1000", |name| match name {
+            "@kv" => "12.47 / sqrt3".to_string(),
+            "@loop" => "@loop + 1".to_string(),
+            _ => name.to_string(),
+        })
+        .unwrap();
+        assert_eq!(rpn, "12.47 sqrt3 / 1000 *");
+
+        let rpn = infix_to_rpn_with("@x + 1", |name| match name {
+            "@x" => "@x + 1".to_string(),
+            _ => name.to_string(),
+        });
+        assert!(rpn.is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(infix_to_rpn("(1 + 2").is_err());
+        assert!(infix_to_rpn("1 + 2)").is_err());
+        assert!(infix_to_rpn("1 +").is_err());
+        assert!(infix_to_rpn("* 2").is_err());
+        assert!(infix_to_rpn("1 2").is_err());
+        assert!(infix_to_rpn("1 $ 2").is_err());
+    }
+}
@@ -1,31 +1,188 @@
+use dss_common::{Diagnostics, DssError, Warning, WarningKind, codes};
 use std::collections::HashMap;
 use std::fmt;
-use std::str::FromStr;
 
+mod complex_rpn;
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
+mod infix;
+mod render;
 mod rpn;
 
-pub use rpn::RPNCalculator;
+pub use complex_rpn::ComplexRPNCalculator;
+pub use infix::{infix_to_rpn, infix_to_rpn_with};
+pub use num_complex::Complex64;
+pub use render::render_error;
+pub use rpn::{
+    AngleMode, NonFinitePolicy, RPNCalculator, RpnError, RpnResult, SplitMix64, TraceEntry,
+};
+
+// Location of a token in the command string, as a half-open range of
+// character offsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
-// Custom error type for parser problems
-#[derive(Debug)]
-pub struct ParserError {
-    message: String,
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Errors reported by the parser. Everything tied to a token carries the span
+// of that token in the command string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParserError {
+    ConversionError {
+        token: String,
+        target_type: &'static str,
+        span: Span,
+    },
+    UnterminatedQuote {
+        token: String,
+        quote: char,
+        span: Span,
+    },
+    UnknownRpnToken {
+        token: String,
+        span: Span,
+    },
+    UnknownVariable {
+        name: String,
+        span: Span,
+    },
+    MatrixDimensionMismatch {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    InlineMath {
+        source: RpnError,
+        span: Span,
+    },
+    Custom {
+        message: String,
+    },
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+        match self {
+            ParserError::ConversionError {
+                token, target_type, ..
+            } => {
+                let kind = match *target_type {
+                    "integer" => "Integer",
+                    "double" => "Floating point",
+                    "complex" => "Complex",
+                    other => other,
+                };
+                write!(
+                    f,
+                    "{} number conversion error for string: \"{}\"",
+                    kind, token
+                )
+            }
+            ParserError::UnterminatedQuote { token, quote, .. } => write!(
+                f,
+                "Missing closing quote '{}' for string: \"{}\"",
+                quote, token
+            ),
+            ParserError::UnknownRpnToken { token, .. } => {
+                write!(f, "Invalid inline math entry: \"{}\"", token)
+            }
+            ParserError::UnknownVariable { name, .. } => {
+                write!(f, "Variable not found: \"{}\"", name)
+            }
+            ParserError::MatrixDimensionMismatch {
+                expected, found, ..
+            } => write!(
+                f,
+                "Matrix dimension mismatch: expected order {}, found {}",
+                expected, found
+            ),
+            ParserError::InlineMath { source, .. } => write!(f, "{}", source),
+            ParserError::Custom { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParserError::InlineMath { source, .. } => Some(source),
+            _ => None,
+        }
     }
 }
 
-impl std::error::Error for ParserError {}
+impl From<RpnError> for ParserError {
+    fn from(err: RpnError) -> Self {
+        ParserError::from_rpn(err, Span::default())
+    }
+}
 
 impl ParserError {
     pub fn new(message: &str) -> Self {
-        ParserError {
+        ParserError::Custom {
             message: message.to_string(),
         }
     }
+
+    // Classifies a calculator error: unknown entries are either unresolved
+    // variables or unknown operations
+    pub fn from_rpn(err: RpnError, span: Span) -> Self {
+        match err {
+            RpnError::InvalidEntry { token }
+                if token.starts_with(DSSParser::VARIABLE_DELIMITER) =>
+            {
+                ParserError::UnknownVariable { name: token, span }
+            }
+            RpnError::InvalidEntry { token } => ParserError::UnknownRpnToken { token, span },
+            source => ParserError::InlineMath { source, span },
+        }
+    }
+
+    // OpenDSS error number reported for this kind of problem
+    pub fn error_number(&self) -> i32 {
+        match self {
+            ParserError::ConversionError { .. } => codes::CONVERSION_ERROR,
+            ParserError::UnterminatedQuote { .. } => codes::UNTERMINATED_QUOTE,
+            ParserError::UnknownRpnToken { .. } => codes::UNKNOWN_RPN_TOKEN,
+            ParserError::UnknownVariable { .. } => codes::UNKNOWN_VARIABLE,
+            ParserError::MatrixDimensionMismatch { .. } => codes::MATRIX_DIMENSION_MISMATCH,
+            ParserError::InlineMath { .. } => codes::INLINE_MATH_ERROR,
+            ParserError::Custom { .. } => codes::PARSER_ERROR,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParserError::ConversionError { span, .. }
+            | ParserError::UnterminatedQuote { span, .. }
+            | ParserError::UnknownRpnToken { span, .. }
+            | ParserError::UnknownVariable { span, .. }
+            | ParserError::MatrixDimensionMismatch { span, .. }
+            | ParserError::InlineMath { span, .. } => Some(*span),
+            ParserError::Custom { .. } => None,
+        }
+    }
+}
+
+impl From<ParserError> for DssError {
+    fn from(err: ParserError) -> Self {
+        DssError::new(err.error_number(), &err.to_string())
+    }
 }
 
 // Parser Variables
@@ -101,6 +258,92 @@ impl ParserVar {
     pub fn num_variables(&self) -> usize {
         self.variables.len()
     }
+
+    // Export all variables (including intrinsics) as a flat JSON object
+    // `{"@name": "definition", ...}`. Keys come out sorted.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> String {
+        let map: serde_json::Map<String, serde_json::Value> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), serde_json::Value::String(value.clone())))
+            .collect();
+        serde_json::Value::Object(map).to_string()
+    }
+
+    // Build a variable set from a flat JSON object. Intrinsic variables are
+    // always present; names lacking the leading '@' get one, numbers and
+    // booleans are stored by their textual form. Values already wrapped in
+    // braces (as produced by `to_json`) are kept verbatim.
+    #[cfg(feature = "serde_json")]
+    pub fn from_json(json: &str) -> Result<Self, ParserError> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| ParserError::new(&format!("Invalid variable JSON: {}", e)))?;
+        let serde_json::Value::Object(map) = value else {
+            return Err(ParserError::new(
+                "Invalid variable JSON: expected an object of name/value pairs",
+            ));
+        };
+
+        let mut vars = ParserVar::new();
+        for (name, value) in map {
+            let name = if name.starts_with(DSSParser::VARIABLE_DELIMITER) {
+                name
+            } else {
+                format!("{}{}", DSSParser::VARIABLE_DELIMITER, name)
+            };
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                serde_json::Value::Null => "null".to_string(),
+                _ => {
+                    return Err(ParserError::new(&format!(
+                        "Invalid value for variable \"{}\": expected a string, number or boolean",
+                        name
+                    )));
+                }
+            };
+            if value.starts_with('{') && value.ends_with('}') {
+                vars.variables.insert(name, value);
+            } else {
+                vars.add(&name, &value);
+            }
+        }
+        Ok(vars)
+    }
+}
+
+impl Default for ParserVar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Substitutes a variable reference (`@name`, optionally followed by a `.node`
+// or `^` suffix that is kept) with the variable's value. Returns `None` if the
+// token does not name a known variable; otherwise the new token and whether the
+// definition was a braced expression that must be evaluated.
+fn substitute_var(vars: &mut ParserVar, token: &str) -> Option<(String, bool)> {
+    if token.len() <= 1 || !token.starts_with(DSSParser::VARIABLE_DELIMITER) {
+        return None;
+    }
+
+    // both delimiters are ASCII, so the split always falls on a char boundary
+    let delimiter_pos = token.find('^').or_else(|| token.find('.'));
+    let (variable_name, suffix) = token.split_at(delimiter_pos.unwrap_or(token.len()));
+    if !vars.lookup(variable_name) {
+        return None;
+    }
+
+    let var_value = vars.get_value();
+    match var_value
+        .strip_prefix('{')
+        .and_then(|value| value.strip_suffix('}'))
+    {
+        Some(inner_value) => Some((format!("{}{}", inner_value, suffix), true)),
+        None => Some((format!("{}{}", var_value, suffix), false)),
+    }
 }
 
 // Main DSS Parser
@@ -111,6 +354,8 @@ pub struct DSSParser {
     position: usize,
     parameter_buffer: String,
     token_buffer: String,
+    token_span: Span,
+    unterminated_quote: Option<char>,
     delim_chars: String,
     whitespace_chars: String,
     begin_quote_chars: String,
@@ -120,7 +365,12 @@ pub struct DSSParser {
     auto_increment: bool,
     convert_error: bool,
     is_quoted_string: bool,
+    infix_math: bool,
+    // angle unit every quoted expression starts in
+    angle_mode: AngleMode,
     rpn_calculator: RPNCalculator,
+    complex_calculator: ComplexRPNCalculator,
+    diagnostics: Diagnostics,
 }
 
 impl DSSParser {
@@ -134,36 +384,51 @@ impl DSSParser {
             position: 0,
             parameter_buffer: String::new(),
             token_buffer: String::new(),
+            token_span: Span::default(),
+            unterminated_quote: None,
             delim_chars: ",=".to_string(),
             whitespace_chars: " \t".to_string(),
             begin_quote_chars: "(\"'[{".to_string(),
-            end_quote_chars: ")}']".to_string(),
+            end_quote_chars: ")\"']}".to_string(),
             last_delimiter: ' ',
             matrix_row_terminator: '|',
             auto_increment: false,
             convert_error: false,
             is_quoted_string: false,
+            infix_math: false,
+            angle_mode: AngleMode::Degrees,
             rpn_calculator: RPNCalculator::new(),
+            complex_calculator: ComplexRPNCalculator::new(),
+            diagnostics: Diagnostics::new(),
         }
     }
 
-    // pub fn set_vars(&mut self, vars: ParserVar) {
-    //     self.parser_vars = Some(vars);
-    // }
+    pub fn set_vars(&mut self, vars: ParserVar) {
+        self.parser_vars = Some(vars);
+    }
+
+    pub fn get_vars(&self) -> Option<&ParserVar> {
+        self.parser_vars.as_ref()
+    }
+
+    pub fn get_vars_mut(&mut self) -> Option<&mut ParserVar> {
+        self.parser_vars.as_mut()
+    }
 
-    // pub fn set_cmd_string(&mut self, value: &str) {
-    //     self.cmd_buffer = format!("{} ", value); // add whitespace at end
-    //     self.position = 0;
-    //     self.skip_whitespace();
-    // }
+    pub fn set_cmd_string(&mut self, value: &str) {
+        self.cmd_buffer = format!("{} ", value); // add whitespace at end
+        self.position = 0;
+        self.diagnostics.clear();
+        self.skip_whitespace();
+    }
 
-    // pub fn reset_delims(&mut self) {
-    //     self.delim_chars = ",=".to_string();
-    //     self.whitespace_chars = " \t".to_string();
-    //     self.matrix_row_terminator = '|';
-    //     self.begin_quote_chars = "(\"'[{".to_string();
-    //     self.end_quote_chars = ")}']".to_string();
-    // }
+    pub fn reset_delims(&mut self) {
+        self.delim_chars = ",=".to_string();
+        self.whitespace_chars = " \t".to_string();
+        self.matrix_row_terminator = '|';
+        self.begin_quote_chars = "(\"'[{".to_string();
+        self.end_quote_chars = ")\"']}".to_string();
+    }
 
     fn is_whitespace(&self, ch: char) -> bool {
         self.whitespace_chars.contains(ch)
@@ -188,19 +453,25 @@ impl DSSParser {
         self.is_comment_char(ch, next_ch) || self.is_delim_char(ch) || self.is_whitespace(ch)
     }
 
-    fn get_token(&mut self) -> String {
+    fn parse_token(&mut self) -> String {
         let chars: Vec<char> = self.cmd_buffer.chars().collect();
 
         if self.position >= chars.len() {
+            self.token_span = Span::new(self.position, self.position);
+            self.unterminated_quote = None;
             return String::new();
         }
 
         self.is_quoted_string = false;
+        self.unterminated_quote = None;
         let ch = chars[self.position];
+        let token_start = self.position;
 
-        // Check for quotes
-        if let Some(quote_pos) = self.begin_quote_chars.find(ch) {
-            let end_quote = self.end_quote_chars.chars().nth(quote_pos).unwrap();
+        // Check for quotes. Quote characters are paired by position; a begin
+        // quote without a partner closes itself.
+        let quote_pos = self.begin_quote_chars.chars().position(|c| c == ch);
+        let token: String = if let Some(quote_pos) = quote_pos {
+            let end_quote = self.end_quote_chars.chars().nth(quote_pos).unwrap_or(ch);
             self.position += 1;
             let start = self.position;
 
@@ -211,33 +482,33 @@ impl DSSParser {
             let token = chars[start..self.position].iter().collect();
             if self.position < chars.len() {
                 self.position += 1; // skip end quote
+            } else {
+                self.unterminated_quote = Some(end_quote);
             }
             self.is_quoted_string = true;
-            return token;
-        }
-
-        // Parse regular token
-        let start = self.position;
-        let next_ch = if self.position + 1 < chars.len() {
-            Some(chars[self.position + 1])
+            token
         } else {
-            None
+            // Parse regular token
+            let start = self.position;
+            while self.position < chars.len()
+                && !self.is_delimiter(chars[self.position], chars.get(self.position + 1).copied())
+            {
+                self.position += 1;
+            }
+            chars[start..self.position].iter().collect()
         };
+        self.token_span = Span::new(token_start, self.position);
 
-        while self.position < chars.len() && !self.is_delimiter(chars[self.position], next_ch) {
-            self.position += 1;
-        }
-
-        let token: String = chars[start..self.position].iter().collect();
-
-        // Handle delimiter
+        // Handle delimiter: stop on a comment, otherwise consume one delimiter
+        // character together with any surrounding whitespace
         if self.position < chars.len() {
-            self.last_delimiter = chars[self.position];
-
-            if self.is_comment_char(chars[self.position], next_ch) {
+            if self.is_comment_char(chars[self.position], chars.get(self.position + 1).copied()) {
                 self.position = chars.len(); // Skip to end on comment
             } else {
-                if self.is_delim_char(chars[self.position]) {
+                self.last_delimiter = chars[self.position];
+                self.skip_whitespace();
+                if self.position < chars.len() && self.is_delim_char(chars[self.position]) {
+                    self.last_delimiter = chars[self.position];
                     self.position += 1;
                 }
                 self.skip_whitespace();
@@ -247,415 +518,1156 @@ impl DSSParser {
         token
     }
 
-    // fn check_for_var(&mut self, token: &mut String) -> bool {
-    //     if token.len() <= 1 || !token.starts_with('@') {
-    //         return false;
-    //     }
-
-    //     let original_token = token.clone();
-
-    //     // Find dot or caret position
-    //     let dot_pos = token.find('.').or_else(|| token.find('^'));
-
-    //     let var_name = if let Some(pos) = dot_pos {
-    //         &token[..pos]
-    //     } else {
-    //         token
-    //     };
-
-    //     if let Some(ref mut vars) = self.parser_vars {
-    //         if vars.lookup(var_name) {
-    //             let var_value = vars.get_value();
-
-    //             if var_value.starts_with('{') && var_value.ends_with('}') {
-    //                 let inner_value = &var_value[1..var_value.len() - 1];
-    //                 if let Some(pos) = dot_pos {
-    //                     *token = format!("{}{}", inner_value, &token[pos..]);
-    //                 } else {
-    //                     *token = inner_value.to_string();
-    //                 }
-    //                 self.is_quoted_string = true;
-    //             } else {
-    //                 if let Some(pos) = dot_pos {
-    //                     *token = format!("{}{}", var_value, &token[pos..]);
-    //                 } else {
-    //                     *token = var_value;
-    //                 }
-    //             }
-    //             return true;
-    //         }
-    //     }
-
-    //     *token = original_token;
-    //     false
-    // }
-
     fn check_for_var(&mut self) -> bool {
-        let original_token = self.token_buffer.clone();
-
-        if self.token_buffer.len() > 1 && self.token_buffer.starts_with(Self::VARIABLE_DELIMITER) {
-            let delimiter_pos = self
-                .token_buffer
-                .find('^')
-                .or_else(|| self.token_buffer.find('.'));
-
-            let variable_name = if let Some(pos) = delimiter_pos {
-                &self.token_buffer[..pos]
-            } else {
-                &self.token_buffer
-            };
-
-            if let Some(ref mut vars) = self.parser_vars {
-                if vars.lookup(variable_name) {
-                    let var_value = vars.get_value();
-
-                    if var_value.starts_with('{') && var_value.ends_with('}') {
-                        let inner_value = &var_value[1..var_value.len() - 1];
-                        self.token_buffer = if let Some(pos) = delimiter_pos {
-                            format!("{}{}", inner_value, &self.token_buffer[pos..])
-                        } else {
-                            inner_value.to_string()
-                        };
-                        self.is_quoted_string = true;
-                    } else {
-                        self.token_buffer = if let Some(pos) = delimiter_pos {
-                            format!("{}{}", var_value, &self.token_buffer[pos..])
-                        } else {
-                            var_value
-                        };
-                    }
-                }
+        if let Some(ref mut vars) = self.parser_vars
+            && let Some((value, is_expression)) = substitute_var(vars, &self.token_buffer)
+        {
+            self.token_buffer = value;
+            if is_expression {
+                self.is_quoted_string = true;
             }
+            return false;
         }
-
-        self.token_buffer == original_token
+        true
     }
 
     pub fn next_param(&mut self) -> String {
-        if self.position < self.cmd_buffer.len() {
+        if self.position < self.cmd_buffer.chars().count() {
             self.last_delimiter = ' ';
-            self.token_buffer = self.get_token();
+            self.token_buffer = self.parse_token();
 
             if self.last_delimiter == '=' {
                 self.parameter_buffer = self.token_buffer.clone();
-                self.token_buffer = self.get_token();
+                self.token_buffer = self.parse_token();
             } else {
                 self.parameter_buffer.clear();
             }
         } else {
             self.parameter_buffer.clear();
             self.token_buffer.clear();
+            self.token_span = Span::new(self.position, self.position);
+            self.unterminated_quote = None;
         }
 
         self.check_for_var();
         self.parameter_buffer.clone()
     }
 
-    // pub fn parse_as_bus_name(&mut self, param: &str) -> (String, Vec<i32>) {
-    //     self.token_buffer = param.to_string();
-
-    //     if self.auto_increment {
-    //         self.next_param();
-    //     }
-
-    //     let mut nodes = Vec::new();
-
-    //     if let Some(dot_pos) = self.token_buffer.find('.') {
-    //         let bus_name = self.token_buffer[..dot_pos].trim().to_string();
-    //         let node_part = &self.token_buffer[dot_pos + 1..];
-
-    //         for node_str in node_part.split('.') {
-    //             if let Ok(node) = node_str.parse::<i32>() {
-    //                 nodes.push(node);
-    //             } else {
-    //                 nodes.push(-1); // Error indicator
-    //             }
-    //         }
-
-    //         (bus_name, nodes)
-    //     } else {
-    //         (self.token_buffer.clone(), nodes)
-    //     }
-    // }
-
-    // pub fn parse_as_vector(&mut self, expected_size: usize) -> Vec<f64> {
-    //     if self.auto_increment {
-    //         self.next_param();
-    //     }
-
-    //     let mut vector = vec![0.0; expected_size];
-    //     let mut elements_found = 0;
-
-    //     let parse_buffer = format!("{} ", self.token_buffer);
-    //     let mut parse_pos = 0;
-    //     let chars: Vec<char> = parse_buffer.chars().collect();
-
-    //     while parse_pos < chars.len() {
-    //         // Skip whitespace
-    //         while parse_pos < chars.len() && chars[parse_pos].is_whitespace() {
-    //             parse_pos += 1;
-    //         }
-
-    //         if parse_pos >= chars.len() {
-    //             break;
-    //         }
-
-    //         // Get token
-    //         let start = parse_pos;
-    //         while parse_pos < chars.len()
-    //             && !chars[parse_pos].is_whitespace()
-    //             && chars[parse_pos] != self.matrix_row_terminator
-    //         {
-    //             parse_pos += 1;
-    //         }
-
-    //         if start == parse_pos {
-    //             break;
-    //         }
-
-    //         let token: String = chars[start..parse_pos].iter().collect();
-
-    //         if elements_found < expected_size {
-    //             if let Ok(value) = token.parse::<f64>() {
-    //                 vector[elements_found] = value;
-    //             }
-    //         }
-
-    //         elements_found += 1;
-
-    //         if parse_pos < chars.len() && chars[parse_pos] == self.matrix_row_terminator {
-    //             break;
-    //         }
-    //     }
-
-    //     vector
-    // }
-
-    // pub fn make_string(&mut self) -> String {
-    //     if self.auto_increment {
-    //         self.next_param();
-    //     }
-    //     self.token_buffer.clone()
-    // }
-
-    // pub fn make_integer(&mut self) -> Result<i32, ParserError> {
-    //     self.convert_error = false;
-
-    //     if self.auto_increment {
-    //         self.next_param();
-    //     }
-
-    //     if self.token_buffer.is_empty() {
-    //         return Ok(0);
-    //     }
-
-    //     if self.is_quoted_string {
-    //         let value = self.interpret_rpn_string()?;
-    //         return Ok(value.round() as i32);
-    //     }
-
-    //     // Try direct conversion
-    //     if let Ok(value) = self.token_buffer.parse::<i32>() {
-    //         return Ok(value);
-    //     }
-
-    //     // Try as float then round
-    //     if let Ok(value) = self.token_buffer.parse::<f64>() {
-    //         return Ok(value.round() as i32);
-    //     }
-
-    //     self.convert_error = true;
-    //     Err(ParserError::new(&format!(
-    //         "Integer number conversion error for string: \"{}\"",
-    //         self.token_buffer
-    //     )))
-    // }
-
-    // pub fn make_double(&mut self) -> Result<f64, ParserError> {
-    //     self.convert_error = false;
-
-    //     if self.auto_increment {
-    //         self.next_param();
-    //     }
-
-    //     if self.token_buffer.is_empty() {
-    //         return Ok(0.0);
-    //     }
-
-    //     if self.is_quoted_string {
-    //         return self.interpret_rpn_string();
-    //     }
-
-    //     match self.token_buffer.parse::<f64>() {
-    //         Ok(value) => Ok(value),
-    //         Err(_) => {
-    //             self.convert_error = true;
-    //             Err(ParserError::new(&format!(
-    //                 "Floating point number conversion error for string: \"{}\"",
-    //                 self.token_buffer
-    //             )))
-    //         }
-    //     }
-    // }
-
-    // fn interpret_rpn_string(&mut self) -> Result<f64, ParserError> {
-    //     let parse_buffer = format!("{} ", self.token_buffer);
-    //     let mut parse_pos = 0;
-    //     let chars: Vec<char> = parse_buffer.chars().collect();
-
-    //     while parse_pos < chars.len() {
-    //         // Skip whitespace
-    //         while parse_pos < chars.len() && chars[parse_pos].is_whitespace() {
-    //             parse_pos += 1;
-    //         }
-
-    //         if parse_pos >= chars.len() {
-    //             break;
-    //         }
-
-    //         // Get token
-    //         let start = parse_pos;
-    //         while parse_pos < chars.len() && !chars[parse_pos].is_whitespace() {
-    //             parse_pos += 1;
-    //         }
-
-    //         let token: String = chars[start..parse_pos].iter().collect();
-    //         self.process_rpn_command(&token)?;
-    //     }
-
-    //     Ok(self.rpn_calculator.get_x())
-    // }
-
-    // fn process_rpn_command(&mut self, token: &str) -> Result<(), ParserError> {
-    //     // Try to parse as number first
-    //     if let Ok(number) = token.parse::<f64>() {
-    //         self.rpn_calculator.set_x(number);
-    //         return Ok(());
-    //     }
-
-    //     // Process RPN commands
-    //     match token.to_lowercase().as_str() {
-    //         "+" => self.rpn_calculator.add(),
-    //         "-" => self.rpn_calculator.subtract(),
-    //         "*" => self.rpn_calculator.multiply(),
-    //         "/" => self.rpn_calculator.divide(),
-    //         "sqrt" => self.rpn_calculator.sqrt(),
-    //         "sqr" => self.rpn_calculator.square(),
-    //         "^" => self.rpn_calculator.y_to_the_x_power(),
-    //         "sin" => self.rpn_calculator.sin_deg(),
-    //         "cos" => self.rpn_calculator.cos_deg(),
-    //         "tan" => self.rpn_calculator.tan_deg(),
-    //         "asin" => self.rpn_calculator.asin_deg(),
-    //         "acos" => self.rpn_calculator.acos_deg(),
-    //         "atan" => self.rpn_calculator.atan_deg(),
-    //         "atan2" => self.rpn_calculator.atan2_deg(),
-    //         "swap" => self.rpn_calculator.swap_xy(),
-    //         "rollup" => self.rpn_calculator.roll_up(),
-    //         "rolldn" => self.rpn_calculator.roll_down(),
-    //         "ln" => self.rpn_calculator.natlog(),
-    //         "pi" => self.rpn_calculator.enter_pi(),
-    //         "log10" => self.rpn_calculator.ten_log(),
-    //         "exp" => self.rpn_calculator.etothex(),
-    //         "inv" => self.rpn_calculator.inv(),
-    //         _ => {
-    //             return Err(ParserError::new(&format!(
-    //                 "Invalid inline math entry: \"{}\"",
-    //                 token
-    //             )));
-    //         }
-    //     }
-
-    //     Ok(())
-    // }
-
-    // pub fn get_remainder(&self) -> String {
-    //     if self.position < self.cmd_buffer.len() {
-    //         self.cmd_buffer[self.position..].to_string()
-    //     } else {
-    //         String::new()
-    //     }
-    // }
+    pub fn parse_as_bus_name(&mut self, param: &str) -> (String, Vec<i32>) {
+        self.token_buffer = param.to_string();
+
+        if self.auto_increment {
+            self.next_param();
+        }
+
+        let mut nodes = Vec::new();
+
+        if let Some(dot_pos) = self.token_buffer.find('.') {
+            let bus_name = self.token_buffer[..dot_pos].trim().to_string();
+            let node_part = &self.token_buffer[dot_pos + 1..];
+
+            for node_str in node_part.split('.') {
+                if let Ok(node) = node_str.parse::<i32>() {
+                    nodes.push(node);
+                } else {
+                    self.diagnostics.warn(
+                        WarningKind::Syntax,
+                        &format!(
+                            "Invalid node number \"{}\" in bus name \"{}\"",
+                            node_str, bus_name
+                        ),
+                    );
+                    nodes.push(-1); // Error indicator
+                }
+            }
+
+            (bus_name, nodes)
+        } else {
+            (self.token_buffer.clone(), nodes)
+        }
+    }
+
+    pub fn parse_as_vector(&mut self, expected_size: usize) -> Vec<f64> {
+        if self.auto_increment {
+            self.next_param();
+        }
+
+        let mut vector = vec![0.0; expected_size];
+        let mut elements_found = 0;
+
+        let parse_buffer = format!("{} ", self.token_buffer);
+        let mut parse_pos = 0;
+        let chars: Vec<char> = parse_buffer.chars().collect();
+
+        while parse_pos < chars.len() {
+            // Skip whitespace
+            while parse_pos < chars.len() && chars[parse_pos].is_whitespace() {
+                parse_pos += 1;
+            }
+
+            if parse_pos >= chars.len() {
+                break;
+            }
+
+            // Get token
+            let start = parse_pos;
+            while parse_pos < chars.len()
+                && !chars[parse_pos].is_whitespace()
+                && chars[parse_pos] != self.matrix_row_terminator
+            {
+                parse_pos += 1;
+            }
+
+            if start == parse_pos {
+                break;
+            }
+
+            let token: String = chars[start..parse_pos].iter().collect();
+
+            if elements_found < expected_size {
+                match token.parse::<f64>() {
+                    Ok(value) => vector[elements_found] = value,
+                    Err(_) => self.diagnostics.warn(
+                        WarningKind::ValueIgnored,
+                        &format!("Invalid number \"{}\" in vector, using 0", token),
+                    ),
+                }
+            }
+
+            elements_found += 1;
+
+            if parse_pos < chars.len() && chars[parse_pos] == self.matrix_row_terminator {
+                break;
+            }
+        }
+
+        if elements_found > expected_size {
+            self.diagnostics.warn(
+                WarningKind::ValueIgnored,
+                &format!(
+                    "{} values given where {} expected, the rest is ignored",
+                    elements_found, expected_size
+                ),
+            );
+        }
+        vector
+    }
+
+    pub fn make_string(&mut self) -> String {
+        if self.auto_increment {
+            self.next_param();
+        }
+        if let Err(err) = self.check_quotes() {
+            self.diagnostics.warn(WarningKind::Syntax, &err.to_string());
+        }
+        self.token_buffer.clone()
+    }
+
+    pub fn make_integer(&mut self) -> Result<i32, ParserError> {
+        self.convert_error = false;
+
+        if self.auto_increment {
+            self.next_param();
+        }
+
+        self.check_quotes()?;
+        if self.token_buffer.is_empty() {
+            return Ok(0);
+        }
+
+        if self.is_quoted_string {
+            let value = self.interpret_rpn_string()?;
+            return Ok(value.round() as i32);
+        }
+
+        // Try direct conversion
+        if let Ok(value) = self.token_buffer.parse::<i32>() {
+            return Ok(value);
+        }
+
+        // Try as float then round
+        if let Ok(value) = self.token_buffer.parse::<f64>() {
+            return Ok(value.round() as i32);
+        }
+
+        Err(self.conversion_error("integer"))
+    }
+
+    pub fn make_double(&mut self) -> Result<f64, ParserError> {
+        self.convert_error = false;
+
+        if self.auto_increment {
+            self.next_param();
+        }
+
+        self.check_quotes()?;
+        if self.token_buffer.is_empty() {
+            return Ok(0.0);
+        }
+
+        if self.is_quoted_string {
+            return self.interpret_rpn_string();
+        }
+
+        self.token_buffer
+            .parse::<f64>()
+            .map_err(|_| self.conversion_error("double"))
+    }
+
+    // Reads a complex value. Quoted values are evaluated by the complex RPN
+    // calculator, e.g. "{0.1 0.3 rect}" or "{7.2 -30 polar}"; anything else
+    // must be a real number.
+    pub fn make_complex(&mut self) -> Result<Complex64, ParserError> {
+        self.convert_error = false;
+
+        if self.auto_increment {
+            self.next_param();
+        }
+
+        self.check_quotes()?;
+        if self.token_buffer.is_empty() {
+            return Ok(Complex64::new(0.0, 0.0));
+        }
+
+        if self.is_quoted_string {
+            self.complex_calculator.clear_stack();
+            self.complex_calculator.set_angle_mode(self.angle_mode);
+            let vars = &mut self.parser_vars;
+            let span = self.token_span;
+            let result = self
+                .complex_calculator
+                .eval_with(&self.token_buffer, |token| match vars {
+                    Some(vars) => substitute_var(vars, token)
+                        .map_or_else(|| token.to_string(), |(value, _)| value),
+                    None => token.to_string(),
+                })
+                .map_err(|err| ParserError::from_rpn(err, span));
+            let clamped = self.complex_calculator.take_clamped();
+            self.warn_clamped(clamped);
+            return result;
+        }
+
+        match self.token_buffer.parse::<f64>() {
+            Ok(value) => Ok(Complex64::new(value, 0.0)),
+            Err(_) => Err(self.conversion_error("complex")),
+        }
+    }
+
+    // Reads a square matrix of the given order with rows separated by the
+    // matrix row terminator, e.g. "[1 2 | 3 4]". Missing elements are zero.
+    // The result is in column order (Fortran order), as in OpenDSS.
+    pub fn parse_as_matrix(&mut self, expected_order: usize) -> Result<Vec<f64>, ParserError> {
+        let rows = self.parse_matrix_rows()?;
+        self.check_matrix_order(expected_order, &rows, |_| expected_order)?;
+
+        let mut matrix = vec![0.0; expected_order * expected_order];
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                matrix[i + j * expected_order] = *value;
+            }
+        }
+        Ok(matrix)
+    }
+
+    // Reads a symmetric matrix given by its lower triangle, e.g. "[1 | 2 3]";
+    // row i holds at most i elements
+    pub fn parse_as_sym_matrix(&mut self, expected_order: usize) -> Result<Vec<f64>, ParserError> {
+        let rows = self.parse_matrix_rows()?;
+        self.check_matrix_order(expected_order, &rows, |i| i + 1)?;
+
+        let mut matrix = vec![0.0; expected_order * expected_order];
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                matrix[i + j * expected_order] = *value;
+                matrix[j + i * expected_order] = *value;
+            }
+        }
+        Ok(matrix)
+    }
+
+    fn parse_matrix_rows(&mut self) -> Result<Vec<Vec<f64>>, ParserError> {
+        if self.auto_increment {
+            self.next_param();
+        }
+        self.check_quotes()?;
+
+        let mut rows = Vec::new();
+        for row in self.token_buffer.split(self.matrix_row_terminator) {
+            let mut values = Vec::new();
+            for element in row.split(|c: char| c.is_whitespace() || c == ',') {
+                if element.is_empty() {
+                    continue;
+                }
+                let value = element.parse::<f64>().map_err(|_| {
+                    self.convert_error = true;
+                    ParserError::ConversionError {
+                        token: element.to_string(),
+                        target_type: "double",
+                        span: self.token_span,
+                    }
+                })?;
+                values.push(value);
+            }
+            rows.push(values);
+        }
+
+        // a trailing row terminator does not start another row
+        if rows.len() > 1 && rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+        Ok(rows)
+    }
+
+    fn check_matrix_order(
+        &self,
+        expected_order: usize,
+        rows: &[Vec<f64>],
+        row_capacity: impl Fn(usize) -> usize,
+    ) -> Result<(), ParserError> {
+        let widest_row = rows
+            .iter()
+            .enumerate()
+            .filter(|(i, row)| row.len() > row_capacity(*i))
+            .map(|(_, row)| row.len())
+            .max();
+
+        let found = match widest_row {
+            Some(width) => width.max(rows.len()),
+            None if rows.len() > expected_order => rows.len(),
+            None => return Ok(()),
+        };
+        Err(ParserError::MatrixDimensionMismatch {
+            expected: expected_order,
+            found,
+            span: self.token_span,
+        })
+    }
+
+    // A numeric value cannot be read from a quoted string that never ends
+    fn check_quotes(&self) -> Result<(), ParserError> {
+        match self.unterminated_quote {
+            Some(quote) => Err(ParserError::UnterminatedQuote {
+                token: self.token_buffer.clone(),
+                quote,
+                span: self.token_span,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Error for a token that is not a number. A token still starting with '@'
+    // after substitution names no variable.
+    fn conversion_error(&mut self, target_type: &'static str) -> ParserError {
+        self.convert_error = true;
+        if self.token_buffer.len() > 1 && self.token_buffer.starts_with(Self::VARIABLE_DELIMITER) {
+            ParserError::UnknownVariable {
+                name: self.token_buffer.clone(),
+                span: self.token_span,
+            }
+        } else {
+            ParserError::ConversionError {
+                token: self.token_buffer.clone(),
+                target_type,
+                span: self.token_span,
+            }
+        }
+    }
+
+    // Every quoted value starts from an empty stack in the parser's angle
+    // mode, so a long script does not pile up what earlier expressions left
+    // behind and a rad in one value does not change the next. Registers
+    // carry over, for sto in one value and rcl in a later one.
+    fn interpret_rpn_string(&mut self) -> Result<f64, ParserError> {
+        self.rpn_calculator.clear_stack();
+        self.rpn_calculator.set_angle_mode(self.angle_mode);
+        let vars = &mut self.parser_vars;
+        let expand = |token: &str| match vars {
+            Some(vars) => {
+                substitute_var(vars, token).map_or_else(|| token.to_string(), |(value, _)| value)
+            }
+            None => token.to_string(),
+        };
+
+        let result = if self.infix_math {
+            self.rpn_calculator
+                .eval_infix_with(&self.token_buffer, expand)
+        } else {
+            self.rpn_calculator.eval_with(&self.token_buffer, expand)
+        };
+        let clamped = self.rpn_calculator.take_clamped();
+        self.warn_clamped(clamped);
+        result.map_err(|err| ParserError::from_rpn(err, self.token_span))
+    }
+
+    // The Clamp policy keeps going with a finite value, but the script
+    // should still hear that its math overflowed
+    fn warn_clamped(&mut self, tokens: Vec<String>) {
+        for token in tokens {
+            self.diagnostics.push(
+                Warning::new(
+                    WarningKind::ValueClamped,
+                    &format!("\"{}\" gave a non-finite value, clamped", token),
+                )
+                .with_context(&self.token_buffer),
+            );
+        }
+    }
+
+    pub fn get_remainder(&self) -> String {
+        self.cmd_buffer.chars().skip(self.position).collect()
+    }
 
     // Getters and setters
-    // pub fn get_token(&self) -> &str {
-    //     &self.token_buffer
-    // }
+    pub fn get_token(&self) -> &str {
+        &self.token_buffer
+    }
+
+    pub fn set_token(&mut self, token: &str) {
+        self.token_buffer = token.to_string();
+        self.token_span = Span::new(0, token.chars().count());
+        self.unterminated_quote = None;
+    }
+
+    // Location of the current token in the command string
+    pub fn get_token_span(&self) -> Span {
+        self.token_span
+    }
+
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, pos: usize) {
+        self.position = pos;
+    }
+
+    pub fn get_delimiters(&self) -> &str {
+        &self.delim_chars
+    }
+
+    pub fn set_delimiters(&mut self, delims: &str) {
+        self.delim_chars = delims.to_string();
+    }
+
+    pub fn get_auto_increment(&self) -> bool {
+        self.auto_increment
+    }
+
+    pub fn set_auto_increment(&mut self, auto_inc: bool) {
+        self.auto_increment = auto_inc;
+    }
+
+    // Renders an error raised while parsing the current command string with
+    // the offending token underlined (see `render_error`)
+    pub fn render_error(&self, err: &ParserError) -> String {
+        let source = self
+            .cmd_buffer
+            .strip_suffix(' ')
+            .unwrap_or(&self.cmd_buffer);
+        render_error(source, err)
+    }
+
+    // Warnings raised since the last command string was set
+    pub fn get_diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.diagnostics.take()
+    }
+
+    // When enabled, invalid inline math (division by zero, sqrt of a negative
+    // number, too few operands) is reported as an error instead of yielding NaN/inf.
+    pub fn get_checked_math(&self) -> bool {
+        self.rpn_calculator.is_checked()
+    }
+
+    pub fn set_checked_math(&mut self, checked: bool) {
+        self.rpn_calculator.set_checked(checked);
+        self.complex_calculator.set_checked(checked);
+    }
+
+    // Quoted numeric values are RPN expressions by default, as in OpenDSS.
+    // With infix math enabled they are read as conventional expressions
+    // instead, e.g. "{(3+4)*sqrt(2)}".
+    pub fn get_infix_math(&self) -> bool {
+        self.infix_math
+    }
 
-    // pub fn set_token(&mut self, token: &str) {
-    //     self.token_buffer = token.to_string();
-    // }
+    pub fn set_infix_math(&mut self, infix: bool) {
+        self.infix_math = infix;
+    }
+
+    // Angle unit of the trig functions in quoted expressions, degrees by
+    // default. A rad or deg inside an expression lasts to its end only.
+    pub fn get_angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+    }
 
-    // pub fn get_position(&self) -> usize {
-    //     self.position
-    // }
+    // Decides whether NaN/inf results of inline math reach the caller, raise
+    // an error or get clamped to finite values
+    pub fn get_non_finite_policy(&self) -> NonFinitePolicy {
+        self.rpn_calculator.get_non_finite_policy()
+    }
 
-    // pub fn set_position(&mut self, pos: usize) {
-    //     self.position = pos;
-    // }
+    pub fn set_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.rpn_calculator.set_non_finite_policy(policy);
+        self.complex_calculator.set_non_finite_policy(policy);
+    }
 
-    // pub fn get_delimiters(&self) -> &str {
-    //     &self.delim_chars
-    // }
+    // Records the steps of every quoted expression evaluated; `get_rpn_trace`
+    // returns the steps of the most recent one
+    pub fn set_rpn_trace(&mut self, tracing: bool) {
+        self.rpn_calculator.set_tracing(tracing);
+    }
 
-    // pub fn set_delimiters(&mut self, delims: &str) {
-    //     self.delim_chars = delims.to_string();
-    // }
+    pub fn get_rpn_trace(&self) -> &[TraceEntry] {
+        self.rpn_calculator.trace()
+    }
 
-    // pub fn get_auto_increment(&self) -> bool {
-    //     self.auto_increment
-    // }
+    // Makes an application-defined function available in every quoted
+    // expression parsed from now on (see `RPNCalculator::register_fn`)
+    pub fn register_rpn_fn(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) {
+        self.rpn_calculator.register_fn(name, arity, func);
+    }
+}
 
-    // pub fn set_auto_increment(&mut self, auto_inc: bool) {
-    //     self.auto_increment = auto_inc;
-    // }
+impl Default for DSSParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     #[test]
-//     fn test_basic_parsing() {
-//         let mut parser = DSSParser::new();
-//         parser.set_cmd_string("param1=value1 param2=value2");
-
-//         let param1 = parser.next_param();
-//         assert_eq!(param1, "param1");
-//         assert_eq!(parser.get_token(), "value1");
-
-//         let param2 = parser.next_param();
-//         assert_eq!(param2, "param2");
-//         assert_eq!(parser.get_token(), "value2");
-//     }
-
-//     #[test]
-//     fn test_rpn_calculator() {
-//         let mut calc = RPNCalculator::new();
-//         calc.set_x(5.0);
-//         calc.set_x(3.0);
-//         calc.add();
-//         assert_eq!(calc.get_x(), 8.0);
-//     }
-
-//     #[test]
-//     fn test_variable_parsing() {
-//         let mut vars = ParserVar::new();
-//         vars.add("@myvar", "42");
-
-//         let mut parser = DSSParser::new();
-//         parser.set_vars(vars);
-//         parser.set_cmd_string("@myvar");
-
-//         parser.next_param();
-//         let result = parser.make_integer().unwrap();
-//         assert_eq!(result, 42);
-//     }
-
-//     #[test]
-//     fn test_bus_name_parsing() {
-//         let mut parser = DSSParser::new();
-//         let (bus_name, nodes) = parser.parse_as_bus_name("Bus1.1.2.3");
-//         assert_eq!(bus_name, "Bus1");
-//         assert_eq!(nodes, vec![1, 2, 3]);
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_parsing() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("param1=value1 param2=value2");
+
+        let param1 = parser.next_param();
+        assert_eq!(param1, "param1");
+        assert_eq!(parser.get_token(), "value1");
+
+        let param2 = parser.next_param();
+        assert_eq!(param2, "param2");
+        assert_eq!(parser.get_token(), "value2");
+    }
+
+    #[test]
+    fn test_rpn_calculator() {
+        let mut calc = RPNCalculator::new();
+        calc.set_x(5.0);
+        calc.set_x(3.0);
+        calc.add().unwrap();
+        assert_eq!(calc.get_x(), 8.0);
+    }
+
+    #[test]
+    fn test_variable_parsing() {
+        let mut vars = ParserVar::new();
+        vars.add("@myvar", "42");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser.set_cmd_string("@myvar");
+
+        parser.next_param();
+        let result = parser.make_integer().unwrap();
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn test_bus_name_parsing() {
+        let mut parser = DSSParser::new();
+        let (bus_name, nodes) = parser.parse_as_bus_name("Bus1.1.2.3");
+        assert_eq!(bus_name, "Bus1");
+        assert_eq!(nodes, vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_variables_json_round_trip() {
+        let mut vars = ParserVar::new();
+        vars.add("@kv", "12.47");
+        vars.add("@base", "@kv 1000 *");
+
+        let restored = ParserVar::from_json(&vars.to_json()).unwrap();
+        assert_eq!(restored.num_variables(), vars.num_variables());
+        assert_eq!(restored.get_var_string("@kv"), "@kv. 12.47");
+        assert_eq!(restored.get_var_string("@base"), "@base. {@kv 1000 *}");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_variables_from_json_injection() {
+        let vars = ParserVar::from_json(r#"{"kw": 25, "@name": "feeder1"}"#).unwrap();
+        assert_eq!(vars.get_var_string("@kw"), "@kw. 25");
+        assert_eq!(vars.get_var_string("@name"), "@name. feeder1");
+        assert_eq!(vars.get_var_string("@result"), "@result. null");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser.set_cmd_string("kw=@kw");
+        assert_eq!(parser.next_param(), "kw");
+        assert_eq!(parser.make_double().unwrap(), 25.0);
+
+        assert!(ParserVar::from_json("[1, 2]").is_err());
+        assert!(ParserVar::from_json(r#"{"@x": [1]}"#).is_err());
+        assert!(ParserVar::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_checked_math_errors() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("x=(1 0 /)");
+        parser.next_param();
+        assert!(parser.make_double().unwrap().is_infinite());
+
+        parser.set_checked_math(true);
+        parser.set_cmd_string("x=(-4 sqrt)");
+        parser.next_param();
+        let err = parser.make_double().unwrap_err();
+        assert!(err.to_string().contains("sqrt"));
+
+        parser.set_cmd_string("x=(inf 2 /)");
+        parser.next_param();
+        let err = parser.make_double().unwrap_err();
+        assert_eq!(err.error_number(), codes::INLINE_MATH_ERROR);
+
+        parser.set_cmd_string("x=(2 3 + sqr)");
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 25.0);
+    }
+
+    #[test]
+    fn test_rpn_rounding_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a={-1 abs} b={8760 24 mod} c={2.6 round neg}");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 1.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 0.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), -3.0);
+    }
+
+    #[test]
+    fn test_rpn_min_max_hypot_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("z=(3 4 hypot) lo=(1 2 min) hi=(1 2 max)");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 5.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 1.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_rpn_hyperbolic_and_log_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a=(0 cosh) b=(1024 log2) c=(2 exp10) d=(0.5 tanh atanh)");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 1.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 10.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 100.0);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rpn_register_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a=(3 sqrt sto s3 12.47 rcl s3 /) b=(rcl S3 sqr) c=(2 sto)");
+
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 12.47 / 3f64.sqrt()).abs() < 1e-12);
+        // registers persist across expressions
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 3.0).abs() < 1e-12);
+        parser.next_param();
+        assert!(parser.make_double().is_err());
+    }
+
+    #[test]
+    fn test_rpn_stack_bounded_across_expressions() {
+        let mut parser = DSSParser::new();
+        for _ in 0..1000 {
+            parser.set_cmd_string("a=(1 2 +) b={1 2 +}");
+            parser.next_param();
+            assert_eq!(parser.make_double().unwrap(), 3.0);
+            parser.next_param();
+            assert_eq!(parser.make_complex().unwrap(), Complex64::new(3.0, 0.0));
+        }
+        assert!(parser.rpn_calculator.stack().len() < 20);
+        assert!(parser.complex_calculator.stack().len() < 20);
+    }
+
+    #[test]
+    fn test_rpn_lastx_and_clear_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a=(7 2 * lastx +) b=(5 6 clx +) c=(1 2 3 clst)");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 16.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 5.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_rpn_comparison_commands() {
+        let mut vars = ParserVar::new();
+        vars.add("@v", "1.062");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser
+            .set_cmd_string("high={@v 1.05 >} ok={@v 0.95 >= @v 1.05 <= and} low={@v 0.95 < not}");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 1.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 0.0);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_rpn_random_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a=(5 seed rand) b=(rand 100 *) c=(5 seed rand)");
+
+        parser.next_param();
+        let a = parser.make_double().unwrap();
+        assert!((0.0..1.0).contains(&a));
+        parser.next_param();
+        let b = parser.make_double().unwrap();
+        assert!((0.0..100.0).contains(&b));
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), a);
+    }
+
+    #[test]
+    fn test_rpn_constant_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("kv=(12.47 sqrt3 /) a=(e ln) b=(sqrt2 sqr)");
+
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 7.199557).abs() < 1e-6);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rpn_user_function() {
+        let mut parser = DSSParser::new();
+        parser.register_rpn_fn("c2f", 1, |args| args[0] * 9.0 / 5.0 + 32.0);
+        parser.set_cmd_string("temp=(25 c2f)");
+
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 77.0);
+    }
+
+    #[test]
+    fn test_infix_math_option() {
+        let mut vars = ParserVar::new();
+        vars.add("@kv", "12.47");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser.set_infix_math(true);
+        parser.set_cmd_string("a={(3+4)*sqrt(2)} b={@kv / sqrt3 * 1000} c=5");
+
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 7.0 * 2f64.sqrt()).abs() < 1e-12);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 7199.557).abs() < 1e-3);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 5.0);
+
+        // RPN remains the default
+        parser.set_infix_math(false);
+        parser.set_cmd_string("a={3 4 +}");
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 7.0);
+    }
+
+    #[test]
+    fn test_make_complex() {
+        let mut vars = ParserVar::new();
+        vars.add("@vln", "7.2");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser.set_cmd_string("z1=(0.1 0.3 rect) v=(@vln -120 polar) r=5 bad=x");
+
+        parser.next_param();
+        let z1 = parser.make_complex().unwrap();
+        assert!((z1 - Complex64::new(0.1, 0.3)).norm() < 1e-12);
+
+        parser.next_param();
+        let v = parser.make_complex().unwrap();
+        assert!((v.norm() - 7.2).abs() < 1e-12);
+        assert!((v.arg().to_degrees() + 120.0).abs() < 1e-9);
+
+        parser.next_param();
+        assert_eq!(parser.make_complex().unwrap(), Complex64::new(5.0, 0.0));
+
+        parser.next_param();
+        assert!(parser.make_complex().is_err());
+    }
+
+    #[test]
+    fn test_rpn_trace() {
+        let mut vars = ParserVar::new();
+        vars.add("@kv", "12.47");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        parser.set_rpn_trace(true);
+        parser.set_cmd_string("kv=(@kv sqrt3 /)");
+        parser.next_param();
+        parser.make_double().unwrap();
+
+        let tokens: Vec<&str> = parser
+            .get_rpn_trace()
+            .iter()
+            .map(|e| e.token.as_str())
+            .collect();
+        assert_eq!(tokens, ["12.47", "sqrt3", "/"]);
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        let mut parser = DSSParser::new();
+        parser.set_non_finite_policy(NonFinitePolicy::Error);
+        parser.set_cmd_string("a=(1 0 /) b=(1000 exp)");
+
+        parser.next_param();
+        assert!(parser.make_double().is_err());
+
+        parser.set_non_finite_policy(NonFinitePolicy::Clamp);
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), f64::MAX);
+        let warnings = parser.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::ValueClamped);
+        assert_eq!(
+            warnings[0].to_string(),
+            "Warning: \"exp\" gave a non-finite value, clamped (1000 exp)"
+        );
+
+        parser.set_cmd_string("z={1 0 /} x=(2 3 *)");
+        parser.next_param();
+        parser.make_complex().unwrap();
+        parser.next_param();
+        parser.make_double().unwrap();
+        let warnings = parser.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].context.as_deref(), Some("1 0 /"));
+    }
+
+    #[test]
+    fn test_conversion_error_kinds() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("kv=abc");
+        parser.next_param();
+        let err = parser.make_double().unwrap_err();
+        assert_eq!(
+            err,
+            ParserError::ConversionError {
+                token: "abc".to_string(),
+                target_type: "double",
+                span: Span::new(3, 6),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Floating point number conversion error for string: \"abc\""
+        );
+        assert!(matches!(
+            parser.make_integer(),
+            Err(ParserError::ConversionError {
+                target_type: "integer",
+                ..
+            })
+        ));
+
+        parser.set_vars(ParserVar::new());
+        parser.set_cmd_string("phases=@nphases");
+        parser.next_param();
+        assert_eq!(
+            parser.make_integer().unwrap_err(),
+            ParserError::UnknownVariable {
+                name: "@nphases".to_string(),
+                span: Span::new(7, 15),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unterminated_quote_error() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("kw=(2 3 +");
+        parser.next_param();
+        let err = parser.make_double().unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::UnterminatedQuote { quote: ')', .. }
+        ));
+        assert_eq!(err.span(), Some(Span::new(3, 10)));
+
+        parser.set_cmd_string("kw=(2 3 +)");
+        parser.next_param();
+        assert_eq!(parser.make_double().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_inline_math_error_kinds() {
+        use std::error::Error;
+
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("x=1 y=(1 foo +)");
+        parser.next_param();
+        parser.next_param();
+        assert_eq!(
+            parser.make_double().unwrap_err(),
+            ParserError::UnknownRpnToken {
+                token: "foo".to_string(),
+                span: Span::new(6, 15),
+            }
+        );
+
+        parser.set_cmd_string("y=(@undefined 2 *)");
+        parser.next_param();
+        assert!(matches!(
+            parser.make_double(),
+            Err(ParserError::UnknownVariable { name, .. }) if name == "@undefined"
+        ));
+
+        parser.set_checked_math(true);
+        parser.set_cmd_string("y=(1 0 /)");
+        parser.next_param();
+        let err = parser.make_double().unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::InlineMath {
+                source: RpnError::DivisionByZero,
+                ..
+            }
+        ));
+        assert!(err.source().is_some());
+        assert_eq!(err.to_string(), RpnError::DivisionByZero.to_string());
+
+        assert_eq!(ParserError::new("custom").span(), None);
+    }
+
+    #[test]
+    fn test_parse_as_matrix() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("rmatrix=[1 2 | 3 4]");
+        parser.next_param();
+        assert_eq!(parser.parse_as_matrix(2).unwrap(), vec![1.0, 3.0, 2.0, 4.0]);
+
+        parser.set_cmd_string("rmatrix=[1 | 2 3 |]");
+        parser.next_param();
+        assert_eq!(
+            parser.parse_as_sym_matrix(2).unwrap(),
+            vec![1.0, 2.0, 2.0, 3.0]
+        );
+
+        // missing elements are zero
+        parser.set_cmd_string("rmatrix=[1 | 2]");
+        parser.next_param();
+        assert_eq!(parser.parse_as_matrix(2).unwrap(), vec![1.0, 2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_matrix_dimension_mismatch() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("rmatrix=[1 2 3 | 4 5 6]");
+        parser.next_param();
+        let err = parser.parse_as_matrix(2).unwrap_err();
+        assert_eq!(
+            err,
+            ParserError::MatrixDimensionMismatch {
+                expected: 2,
+                found: 3,
+                span: Span::new(8, 23),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Matrix dimension mismatch: expected order 2, found 3"
+        );
+
+        parser.set_cmd_string("xmatrix=[1 | 2 3 | 4 5 6]");
+        parser.next_param();
+        assert!(matches!(
+            parser.parse_as_sym_matrix(2),
+            Err(ParserError::MatrixDimensionMismatch { found: 3, .. })
+        ));
+
+        // the lower triangle may not hold elements above the diagonal
+        parser.set_cmd_string("xmatrix=[1 2 | 3 4]");
+        parser.next_param();
+        assert!(parser.parse_as_sym_matrix(2).is_err());
+
+        parser.set_cmd_string("xmatrix=[1 x | 3 4]");
+        parser.next_param();
+        assert!(matches!(
+            parser.parse_as_matrix(2),
+            Err(ParserError::ConversionError { token, .. }) if token == "x"
+        ));
+    }
+
+    #[test]
+    fn test_dss_error_numbers() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("kv=abc");
+        parser.next_param();
+        let err = DssError::from(parser.make_double().unwrap_err());
+        assert_eq!(err.number(), codes::CONVERSION_ERROR);
+        assert_eq!(
+            err.message(),
+            "Floating point number conversion error for string: \"abc\""
+        );
+
+        parser.set_cmd_string("kv=(1 bar)");
+        parser.next_param();
+        let err: DssError = parser.make_double().unwrap_err().into();
+        assert_eq!(err.number(), codes::UNKNOWN_RPN_TOKEN);
+        assert_eq!(ParserError::new("x").error_number(), codes::PARSER_ERROR);
+    }
+
+    #[test]
+    fn test_parser_warnings() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("mult=[1 2 x 4] bus1=b1.1.a name=\"unfinished");
+        parser.next_param();
+        assert_eq!(parser.parse_as_vector(2), vec![1.0, 2.0]);
+        let warnings = parser.get_diagnostics().warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::ValueIgnored);
+        assert!(
+            warnings[0]
+                .message
+                .contains("4 values given where 2 expected")
+        );
+
+        parser.next_param();
+        let bus = parser.get_token().to_string();
+        assert_eq!(
+            parser.parse_as_bus_name(&bus),
+            ("b1".to_string(), vec![1, -1])
+        );
+        parser.next_param();
+        assert_eq!(parser.make_string(), "unfinished ");
+
+        let warnings = parser.take_warnings();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[1].kind, WarningKind::Syntax);
+        assert!(warnings[2].message.contains("Missing closing quote"));
+        assert!(parser.get_diagnostics().is_empty());
+
+        // each command string starts with a clean slate
+        parser.set_cmd_string("mult=[1 2 3]");
+        parser.next_param();
+        parser.parse_as_vector(2);
+        assert_eq!(parser.get_diagnostics().len(), 1);
+        parser.set_cmd_string("mult=[1 2]");
+        assert!(parser.get_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_non_ascii_input_does_not_panic() {
+        let mut vars = ParserVar::new();
+        vars.add("@é", "{ü 2 *}");
+        vars.add("@ß", "ä.ö^");
+
+        let mut parser = DSSParser::new();
+        parser.set_vars(vars);
+        for cmd in [
+            "é=ü ß=@é bus=@ß.1.ü @é^",
+            "ä=(√2 sqr) ö=[1 ü | ∞] '«»'",
+            "@ {é} (ü\u{2028}",
+            "@é.é^ü=@",
+        ] {
+            parser.set_cmd_string(cmd);
+            for _ in 0..8 {
+                parser.next_param();
+                let token = parser.get_token().to_string();
+                parser.parse_as_bus_name(&token);
+                let _ = parser.make_double();
+                let _ = parser.make_complex();
+                let _ = parser.parse_as_sym_matrix(2);
+            }
+            parser.set_position(cmd.len() * 2);
+            assert_eq!(parser.next_param(), "");
+        }
+    }
+
+    #[test]
+    fn test_rpn_angle_mode_commands() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a=(rad pi 2 / sin) b=(90 sin) c=(deg 90 sin)");
+
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+        // rad ended with the expression it was in
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+
+        parser.set_angle_mode(AngleMode::Radians);
+        parser.set_cmd_string("a=(90 sin) b=(deg 90 sin) c=(pi 2 / sin) d={1 90 polar}");
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 90f64.sin()).abs() < 1e-12);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+        parser.next_param();
+        assert!((parser.make_double().unwrap() - 1.0).abs() < 1e-12);
+        parser.next_param();
+        let z = parser.make_complex().unwrap();
+        assert!((z - Complex64::from_polar(1.0, 90.0)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_delimiters_around_quotes_and_spaces() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("a = 1 b=(2 3 +), c='x y' d=4 // comment");
+
+        assert_eq!(parser.next_param(), "a");
+        assert_eq!(parser.get_token(), "1");
+        assert_eq!(parser.next_param(), "b");
+        assert_eq!(parser.get_token(), "2 3 +");
+        assert_eq!(parser.next_param(), "c");
+        assert_eq!(parser.get_token(), "x y");
+        assert_eq!(parser.next_param(), "d");
+        assert_eq!(parser.get_token(), "4");
+        parser.next_param();
+        assert_eq!(parser.get_token(), "");
+    }
+}
@@ -0,0 +1,108 @@
+// Renders parser errors against the command they came from:
+//
+//   error: Floating point number conversion error for string: "abc"
+//     |
+//   1 | new load.l1 kv=abc
+//     |                ^^^
+
+use crate::{ParserError, Span};
+
+// Formats `err` with the source line containing its span underlined. Errors
+// without a span (or with a span outside `source`) render as the message alone.
+pub fn render_error(source: &str, err: &ParserError) -> String {
+    let mut out = format!("error: {}", err);
+    if let Some(span) = err.span()
+        && let Some(excerpt) = render_span(source, span)
+    {
+        out.push('\n');
+        out.push_str(&excerpt);
+    }
+    out
+}
+
+fn render_span(source: &str, span: Span) -> Option<String> {
+    let mut line_start = 0;
+    for (index, line) in source.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let line_len = line.chars().count();
+        if span.start <= line_start + line_len {
+            let column = span.start - line_start;
+            let width = span.len().min(line_len.saturating_sub(column)).max(1);
+
+            // keep tabs so the caret lines up with the excerpt
+            let padding: String = line
+                .chars()
+                .take(column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let number = (index + 1).to_string();
+            let gutter = " ".repeat(number.len());
+            return Some(format!(
+                "{gutter} |\n{number} | {line}\n{gutter} | {padding}{carets}",
+                carets = "^".repeat(width)
+            ));
+        }
+        // skip the line and its terminating newline
+        line_start += line_len + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DSSParser;
+
+    #[test]
+    fn test_render_conversion_error() {
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("new load.l1 kv=abc");
+        for _ in 0..3 {
+            parser.next_param();
+        }
+        let err = parser.make_double().unwrap_err();
+        assert_eq!(
+            render_error("new load.l1 kv=abc", &err),
+            "error: Floating point number conversion error for string: \"abc\"\n  |\n1 | new load.l1 kv=abc\n  |                ^^^"
+        );
+        assert_eq!(
+            parser.render_error(&err),
+            render_error("new load.l1 kv=abc", &err)
+        );
+    }
+
+    #[test]
+    fn test_render_multiline_and_tabs() {
+        let err = ParserError::UnknownVariable {
+            name: "@x".to_string(),
+            span: Span::new(9, 11),
+        };
+        assert_eq!(
+            render_error("a=1\n\tkv= @x", &err),
+            "error: Variable not found: \"@x\"\n  |\n2 | \tkv= @x\n  | \t    ^^"
+        );
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let err = ParserError::new("Something went wrong");
+        assert_eq!(render_error("x", &err), "error: Something went wrong");
+
+        let err = ParserError::UnknownRpnToken {
+            token: "foo".to_string(),
+            span: Span::new(40, 43),
+        };
+        assert_eq!(
+            render_error("short", &err),
+            "error: Invalid inline math entry: \"foo\""
+        );
+
+        // an empty span still gets one caret
+        let err = ParserError::UnterminatedQuote {
+            token: String::new(),
+            quote: ')',
+            span: Span::new(3, 3),
+        };
+        assert!(render_error("kw=", &err).ends_with("|    ^"));
+    }
+}
@@ -1,22 +1,333 @@
-use std::f64::consts::PI;
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+use crate::infix::{infix_to_rpn, infix_to_rpn_with};
+use std::f64::consts::{E, PI, SQRT_2};
+use std::fmt;
+
+// Number of registers always present (zero-filled), as in the HP-style
+// Pascal calculator. The stack grows beyond this instead of dropping values.
+const MIN_STACK_SIZE: usize = 10;
+
+// Registers of a calculator, kept in one piece with x at the end so values
+// go on and come off without moving the others. Indices count from x: 0 is
+// x, 1 is y, 2 is z.
+#[derive(Debug, Clone)]
+pub(crate) struct Stack<T>(Vec<T>);
+
+impl<T: Copy> Stack<T> {
+    pub(crate) fn new(zero: T) -> Self {
+        Stack(vec![zero; MIN_STACK_SIZE])
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<T> {
+        let position = self.0.len().checked_sub(index + 1)?;
+        Some(self.0[position])
+    }
+
+    // Bottom first, x last
+    pub(crate) fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+
+    // x first
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().rev()
+    }
+
+    pub(crate) fn swap(&mut self, a: usize, b: usize) {
+        let len = self.0.len();
+        self.0.swap(len - 1 - a, len - 1 - b);
+    }
+
+    // Duplicates x into y, pushing every other value one level deeper
+    pub(crate) fn roll_up(&mut self) {
+        let x = self[0];
+        self.0.push(x);
+    }
+
+    // Drops x, pulling every other value one level up. At the minimum size
+    // the bottom register is duplicated, like the fixed-size Pascal stack.
+    pub(crate) fn roll_down(&mut self) {
+        self.0.pop();
+        if self.0.len() < MIN_STACK_SIZE {
+            let bottom = self.0[0];
+            self.0.insert(0, bottom);
+        }
+    }
+}
+
+impl<T> Index<usize> for Stack<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.0[self.0.len() - 1 - index]
+    }
+}
+
+impl<T> IndexMut<usize> for Stack<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.0.len();
+        &mut self.0[len - 1 - index]
+    }
+}
+
+// Errors reported by the calculator when checked mode is enabled
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpnError {
+    DivisionByZero,
+    DomainError { op: &'static str, value: f64 },
+    StackUnderflow { op: String },
+    InvalidEntry { token: String },
+    MissingRegister { op: &'static str },
+    InvalidExpression { message: String },
+    NonFinite { token: String, value: f64 },
+}
+
+impl fmt::Display for RpnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpnError::DivisionByZero => write!(f, "Inline math error: division by zero"),
+            RpnError::DomainError { op, value } => write!(
+                f,
+                "Inline math error: argument {} is out of the domain of \"{}\"",
+                value, op
+            ),
+            RpnError::StackUnderflow { op } => write!(
+                f,
+                "Inline math error: too few values on the stack for \"{}\"",
+                op
+            ),
+            RpnError::InvalidEntry { token } => {
+                write!(f, "Invalid inline math entry: \"{}\"", token)
+            }
+            RpnError::NonFinite { token, value } => write!(
+                f,
+                "Inline math error: \"{}\" produced the non-finite value {}",
+                token, value
+            ),
+            RpnError::InvalidExpression { message } => {
+                write!(f, "Invalid inline math expression: {}", message)
+            }
+            RpnError::MissingRegister { op } => write!(
+                f,
+                "Missing register name after \"{}\" in inline math entry",
+                op
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RpnError {}
+
+pub type RpnResult = Result<(), RpnError>;
 
-const MAX_STACK_SIZE: usize = 10;
+// What to do when an operation leaves NaN or an infinity in x. OpenDSS lets
+// such values propagate into the circuit data, which is the default here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinitePolicy {
+    #[default]
+    Propagate,
+    Error,
+    // NaN becomes 0, infinities become the largest finite value of their sign
+    Clamp,
+}
+
+impl NonFinitePolicy {
+    pub fn clamp(value: f64) -> f64 {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(f64::MIN, f64::MAX)
+        }
+    }
+}
+
+// Unit used for the arguments of trig functions and the results of their inverses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Degrees,
+    Radians,
+}
+
+// SplitMix64 generator: small, fast and fully determined by its seed, which
+// keeps scripted Monte Carlo runs reproducible across platforms.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub const DEFAULT_SEED: u64 = 0x853C_49E6_748F_EA9B;
+
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal by the Box-Muller transform
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1], keeps ln finite
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+// One step of an evaluation recorded in trace mode: the token executed and
+// the entered part of the stack afterwards (x first), or the error it raised
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub token: String,
+    pub stack: Vec<f64>,
+    pub error: Option<RpnError>,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.error {
+            Some(err) => write!(f, "{:<12} {}", self.token, err),
+            None => write!(f, "{:<12} {:?}", self.token, self.stack),
+        }
+    }
+}
+
+type RpnFn = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+// Function registered by the embedding application, see `register_fn`
+struct UserFn {
+    arity: usize,
+    func: RpnFn,
+}
+
+impl fmt::Debug for UserFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserFn")
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
 
 #[derive(Debug)]
 pub struct RPNCalculator {
-    stack: [f64; MAX_STACK_SIZE],
+    stack: Stack<f64>, // stack[0] is the x register
+    depth: usize,      // number of values explicitly entered, used in checked mode
+    checked: bool,
+    angle_mode: AngleMode,
+    registers: HashMap<String, f64>, // memory registers, keyed by lowercase name
+    last_x: f64,                     // x as it was before the last operation
+    rng: SplitMix64,
+    user_fns: HashMap<String, UserFn>, // keyed by lowercase name
+    trace: Option<Vec<TraceEntry>>,    // Some while trace mode is on
+    non_finite_policy: NonFinitePolicy,
+    clamped: Vec<String>, // tokens whose result the Clamp policy replaced
 }
 
 impl RPNCalculator {
     const DEG_TO_RAD: f64 = PI / 180.0;
     const RAD_TO_DEG: f64 = 180.0 / PI;
+    const SQRT_3: f64 = 1.732_050_807_568_877_2;
 
     pub fn new() -> Self {
         RPNCalculator {
-            stack: [0.0; MAX_STACK_SIZE],
+            stack: Stack::new(0.0),
+            depth: 0,
+            checked: false,
+            angle_mode: AngleMode::Degrees,
+            registers: HashMap::new(),
+            last_x: 0.0,
+            rng: SplitMix64::new(SplitMix64::DEFAULT_SEED),
+            user_fns: HashMap::new(),
+            trace: None,
+            non_finite_policy: NonFinitePolicy::Propagate,
+            clamped: Vec::new(),
+        }
+    }
+
+    // In checked mode operations validate their operands and return an error
+    // instead of producing NaN/inf; otherwise they behave like OpenDSS.
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
+    // The trig functions keep their Pascal `_deg` names but follow the angle
+    // mode; degrees is the default, as in OpenDSS.
+    pub fn get_angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+    }
+
+    fn angle_in(&self, angle: f64) -> f64 {
+        match self.angle_mode {
+            AngleMode::Degrees => Self::DEG_TO_RAD * angle,
+            AngleMode::Radians => angle,
+        }
+    }
+
+    fn angle_out(&self, angle: f64) -> f64 {
+        match self.angle_mode {
+            AngleMode::Degrees => Self::RAD_TO_DEG * angle,
+            AngleMode::Radians => angle,
         }
     }
 
+    fn require(&self, count: usize, op: &'static str) -> RpnResult {
+        if self.checked && self.depth < count {
+            return Err(RpnError::StackUnderflow { op: op.to_string() });
+        }
+        Ok(())
+    }
+
+    fn require_domain(&self, valid: bool, op: &'static str, value: f64) -> RpnResult {
+        if self.checked && !valid {
+            return Err(RpnError::DomainError { op, value });
+        }
+        Ok(())
+    }
+
+    fn require_nonzero(&self, value: f64) -> RpnResult {
+        if self.checked && value == 0.0 {
+            return Err(RpnError::DivisionByZero);
+        }
+        Ok(())
+    }
+
+    // The whole stack, bottom first and x last. Always holds at least the
+    // ten HP-style registers, so it includes zero padding below the entered
+    // values.
+    pub fn stack(&self) -> &[f64] {
+        self.stack.as_slice()
+    }
+
+    // Number of values entered and not yet consumed by operations
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Register by position: 0 is x, 1 is y, 2 is z, 3 is t, ...
+    pub fn get(&self, index: usize) -> Option<f64> {
+        self.stack.get(index)
+    }
+
     pub fn get_x(&self) -> f64 {
         self.stack[0] // Pascal FStack[1] = Rust stack[0]
     }
@@ -36,88 +347,513 @@ impl RPNCalculator {
 
     pub fn set_y(&mut self, value: f64) {
         self.stack[1] = value;
+        self.depth = self.depth.max(2);
     }
 
     pub fn set_z(&mut self, value: f64) {
         self.stack[2] = value;
+        self.depth = self.depth.max(3);
     }
 
-    pub fn add(&mut self) {
-        self.stack[1] = self.stack[0] + self.stack[1];
+    pub fn add(&mut self) -> RpnResult {
+        self.require(2, "+")?;
+        self.last_x = self.stack[0];
+        self.stack[1] += self.stack[0];
         self.roll_down();
+        Ok(())
     }
 
-    pub fn subtract(&mut self) {
-        self.stack[1] = self.stack[1] - self.stack[0];
+    pub fn subtract(&mut self) -> RpnResult {
+        self.require(2, "-")?;
+        self.last_x = self.stack[0];
+        self.stack[1] -= self.stack[0];
         self.roll_down();
+        Ok(())
     }
 
-    pub fn multiply(&mut self) {
-        self.stack[1] = self.stack[1] * self.stack[0];
+    pub fn multiply(&mut self) -> RpnResult {
+        self.require(2, "*")?;
+        self.last_x = self.stack[0];
+        self.stack[1] *= self.stack[0];
         self.roll_down();
+        Ok(())
+    }
+
+    pub fn divide(&mut self) -> RpnResult {
+        self.require(2, "/")?;
+        self.require_nonzero(self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[1] /= self.stack[0];
+        self.roll_down();
+        Ok(())
+    }
+
+    // y mod x, with the sign of y (truncated division)
+    pub fn modulo(&mut self) -> RpnResult {
+        self.require(2, "mod")?;
+        self.require_nonzero(self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[1] %= self.stack[0];
+        self.roll_down();
+        Ok(())
+    }
+
+    pub fn min(&mut self) -> RpnResult {
+        self.require(2, "min")?;
+        self.last_x = self.stack[0];
+        self.stack[1] = self.stack[1].min(self.stack[0]);
+        self.roll_down();
+        Ok(())
+    }
+
+    pub fn max(&mut self) -> RpnResult {
+        self.require(2, "max")?;
+        self.last_x = self.stack[0];
+        self.stack[1] = self.stack[1].max(self.stack[0]);
+        self.roll_down();
+        Ok(())
+    }
+
+    // sqrt(x^2 + y^2), e.g. |Z| from R and X
+    pub fn hypot(&mut self) -> RpnResult {
+        self.require(2, "hypot")?;
+        self.last_x = self.stack[0];
+        self.stack[1] = self.stack[1].hypot(self.stack[0]);
+        self.roll_down();
+        Ok(())
+    }
+
+    // Comparisons and logic operate on y (left operand) and x (right operand)
+    // and replace them with 1.0 for true or 0.0 for false. Any non-zero value
+    // counts as true.
+    fn binary_predicate(&mut self, op: &'static str, predicate: fn(f64, f64) -> bool) -> RpnResult {
+        self.require(2, op)?;
+        self.last_x = self.stack[0];
+        self.stack[1] = if predicate(self.stack[1], self.stack[0]) {
+            1.0
+        } else {
+            0.0
+        };
+        self.roll_down();
+        Ok(())
+    }
+
+    pub fn less_than(&mut self) -> RpnResult {
+        self.binary_predicate("<", |y, x| y < x)
+    }
+
+    pub fn greater_than(&mut self) -> RpnResult {
+        self.binary_predicate(">", |y, x| y > x)
+    }
+
+    pub fn less_equal(&mut self) -> RpnResult {
+        self.binary_predicate("<=", |y, x| y <= x)
+    }
+
+    pub fn greater_equal(&mut self) -> RpnResult {
+        self.binary_predicate(">=", |y, x| y >= x)
+    }
+
+    pub fn equal(&mut self) -> RpnResult {
+        self.binary_predicate("==", |y, x| y == x)
+    }
+
+    pub fn logical_and(&mut self) -> RpnResult {
+        self.binary_predicate("and", |y, x| y != 0.0 && x != 0.0)
     }
 
-    pub fn divide(&mut self) {
-        self.stack[1] = self.stack[1] / self.stack[0];
+    pub fn logical_or(&mut self) -> RpnResult {
+        self.binary_predicate("or", |y, x| y != 0.0 || x != 0.0)
+    }
+
+    pub fn logical_not(&mut self) -> RpnResult {
+        self.require(1, "not")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = if self.stack[0] == 0.0 { 1.0 } else { 0.0 };
+        Ok(())
+    }
+
+    // Percent to fraction: x / 100
+    pub fn percent(&mut self) -> RpnResult {
+        self.require(1, "pct")?;
+        self.last_x = self.stack[0];
+        self.stack[0] /= 100.0;
+        Ok(())
+    }
+
+    // Fraction (or per-unit) to percent: x * 100
+    pub fn to_percent(&mut self) -> RpnResult {
+        self.require(1, "topct")?;
+        self.last_x = self.stack[0];
+        self.stack[0] *= 100.0;
+        Ok(())
+    }
+
+    // Per-unit value of y on the base x: y / x
+    pub fn per_unit(&mut self) -> RpnResult {
+        self.require(2, "pu")?;
+        self.require_nonzero(self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[1] /= self.stack[0];
         self.roll_down();
+        Ok(())
+    }
+
+    pub fn abs(&mut self) -> RpnResult {
+        self.require(1, "abs")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].abs();
+        Ok(())
+    }
+
+    pub fn negate(&mut self) -> RpnResult {
+        self.require(1, "neg")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = -self.stack[0];
+        Ok(())
+    }
+
+    pub fn floor(&mut self) -> RpnResult {
+        self.require(1, "floor")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].floor();
+        Ok(())
+    }
+
+    pub fn ceil(&mut self) -> RpnResult {
+        self.require(1, "ceil")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].ceil();
+        Ok(())
+    }
+
+    // Rounds half-way cases away from zero
+    pub fn round(&mut self) -> RpnResult {
+        self.require(1, "round")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].round();
+        Ok(())
+    }
+
+    pub fn trunc(&mut self) -> RpnResult {
+        self.require(1, "trunc")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].trunc();
+        Ok(())
     }
 
-    pub fn sqrt(&mut self) {
+    pub fn sqrt(&mut self) -> RpnResult {
+        self.require(1, "sqrt")?;
+        self.require_domain(self.stack[0] >= 0.0, "sqrt", self.stack[0])?;
+        self.last_x = self.stack[0];
         self.stack[0] = self.stack[0].sqrt();
+        Ok(())
     }
 
-    pub fn square(&mut self) {
+    pub fn square(&mut self) -> RpnResult {
+        self.require(1, "sqr")?;
+        self.last_x = self.stack[0];
         self.stack[0] = self.stack[0] * self.stack[0];
+        Ok(())
     }
 
-    pub fn y_to_the_x_power(&mut self) {
-        self.stack[1] = self.stack[1].powf(self.stack[0]);
+    pub fn y_to_the_x_power(&mut self) -> RpnResult {
+        self.require(2, "^")?;
+        let (x, y) = (self.stack[0], self.stack[1]);
+        if self.checked && y == 0.0 && x < 0.0 {
+            return Err(RpnError::DivisionByZero);
+        }
+        self.require_domain(y >= 0.0 || x.fract() == 0.0, "^", y)?;
+        self.last_x = self.stack[0];
+        self.stack[1] = y.powf(x);
         self.roll_down();
+        Ok(())
     }
 
-    pub fn inv(&mut self) {
+    pub fn inv(&mut self) -> RpnResult {
+        self.require(1, "inv")?;
+        self.require_nonzero(self.stack[0])?;
+        self.last_x = self.stack[0];
         self.stack[0] = 1.0 / self.stack[0];
+        Ok(())
     }
 
-    pub fn sin_deg(&mut self) {
-        self.stack[0] = (Self::DEG_TO_RAD * self.stack[0]).sin();
+    pub fn sin_deg(&mut self) -> RpnResult {
+        self.require(1, "sin")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_in(self.stack[0]).sin();
+        Ok(())
     }
 
-    pub fn cos_deg(&mut self) {
-        self.stack[0] = (Self::DEG_TO_RAD * self.stack[0]).cos();
+    pub fn cos_deg(&mut self) -> RpnResult {
+        self.require(1, "cos")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_in(self.stack[0]).cos();
+        Ok(())
     }
 
-    pub fn tan_deg(&mut self) {
-        self.stack[0] = (Self::DEG_TO_RAD * self.stack[0]).tan();
+    pub fn tan_deg(&mut self) -> RpnResult {
+        self.require(1, "tan")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_in(self.stack[0]).tan();
+        Ok(())
     }
 
-    pub fn asin_deg(&mut self) {
-        self.stack[0] = Self::RAD_TO_DEG * self.stack[0].asin();
+    pub fn asin_deg(&mut self) -> RpnResult {
+        self.require(1, "asin")?;
+        self.require_domain(self.stack[0].abs() <= 1.0, "asin", self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_out(self.stack[0].asin());
+        Ok(())
     }
 
-    pub fn acos_deg(&mut self) {
-        self.stack[0] = Self::RAD_TO_DEG * self.stack[0].acos();
+    pub fn acos_deg(&mut self) -> RpnResult {
+        self.require(1, "acos")?;
+        self.require_domain(self.stack[0].abs() <= 1.0, "acos", self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_out(self.stack[0].acos());
+        Ok(())
     }
 
-    pub fn atan_deg(&mut self) {
-        self.stack[0] = Self::RAD_TO_DEG * self.stack[0].atan();
+    pub fn atan_deg(&mut self) -> RpnResult {
+        self.require(1, "atan")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.angle_out(self.stack[0].atan());
+        Ok(())
     }
 
-    pub fn atan2_deg(&mut self) {
-        self.stack[1] = Self::RAD_TO_DEG * self.stack[1].atan2(self.stack[0]);
+    pub fn atan2_deg(&mut self) -> RpnResult {
+        self.require(2, "atan2")?;
+        self.last_x = self.stack[0];
+        self.stack[1] = self.angle_out(self.stack[1].atan2(self.stack[0]));
         self.roll_down();
+        Ok(())
     }
 
-    pub fn nat_log(&mut self) {
+    pub fn nat_log(&mut self) -> RpnResult {
+        self.require(1, "ln")?;
+        self.require_domain(self.stack[0] > 0.0, "ln", self.stack[0])?;
+        self.last_x = self.stack[0];
         self.stack[0] = self.stack[0].ln();
+        Ok(())
     }
 
-    pub fn ten_log(&mut self) {
+    pub fn ten_log(&mut self) -> RpnResult {
+        self.require(1, "log10")?;
+        self.require_domain(self.stack[0] > 0.0, "log10", self.stack[0])?;
+        self.last_x = self.stack[0];
         self.stack[0] = self.stack[0].log10();
+        Ok(())
+    }
+
+    pub fn two_log(&mut self) -> RpnResult {
+        self.require(1, "log2")?;
+        self.require_domain(self.stack[0] > 0.0, "log2", self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].log2();
+        Ok(())
+    }
+
+    pub fn tentothex(&mut self) -> RpnResult {
+        self.require(1, "exp10")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = 10f64.powf(self.stack[0]);
+        Ok(())
+    }
+
+    pub fn sinh(&mut self) -> RpnResult {
+        self.require(1, "sinh")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].sinh();
+        Ok(())
+    }
+
+    pub fn cosh(&mut self) -> RpnResult {
+        self.require(1, "cosh")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].cosh();
+        Ok(())
+    }
+
+    pub fn tanh(&mut self) -> RpnResult {
+        self.require(1, "tanh")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].tanh();
+        Ok(())
+    }
+
+    pub fn asinh(&mut self) -> RpnResult {
+        self.require(1, "asinh")?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].asinh();
+        Ok(())
+    }
+
+    pub fn acosh(&mut self) -> RpnResult {
+        self.require(1, "acosh")?;
+        self.require_domain(self.stack[0] >= 1.0, "acosh", self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].acosh();
+        Ok(())
+    }
+
+    pub fn atanh(&mut self) -> RpnResult {
+        self.require(1, "atanh")?;
+        self.require_domain(self.stack[0].abs() < 1.0, "atanh", self.stack[0])?;
+        self.last_x = self.stack[0];
+        self.stack[0] = self.stack[0].atanh();
+        Ok(())
     }
 
-    pub fn etothex(&mut self) {
+    pub fn etothex(&mut self) -> RpnResult {
+        self.require(1, "exp")?;
+        self.last_x = self.stack[0];
         self.stack[0] = self.stack[0].exp();
+        Ok(())
+    }
+
+    // Copies x into a memory register; the stack is left unchanged.
+    // Registers may be numbered ("1") or named ("vbase"), case-insensitive.
+    pub fn store(&mut self, register: &str) {
+        self.registers
+            .insert(register.to_lowercase(), self.stack[0]);
+    }
+
+    // Pushes the value of a memory register; unset registers read as zero
+    pub fn recall(&mut self, register: &str) {
+        let value = self
+            .registers
+            .get(&register.to_lowercase())
+            .copied()
+            .unwrap_or(0.0);
+        self.set_x(value);
+    }
+
+    pub fn clear_registers(&mut self) {
+        self.registers.clear();
+    }
+
+    // Pushes the x value that was consumed by the last operation
+    pub fn last_x(&mut self) {
+        self.set_x(self.last_x);
+    }
+
+    pub fn clear_x(&mut self) {
+        self.stack[0] = 0.0;
+    }
+
+    pub fn clear_stack(&mut self) {
+        self.stack = Stack::new(0.0);
+        self.depth = 0;
+    }
+
+    // Restarts the random sequence; the same seed always yields the same numbers
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SplitMix64::new(seed);
+    }
+
+    // Consumes x and uses its integer part as the new seed
+    pub fn seed_from_x(&mut self) -> RpnResult {
+        self.require(1, "seed")?;
+        self.last_x = self.stack[0];
+        self.set_seed(self.stack[0].trunc() as i64 as u64);
+        self.roll_down();
+        Ok(())
+    }
+
+    // Pushes a uniformly distributed number in [0, 1)
+    pub fn random(&mut self) {
+        let value = self.rng.next_f64();
+        self.set_x(value);
+    }
+
+    // Pushes a standard normally distributed number (mean 0, deviation 1)
+    pub fn random_normal(&mut self) {
+        let value = self.rng.next_normal();
+        self.set_x(value);
+    }
+
+    // Makes `name` usable as an RPN command. The function receives `arity`
+    // values in entry order (for "500 12.47 kva2amps" that is [500, 12.47]),
+    // which are replaced on the stack by its result. Built-in commands take
+    // precedence; registering a name again replaces the previous function.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) {
+        let func = Box::new(func);
+        self.user_fns
+            .insert(name.to_lowercase(), UserFn { arity, func });
+    }
+
+    pub fn unregister_fn(&mut self, name: &str) -> bool {
+        self.user_fns.remove(&name.to_lowercase()).is_some()
+    }
+
+    fn call_user_fn(&mut self, name: &str) -> Option<RpnResult> {
+        let user_fn = self.user_fns.get(name)?;
+        if self.checked && self.depth < user_fn.arity {
+            return Some(Err(RpnError::StackUnderflow {
+                op: name.to_string(),
+            }));
+        }
+
+        // registers beyond the stack read as zero, like the padding below x
+        let args: Vec<f64> = (0..user_fn.arity)
+            .rev()
+            .map(|i| self.stack.get(i).unwrap_or(0.0))
+            .collect();
+        let result = (user_fn.func)(&args);
+        if user_fn.arity > 0 {
+            self.last_x = self.stack[0];
+        }
+        for _ in 0..user_fn.arity {
+            self.roll_down();
+        }
+        self.set_x(result);
+        Some(Ok(()))
+    }
+
+    // Applied after each command executed by `command`/`eval`
+    pub fn get_non_finite_policy(&self) -> NonFinitePolicy {
+        self.non_finite_policy
+    }
+
+    pub fn set_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.non_finite_policy = policy;
+    }
+
+    // Tokens whose non-finite result was clamped since the last call
+    pub fn take_clamped(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.clamped)
+    }
+
+    // In trace mode every evaluation records the tokens it executes and the
+    // resulting stacks; the record of the last evaluation is kept until the
+    // next one starts.
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.trace = if tracing { Some(Vec::new()) } else { None };
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    fn record(&mut self, token: &str, result: &RpnResult) {
+        let stack = self.stack.iter().take(self.depth).copied().collect();
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                token: token.to_string(),
+                stack,
+                error: result.clone().err(),
+            });
+        }
     }
 
     pub fn enter_pi(&mut self) {
@@ -125,16 +861,217 @@ impl RPNCalculator {
         self.stack[0] = PI;
     }
 
-    pub fn swap_xy(&mut self) {
+    pub fn enter_e(&mut self) {
+        self.set_x(E);
+    }
+
+    pub fn enter_sqrt2(&mut self) {
+        self.set_x(SQRT_2);
+    }
+
+    // Line-to-line / line-to-neutral ratio in balanced three-phase systems
+    pub fn enter_sqrt3(&mut self) {
+        self.set_x(Self::SQRT_3);
+    }
+
+    pub fn swap_xy(&mut self) -> RpnResult {
+        self.require(2, "swap")?;
         self.stack.swap(0, 1);
+        Ok(())
     }
 
+    // Duplicates x into y, pushing every other value one level deeper
     pub fn roll_up(&mut self) {
-        self.stack.copy_within(0..MAX_STACK_SIZE - 1, 1);
+        self.stack.roll_up();
+        self.depth += 1;
     }
 
+    // Drops x, pulling every other value one level up. At the minimum size
+    // the bottom register is duplicated, like the fixed-size Pascal stack.
     pub fn roll_down(&mut self) {
-        self.stack.copy_within(1.., 0);
+        self.stack.roll_down();
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    // Executes a single RPN token and applies the non-finite policy to the
+    // resulting x
+    pub fn command(&mut self, token: &str) -> RpnResult {
+        self.execute(token)?;
+
+        let x = self.stack[0];
+        if x.is_finite() {
+            return Ok(());
+        }
+        match self.non_finite_policy {
+            NonFinitePolicy::Propagate => Ok(()),
+            NonFinitePolicy::Error => Err(RpnError::NonFinite {
+                token: token.to_string(),
+                value: x,
+            }),
+            NonFinitePolicy::Clamp => {
+                self.stack[0] = NonFinitePolicy::clamp(x);
+                self.clamped.push(token.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    // A number is pushed, anything else must be one of the operation names
+    // below (case-insensitive) or a registered function
+    fn execute(&mut self, token: &str) -> RpnResult {
+        // Try to parse as number first
+        if let Ok(number) = token.parse::<f64>() {
+            // inf and nan parse as numbers, but checked mode takes no
+            // non-finite operands
+            if self.checked && !number.is_finite() {
+                return Err(RpnError::NonFinite {
+                    token: token.to_string(),
+                    value: number,
+                });
+            }
+            self.set_x(number);
+            return Ok(());
+        }
+
+        match token.to_lowercase().as_str() {
+            "+" => self.add()?,
+            "-" => self.subtract()?,
+            "*" => self.multiply()?,
+            "/" => self.divide()?,
+            "sqrt" => self.sqrt()?,
+            "sqr" => self.square()?,
+            "^" => self.y_to_the_x_power()?,
+            "sin" => self.sin_deg()?,
+            "cos" => self.cos_deg()?,
+            "tan" => self.tan_deg()?,
+            "asin" => self.asin_deg()?,
+            "acos" => self.acos_deg()?,
+            "atan" => self.atan_deg()?,
+            "atan2" => self.atan2_deg()?,
+            "swap" => self.swap_xy()?,
+            "rollup" => self.roll_up(),
+            "rolldn" => self.roll_down(),
+            "ln" => self.nat_log()?,
+            "pi" => self.enter_pi(),
+            "e" => self.enter_e(),
+            "sqrt2" => self.enter_sqrt2(),
+            "sqrt3" => self.enter_sqrt3(),
+            "log10" => self.ten_log()?,
+            "exp" => self.etothex()?,
+            "inv" => self.inv()?,
+            "abs" => self.abs()?,
+            "pct" => self.percent()?,
+            "topct" => self.to_percent()?,
+            "pu" => self.per_unit()?,
+            "neg" => self.negate()?,
+            "mod" => self.modulo()?,
+            "floor" => self.floor()?,
+            "ceil" => self.ceil()?,
+            "round" => self.round()?,
+            "trunc" => self.trunc()?,
+            "min" => self.min()?,
+            "max" => self.max()?,
+            "hypot" => self.hypot()?,
+            "sinh" => self.sinh()?,
+            "cosh" => self.cosh()?,
+            "tanh" => self.tanh()?,
+            "asinh" => self.asinh()?,
+            "acosh" => self.acosh()?,
+            "atanh" => self.atanh()?,
+            "log2" => self.two_log()?,
+            "exp10" => self.tentothex()?,
+            "lastx" => self.last_x(),
+            "clx" => self.clear_x(),
+            "clst" => self.clear_stack(),
+            "<" => self.less_than()?,
+            ">" => self.greater_than()?,
+            "<=" => self.less_equal()?,
+            ">=" => self.greater_equal()?,
+            "==" => self.equal()?,
+            "and" => self.logical_and()?,
+            "or" => self.logical_or()?,
+            "not" => self.logical_not()?,
+            "rand" => self.random(),
+            "randn" => self.random_normal(),
+            "seed" => self.seed_from_x()?,
+            "rad" => self.set_angle_mode(AngleMode::Radians),
+            "deg" => self.set_angle_mode(AngleMode::Degrees),
+            name => {
+                return self.call_user_fn(name).unwrap_or_else(|| {
+                    Err(RpnError::InvalidEntry {
+                        token: token.to_string(),
+                    })
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Evaluates a whitespace separated RPN expression, e.g. "2 3 + sqrt",
+    // and returns x. The stack, registers and modes persist between calls.
+    pub fn eval(&mut self, expr: &str) -> Result<f64, RpnError> {
+        self.eval_with(expr, |token| token.to_string())
+    }
+
+    // Evaluates a conventional infix expression such as "(3+4)*sqrt(2)"; see
+    // the `infix` module for the supported syntax
+    pub fn eval_infix(&mut self, expr: &str) -> Result<f64, RpnError> {
+        self.eval(&infix_to_rpn(expr)?)
+    }
+
+    // Infix counterpart of `eval_with`: names that `expand` replaces are
+    // parsed as infix sub-expressions
+    pub fn eval_infix_with(
+        &mut self,
+        expr: &str,
+        expand: impl FnMut(&str) -> String,
+    ) -> Result<f64, RpnError> {
+        self.eval(&infix_to_rpn_with(expr, expand)?)
+    }
+
+    // Same as `eval`, but every token is passed through `expand` first; the
+    // parser uses this to substitute variables. An expansion may produce
+    // several tokens. Register names after sto/rcl are not expanded.
+    pub fn eval_with(
+        &mut self,
+        expr: &str,
+        mut expand: impl FnMut(&str) -> String,
+    ) -> Result<f64, RpnError> {
+        let mut register_command: Option<&'static str> = None; // sto/rcl awaiting a register name
+        if let Some(trace) = &mut self.trace {
+            trace.clear();
+        }
+
+        for token in expr.split_whitespace() {
+            if let Some(command) = register_command.take() {
+                match command {
+                    "sto" => self.store(token),
+                    _ => self.recall(token),
+                }
+                if self.trace.is_some() {
+                    self.record(&format!("{} {}", command, token), &Ok(()));
+                }
+            } else if token.eq_ignore_ascii_case("sto") {
+                register_command = Some("sto");
+            } else if token.eq_ignore_ascii_case("rcl") {
+                register_command = Some("rcl");
+            } else {
+                for token in expand(token).split_whitespace() {
+                    let result = self.command(token);
+                    if self.trace.is_some() {
+                        self.record(token, &result);
+                    }
+                    result?;
+                }
+            }
+        }
+
+        if let Some(op) = register_command {
+            return Err(RpnError::MissingRegister { op });
+        }
+
+        Ok(self.get_x())
     }
 }
 
@@ -147,7 +1084,6 @@ impl Default for RPNCalculator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::f64::consts::E;
 
     const EPSILON: f64 = 1e-10;
 
@@ -187,25 +1123,25 @@ mod tests {
         // 5 + 3 = 8
         calc.set_x(5.0);
         calc.set_x(3.0);
-        calc.add();
+        calc.add().unwrap();
         assert_eq!(calc.get_x(), 8.0);
 
         // 10 - 4 = 6
         calc.set_x(10.0);
         calc.set_x(4.0);
-        calc.subtract();
+        calc.subtract().unwrap();
         assert_eq!(calc.get_x(), 6.0);
 
         // 7 * 8 = 56
         calc.set_x(7.0);
         calc.set_x(8.0);
-        calc.multiply();
+        calc.multiply().unwrap();
         assert_eq!(calc.get_x(), 56.0);
 
         // 20 / 4 = 5
         calc.set_x(20.0);
         calc.set_x(4.0);
-        calc.divide();
+        calc.divide().unwrap();
         assert_eq!(calc.get_x(), 5.0);
     }
 
@@ -242,7 +1178,7 @@ mod tests {
         assert_eq!(calc.get_x(), 10.0);
         assert_eq!(calc.get_y(), 5.0);
 
-        calc.swap_xy();
+        calc.swap_xy().unwrap();
         assert_eq!(calc.get_x(), 5.0);
         assert_eq!(calc.get_y(), 10.0);
     }
@@ -253,22 +1189,22 @@ mod tests {
 
         // sqrt(25) = 5
         calc.set_x(25.0);
-        calc.sqrt();
+        calc.sqrt().unwrap();
         assert_eq!(calc.get_x(), 5.0);
 
         // square(6) = 36
         calc.set_x(6.0);
-        calc.square();
+        calc.square().unwrap();
         assert_eq!(calc.get_x(), 36.0);
 
         // inv(0.5) = 2
         calc.set_x(0.5);
-        calc.inv();
+        calc.inv().unwrap();
         assert_eq!(calc.get_x(), 2.0);
 
         // inv(4) = 0.25
         calc.set_x(4.0);
-        calc.inv();
+        calc.inv().unwrap();
         assert_eq!(calc.get_x(), 0.25);
     }
 
@@ -279,19 +1215,19 @@ mod tests {
         // 2^3 = 8
         calc.set_x(2.0);
         calc.set_x(3.0);
-        calc.y_to_the_x_power();
+        calc.y_to_the_x_power().unwrap();
         assert_eq!(calc.get_x(), 8.0);
 
         // 3^4 = 81
         calc.set_x(3.0);
         calc.set_x(4.0);
-        calc.y_to_the_x_power();
+        calc.y_to_the_x_power().unwrap();
         assert_eq!(calc.get_x(), 81.0);
 
         // 10^2 = 100
         calc.set_x(10.0);
         calc.set_x(2.0);
-        calc.y_to_the_x_power();
+        calc.y_to_the_x_power().unwrap();
         assert_eq!(calc.get_x(), 100.0);
     }
 
@@ -301,42 +1237,42 @@ mod tests {
 
         // sin(0 deg) = 0
         calc.set_x(0.0);
-        calc.sin_deg();
+        calc.sin_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // sin(30 deg) = 0.5
         calc.set_x(30.0);
-        calc.sin_deg();
+        calc.sin_deg().unwrap();
         assert!((calc.get_x() - 0.5).abs() < EPSILON);
 
         // sin(90 deg) = 1
         calc.set_x(90.0);
-        calc.sin_deg();
+        calc.sin_deg().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
 
         // cos(0 deg) = 1
         calc.set_x(0.0);
-        calc.cos_deg();
+        calc.cos_deg().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
 
         // cos(60 deg) = 0.5
         calc.set_x(60.0);
-        calc.cos_deg();
+        calc.cos_deg().unwrap();
         assert!((calc.get_x() - 0.5).abs() < EPSILON);
 
         // cos(90 deg) = 0
         calc.set_x(90.0);
-        calc.cos_deg();
+        calc.cos_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // tan(0 deg) = 0
         calc.set_x(0.0);
-        calc.tan_deg();
+        calc.tan_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // tan(45 deg) = 1
         calc.set_x(45.0);
-        calc.tan_deg();
+        calc.tan_deg().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
     }
 
@@ -346,42 +1282,42 @@ mod tests {
 
         // asin(0) = 0 deg
         calc.set_x(0.0);
-        calc.asin_deg();
+        calc.asin_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // asin(0.5) = 30 deg
         calc.set_x(0.5);
-        calc.asin_deg();
+        calc.asin_deg().unwrap();
         assert!((calc.get_x() - 30.0).abs() < EPSILON);
 
         // asin(1) = 90 deg
         calc.set_x(1.0);
-        calc.asin_deg();
+        calc.asin_deg().unwrap();
         assert!((calc.get_x() - 90.0).abs() < EPSILON);
 
         // acos(1) = 0 deg
         calc.set_x(1.0);
-        calc.acos_deg();
+        calc.acos_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // acos(0.5) = 60 deg
         calc.set_x(0.5);
-        calc.acos_deg();
+        calc.acos_deg().unwrap();
         assert!((calc.get_x() - 60.0).abs() < EPSILON);
 
         // acos(0) = 90 deg
         calc.set_x(0.0);
-        calc.acos_deg();
+        calc.acos_deg().unwrap();
         assert!((calc.get_x() - 90.0).abs() < EPSILON);
 
         // atan(0) = 0 deg
         calc.set_x(0.0);
-        calc.atan_deg();
+        calc.atan_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // atan(1) = 45 deg
         calc.set_x(1.0);
-        calc.atan_deg();
+        calc.atan_deg().unwrap();
         assert!((calc.get_x() - 45.0).abs() < EPSILON);
     }
 
@@ -392,19 +1328,19 @@ mod tests {
         // atan2(1, 1) = 45 deg
         calc.set_x(1.0);
         calc.set_y(1.0);
-        calc.atan2_deg();
+        calc.atan2_deg().unwrap();
         assert!((calc.get_x() - 45.0).abs() < EPSILON);
 
         // atan2(1, 0) = 90 deg
         calc.set_x(0.0);
         calc.set_y(1.0);
-        calc.atan2_deg();
+        calc.atan2_deg().unwrap();
         assert!((calc.get_x() - 90.0).abs() < EPSILON);
 
         // atan2(0, 1) = 0 deg
         calc.set_x(1.0);
         calc.set_y(0.0);
-        calc.atan2_deg();
+        calc.atan2_deg().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
     }
 
@@ -414,27 +1350,27 @@ mod tests {
 
         // ln(e) = 1
         calc.set_x(E);
-        calc.nat_log();
+        calc.nat_log().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
 
         // ln(1) = 0
         calc.set_x(1.0);
-        calc.nat_log();
+        calc.nat_log().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
 
         // log10(10) = 1
         calc.set_x(10.0);
-        calc.ten_log();
+        calc.ten_log().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
 
         // log10(100) = 2
         calc.set_x(100.0);
-        calc.ten_log();
+        calc.ten_log().unwrap();
         assert!((calc.get_x() - 2.0).abs() < EPSILON);
 
         // log10(1) = 0
         calc.set_x(1.0);
-        calc.ten_log();
+        calc.ten_log().unwrap();
         assert!(calc.get_x().abs() < EPSILON);
     }
 
@@ -444,17 +1380,17 @@ mod tests {
 
         // e^0 = 1
         calc.set_x(0.0);
-        calc.etothex();
+        calc.etothex().unwrap();
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
 
         // e^1 = e
         calc.set_x(1.0);
-        calc.etothex();
+        calc.etothex().unwrap();
         assert!((calc.get_x() - E).abs() < EPSILON);
 
         // e^2 == 7.38905609893065
         calc.set_x(2.0);
-        calc.etothex();
+        calc.etothex().unwrap();
         assert!((calc.get_x() - 7.38905609893065).abs() < EPSILON);
     }
 
@@ -486,21 +1422,21 @@ mod tests {
         // (5 + 3) * 2 = 16
         calc.set_x(5.0);
         calc.set_x(3.0);
-        calc.add(); // 8
+        calc.add().unwrap(); // 8
         calc.set_x(2.0);
-        calc.multiply();
+        calc.multiply().unwrap();
         assert_eq!(calc.get_x(), 16.0);
 
         let mut calc = RPNCalculator::new();
         // sqrt((3^2) + (4^2)) = 5
         calc.set_x(3.0);
-        calc.square(); // x = 9
+        calc.square().unwrap(); // x = 9
         calc.set_x(4.0);
-        calc.square(); // x = 16, y = 9, z = 0
+        calc.square().unwrap(); // x = 16, y = 9, z = 0
         assert_eq!((calc.get_x(), calc.get_y(), calc.get_z()), (16.0, 9.0, 0.0));
-        calc.add(); // x = 25, y = 9, z = 0
+        calc.add().unwrap(); // x = 25, y = 9, z = 0
         assert_eq!((calc.get_x(), calc.get_y(), calc.get_z()), (25.0, 0.0, 0.0));
-        calc.sqrt(); // x = 5,  y = 9, z = 0
+        calc.sqrt().unwrap(); // x = 5,  y = 9, z = 0
         assert_eq!((calc.get_x(), calc.get_y(), calc.get_z()), (5.0, 0.0, 0.0));
     }
 
@@ -510,14 +1446,14 @@ mod tests {
 
         // sin^2(30 deg) + cos^2(30 deg) = 1
         calc.set_x(30.0);
-        calc.sin_deg();
-        calc.square(); // sin^(30 deg)
+        calc.sin_deg().unwrap();
+        calc.square().unwrap(); // sin^(30 deg)
 
         calc.set_x(30.0);
-        calc.cos_deg();
-        calc.square(); // cos^2(30 deg)
+        calc.cos_deg().unwrap();
+        calc.square().unwrap(); // cos^2(30 deg)
 
-        calc.add(); // sin^2(30 deg) + cos^2(30 deg)
+        calc.add().unwrap(); // sin^2(30 deg) + cos^2(30 deg)
         assert!((calc.get_x() - 1.0).abs() < EPSILON);
     }
 
@@ -528,17 +1464,17 @@ mod tests {
         // ln(e^x) = x for x = 2.5
         let test_value = 2.5;
         calc.set_x(test_value);
-        calc.etothex(); // e^2.5
-        calc.nat_log(); // ln(e^2.5) = 2.5
+        calc.etothex().unwrap(); // e^2.5
+        calc.nat_log().unwrap(); // ln(e^2.5) = 2.5
         assert!((calc.get_x() - test_value).abs() < EPSILON);
 
         // 10^(log10(x)) = x for x = 123.456
         let test_value = 123.456;
         calc.set_x(test_value);
-        calc.ten_log(); // log10(123.456)
+        calc.ten_log().unwrap(); // log10(123.456)
         calc.set_x(10.0);
-        calc.swap_xy();
-        calc.y_to_the_x_power(); // 10^(log10(123.456)) = 123.456
+        calc.swap_xy().unwrap();
+        calc.y_to_the_x_power().unwrap(); // 10^(log10(123.456)) = 123.456
         assert!((calc.get_x() - test_value).abs() < 1e-10);
     }
 
@@ -548,18 +1484,18 @@ mod tests {
 
         // sqrt(0) = 0
         calc.set_x(0.0);
-        calc.sqrt();
+        calc.sqrt().unwrap();
         assert_eq!(calc.get_x(), 0.0);
 
         // 0^0
         calc.set_x(0.0);
         calc.set_x(0.0);
-        calc.y_to_the_x_power();
+        calc.y_to_the_x_power().unwrap();
         assert_eq!(calc.get_x(), 1.0);
 
         // ln(1) = 0
         calc.set_x(1.0);
-        calc.nat_log();
+        calc.nat_log().unwrap();
         assert_eq!(calc.get_x(), 0.0);
     }
 
@@ -585,4 +1521,653 @@ mod tests {
         assert_eq!(calc.get_y(), 2.0);
         assert_eq!(calc.get_z(), 1.0);
     }
+
+    #[test]
+    fn test_checked_mode_errors() {
+        let mut calc = RPNCalculator::new();
+        calc.set_checked(true);
+
+        calc.set_x(1.0);
+        calc.set_x(0.0);
+        assert_eq!(calc.divide(), Err(RpnError::DivisionByZero));
+        // stack untouched on error
+        assert_eq!((calc.get_x(), calc.get_y()), (0.0, 1.0));
+        assert_eq!(calc.inv(), Err(RpnError::DivisionByZero));
+
+        calc.set_x(-4.0);
+        assert_eq!(
+            calc.sqrt(),
+            Err(RpnError::DomainError {
+                op: "sqrt",
+                value: -4.0
+            })
+        );
+        calc.set_x(0.0);
+        assert!(matches!(calc.nat_log(), Err(RpnError::DomainError { .. })));
+        calc.set_x(2.0);
+        assert!(matches!(calc.asin_deg(), Err(RpnError::DomainError { .. })));
+
+        calc.set_x(-8.0);
+        calc.set_x(0.5);
+        assert!(matches!(
+            calc.y_to_the_x_power(),
+            Err(RpnError::DomainError { .. })
+        ));
+        calc.set_x(-8.0);
+        calc.set_x(3.0);
+        calc.y_to_the_x_power().unwrap();
+        assert_eq!(calc.get_x(), -512.0);
+
+        // non-finite literals would slip past the domain checks
+        assert!(matches!(
+            calc.eval("inf sqrt"),
+            Err(RpnError::NonFinite { .. })
+        ));
+        assert!(matches!(
+            calc.eval("NaN 1 +"),
+            Err(RpnError::NonFinite { .. })
+        ));
+    }
+
+    #[test]
+    fn test_checked_mode_stack_underflow() {
+        let mut calc = RPNCalculator::new();
+        calc.set_checked(true);
+
+        assert_eq!(
+            calc.sqrt(),
+            Err(RpnError::StackUnderflow {
+                op: "sqrt".to_string()
+            })
+        );
+        calc.set_x(3.0);
+        assert_eq!(
+            calc.add(),
+            Err(RpnError::StackUnderflow {
+                op: "+".to_string()
+            })
+        );
+        calc.set_x(4.0);
+        calc.add().unwrap();
+        assert_eq!(calc.get_x(), 7.0);
+        assert!(calc.multiply().is_err());
+    }
+
+    #[test]
+    fn test_unchecked_mode_propagates_non_finite() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(1.0);
+        calc.set_x(0.0);
+        calc.divide().unwrap();
+        assert!(calc.get_x().is_infinite());
+
+        calc.set_x(-1.0);
+        calc.sqrt().unwrap();
+        assert!(calc.get_x().is_nan());
+
+        // operating on the implicit zeros of an empty stack is allowed
+        let mut calc = RPNCalculator::new();
+        calc.add().unwrap();
+        assert_eq!(calc.get_x(), 0.0);
+    }
+
+    #[test]
+    fn test_stack_grows_beyond_ten_values() {
+        let mut calc = RPNCalculator::new();
+
+        // 1 2 3 ... 15 + + + ... (14 times) = 120
+        for i in 1..=15 {
+            calc.set_x(i as f64);
+        }
+        for _ in 0..14 {
+            calc.add().unwrap();
+        }
+        assert_eq!(calc.get_x(), 120.0);
+        assert_eq!(calc.get_y(), 0.0);
+    }
+
+    #[test]
+    fn test_roll_down_keeps_bottom_register() {
+        let mut calc = RPNCalculator::new();
+        calc.set_x(1.0);
+        for _ in 0..20 {
+            calc.roll_down();
+        }
+        assert_eq!(calc.get_x(), 0.0);
+        assert_eq!(calc.stack().len(), MIN_STACK_SIZE);
+    }
+
+    #[test]
+    fn test_rounding_and_sign_functions() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(-1.0);
+        calc.abs().unwrap();
+        assert_eq!(calc.get_x(), 1.0);
+
+        calc.negate().unwrap();
+        assert_eq!(calc.get_x(), -1.0);
+
+        calc.set_x(2.5);
+        calc.floor().unwrap();
+        assert_eq!(calc.get_x(), 2.0);
+
+        calc.set_x(2.1);
+        calc.ceil().unwrap();
+        assert_eq!(calc.get_x(), 3.0);
+
+        calc.set_x(2.5);
+        calc.round().unwrap();
+        assert_eq!(calc.get_x(), 3.0);
+        calc.set_x(-2.5);
+        calc.round().unwrap();
+        assert_eq!(calc.get_x(), -3.0);
+
+        calc.set_x(-2.7);
+        calc.trunc().unwrap();
+        assert_eq!(calc.get_x(), -2.0);
+    }
+
+    #[test]
+    fn test_modulo() {
+        let mut calc = RPNCalculator::new();
+
+        // 8760 mod 24 = 0
+        calc.set_x(8760.0);
+        calc.set_x(24.0);
+        calc.modulo().unwrap();
+        assert_eq!(calc.get_x(), 0.0);
+
+        // 100 mod 24 = 4
+        calc.set_x(100.0);
+        calc.set_x(24.0);
+        calc.modulo().unwrap();
+        assert_eq!(calc.get_x(), 4.0);
+
+        // -7 mod 3 = -1
+        calc.set_x(-7.0);
+        calc.set_x(3.0);
+        calc.modulo().unwrap();
+        assert_eq!(calc.get_x(), -1.0);
+
+        calc.set_checked(true);
+        calc.set_x(0.0);
+        assert_eq!(calc.modulo(), Err(RpnError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_min_max_hypot() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(3.0);
+        calc.set_x(-2.0);
+        calc.min().unwrap();
+        assert_eq!(calc.get_x(), -2.0);
+
+        calc.set_x(7.5);
+        calc.max().unwrap();
+        assert_eq!(calc.get_x(), 7.5);
+
+        // |0.3 + j0.4| = 0.5
+        calc.set_x(0.3);
+        calc.set_x(0.4);
+        calc.hypot().unwrap();
+        assert!((calc.get_x() - 0.5).abs() < EPSILON);
+        assert_eq!(calc.get_y(), 7.5);
+    }
+
+    #[test]
+    fn test_hyperbolic_functions() {
+        let mut calc = RPNCalculator::new();
+
+        // sinh(1) = (e - 1/e) / 2
+        calc.set_x(1.0);
+        calc.sinh().unwrap();
+        assert!((calc.get_x() - (E - 1.0 / E) / 2.0).abs() < EPSILON);
+
+        // cosh(0) = 1
+        calc.set_x(0.0);
+        calc.cosh().unwrap();
+        assert!((calc.get_x() - 1.0).abs() < EPSILON);
+
+        // tanh(0) = 0
+        calc.set_x(0.0);
+        calc.tanh().unwrap();
+        assert!(calc.get_x().abs() < EPSILON);
+
+        // inverse functions round-trip
+        calc.set_x(0.75);
+        calc.sinh().unwrap();
+        calc.asinh().unwrap();
+        assert!((calc.get_x() - 0.75).abs() < EPSILON);
+
+        calc.set_x(0.75);
+        calc.cosh().unwrap();
+        calc.acosh().unwrap();
+        assert!((calc.get_x() - 0.75).abs() < EPSILON);
+
+        calc.set_x(0.75);
+        calc.tanh().unwrap();
+        calc.atanh().unwrap();
+        assert!((calc.get_x() - 0.75).abs() < EPSILON);
+
+        calc.set_checked(true);
+        calc.set_x(0.5);
+        assert!(matches!(calc.acosh(), Err(RpnError::DomainError { .. })));
+        calc.set_x(1.0);
+        assert!(matches!(calc.atanh(), Err(RpnError::DomainError { .. })));
+    }
+
+    #[test]
+    fn test_log2_and_exp10() {
+        let mut calc = RPNCalculator::new();
+
+        // log2(8) = 3
+        calc.set_x(8.0);
+        calc.two_log().unwrap();
+        assert!((calc.get_x() - 3.0).abs() < EPSILON);
+
+        // 10^(-6 / 20) for a -6 dB ratio
+        calc.set_x(-0.3);
+        calc.tentothex().unwrap();
+        assert!((calc.get_x() - 0.5011872336272722).abs() < EPSILON);
+
+        calc.set_x(3.0);
+        calc.tentothex().unwrap();
+        assert!((calc.get_x() - 1000.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_radians_mode() {
+        let mut calc = RPNCalculator::new();
+        assert_eq!(calc.get_angle_mode(), AngleMode::Degrees);
+        calc.set_angle_mode(AngleMode::Radians);
+
+        // sin(pi/6) = 0.5
+        calc.set_x(PI / 6.0);
+        calc.sin_deg().unwrap();
+        assert!((calc.get_x() - 0.5).abs() < EPSILON);
+
+        // acos(0) = pi/2
+        calc.set_x(0.0);
+        calc.acos_deg().unwrap();
+        assert!((calc.get_x() - PI / 2.0).abs() < EPSILON);
+
+        // atan2(1, 1) = pi/4
+        calc.set_x(1.0);
+        calc.set_y(1.0);
+        calc.atan2_deg().unwrap();
+        assert!((calc.get_x() - PI / 4.0).abs() < EPSILON);
+
+        calc.set_angle_mode(AngleMode::Degrees);
+        calc.set_x(1.0);
+        calc.atan_deg().unwrap();
+        assert!((calc.get_x() - 45.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_memory_registers() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(12.47);
+        calc.store("1");
+        calc.set_x(3.0);
+        calc.sqrt().unwrap();
+        calc.store("Sqrt3");
+        assert_eq!(calc.get_y(), 12.47);
+
+        // 12.47 / sqrt(3)
+        calc.recall("1");
+        calc.recall("SQRT3");
+        calc.divide().unwrap();
+        assert!((calc.get_x() - 12.47 / 3f64.sqrt()).abs() < EPSILON);
+
+        calc.recall("unset");
+        assert_eq!(calc.get_x(), 0.0);
+
+        calc.clear_registers();
+        calc.recall("1");
+        assert_eq!(calc.get_x(), 0.0);
+    }
+
+    #[test]
+    fn test_last_x() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(10.0);
+        calc.set_x(4.0);
+        calc.subtract().unwrap();
+        assert_eq!(calc.get_x(), 6.0);
+
+        // recover the operand of the subtraction
+        calc.last_x();
+        assert_eq!((calc.get_x(), calc.get_y()), (4.0, 6.0));
+
+        calc.set_x(9.0);
+        calc.sqrt().unwrap();
+        calc.last_x();
+        assert_eq!((calc.get_x(), calc.get_y()), (9.0, 3.0));
+
+        // stack manipulation does not touch lastx
+        calc.swap_xy().unwrap();
+        calc.roll_down();
+        calc.last_x();
+        assert_eq!(calc.get_x(), 9.0);
+    }
+
+    #[test]
+    fn test_clear_x_and_stack() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(1.0);
+        calc.set_x(2.0);
+        calc.clear_x();
+        assert_eq!((calc.get_x(), calc.get_y()), (0.0, 1.0));
+
+        for i in 1..=12 {
+            calc.set_x(i as f64);
+        }
+        calc.clear_stack();
+        assert_eq!((calc.get_x(), calc.get_y(), calc.get_z()), (0.0, 0.0, 0.0));
+        assert_eq!(calc.stack().len(), MIN_STACK_SIZE);
+
+        calc.set_checked(true);
+        calc.set_x(1.0);
+        assert!(calc.add().is_err());
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let mut calc = RPNCalculator::new();
+        let mut compare = |y: f64, x: f64, op: fn(&mut RPNCalculator) -> RpnResult| {
+            calc.set_x(y);
+            calc.set_x(x);
+            op(&mut calc).unwrap();
+            calc.get_x()
+        };
+
+        assert_eq!(compare(1.06, 1.05, RPNCalculator::greater_than), 1.0);
+        assert_eq!(compare(1.04, 1.05, RPNCalculator::greater_than), 0.0);
+        assert_eq!(compare(1.04, 1.05, RPNCalculator::less_than), 1.0);
+        assert_eq!(compare(1.05, 1.05, RPNCalculator::less_equal), 1.0);
+        assert_eq!(compare(1.05, 1.05, RPNCalculator::greater_equal), 1.0);
+        assert_eq!(compare(1.05, 1.06, RPNCalculator::greater_equal), 0.0);
+        assert_eq!(compare(2.0, 2.0, RPNCalculator::equal), 1.0);
+        assert_eq!(compare(2.0, 3.0, RPNCalculator::equal), 0.0);
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let mut calc = RPNCalculator::new();
+
+        calc.set_x(1.0);
+        calc.set_x(0.0);
+        calc.logical_and().unwrap();
+        assert_eq!(calc.get_x(), 0.0);
+
+        calc.set_x(-2.0);
+        calc.logical_or().unwrap();
+        assert_eq!(calc.get_x(), 1.0);
+
+        calc.logical_not().unwrap();
+        assert_eq!(calc.get_x(), 0.0);
+        calc.logical_not().unwrap();
+        assert_eq!(calc.get_x(), 1.0);
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let mut calc = RPNCalculator::new();
+        calc.set_seed(42);
+        let first: Vec<f64> = (0..5)
+            .map(|_| {
+                calc.random();
+                calc.get_x()
+            })
+            .collect();
+
+        calc.set_seed(42);
+        for value in &first {
+            calc.random();
+            assert_eq!(calc.get_x(), *value);
+        }
+        assert!(first.iter().all(|v| (0.0..1.0).contains(v)));
+
+        calc.set_x(7.0);
+        calc.seed_from_x().unwrap();
+        calc.random_normal();
+        let a = calc.get_x();
+        calc.set_seed(7);
+        calc.random_normal();
+        assert_eq!(calc.get_x(), a);
+    }
+
+    #[test]
+    fn test_random_distributions() {
+        let mut calc = RPNCalculator::new();
+        calc.set_seed(1);
+        let n = 20000;
+
+        let mut sum = 0.0;
+        for _ in 0..n {
+            calc.random();
+            sum += calc.get_x();
+            calc.roll_down();
+        }
+        assert!((sum / n as f64 - 0.5).abs() < 0.01);
+
+        let (mut sum, mut sum_sq) = (0.0, 0.0);
+        for _ in 0..n {
+            calc.random_normal();
+            sum += calc.get_x();
+            sum_sq += calc.get_x() * calc.get_x();
+            calc.roll_down();
+        }
+        let mean = sum / n as f64;
+        let variance = sum_sq / n as f64 - mean * mean;
+        assert!(mean.abs() < 0.03);
+        assert!((variance - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_enter_constants() {
+        let mut calc = RPNCalculator::new();
+
+        calc.enter_e();
+        assert_eq!(calc.get_x(), E);
+
+        calc.enter_sqrt2();
+        calc.square().unwrap();
+        assert!((calc.get_x() - 2.0).abs() < EPSILON);
+        assert_eq!(calc.get_y(), E);
+
+        calc.enter_sqrt3();
+        calc.square().unwrap();
+        assert!((calc.get_x() - 3.0).abs() < EPSILON);
+
+        // 12.47 kV LL -> 7.2 kV LN
+        calc.set_x(12.47);
+        calc.enter_sqrt3();
+        calc.divide().unwrap();
+        assert!((calc.get_x() - 7.199557).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stack_inspection() {
+        let mut calc = RPNCalculator::new();
+        assert_eq!(calc.depth(), 0);
+        assert_eq!(calc.stack(), &[0.0; MIN_STACK_SIZE]);
+
+        for i in 1..=4 {
+            calc.set_x(i as f64);
+        }
+        assert_eq!(calc.depth(), 4);
+        // x comes last
+        assert!(calc.stack().ends_with(&[0.0, 1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(calc.get(3), Some(1.0));
+
+        calc.add().unwrap();
+        assert_eq!(calc.depth(), 3);
+        assert!(calc.stack().ends_with(&[1.0, 2.0, 7.0]));
+
+        for i in 0..12 {
+            calc.set_x(i as f64);
+        }
+        assert_eq!(calc.depth(), 15);
+        assert_eq!(calc.stack().len(), MIN_STACK_SIZE + 15);
+        assert_eq!(calc.get(14), Some(1.0));
+        assert_eq!(calc.get(100), None);
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut calc = RPNCalculator::new();
+
+        assert_eq!(calc.eval("2 3 + sqr").unwrap(), 25.0);
+        assert!((calc.eval("2 3 + sqrt").unwrap() - 5f64.sqrt()).abs() < EPSILON);
+        assert!((calc.eval("12.47 SQRT3 /").unwrap() - 7.199557).abs() < 1e-6);
+
+        // registers and stack persist between evaluations
+        calc.eval("1.05 sto vmax").unwrap();
+        assert_eq!(calc.eval("clst rcl vmax 2 *").unwrap(), 2.1);
+
+        assert_eq!(
+            calc.eval("1 foo +"),
+            Err(RpnError::InvalidEntry {
+                token: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            calc.eval("rcl"),
+            Err(RpnError::MissingRegister { op: "rcl" })
+        );
+    }
+
+    #[test]
+    fn test_eval_with_expansion() {
+        let mut calc = RPNCalculator::new();
+        let value = calc
+            .eval_with("@kv 1000 *", |token| match token {
+                "@kv" => "12.47 sqrt3 /".to_string(),
+                _ => token.to_string(),
+            })
+            .unwrap();
+        assert!((value - 7199.557).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_user_functions() {
+        let mut calc = RPNCalculator::new();
+        calc.register_fn("kva2amps", 2, |args| args[0] / (3f64.sqrt() * args[1]));
+        calc.register_fn("c2f", 1, |args| args[0] * 9.0 / 5.0 + 32.0);
+        calc.register_fn("answer", 0, |_| 42.0);
+
+        // 500 kVA at 12.47 kV
+        assert!((calc.eval("1 500 12.47 KVA2AMPS").unwrap() - 23.149).abs() < 1e-3);
+        assert_eq!(calc.get_y(), 1.0);
+        calc.last_x();
+        assert_eq!(calc.get_x(), 12.47);
+
+        assert_eq!(calc.eval("100 c2f").unwrap(), 212.0);
+        assert_eq!(calc.eval("answer 2 /").unwrap(), 21.0);
+
+        // built-ins cannot be shadowed
+        calc.register_fn("sqr", 1, |_| 0.0);
+        assert_eq!(calc.eval("3 sqr").unwrap(), 9.0);
+
+        assert!(calc.unregister_fn("C2F"));
+        assert!(calc.eval("1 c2f").is_err());
+
+        calc.set_checked(true);
+        calc.clear_stack();
+        assert_eq!(
+            calc.eval("1 kva2amps"),
+            Err(RpnError::StackUnderflow {
+                op: "kva2amps".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_eval_infix() {
+        let mut calc = RPNCalculator::new();
+
+        let value = calc.eval_infix("(3+4)*sqrt(2)").unwrap();
+        assert!((value - 7.0 * 2f64.sqrt()).abs() < EPSILON);
+        assert_eq!(calc.eval_infix("hypot(3, 4) + -2^2").unwrap(), 1.0);
+        assert_eq!(calc.eval_infix("8760 % 24 == 0").unwrap(), 1.0);
+        assert!(matches!(
+            calc.eval_infix("(1 + 2"),
+            Err(RpnError::InvalidExpression { .. })
+        ));
+    }
+
+    #[test]
+    fn test_percent_and_per_unit() {
+        let mut calc = RPNCalculator::new();
+
+        // 6.5 %Z as a fraction
+        assert_eq!(calc.eval("6.5 pct").unwrap(), 0.065);
+        assert_eq!(calc.eval("0.05 topct").unwrap(), 5.0);
+
+        // 124 V on a 120 V base
+        let pu = calc.eval("124 120 pu").unwrap();
+        assert!((pu - 1.033333).abs() < 1e-6);
+        assert!((calc.eval("topct").unwrap() - 103.3333).abs() < 1e-4);
+
+        // back to volts: 1.05 pu of 7.2 kV
+        assert!((calc.eval("1.05 7.2 *").unwrap() - 7.56).abs() < EPSILON);
+
+        calc.set_checked(true);
+        assert_eq!(calc.eval("1 0 pu"), Err(RpnError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_trace_mode() {
+        let mut calc = RPNCalculator::new();
+        calc.eval("1 2 +").unwrap();
+        assert!(calc.trace().is_empty());
+
+        calc.set_tracing(true);
+        calc.clear_stack();
+        calc.eval("2 3 + sto a sqr").unwrap();
+        let tokens: Vec<&str> = calc.trace().iter().map(|e| e.token.as_str()).collect();
+        assert_eq!(tokens, ["2", "3", "+", "sto a", "sqr"]);
+        assert_eq!(calc.trace()[1].stack, vec![3.0, 2.0]);
+        assert_eq!(calc.trace()[4].stack, vec![25.0]);
+        assert_eq!(calc.trace()[4].to_string(), "sqr          [25.0]");
+
+        // a new evaluation replaces the record, errors are recorded too
+        assert!(calc.eval("1 bogus").is_err());
+        assert_eq!(calc.trace().len(), 2);
+        assert!(calc.trace()[1].error.is_some());
+
+        calc.set_tracing(false);
+        calc.eval("1").unwrap();
+        assert!(calc.trace().is_empty());
+    }
+
+    #[test]
+    fn test_non_finite_policy() {
+        let mut calc = RPNCalculator::new();
+        assert_eq!(calc.get_non_finite_policy(), NonFinitePolicy::Propagate);
+        assert!(calc.eval("1000 exp").unwrap().is_infinite());
+        assert!(calc.eval("-1 sqrt").unwrap().is_nan());
+
+        calc.set_non_finite_policy(NonFinitePolicy::Error);
+        assert!(matches!(
+            calc.eval("1000 exp 2 *"),
+            Err(RpnError::NonFinite { ref token, .. }) if token == "exp"
+        ));
+        assert_eq!(calc.eval("2 3 *").unwrap(), 6.0);
+
+        calc.set_non_finite_policy(NonFinitePolicy::Clamp);
+        assert_eq!(calc.eval("1000 exp").unwrap(), f64::MAX);
+        assert_eq!(calc.eval("1000 exp neg").unwrap(), f64::MIN);
+        assert_eq!(calc.eval("-1 sqrt").unwrap(), 0.0);
+        assert_eq!(calc.eval("1 0 / 2 +").unwrap(), f64::MAX);
+        assert_eq!(calc.take_clamped(), ["exp", "exp", "sqrt", "/"]);
+        assert!(calc.take_clamped().is_empty());
+    }
 }
@@ -0,0 +1,273 @@
+// AutoAdd (Pascal TAutoAdd): tries a generator or a capacitor at each
+// candidate bus in turn and ranks the buses by how much the addition cuts
+// the losses and the energy exceeding normal ratings the energy meters find
+// (EEN), per kW or kvar added. The best bus keeps the element; the search
+// repeats for every element asked for, each seeing the ones placed before.
+
+use dss_common::{DssError, DssResult, codes};
+use dss_core::{Circuit, DssClass, DssObject, EnergyMeter, SolutionState, find_class};
+use dss_parser::DSSParser;
+
+use crate::solution::Solution;
+
+// What AutoAdd places (Pascal GENADD and CAPADD)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AddType {
+    #[default]
+    Generator,
+    Capacitor,
+}
+
+impl AddType {
+    pub const NAMES: &'static [&'static str] = &["generator", "capacitor"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "generator" => Some(AddType::Generator),
+            "capacitor" => Some(AddType::Capacitor),
+            _ => None,
+        }
+    }
+
+    fn class_name(self) -> &'static str {
+        match self {
+            AddType::Generator => "Generator",
+            AddType::Capacitor => "Capacitor",
+        }
+    }
+
+    // Elements placed are named gadd1, gadd2... and cadd1, cadd2...
+    fn prefix(self) -> &'static str {
+        match self {
+            AddType::Generator => "gadd",
+            AddType::Capacitor => "cadd",
+        }
+    }
+}
+
+// Settings of a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoAdd {
+    pub add_type: AddType,
+    pub gen_kw: f64,
+    pub gen_pf: f64,
+    pub cap_kvar: f64,
+    // Weights of the loss and EEN improvements in the figure of merit
+    pub loss_weight: f64,
+    pub ue_weight: f64,
+    // Buses to try; every bus when empty
+    pub buses: Vec<String>,
+}
+
+impl Default for AutoAdd {
+    fn default() -> Self {
+        AutoAdd {
+            add_type: AddType::Generator,
+            gen_kw: 1000.0,
+            gen_pf: 1.0,
+            cap_kvar: 600.0,
+            loss_weight: 1.0,
+            ue_weight: 1.0,
+            buses: Vec::new(),
+        }
+    }
+}
+
+// How the circuit fared with the element at a bus
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddCandidate {
+    pub bus: String,
+    pub kw_losses: f64,
+    pub een: f64,
+    // Weighted cut in losses and EEN per kW (kvar) placed; higher is better
+    pub improvement: f64,
+}
+
+// One element placed and the trials that chose its bus, best first
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddResult {
+    pub element: String,
+    pub base_kw_losses: f64,
+    pub base_een: f64,
+    pub candidates: Vec<AddCandidate>,
+}
+
+impl AddResult {
+    pub fn best(&self) -> Option<&AddCandidate> {
+        self.candidates.first()
+    }
+}
+
+// Bus spec, phases and kV of an element at a bus: its phase nodes, at
+// line-to-line voltage across three of them and line-to-neutral else. None
+// for buses without phase nodes or a known voltage.
+fn placement(circuit: &Circuit, index: usize, nominal_kv: &[Option<f64>]) -> Option<String> {
+    let bus = &circuit.buses()[index];
+    let nodes: Vec<u32> = bus
+        .nodes()
+        .iter()
+        .copied()
+        .filter(|node| (1..=3).contains(node))
+        .collect();
+    let kv = Some(bus.get_kv_base())
+        .filter(|&kv| kv > 0.0)
+        .or(nominal_kv[index])?;
+    if nodes.is_empty() {
+        return None;
+    }
+    let kv = if nodes.len() == 3 {
+        kv
+    } else {
+        kv / 3f64.sqrt()
+    };
+    let spec = nodes.iter().fold(bus.name().to_string(), |spec, node| {
+        format!("{}.{}", spec, node)
+    });
+    Some(format!("phases={} bus1={} kv={}", nodes.len(), spec, kv))
+}
+
+pub(crate) fn new_element(
+    class: &dyn DssClass,
+    name: &str,
+    properties: &str,
+    circuit: &Circuit,
+) -> DssResult<Box<dyn DssObject>> {
+    let mut element = class.new_object(name);
+    let mut parser = DSSParser::new();
+    parser.set_cmd_string(properties);
+    class.edit(element.as_mut(), &mut parser, circuit)?;
+    Ok(element)
+}
+
+// Bus indices of the names given, or of every bus
+fn candidate_buses(circuit: &Circuit, names: &[String]) -> DssResult<Vec<usize>> {
+    if names.is_empty() {
+        return Ok((0..circuit.buses().len()).collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            circuit.bus_list().find(name).ok_or_else(|| {
+                DssError::new(
+                    codes::OBJECT_NOT_FOUND,
+                    &format!("AutoAdd bus \"{}\" not found", name),
+                )
+            })
+        })
+        .collect()
+}
+
+impl Solution {
+    // Places `number` elements of the settings, each at the candidate bus
+    // where it improves the circuit most; `add` puts each one placed in the
+    // circuit. The trials sample the energy meters for their EEN, so the
+    // meters are left reset.
+    pub fn auto_add(
+        &mut self,
+        circuit: &mut Circuit,
+        settings: &AutoAdd,
+        number: usize,
+        max_control_iterations: usize,
+        mut add: impl FnMut(&mut Circuit, Box<dyn DssObject>),
+    ) -> DssResult<Vec<AddResult>> {
+        let class = find_class(settings.add_type.class_name()).ok_or_else(|| {
+            DssError::new(codes::UNKNOWN_CLASS, "AutoAdd element class not found")
+        })?;
+        let size = match settings.add_type {
+            AddType::Generator => settings.gen_kw,
+            AddType::Capacitor => settings.cap_kvar,
+        };
+        let rating = match settings.add_type {
+            AddType::Generator => format!("kw={} pf={}", settings.gen_kw, settings.gen_pf),
+            AddType::Capacitor => format!("kvar={}", settings.cap_kvar),
+        };
+        let mut results = Vec::new();
+        for _ in 0..number {
+            let (base_kw_losses, base_een) = self.evaluate(circuit, max_control_iterations)?;
+            let name = (1..)
+                .map(|k| format!("{}{}", settings.add_type.prefix(), k))
+                .find(|name| circuit.find_element(class.name(), name).is_none())
+                .unwrap_or_default();
+            let nominal_kv = circuit.nominal_kv();
+            let mut candidates = Vec::new();
+            for index in candidate_buses(circuit, &settings.buses)? {
+                let Some(place) = placement(circuit, index, &nominal_kv) else {
+                    continue;
+                };
+                let bus = circuit.buses()[index].name().to_string();
+                let properties = format!("{} {}", place, rating);
+                let element = new_element(class, &name, &properties, circuit)?;
+                circuit.add_element(element);
+                let trial = self.evaluate(circuit, max_control_iterations);
+                circuit.remove_last_element();
+                // a bus the circuit does not solve with is no candidate
+                let Ok((kw_losses, een)) = trial else {
+                    continue;
+                };
+                let improvement = (settings.loss_weight * (base_kw_losses - kw_losses)
+                    + settings.ue_weight * (base_een - een))
+                    / size;
+                candidates.push((
+                    properties,
+                    AddCandidate {
+                        bus,
+                        kw_losses,
+                        een,
+                        improvement,
+                    },
+                ));
+            }
+            candidates.sort_by(|a, b| b.1.improvement.total_cmp(&a.1.improvement));
+            let Some((properties, _)) = candidates.first() else {
+                break;
+            };
+            let element = new_element(class, &name, properties, circuit)?;
+            add(circuit, element);
+            results.push(AddResult {
+                element: format!("{}.{}", class.name(), name),
+                base_kw_losses,
+                base_een,
+                candidates: candidates
+                    .into_iter()
+                    .map(|(_, candidate)| candidate)
+                    .collect(),
+            });
+        }
+        // leave the circuit solved with what was placed
+        self.evaluate(circuit, max_control_iterations)?;
+        Ok(results)
+    }
+
+    // Losses in kW and EEN in kWh over an hour at the solution of the
+    // circuit as it stands (Pascal ComputekWLosses_EEN)
+    fn evaluate(
+        &mut self,
+        circuit: &mut Circuit,
+        max_control_iterations: usize,
+    ) -> DssResult<(f64, f64)> {
+        if !self.solve_with_controls(circuit, max_control_iterations)? {
+            return Err(DssError::new(
+                codes::NOT_CONVERGED,
+                "AutoAdd solution did not converge",
+            ));
+        }
+        let kw_losses = circuit.losses().re / 1000.0;
+        circuit.reset_meters(Some("EnergyMeter"));
+        circuit.sample_class_meters(
+            Some("EnergyMeter"),
+            &SolutionState {
+                interval: 3600.0,
+                ..self.state()
+            },
+        );
+        let een = circuit
+            .class_elements("EnergyMeter")
+            .iter()
+            .filter_map(|&id| circuit.element(id))
+            .filter_map(|element| element.as_any().downcast_ref::<EnergyMeter>())
+            .filter_map(|meter| meter.get_register("Load EEN"))
+            .fold(0.0, |sum, een| sum + een);
+        circuit.reset_meters(Some("EnergyMeter"));
+        Ok((kw_losses, een))
+    }
+}
@@ -0,0 +1,85 @@
+// Harmonic injections (Pascal InjCurrents in harmonic mode): at a harmonic
+// each power conversion element injects its fundamental current scaled and
+// turned by its spectrum, while the sources drive their spectrum voltages
+// through their impedance. The elements are in the system matrix at their
+// admittance at the harmonic frequency.
+
+use dss_core::{Circuit, CktElement, Isource, Vsource, find_spectrum};
+use num_complex::Complex64;
+
+// Spectrum multiplier of an element at a harmonic; 0 without a spectrum
+fn spectrum_mult(circuit: &Circuit, element: &dyn CktElement, harmonic: f64) -> Complex64 {
+    element
+        .as_pc_element()
+        .and_then(|pc| find_spectrum(circuit, pc.pc_base().get_spectrum()))
+        .map_or(Complex64::new(0.0, 0.0), |spectrum| {
+            spectrum.get_mult(harmonic)
+        })
+}
+
+// Currents an element injects into its conductors at a harmonic
+pub(crate) fn harmonic_injection(
+    circuit: &Circuit,
+    element: &dyn CktElement,
+    harmonic: f64,
+) -> Vec<Complex64> {
+    let mult = spectrum_mult(circuit, element, harmonic);
+    let order = element.ckt_base().y_order();
+    let mut injection = vec![Complex64::new(0.0, 0.0); order];
+    if let Some(source) = element.as_any().downcast_ref::<Vsource>() {
+        let Some(yprim) = element.ckt_base().get_yprim() else {
+            return injection;
+        };
+        let mut e = source.harmonic_voltages(harmonic, mult);
+        e.resize(order, Complex64::new(0.0, 0.0));
+        return yprim.mv_mult(&e);
+    }
+    if let Some(source) = element.as_any().downcast_ref::<Isource>() {
+        let nphases = element.ckt_base().nphases();
+        for (phase, current) in source
+            .harmonic_currents(harmonic, mult)
+            .into_iter()
+            .enumerate()
+        {
+            injection[phase] = current;
+            injection[phase + nphases] = -current;
+        }
+        return injection;
+    }
+    // what the element draws at the fundamental, it draws at the harmonic
+    // as its spectrum says
+    let Some(pc) = element.as_pc_element() else {
+        return injection;
+    };
+    for (k, current) in pc
+        .pc_base()
+        .harmonic_currents(harmonic, mult)
+        .into_iter()
+        .take(order)
+        .enumerate()
+    {
+        injection[k] = -current;
+    }
+    injection
+}
+
+// The harmonics in the spectra of the enabled power conversion elements,
+// ascending (Pascal "Harmonics=ALL")
+pub fn spectrum_harmonics(circuit: &Circuit) -> Vec<f64> {
+    let mut harmonics: Vec<f64> = Vec::new();
+    for element in circuit.elements() {
+        let Some(pc) = element
+            .as_ckt_element()
+            .filter(|element| element.ckt_base().is_enabled())
+            .and_then(|element| element.as_pc_element())
+        else {
+            continue;
+        };
+        if let Some(spectrum) = find_spectrum(circuit, pc.pc_base().get_spectrum()) {
+            harmonics.extend_from_slice(spectrum.harmonics());
+        }
+    }
+    harmonics.sort_by(f64::total_cmp);
+    harmonics.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+    harmonics
+}
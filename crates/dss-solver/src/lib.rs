@@ -1,14 +1,20 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+// Solution engine: the system admittance matrix of a circuit, the sparse
+// linear algebra to solve it and the power flow built on them.
 
+mod auto_add;
+mod harmonics;
+mod linear_solver;
+mod solution;
+mod sparse;
+mod sparse_lu;
 #[cfg(test)]
-mod tests {
-    use super::*;
+mod test_util;
+mod ymatrix;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use auto_add::{AddCandidate, AddResult, AddType, AutoAdd};
+pub use harmonics::spectrum_harmonics;
+pub use linear_solver::LinearSolver;
+pub use solution::{Algorithm, ControlMode, Solution};
+pub use sparse::SparseMatrix;
+pub use sparse_lu::SparseLU;
+pub use ymatrix::{SystemY, YBuildOption};
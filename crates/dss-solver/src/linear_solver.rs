@@ -0,0 +1,27 @@
+// Backend solving the system admittance matrix for node voltages (OpenDSS
+// links the KLUSolve library for this). The solution factors the matrix when
+// it is rebuilt and solves it again for every new set of injection currents.
+
+use std::fmt::Debug;
+
+use dss_common::DssResult;
+use num_complex::Complex64;
+
+use crate::sparse::SparseMatrix;
+
+pub trait LinearSolver: Debug {
+    // Factors a matrix, working out the elimination order for its pattern
+    fn factor(&mut self, matrix: &SparseMatrix) -> DssResult<()>;
+
+    // Factors a matrix with new values, reusing the order of the last one
+    // where the pattern allows it
+    fn refactor(&mut self, matrix: &SparseMatrix) -> DssResult<()> {
+        self.factor(matrix)
+    }
+
+    // Solution x of A x = b for the matrix factored last
+    fn solve(&self, rhs: &[Complex64]) -> DssResult<Vec<Complex64>>;
+
+    // Order of the matrix factored last
+    fn order(&self) -> usize;
+}
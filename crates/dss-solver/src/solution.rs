@@ -0,0 +1,1045 @@
+// Snapshot power flow by the normal current injection method (Pascal
+// SolveSnap with DoNormalSolution). Every element is in the system matrix at
+// its nominal admittance; each iteration takes the currents the power
+// conversion elements inject apart from that admittance at the voltages of
+// the last iteration and solves the matrix again, until no node voltage
+// magnitude moves by more than the tolerance. After a solution the control
+// elements sample it and queue their actions; those the control mode lets
+// through change the circuit, which is solved again. The Newton algorithm
+// solves for a correction of the voltages instead. The currents of constant
+// power elements depend on the conjugate of the voltage as well as on the
+// voltage, so the Jacobian takes both slopes (the Wirtinger derivatives)
+// and solves for the correction and its conjugate together, on a matrix of
+// twice the order.
+//
+// A fault study solves the circuit without its faults, then takes the
+// short circuit impedances of each bus from the matrix with the machines
+// behind their transient impedances. In dynamics the machines are their
+// internal voltages behind those impedances; each time step integrates
+// their swing as it solves.
+//
+// A harmonic solution starts from a solved fundamental: every power
+// conversion element keeps the currents it drew then, the matrix is built at
+// each harmonic frequency and solved for the injections of the spectra.
+
+use dss_common::{DssError, DssResult, codes};
+use dss_core::{CMatrix, Circuit, CktElement, ControlQueue, Fault, PcElement, SolutionState};
+use num_complex::Complex64;
+
+use crate::harmonics::harmonic_injection;
+use crate::linear_solver::LinearSolver;
+use crate::sparse::SparseMatrix;
+use crate::sparse_lu::SparseLU;
+use crate::ymatrix::{SystemY, YBuildOption};
+
+// Iterations taken before convergence counts (Pascal MinIterations)
+const MIN_ITERATIONS: usize = 2;
+
+// Step of the voltages the slopes of the currents are taken over, per unit
+// of the voltage
+const SLOPE_STEP: f64 = 1e-6;
+
+// Power flow algorithm (Pascal NORMALSOLVE and NEWTONSOLVE)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Algorithm {
+    #[default]
+    Normal,
+    Newton,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "normal" => Some(Algorithm::Normal),
+            "newton" => Some(Algorithm::Newton),
+            _ => None,
+        }
+    }
+}
+
+// When queued control actions are carried out (Pascal CTRLSTATIC,
+// EVENTDRIVEN, TIMEDRIVEN and CONTROLSOFF)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ControlMode {
+    // The earliest actions at once, whatever their delay; the time stays
+    #[default]
+    Static,
+    // The earliest actions, the time of the solution moving on to theirs
+    Event,
+    // Only the actions due by the time of the solution; the rest wait
+    Time,
+    // The controls are not sampled
+    Off,
+}
+
+impl ControlMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "static" => Some(ControlMode::Static),
+            "event" => Some(ControlMode::Event),
+            "time" => Some(ControlMode::Time),
+            "off" => Some(ControlMode::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Solution {
+    system_y: SystemY,
+    solver: Box<dyn LinearSolver>,
+    // False until the solver holds the factors of the present matrix; the
+    // Newton iterations leave it with another
+    factored: bool,
+    algorithm: Algorithm,
+    frequency: f64,
+    max_iterations: usize,
+    tolerance: f64,
+    iterations: usize,
+    converged: bool,
+    max_delta_v: f64,
+    // Time of the solution, seconds within the hour
+    hour: i32,
+    sec: f64,
+    control_queue: ControlQueue,
+    control_mode: ControlMode,
+    control_iteration: usize,
+    // Harmonic of the voltages in the circuit, None at the fundamental, and
+    // the fundamental voltages the harmonic solutions started from
+    harmonic: Option<f64>,
+    fundamental_voltages: Vec<Complex64>,
+    // The power conversion elements are in their dynamics models
+    dynamic: bool,
+}
+
+impl Default for Solution {
+    fn default() -> Self {
+        Solution::new()
+    }
+}
+
+// Largest change of a node voltage magnitude, per unit of the last one
+fn max_change(before: &[Complex64], after: &[Complex64]) -> f64 {
+    before
+        .iter()
+        .zip(after)
+        .skip(1)
+        .filter(|(before, _)| before.norm() > 0.0)
+        .map(|(before, after)| (1.0 - after.norm() / before.norm()).abs())
+        .fold(0.0, f64::max)
+}
+
+impl Solution {
+    pub fn new() -> Self {
+        Solution::with_solver(Box::new(SparseLU::new()))
+    }
+
+    pub fn with_solver(solver: Box<dyn LinearSolver>) -> Self {
+        Solution {
+            system_y: SystemY::new(),
+            solver,
+            factored: false,
+            algorithm: Algorithm::Normal,
+            frequency: 60.0,
+            max_iterations: 15,
+            tolerance: 0.0001,
+            iterations: 0,
+            converged: false,
+            max_delta_v: 0.0,
+            hour: 0,
+            sec: 0.0,
+            control_queue: ControlQueue::new(),
+            control_mode: ControlMode::Static,
+            control_iteration: 0,
+            harmonic: None,
+            fundamental_voltages: Vec::new(),
+            dynamic: false,
+        }
+    }
+
+    pub fn get_algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        self.algorithm = algorithm;
+    }
+
+    pub fn get_frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations.max(1);
+    }
+
+    // Per unit change of the node voltages below which the solution has
+    // converged
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        self.tolerance = tolerance;
+    }
+
+    // Iterations the last solution took
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.converged
+    }
+
+    pub fn get_max_delta_v(&self) -> f64 {
+        self.max_delta_v
+    }
+
+    pub fn get_time(&self) -> (i32, f64) {
+        (self.hour, self.sec)
+    }
+
+    // Moves the solution, and the control queue with it, to a time
+    pub fn set_time(&mut self, hour: i32, sec: f64) {
+        self.control_queue.set_time(hour, sec);
+        (self.hour, self.sec) = self.control_queue.get_time();
+    }
+
+    // Time of the solution in hours
+    pub fn hours(&self) -> f64 {
+        self.hour as f64 + self.sec / 3600.0
+    }
+
+    pub fn control_queue(&self) -> &ControlQueue {
+        &self.control_queue
+    }
+
+    pub fn control_queue_mut(&mut self) -> &mut ControlQueue {
+        &mut self.control_queue
+    }
+
+    pub fn get_control_mode(&self) -> ControlMode {
+        self.control_mode
+    }
+
+    pub fn set_control_mode(&mut self, mode: ControlMode) {
+        self.control_mode = mode;
+    }
+
+    // Control iterations the last solution took
+    pub fn get_control_iteration(&self) -> usize {
+        self.control_iteration
+    }
+
+    pub fn get_harmonic(&self) -> Option<f64> {
+        self.harmonic
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    pub fn system_y(&self) -> &SystemY {
+        &self.system_y
+    }
+
+    // Forgets the matrix, so the next solution builds it again from every
+    // Yprim; for when the circuit changed behind the elements' backs
+    pub fn invalidate(&mut self) {
+        self.system_y.invalidate();
+        self.factored = false;
+    }
+
+    // What the meters record of the last solution
+    pub fn state(&self) -> SolutionState {
+        SolutionState {
+            hour: self.hour,
+            sec: self.sec,
+            frequency: self.frequency * self.harmonic.unwrap_or(1.0),
+            harmonic: self.harmonic,
+            control_iteration: self.control_iteration,
+            iterations: self.iterations,
+            converged: self.converged,
+            max_delta_v: self.max_delta_v,
+            ..SolutionState::default()
+        }
+    }
+
+    // Solves the circuit at its present state, leaving the node voltages in
+    // it. The voltages of the last solution are the starting point when the
+    // nodes are still the same; else the nominal admittances with only the
+    // sources injecting are solved first. True when it converged.
+    pub fn solve_snapshot(&mut self, circuit: &mut Circuit) -> DssResult<bool> {
+        // the voltages of a harmonic are no start for the fundamental
+        let restart = self.harmonic.take().is_some();
+        self.update_matrix(circuit)?;
+        let order = self.system_y.order();
+        let zero = Complex64::new(0.0, 0.0);
+        let mut voltages = circuit.get_node_voltages().to_vec();
+        if restart {
+            voltages = self.fundamental_voltages.clone();
+        }
+        if voltages.len() != order + 1 {
+            voltages = self.solve_injections(circuit, &vec![zero; order + 1])?;
+        }
+
+        self.iterations = 0;
+        self.converged = false;
+        while self.iterations < self.max_iterations {
+            self.iterations += 1;
+            update_pc_elements(circuit, &voltages);
+            let next = match self.algorithm {
+                Algorithm::Normal => self.solve_injections(circuit, &voltages)?,
+                Algorithm::Newton => self.newton_step(circuit, &voltages)?,
+            };
+            self.max_delta_v = max_change(&voltages, &next);
+            voltages = next;
+            if self.max_delta_v <= self.tolerance && self.iterations >= MIN_ITERATIONS {
+                self.converged = true;
+                break;
+            }
+        }
+        circuit.set_node_voltages(voltages);
+        Ok(self.converged)
+    }
+
+    // Solves the circuit, then lets the controls act on it and solves again
+    // until none has anything left to do in the control mode (Pascal
+    // SolveSnap with CheckControls). Fails when the controls are still
+    // acting after `max_control_iterations`.
+    pub fn solve_with_controls(
+        &mut self,
+        circuit: &mut Circuit,
+        max_control_iterations: usize,
+    ) -> DssResult<bool> {
+        self.control_iteration = 0;
+        let mut converged = self.solve_snapshot(circuit)?;
+        if self.control_mode == ControlMode::Off {
+            return Ok(converged);
+        }
+        loop {
+            self.sample_controls(circuit);
+            if !self.do_control_actions(circuit) {
+                return Ok(converged);
+            }
+            self.control_iteration += 1;
+            if self.control_iteration >= max_control_iterations {
+                return Err(DssError::new(
+                    codes::MAX_CONTROL_ITERATIONS,
+                    &format!(
+                        "Maximum control iterations exceeded ({}) at hour {} sec {}",
+                        max_control_iterations, self.hour, self.sec
+                    ),
+                ));
+            }
+            converged = self.solve_snapshot(circuit)?;
+        }
+    }
+
+    // Has every enabled control element look at the solved circuit and queue
+    // what it calls for (Pascal SampleControlDevices)
+    pub fn sample_controls(&mut self, circuit: &mut Circuit) {
+        let voltages = circuit.get_node_voltages().to_vec();
+        for id in 0..circuit.elements().len() {
+            let is_control = circuit
+                .element(id)
+                .and_then(|element| element.as_ckt_element())
+                .filter(|element| element.ckt_base().is_enabled())
+                .and_then(|element| element.as_control_element())
+                .is_some();
+            if !is_control {
+                continue;
+            }
+            let Some(mut element) = circuit.take_element(id) else {
+                continue;
+            };
+            if let Some(control) = element
+                .as_ckt_element_mut()
+                .and_then(|element| element.as_control_element_mut())
+            {
+                control.sample(id, circuit, &voltages, &mut self.control_queue);
+            }
+            circuit.replace_element(id, element);
+        }
+    }
+
+    // Carries out what the control mode lets through of the queue; false
+    // when that was nothing (Pascal DoControlActions)
+    fn do_control_actions(&mut self, circuit: &mut Circuit) -> bool {
+        match self.control_mode {
+            ControlMode::Static => self.do_nearest_actions(circuit, false),
+            ControlMode::Event => self.do_nearest_actions(circuit, true),
+            ControlMode::Time => self.do_due_actions(circuit),
+            ControlMode::Off => false,
+        }
+    }
+
+    // Carries out the earliest actions queued, those due at the same time;
+    // `advance` moves the time of the solution on to theirs, else it stays
+    fn do_nearest_actions(&mut self, circuit: &mut Circuit, advance: bool) -> bool {
+        let Some((hour, sec)) = self.control_queue.next_time() else {
+            return false;
+        };
+        self.control_queue.set_time(hour, sec);
+        self.do_due_actions(circuit);
+        let later = hour as f64 * 3600.0 + sec > self.hour as f64 * 3600.0 + self.sec;
+        if advance && later {
+            (self.hour, self.sec) = (hour, sec);
+        }
+        self.control_queue.set_time(self.hour, self.sec);
+        true
+    }
+
+    // Carries out every action due by the time of the queue, in time order;
+    // false when none was
+    fn do_due_actions(&mut self, circuit: &mut Circuit) -> bool {
+        let voltages = circuit.get_node_voltages().to_vec();
+        let mut done = false;
+        while let Some(action) = self.control_queue.pop_due() {
+            done = true;
+            let Some(mut element) = circuit.take_element(action.owner) else {
+                continue;
+            };
+            if let Some(control) = element
+                .as_ckt_element_mut()
+                .and_then(|element| element.as_control_element_mut())
+            {
+                control.do_pending_action(&action, circuit, &voltages, &mut self.control_queue);
+            }
+            circuit.replace_element(action.owner, element);
+        }
+        done
+    }
+
+    // Puts the power conversion elements in their dynamics models, starting
+    // from the solved circuit (Pascal InitializeForDynamics)
+    pub fn init_dynamics(&mut self, circuit: &mut Circuit) {
+        let voltages = circuit.get_node_voltages().to_vec();
+        for_each_pc_element(circuit, |element| element.init_dynamics(&voltages));
+        self.dynamic = true;
+    }
+
+    // Back to the power flow models
+    pub fn end_dynamics(&mut self, circuit: &mut Circuit) {
+        if self.dynamic {
+            for_each_pc_element(circuit, |element| element.end_dynamics());
+            self.dynamic = false;
+        }
+    }
+
+    // Solves one time step of `step` seconds in dynamics, ending at the time
+    // of the solution (Pascal SolveDynamic): faults due by then go on, the
+    // machines move their state at every iteration, and temporary faults
+    // whose current died out clear afterwards. True when it converged.
+    pub fn solve_dynamic_step(&mut self, circuit: &mut Circuit, step: f64) -> DssResult<bool> {
+        let time = self.hours() * 3600.0;
+        for id in circuit.class_elements("Fault").to_vec() {
+            if let Some(fault) = circuit
+                .element_mut(id)
+                .and_then(|fault| fault.as_any_mut().downcast_mut::<Fault>())
+                .filter(|fault| fault.get_on_time() > time - step)
+            {
+                // only as its time comes: a cleared fault stays cleared
+                fault.check_status(time);
+            }
+        }
+        self.update_matrix(circuit)?;
+        let order = self.system_y.order();
+        let mut voltages = circuit.get_node_voltages().to_vec();
+        if voltages.len() != order + 1 {
+            voltages =
+                self.solve_injections(circuit, &vec![Complex64::new(0.0, 0.0); order + 1])?;
+        }
+
+        self.iterations = 0;
+        self.converged = false;
+        while self.iterations < self.max_iterations {
+            let iteration = self.iterations;
+            self.iterations += 1;
+            update_pc_elements(circuit, &voltages);
+            for_each_pc_element(circuit, |element| {
+                element.integrate_states(&voltages, step, iteration)
+            });
+            let next = self.solve_injections(circuit, &voltages)?;
+            self.max_delta_v = max_change(&voltages, &next);
+            voltages = next;
+            if self.max_delta_v <= self.tolerance && self.iterations >= MIN_ITERATIONS {
+                self.converged = true;
+                break;
+            }
+        }
+        for id in circuit.class_elements("Fault").to_vec() {
+            if let Some(fault) = circuit
+                .element_mut(id)
+                .and_then(|fault| fault.as_any_mut().downcast_mut::<Fault>())
+            {
+                let currents = fault.get_currents(&voltages);
+                fault.check_clearing(&currents);
+            }
+        }
+        circuit.set_node_voltages(voltages);
+        Ok(self.converged)
+    }
+
+    // Solves the circuit with its faults out, then gives every bus its
+    // short circuit matrices and the voltages found (Pascal SolveFaultStudy).
+    // The faults are put back afterwards. Fails when the solution does not
+    // converge.
+    pub fn solve_fault_study(&mut self, circuit: &mut Circuit) -> DssResult<()> {
+        let faults: Vec<usize> = circuit
+            .class_elements("Fault")
+            .to_vec()
+            .into_iter()
+            .filter(|&id| set_enabled(circuit, id, false))
+            .collect();
+        let result = self.compute_short_circuit(circuit);
+        for id in faults {
+            set_enabled(circuit, id, true);
+        }
+        result
+    }
+
+    fn compute_short_circuit(&mut self, circuit: &mut Circuit) -> DssResult<()> {
+        if !self.solve_snapshot(circuit)? {
+            return Err(DssError::new(
+                codes::NOT_CONVERGED,
+                &format!(
+                    "The circuit did not converge before the fault study; largest voltage change {:.6} pu",
+                    self.max_delta_v
+                ),
+            ));
+        }
+        let was_dynamic = self.dynamic;
+        self.init_dynamics(circuit);
+        let result = self.compute_zsc(circuit);
+        if !was_dynamic {
+            self.end_dynamics(circuit);
+        }
+        result
+    }
+
+    // Column j of Zsc is the voltages at the bus nodes of 1 A into node j
+    fn compute_zsc(&mut self, circuit: &mut Circuit) -> DssResult<()> {
+        self.update_matrix(circuit)?;
+        let order = self.system_y.order();
+        let voltages = circuit.get_node_voltages().to_vec();
+        for bus in circuit.buses_mut() {
+            let refs = bus.refs().to_vec();
+            let mut zsc = CMatrix::new(refs.len());
+            for (j, &node) in refs.iter().enumerate() {
+                let mut injection = vec![Complex64::new(0.0, 0.0); order];
+                injection[node - 1] = Complex64::new(1.0, 0.0);
+                let solved = self.solver.solve(&injection)?;
+                for (i, &other) in refs.iter().enumerate() {
+                    zsc.set(i, j, solved[other - 1]);
+                }
+            }
+            let vbus = refs.iter().map(|&node| voltages[node]).collect();
+            bus.set_short_circuit(zsc, vbus);
+        }
+        Ok(())
+    }
+
+    // Has every power conversion element keep the currents of the solved
+    // fundamental for its harmonic injections (Pascal InitializeForHarmonics)
+    pub fn init_harmonics(&mut self, circuit: &mut Circuit) {
+        let voltages = circuit.get_node_voltages().to_vec();
+        for_each_pc_element(circuit, |element| element.init_harmonics(&voltages));
+        self.fundamental_voltages = voltages;
+    }
+
+    // Solves the circuit at a harmonic of the fundamental, leaving the
+    // harmonic voltages in it (Pascal SolveHarmonic). The elements are in the
+    // matrix at their admittance at the harmonic frequency; a harmonic of 1
+    // puts back the fundamental voltages.
+    pub fn solve_harmonic(&mut self, circuit: &mut Circuit, harmonic: f64) -> DssResult<()> {
+        self.harmonic = Some(harmonic);
+        if (harmonic - 1.0).abs() < 1e-6 {
+            circuit.set_node_voltages(self.fundamental_voltages.clone());
+            return Ok(());
+        }
+        self.update_matrix(circuit)?;
+        let mut currents = vec![Complex64::new(0.0, 0.0); self.system_y.order()];
+        for element in pc_elements(circuit) {
+            let base = element.ckt_base();
+            let nconds = base.nconds().max(1);
+            let injection = harmonic_injection(circuit, element, harmonic);
+            for (k, (&node, current)) in base.node_refs().iter().zip(injection).enumerate() {
+                if node > 0 && node <= currents.len() && base.is_closed(k / nconds, k % nconds) {
+                    currents[node - 1] += current;
+                }
+            }
+        }
+        let mut voltages = self.solver.solve(&currents)?;
+        voltages.insert(0, Complex64::new(0.0, 0.0));
+        circuit.set_node_voltages(voltages);
+        Ok(())
+    }
+
+    // Builds the matrix and factors it again when it changed
+    fn update_matrix(&mut self, circuit: &mut Circuit) -> DssResult<()> {
+        circuit.make_bus_list()?;
+        let frequency = self.frequency * self.harmonic.unwrap_or(1.0);
+        let changed = self
+            .system_y
+            .build(circuit, frequency, YBuildOption::WholeMatrix)?;
+        if changed || !self.factored {
+            self.factored = false;
+            self.solver.refactor(self.system_y.matrix())?;
+            self.factored = true;
+        }
+        Ok(())
+    }
+
+    // Node voltages, ground first, for the injection currents the power
+    // conversion elements give at `voltages`
+    fn solve_injections(
+        &self,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+    ) -> DssResult<Vec<Complex64>> {
+        let currents = self.injection_currents(circuit, voltages);
+        let mut solved = self.solver.solve(&currents)?;
+        solved.insert(0, Complex64::new(0.0, 0.0));
+        Ok(solved)
+    }
+
+    fn injection_currents(&self, circuit: &Circuit, voltages: &[Complex64]) -> Vec<Complex64> {
+        let mut currents = vec![Complex64::new(0.0, 0.0); self.system_y.order()];
+        for element in pc_elements(circuit) {
+            add_injection(element, voltages, &mut currents);
+        }
+        currents
+    }
+
+    // One Newton iteration: the currents left over at each node at
+    // `voltages`, solved on the matrix of their slopes for the correction.
+    // With F the currents left over, dV the correction and A and B the
+    // slopes of the matrix currents less the injections over V and over
+    // conj(V), the step solves
+    //
+    //   | A        B       | | dV       |   | F       |
+    //   | conj(B)  conj(A) | | conj(dV) | = | conj(F) |
+    //
+    // whose diagonal blocks are the system matrix less the injection slopes,
+    // so the pivots stay on the diagonal.
+    fn newton_step(
+        &mut self,
+        circuit: &Circuit,
+        voltages: &[Complex64],
+    ) -> DssResult<Vec<Complex64>> {
+        let injected = self.injection_currents(circuit, voltages);
+        let drawn = self.system_y.matrix().mv_mult(&voltages[1..]);
+        let mismatch: Vec<Complex64> = drawn.iter().zip(&injected).map(|(y, i)| y - i).collect();
+
+        let matrix = self.system_y.matrix();
+        let n = matrix.order();
+        let mut triplets = Vec::with_capacity(2 * matrix.nnz());
+        for col in 0..n {
+            let (rows, values) = matrix.column(col);
+            for (&row, &y) in rows.iter().zip(values) {
+                triplets.push((row, col, y));
+                triplets.push((n + row, n + col, y.conj()));
+            }
+        }
+        for element in pc_elements(circuit) {
+            add_injection_slopes(element, voltages, n, &mut triplets);
+        }
+        let jacobian = SparseMatrix::from_triplets(2 * n, &triplets);
+        self.factored = false;
+        self.solver.refactor(&jacobian)?;
+
+        let mut rhs = mismatch.clone();
+        rhs.extend(mismatch.iter().map(|f| f.conj()));
+        let correction = self.solver.solve(&rhs)?;
+        let mut next = voltages.to_vec();
+        for (v, dv) in next.iter_mut().skip(1).zip(&correction[..n]) {
+            *v -= dv;
+        }
+        Ok(next)
+    }
+}
+
+fn pc_elements(circuit: &Circuit) -> impl Iterator<Item = &dyn CktElement> {
+    circuit.elements().iter().filter_map(|element| {
+        element
+            .as_ckt_element()
+            .filter(|element| element.ckt_base().is_enabled())
+            .filter(|element| element.as_pc_element().is_some())
+    })
+}
+
+// Takes the slopes of what an element injects off the Jacobian of a system
+// of order `n`. A step of each node voltage along the real and along the
+// imaginary axis in turn gives the slopes over the voltage and over its
+// conjugate, dI/dV = (Sr + Si) / 2 and dI/dconj(V) = (Sr - Si) / 2, Sr and
+// Si being the change of the injection over the real and the imaginary
+// step.
+fn add_injection_slopes(
+    element: &dyn CktElement,
+    voltages: &[Complex64],
+    n: usize,
+    triplets: &mut Vec<(usize, usize, Complex64)>,
+) {
+    let base = element.ckt_base();
+    let nconds = base.nconds().max(1);
+    let refs = base.node_refs();
+    let at = element.get_injection_currents(voltages);
+    let mut stepped = voltages.to_vec();
+    let mut nodes: Vec<usize> = refs.iter().copied().filter(|&node| node > 0).collect();
+    nodes.sort_unstable();
+    nodes.dedup();
+    for node in nodes.into_iter().filter(|&node| node < voltages.len()) {
+        let step = SLOPE_STEP * voltages[node].norm().max(1.0);
+        let mut slopes = [Vec::new(), Vec::new()];
+        for (slope, direction) in slopes.iter_mut().zip([Complex64::ONE, Complex64::I]) {
+            stepped[node] = voltages[node] + direction * step;
+            let after = element.get_injection_currents(&stepped);
+            *slope = after
+                .iter()
+                .zip(&at)
+                .map(|(after, at)| (after - at) / (direction * step))
+                .collect();
+        }
+        stepped[node] = voltages[node];
+        let [real, imag] = slopes;
+        for (k, &row) in refs.iter().enumerate() {
+            if row > 0 && base.is_closed(k / nconds, k % nconds) {
+                let (row, col) = (row - 1, node - 1);
+                let over_v = (real[k] + imag[k]) / 2.0;
+                let over_conj = (real[k] - imag[k]) / 2.0;
+                if over_v.norm() > 0.0 {
+                    triplets.push((row, col, -over_v));
+                    triplets.push((n + row, n + col, -over_v.conj()));
+                }
+                if over_conj.norm() > 0.0 {
+                    triplets.push((row, n + col, -over_conj));
+                    triplets.push((n + row, col, -over_conj.conj()));
+                }
+            }
+        }
+    }
+}
+
+// Adds what an element injects to the currents into each node; nothing is
+// injected through an open conductor or into ground
+fn add_injection(element: &dyn CktElement, voltages: &[Complex64], currents: &mut [Complex64]) {
+    let base = element.ckt_base();
+    let nconds = base.nconds().max(1);
+    let injection = element.get_injection_currents(voltages);
+    for (k, (&node, current)) in base.node_refs().iter().zip(injection).enumerate() {
+        if node > 0 && node <= currents.len() && base.is_closed(k / nconds, k % nconds) {
+            currents[node - 1] += current;
+        }
+    }
+}
+
+fn for_each_pc_element(circuit: &mut Circuit, mut action: impl FnMut(&mut dyn PcElement)) {
+    for id in 0..circuit.elements().len() {
+        if let Some(element) = circuit
+            .element_mut(id)
+            .and_then(|element| element.as_ckt_element_mut())
+            .filter(|element| element.ckt_base().is_enabled())
+            .and_then(|element| element.as_pc_element_mut())
+        {
+            action(element);
+        }
+    }
+}
+
+// Enables or disables an enabled or disabled element; false when it was
+// that way already
+fn set_enabled(circuit: &mut Circuit, id: usize, enabled: bool) -> bool {
+    let Some(element) = circuit
+        .element_mut(id)
+        .and_then(|element| element.as_ckt_element_mut())
+        .filter(|element| element.ckt_base().is_enabled() != enabled)
+    else {
+        return false;
+    };
+    element.ckt_base_mut().set_enabled(enabled);
+    true
+}
+
+fn update_pc_elements(circuit: &mut Circuit, voltages: &[Complex64]) {
+    for_each_pc_element(circuit, |element| element.update_vars(voltages));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::add;
+
+    #[test]
+    fn test_solve_snapshot() {
+        let mut circuit = Circuit::new("test");
+        add(
+            &mut circuit,
+            "vsource",
+            "source",
+            "bus1=sourcebus phases=1 basekv=1 pu=1 r1=0.5 x1=0 r0=0.5 x0=0",
+        );
+        add(
+            &mut circuit,
+            "line",
+            "l1",
+            "bus1=sourcebus bus2=b phases=1 r1=0.5 x1=0 r0=0.5 x0=0 c1=0 c0=0",
+        );
+        let load = add(
+            &mut circuit,
+            "load",
+            "ld1",
+            "bus1=b.1 phases=1 kv=1 kw=10 pf=1",
+        );
+
+        let mut solution = Solution::new();
+        solution.set_tolerance(1e-10);
+        solution.set_max_iterations(50);
+        assert!(solution.solve_snapshot(&mut circuit).unwrap());
+        assert!(solution.get_iterations() >= MIN_ITERATIONS);
+
+        // 10 kW behind 1 ohm from 1000 V: V^2 - 1000 V + 10000 = 0
+        let expected = (1000.0 + (1000.0_f64.powi(2) - 4.0 * 10000.0).sqrt()) / 2.0;
+        let voltages = circuit.get_node_voltages().to_vec();
+        let b = circuit.bus_list().node_ref("b", 1).unwrap();
+        assert!((voltages[b] - Complex64::new(expected, 0.0)).norm() < 1e-6);
+        let load = circuit.element(load).unwrap().as_ckt_element().unwrap();
+        let power = load.total_power(&voltages);
+        assert!((power - Complex64::new(10000.0, 0.0)).norm() < 1e-4);
+
+        // solved again from its own voltages, it is done at once
+        assert!(solution.solve_snapshot(&mut circuit).unwrap());
+        assert_eq!(solution.get_iterations(), MIN_ITERATIONS);
+    }
+
+    #[test]
+    fn test_unbalanced_three_phase() {
+        let mut circuit = Circuit::new("test");
+        add(
+            &mut circuit,
+            "vsource",
+            "source",
+            "bus1=sourcebus phases=3 basekv=12.47 pu=1 r1=0.1 x1=0.5 r0=0.1 x0=0.5",
+        );
+        add(
+            &mut circuit,
+            "line",
+            "l1",
+            "bus1=sourcebus bus2=b phases=3 rmatrix=[0.3 | 0.1 0.3 | 0.1 0.1 0.3] \
+             xmatrix=[0.6 | 0.2 0.6 | 0.2 0.2 0.6] cmatrix=[0 | 0 0 | 0 0 0]",
+        );
+        // a different constant impedance on each phase
+        let loads = [(1, 1000.0, 300.0), (2, 500.0, 100.0), (3, 200.0, 0.0)];
+        for (phase, kw, kvar) in loads {
+            add(
+                &mut circuit,
+                "load",
+                &format!("ld{}", phase),
+                &format!(
+                    "bus1=b.{} phases=1 kv=7.2 kw={} kvar={} model=2",
+                    phase, kw, kvar
+                ),
+            );
+        }
+
+        let mut solution = Solution::new();
+        solution.set_tolerance(1e-10);
+        solution.set_max_iterations(50);
+        assert!(solution.solve_snapshot(&mut circuit).unwrap());
+
+        // Reference from the circuit equations: E - (Zs + Zline) Y Vb = Vb,
+        // the source impedance without mutual terms as r0 = r1 and x0 = x1
+        let vln = 12470.0 / 3.0_f64.sqrt();
+        let a = Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI / 3.0);
+        let e = [Complex64::new(vln, 0.0), a * vln, a * a * vln];
+        let mut z = CMatrix::new(3);
+        for i in 0..3 {
+            for j in 0..3 {
+                let zij = if i == j {
+                    Complex64::new(0.1 + 0.3, 0.5 + 0.6)
+                } else {
+                    Complex64::new(0.1, 0.2)
+                };
+                z.set(i, j, zij);
+            }
+        }
+        let y: Vec<Complex64> = loads
+            .iter()
+            .map(|&(_, kw, kvar)| Complex64::new(kw, -kvar) * 1000.0 / 7200.0_f64.powi(2))
+            .collect();
+        let mut m = CMatrix::new(3);
+        for i in 0..3 {
+            m.set(i, i, Complex64::new(1.0, 0.0));
+            for (j, yj) in y.iter().enumerate() {
+                m.add(i, j, z.get(i, j) * yj);
+            }
+        }
+        assert!(m.invert());
+        let expected = m.mv_mult(&e);
+
+        let voltages = circuit.get_node_voltages();
+        for (phase, expected) in expected.iter().enumerate() {
+            let node = circuit.bus_list().node_ref("b", phase as u32 + 1).unwrap();
+            assert!(
+                (voltages[node] - expected).norm() < 1e-9 * vln,
+                "phase {}: {} != {}",
+                phase + 1,
+                voltages[node],
+                expected
+            );
+        }
+        // the unequal loads and the coupling leave the phases unequal
+        let drop: Vec<f64> = (1..=3)
+            .map(|phase| vln - voltages[circuit.bus_list().node_ref("b", phase).unwrap()].norm())
+            .collect();
+        assert!(drop[0] > drop[1] && drop[1] > drop[2]);
+    }
+
+    #[test]
+    fn test_newton() {
+        let heavy = |algorithm: Algorithm| {
+            let mut circuit = Circuit::new("test");
+            add(
+                &mut circuit,
+                "vsource",
+                "source",
+                "bus1=sourcebus phases=1 basekv=1 pu=1 r1=0.5 x1=0 r0=0.5 x0=0",
+            );
+            add(
+                &mut circuit,
+                "line",
+                "l1",
+                "bus1=sourcebus bus2=b phases=1 r1=0.5 x1=0 r0=0.5 x0=0 c1=0 c0=0",
+            );
+            add(
+                &mut circuit,
+                "load",
+                "ld1",
+                "bus1=b.1 phases=1 kv=1 kw=200 pf=1 vminpu=0.6",
+            );
+            let mut solution = Solution::new();
+            solution.set_algorithm(algorithm);
+            solution.set_tolerance(1e-9);
+            let converged = solution.solve_snapshot(&mut circuit).unwrap();
+            let b = circuit.bus_list().node_ref("b", 1).unwrap();
+            (converged, circuit.get_node_voltages()[b])
+        };
+
+        // 200 kW of the 250 kW the source can give through 1 ohm
+        let expected = (1000.0 + (1000.0_f64.powi(2) - 4.0 * 200000.0).sqrt()) / 2.0;
+        let (converged, v) = heavy(Algorithm::Newton);
+        assert!(converged);
+        assert!((v - Complex64::new(expected, 0.0)).norm() < 1e-5);
+        assert!(!heavy(Algorithm::Normal).0);
+        assert_eq!(Algorithm::from_name("NEWTON"), Some(Algorithm::Newton));
+    }
+
+    #[test]
+    fn test_newton_iterations() {
+        // 1 kV through 1 + j0.5 ohm to a 150 kW load at 0.9 power factor,
+        // close to the most the feeder can carry
+        let iterations = |algorithm: Algorithm| {
+            let mut circuit = Circuit::new("test");
+            add(
+                &mut circuit,
+                "vsource",
+                "source",
+                "bus1=sourcebus phases=1 basekv=1 pu=1 r1=0.5 x1=0 r0=0.5 x0=0",
+            );
+            add(
+                &mut circuit,
+                "line",
+                "l1",
+                "bus1=sourcebus bus2=b phases=1 r1=0.5 x1=0.5 r0=0.5 x0=0.5 c1=0 c0=0",
+            );
+            add(
+                &mut circuit,
+                "load",
+                "ld1",
+                "bus1=b.1 phases=1 kv=1 kw=150 pf=0.9 vminpu=0.5",
+            );
+            let mut solution = Solution::new();
+            solution.set_algorithm(algorithm);
+            solution.set_max_iterations(100);
+            solution.set_tolerance(1e-9);
+            assert!(solution.solve_snapshot(&mut circuit).unwrap());
+            let b = circuit.bus_list().node_ref("b", 1).unwrap();
+            (solution.get_iterations(), circuit.get_node_voltages()[b])
+        };
+
+        let (newton, v_newton) = iterations(Algorithm::Newton);
+        let (normal, v_normal) = iterations(Algorithm::Normal);
+        assert!((v_newton - v_normal).norm() < 1e-5);
+        // a true Newton step converges in a handful of iterations
+        assert!(newton <= 5 && newton < normal);
+    }
+
+    #[test]
+    fn test_solve_harmonic() {
+        let mut circuit = Circuit::new("test");
+        add(
+            &mut circuit,
+            "vsource",
+            "source",
+            "bus1=sourcebus phases=1 basekv=1 pu=1 r1=1 x1=0 r0=1 x0=0",
+        );
+        add(
+            &mut circuit,
+            "load",
+            "ld1",
+            "bus1=sourcebus.1 phases=1 kv=1 kw=10 pf=1",
+        );
+        let mut solution = Solution::new();
+        solution.set_tolerance(1e-10);
+        solution.set_max_iterations(50);
+        assert!(solution.solve_snapshot(&mut circuit).unwrap());
+        let fundamental = circuit.get_node_voltages().to_vec();
+        let node = circuit.bus_list().node_ref("sourcebus", 1).unwrap();
+        solution.init_harmonics(&mut circuit);
+
+        // the load puts 20 % of its current back at the 5th, into the 1 ohm
+        // of the source beside its own 0.01 S; the source has no 5th
+        let current = 10000.0 / fundamental[node].norm();
+        solution.solve_harmonic(&mut circuit, 5.0).unwrap();
+        assert_eq!(solution.get_harmonic(), Some(5.0));
+        assert_eq!(solution.state().frequency, 300.0);
+        let v5 = circuit.get_node_voltages()[node];
+        assert!((v5 - Complex64::new(0.2 * current / 1.01, 0.0)).norm() < 1e-6);
+
+        // nothing in the spectrum, nothing injected
+        solution.solve_harmonic(&mut circuit, 2.0).unwrap();
+        assert!(circuit.get_node_voltages()[node].norm() < 1e-9);
+        solution.solve_harmonic(&mut circuit, 1.0).unwrap();
+        assert_eq!(circuit.get_node_voltages(), &fundamental[..]);
+
+        // the next power flow is at the fundamental again
+        assert!(solution.solve_snapshot(&mut circuit).unwrap());
+        assert_eq!(solution.get_harmonic(), None);
+        assert!((circuit.get_node_voltages()[node] - fundamental[node]).norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_not_converged() {
+        let mut circuit = Circuit::new("test");
+        add(
+            &mut circuit,
+            "vsource",
+            "source",
+            "bus1=sourcebus phases=1 basekv=1 pu=1 r1=0.5 x1=0 r0=0.5 x0=0",
+        );
+        add(
+            &mut circuit,
+            "load",
+            "ld1",
+            "bus1=sourcebus.1 phases=1 kv=1 kw=100 pf=1",
+        );
+        let mut solution = Solution::new();
+        solution.set_tolerance(1e-12);
+        solution.set_max_iterations(3);
+        assert!(!solution.solve_snapshot(&mut circuit).unwrap());
+        assert_eq!(solution.get_iterations(), 3);
+        assert!(solution.get_max_delta_v() > 1e-12);
+        assert_eq!(circuit.get_node_voltages().len(), circuit.num_nodes() + 1);
+    }
+}
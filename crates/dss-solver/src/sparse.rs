@@ -0,0 +1,113 @@
+// Complex sparse matrix in compressed sparse column form, as the system
+// admittance matrix is kept. Entries are collected as (row, column, value)
+// triplets, duplicates summed, then compressed column by column with the
+// rows ascending.
+
+use num_complex::Complex64;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SparseMatrix {
+    order: usize,
+    // Start of each column in `rows` and `values`, and the end of the last
+    col_starts: Vec<usize>,
+    rows: Vec<usize>,
+    values: Vec<Complex64>,
+}
+
+impl SparseMatrix {
+    // A square matrix of the given order from triplets; entries at the same
+    // place are added, entries outside the matrix ignored
+    pub fn from_triplets(order: usize, triplets: &[(usize, usize, Complex64)]) -> Self {
+        let mut sorted: Vec<(usize, usize, Complex64)> = triplets
+            .iter()
+            .copied()
+            .filter(|&(row, col, _)| row < order && col < order)
+            .collect();
+        sorted.sort_by_key(|&(row, col, _)| (col, row));
+
+        let mut matrix = SparseMatrix {
+            order,
+            col_starts: vec![0; order + 1],
+            rows: Vec::with_capacity(sorted.len()),
+            values: Vec::with_capacity(sorted.len()),
+        };
+        let mut last = None;
+        for (row, col, value) in sorted {
+            if last == Some((row, col)) {
+                *matrix.values.last_mut().unwrap() += value;
+                continue;
+            }
+            matrix.rows.push(row);
+            matrix.values.push(value);
+            matrix.col_starts[col + 1] += 1;
+            last = Some((row, col));
+        }
+        for col in 0..order {
+            matrix.col_starts[col + 1] += matrix.col_starts[col];
+        }
+        matrix
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    // Number of entries stored
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    // Rows and values of the entries of a column, rows ascending
+    pub fn column(&self, col: usize) -> (&[usize], &[Complex64]) {
+        let range = self.col_starts[col]..self.col_starts[col + 1];
+        (&self.rows[range.clone()], &self.values[range])
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Complex64 {
+        let (rows, values) = self.column(col);
+        rows.binary_search(&row)
+            .map_or(Complex64::new(0.0, 0.0), |k| values[k])
+    }
+
+    // Product with a vector of the matrix order
+    pub fn mv_mult(&self, vector: &[Complex64]) -> Vec<Complex64> {
+        let mut result = vec![Complex64::new(0.0, 0.0); self.order];
+        for (col, &x) in vector.iter().enumerate().take(self.order) {
+            let (rows, values) = self.column(col);
+            for (&row, &value) in rows.iter().zip(values) {
+                result[row] += value * x;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_triplets() {
+        let c = |re: f64| Complex64::new(re, 0.0);
+        let matrix = SparseMatrix::from_triplets(
+            3,
+            &[
+                (2, 0, c(4.0)),
+                (0, 0, c(1.0)),
+                (0, 0, c(1.0)),
+                (1, 1, c(3.0)),
+                (0, 2, c(5.0)),
+                (3, 3, c(9.0)),
+            ],
+        );
+        assert_eq!(matrix.nnz(), 4);
+        assert_eq!(matrix.get(0, 0), c(2.0));
+        assert_eq!(matrix.get(2, 0), c(4.0));
+        assert_eq!(matrix.get(1, 0), c(0.0));
+        assert_eq!(matrix.column(0).0, [0, 2]);
+        assert_eq!(
+            matrix.mv_mult(&[c(1.0), c(1.0), c(1.0)]),
+            [c(7.0), c(3.0), c(4.0)]
+        );
+    }
+}
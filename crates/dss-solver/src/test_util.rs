@@ -0,0 +1,18 @@
+// Helpers shared by the unit tests of the solution engine
+
+use dss_core::{Circuit, ElementId, find_class};
+
+use crate::auto_add::new_element;
+
+// A new element of class `class_name` with `properties` added to `circuit`,
+// as "New" makes it
+pub(crate) fn add(
+    circuit: &mut Circuit,
+    class_name: &str,
+    name: &str,
+    properties: &str,
+) -> ElementId {
+    let class = find_class(class_name).unwrap();
+    let element = new_element(class, name, properties, circuit).unwrap();
+    circuit.add_element(element)
+}
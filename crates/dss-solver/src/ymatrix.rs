@@ -0,0 +1,186 @@
+// System admittance matrix (Pascal BuildYMatrix): the Yprim of every enabled
+// circuit element stamped onto the global node numbers of its conductors.
+// Ground is node 0 and has no row; node n is row n - 1. Yprim is computed
+// again only for the elements whose Yprim was invalidated, unless the
+// frequency changes, and the matrix is assembled again only when an element
+// or its connections changed.
+
+use dss_common::DssResult;
+use dss_core::{Circuit, CktElement, ElementId};
+use num_complex::Complex64;
+
+use crate::sparse::SparseMatrix;
+
+// Which elements go into the matrix (Pascal WHOLEMATRIX and SERIESONLY)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum YBuildOption {
+    #[default]
+    WholeMatrix,
+    // Power delivery elements only, leaving out loads, generators and
+    // sources
+    SeriesOnly,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemY {
+    matrix: SparseMatrix,
+    frequency: f64,
+    option: YBuildOption,
+    // Elements stamped and the node numbers of their conductors
+    stamped: Vec<(ElementId, Vec<usize>)>,
+    built: bool,
+}
+
+fn included(element: &dyn CktElement, option: YBuildOption) -> bool {
+    element.ckt_base().is_enabled()
+        && element.as_control_element().is_none()
+        && element.as_meter_element().is_none()
+        && (option == YBuildOption::WholeMatrix || element.as_pc_element().is_none())
+}
+
+impl SystemY {
+    pub fn new() -> Self {
+        SystemY::default()
+    }
+
+    // Brings the matrix up to date with the circuit at a frequency; the bus
+    // list must be made. True when the matrix was assembled again.
+    pub fn build(
+        &mut self,
+        circuit: &mut Circuit,
+        frequency: f64,
+        option: YBuildOption,
+    ) -> DssResult<bool> {
+        let recalc_all = !self.built || frequency != self.frequency;
+        let mut changed = recalc_all || option != self.option;
+        let mut stamped = Vec::new();
+        for id in 0..circuit.elements().len() {
+            let Some(element) = circuit
+                .element_mut(id)
+                .and_then(|element| element.as_ckt_element_mut())
+            else {
+                continue;
+            };
+            if !included(element, option) {
+                continue;
+            }
+            if recalc_all || element.ckt_base().get_yprim().is_none() {
+                element.calc_yprim(frequency)?;
+                changed = true;
+            }
+            stamped.push((id, element.ckt_base().node_refs().to_vec()));
+        }
+        let order = circuit.num_nodes();
+        changed |= stamped != self.stamped || order != self.matrix.order();
+        self.frequency = frequency;
+        self.option = option;
+        self.stamped = stamped;
+        self.built = true;
+        if changed {
+            self.assemble(circuit, order);
+        }
+        Ok(changed)
+    }
+
+    fn assemble(&mut self, circuit: &Circuit, order: usize) {
+        let mut triplets: Vec<(usize, usize, Complex64)> = Vec::new();
+        for (id, refs) in &self.stamped {
+            let Some(yprim) = circuit
+                .element(*id)
+                .and_then(|element| element.as_ckt_element())
+                .and_then(|element| element.ckt_base().get_yprim())
+            else {
+                continue;
+            };
+            let n = yprim.order().min(refs.len());
+            for i in (0..n).filter(|&i| refs[i] > 0) {
+                for j in (0..n).filter(|&j| refs[j] > 0) {
+                    triplets.push((refs[i] - 1, refs[j] - 1, yprim.get(i, j)));
+                }
+            }
+        }
+        self.matrix = SparseMatrix::from_triplets(order, &triplets);
+    }
+
+    // Marks the matrix for a full rebuild, every Yprim included
+    pub fn invalidate(&mut self) {
+        self.built = false;
+    }
+
+    pub fn matrix(&self) -> &SparseMatrix {
+        &self.matrix
+    }
+
+    pub fn order(&self) -> usize {
+        self.matrix.order()
+    }
+
+    pub fn get_frequency(&self) -> f64 {
+        self.frequency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dss_core::{Circuit, find_class};
+    use dss_parser::DSSParser;
+
+    use super::*;
+    use crate::test_util::add;
+
+    #[test]
+    fn test_build() {
+        let mut circuit = Circuit::new("test");
+        let line = add(
+            &mut circuit,
+            "line",
+            "l1",
+            "bus1=sourcebus bus2=b phases=1 r1=1 x1=1 r0=1 x0=1 c1=0 c0=0",
+        );
+        add(
+            &mut circuit,
+            "load",
+            "ld1",
+            "bus1=b.1 phases=1 kv=1 kw=1 pf=1",
+        );
+        circuit.make_bus_list().unwrap();
+
+        let mut y = SystemY::new();
+        assert!(
+            y.build(&mut circuit, 60.0, YBuildOption::SeriesOnly)
+                .unwrap()
+        );
+        // three source nodes, b.1 and the load neutral grounded
+        assert_eq!(y.order(), 4);
+        let b = circuit.bus_list().node_ref("b", 1).unwrap() - 1;
+        let series = Complex64::new(0.5, -0.5);
+        assert!((y.matrix().get(b, b) - series).norm() < 1e-9);
+        assert!((y.matrix().get(0, b) + series).norm() < 1e-9);
+        assert!(
+            !y.build(&mut circuit, 60.0, YBuildOption::SeriesOnly)
+                .unwrap()
+        );
+
+        // the load's 1 kW at 1 kV is 1 mS more at b
+        assert!(
+            y.build(&mut circuit, 60.0, YBuildOption::WholeMatrix)
+                .unwrap()
+        );
+        assert!((y.matrix().get(b, b) - series - Complex64::new(0.001, 0.0)).norm() < 1e-9);
+
+        // an edit invalidates the line's Yprim and the matrix follows
+        let mut parser = DSSParser::new();
+        parser.set_cmd_string("r1=2 x1=2 r0=2 x0=2");
+        let element = circuit.element_mut(line).unwrap();
+        find_class("line")
+            .unwrap()
+            .edit(element, &mut parser, &Circuit::new("other"))
+            .unwrap();
+        assert!(
+            y.build(&mut circuit, 60.0, YBuildOption::WholeMatrix)
+                .unwrap()
+        );
+        let series = Complex64::new(0.25, -0.25);
+        assert!((y.matrix().get(0, b) + series).norm() < 1e-9);
+    }
+}